@@ -0,0 +1,123 @@
+use core::fmt::Debug;
+
+use crate::ipv4::IpAddr;
+
+pub trait Resolve {
+    type Error: Debug;
+
+    /// Resolves `host` to an IP address.
+    /// Returns `Ok(None)` if the host does not exist (NXDOMAIN).
+    fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error>;
+}
+
+impl<R> Resolve for &R
+where
+    R: Resolve,
+{
+    type Error = R::Error;
+
+    fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error> {
+        (*self).resolve(host)
+    }
+}
+
+impl<R> Resolve for &mut R
+where
+    R: Resolve,
+{
+    type Error = R::Error;
+
+    fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error> {
+        (**self).resolve(host)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::fmt::Debug;
+
+    use crate::ipv4::IpAddr;
+
+    pub trait Resolve {
+        type Error: Debug;
+
+        async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error>;
+    }
+
+    impl<R> Resolve for &R
+    where
+        R: Resolve,
+    {
+        type Error = R::Error;
+
+        async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error> {
+            (*self).resolve(host).await
+        }
+    }
+
+    impl<R> Resolve for &mut R
+    where
+        R: Resolve,
+    {
+        type Error = R::Error;
+
+        async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, Self::Error> {
+            (**self).resolve(host).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ipv4::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    /// Knows a handful of hosts, and fails on the empty host name
+    struct MockResolver;
+
+    impl Resolve for MockResolver {
+        type Error = ();
+
+        fn resolve(&self, host: &str) -> Result<Option<IpAddr>, ()> {
+            match host {
+                "" => Err(()),
+                "gateway.example.com" => Ok(Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)))),
+                "ipv6.example.com" => Ok(Some(IpAddr::V6(Ipv6Addr::LOCALHOST))),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn resolve<R: Resolve>(resolver: R, host: &str) -> Result<Option<IpAddr>, R::Error> {
+        resolver.resolve(host)
+    }
+
+    #[test]
+    fn resolve_known_host() {
+        assert_eq!(
+            MockResolver.resolve("gateway.example.com"),
+            Ok(Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1))))
+        );
+        assert_eq!(
+            MockResolver.resolve("ipv6.example.com"),
+            Ok(Some(IpAddr::V6(Ipv6Addr::LOCALHOST)))
+        );
+    }
+
+    #[test]
+    fn resolve_nxdomain() {
+        assert_eq!(MockResolver.resolve("nonexistent.example.com"), Ok(None));
+        assert_eq!(MockResolver.resolve(""), Err(()));
+    }
+
+    #[test]
+    fn resolve_through_references() {
+        let mut resolver = MockResolver;
+
+        assert_eq!(resolve(&resolver, "nonexistent.example.com"), Ok(None));
+        assert!(resolve(&mut resolver, "gateway.example.com")
+            .unwrap()
+            .is_some());
+    }
+}
@@ -1,5 +1,22 @@
 use core::fmt::Debug;
 
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{ipv4, ipv6};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DriverInfo {
+    pub mac: [u8; 6],
+    pub phy_model: heapless::String<16>,
+    pub phy_addr: u8,
+    pub rx_errors: u32,
+    pub tx_errors: u32,
+    pub clock_errors: u32,
+}
+
 pub trait Eth {
     type Error: Debug;
 
@@ -8,6 +25,79 @@ pub trait Eth {
 
     fn is_started(&self) -> Result<bool, Self::Error>;
     fn is_connected(&self) -> Result<bool, Self::Error>;
+
+    /// Returns the IP configuration of the Ethernet interface, or `Ok(None)` if the driver
+    /// does not support querying it.
+    fn get_ip_configuration(&self) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Applies the IP configuration to the Ethernet interface.
+    ///
+    /// Switching between `DHCP` and `Fixed` should take effect without restarting the driver.
+    /// Drivers which cannot do that at runtime should return an error rather than silently
+    /// deferring the change until the next `start`.
+    ///
+    /// Returns `Ok(false)` if the driver does not support changing the IP configuration.
+    fn set_ip_configuration(
+        &mut self,
+        _conf: &ipv4::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Same as `get_ip_configuration`, but for the IPv6 configuration of the interface.
+    fn get_ipv6_configuration(&self) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Same as `set_ip_configuration`, but for the IPv6 configuration of the interface.
+    fn set_ipv6_configuration(
+        &mut self,
+        _conf: &ipv6::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Returns the IPv4 and the IPv6 configurations together, or `Ok(None)` if the driver supports querying neither.
+    fn get_ip_stack_configuration(&self) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+        Ok(ipv6::IpConfiguration::new(
+            self.get_ip_configuration()?,
+            self.get_ipv6_configuration()?,
+        ))
+    }
+
+    /// Applies the IPv4 and the IPv6 parts of the configuration; a protocol without a part is left as is.
+    ///
+    /// Returns `Ok(false)` if the driver does not support changing the configuration of either part.
+    fn set_ip_stack_configuration(
+        &mut self,
+        conf: &ipv6::IpConfiguration,
+    ) -> Result<bool, Self::Error> {
+        let ipv4 = match conf.ipv4() {
+            Some(ipv4) => self.set_ip_configuration(ipv4)?,
+            None => true,
+        };
+
+        let ipv6 = match conf.ipv6() {
+            Some(ipv6) => self.set_ipv6_configuration(ipv6)?,
+            None => true,
+        };
+
+        Ok(ipv4 && ipv6)
+    }
+
+    /// Returns the current DHCP lease of the interface, or `Ok(None)` if the interface is not
+    /// bound to a lease or if the driver does not support reporting it.
+    fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Returns the MAC address, the PHY and the error counters of the driver, or `Ok(None)`
+    /// if the driver does not support reporting them.
+    fn get_driver_info(&self) -> Result<Option<DriverInfo>, Self::Error> {
+        Ok(None)
+    }
 }
 
 impl<E> Eth for &mut E
@@ -31,6 +121,47 @@ where
     fn is_connected(&self) -> Result<bool, Self::Error> {
         (**self).is_connected()
     }
+
+    fn get_ip_configuration(&self) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+        (**self).get_ip_configuration()
+    }
+
+    fn set_ip_configuration(
+        &mut self,
+        conf: &ipv4::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ip_configuration(conf)
+    }
+
+    fn get_ipv6_configuration(&self) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+        (**self).get_ipv6_configuration()
+    }
+
+    fn set_ipv6_configuration(
+        &mut self,
+        conf: &ipv6::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ipv6_configuration(conf)
+    }
+
+    fn get_ip_stack_configuration(&self) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+        (**self).get_ip_stack_configuration()
+    }
+
+    fn set_ip_stack_configuration(
+        &mut self,
+        conf: &ipv6::IpConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ip_stack_configuration(conf)
+    }
+
+    fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+        (**self).get_dhcp_lease_info()
+    }
+
+    fn get_driver_info(&self) -> Result<Option<DriverInfo>, Self::Error> {
+        (**self).get_driver_info()
+    }
 }
 
 #[cfg(feature = "nightly")]
@@ -45,6 +176,66 @@ pub mod asynch {
 
         async fn is_started(&self) -> Result<bool, Self::Error>;
         async fn is_connected(&self) -> Result<bool, Self::Error>;
+
+        async fn get_ip_configuration(
+            &self,
+        ) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn set_ip_configuration(
+            &mut self,
+            _conf: &ipv4::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        async fn get_ipv6_configuration(
+            &self,
+        ) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn set_ipv6_configuration(
+            &mut self,
+            _conf: &ipv6::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        async fn get_ip_stack_configuration(
+            &self,
+        ) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+            Ok(ipv6::IpConfiguration::new(
+                self.get_ip_configuration().await?,
+                self.get_ipv6_configuration().await?,
+            ))
+        }
+
+        async fn set_ip_stack_configuration(
+            &mut self,
+            conf: &ipv6::IpConfiguration,
+        ) -> Result<bool, Self::Error> {
+            let ipv4 = match conf.ipv4() {
+                Some(ipv4) => self.set_ip_configuration(ipv4).await?,
+                None => true,
+            };
+
+            let ipv6 = match conf.ipv6() {
+                Some(ipv6) => self.set_ipv6_configuration(ipv6).await?,
+                None => true,
+            };
+
+            Ok(ipv4 && ipv6)
+        }
+
+        async fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn get_driver_info(&self) -> Result<Option<DriverInfo>, Self::Error> {
+            Ok(None)
+        }
     }
 
     impl<E> Eth for &mut E
@@ -68,5 +259,331 @@ pub mod asynch {
         async fn is_connected(&self) -> Result<bool, Self::Error> {
             (**self).is_connected().await
         }
+
+        async fn get_ip_configuration(
+            &self,
+        ) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+            (**self).get_ip_configuration().await
+        }
+
+        async fn set_ip_configuration(
+            &mut self,
+            conf: &ipv4::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ip_configuration(conf).await
+        }
+
+        async fn get_ipv6_configuration(
+            &self,
+        ) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+            (**self).get_ipv6_configuration().await
+        }
+
+        async fn set_ipv6_configuration(
+            &mut self,
+            conf: &ipv6::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ipv6_configuration(conf).await
+        }
+
+        async fn get_ip_stack_configuration(
+            &self,
+        ) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+            (**self).get_ip_stack_configuration().await
+        }
+
+        async fn set_ip_stack_configuration(
+            &mut self,
+            conf: &ipv6::IpConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ip_stack_configuration(conf).await
+        }
+
+        async fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+            (**self).get_dhcp_lease_info().await
+        }
+
+        async fn get_driver_info(&self) -> Result<Option<DriverInfo>, Self::Error> {
+            (**self).get_driver_info().await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::{Infallible, TryInto};
+    use core::time::Duration;
+
+    use crate::ipv4::{
+        ClientConfiguration, ClientSettings, DHCPClientSettings, DhcpLeaseInfo, DhcpLeaseState,
+        Ipv4Addr, Subnet,
+    };
+
+    use super::*;
+
+    /// A driver which applies the IP configuration at runtime, as a real one would on the netif
+    #[derive(Default)]
+    struct MockEth {
+        started: bool,
+        conf: ClientConfiguration,
+        ipv6_conf: ipv6::ClientConfiguration,
+        rx_errors: u32,
+        lease: Option<DhcpLeaseInfo>,
+    }
+
+    impl MockEth {
+        /// Simulates the DHCP ACK of the server, `at` the given time since boot
+        fn bind(&mut self, at: Duration) {
+            self.lease = Some(DhcpLeaseInfo {
+                server: Ipv4Addr::new(192, 168, 1, 1),
+                lease_duration: Duration::from_secs(3600),
+                obtained_at: Some(at),
+                renewal_in: Some(Duration::from_secs(1800)),
+                state: DhcpLeaseState::Bound,
+            });
+        }
+    }
+
+    impl Eth for MockEth {
+        type Error = Infallible;
+
+        fn start(&mut self) -> Result<(), Self::Error> {
+            self.started = true;
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), Self::Error> {
+            self.started = false;
+            self.lease = None;
+            Ok(())
+        }
+
+        fn is_started(&self) -> Result<bool, Self::Error> {
+            Ok(self.started)
+        }
+
+        fn is_connected(&self) -> Result<bool, Self::Error> {
+            Ok(self.started)
+        }
+
+        fn get_ip_configuration(&self) -> Result<Option<ClientConfiguration>, Self::Error> {
+            Ok(Some(self.conf.clone()))
+        }
+
+        fn set_ip_configuration(
+            &mut self,
+            conf: &ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            self.conf = conf.clone();
+            Ok(true)
+        }
+
+        fn get_ipv6_configuration(&self) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+            Ok(Some(self.ipv6_conf.clone()))
+        }
+
+        fn set_ipv6_configuration(
+            &mut self,
+            conf: &ipv6::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            self.ipv6_conf = conf.clone();
+            Ok(true)
+        }
+
+        fn get_dhcp_lease_info(&self) -> Result<Option<DhcpLeaseInfo>, Self::Error> {
+            Ok(self.lease)
+        }
+
+        fn get_driver_info(&self) -> Result<Option<DriverInfo>, Self::Error> {
+            Ok(Some(DriverInfo {
+                mac: [0x02, 0x00, 0x5e, 0x10, 0x00, 0x01],
+                phy_model: "LAN8720".try_into().unwrap(),
+                phy_addr: 1,
+                rx_errors: self.rx_errors,
+                tx_errors: 0,
+                clock_errors: 0,
+            }))
+        }
+    }
+
+    /// A driver implementing only the required methods
+    struct BareEth;
+
+    impl Eth for BareEth {
+        type Error = Infallible;
+
+        fn start(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn is_started(&self) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        fn is_connected(&self) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+    }
+
+    fn fixed() -> ClientConfiguration {
+        ClientConfiguration::Fixed(ClientSettings {
+            ip: Ipv4Addr::new(192, 168, 1, 20),
+            subnet: "192.168.1.1/24".parse::<Subnet>().unwrap(),
+            dns: Some(Ipv4Addr::new(192, 168, 1, 1)),
+            secondary_dns: Some(Ipv4Addr::new(9, 9, 9, 9)),
+        })
+    }
+
+    #[test]
+    fn ip_configuration_round_trip() {
+        let mut eth = MockEth::default();
+        eth.start().unwrap();
+
+        assert_eq!(
+            eth.get_ip_configuration().unwrap(),
+            Some(ClientConfiguration::default())
+        );
+
+        // DHCP -> Fixed and back, without restarting the driver
+        assert!(eth.set_ip_configuration(&fixed()).unwrap());
+        assert_eq!(eth.get_ip_configuration().unwrap(), Some(fixed()));
+        assert!(eth.is_started().unwrap());
+
+        let dhcp = ClientConfiguration::DHCP(DHCPClientSettings {
+            hostname: Some("sensor".try_into().unwrap()),
+            dns: Some(Ipv4Addr::new(1, 1, 1, 1)),
+            secondary_dns: None,
+        });
+
+        assert!(eth.set_ip_configuration(&dhcp).unwrap());
+        assert_eq!(eth.get_ip_configuration().unwrap(), Some(dhcp));
+
+        // Through the `&mut` forwarding impl as well
+        fn reconfigure<E: Eth>(
+            mut eth: E,
+            conf: &ClientConfiguration,
+        ) -> Option<ClientConfiguration> {
+            assert!(eth.set_ip_configuration(conf).unwrap());
+            eth.get_ip_configuration().unwrap()
+        }
+
+        assert_eq!(reconfigure(&mut eth, &fixed()), Some(fixed()));
+        assert_eq!(eth.get_ip_configuration().unwrap(), Some(fixed()));
+    }
+
+    #[test]
+    fn ip_configuration_unsupported() {
+        let mut eth = BareEth;
+
+        assert_eq!(eth.get_ip_configuration().unwrap(), None);
+        assert!(!eth.set_ip_configuration(&fixed()).unwrap());
+        assert_eq!(eth.get_ipv6_configuration().unwrap(), None);
+        assert_eq!(eth.get_ip_stack_configuration().unwrap(), None);
+        assert!(!eth
+            .set_ip_stack_configuration(&ipv6::IpConfiguration::default())
+            .unwrap());
+        assert_eq!(eth.get_dhcp_lease_info().unwrap(), None);
+        assert_eq!(eth.get_driver_info().unwrap(), None);
+    }
+
+    #[test]
+    fn driver_info() {
+        let mut eth = MockEth::default();
+
+        let info = eth.get_driver_info().unwrap().unwrap();
+        assert_eq!(info.mac, [0x02, 0x00, 0x5e, 0x10, 0x00, 0x01]);
+        assert_eq!(info.phy_model, "LAN8720");
+        assert_eq!(info.phy_addr, 1);
+        assert_eq!(info.rx_errors, 0);
+
+        // The counters are read live from the driver
+        eth.rx_errors = 3;
+        assert_eq!(eth.get_driver_info().unwrap().unwrap().rx_errors, 3);
+    }
+
+    #[cfg(all(feature = "std", feature = "use_serde"))]
+    #[test]
+    fn driver_info_serde() {
+        use crate::test_json::{from_slice, to_vec};
+
+        let info = MockEth::default().get_driver_info().unwrap().unwrap();
+        let json = to_vec(&info);
+
+        assert_eq!(
+            core::str::from_utf8(&json).unwrap(),
+            r#"{"mac":[2,0,94,16,0,1],"phy_model":"LAN8720","phy_addr":1,"rx_errors":0,"tx_errors":0,"clock_errors":0}"#
+        );
+        assert_eq!(from_slice::<DriverInfo>(&json).unwrap(), info);
+    }
+
+    #[test]
+    fn ip_stack_configuration() {
+        let mut eth = MockEth::default();
+
+        assert_eq!(
+            eth.get_ip_stack_configuration().unwrap(),
+            Some(ipv6::IpConfiguration::DualStack(
+                ClientConfiguration::default(),
+                ipv6::ClientConfiguration::SLAAC
+            ))
+        );
+
+        // Only the IPv6 part changes
+        assert!(eth
+            .set_ip_stack_configuration(&ipv6::ClientConfiguration::DHCP.into())
+            .unwrap());
+        assert_eq!(
+            eth.get_ip_configuration().unwrap(),
+            Some(ClientConfiguration::default())
+        );
+        assert_eq!(
+            eth.get_ipv6_configuration().unwrap(),
+            Some(ipv6::ClientConfiguration::DHCP)
+        );
+
+        let conf = ipv6::IpConfiguration::DualStack(fixed(), ipv6::ClientConfiguration::SLAAC);
+        assert!(eth.set_ip_stack_configuration(&conf).unwrap());
+        assert_eq!(eth.get_ip_stack_configuration().unwrap(), Some(conf));
+    }
+
+    #[test]
+    fn dhcp_lease_info() {
+        let mut eth = MockEth::default();
+        eth.start().unwrap();
+
+        assert_eq!(eth.get_dhcp_lease_info().unwrap(), None);
+
+        eth.bind(Duration::from_secs(12));
+
+        let lease = eth.get_dhcp_lease_info().unwrap().unwrap();
+        assert_eq!(lease.server, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(lease.lease_duration, Duration::from_secs(3600));
+        assert_eq!(lease.obtained_at, Some(Duration::from_secs(12)));
+        assert_eq!(lease.renewal_in, Some(Duration::from_secs(1800)));
+        assert_eq!(lease.state, DhcpLeaseState::Bound);
+
+        // The lease goes away with the link
+        eth.stop().unwrap();
+        assert_eq!(eth.get_dhcp_lease_info().unwrap(), None);
+    }
+
+    #[cfg(all(feature = "std", feature = "use_serde"))]
+    #[test]
+    fn dhcp_lease_info_without_renewal() {
+        use crate::test_json::from_slice;
+
+        // As serialized before `renewal_in` was added
+        let lease: DhcpLeaseInfo = from_slice(
+            br#"{"server":"10.0.0.1","lease_duration":{"secs":600,"nanos":0},"obtained_at":null,"state":"Renewing"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(lease.lease_duration, Duration::from_secs(600));
+        assert_eq!(lease.renewal_in, None);
+        assert_eq!(lease.state, DhcpLeaseState::Renewing);
     }
 }
@@ -1,4 +1,4 @@
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
 use core::result::Result;
 use core::time::Duration;
 
@@ -28,7 +28,7 @@ pub trait Postbox<P>: ErrorType {
     fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error>;
 }
 
-impl<'a, P, PB> Postbox<P> for &'a mut PB
+impl<P, PB> Postbox<P> for &mut PB
 where
     PB: Postbox<P> + ErrorType,
 {
@@ -37,7 +37,7 @@ where
     }
 }
 
-impl<'a, P, PB> Postbox<P> for &'a PB
+impl<P, PB> Postbox<P> for &PB
 where
     PB: Postbox<P> + ErrorType,
 {
@@ -56,7 +56,7 @@ pub trait EventBus<P>: ErrorType {
         F: FnMut(&P) + Send + 'static;
 }
 
-impl<'e, P, E> EventBus<P> for &'e E
+impl<P, E> EventBus<P> for &E
 where
     E: EventBus<P>,
 {
@@ -70,7 +70,7 @@ where
     }
 }
 
-impl<'e, P, E> EventBus<P> for &'e mut E
+impl<P, E> EventBus<P> for &mut E
 where
     E: EventBus<P>,
 {
@@ -84,6 +84,46 @@ where
     }
 }
 
+/// An event bus whose callbacks are called in the context (thread or core) they were subscribed from,
+/// and therefore need not be `Send`
+pub trait PinnedEventBus<P>: ErrorType {
+    type Subscription<'a>
+    where
+        Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + 'static;
+}
+
+impl<P, E> PinnedEventBus<P> for &E
+where
+    E: PinnedEventBus<P>,
+{
+    type Subscription<'a> = E::Subscription<'a> where Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + 'static,
+    {
+        (**self).subscribe(callback)
+    }
+}
+
+impl<P, E> PinnedEventBus<P> for &mut E
+where
+    E: PinnedEventBus<P>,
+{
+    type Subscription<'a> = E::Subscription<'a> where Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + 'static,
+    {
+        (**self).subscribe(callback)
+    }
+}
+
 pub trait PostboxProvider<P>: ErrorType {
     type Postbox<'a>: Postbox<P, Error = Self::Error>
     where
@@ -92,7 +132,7 @@ pub trait PostboxProvider<P>: ErrorType {
     fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error>;
 }
 
-impl<'p, P, PP> PostboxProvider<P> for &'p mut PP
+impl<P, PP> PostboxProvider<P> for &mut PP
 where
     PP: PostboxProvider<P>,
 {
@@ -103,7 +143,7 @@ where
     }
 }
 
-impl<'p, P, PP> PostboxProvider<P> for &'p PP
+impl<P, PP> PostboxProvider<P> for &PP
 where
     PP: PostboxProvider<P>,
 {
@@ -114,9 +154,97 @@ where
     }
 }
 
+/// The error of `TrySender::try_send`, returning the data which was not sent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrySendError<T> {
+    /// There is no room for the data right now
+    Full(T),
+    /// All the receivers are gone
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full(data) | Self::Closed(data) => data,
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(_) => write!(f, "Full"),
+            Self::Closed(_) => write!(f, "Closed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> std::error::Error for TrySendError<T> where T: Debug {}
+
+/// The error of `TryReceiver::try_recv`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TryRecvError {
+    /// There is no data right now
+    Empty,
+    /// There is no data, and all the senders are gone
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Empty"),
+            Self::Closed => write!(f, "Closed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryRecvError {}
+
+/// The non-waiting side of an (async) sender, for contexts which cannot await, like ISRs
+pub trait TrySender {
+    type Data;
+
+    fn try_send(&mut self, data: Self::Data) -> Result<(), TrySendError<Self::Data>>;
+}
+
+impl<S> TrySender for &mut S
+where
+    S: TrySender,
+{
+    type Data = S::Data;
+
+    fn try_send(&mut self, data: Self::Data) -> Result<(), TrySendError<Self::Data>> {
+        (**self).try_send(data)
+    }
+}
+
+/// The non-waiting side of an (async) receiver, for contexts which cannot await, like ISRs
+pub trait TryReceiver {
+    type Data;
+
+    fn try_recv(&mut self) -> Result<Self::Data, TryRecvError>;
+}
+
+impl<R> TryReceiver for &mut R
+where
+    R: TryReceiver,
+{
+    type Data = R::Data;
+
+    fn try_recv(&mut self) -> Result<Self::Data, TryRecvError> {
+        (**self).try_recv()
+    }
+}
+
 #[cfg(feature = "nightly")]
 pub mod asynch {
-    pub use super::{ErrorType, Spin};
+    pub use super::{ErrorType, Spin, TryReceiver, TryRecvError, TrySendError, TrySender};
 
     pub trait Sender: ErrorType {
         type Data: Send;
@@ -237,6 +237,13 @@ impl HandlerError {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for HandlerError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", self.message())
+    }
+}
+
 impl<E> From<E> for HandlerError
 where
     E: Debug,
@@ -1,6 +1,171 @@
+//! The IO traits used throughout the crate, which are those of the `embedded-io` crate
+//! (and of `embedded-io-async` for the async ones), re-exported.
+//!
+//! Drivers implementing `embedded-io` can therefore be passed to the HTTP, OTA and other utilities as-is.
+
 pub use embedded_io::*;
 
 #[cfg(feature = "nightly")]
 pub mod asynch {
     pub use embedded_io_async::*;
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::ota::{Ota, OtaUpdate};
+    use crate::test_ota::MockOta;
+    use crate::utils::io::{copy, BufReader, CopyError, Lines};
+
+    use super::{Error, ErrorKind, SliceWriteError};
+
+    #[derive(Debug, PartialEq)]
+    enum UartError {
+        Overrun,
+    }
+
+    impl embedded_io::Error for UartError {
+        fn kind(&self) -> embedded_io::ErrorKind {
+            embedded_io::ErrorKind::InvalidData
+        }
+    }
+
+    /// A loopback UART, as a driver would implement it: on top of `embedded-io` only
+    #[derive(Default)]
+    struct Uart {
+        fifo: VecDeque<u8>,
+        overrun: bool,
+    }
+
+    impl embedded_io::ErrorType for Uart {
+        type Error = UartError;
+    }
+
+    impl embedded_io::Read for Uart {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.fifo.is_empty() && self.overrun {
+                return Err(UartError::Overrun);
+            }
+
+            // At most one FIFO's worth at a time
+            let len = buf.len().min(self.fifo.len()).min(16);
+
+            for (byte, data) in buf.iter_mut().zip(self.fifo.drain(..len)) {
+                *byte = data;
+            }
+
+            Ok(len)
+        }
+    }
+
+    impl embedded_io::Write for Uart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(16);
+
+            self.fifo.extend(&buf[..len]);
+
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn embedded_io_types_as_is() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        let mut uart = Uart::default();
+        crate::io::Write::write_all(&mut uart, &data).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(copy(&mut uart, &mut out, &mut [0; 64]).unwrap(), 256);
+        assert_eq!(out, data);
+
+        // Into an OTA update
+        let mut ota = MockOta::new();
+
+        crate::io::Write::write_all(&mut uart, &data).unwrap();
+
+        let mut update = ota.initiate_update().unwrap();
+        copy(&mut uart, &mut update, &mut [0; 64]).unwrap();
+        update.complete().unwrap();
+
+        assert_eq!(ota.completed, Some(data));
+    }
+
+    #[test]
+    fn embedded_io_lines() {
+        let mut uart = Uart::default();
+        crate::io::Write::write_all(&mut uart, b"AT+CSQ\r\n+CSQ: 24,99\r\n\r\nOK\r\n").unwrap();
+
+        let mut lines = Lines::<_, 16>::new(BufReader::<_, 8>::new(&mut uart));
+
+        for expected in ["AT+CSQ", "+CSQ: 24,99", "", "OK"] {
+            assert_eq!(lines.next_line().unwrap(), Some(expected));
+        }
+
+        assert_eq!(lines.next_line().unwrap(), None);
+    }
+
+    #[test]
+    fn embedded_io_kinds_preserved() {
+        let mut uart = Uart {
+            overrun: true,
+            ..Default::default()
+        };
+
+        let result = copy(&mut uart, Vec::new(), &mut [0; 64]);
+        assert!(matches!(result, Err(CopyError::Read(UartError::Overrun))));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // `embedded-io`'s own writers report a full buffer as an error rather than a short write
+        let mut buf = [0; 4];
+        let result = copy(&[1_u8; 10][..], &mut buf[..], &mut [0; 3]);
+        assert!(matches!(
+            result,
+            Err(CopyError::Write(SliceWriteError::Full))
+        ));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(buf, [1; 4]);
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use crate::utils::asyncify::block_on;
+
+        use super::Uart;
+
+        impl embedded_io_async::Read for Uart {
+            async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                embedded_io::Read::read(self, buf)
+            }
+        }
+
+        impl embedded_io_async::Write for Uart {
+            async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                embedded_io::Write::write(self, buf)
+            }
+        }
+
+        #[test]
+        fn embedded_io_async_types_as_is() {
+            let data: Vec<u8> = (0..=255).collect();
+
+            let mut uart = Uart::default();
+            block_on(crate::io::asynch::Write::write_all(&mut uart, &data)).unwrap();
+
+            let mut out = Vec::new();
+            let copied = block_on(crate::utils::io::asynch::copy(
+                &mut uart,
+                &mut out,
+                &mut [0; 64],
+            ));
+
+            assert_eq!(copied.unwrap(), 256);
+            assert_eq!(out, data);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use core::convert::TryFrom;
 use core::fmt::Display;
 use core::str::FromStr;
+use core::time::Duration;
 
 #[cfg(feature = "std")]
 pub use std::net::{
@@ -13,23 +14,38 @@ pub use no_std_net::*;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 
+/// Serialized as a string (i.e. `"24"`) when the serde format is human-readable and as a number otherwise.
+/// Human-readable formats also accept the number representation.
+///
+/// The prefix length is between 0 (i.e. the default route) and 32, whether parsed or deserialized.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "std", derive(Hash))]
-#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct Mask(pub u8);
 
 impl FromStr for Mask {
     type Err = &'static str;
 
+    /// Accepts a prefix length (`24` or `/24`) as well as a dotted netmask (`255.255.255.0`)
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('/').unwrap_or(s);
+
+        if s.contains('.') {
+            return s
+                .parse::<Ipv4Addr>()
+                .map_err(|_| "Invalid subnet mask")
+                .and_then(|addr| {
+                    Mask::try_from(addr).map_err(|_| "Subnet mask bits should be contiguous")
+                });
+        }
+
         s.parse::<u8>()
             .map_err(|_| "Invalid subnet mask")
             .map_or_else(Err, |mask| {
-                if (1..=32).contains(&mask) {
+                if mask <= 32 {
                     Ok(Mask(mask))
                 } else {
-                    Err("Mask should be a number between 1 and 32")
+                    Err("Mask should be a number between 0 and 32")
                 }
             })
     }
@@ -41,49 +57,99 @@ impl Display for Mask {
     }
 }
 
-impl TryFrom<Ipv4Addr> for Mask {
-    type Error = ();
-
-    fn try_from(ip: Ipv4Addr) -> Result<Self, Self::Error> {
-        let octets = ip.octets();
-        let addr: u32 = ((octets[0] as u32 & 0xff) << 24)
-            | ((octets[1] as u32 & 0xff) << 16)
-            | ((octets[2] as u32 & 0xff) << 8)
-            | (octets[3] as u32 & 0xff);
+impl Mask {
+    /// Converts a dotted netmask (i.e. `255.255.255.0`) into a prefix length.
+    /// Returns `None` if the bits of the netmask are not contiguous.
+    pub const fn from_netmask(netmask: Ipv4Addr) -> Option<Self> {
+        let addr = to_u32(netmask);
 
         if addr.leading_ones() + addr.trailing_zeros() == 32 {
-            Ok(Mask(addr.leading_ones() as u8))
+            Some(Mask(addr.leading_ones() as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Converts the prefix length into a dotted netmask (i.e. `255.255.255.0`).
+    pub const fn netmask(&self) -> Ipv4Addr {
+        from_u32(self.bits())
+    }
+
+    const fn bits(&self) -> u32 {
+        if self.0 == 0 {
+            0
+        } else if self.0 >= 32 {
+            u32::MAX
         } else {
-            Err(())
+            u32::MAX << (32 - self.0)
         }
     }
 }
 
-impl From<Mask> for Ipv4Addr {
-    fn from(mask: Mask) -> Self {
-        let addr: u32 = ((1 << (32 - mask.0)) - 1) ^ 0xffffffffu32;
+impl TryFrom<Ipv4Addr> for Mask {
+    type Error = ();
 
-        let (a, b, c, d) = (
-            ((addr >> 24) & 0xff) as u8,
-            ((addr >> 16) & 0xff) as u8,
-            ((addr >> 8) & 0xff) as u8,
-            (addr & 0xff) as u8,
-        );
+    fn try_from(ip: Ipv4Addr) -> Result<Self, Self::Error> {
+        Mask::from_netmask(ip).ok_or(())
+    }
+}
 
-        Ipv4Addr::new(a, b, c, d)
+impl From<Mask> for Ipv4Addr {
+    fn from(mask: Mask) -> Self {
+        mask.netmask()
     }
 }
 
+/// Serialized as a string (i.e. `"192.168.1.1/24"`) when the serde format is human-readable and as a
+/// `(gateway, mask)` struct otherwise. Human-readable formats also accept the struct representation.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "std", derive(Hash))]
-#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct Subnet {
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     pub gateway: Ipv4Addr,
     pub mask: Mask,
 }
 
+impl Subnet {
+    /// The network address of the subnet, i.e. the gateway address with all host bits cleared
+    pub const fn network(&self) -> Ipv4Addr {
+        from_u32(to_u32(self.gateway) & self.mask.bits())
+    }
+
+    pub const fn contains(&self, ip: Ipv4Addr) -> bool {
+        to_u32(ip) & self.mask.bits() == to_u32(self.gateway) & self.mask.bits()
+    }
+
+    pub const fn broadcast(&self) -> Ipv4Addr {
+        from_u32(to_u32(self.gateway) | !self.mask.bits())
+    }
+
+    /// The first address in the subnet which can be assigned to a host.
+    /// For /31 subnets (RFC 3021) this is the network address, for /32 subnets - the only address.
+    pub const fn first_host(&self) -> Ipv4Addr {
+        let network = to_u32(self.network());
+
+        if self.mask.0 >= 31 {
+            from_u32(network)
+        } else {
+            from_u32(network + 1)
+        }
+    }
+
+    /// The last address in the subnet which can be assigned to a host.
+    /// For /31 and /32 subnets this is the broadcast address.
+    pub const fn last_host(&self) -> Ipv4Addr {
+        let broadcast = to_u32(self.broadcast());
+
+        if self.mask.0 >= 31 {
+            from_u32(broadcast)
+        } else {
+            from_u32(broadcast - 1)
+        }
+    }
+}
+
 impl Display for Subnet {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}/{}", self.gateway, self.mask)
@@ -111,6 +177,24 @@ impl FromStr for Subnet {
     }
 }
 
+const fn to_u32(addr: Ipv4Addr) -> u32 {
+    let octets = addr.octets();
+
+    ((octets[0] as u32) << 24)
+        | ((octets[1] as u32) << 16)
+        | ((octets[2] as u32) << 8)
+        | (octets[3] as u32)
+}
+
+const fn from_u32(addr: u32) -> Ipv4Addr {
+    Ipv4Addr::new(
+        ((addr >> 24) & 0xff) as u8,
+        ((addr >> 16) & 0xff) as u8,
+        ((addr >> 8) & 0xff) as u8,
+        (addr & 0xff) as u8,
+    )
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -124,6 +208,71 @@ pub struct ClientSettings {
     pub secondary_dns: Option<Ipv4Addr>,
 }
 
+impl Display for ClientSettings {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ip={} subnet={}", self.ip, self.subnet)?;
+
+        if let Some(dns) = self.dns {
+            write!(f, " dns={dns}")?;
+        }
+
+        if let Some(secondary_dns) = self.secondary_dns {
+            write!(f, " secondary_dns={secondary_dns}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for ClientSettings {
+    type Err = &'static str;
+
+    /// Parses the format produced by `Display`, i.e.
+    /// `ip=<ip-address> subnet=<gateway-ip-address>/<mask> [dns=<ip-address>] [secondary_dns=<ip-address>]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ip = None;
+        let mut subnet = None;
+        let mut dns = None;
+        let mut secondary_dns = None;
+
+        for part in s.split_whitespace() {
+            let mut split = part.splitn(2, '=');
+
+            let (key, value) = match (split.next(), split.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err("Expected <key>=<value>"),
+            };
+
+            let (slot, parsed) = match key {
+                "ip" => (&mut ip, value.parse::<Ipv4Addr>().ok()),
+                "dns" => (&mut dns, value.parse::<Ipv4Addr>().ok()),
+                "secondary_dns" => (&mut secondary_dns, value.parse::<Ipv4Addr>().ok()),
+                "subnet" => {
+                    if subnet.replace(value.parse::<Subnet>()?).is_some() {
+                        return Err("Duplicate key");
+                    }
+
+                    continue;
+                }
+                _ => return Err("Unknown key, expected one of ip, subnet, dns, secondary_dns"),
+            };
+
+            let parsed = parsed.ok_or("Invalid IP address format, expected XXX.XXX.XXX.XXX")?;
+
+            if slot.replace(parsed).is_some() {
+                return Err("Duplicate key");
+            }
+        }
+
+        Ok(Self {
+            ip: ip.ok_or("Missing ip")?,
+            subnet: subnet.ok_or("Missing subnet")?,
+            dns,
+            secondary_dns,
+        })
+    }
+}
+
 impl Default for ClientSettings {
     fn default() -> ClientSettings {
         ClientSettings {
@@ -141,8 +290,16 @@ impl Default for ClientSettings {
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "use_serde", serde(default))]
 pub struct DHCPClientSettings {
     pub hostname: Option<heapless::String<30>>,
+    /// If set, implementations must use this DNS server instead of the one provided by the DHCP lease,
+    /// and must re-apply it after each lease acquisition or renewal
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub dns: Option<Ipv4Addr>,
+    /// If set, implementations must use this secondary DNS server instead of the one provided by the DHCP lease
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub secondary_dns: Option<Ipv4Addr>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -188,6 +345,69 @@ pub struct RouterConfiguration {
     pub dns: Option<Ipv4Addr>,
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     pub secondary_dns: Option<Ipv4Addr>,
+    /// The range of addresses the DHCP server leases from; the whole subnet if not set.
+    /// Drivers which cannot constrain the DHCP pool should fail when applying the configuration.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub dhcp_pool: Option<DhcpPool>,
+    /// Addresses the DHCP server always leases to the clients with the given MAC addresses.
+    /// Drivers which do not support reservations should fail when applying the configuration.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub dhcp_reservations: heapless::Vec<DhcpReservation, 8>,
+}
+
+impl RouterConfiguration {
+    pub fn validate(&self) -> Result<(), RouterConfigurationError> {
+        if let Some(pool) = &self.dhcp_pool {
+            if !self.subnet.contains(pool.start) || !self.subnet.contains(pool.end) {
+                return Err(RouterConfigurationError::PoolOutOfSubnet);
+            }
+
+            if to_u32(pool.start) > to_u32(pool.end) {
+                return Err(RouterConfigurationError::PoolEmpty);
+            }
+
+            let gateway = to_u32(self.subnet.gateway);
+
+            if to_u32(pool.start) < to_u32(self.subnet.first_host())
+                || to_u32(pool.end) > to_u32(self.subnet.last_host())
+                || (to_u32(pool.start)..=to_u32(pool.end)).contains(&gateway)
+            {
+                return Err(RouterConfigurationError::PoolNotAssignable);
+            }
+        }
+
+        for (index, reservation) in self.dhcp_reservations.iter().enumerate() {
+            if !self.subnet.contains(reservation.ip) {
+                return Err(RouterConfigurationError::ReservationOutOfSubnet(index));
+            }
+
+            if !self.is_assignable(reservation.ip) {
+                return Err(RouterConfigurationError::ReservationNotAssignable(index));
+            }
+
+            if self.dhcp_reservations[..index]
+                .iter()
+                .any(|other| other.ip == reservation.ip || other.mac == reservation.mac)
+            {
+                return Err(RouterConfigurationError::OverlappingReservation(index));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn validated(self) -> Result<Self, RouterConfigurationError> {
+        self.validate().map(|_| self)
+    }
+
+    /// Whether the DHCP server can lease `ip`, i.e. it is a host address of the subnet other than the gateway
+    fn is_assignable(&self, ip: Ipv4Addr) -> bool {
+        let ip = to_u32(ip);
+
+        ip >= to_u32(self.subnet.first_host())
+            && ip <= to_u32(self.subnet.last_host())
+            && ip != to_u32(self.subnet.gateway)
+    }
 }
 
 impl Default for RouterConfiguration {
@@ -200,10 +420,75 @@ impl Default for RouterConfiguration {
             dhcp_enabled: true,
             dns: Some(Ipv4Addr::new(8, 8, 8, 8)),
             secondary_dns: Some(Ipv4Addr::new(8, 8, 4, 4)),
+            dhcp_pool: None,
+            dhcp_reservations: heapless::Vec::new(),
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpPool {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub start: Ipv4Addr,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub end: Ipv4Addr,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpReservation {
+    pub mac: [u8; 6],
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Ipv4Addr,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RouterConfigurationError {
+    PoolOutOfSubnet,
+    PoolEmpty,
+    /// The pool includes the network address, the broadcast address or the gateway
+    PoolNotAssignable,
+    /// The reservation with the given index is outside of the subnet
+    ReservationOutOfSubnet(usize),
+    /// The reservation with the given index is the network address, the broadcast address or the gateway
+    ReservationNotAssignable(usize),
+    /// The reservation with the given index has the same MAC or IP address as an earlier one
+    OverlappingReservation(usize),
+}
+
+impl Display for RouterConfigurationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PoolOutOfSubnet => write!(f, "DHCP pool is outside of the subnet"),
+            Self::PoolEmpty => write!(f, "DHCP pool start is after its end"),
+            Self::PoolNotAssignable => write!(
+                f,
+                "DHCP pool includes the network, broadcast or gateway address"
+            ),
+            Self::ReservationOutOfSubnet(index) => {
+                write!(f, "DHCP reservation #{index} is outside of the subnet")
+            }
+            Self::ReservationNotAssignable(index) => write!(
+                f,
+                "DHCP reservation #{index} is the network, broadcast or gateway address"
+            ),
+            Self::OverlappingReservation(index) => {
+                write!(
+                    f,
+                    "DHCP reservation #{index} overlaps with another reservation"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RouterConfigurationError {}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -231,6 +516,30 @@ pub struct IpInfo {
     pub secondary_dns: Option<Ipv4Addr>,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum DhcpLeaseState {
+    Bound,
+    Renewing,
+    Rebinding,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpLeaseInfo {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub server: Ipv4Addr,
+    pub lease_duration: Duration,
+    /// Time since boot at which the lease was obtained, if known
+    pub obtained_at: Option<Duration>,
+    /// Time from `obtained_at` until the client starts renewing the lease with its server (T1), if known
+    pub renewal_in: Option<Duration>,
+    pub state: DhcpLeaseState,
+}
+
 pub trait Interface {
     type Error;
 
@@ -240,4 +549,619 @@ pub trait Interface {
     fn is_iface_up(&self) -> bool;
 
     fn get_ip_info(&self) -> Result<IpInfo, Self::Error>;
+
+    /// Returns the current DHCP lease, or `Ok(None)` if the interface is not bound to a lease
+    /// or if the implementation does not support reporting it.
+    fn get_dhcp_lease_info(&self) -> Result<Option<DhcpLeaseInfo>, Self::Error> {
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+mod serde_impl {
+    use core::convert::TryFrom;
+    use core::fmt::{self, Write};
+
+    use serde::de::{self, value::MapAccessDeserializer, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Ipv4Addr, Mask, Subnet};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Mask")]
+    struct MaskRepr(u8);
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Subnet")]
+    struct SubnetRepr {
+        gateway: Ipv4Addr,
+        mask: Mask,
+    }
+
+    fn serialize_display<S, T, const N: usize>(serializer: S, value: &T) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: fmt::Display,
+    {
+        let mut str = heapless::String::<N>::new();
+
+        write!(&mut str, "{value}").map_err(|_| serde::ser::Error::custom("Value too long"))?;
+
+        serializer.serialize_str(&str)
+    }
+
+    impl Serialize for Mask {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if serializer.is_human_readable() {
+                serialize_display::<_, _, 2>(serializer, self)
+            } else {
+                MaskRepr(self.0).serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Mask {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct MaskVisitor;
+
+            impl<'de> Visitor<'de> for MaskVisitor {
+                type Value = Mask;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a subnet mask")
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    v.parse().map_err(E::custom)
+                }
+
+                fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    if v <= 32 {
+                        Ok(Mask(v as _))
+                    } else {
+                        Err(E::custom("Mask should be a number between 0 and 32"))
+                    }
+                }
+
+                fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    u64::try_from(v)
+                        .map_err(|_| E::custom("Mask should be a number between 0 and 32"))
+                        .and_then(|v| self.visit_u64(v))
+                }
+            }
+
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(MaskVisitor)
+            } else {
+                let mask = MaskRepr::deserialize(deserializer)?;
+
+                MaskVisitor.visit_u64(mask.0 as _)
+            }
+        }
+    }
+
+    impl Serialize for Subnet {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if serializer.is_human_readable() {
+                serialize_display::<_, _, 18>(serializer, self)
+            } else {
+                SubnetRepr {
+                    gateway: self.gateway,
+                    mask: self.mask,
+                }
+                .serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Subnet {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct SubnetVisitor;
+
+            impl<'de> Visitor<'de> for SubnetVisitor {
+                type Value = Subnet;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a subnet")
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    v.parse().map_err(E::custom)
+                }
+
+                fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+                where
+                    A: de::MapAccess<'de>,
+                {
+                    SubnetRepr::deserialize(MapAccessDeserializer::new(map)).map(Into::into)
+                }
+            }
+
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(SubnetVisitor)
+            } else {
+                SubnetRepr::deserialize(deserializer).map(Into::into)
+            }
+        }
+    }
+
+    impl From<SubnetRepr> for Subnet {
+        fn from(subnet: SubnetRepr) -> Self {
+            Self {
+                gateway: subnet.gateway,
+                mask: subnet.mask,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "use_serde")]
+    use crate::test_json::{from_slice, to_vec};
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn dhcp_settings_without_dns_override() {
+        let settings: DHCPClientSettings = from_slice(br#"{"hostname":"sensor"}"#).unwrap();
+
+        assert_eq!(settings.hostname.as_deref(), Some("sensor"));
+        assert_eq!(settings.dns, None);
+        assert_eq!(settings.secondary_dns, None);
+
+        let conf: ClientConfiguration = from_slice(br#"{"DHCP":{"hostname":null}}"#).unwrap();
+
+        assert_eq!(conf, ClientConfiguration::DHCP(Default::default()));
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn client_settings_without_secondary_dns() {
+        // As persisted before `secondary_dns` was added, when `Subnet` was still serialized as a struct
+        let settings: ClientSettings = from_slice(
+            br#"{"ip":"192.168.1.10","subnet":{"gateway":"192.168.1.1","mask":24},"dns":"1.1.1.1"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.ip, Ipv4Addr::new(192, 168, 1, 10));
+        assert_eq!(settings.subnet.gateway, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(settings.subnet.mask, Mask(24));
+        assert_eq!(settings.dns, Some(Ipv4Addr::new(1, 1, 1, 1)));
+        assert_eq!(settings.secondary_dns, None);
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn dns_override_round_trip() {
+        let conf = ClientConfiguration::DHCP(DHCPClientSettings {
+            hostname: None,
+            dns: Some(Ipv4Addr::new(10, 0, 0, 53)),
+            secondary_dns: Some(Ipv4Addr::new(10, 0, 1, 53)),
+        });
+
+        let json = to_vec(&conf);
+
+        assert_eq!(
+            core::str::from_utf8(&json).unwrap(),
+            r#"{"DHCP":{"hostname":null,"dns":"10.0.0.53","secondary_dns":"10.0.1.53"}}"#
+        );
+        assert_eq!(from_slice::<ClientConfiguration>(&json).unwrap(), conf);
+    }
+
+    #[test]
+    fn client_settings_from_str() {
+        let settings: ClientSettings =
+            "ip=192.168.1.10 subnet=192.168.1.1/24 secondary_dns=8.8.4.4"
+                .parse()
+                .unwrap();
+
+        assert_eq!(settings.dns, None);
+        assert_eq!(settings.secondary_dns, Some(Ipv4Addr::new(8, 8, 4, 4)));
+        assert_eq!(
+            settings.to_string(),
+            "ip=192.168.1.10 subnet=192.168.1.1/24 secondary_dns=8.8.4.4"
+        );
+    }
+
+    fn subnet(gateway: [u8; 4], mask: u8) -> Subnet {
+        Subnet {
+            gateway: gateway.into(),
+            mask: Mask(mask),
+        }
+    }
+
+    #[test]
+    fn netmask_conversions() {
+        for bits in 0..=32 {
+            let mask = Mask(bits);
+
+            assert_eq!(Mask::from_netmask(mask.netmask()), Some(mask));
+            assert_eq!(Mask::try_from(Ipv4Addr::from(mask)), Ok(mask));
+        }
+
+        assert_eq!(Mask(0).netmask(), Ipv4Addr::new(0, 0, 0, 0));
+        assert_eq!(Mask(31).netmask(), Ipv4Addr::new(255, 255, 255, 254));
+        assert_eq!(Mask(32).netmask(), Ipv4Addr::new(255, 255, 255, 255));
+
+        assert_eq!(Mask::from_netmask(Ipv4Addr::new(255, 0, 255, 0)), None);
+        assert_eq!(Mask::from_netmask(Ipv4Addr::new(0, 0, 0, 255)), None);
+        assert_eq!(Mask::try_from(Ipv4Addr::new(255, 255, 255, 253)), Err(()));
+    }
+
+    #[test]
+    fn mask_from_str() {
+        assert_eq!("24".parse(), Ok(Mask(24)));
+        assert_eq!("/32".parse(), Ok(Mask(32)));
+        assert_eq!("255.255.254.0".parse(), Ok(Mask(23)));
+
+        assert_eq!("0".parse(), Ok(Mask(0)));
+        assert_eq!("/0".parse(), Ok(Mask(0)));
+        assert_eq!("0.0.0.0".parse(), Ok(Mask(0)));
+
+        assert!("33".parse::<Mask>().is_err());
+        assert!("255.0.255.0".parse::<Mask>().is_err());
+
+        for bits in 0..=32 {
+            assert_eq!(Mask(bits).to_string().parse(), Ok(Mask(bits)));
+        }
+    }
+
+    #[test]
+    fn subnet_from_str() {
+        for (s, gateway, mask) in [
+            ("192.168.1.1/24", [192, 168, 1, 1], 24),
+            ("10.0.0.1/8", [10, 0, 0, 1], 8),
+            ("10.0.0.1/32", [10, 0, 0, 1], 32),
+            ("172.16.0.1/255.240.0.0", [172, 16, 0, 1], 12),
+        ] {
+            assert_eq!(s.parse(), Ok(subnet(gateway, mask)), "{s}");
+        }
+
+        // The canonical form uses the prefix length
+        assert_eq!(
+            "172.16.0.1/255.240.0.0"
+                .parse::<Subnet>()
+                .unwrap()
+                .to_string(),
+            "172.16.0.1/12"
+        );
+
+        for s in ["192.168.1.1/24", "10.0.0.1/1", "255.255.255.255/32"] {
+            assert_eq!(s.parse::<Subnet>().unwrap().to_string(), s);
+        }
+
+        for s in [
+            "",
+            "/",
+            "192.168.1.1",
+            "192.168.1.1/",
+            "/24",
+            "192.168.1.1/33",
+            "192.168.1.1/-1",
+            "192.168.1.1/24/24",
+            "192.168.1/24",
+            "192.168.1.256/24",
+            "192.168.1.1 /24",
+            "192.168.1.1/24 ",
+            "192.168.1.1/255.0.255.0",
+            "fd00::1/64",
+            "gateway/24",
+        ] {
+            assert!(s.parse::<Subnet>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn subnet_ranges() {
+        let lan = subnet([192, 168, 1, 1], 24);
+
+        assert_eq!(lan.network(), Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(lan.broadcast(), Ipv4Addr::new(192, 168, 1, 255));
+        assert_eq!(lan.first_host(), Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(lan.last_host(), Ipv4Addr::new(192, 168, 1, 254));
+        assert!(lan.contains(Ipv4Addr::new(192, 168, 1, 200)));
+        assert!(!lan.contains(Ipv4Addr::new(192, 168, 2, 1)));
+    }
+
+    #[test]
+    fn subnet_edge_cases() {
+        let all = subnet([10, 1, 2, 3], 0);
+
+        assert_eq!(all.network(), Ipv4Addr::new(0, 0, 0, 0));
+        assert_eq!(all.broadcast(), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(all.first_host(), Ipv4Addr::new(0, 0, 0, 1));
+        assert_eq!(all.last_host(), Ipv4Addr::new(255, 255, 255, 254));
+        assert!(all.contains(Ipv4Addr::new(203, 0, 113, 7)));
+
+        // RFC 3021 point-to-point links have no network and broadcast addresses
+        let p2p = subnet([10, 0, 0, 1], 31);
+
+        assert_eq!(p2p.network(), Ipv4Addr::new(10, 0, 0, 0));
+        assert_eq!(p2p.broadcast(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(p2p.first_host(), Ipv4Addr::new(10, 0, 0, 0));
+        assert_eq!(p2p.last_host(), Ipv4Addr::new(10, 0, 0, 1));
+        assert!(p2p.contains(Ipv4Addr::new(10, 0, 0, 0)));
+        assert!(!p2p.contains(Ipv4Addr::new(10, 0, 0, 2)));
+
+        let host = subnet([10, 0, 0, 7], 32);
+
+        assert_eq!(host.network(), Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(host.broadcast(), Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(host.first_host(), Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(host.last_host(), Ipv4Addr::new(10, 0, 0, 7));
+        assert!(host.contains(Ipv4Addr::new(10, 0, 0, 7)));
+        assert!(!host.contains(Ipv4Addr::new(10, 0, 0, 6)));
+    }
+
+    #[test]
+    fn const_helpers() {
+        const LAN: Subnet = Subnet {
+            gateway: Ipv4Addr::new(172, 16, 0, 1),
+            mask: Mask(12),
+        };
+        const BROADCAST: Ipv4Addr = LAN.broadcast();
+        const CONTAINED: [bool; 2] = [
+            LAN.contains(Ipv4Addr::new(172, 31, 255, 1)),
+            LAN.contains(Ipv4Addr::new(172, 32, 0, 1)),
+        ];
+
+        assert_eq!(BROADCAST, Ipv4Addr::new(172, 31, 255, 255));
+        assert_eq!(CONTAINED, [true, false]);
+    }
+
+    fn router(
+        pool: Option<([u8; 4], [u8; 4])>,
+        reservations: &[([u8; 6], [u8; 4])],
+    ) -> RouterConfiguration {
+        RouterConfiguration {
+            subnet: subnet([192, 168, 4, 1], 24),
+            dhcp_pool: pool.map(|(start, end)| DhcpPool {
+                start: start.into(),
+                end: end.into(),
+            }),
+            dhcp_reservations: reservations
+                .iter()
+                .map(|(mac, ip)| DhcpReservation {
+                    mac: *mac,
+                    ip: (*ip).into(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn router_validation() {
+        let mac1 = [0x02, 0, 0, 0, 0, 1];
+        let mac2 = [0x02, 0, 0, 0, 0, 2];
+
+        assert_eq!(RouterConfiguration::default().validate(), Ok(()));
+        assert_eq!(
+            router(
+                Some(([192, 168, 4, 100], [192, 168, 4, 199])),
+                &[(mac1, [192, 168, 4, 10]), (mac2, [192, 168, 4, 11])]
+            )
+            .validate(),
+            Ok(())
+        );
+
+        // A single address pool
+        assert_eq!(
+            router(Some(([192, 168, 4, 100], [192, 168, 4, 100])), &[]).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn router_validation_failures() {
+        let mac1 = [0x02, 0, 0, 0, 0, 1];
+        let mac2 = [0x02, 0, 0, 0, 0, 2];
+
+        assert_eq!(
+            router(Some(([192, 168, 4, 100], [192, 168, 5, 10])), &[]).validate(),
+            Err(RouterConfigurationError::PoolOutOfSubnet)
+        );
+        assert_eq!(
+            router(Some(([192, 168, 4, 200], [192, 168, 4, 100])), &[]).validate(),
+            Err(RouterConfigurationError::PoolEmpty)
+        );
+        assert_eq!(
+            router(None, &[(mac1, [192, 168, 4, 10]), (mac2, [10, 0, 0, 1])]).validate(),
+            Err(RouterConfigurationError::ReservationOutOfSubnet(1))
+        );
+        assert_eq!(
+            router(
+                None,
+                &[(mac1, [192, 168, 4, 10]), (mac2, [192, 168, 4, 10])]
+            )
+            .validate(),
+            Err(RouterConfigurationError::OverlappingReservation(1))
+        );
+        assert_eq!(
+            router(
+                None,
+                &[(mac1, [192, 168, 4, 10]), (mac1, [192, 168, 4, 11])]
+            )
+            .validated(),
+            Err(RouterConfigurationError::OverlappingReservation(1))
+        );
+    }
+
+    #[test]
+    fn router_validation_reserved_addresses() {
+        let mac = [0x02, 0, 0, 0, 0, 1];
+
+        // The subnet is 192.168.4.0/24, with the gateway at 192.168.4.1
+        for (start, end) in [
+            ([192, 168, 4, 0], [192, 168, 4, 99]),
+            ([192, 168, 4, 200], [192, 168, 4, 255]),
+            ([192, 168, 4, 1], [192, 168, 4, 99]),
+            ([192, 168, 4, 0], [192, 168, 4, 255]),
+        ] {
+            assert_eq!(
+                router(Some((start, end)), &[]).validate(),
+                Err(RouterConfigurationError::PoolNotAssignable)
+            );
+        }
+
+        for ip in [[192, 168, 4, 0], [192, 168, 4, 1], [192, 168, 4, 255]] {
+            assert_eq!(
+                router(None, &[(mac, [192, 168, 4, 10]), (mac, ip)]).validate(),
+                Err(RouterConfigurationError::ReservationNotAssignable(1))
+            );
+        }
+
+        // The pool can start right after the gateway and end right before the broadcast address
+        assert_eq!(
+            router(Some(([192, 168, 4, 2], [192, 168, 4, 254])), &[]).validate(),
+            Ok(())
+        );
+
+        // A gateway in the middle of the subnet splits the assignable addresses
+        let mut conf = router(Some(([192, 168, 4, 2], [192, 168, 4, 99])), &[]);
+        conf.subnet = subnet([192, 168, 4, 100], 24);
+
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.dhcp_pool.as_mut().unwrap().end = Ipv4Addr::new(192, 168, 4, 100);
+        assert_eq!(
+            conf.validate(),
+            Err(RouterConfigurationError::PoolNotAssignable)
+        );
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn router_without_pool_and_reservations() {
+        let conf: RouterConfiguration = from_slice(
+            br#"{"subnet":"192.168.71.1/24","dhcp_enabled":true,"dns":"8.8.8.8","secondary_dns":"8.8.4.4"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(conf, RouterConfiguration::default());
+
+        let conf = router(
+            Some(([192, 168, 4, 100], [192, 168, 4, 199])),
+            &[([0x02, 0, 0, 0, 0, 1], [192, 168, 4, 10])],
+        );
+
+        assert_eq!(
+            from_slice::<RouterConfiguration>(&to_vec(&conf)).unwrap(),
+            conf
+        );
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn mask_serde() {
+        for bits in [0, 1, 24, 32] {
+            let json = to_vec(&Mask(bits));
+
+            assert_eq!(json, format!("\"{bits}\"").as_bytes());
+            assert_eq!(from_slice::<Mask>(&json).unwrap(), Mask(bits));
+            assert_eq!(
+                from_slice::<Mask>(bits.to_string().as_bytes()).unwrap(),
+                Mask(bits)
+            );
+        }
+
+        assert!(from_slice::<Mask>(b"33").is_err());
+        assert!(from_slice::<Mask>(b"-1").is_err());
+        assert!(from_slice::<Mask>(br#""33""#).is_err());
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn subnet_serde() {
+        let lan = subnet([192, 168, 1, 1], 24);
+
+        assert_eq!(to_vec(&lan), br#""192.168.1.1/24""#);
+        assert_eq!(from_slice::<Subnet>(br#""192.168.1.1/24""#).unwrap(), lan);
+
+        // The shape before `Subnet` was serialized as a string
+        assert_eq!(
+            from_slice::<Subnet>(br#"{"gateway":"192.168.1.1","mask":24}"#).unwrap(),
+            lan
+        );
+        assert_eq!(
+            from_slice::<Subnet>(br#"{"gateway":"0.0.0.0","mask":0}"#).unwrap(),
+            subnet([0, 0, 0, 0], 0)
+        );
+
+        assert!(from_slice::<Subnet>(br#"{"gateway":"192.168.1.1","mask":33}"#).is_err());
+        assert!(from_slice::<Subnet>(br#"{"gateway":"192.168.1.1"}"#).is_err());
+        assert!(from_slice::<Subnet>(br#""192.168.1.1""#).is_err());
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn compact_serde() {
+        use crate::test_json::{from_slice_compact, to_vec_compact};
+
+        // The previous representation, which the compact formats keep
+        assert_eq!(to_vec_compact(&Mask(0)), b"0");
+        assert_eq!(to_vec_compact(&Mask(24)), b"24");
+        assert_eq!(
+            to_vec_compact(&subnet([192, 168, 1, 1], 24)),
+            br#"{"gateway":[192,168,1,1],"mask":24}"#
+        );
+
+        for bits in [0, 24, 32] {
+            assert_eq!(
+                from_slice_compact::<Mask>(&to_vec_compact(&Mask(bits))).unwrap(),
+                Mask(bits)
+            );
+        }
+
+        assert!(from_slice_compact::<Mask>(b"33").is_err());
+        assert!(from_slice_compact::<Subnet>(br#"{"gateway":[192,168,1,1],"mask":33}"#).is_err());
+
+        let settings = ClientSettings {
+            ip: Ipv4Addr::new(10, 0, 0, 20),
+            subnet: subnet([10, 0, 0, 1], 8),
+            dns: Some(Ipv4Addr::new(10, 0, 0, 53)),
+            secondary_dns: None,
+        };
+
+        assert_eq!(
+            from_slice_compact::<ClientSettings>(&to_vec_compact(&settings)).unwrap(),
+            settings
+        );
+
+        let conf = router(
+            Some(([192, 168, 4, 100], [192, 168, 4, 199])),
+            &[([0x02, 0, 0, 0, 0, 1], [192, 168, 4, 10])],
+        );
+
+        assert_eq!(
+            from_slice_compact::<RouterConfiguration>(&to_vec_compact(&conf)).unwrap(),
+            conf
+        );
+    }
 }
@@ -0,0 +1,294 @@
+use core::fmt::Display;
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+pub use std::net::Ipv6Addr;
+
+#[cfg(not(feature = "std"))]
+pub use no_std_net::Ipv6Addr;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ipv4;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Prefix(pub u8);
+
+impl FromStr for Prefix {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u8>()
+            .map_err(|_| "Invalid prefix length")
+            .map_or_else(Err, |prefix| {
+                if prefix <= 128 {
+                    Ok(Prefix(prefix))
+                } else {
+                    Err("Prefix length should be a number between 0 and 128")
+                }
+            })
+    }
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Subnet {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub gateway: Ipv6Addr,
+    pub prefix: Prefix,
+}
+
+impl Display for Subnet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{}", self.gateway, self.prefix)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut split = s.split('/');
+        if let Some(gateway_str) = split.next() {
+            if let Some(prefix_str) = split.next() {
+                if split.next().is_none() {
+                    if let Ok(gateway) = gateway_str.parse::<Ipv6Addr>() {
+                        return prefix_str
+                            .parse::<Prefix>()
+                            .map(|prefix| Self { gateway, prefix });
+                    } else {
+                        return Err("Invalid IPv6 address format");
+                    }
+                }
+            }
+        }
+
+        Err("Expected <gateway-ip-address>/<prefix>")
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct ClientSettings {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Ipv6Addr,
+    pub subnet: Subnet,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub dns: Option<Ipv6Addr>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub secondary_dns: Option<Ipv6Addr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Default)]
+pub enum ClientConfiguration {
+    #[default]
+    SLAAC,
+    DHCP,
+    Fixed(ClientSettings),
+}
+
+impl ClientConfiguration {
+    pub fn as_fixed_settings_ref(&self) -> Option<&ClientSettings> {
+        match self {
+            Self::Fixed(client_settings) => Some(client_settings),
+            _ => None,
+        }
+    }
+}
+
+/// The IP configuration of an interface which may run IPv4, IPv6 or both
+///
+/// Drivers supporting only IPv4 keep using `ipv4::ClientConfiguration`, which converts into `IpConfiguration::Ipv4`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum IpConfiguration {
+    Ipv4(ipv4::ClientConfiguration),
+    Ipv6(ClientConfiguration),
+    DualStack(ipv4::ClientConfiguration, ClientConfiguration),
+}
+
+impl IpConfiguration {
+    /// Combines the configurations of either protocol, returning `None` if there is none
+    pub fn new(
+        ipv4: Option<ipv4::ClientConfiguration>,
+        ipv6: Option<ClientConfiguration>,
+    ) -> Option<Self> {
+        match (ipv4, ipv6) {
+            (Some(ipv4), Some(ipv6)) => Some(Self::DualStack(ipv4, ipv6)),
+            (Some(ipv4), None) => Some(Self::Ipv4(ipv4)),
+            (None, Some(ipv6)) => Some(Self::Ipv6(ipv6)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn ipv4(&self) -> Option<&ipv4::ClientConfiguration> {
+        match self {
+            Self::Ipv4(ipv4) | Self::DualStack(ipv4, _) => Some(ipv4),
+            Self::Ipv6(_) => None,
+        }
+    }
+
+    pub fn ipv6(&self) -> Option<&ClientConfiguration> {
+        match self {
+            Self::Ipv6(ipv6) | Self::DualStack(_, ipv6) => Some(ipv6),
+            Self::Ipv4(_) => None,
+        }
+    }
+}
+
+impl Default for IpConfiguration {
+    fn default() -> Self {
+        Self::Ipv4(Default::default())
+    }
+}
+
+impl From<ipv4::ClientConfiguration> for IpConfiguration {
+    fn from(conf: ipv4::ClientConfiguration) -> Self {
+        Self::Ipv4(conf)
+    }
+}
+
+impl From<ClientConfiguration> for IpConfiguration {
+    fn from(conf: ClientConfiguration) -> Self {
+        Self::Ipv6(conf)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct IpInfo {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Ipv6Addr,
+    pub subnet: Subnet,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub dns: Option<Ipv6Addr>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub secondary_dns: Option<Ipv6Addr>,
+}
+
+pub trait Interface {
+    type Error;
+
+    fn get_iface_configuration(&self) -> Result<ClientConfiguration, Self::Error>;
+    fn set_iface_configuration(&mut self, conf: &ClientConfiguration) -> Result<(), Self::Error>;
+
+    fn is_iface_up(&self) -> bool;
+
+    fn get_ip_info(&self) -> Result<IpInfo, Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix() {
+        assert_eq!("0".parse::<Prefix>(), Ok(Prefix(0)));
+        assert_eq!("64".parse::<Prefix>(), Ok(Prefix(64)));
+        assert_eq!("128".parse::<Prefix>(), Ok(Prefix(128)));
+
+        assert!("129".parse::<Prefix>().is_err());
+        assert!("-1".parse::<Prefix>().is_err());
+        assert!("/64".parse::<Prefix>().is_err());
+        assert!("".parse::<Prefix>().is_err());
+    }
+
+    #[test]
+    fn subnet() {
+        let subnet = "fd00::1/64".parse::<Subnet>().unwrap();
+        assert_eq!(subnet.gateway, Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(subnet.prefix, Prefix(64));
+
+        // The default route
+        let default = "::/0".parse::<Subnet>().unwrap();
+        assert_eq!(default.gateway, Ipv6Addr::UNSPECIFIED);
+        assert_eq!(default.prefix, Prefix(0));
+
+        assert!("fd00::1".parse::<Subnet>().is_err());
+        assert!("fd00::1/129".parse::<Subnet>().is_err());
+        assert!("fd00::1/64/64".parse::<Subnet>().is_err());
+        assert!("192.168.1.1/24".parse::<Subnet>().is_err());
+        assert!("fd00:::1/64".parse::<Subnet>().is_err());
+    }
+
+    #[test]
+    fn subnet_display() {
+        for s in ["fd00::1/64", "::/0", "2001:db8::/128"] {
+            let subnet = s.parse::<Subnet>().unwrap();
+
+            assert_eq!(subnet.to_string(), s);
+            assert_eq!(subnet.to_string().parse::<Subnet>(), Ok(subnet));
+        }
+    }
+
+    #[test]
+    fn ip_configuration() {
+        let ipv4 = ipv4::ClientConfiguration::default();
+        let ipv6 = ClientConfiguration::DHCP;
+
+        let conf = IpConfiguration::new(Some(ipv4.clone()), Some(ipv6.clone())).unwrap();
+        assert_eq!(conf.ipv4(), Some(&ipv4));
+        assert_eq!(conf.ipv6(), Some(&ipv6));
+
+        let conf = IpConfiguration::new(Some(ipv4.clone()), None).unwrap();
+        assert_eq!(conf, IpConfiguration::from(ipv4));
+        assert_eq!(conf, IpConfiguration::default());
+        assert_eq!(conf.ipv6(), None);
+
+        let conf = IpConfiguration::new(None, Some(ipv6.clone())).unwrap();
+        assert_eq!(conf, IpConfiguration::from(ipv6));
+        assert_eq!(conf.ipv4(), None);
+
+        assert_eq!(IpConfiguration::new(None, None), None);
+    }
+
+    #[cfg(all(feature = "std", feature = "use_serde"))]
+    #[test]
+    fn serde() {
+        use crate::test_json::{from_slice, to_vec};
+
+        let settings = ClientSettings {
+            ip: "fd00::20".parse().unwrap(),
+            subnet: "fd00::1/64".parse().unwrap(),
+            dns: Some("fd00::53".parse().unwrap()),
+            secondary_dns: None,
+        };
+
+        let json = to_vec(&settings);
+        assert_eq!(
+            core::str::from_utf8(&json).unwrap(),
+            r#"{"ip":"fd00::20","subnet":{"gateway":"fd00::1","prefix":64},"dns":"fd00::53","secondary_dns":null}"#
+        );
+        assert_eq!(from_slice::<ClientSettings>(&json).unwrap(), settings);
+
+        for conf in [
+            IpConfiguration::Ipv6(ClientConfiguration::SLAAC),
+            IpConfiguration::DualStack(
+                ipv4::ClientConfiguration::default(),
+                ClientConfiguration::Fixed(settings),
+            ),
+        ] {
+            assert_eq!(from_slice::<IpConfiguration>(&to_vec(&conf)).unwrap(), conf);
+        }
+
+        assert_eq!(from_slice::<Prefix>(b"0").unwrap(), Prefix(0));
+    }
+}
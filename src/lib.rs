@@ -12,18 +12,37 @@ extern crate alloc;
 #[cfg(all(feature = "defmt", feature = "log"))]
 compile_error!("You must enable at most one of the following features: defmt, log");
 
+pub mod dns;
 pub mod eth;
 pub mod event_bus;
 pub mod http;
 pub mod io;
 pub mod ipv4;
+pub mod ipv6;
 pub mod log;
+pub mod mdns;
 pub mod mqtt;
+pub mod netif;
 pub mod ota;
 pub mod ping;
+pub mod sntp;
+pub mod socket;
 pub mod storage;
 pub mod sys_time;
 pub mod timer;
 pub mod utils;
 pub mod wifi;
 pub mod ws;
+
+#[cfg(all(test, feature = "std", feature = "use_serde"))]
+mod test_json;
+#[cfg(all(test, feature = "std"))]
+mod test_event_bus;
+#[cfg(all(test, feature = "std"))]
+mod test_http;
+#[cfg(all(test, feature = "std"))]
+mod test_netif;
+#[cfg(all(test, feature = "std"))]
+mod test_ota;
+#[cfg(all(test, feature = "std"))]
+mod test_timer;
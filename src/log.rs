@@ -32,3 +32,23 @@ macro_rules! svc_log {
 }
 
 pub(crate) use svc_log;
+
+/// Wraps a value to be logged which implements `Debug`, but not necessarily `defmt::Format`
+#[cfg(feature = "defmt")]
+#[allow(unused)]
+pub(crate) fn debug<T>(value: &T) -> defmt::Debug2Format<'_, T>
+where
+    T: core::fmt::Debug + ?Sized,
+{
+    defmt::Debug2Format(value)
+}
+
+/// Wraps a value to be logged which implements `Debug`, but not necessarily `defmt::Format`
+#[cfg(not(feature = "defmt"))]
+#[allow(unused)]
+pub(crate) fn debug<T>(value: &T) -> &T
+where
+    T: core::fmt::Debug + ?Sized,
+{
+    value
+}
@@ -0,0 +1,1012 @@
+//! mDNS / DNS-SD, i.e. announcing the device and its services on the local network, and discovering the ones of others
+//!
+//! A responder (`Mdns`) answers for its host name (e.g. `kitchen-sensor`, queried as `kitchen-sensor.local`)
+//! and for the services registered with it, each being an instance (e.g. `Kitchen Sensor`) of a service type
+//! (e.g. `_http._tcp`) on a port, with TXT records describing it (e.g. `path=/api`).
+//!
+//! Discovery (`Browse`) returns the instances of a service type as `Record`s, which carry everything needed
+//! for connecting to them. Host names are always without the `.local` domain.
+//!
+//! With the `std` feature, `MockNetwork` simulates a network of responders in memory, for host tests.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ipv4::IpAddr;
+
+/// The maximum number of TXT records of a service
+pub const MAX_TXT_RECORDS: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct TxtRecord {
+    pub key: heapless::String<16>,
+    pub value: heapless::String<64>,
+}
+
+pub type Txt = heapless::Vec<TxtRecord, MAX_TXT_RECORDS>;
+
+/// A service to announce
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Service {
+    /// The name of the instance, unique for the service type on the network, e.g. `Kitchen Sensor`
+    pub instance: heapless::String<64>,
+    /// The service type, including the protocol, e.g. `_http._tcp`
+    pub service_type: heapless::String<32>,
+    pub port: u16,
+    pub txt: Txt,
+}
+
+/// A service discovered on the network
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Record {
+    pub instance: heapless::String<64>,
+    /// The host name of the responder of the service, without the `.local` domain
+    pub host: heapless::String<64>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: IpAddr,
+    pub port: u16,
+    pub txt: Txt,
+}
+
+pub trait Mdns {
+    type Error: Debug;
+
+    /// Sets the host name the responder answers for, without the `.local` domain
+    fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error>;
+
+    /// Announces `service`, replacing the one with the same instance and service type, if any
+    fn add_service(&mut self, service: &Service) -> Result<(), Self::Error>;
+
+    /// Replaces the TXT records of a service added already, and announces the change
+    ///
+    /// `txt` has at most `MAX_TXT_RECORDS` records.
+    fn set_txt(
+        &mut self,
+        instance: &str,
+        service_type: &str,
+        txt: &[TxtRecord],
+    ) -> Result<(), Self::Error>;
+
+    fn remove_service(&mut self, instance: &str, service_type: &str) -> Result<(), Self::Error>;
+}
+
+impl<M> Mdns for &mut M
+where
+    M: Mdns,
+{
+    type Error = M::Error;
+
+    fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error> {
+        (*self).set_hostname(hostname)
+    }
+
+    fn add_service(&mut self, service: &Service) -> Result<(), Self::Error> {
+        (*self).add_service(service)
+    }
+
+    fn set_txt(
+        &mut self,
+        instance: &str,
+        service_type: &str,
+        txt: &[TxtRecord],
+    ) -> Result<(), Self::Error> {
+        (*self).set_txt(instance, service_type, txt)
+    }
+
+    fn remove_service(&mut self, instance: &str, service_type: &str) -> Result<(), Self::Error> {
+        (*self).remove_service(instance, service_type)
+    }
+}
+
+pub trait Browse {
+    type Error: Debug;
+
+    /// Resolves `host`, a host name without the `.local` domain, waiting for at most `timeout`
+    ///
+    /// Returns `Ok(None)` if no responder answered.
+    fn query_host(&self, host: &str, timeout: Duration) -> Result<Option<IpAddr>, Self::Error>;
+
+    /// Collects the instances of `service_type` answering within `timeout`
+    ///
+    /// Returns the first `N` records, and the number of instances found, which might be larger than `N`.
+    fn browse_n<const N: usize>(
+        &self,
+        service_type: &str,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<Record, N>, usize), Self::Error>;
+}
+
+impl<B> Browse for &B
+where
+    B: Browse,
+{
+    type Error = B::Error;
+
+    fn query_host(&self, host: &str, timeout: Duration) -> Result<Option<IpAddr>, Self::Error> {
+        (*self).query_host(host, timeout)
+    }
+
+    fn browse_n<const N: usize>(
+        &self,
+        service_type: &str,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<Record, N>, usize), Self::Error> {
+        (*self).browse_n(service_type, timeout)
+    }
+}
+
+impl<B> Browse for &mut B
+where
+    B: Browse,
+{
+    type Error = B::Error;
+
+    fn query_host(&self, host: &str, timeout: Duration) -> Result<Option<IpAddr>, Self::Error> {
+        (**self).query_host(host, timeout)
+    }
+
+    fn browse_n<const N: usize>(
+        &self,
+        service_type: &str,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<Record, N>, usize), Self::Error> {
+        (**self).browse_n(service_type, timeout)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::fmt::Debug;
+    use core::time::Duration;
+
+    use crate::ipv4::IpAddr;
+
+    pub use super::{Record, Service, Txt, TxtRecord, MAX_TXT_RECORDS};
+
+    pub trait Mdns {
+        type Error: Debug;
+
+        async fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error>;
+
+        async fn add_service(&mut self, service: &Service) -> Result<(), Self::Error>;
+
+        async fn set_txt(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+            txt: &[TxtRecord],
+        ) -> Result<(), Self::Error>;
+
+        async fn remove_service(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+        ) -> Result<(), Self::Error>;
+    }
+
+    impl<M> Mdns for &mut M
+    where
+        M: Mdns,
+    {
+        type Error = M::Error;
+
+        async fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error> {
+            (*self).set_hostname(hostname).await
+        }
+
+        async fn add_service(&mut self, service: &Service) -> Result<(), Self::Error> {
+            (*self).add_service(service).await
+        }
+
+        async fn set_txt(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+            txt: &[TxtRecord],
+        ) -> Result<(), Self::Error> {
+            (*self).set_txt(instance, service_type, txt).await
+        }
+
+        async fn remove_service(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+        ) -> Result<(), Self::Error> {
+            (*self).remove_service(instance, service_type).await
+        }
+    }
+
+    pub trait Browse {
+        type Error: Debug;
+
+        type Browser<'a>: Browser<Error = Self::Error>
+        where
+            Self: 'a;
+
+        async fn query_host(
+            &self,
+            host: &str,
+            timeout: Duration,
+        ) -> Result<Option<IpAddr>, Self::Error>;
+
+        /// Starts discovering the instances of `service_type`, until the returned browser is dropped
+        async fn browse(&self, service_type: &str) -> Result<Self::Browser<'_>, Self::Error>;
+    }
+
+    impl<B> Browse for &B
+    where
+        B: Browse,
+    {
+        type Error = B::Error;
+
+        type Browser<'a> = B::Browser<'a> where Self: 'a;
+
+        async fn query_host(
+            &self,
+            host: &str,
+            timeout: Duration,
+        ) -> Result<Option<IpAddr>, Self::Error> {
+            (*self).query_host(host, timeout).await
+        }
+
+        async fn browse(&self, service_type: &str) -> Result<Self::Browser<'_>, Self::Error> {
+            (*self).browse(service_type).await
+        }
+    }
+
+    impl<B> Browse for &mut B
+    where
+        B: Browse,
+    {
+        type Error = B::Error;
+
+        type Browser<'a> = B::Browser<'a> where Self: 'a;
+
+        async fn query_host(
+            &self,
+            host: &str,
+            timeout: Duration,
+        ) -> Result<Option<IpAddr>, Self::Error> {
+            (**self).query_host(host, timeout).await
+        }
+
+        async fn browse(&self, service_type: &str) -> Result<Self::Browser<'_>, Self::Error> {
+            (**self).browse(service_type).await
+        }
+    }
+
+    /// The stream of the instances of a service type, as they are discovered
+    pub trait Browser {
+        type Error: Debug;
+
+        /// Waits for the next instance found, or for an instance found already whose TXT records,
+        /// host or IP changed
+        ///
+        /// Instances leaving the network are not reported.
+        async fn next(&mut self) -> Result<Record, Self::Error>;
+    }
+
+    impl<B> Browser for &mut B
+    where
+        B: Browser,
+    {
+        type Error = B::Error;
+
+        async fn next(&mut self) -> Result<Record, Self::Error> {
+            (*self).next().await
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use mock::*;
+
+#[cfg(feature = "std")]
+mod mock {
+    use core::convert::TryInto;
+    use core::fmt;
+    use core::time::Duration;
+
+    use std::sync::{Arc, Mutex};
+    use std::task::Waker;
+
+    use crate::ipv4::IpAddr;
+
+    use super::{Browse, Mdns, Record, Service, Txt, TxtRecord};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum MockError {
+        /// A name, or the TXT records, do not fit the bounds of `Service`
+        Overflow,
+        /// A service was added before setting the host name
+        NoHostname,
+        UnknownService,
+    }
+
+    impl fmt::Display for MockError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Overflow => write!(f, "Name or TXT records too long"),
+                Self::NoHostname => write!(f, "No host name set"),
+                Self::UnknownService => write!(f, "Unknown service"),
+            }
+        }
+    }
+
+    impl std::error::Error for MockError {}
+
+    struct Registered {
+        responder: usize,
+        service_type: heapless::String<32>,
+        record: Record,
+    }
+
+    #[derive(Default)]
+    struct State {
+        responders: usize,
+        hosts: Vec<(usize, heapless::String<64>, IpAddr)>,
+        services: Vec<Registered>,
+        /// The services as announced, in order, for the browsers
+        announcements: Vec<(heapless::String<32>, Record)>,
+        wakers: Vec<Waker>,
+    }
+
+    impl State {
+        fn announce(&mut self, service_type: &heapless::String<32>, record: &Record) {
+            self.announcements
+                .push((service_type.clone(), record.clone()));
+
+            for waker in self.wakers.drain(..) {
+                waker.wake();
+            }
+        }
+
+        fn browse<'a>(&'a self, service_type: &'a str) -> impl Iterator<Item = &'a Record> {
+            self.services
+                .iter()
+                .filter(move |registered| {
+                    registered.service_type.eq_ignore_ascii_case(service_type)
+                })
+                .map(|registered| &registered.record)
+        }
+    }
+
+    fn string<const N: usize>(s: &str) -> Result<heapless::String<N>, MockError> {
+        s.try_into().map_err(|_| MockError::Overflow)
+    }
+
+    fn host(host: &str) -> &str {
+        host.strip_suffix(".local").unwrap_or(host)
+    }
+
+    /// A network of `MockMdns` responders in memory, for host tests, where announcements and queries
+    /// are answered immediately and reliably
+    ///
+    /// Clones share the same network.
+    #[derive(Clone, Default)]
+    pub struct MockNetwork(Arc<Mutex<State>>);
+
+    impl MockNetwork {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        /// A responder joining the network with IP `ip`, with no host name and no services yet
+        pub fn responder(&self, ip: IpAddr) -> MockMdns {
+            let mut state = self.0.lock().unwrap();
+
+            let id = state.responders;
+            state.responders += 1;
+
+            MockMdns {
+                network: self.clone(),
+                id,
+                ip,
+                hostname: None,
+            }
+        }
+    }
+
+    impl Browse for MockNetwork {
+        type Error = MockError;
+
+        fn query_host(
+            &self,
+            host_name: &str,
+            _timeout: Duration,
+        ) -> Result<Option<IpAddr>, Self::Error> {
+            let host_name = host(host_name);
+
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .hosts
+                .iter()
+                .find(|(_, hostname, _)| hostname.eq_ignore_ascii_case(host_name))
+                .map(|(_, _, ip)| *ip))
+        }
+
+        fn browse_n<const N: usize>(
+            &self,
+            service_type: &str,
+            _timeout: Duration,
+        ) -> Result<(heapless::Vec<Record, N>, usize), Self::Error> {
+            let state = self.0.lock().unwrap();
+
+            let mut records = heapless::Vec::new();
+            let mut found = 0;
+
+            for record in state.browse(service_type) {
+                let _ = records.push(record.clone());
+                found += 1;
+            }
+
+            Ok((records, found))
+        }
+    }
+
+    /// A responder on a `MockNetwork`, which leaves the network when dropped
+    ///
+    /// Browses the whole network, including its own services.
+    pub struct MockMdns {
+        network: MockNetwork,
+        id: usize,
+        ip: IpAddr,
+        hostname: Option<heapless::String<64>>,
+    }
+
+    impl MockMdns {
+        pub fn network(&self) -> &MockNetwork {
+            &self.network
+        }
+    }
+
+    impl Drop for MockMdns {
+        fn drop(&mut self) {
+            let mut state = self.network.0.lock().unwrap();
+
+            state
+                .hosts
+                .retain(|(responder, _, _)| *responder != self.id);
+            state
+                .services
+                .retain(|registered| registered.responder != self.id);
+        }
+    }
+
+    impl Mdns for MockMdns {
+        type Error = MockError;
+
+        fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error> {
+            let hostname: heapless::String<64> = string(host(hostname))?;
+
+            let mut state = self.network.0.lock().unwrap();
+
+            state
+                .hosts
+                .retain(|(responder, _, _)| *responder != self.id);
+            state.hosts.push((self.id, hostname.clone(), self.ip));
+
+            let changed = state
+                .services
+                .iter_mut()
+                .filter(|registered| registered.responder == self.id)
+                .map(|registered| {
+                    registered.record.host = hostname.clone();
+                    (registered.service_type.clone(), registered.record.clone())
+                })
+                .collect::<Vec<_>>();
+
+            for (service_type, record) in &changed {
+                state.announce(service_type, record);
+            }
+
+            self.hostname = Some(hostname);
+
+            Ok(())
+        }
+
+        fn add_service(&mut self, service: &Service) -> Result<(), Self::Error> {
+            let host = self.hostname.clone().ok_or(MockError::NoHostname)?;
+
+            let record = Record {
+                instance: service.instance.clone(),
+                host,
+                ip: self.ip,
+                port: service.port,
+                txt: service.txt.clone(),
+            };
+
+            let mut state = self.network.0.lock().unwrap();
+
+            state.services.retain(|registered| {
+                !(registered.responder == self.id
+                    && registered.record.instance == service.instance
+                    && registered.service_type == service.service_type)
+            });
+
+            state.announce(&service.service_type, &record);
+
+            state.services.push(Registered {
+                responder: self.id,
+                service_type: service.service_type.clone(),
+                record,
+            });
+
+            Ok(())
+        }
+
+        fn set_txt(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+            txt: &[TxtRecord],
+        ) -> Result<(), Self::Error> {
+            let txt = Txt::from_slice(txt).map_err(|_| MockError::Overflow)?;
+
+            let mut state = self.network.0.lock().unwrap();
+
+            let registered = state
+                .services
+                .iter_mut()
+                .find(|registered| {
+                    registered.responder == self.id
+                        && registered.record.instance == instance
+                        && registered.service_type == service_type
+                })
+                .ok_or(MockError::UnknownService)?;
+
+            registered.record.txt = txt;
+
+            let (service_type, record) =
+                (registered.service_type.clone(), registered.record.clone());
+
+            state.announce(&service_type, &record);
+
+            Ok(())
+        }
+
+        fn remove_service(
+            &mut self,
+            instance: &str,
+            service_type: &str,
+        ) -> Result<(), Self::Error> {
+            let mut state = self.network.0.lock().unwrap();
+
+            let count = state.services.len();
+
+            state.services.retain(|registered| {
+                !(registered.responder == self.id
+                    && registered.record.instance == instance
+                    && registered.service_type == service_type)
+            });
+
+            if state.services.len() < count {
+                Ok(())
+            } else {
+                Err(MockError::UnknownService)
+            }
+        }
+    }
+
+    impl Browse for MockMdns {
+        type Error = MockError;
+
+        fn query_host(&self, host: &str, timeout: Duration) -> Result<Option<IpAddr>, Self::Error> {
+            self.network.query_host(host, timeout)
+        }
+
+        fn browse_n<const N: usize>(
+            &self,
+            service_type: &str,
+            timeout: Duration,
+        ) -> Result<(heapless::Vec<Record, N>, usize), Self::Error> {
+            self.network.browse_n(service_type, timeout)
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    pub use asynch::*;
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use core::future::poll_fn;
+        use core::task::Poll;
+        use core::time::Duration;
+
+        use std::collections::VecDeque;
+
+        use crate::ipv4::IpAddr;
+        use crate::mdns::asynch::{Browse, Browser, Mdns};
+
+        use super::{MockError, MockMdns, MockNetwork, Record, Service, TxtRecord};
+
+        /// Yields the instances on the network when browsing started, and then the ones announced since
+        pub struct MockBrowser {
+            network: MockNetwork,
+            service_type: heapless::String<32>,
+            found: VecDeque<Record>,
+            announced: usize,
+        }
+
+        impl Browser for MockBrowser {
+            type Error = MockError;
+
+            async fn next(&mut self) -> Result<Record, Self::Error> {
+                poll_fn(|cx| {
+                    if let Some(record) = self.found.pop_front() {
+                        return Poll::Ready(Ok(record));
+                    }
+
+                    let mut state = self.network.0.lock().unwrap();
+
+                    while let Some((service_type, record)) = state.announcements.get(self.announced)
+                    {
+                        self.announced += 1;
+
+                        if service_type.eq_ignore_ascii_case(&self.service_type) {
+                            return Poll::Ready(Ok(record.clone()));
+                        }
+                    }
+
+                    if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                        state.wakers.push(cx.waker().clone());
+                    }
+
+                    Poll::Pending
+                })
+                .await
+            }
+        }
+
+        impl Browse for MockNetwork {
+            type Error = MockError;
+
+            type Browser<'a> = MockBrowser where Self: 'a;
+
+            async fn query_host(
+                &self,
+                host: &str,
+                timeout: Duration,
+            ) -> Result<Option<IpAddr>, Self::Error> {
+                crate::mdns::Browse::query_host(self, host, timeout)
+            }
+
+            async fn browse(&self, service_type: &str) -> Result<Self::Browser<'_>, Self::Error> {
+                let service_type = super::string(service_type)?;
+
+                let state = self.0.lock().unwrap();
+
+                Ok(MockBrowser {
+                    network: self.clone(),
+                    found: state.browse(&service_type).cloned().collect(),
+                    announced: state.announcements.len(),
+                    service_type,
+                })
+            }
+        }
+
+        impl Browse for MockMdns {
+            type Error = MockError;
+
+            type Browser<'a> = MockBrowser where Self: 'a;
+
+            async fn query_host(
+                &self,
+                host: &str,
+                timeout: Duration,
+            ) -> Result<Option<IpAddr>, Self::Error> {
+                Browse::query_host(&self.network, host, timeout).await
+            }
+
+            async fn browse(&self, service_type: &str) -> Result<Self::Browser<'_>, Self::Error> {
+                self.network.browse(service_type).await
+            }
+        }
+
+        impl Mdns for MockMdns {
+            type Error = MockError;
+
+            async fn set_hostname(&mut self, hostname: &str) -> Result<(), Self::Error> {
+                crate::mdns::Mdns::set_hostname(self, hostname)
+            }
+
+            async fn add_service(&mut self, service: &Service) -> Result<(), Self::Error> {
+                crate::mdns::Mdns::add_service(self, service)
+            }
+
+            async fn set_txt(
+                &mut self,
+                instance: &str,
+                service_type: &str,
+                txt: &[TxtRecord],
+            ) -> Result<(), Self::Error> {
+                crate::mdns::Mdns::set_txt(self, instance, service_type, txt)
+            }
+
+            async fn remove_service(
+                &mut self,
+                instance: &str,
+                service_type: &str,
+            ) -> Result<(), Self::Error> {
+                crate::mdns::Mdns::remove_service(self, instance, service_type)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::TryInto;
+
+    use crate::ipv4::Ipv4Addr;
+
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    fn ip(host: u8) -> IpAddr {
+        Ipv4Addr::new(192, 168, 1, host).into()
+    }
+
+    fn txt(records: &[(&str, &str)]) -> Txt {
+        records
+            .iter()
+            .map(|(key, value)| TxtRecord {
+                key: (*key).try_into().unwrap(),
+                value: (*value).try_into().unwrap(),
+            })
+            .collect()
+    }
+
+    fn http(instance: &str, port: u16) -> Service {
+        Service {
+            instance: instance.try_into().unwrap(),
+            service_type: "_http._tcp".try_into().unwrap(),
+            port,
+            txt: txt(&[("path", "/api")]),
+        }
+    }
+
+    fn browse<B: Browse>(browse: &B, service_type: &str) -> (Vec<Record>, usize) {
+        let (records, found) = browse.browse_n::<4>(service_type, SEC).unwrap();
+
+        (records.into_iter().collect(), found)
+    }
+
+    #[test]
+    fn registration() {
+        let network = MockNetwork::new();
+
+        let mut sensor = network.responder(ip(10));
+        let other = network.responder(ip(20));
+
+        assert_eq!(
+            sensor.add_service(&http("Kitchen Sensor", 80)),
+            Err(MockError::NoHostname)
+        );
+
+        sensor.set_hostname("kitchen-sensor.local").unwrap();
+
+        // Without the `.local` domain, and regardless of the case
+        for host in ["kitchen-sensor", "Kitchen-Sensor.local"] {
+            assert_eq!(other.query_host(host, SEC), Ok(Some(ip(10))));
+        }
+        assert_eq!(other.query_host("hallway", SEC), Ok(None));
+
+        sensor.add_service(&http("Kitchen Sensor", 80)).unwrap();
+
+        let (records, found) = browse(&other, "_HTTP._tcp");
+        assert_eq!(found, 1);
+        assert_eq!(
+            records[0],
+            Record {
+                instance: "Kitchen Sensor".try_into().unwrap(),
+                host: "kitchen-sensor".try_into().unwrap(),
+                ip: ip(10),
+                port: 80,
+                txt: txt(&[("path", "/api")]),
+            }
+        );
+
+        // Replaced
+        sensor.add_service(&http("Kitchen Sensor", 8080)).unwrap();
+
+        let (records, found) = browse(&sensor, "_http._tcp");
+        assert_eq!(found, 1);
+        assert_eq!(records[0].port, 8080);
+
+        // Renamed
+        sensor.set_hostname("kitchen").unwrap();
+
+        assert_eq!(other.query_host("kitchen-sensor", SEC), Ok(None));
+        assert_eq!(browse(&other, "_http._tcp").0[0].host, "kitchen");
+
+        sensor
+            .remove_service("Kitchen Sensor", "_http._tcp")
+            .unwrap();
+        assert_eq!(browse(&other, "_http._tcp").1, 0);
+
+        assert_eq!(
+            sensor.remove_service("Kitchen Sensor", "_http._tcp"),
+            Err(MockError::UnknownService)
+        );
+    }
+
+    #[test]
+    fn left_network() {
+        let network = MockNetwork::new();
+
+        let mut sensor = network.responder(ip(10));
+        sensor.set_hostname("kitchen-sensor").unwrap();
+        sensor.add_service(&http("Kitchen Sensor", 80)).unwrap();
+
+        drop(sensor);
+
+        assert_eq!(network.query_host("kitchen-sensor", SEC), Ok(None));
+        assert_eq!(browse(&network, "_http._tcp").1, 0);
+    }
+
+    #[test]
+    fn txt_update() {
+        let network = MockNetwork::new();
+
+        let mut sensor = network.responder(ip(10));
+        sensor.set_hostname("kitchen-sensor").unwrap();
+        sensor.add_service(&http("Kitchen Sensor", 80)).unwrap();
+
+        let updated = txt(&[("path", "/v2"), ("fw", "1.2.0")]);
+
+        sensor
+            .set_txt("Kitchen Sensor", "_http._tcp", &updated)
+            .unwrap();
+        assert_eq!(browse(&network, "_http._tcp").0[0].txt, updated);
+
+        assert_eq!(
+            sensor.set_txt("Hallway Sensor", "_http._tcp", &updated),
+            Err(MockError::UnknownService)
+        );
+
+        let too_many = vec![TxtRecord::default(); MAX_TXT_RECORDS + 1];
+        assert_eq!(
+            sensor.set_txt("Kitchen Sensor", "_http._tcp", &too_many),
+            Err(MockError::Overflow)
+        );
+        assert_eq!(browse(&network, "_http._tcp").0[0].txt, updated);
+
+        assert_eq!(
+            sensor.set_hostname(&"x".repeat(65)),
+            Err(MockError::Overflow)
+        );
+    }
+
+    #[test]
+    fn browse_all() {
+        let network = MockNetwork::new();
+
+        let mut responders: Vec<_> = (0..3)
+            .map(|index| {
+                let mut responder = network.responder(ip(10 + index));
+                responder
+                    .set_hostname(&format!("sensor-{}", index))
+                    .unwrap();
+
+                responder
+            })
+            .collect();
+
+        for (index, responder) in responders.iter_mut().enumerate() {
+            responder
+                .add_service(&http(&format!("Sensor {}", index), 80))
+                .unwrap();
+        }
+
+        responders[0]
+            .add_service(&Service {
+                service_type: "_mqtt._tcp".try_into().unwrap(),
+                ..http("Broker", 1883)
+            })
+            .unwrap();
+
+        // The first `N`, out of all the instances found
+        let (records, found) = network.browse_n::<2>("_http._tcp", SEC).unwrap();
+
+        assert_eq!(found, 3);
+        assert_eq!(records.len(), 2);
+
+        let (records, found) = browse(&network, "_mqtt._tcp");
+
+        assert_eq!(found, 1);
+        assert_eq!(records[0].host, "sensor-0");
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use core::convert::TryInto;
+        use core::future::Future;
+        use core::pin::{pin, Pin};
+        use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        use crate::mdns::asynch::{Browse, Browser, Mdns};
+        use crate::mdns::{MockNetwork, Service};
+        use crate::utils::asyncify::block_on;
+
+        use super::{http, ip, txt};
+
+        const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+            |_| RawWaker::new(core::ptr::null(), &NOOP_VTABLE),
+            |_| (),
+            |_| (),
+            |_| (),
+        );
+
+        fn poll<F>(future: Pin<&mut F>) -> Poll<F::Output>
+        where
+            F: Future,
+        {
+            // Safety: the vtable functions do nothing with the (null) data pointer
+            let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &NOOP_VTABLE)) };
+
+            future.poll(&mut Context::from_waker(&waker))
+        }
+
+        #[test]
+        fn browse_async() {
+            let network = MockNetwork::new();
+
+            let mut sensor = network.responder(ip(10));
+
+            block_on(async {
+                sensor.set_hostname("kitchen-sensor").await.unwrap();
+                sensor
+                    .add_service(&http("Kitchen Sensor", 80))
+                    .await
+                    .unwrap();
+            });
+
+            let mut browser = block_on(network.browse("_http._tcp")).unwrap();
+
+            // Found when browsing started
+            let record = block_on(browser.next()).unwrap();
+            assert_eq!(record.instance, "Kitchen Sensor");
+
+            assert!(poll(pin!(browser.next())).is_pending());
+
+            // Announced since, skipping the other service types
+            block_on(async {
+                sensor
+                    .add_service(&Service {
+                        service_type: "_mqtt._tcp".try_into().unwrap(),
+                        ..http("Broker", 1883)
+                    })
+                    .await
+                    .unwrap();
+
+                sensor
+                    .set_txt("Kitchen Sensor", "_http._tcp", &txt(&[("fw", "2")]))
+                    .await
+                    .unwrap();
+            });
+
+            let record = block_on(browser.next()).unwrap();
+            assert_eq!(record.txt, txt(&[("fw", "2")]));
+
+            assert!(poll(pin!(browser.next())).is_pending());
+
+            assert_eq!(
+                block_on(network.query_host("kitchen-sensor", super::SEC)),
+                Ok(Some(ip(10)))
+            );
+        }
+    }
+}
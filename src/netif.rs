@@ -0,0 +1,437 @@
+//! The network interfaces of the device, regardless of whether they are Wifi or Ethernet
+//!
+//! Higher layers which only need to know whether the device is online and under which IP can take an `Interface`,
+//! rather than a `wifi::Wifi` or an `eth::Eth`. `WifiInterface` and `EthInterface` adapt the drivers, together
+//! with the `ipv4::Interface` of their network interface, which reports the IP actually in use.
+//!
+//! The changes of an interface are reported as `Event`s, which `Monitor` posts to any event bus `Postbox`.
+
+use core::fmt::Debug;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::event_bus::Postbox;
+use crate::utils::event_bus::EitherError;
+use crate::{eth, ipv4, ipv6, wifi};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Kind {
+    Wifi,
+    Ethernet,
+    Other,
+}
+
+pub trait Interface {
+    type Error: Debug;
+
+    /// The name of the interface, e.g. `sta` or `eth0`
+    fn name(&self) -> &str;
+
+    fn kind(&self) -> Kind;
+
+    /// Whether the interface is connected, and has its IP configured
+    fn is_up(&self) -> Result<bool, Self::Error>;
+
+    /// The IPv4 settings in use (IP, netmask, gateway and DNS), or `Ok(None)` if the interface is down
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error>;
+
+    /// Same as `ip_info`, but for IPv6, which is not supported by default
+    fn ipv6_info(&self) -> Result<Option<ipv6::ClientSettings>, Self::Error> {
+        Ok(None)
+    }
+}
+
+impl<I> Interface for &I
+where
+    I: Interface + ?Sized,
+{
+    type Error = I::Error;
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn kind(&self) -> Kind {
+        (**self).kind()
+    }
+
+    fn is_up(&self) -> Result<bool, Self::Error> {
+        (**self).is_up()
+    }
+
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error> {
+        (**self).ip_info()
+    }
+
+    fn ipv6_info(&self) -> Result<Option<ipv6::ClientSettings>, Self::Error> {
+        (**self).ipv6_info()
+    }
+}
+
+impl<I> Interface for &mut I
+where
+    I: Interface + ?Sized,
+{
+    type Error = I::Error;
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn kind(&self) -> Kind {
+        (**self).kind()
+    }
+
+    fn is_up(&self) -> Result<bool, Self::Error> {
+        (**self).is_up()
+    }
+
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error> {
+        (**self).ip_info()
+    }
+
+    fn ipv6_info(&self) -> Result<Option<ipv6::ClientSettings>, Self::Error> {
+        (**self).ipv6_info()
+    }
+}
+
+fn settings(ip_info: ipv4::IpInfo) -> ipv4::ClientSettings {
+    ipv4::ClientSettings {
+        ip: ip_info.ip,
+        subnet: ip_info.subnet,
+        dns: ip_info.dns,
+        secondary_dns: ip_info.secondary_dns,
+    }
+}
+
+fn ip_info<N>(up: bool, netif: &N) -> Result<Option<ipv4::ClientSettings>, N::Error>
+where
+    N: ipv4::Interface,
+{
+    if up {
+        netif.get_ip_info().map(settings).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// A `wifi::Wifi` driver, and the `ipv4::Interface` of its station, as an `Interface`
+pub struct WifiInterface<'a, W, N> {
+    name: &'a str,
+    wifi: W,
+    netif: N,
+}
+
+impl<'a, W, N> WifiInterface<'a, W, N> {
+    pub const fn new(name: &'a str, wifi: W, netif: N) -> Self {
+        Self { name, wifi, netif }
+    }
+
+    pub fn wifi(&self) -> &W {
+        &self.wifi
+    }
+
+    pub fn wifi_mut(&mut self) -> &mut W {
+        &mut self.wifi
+    }
+
+    pub fn netif(&self) -> &N {
+        &self.netif
+    }
+
+    pub fn release(self) -> (W, N) {
+        (self.wifi, self.netif)
+    }
+}
+
+impl<'a, W, N> Interface for WifiInterface<'a, W, N>
+where
+    W: wifi::Wifi,
+    N: ipv4::Interface<Error = W::Error>,
+{
+    type Error = W::Error;
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Wifi
+    }
+
+    fn is_up(&self) -> Result<bool, Self::Error> {
+        Ok(self.wifi.is_connected()? && self.netif.is_iface_up())
+    }
+
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error> {
+        ip_info(self.is_up()?, &self.netif)
+    }
+}
+
+/// An `eth::Eth` driver, and the `ipv4::Interface` of its network interface, as an `Interface`
+pub struct EthInterface<'a, E, N> {
+    name: &'a str,
+    eth: E,
+    netif: N,
+}
+
+impl<'a, E, N> EthInterface<'a, E, N> {
+    pub const fn new(name: &'a str, eth: E, netif: N) -> Self {
+        Self { name, eth, netif }
+    }
+
+    pub fn eth(&self) -> &E {
+        &self.eth
+    }
+
+    pub fn eth_mut(&mut self) -> &mut E {
+        &mut self.eth
+    }
+
+    pub fn netif(&self) -> &N {
+        &self.netif
+    }
+
+    pub fn release(self) -> (E, N) {
+        (self.eth, self.netif)
+    }
+}
+
+impl<'a, E, N> Interface for EthInterface<'a, E, N>
+where
+    E: eth::Eth,
+    N: ipv4::Interface<Error = E::Error>,
+{
+    type Error = E::Error;
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Ethernet
+    }
+
+    fn is_up(&self) -> Result<bool, Self::Error> {
+        Ok(self.eth.is_connected()? && self.netif.is_iface_up())
+    }
+
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error> {
+        ip_info(self.is_up()?, &self.netif)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Event {
+    Up,
+    Down,
+    /// The IPv4 settings changed, or were lost (`None`)
+    IpChanged(Option<ipv4::ClientSettings>),
+    /// The IPv6 settings changed, or were lost (`None`)
+    Ipv6Changed(Option<ipv6::ClientSettings>),
+}
+
+/// Reports the changes of an `Interface` as `Event`s, by comparing its state with the one of the previous check
+///
+/// `check` is meant to be called on the events of the driver (e.g. Wifi connected, or IP assigned), or periodically.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Monitor {
+    up: bool,
+    ip: Option<ipv4::ClientSettings>,
+    ipv6: Option<ipv6::ClientSettings>,
+}
+
+impl Monitor {
+    /// Creates a monitor whose first check reports the interface if it is up already
+    pub const fn new() -> Self {
+        Self {
+            up: false,
+            ip: None,
+            ipv6: None,
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+
+    /// The IPv4 settings found by the last check
+    pub fn ip_info(&self) -> Option<&ipv4::ClientSettings> {
+        self.ip.as_ref()
+    }
+
+    /// Checks `interface`, and posts the changes since the last check to `postbox`, returning whether there were any
+    ///
+    /// When going up, `Event::Up` is posted before the IP settings, and when going down, after them.
+    pub fn check<I, P>(
+        &mut self,
+        interface: &I,
+        postbox: &P,
+    ) -> Result<bool, EitherError<I::Error, P::Error>>
+    where
+        I: Interface,
+        P: Postbox<Event>,
+    {
+        let up = interface.is_up().map_err(EitherError::First)?;
+        let ip = interface.ip_info().map_err(EitherError::First)?;
+        let ipv6 = interface.ipv6_info().map_err(EitherError::First)?;
+
+        let post = |event| postbox.post(&event, None).map_err(EitherError::Second);
+
+        let changed = up != self.up || ip != self.ip || ipv6 != self.ipv6;
+
+        if up && !self.up {
+            post(Event::Up)?;
+        }
+
+        if ip != self.ip {
+            post(Event::IpChanged(ip))?;
+        }
+
+        if ipv6 != self.ipv6 {
+            post(Event::Ipv6Changed(ipv6))?;
+        }
+
+        if !up && self.up {
+            post(Event::Down)?;
+        }
+
+        self.up = up;
+        self.ip = ip;
+        self.ipv6 = ipv6;
+
+        Ok(changed)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::ipv6::{Ipv6Addr, Prefix};
+    use crate::test_event_bus::MockBus;
+    use crate::test_netif::MockInterface;
+
+    use super::*;
+
+    fn ipv6() -> ipv6::ClientSettings {
+        ipv6::ClientSettings {
+            ip: Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2),
+            subnet: ipv6::Subnet {
+                gateway: Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+                prefix: Prefix(64),
+            },
+            dns: None,
+            secondary_dns: None,
+        }
+    }
+
+    #[test]
+    fn up_and_down() {
+        let interface = MockInterface::new("sta", Kind::Wifi);
+        let bus = MockBus::new();
+
+        let mut monitor = Monitor::new();
+
+        // Down already
+        assert!(!monitor.check(&interface, &bus).unwrap());
+        assert!(bus.posted().is_empty());
+
+        let ip = MockInterface::settings(1, 10);
+        interface.set_ip(Some(ip));
+
+        // Up before the IP settings
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(bus.posted(), [Event::Up, Event::IpChanged(Some(ip))]);
+        assert!(monitor.is_up());
+        assert_eq!(monitor.ip_info(), Some(&ip));
+
+        // No change
+        assert!(!monitor.check(&interface, &bus).unwrap());
+        assert_eq!(bus.posted().len(), 2);
+
+        // Down after the IP settings
+        interface.set_ip(None);
+
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(bus.posted()[2..], [Event::IpChanged(None), Event::Down]);
+        assert!(!monitor.is_up());
+        assert_eq!(monitor.ip_info(), None);
+    }
+
+    #[test]
+    fn ip_changed() {
+        let interface = MockInterface::new("eth0", Kind::Ethernet);
+        let bus = MockBus::new();
+
+        let mut monitor = Monitor::new();
+
+        interface.set_ip(Some(MockInterface::settings(1, 10)));
+        monitor.check(&interface, &bus).unwrap();
+
+        // Renewed with another IP, while staying up
+        let ip = MockInterface::settings(2, 20);
+        interface.set_ip(Some(ip));
+
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(bus.posted()[2..], [Event::IpChanged(Some(ip))]);
+
+        // IPv6 on top
+        interface.set_ipv6(Some(ipv6()));
+
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(bus.posted()[3..], [Event::Ipv6Changed(Some(ipv6()))]);
+
+        interface.set_ip(None);
+        interface.set_ipv6(None);
+
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(
+            bus.posted()[4..],
+            [
+                Event::IpChanged(None),
+                Event::Ipv6Changed(None),
+                Event::Down
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_checks() {
+        let interface = MockInterface::new("sta", Kind::Wifi);
+        let bus = MockBus::new();
+
+        let mut monitor = Monitor::new();
+
+        interface.set_ip(Some(MockInterface::settings(1, 10)));
+
+        interface.set_fail(true);
+        assert!(matches!(
+            monitor.check(&interface, &bus),
+            Err(EitherError::First(()))
+        ));
+
+        interface.set_fail(false);
+        bus.set_fail(true);
+        assert!(matches!(
+            monitor.check(&interface, &bus),
+            Err(EitherError::Second(()))
+        ));
+
+        // The state is only updated once the events are posted, so they are posted again on the next check
+        assert!(!monitor.is_up());
+
+        bus.set_fail(false);
+        assert!(monitor.check(&interface, &bus).unwrap());
+        assert_eq!(
+            bus.posted(),
+            [
+                Event::Up,
+                Event::IpChanged(Some(MockInterface::settings(1, 10)))
+            ]
+        );
+    }
+}
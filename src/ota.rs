@@ -4,13 +4,19 @@ use serde::{Deserialize, Serialize};
 use crate::io::{ErrorType, Read, Write};
 use crate::utils::io::*;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct Slot {
     pub label: heapless::String<32>,
     pub state: SlotState,
     pub firmware: Option<FirmwareInfo>,
+    /// Whether this is the slot of the running firmware
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub running: bool,
+    /// Whether the bootloader would boot this slot if it was activated
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub bootable: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -42,24 +48,42 @@ pub enum LoadResult {
     Loaded,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "std", derive(Hash))]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub enum SlotState {
+    /// The factory image
     Factory,
+    /// The image was marked as valid with `Ota::mark_running_slot_valid`
     Valid,
+    /// The image was marked as invalid and will not be booted anymore
     Invalid,
+    /// The image was booted for the first time after an update and is pending verification
     Unverified,
+    #[default]
     Unknown,
 }
 
+/// An update re-opened with `Ota::resume_update`
+pub struct ResumedUpdate<U> {
+    pub update: U,
+    /// The offset in the image from which the update should be continued.
+    ///
+    /// Implementations should report the end of the last fully flashed sector,
+    /// so that a partially flashed sector is written again.
+    pub offset: u64,
+}
+
 pub trait FirmwareInfoLoader: ErrorType {
     fn load(&mut self, buf: &[u8]) -> Result<LoadResult, Self::Error>;
 
     fn is_loaded(&self) -> bool;
 
     fn get_info(&self) -> Result<FirmwareInfo, Self::Error>;
+
+    /// Discards the loaded info and any partially loaded data, so that the loader can be used for another image
+    fn reset(&mut self);
 }
 
 impl<F> FirmwareInfoLoader for &mut F
@@ -77,8 +101,19 @@ where
     fn get_info(&self) -> Result<FirmwareInfo, Self::Error> {
         (**self).get_info()
     }
+
+    fn reset(&mut self) {
+        (*self).reset()
+    }
 }
 
+/// The expected boot flow after an update is as follows:
+/// - The bootloader boots the newly activated slot, which is reported with `SlotState::Unverified`
+/// - The application checks its health (connectivity, configuration etc.) and then either calls
+///   `mark_running_slot_valid`, which makes the update permanent, or
+///   `mark_running_slot_invalid_and_reboot`, which makes the bootloader roll back to the previous slot
+/// - Bootloaders which support it will also roll back automatically if the application reboots
+///   without marking the slot as valid
 pub trait Ota: ErrorType {
     type Update<'a>: OtaUpdate<Error = Self::Error>
     where
@@ -90,15 +125,40 @@ pub trait Ota: ErrorType {
 
     fn get_update_slot(&self) -> Result<Slot, Self::Error>;
 
+    /// Fills `slots` with the slots of the device and returns their number.
+    ///
+    /// The default implementation only reports the boot, running and update slots.
+    fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, Self::Error> {
+        Ok(fill_slots(
+            slots,
+            [
+                self.get_boot_slot()?,
+                self.get_running_slot()?,
+                self.get_update_slot()?,
+            ],
+        ))
+    }
+
     fn is_factory_reset_supported(&self) -> Result<bool, Self::Error>;
 
     fn factory_reset(&mut self) -> Result<(), Self::Error>;
 
     fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error>;
 
+    /// Re-opens an update which was interrupted (e.g. by a reboot) before it was completed.
+    ///
+    /// Returns `None` if there is no such update or if resuming updates is not supported.
+    fn resume_update(&mut self) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+        Ok(None)
+    }
+
     fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error>;
 
     fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error;
+
+    fn is_running_slot_pending_verification(&self) -> Result<bool, Self::Error> {
+        Ok(self.get_running_slot()?.state == SlotState::Unverified)
+    }
 }
 
 impl<O> Ota for &mut O
@@ -119,6 +179,10 @@ where
         (**self).get_update_slot()
     }
 
+    fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, Self::Error> {
+        (**self).get_slots(slots)
+    }
+
     fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
         (**self).is_factory_reset_supported()
     }
@@ -131,6 +195,10 @@ where
         (*self).initiate_update()
     }
 
+    fn resume_update(&mut self) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+        (*self).resume_update()
+    }
+
     fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
         (*self).mark_running_slot_valid()
     }
@@ -138,6 +206,27 @@ where
     fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
         (*self).mark_running_slot_invalid_and_reboot()
     }
+
+    fn is_running_slot_pending_verification(&self) -> Result<bool, Self::Error> {
+        (**self).is_running_slot_pending_verification()
+    }
+}
+
+fn fill_slots(slots: &mut [Slot], candidates: [Slot; 3]) -> usize {
+    let mut count = 0;
+
+    for candidate in candidates {
+        if count < slots.len()
+            && !slots[..count]
+                .iter()
+                .any(|slot| slot.label == candidate.label)
+        {
+            slots[count] = candidate;
+            count += 1;
+        }
+    }
+
+    count
 }
 
 pub trait OtaUpdate: Write {
@@ -149,6 +238,12 @@ pub trait OtaUpdate: Write {
 
     fn abort(self) -> Result<(), Self::Error>;
 
+    /// The number of bytes of the image written so far, including the ones written before
+    /// the update was resumed, if tracked by the implementation
+    fn written(&self) -> Option<u64> {
+        None
+    }
+
     fn update<R>(
         mut self,
         read: R,
@@ -160,7 +255,7 @@ pub trait OtaUpdate: Write {
     {
         let mut buf = [0_u8; 64];
 
-        match copy_len_with_progress(read, &mut self, &mut buf, u64::MAX, progress) {
+        match copy_with_progress(read, &mut self, &mut buf, progress) {
             Ok(_) => self.complete().map_err(CopyError::Write),
             Err(e) => {
                 self.abort().map_err(CopyError::Write)?;
@@ -175,12 +270,238 @@ pub trait OtaUpdateFinished: ErrorType {
     fn activate(self) -> Result<(), Self::Error>;
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ProgressEvent {
+    Writing,
+    Completed,
+    Aborted,
+}
+
+/// An `OtaUpdate` decorator tracking the number of bytes written against a declared total size.
+///
+/// The callback is called with the event and the written and total sizes each time the written
+/// percentage advances by (at least) `granularity` percent, and exactly once when the update
+/// is completed, finished or aborted.
+pub struct ProgressOta<U, F> {
+    update: U,
+    total: u64,
+    written: u64,
+    granularity: u8,
+    reported: u8,
+    callback: F,
+}
+
+impl<U, F> ProgressOta<U, F>
+where
+    F: FnMut(ProgressEvent, u64, u64),
+{
+    pub fn new(update: U, total: u64, granularity: u8, callback: F) -> Self {
+        Self {
+            update,
+            total,
+            written: 0,
+            granularity: granularity.clamp(1, 100),
+            reported: 0,
+            callback,
+        }
+    }
+
+    /// The number of bytes written so far through this decorator, as reported to the callback
+    ///
+    /// Unlike `OtaUpdate::written`, this does not include the bytes written before the update was resumed.
+    pub fn progress(&self) -> u64 {
+        self.written
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.total.saturating_sub(self.written)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    fn on_written(&mut self, size: usize) {
+        self.written += size as u64;
+
+        let percent = (self.written.min(self.total) * 100)
+            .checked_div(self.total)
+            .unwrap_or(100) as u8;
+
+        if percent >= self.reported.saturating_add(self.granularity)
+            || percent == 100 && self.reported < 100
+        {
+            self.reported = percent;
+
+            (self.callback)(ProgressEvent::Writing, self.written, self.total);
+        }
+    }
+
+    fn on_finished(mut self, event: ProgressEvent) -> U {
+        (self.callback)(event, self.written, self.total);
+
+        self.update
+    }
+}
+
+impl<U, F> ErrorType for ProgressOta<U, F>
+where
+    U: ErrorType,
+{
+    type Error = U::Error;
+}
+
+impl<U, F> Write for ProgressOta<U, F>
+where
+    U: Write,
+    F: FnMut(ProgressEvent, u64, u64),
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let size = self.update.write(buf)?;
+
+        self.on_written(size);
+
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.update.flush()
+    }
+}
+
+impl<U, F> OtaUpdate for ProgressOta<U, F>
+where
+    U: OtaUpdate,
+    F: FnMut(ProgressEvent, u64, u64),
+{
+    type OtaUpdateFinished = U::OtaUpdateFinished;
+
+    fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+        self.on_finished(ProgressEvent::Completed).finish()
+    }
+
+    fn complete(self) -> Result<(), Self::Error> {
+        self.on_finished(ProgressEvent::Completed).complete()
+    }
+
+    fn abort(self) -> Result<(), Self::Error> {
+        self.on_finished(ProgressEvent::Aborted).abort()
+    }
+
+    fn written(&self) -> Option<u64> {
+        self.update.written()
+    }
+}
+
+pub use crate::utils::digest::Digest;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VerifyError<E> {
+    Mismatch,
+    Update(E),
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for VerifyError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<E: core::fmt::Debug> std::error::Error for VerifyError<E> {}
+
+impl<E> crate::io::Error for VerifyError<E>
+where
+    E: crate::io::Error,
+{
+    fn kind(&self) -> crate::io::ErrorKind {
+        match self {
+            Self::Mismatch => crate::io::ErrorKind::InvalidData,
+            Self::Update(e) => e.kind(),
+        }
+    }
+}
+
+/// An `OtaUpdate` decorator hashing all written data with the supplied `Digest`.
+///
+/// The update is only completed if the digest of the written data matches the expected one,
+/// and is aborted otherwise.
+pub struct VerifyingOtaUpdate<U, D>(HashingWrite<U, D>);
+
+impl<U, D> VerifyingOtaUpdate<U, D>
+where
+    U: OtaUpdate,
+    D: Digest<Output = [u8; 32]>,
+{
+    pub const fn new(update: U, digest: D) -> Self {
+        Self(HashingWrite::new(update, digest))
+    }
+
+    pub fn finish(
+        self,
+        expected: &[u8; 32],
+    ) -> Result<U::OtaUpdateFinished, VerifyError<U::Error>> {
+        self.verify(expected)?.finish().map_err(VerifyError::Update)
+    }
+
+    pub fn complete(self, expected: &[u8; 32]) -> Result<(), VerifyError<U::Error>> {
+        self.verify(expected)?
+            .complete()
+            .map_err(VerifyError::Update)
+    }
+
+    pub fn abort(self) -> Result<(), U::Error> {
+        self.0.into_inner().abort()
+    }
+
+    fn verify(self, expected: &[u8; 32]) -> Result<U, VerifyError<U::Error>> {
+        let (update, digest) = self.0.finalize();
+
+        if digest == *expected {
+            Ok(update)
+        } else {
+            update.abort().map_err(VerifyError::Update)?;
+
+            Err(VerifyError::Mismatch)
+        }
+    }
+}
+
+impl<U, D> ErrorType for VerifyingOtaUpdate<U, D>
+where
+    U: ErrorType,
+{
+    type Error = U::Error;
+}
+
+impl<U, D> Write for VerifyingOtaUpdate<U, D>
+where
+    U: Write,
+    D: Digest<Output = [u8; 32]>,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
 #[cfg(feature = "nightly")]
 pub mod asynch {
     use crate::io::asynch::{ErrorType, Read, Write};
     use crate::utils::io::asynch::*;
 
-    pub use super::{FirmwareInfo, FirmwareInfoLoader, LoadResult, Slot, SlotState};
+    pub use super::{
+        Digest, FirmwareInfo, FirmwareInfoLoader, LoadResult, ProgressEvent, ProgressOta,
+        ResumedUpdate, Slot, SlotState, VerifyError,
+    };
 
     pub trait Ota: ErrorType {
         type Update<'a>: OtaUpdate<Error = Self::Error>
@@ -193,15 +514,36 @@ pub mod asynch {
 
         async fn get_update_slot(&self) -> Result<Slot, Self::Error>;
 
+        async fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, Self::Error> {
+            Ok(super::fill_slots(
+                slots,
+                [
+                    self.get_boot_slot().await?,
+                    self.get_running_slot().await?,
+                    self.get_update_slot().await?,
+                ],
+            ))
+        }
+
         async fn is_factory_reset_supported(&self) -> Result<bool, Self::Error>;
 
         async fn factory_reset(&mut self) -> Result<(), Self::Error>;
 
         async fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error>;
 
+        async fn resume_update(
+            &mut self,
+        ) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+            Ok(None)
+        }
+
         async fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error>;
 
         async fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error;
+
+        async fn is_running_slot_pending_verification(&self) -> Result<bool, Self::Error> {
+            Ok(self.get_running_slot().await?.state == SlotState::Unverified)
+        }
     }
 
     impl<O> Ota for &mut O
@@ -222,6 +564,10 @@ pub mod asynch {
             (**self).get_update_slot().await
         }
 
+        async fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, Self::Error> {
+            (**self).get_slots(slots).await
+        }
+
         async fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
             (**self).is_factory_reset_supported().await
         }
@@ -234,6 +580,12 @@ pub mod asynch {
             (*self).initiate_update().await
         }
 
+        async fn resume_update(
+            &mut self,
+        ) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+            (*self).resume_update().await
+        }
+
         async fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
             (*self).mark_running_slot_valid().await
         }
@@ -241,6 +593,10 @@ pub mod asynch {
         async fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
             (*self).mark_running_slot_invalid_and_reboot().await
         }
+
+        async fn is_running_slot_pending_verification(&self) -> Result<bool, Self::Error> {
+            (**self).is_running_slot_pending_verification().await
+        }
     }
 
     pub trait OtaUpdate: Write {
@@ -252,6 +608,10 @@ pub mod asynch {
 
         async fn abort(self) -> Result<(), Self::Error>;
 
+        fn written(&self) -> Option<u64> {
+            None
+        }
+
         async fn update<R>(
             self,
             read: R,
@@ -265,4 +625,700 @@ pub mod asynch {
     pub trait OtaUpdateFinished: ErrorType {
         async fn activate(self) -> Result<(), Self::Error>;
     }
+
+    impl<U, F> Write for ProgressOta<U, F>
+    where
+        U: Write,
+        F: FnMut(ProgressEvent, u64, u64),
+    {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let size = self.update.write(buf).await?;
+
+            self.on_written(size);
+
+            Ok(size)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.update.flush().await
+        }
+    }
+
+    impl<U, F> OtaUpdate for ProgressOta<U, F>
+    where
+        U: OtaUpdate,
+        F: FnMut(ProgressEvent, u64, u64),
+    {
+        type OtaUpdateFinished = U::OtaUpdateFinished;
+
+        async fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+            self.on_finished(ProgressEvent::Completed).finish().await
+        }
+
+        async fn complete(self) -> Result<(), Self::Error> {
+            self.on_finished(ProgressEvent::Completed).complete().await
+        }
+
+        async fn abort(self) -> Result<(), Self::Error> {
+            self.on_finished(ProgressEvent::Aborted).abort().await
+        }
+
+        fn written(&self) -> Option<u64> {
+            self.update.written()
+        }
+
+        async fn update<R>(
+            mut self,
+            read: R,
+            progress: impl Fn(u64, u64),
+        ) -> Result<(), CopyError<R::Error, Self::Error>>
+        where
+            R: Read,
+            Self: Sized,
+        {
+            let mut buf = [0_u8; 64];
+
+            match copy_with_progress(read, &mut self, &mut buf, progress).await {
+                Ok(_) => self.complete().await.map_err(CopyError::Write),
+                Err(e) => {
+                    self.abort().await.map_err(CopyError::Write)?;
+
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    /// Same as `ota::VerifyingOtaUpdate`, for the async `OtaUpdate`
+    pub struct VerifyingOtaUpdate<U, D>(HashingWrite<U, D>);
+
+    impl<U, D> VerifyingOtaUpdate<U, D>
+    where
+        U: OtaUpdate,
+        D: Digest<Output = [u8; 32]>,
+    {
+        pub const fn new(update: U, digest: D) -> Self {
+            Self(HashingWrite::new(update, digest))
+        }
+
+        pub async fn finish(
+            self,
+            expected: &[u8; 32],
+        ) -> Result<U::OtaUpdateFinished, VerifyError<U::Error>> {
+            self.verify(expected)
+                .await?
+                .finish()
+                .await
+                .map_err(VerifyError::Update)
+        }
+
+        pub async fn complete(self, expected: &[u8; 32]) -> Result<(), VerifyError<U::Error>> {
+            self.verify(expected)
+                .await?
+                .complete()
+                .await
+                .map_err(VerifyError::Update)
+        }
+
+        pub async fn abort(self) -> Result<(), U::Error> {
+            self.0.into_inner().abort().await
+        }
+
+        async fn verify(self, expected: &[u8; 32]) -> Result<U, VerifyError<U::Error>> {
+            let (update, digest) = self.0.finalize();
+
+            if digest == *expected {
+                Ok(update)
+            } else {
+                update.abort().await.map_err(VerifyError::Update)?;
+
+                Err(VerifyError::Mismatch)
+            }
+        }
+    }
+
+    impl<U, D> ErrorType for VerifyingOtaUpdate<U, D>
+    where
+        U: ErrorType,
+    {
+        type Error = U::Error;
+    }
+
+    impl<U, D> Write for VerifyingOtaUpdate<U, D>
+    where
+        U: Write,
+        D: Digest<Output = [u8; 32]>,
+    {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.0.write(buf).await
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.0.flush().await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::TryInto;
+
+    use crate::io::{Error, ErrorKind};
+    use crate::test_ota::{padded_crc32, MockOta};
+
+    use super::*;
+
+    type Events = Vec<(ProgressEvent, u64, u64)>;
+
+    fn write_chunks<W: Write>(mut write: W, chunks: usize, chunk_size: usize) -> W {
+        for _ in 0..chunks {
+            write.write_all(&vec![0xa5; chunk_size]).unwrap();
+        }
+
+        write
+    }
+
+    #[test]
+    fn progress_cadence() {
+        let mut ota = MockOta::new();
+        let mut events = Events::new();
+
+        let update = ProgressOta::new(
+            ota.initiate_update().unwrap(),
+            100,
+            25,
+            |event, written, total| events.push((event, written, total)),
+        );
+
+        write_chunks(update, 10, 10).complete().unwrap();
+
+        assert_eq!(
+            events,
+            [
+                (ProgressEvent::Writing, 30, 100),
+                (ProgressEvent::Writing, 60, 100),
+                (ProgressEvent::Writing, 90, 100),
+                (ProgressEvent::Writing, 100, 100),
+                (ProgressEvent::Completed, 100, 100),
+            ]
+        );
+        assert!(ota.activated);
+    }
+
+    #[test]
+    fn progress_aborted() {
+        let mut ota = MockOta::new();
+        let mut events = Events::new();
+
+        let update = ProgressOta::new(
+            ota.initiate_update().unwrap(),
+            100,
+            50,
+            |event, written, total| events.push((event, written, total)),
+        );
+
+        write_chunks(update, 3, 20).abort().unwrap();
+
+        assert_eq!(
+            events,
+            [
+                (ProgressEvent::Writing, 60, 100),
+                (ProgressEvent::Aborted, 60, 100),
+            ]
+        );
+        assert_eq!(ota.aborts, 1);
+        assert!(ota.completed.is_none());
+    }
+
+    #[test]
+    fn progress_finished() {
+        let mut ota = MockOta::new();
+        let mut events = Events::new();
+
+        let update = ProgressOta::new(
+            ota.initiate_update().unwrap(),
+            10,
+            1,
+            |event, written, total| events.push((event, written, total)),
+        );
+
+        write_chunks(update, 1, 10).finish().unwrap();
+
+        assert_eq!(
+            events,
+            [
+                (ProgressEvent::Writing, 10, 10),
+                (ProgressEvent::Completed, 10, 10),
+            ]
+        );
+        // Finished, but not activated
+        assert!(ota.completed.is_some());
+        assert!(!ota.activated);
+    }
+
+    #[test]
+    fn progress_beyond_total() {
+        let mut ota = MockOta::new();
+        let mut events = Events::new();
+
+        // The granularity is clamped to 1..=100
+        let update = ProgressOta::new(
+            ota.initiate_update().unwrap(),
+            20,
+            0,
+            |event, written, total| events.push((event, written, total)),
+        );
+
+        let update = write_chunks(update, 3, 10);
+
+        assert_eq!(update.progress(), 30);
+        assert_eq!(update.remaining(), 0);
+
+        update.complete().unwrap();
+
+        assert_eq!(
+            events,
+            [
+                (ProgressEvent::Writing, 10, 20),
+                (ProgressEvent::Writing, 20, 20),
+                (ProgressEvent::Completed, 30, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn progress_unknown_total() {
+        let mut ota = MockOta::new();
+        let mut events = Events::new();
+
+        let update = ProgressOta::new(
+            ota.initiate_update().unwrap(),
+            0,
+            10,
+            |event, written, total| events.push((event, written, total)),
+        );
+
+        write_chunks(update, 2, 5).complete().unwrap();
+
+        assert_eq!(
+            events,
+            [
+                (ProgressEvent::Writing, 5, 0),
+                (ProgressEvent::Completed, 10, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn progress_short_writes() {
+        let mut ota = MockOta {
+            max_write: Some(7),
+            ..Default::default()
+        };
+
+        let mut update = ProgressOta::new(ota.initiate_update().unwrap(), 100, 10, |_, _, _| ());
+
+        assert_eq!(update.write(&[0; 10]).unwrap(), 7);
+        assert_eq!(update.progress(), 7);
+        assert_eq!(update.remaining(), 93);
+        assert_eq!(update.total(), 100);
+    }
+
+    #[test]
+    fn progress_resumed() {
+        let mut ota = MockOta {
+            sector: 16,
+            ..Default::default()
+        };
+
+        // Interrupted after 40 bytes, of which 32 are in fully flashed sectors
+        write_chunks(ota.initiate_update().unwrap(), 4, 10);
+
+        let resumed = ota.resume_update().unwrap().unwrap();
+        assert_eq!(resumed.offset, 32);
+
+        let mut update = ProgressOta::new(resumed.update, 100 - resumed.offset, 10, |_, _, _| ());
+
+        update.write_all(&[0; 20]).unwrap();
+
+        assert_eq!(update.progress(), 20);
+        assert_eq!(update.remaining(), 48);
+        assert_eq!(update.written(), Some(52));
+    }
+
+    fn expected_crc32(crc: u32) -> [u8; 32] {
+        let mut expected = [0; 32];
+        expected[..4].copy_from_slice(&crc.to_be_bytes());
+
+        expected
+    }
+
+    #[test]
+    fn verified_update() {
+        let mut ota = MockOta {
+            max_write: Some(4),
+            ..Default::default()
+        };
+
+        let mut update = VerifyingOtaUpdate::new(ota.initiate_update().unwrap(), padded_crc32());
+
+        update.write_all(b"123456789").unwrap();
+        update.complete(&expected_crc32(0xcbf43926)).unwrap();
+
+        assert_eq!(ota.completed.as_deref(), Some(&b"123456789"[..]));
+        assert!(ota.activated);
+        assert_eq!(ota.aborts, 0);
+    }
+
+    #[test]
+    fn verified_update_corrupted() {
+        let mut ota = MockOta::new();
+
+        let mut update = VerifyingOtaUpdate::new(ota.initiate_update().unwrap(), padded_crc32());
+
+        update.write_all(b"123456780").unwrap();
+
+        assert!(matches!(
+            update.complete(&expected_crc32(0xcbf43926)),
+            Err(VerifyError::Mismatch)
+        ));
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn verified_update_finish_corrupted() {
+        let mut ota = MockOta::new();
+
+        let mut update = VerifyingOtaUpdate::new(ota.initiate_update().unwrap(), padded_crc32());
+
+        update.write_all(b"12345678").unwrap();
+
+        assert!(matches!(
+            update.finish(&expected_crc32(0xcbf43926)),
+            Err(VerifyError::Mismatch)
+        ));
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn verified_update_write_failure() {
+        let mut ota = MockOta {
+            fail_at: Some(4),
+            max_write: Some(4),
+            ..Default::default()
+        };
+
+        let mut update = VerifyingOtaUpdate::new(ota.initiate_update().unwrap(), padded_crc32());
+
+        assert_eq!(update.write_all(b"123456789"), Err(ErrorKind::Other));
+
+        update.abort().unwrap();
+
+        assert_eq!(ota.aborts, 1);
+        assert_eq!(
+            VerifyError::Update(ErrorKind::Other).kind(),
+            ErrorKind::Other
+        );
+        assert_eq!(
+            crate::io::Error::kind(&VerifyError::<ErrorKind>::Mismatch),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn verified_update_sha256() {
+        const ABC: [u8; 32] = [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+            0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+            0xf2, 0x00, 0x15, 0xad,
+        ];
+
+        let mut ota = MockOta::new();
+
+        let mut update = VerifyingOtaUpdate::new(
+            ota.initiate_update().unwrap(),
+            crate::utils::digest::Sha256::new(),
+        );
+
+        update.write_all(b"ab").unwrap();
+        update.write_all(b"c").unwrap();
+        update.complete(&ABC).unwrap();
+
+        assert_eq!(ota.completed.as_deref(), Some(&b"abc"[..]));
+
+        let mut update = VerifyingOtaUpdate::new(
+            ota.initiate_update().unwrap(),
+            crate::utils::digest::Sha256::new(),
+        );
+
+        update.write_all(b"abC").unwrap();
+
+        assert!(matches!(update.complete(&ABC), Err(VerifyError::Mismatch)));
+        assert_eq!(ota.aborts, 1);
+    }
+
+    /// A dual-bank device, whose bootloader boots an activated slot pending verification,
+    /// and rolls back if the application reboots without marking it as valid
+    struct DualSlotOta {
+        states: [SlotState; 2],
+        running: usize,
+        boot: usize,
+    }
+
+    impl DualSlotOta {
+        fn new() -> Self {
+            Self {
+                states: [SlotState::Valid, SlotState::Unknown],
+                running: 0,
+                boot: 0,
+            }
+        }
+
+        fn slot(&self, index: usize) -> Slot {
+            Slot {
+                label: if index == 0 { "ota_0" } else { "ota_1" }
+                    .try_into()
+                    .unwrap(),
+                state: self.states[index],
+                firmware: None,
+                running: index == self.running,
+                bootable: self.states[index] != SlotState::Invalid,
+            }
+        }
+
+        fn reboot(&mut self) {
+            if self.states[self.running] == SlotState::Unverified && self.boot == self.running {
+                self.states[self.running] = SlotState::Invalid;
+                self.boot = 1 - self.running;
+            }
+
+            self.running = self.boot;
+        }
+    }
+
+    impl ErrorType for DualSlotOta {
+        type Error = ErrorKind;
+    }
+
+    impl Ota for DualSlotOta {
+        type Update<'a>
+            = DualSlotUpdate<'a>
+        where
+            Self: 'a;
+
+        fn get_boot_slot(&self) -> Result<Slot, Self::Error> {
+            Ok(self.slot(self.boot))
+        }
+
+        fn get_running_slot(&self) -> Result<Slot, Self::Error> {
+            Ok(self.slot(self.running))
+        }
+
+        fn get_update_slot(&self) -> Result<Slot, Self::Error> {
+            Ok(self.slot(1 - self.running))
+        }
+
+        fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        fn factory_reset(&mut self) -> Result<(), Self::Error> {
+            Err(ErrorKind::Unsupported)
+        }
+
+        fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error> {
+            self.states[1 - self.running] = SlotState::Unknown;
+
+            Ok(DualSlotUpdate(self))
+        }
+
+        fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
+            self.states[self.running] = SlotState::Valid;
+
+            Ok(())
+        }
+
+        fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
+            self.states[self.running] = SlotState::Invalid;
+            self.boot = 1 - self.running;
+            self.reboot();
+
+            // A real implementation does not return
+            ErrorKind::Interrupted
+        }
+    }
+
+    struct DualSlotUpdate<'a>(&'a mut DualSlotOta);
+
+    impl ErrorType for DualSlotUpdate<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Write for DualSlotUpdate<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl<'a> OtaUpdate for DualSlotUpdate<'a> {
+        type OtaUpdateFinished = DualSlotUpdate<'a>;
+
+        fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+            Ok(self)
+        }
+
+        fn complete(self) -> Result<(), Self::Error> {
+            self.finish()?.activate()
+        }
+
+        fn abort(self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl OtaUpdateFinished for DualSlotUpdate<'_> {
+        fn activate(self) -> Result<(), Self::Error> {
+            let update = 1 - self.0.running;
+
+            self.0.states[update] = SlotState::Unverified;
+            self.0.boot = update;
+
+            Ok(())
+        }
+    }
+
+    fn update(ota: &mut DualSlotOta) {
+        let mut update = ota.initiate_update().unwrap();
+
+        update.write_all(b"firmware").unwrap();
+        update.complete().unwrap();
+
+        assert_eq!(ota.get_boot_slot().unwrap().label, "ota_1");
+        assert_eq!(ota.get_boot_slot().unwrap().state, SlotState::Unverified);
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_0");
+
+        ota.reboot();
+
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_1");
+        assert!(ota.is_running_slot_pending_verification().unwrap());
+    }
+
+    #[test]
+    fn slot_state_verified() {
+        let mut ota = DualSlotOta::new();
+
+        assert!(!ota.is_running_slot_pending_verification().unwrap());
+
+        update(&mut ota);
+
+        ota.mark_running_slot_valid().unwrap();
+
+        assert_eq!(ota.get_running_slot().unwrap().state, SlotState::Valid);
+        assert!(!ota.is_running_slot_pending_verification().unwrap());
+
+        ota.reboot();
+
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_1");
+        assert_eq!(ota.get_update_slot().unwrap().label, "ota_0");
+    }
+
+    #[test]
+    fn slot_state_rolled_back() {
+        let mut ota = DualSlotOta::new();
+
+        update(&mut ota);
+
+        assert_eq!(
+            ota.mark_running_slot_invalid_and_reboot(),
+            ErrorKind::Interrupted
+        );
+
+        let running = ota.get_running_slot().unwrap();
+        let update = ota.get_update_slot().unwrap();
+
+        assert_eq!(running.label, "ota_0");
+        assert_eq!(running.state, SlotState::Valid);
+        assert_eq!(update.label, "ota_1");
+        assert_eq!(update.state, SlotState::Invalid);
+        assert!(!update.bootable);
+    }
+
+    #[test]
+    fn slot_state_rolled_back_on_reboot() {
+        let mut ota = DualSlotOta::new();
+
+        update(&mut ota);
+
+        // Rebooting without marking the slot as valid rolls back
+        ota.reboot();
+
+        assert_eq!(ota.get_running_slot().unwrap().label, "ota_0");
+        assert_eq!(ota.get_update_slot().unwrap().state, SlotState::Invalid);
+        assert!(!ota.is_running_slot_pending_verification().unwrap());
+    }
+
+    fn get_slots_of<O: Ota>(ota: O, slots: &mut [Slot]) -> usize {
+        ota.get_slots(slots).unwrap()
+    }
+
+    #[test]
+    fn get_slots() {
+        let mut ota = DualSlotOta::new();
+        let mut slots: [Slot; 4] = Default::default();
+
+        // The boot slot is also the running one, and is reported only once
+        assert_eq!(ota.get_slots(&mut slots), Ok(2));
+        assert_eq!(slots[0].label, "ota_0");
+        assert!(slots[0].running);
+        assert_eq!(slots[1].label, "ota_1");
+        assert!(!slots[1].running);
+
+        update(&mut ota);
+
+        assert_eq!(get_slots_of(&mut ota, &mut slots), 2);
+        assert_eq!(slots[0].label, "ota_1");
+        assert_eq!(slots[0].state, SlotState::Unverified);
+        assert_eq!(slots[1].label, "ota_0");
+        assert_eq!(slots[1].state, SlotState::Valid);
+    }
+
+    #[test]
+    fn get_slots_truncated() {
+        let ota = DualSlotOta::new();
+        let mut slots: [Slot; 1] = Default::default();
+
+        assert_eq!(ota.get_slots(&mut slots), Ok(1));
+        assert_eq!(slots[0].label, "ota_0");
+
+        assert_eq!(ota.get_slots(&mut []), Ok(0));
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn slot_serde() {
+        let slot = DualSlotOta::new().get_running_slot().unwrap();
+        let json = crate::test_json::to_vec(&slot);
+
+        assert_eq!(
+            json,
+            br#"{"label":"ota_0","state":"Valid","firmware":null,"running":true,"bootable":true}"#
+        );
+
+        let slot: Slot = crate::test_json::from_slice(
+            br#"{"label":"ota_1","state":"Unverified","firmware":null}"#,
+        )
+        .unwrap();
+
+        assert_eq!(slot.label, "ota_1");
+        assert_eq!(slot.state, SlotState::Unverified);
+        assert!(!slot.running);
+        assert!(!slot.bootable);
+    }
 }
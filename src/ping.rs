@@ -1,9 +1,11 @@
 use core::fmt::Debug;
+use core::ops::ControlFlow;
 use core::time::Duration;
 
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::dns::Resolve;
 use crate::ipv4;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -58,12 +60,182 @@ pub struct Summary {
     pub time: Duration,
 }
 
+/// Round-trip statistics of a ping session, computed from the individual replies.
+///
+/// Up to `N` individual round-trip times are kept in `rtts`; with the default of `N = 0` none are kept.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DetailedSummary<const N: usize = 0> {
+    pub transmitted: u32,
+    pub received: u32,
+    pub time: Duration,
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    /// The mean difference between the round-trip times of consecutive replies
+    pub jitter: Duration,
+    /// The (population) standard deviation of the round-trip times, with a microsecond precision
+    pub stddev: Duration,
+    pub rtts: heapless::Vec<Duration, N>,
+    #[cfg_attr(feature = "use_serde", serde(skip))]
+    sum: Duration,
+    #[cfg_attr(feature = "use_serde", serde(skip))]
+    jitter_sum: Duration,
+    #[cfg_attr(feature = "use_serde", serde(skip))]
+    squares_sum: u128,
+    #[cfg_attr(feature = "use_serde", serde(skip))]
+    last: Option<Duration>,
+}
+
+impl<const N: usize> DetailedSummary<N> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn update(&mut self, reply: &Reply) {
+        self.transmitted += 1;
+
+        if let Reply::Success(info) = reply {
+            let rtt = info.elapsed_time;
+
+            self.received += 1;
+            self.sum += rtt;
+
+            if self.received == 1 || rtt < self.min {
+                self.min = rtt;
+            }
+
+            if rtt > self.max {
+                self.max = rtt;
+            }
+
+            self.avg = self.sum / self.received;
+
+            let micros = rtt.as_micros();
+            self.squares_sum += micros * micros;
+
+            let avg = self.sum.as_micros() / self.received as u128;
+            let variance = (self.squares_sum / self.received as u128).saturating_sub(avg * avg);
+
+            self.stddev = Duration::from_micros(isqrt(variance) as u64);
+
+            if let Some(last) = self.last {
+                self.jitter_sum += if rtt > last { rtt - last } else { last - rtt };
+                self.jitter = self.jitter_sum / (self.received - 1);
+            }
+
+            self.last = Some(rtt);
+
+            let _ = self.rtts.push(rtt);
+        }
+    }
+}
+
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut x = value;
+    let mut y = (x + 1) / 2;
+
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+
+    x
+}
+
+/// Same as `Ping::ping`, but computes detailed round-trip statistics from the individual replies
+pub fn ping_detailed<P, const N: usize>(
+    ping: &mut P,
+    ip: ipv4::Ipv4Addr,
+    conf: &Configuration,
+) -> Result<DetailedSummary<N>, P::Error>
+where
+    P: Ping,
+{
+    let mut detailed = DetailedSummary::new();
+
+    let summary = ping.ping_details(ip, conf, |_, reply| {
+        detailed.update(reply);
+
+        ControlFlow::Continue(())
+    })?;
+
+    detailed.time = summary.time;
+
+    Ok(detailed)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Event {
+    Reply(Reply),
+    /// Always the last event of a ping session
+    Summary(Summary),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PingHostError<R, P> {
+    Resolve(R),
+    /// The host does not exist
+    NotFound,
+    /// The host resolved to an IPv6 address, which cannot be pinged
+    Ipv6NotSupported,
+    Ping(P),
+}
+
+impl<R: Debug, P: Debug> core::fmt::Display for PingHostError<R, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Debug, P: Debug> std::error::Error for PingHostError<R, P> {}
+
+impl<R, P> PingHostError<R, P> {
+    fn resolved(ip: Option<ipv4::IpAddr>) -> Result<ipv4::Ipv4Addr, Self> {
+        match ip {
+            Some(ipv4::IpAddr::V4(ip)) => Ok(ip),
+            Some(ipv4::IpAddr::V6(_)) => Err(Self::Ipv6NotSupported),
+            None => Err(Self::NotFound),
+        }
+    }
+}
+
+/// Resolves `host` with `resolver` and then pings the resolved address
+pub fn ping_host<P, R>(
+    ping: &mut P,
+    resolver: &R,
+    host: &str,
+    conf: &Configuration,
+) -> Result<Summary, PingHostError<R::Error, P::Error>>
+where
+    P: Ping,
+    R: Resolve,
+{
+    let ip = PingHostError::resolved(resolver.resolve(host).map_err(PingHostError::Resolve)?)?;
+
+    ping.ping(ip, conf).map_err(PingHostError::Ping)
+}
+
 pub trait Ping {
     type Error: Debug;
 
     fn ping(&mut self, ip: ipv4::Ipv4Addr, conf: &Configuration) -> Result<Summary, Self::Error>;
 
-    fn ping_details<F: FnMut(&Summary, &Reply) + Send>(
+    /// Same as `ping`, but calls `reply_callback` with the summary so far after each echo reply
+    /// or timeout. The session is aborted early if the callback returns `ControlFlow::Break`.
+    ///
+    /// There is no default implementation on top of `ping`, as the trait cannot wait
+    /// `Configuration::interval` between the echoes.
+    fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
         &mut self,
         ip: ipv4::Ipv4Addr,
         conf: &Configuration,
@@ -81,7 +253,7 @@ where
         (*self).ping(ip, conf)
     }
 
-    fn ping_details<F: FnMut(&Summary, &Reply) + Send>(
+    fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
         &mut self,
         ip: ipv4::Ipv4Addr,
         conf: &Configuration,
@@ -93,10 +265,56 @@ where
 #[cfg(feature = "nightly")]
 pub mod asynch {
     use core::fmt::Debug;
+    use core::ops::ControlFlow;
 
     use crate::ipv4;
 
-    pub use super::{Configuration, Reply, Summary};
+    use crate::dns::asynch::Resolve;
+
+    pub use super::{Configuration, DetailedSummary, Event, PingHostError, Reply, Summary};
+
+    pub async fn ping_detailed<P, const N: usize>(
+        ping: &mut P,
+        ip: ipv4::Ipv4Addr,
+        conf: &Configuration,
+    ) -> Result<DetailedSummary<N>, P::Error>
+    where
+        P: Ping,
+    {
+        let mut detailed = DetailedSummary::new();
+
+        let summary = ping
+            .ping_details(ip, conf, |_, reply| {
+                detailed.update(reply);
+
+                ControlFlow::Continue(())
+            })
+            .await?;
+
+        detailed.time = summary.time;
+
+        Ok(detailed)
+    }
+
+    pub async fn ping_host<P, R>(
+        ping: &mut P,
+        resolver: &R,
+        host: &str,
+        conf: &Configuration,
+    ) -> Result<Summary, PingHostError<R::Error, P::Error>>
+    where
+        P: Ping,
+        R: Resolve,
+    {
+        let ip = PingHostError::resolved(
+            resolver
+                .resolve(host)
+                .await
+                .map_err(PingHostError::Resolve)?,
+        )?;
+
+        ping.ping(ip, conf).await.map_err(PingHostError::Ping)
+    }
 
     pub trait Ping {
         type Error: Debug;
@@ -107,7 +325,7 @@ pub mod asynch {
             conf: &Configuration,
         ) -> Result<Summary, Self::Error>;
 
-        async fn ping_details<F: FnMut(&Summary, &Reply) + Send>(
+        async fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
             &mut self,
             ip: ipv4::Ipv4Addr,
             conf: &Configuration,
@@ -129,7 +347,7 @@ pub mod asynch {
             (*self).ping(ip, conf).await
         }
 
-        async fn ping_details<F: FnMut(&Summary, &Reply) + Send>(
+        async fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
             &mut self,
             ip: ipv4::Ipv4Addr,
             conf: &Configuration,
@@ -138,4 +356,287 @@ pub mod asynch {
             (*self).ping_details(ip, conf, reply_callback).await
         }
     }
+    /// The replies of a ping session, as they arrive.
+    /// Dropping the session stops it.
+    pub trait Replies {
+        type Error: Debug;
+
+        /// Returns the next reply, then the summary of the session as the last event,
+        /// then `None`
+        async fn next(&mut self) -> Option<Result<Event, Self::Error>>;
+    }
+
+    impl<R> Replies for &mut R
+    where
+        R: Replies,
+    {
+        type Error = R::Error;
+
+        async fn next(&mut self) -> Option<Result<Event, Self::Error>> {
+            (*self).next().await
+        }
+    }
+
+    pub trait PingReplies {
+        type Error: Debug;
+
+        type Replies<'a>: Replies<Error = Self::Error>
+        where
+            Self: 'a;
+
+        async fn ping_replies(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            conf: &Configuration,
+        ) -> Result<Self::Replies<'_>, Self::Error>;
+    }
+
+    impl<P> PingReplies for &mut P
+    where
+        P: PingReplies,
+    {
+        type Error = P::Error;
+
+        type Replies<'a> = P::Replies<'a> where Self: 'a;
+
+        async fn ping_replies(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            conf: &Configuration,
+        ) -> Result<Self::Replies<'_>, Self::Error> {
+            (*self).ping_replies(ip, conf).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockPing(&'static [Option<u64>]);
+
+    impl Ping for MockPing {
+        type Error = ();
+
+        fn ping(&mut self, ip: ipv4::Ipv4Addr, conf: &Configuration) -> Result<Summary, ()> {
+            self.ping_details(ip, conf, |_, _| ControlFlow::Continue(()))
+        }
+
+        fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            _conf: &Configuration,
+            mut reply_callback: F,
+        ) -> Result<Summary, ()> {
+            let mut summary = Summary::default();
+
+            for (seqno, rtt) in self.0.iter().enumerate() {
+                let reply = match rtt {
+                    Some(rtt) => Reply::Success(Info {
+                        addr: ip,
+                        seqno: seqno as u32,
+                        ttl: 64,
+                        elapsed_time: Duration::from_millis(*rtt),
+                        recv_len: 64,
+                    }),
+                    None => Reply::Timeout,
+                };
+
+                summary.transmitted += 1;
+
+                if let Reply::Success(info) = &reply {
+                    summary.received += 1;
+                    summary.time += info.elapsed_time;
+                }
+
+                if reply_callback(&summary, &reply).is_break() {
+                    break;
+                }
+            }
+
+            Ok(summary)
+        }
+    }
+
+    #[test]
+    fn ping_details_early_abort() {
+        let mut ping = MockPing(&[Some(10), None, Some(20), Some(30), Some(40)]);
+        let mut replies = 0;
+
+        let summary = ping
+            .ping_details(
+                ipv4::Ipv4Addr::LOCALHOST,
+                &Default::default(),
+                |summary, reply| {
+                    replies += 1;
+
+                    assert_eq!(summary.transmitted, replies);
+
+                    if matches!(reply, Reply::Success(info) if info.seqno == 2) {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                },
+            )
+            .unwrap();
+
+        assert_eq!(replies, 3);
+        assert_eq!(
+            summary,
+            Summary {
+                transmitted: 3,
+                received: 2,
+                time: Duration::from_millis(30),
+            }
+        );
+    }
+
+    #[test]
+    fn ping_details_sees_timeouts() {
+        let mut ping = MockPing(&[None, Some(10), None]);
+        let mut timeouts = 0;
+
+        let summary = ping
+            .ping_details(
+                ipv4::Ipv4Addr::LOCALHOST,
+                &Default::default(),
+                |_, reply| {
+                    if *reply == Reply::Timeout {
+                        timeouts += 1;
+                    }
+
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+
+        assert_eq!(timeouts, 2);
+        assert_eq!(summary.transmitted, 3);
+        assert_eq!(summary.received, 1);
+    }
+
+    struct MockResolver;
+
+    impl Resolve for MockResolver {
+        type Error = &'static str;
+
+        fn resolve(&self, host: &str) -> Result<Option<ipv4::IpAddr>, Self::Error> {
+            match host {
+                "gateway.example.com" => {
+                    Ok(Some(ipv4::IpAddr::V4(ipv4::Ipv4Addr::new(192, 168, 1, 1))))
+                }
+                "ipv6.example.com" => Ok(Some(ipv4::IpAddr::V6(ipv4::Ipv6Addr::LOCALHOST))),
+                "unreachable.example.com" => Err("no DNS server"),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn ping_host_resolved() {
+        let mut ping = MockPing(&[Some(10), Some(20)]);
+
+        let summary = ping_host(
+            &mut ping,
+            &MockResolver,
+            "gateway.example.com",
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(summary.transmitted, 2);
+        assert_eq!(summary.received, 2);
+    }
+
+    #[test]
+    fn ping_host_resolution_failures() {
+        let mut ping = MockPing(&[Some(10)]);
+        let conf = Configuration::default();
+
+        assert!(matches!(
+            ping_host(&mut ping, &MockResolver, "nonexistent.example.com", &conf),
+            Err(PingHostError::NotFound)
+        ));
+        assert!(matches!(
+            ping_host(&mut ping, &MockResolver, "ipv6.example.com", &conf),
+            Err(PingHostError::Ipv6NotSupported)
+        ));
+        assert!(matches!(
+            ping_host(&mut ping, &MockResolver, "unreachable.example.com", &conf),
+            Err(PingHostError::Resolve("no DNS server"))
+        ));
+    }
+
+    #[test]
+    fn detailed_statistics() {
+        let mut ping = MockPing(&[Some(10), None, Some(20), Some(30)]);
+
+        let detailed: DetailedSummary<2> =
+            ping_detailed(&mut ping, ipv4::Ipv4Addr::LOCALHOST, &Default::default()).unwrap();
+
+        assert_eq!(detailed.transmitted, 4);
+        assert_eq!(detailed.received, 3);
+        assert_eq!(detailed.time, Duration::from_millis(60));
+        assert_eq!(detailed.min, Duration::from_millis(10));
+        assert_eq!(detailed.avg, Duration::from_millis(20));
+        assert_eq!(detailed.max, Duration::from_millis(30));
+        // Timeouts are skipped: |20 - 10| and |30 - 20|
+        assert_eq!(detailed.jitter, Duration::from_millis(10));
+        // sqrt(((10 - 20)^2 + (20 - 20)^2 + (30 - 20)^2) / 3) ms, truncated to microseconds
+        assert_eq!(detailed.stddev, Duration::from_micros(8164));
+        // Only the first `N` round-trip times are kept
+        assert_eq!(
+            detailed.rtts.as_slice(),
+            &[Duration::from_millis(10), Duration::from_millis(20)]
+        );
+    }
+
+    #[test]
+    fn detailed_statistics_single_reply() {
+        let mut ping = MockPing(&[Some(15)]);
+
+        let detailed: DetailedSummary =
+            ping_detailed(&mut ping, ipv4::Ipv4Addr::LOCALHOST, &Default::default()).unwrap();
+
+        assert_eq!(detailed.min, Duration::from_millis(15));
+        assert_eq!(detailed.avg, Duration::from_millis(15));
+        assert_eq!(detailed.max, Duration::from_millis(15));
+        assert_eq!(detailed.jitter, Duration::ZERO);
+        assert_eq!(detailed.stddev, Duration::ZERO);
+        assert!(detailed.rtts.is_empty());
+    }
+
+    #[test]
+    fn detailed_statistics_all_timeouts() {
+        let mut detailed = DetailedSummary::<4>::new();
+
+        detailed.update(&Reply::Timeout);
+        detailed.update(&Reply::Timeout);
+
+        assert_eq!(detailed.transmitted, 2);
+        assert_eq!(detailed.received, 0);
+        assert_eq!(detailed.min, Duration::ZERO);
+        assert_eq!(detailed.avg, Duration::ZERO);
+        assert_eq!(detailed.max, Duration::ZERO);
+        assert_eq!(detailed.stddev, Duration::ZERO);
+        assert!(detailed.rtts.is_empty());
+    }
+
+    #[test]
+    fn integer_square_root() {
+        for (value, root) in [
+            (0, 0),
+            (1, 1),
+            (3, 1),
+            (4, 2),
+            (15, 3),
+            (16, 4),
+            (66_666_666, 8164),
+        ] {
+            assert_eq!(isqrt(value), root);
+        }
+
+        assert_eq!(isqrt(u64::MAX as u128 * u64::MAX as u128), u64::MAX as u128);
+    }
 }
@@ -0,0 +1,161 @@
+use core::convert::TryInto;
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of NTP servers in a `Configuration`
+pub const MAX_SERVERS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum OperatingMode {
+    /// Syncs on start, and then every `interval`
+    Poll { interval: Duration },
+    /// Syncs once, right after start
+    Immediate,
+}
+
+impl Default for OperatingMode {
+    fn default() -> Self {
+        Self::Poll {
+            interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// The hostnames (or IP addresses) of the NTP servers, in the order they are tried
+    pub servers: heapless::Vec<heapless::String<64>, MAX_SERVERS>,
+    pub operating_mode: OperatingMode,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        let mut servers = heapless::Vec::new();
+        servers.push("pool.ntp.org".try_into().unwrap()).unwrap();
+
+        Self {
+            servers,
+            operating_mode: Default::default(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum SyncStatus {
+    /// The clock was never synced since the service (re)started
+    Reset,
+    InProgress,
+    /// The clock was synced, at `last_synced_at` (the time since the UNIX epoch, as per `sys_time::SystemTime`)
+    Completed {
+        last_synced_at: Duration,
+    },
+}
+
+impl SyncStatus {
+    /// Whether the clock was synced at least once, e.g. for validating certificates
+    pub fn is_synced(&self) -> bool {
+        matches!(self, Self::Completed { .. })
+    }
+}
+
+pub trait Sntp {
+    type Error: Debug;
+
+    type Subscription<'a>
+    where
+        Self: 'a;
+
+    fn get_configuration(&self) -> Result<Configuration, Self::Error>;
+
+    fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error>;
+
+    fn sync_status(&self) -> Result<SyncStatus, Self::Error>;
+
+    /// Calls `callback` with the time synced to on each completed sync, until the subscription is dropped
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(Duration) + Send + 'static;
+}
+
+impl<S> Sntp for &mut S
+where
+    S: Sntp,
+{
+    type Error = S::Error;
+
+    type Subscription<'a> = S::Subscription<'a> where Self: 'a;
+
+    fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+        (**self).get_configuration()
+    }
+
+    fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+        (*self).set_configuration(conf)
+    }
+
+    fn sync_status(&self) -> Result<SyncStatus, Self::Error> {
+        (**self).sync_status()
+    }
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(Duration) + Send + 'static,
+    {
+        (**self).subscribe(callback)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::fmt::Debug;
+    use core::time::Duration;
+
+    pub use super::{Configuration, OperatingMode, SyncStatus, MAX_SERVERS};
+
+    pub trait Sntp {
+        type Error: Debug;
+
+        async fn get_configuration(&self) -> Result<Configuration, Self::Error>;
+
+        async fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error>;
+
+        async fn sync_status(&self) -> Result<SyncStatus, Self::Error>;
+
+        /// Waits until the clock is synced, returning the time it was last synced at
+        ///
+        /// Completes right away if the clock was already synced, so that it can gate e.g. TLS connections.
+        async fn wait_synced(&self) -> Result<Duration, Self::Error>;
+    }
+
+    impl<S> Sntp for &mut S
+    where
+        S: Sntp,
+    {
+        type Error = S::Error;
+
+        async fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+            (**self).get_configuration().await
+        }
+
+        async fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+            (*self).set_configuration(conf).await
+        }
+
+        async fn sync_status(&self) -> Result<SyncStatus, Self::Error> {
+            (**self).sync_status().await
+        }
+
+        async fn wait_synced(&self) -> Result<Duration, Self::Error> {
+            (**self).wait_synced().await
+        }
+    }
+}
@@ -0,0 +1,597 @@
+//! TCP and UDP sockets, for the protocols which do not fit HTTP, MQTT or Websockets (e.g. Modbus TCP, or UDP discovery)
+//!
+//! The errors of the traits are `io::Error`s, whose `kind` tells the failures apart:
+//! - The blocking calls wait indefinitely, unless the implementation was configured with a timeout,
+//!   in which case they fail with `ErrorKind::TimedOut` once it expires. As `io::ErrorKind` has no `WouldBlock`,
+//!   non-blocking sockets are not covered: the `asynch` flavors are the way to not block.
+//! - Failing to connect is `ErrorKind::ConnectionRefused`, or `ErrorKind::NotFound` when the host could not be resolved.
+//! - A connection closed by the peer reads 0 bytes, while a connection reset is `ErrorKind::ConnectionReset`.
+//!
+//! With the `std` feature, `StdStack` implements the traits over `std::net`, for host tests and Linux gateways.
+
+use crate::io::{Error, Read, Write};
+use crate::ipv4::SocketAddr;
+
+pub trait TcpConnect {
+    type Error: Error;
+
+    type Connection<'a>: Read<Error = Self::Error> + Write<Error = Self::Error>
+    where
+        Self: 'a;
+
+    /// Connects to `port` of `host`, a hostname or an IP address
+    fn connect(&self, host: &str, port: u16) -> Result<Self::Connection<'_>, Self::Error>;
+}
+
+impl<T> TcpConnect for &T
+where
+    T: TcpConnect,
+{
+    type Error = T::Error;
+
+    type Connection<'a> = T::Connection<'a> where Self: 'a;
+
+    fn connect(&self, host: &str, port: u16) -> Result<Self::Connection<'_>, Self::Error> {
+        (**self).connect(host, port)
+    }
+}
+
+pub trait TcpListen {
+    type Error: Error;
+
+    type Listener<'a>: TcpAccept<Error = Self::Error>
+    where
+        Self: 'a;
+
+    /// Listens on `addr`; port 0 picks any free port, which `TcpAccept::local_addr` returns
+    fn bind(&self, addr: SocketAddr) -> Result<Self::Listener<'_>, Self::Error>;
+}
+
+impl<T> TcpListen for &T
+where
+    T: TcpListen,
+{
+    type Error = T::Error;
+
+    type Listener<'a> = T::Listener<'a> where Self: 'a;
+
+    fn bind(&self, addr: SocketAddr) -> Result<Self::Listener<'_>, Self::Error> {
+        (**self).bind(addr)
+    }
+}
+
+pub trait TcpAccept {
+    type Error: Error;
+
+    type Connection<'a>: Read<Error = Self::Error> + Write<Error = Self::Error>
+    where
+        Self: 'a;
+
+    fn local_addr(&self) -> Result<SocketAddr, Self::Error>;
+
+    /// Waits for the next incoming connection, returning it together with the address of the peer
+    fn accept(&self) -> Result<(Self::Connection<'_>, SocketAddr), Self::Error>;
+}
+
+impl<T> TcpAccept for &T
+where
+    T: TcpAccept,
+{
+    type Error = T::Error;
+
+    type Connection<'a> = T::Connection<'a> where Self: 'a;
+
+    fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+        (**self).local_addr()
+    }
+
+    fn accept(&self) -> Result<(Self::Connection<'_>, SocketAddr), Self::Error> {
+        (**self).accept()
+    }
+}
+
+pub trait UdpBind {
+    type Error: Error;
+
+    type Socket<'a>: UdpSocket<Error = Self::Error>
+    where
+        Self: 'a;
+
+    /// Binds a socket to `addr`; port 0 picks any free port, which `UdpSocket::local_addr` returns
+    fn bind(&self, addr: SocketAddr) -> Result<Self::Socket<'_>, Self::Error>;
+}
+
+impl<T> UdpBind for &T
+where
+    T: UdpBind,
+{
+    type Error = T::Error;
+
+    type Socket<'a> = T::Socket<'a> where Self: 'a;
+
+    fn bind(&self, addr: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+        (**self).bind(addr)
+    }
+}
+
+pub trait UdpSocket {
+    type Error: Error;
+
+    fn local_addr(&self) -> Result<SocketAddr, Self::Error>;
+
+    /// Sends `buf` as a single datagram to `addr`, returning the number of bytes sent
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, Self::Error>;
+
+    /// Waits for the next datagram, returning its length and the address of its sender
+    ///
+    /// The part of the datagram which does not fit in `buf` is discarded.
+    fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error>;
+}
+
+impl<T> UdpSocket for &T
+where
+    T: UdpSocket,
+{
+    type Error = T::Error;
+
+    fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+        (**self).local_addr()
+    }
+
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, Self::Error> {
+        (**self).send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+        (**self).recv_from(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+pub use stdnet::*;
+
+#[cfg(feature = "std")]
+mod stdnet {
+    use std::io;
+    use std::net::{self, ToSocketAddrs};
+    use std::time::Duration;
+
+    use crate::ipv4::SocketAddr;
+
+    use super::{TcpAccept, TcpConnect, TcpListen, UdpBind, UdpSocket};
+
+    /// Depending on the platform, an expired timeout is reported as `WouldBlock` rather than `TimedOut`
+    /// by `std::net`, while `io::ErrorKind` has no `WouldBlock`
+    fn error(e: io::Error) -> io::Error {
+        if e.kind() == io::ErrorKind::WouldBlock {
+            io::Error::new(io::ErrorKind::TimedOut, e)
+        } else {
+            e
+        }
+    }
+
+    /// The sockets of `std::net`
+    #[derive(Copy, Clone, Debug, Default)]
+    pub struct StdStack {
+        timeout: Option<Duration>,
+    }
+
+    impl StdStack {
+        pub const fn new() -> Self {
+            Self { timeout: None }
+        }
+
+        /// Makes the blocking calls of the sockets fail with `ErrorKind::TimedOut` after `timeout`
+        pub const fn with_timeout(timeout: Duration) -> Self {
+            Self {
+                timeout: Some(timeout),
+            }
+        }
+
+        fn stream(&self, stream: net::TcpStream) -> io::Result<StdTcpConnection> {
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+
+            Ok(StdTcpConnection(stream))
+        }
+    }
+
+    impl TcpConnect for StdStack {
+        type Error = io::Error;
+
+        type Connection<'a> = StdTcpConnection where Self: 'a;
+
+        fn connect(&self, host: &str, port: u16) -> Result<Self::Connection<'_>, Self::Error> {
+            let mut last_error = io::Error::from(io::ErrorKind::NotFound);
+
+            let addrs = (host, port)
+                .to_socket_addrs()
+                .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+
+            for addr in addrs {
+                let stream = if let Some(timeout) = self.timeout {
+                    net::TcpStream::connect_timeout(&addr, timeout)
+                } else {
+                    net::TcpStream::connect(addr)
+                };
+
+                match stream {
+                    Ok(stream) => return self.stream(stream),
+                    Err(e) => last_error = error(e),
+                }
+            }
+
+            Err(last_error)
+        }
+    }
+
+    impl TcpListen for StdStack {
+        type Error = io::Error;
+
+        type Listener<'a> = StdTcpListener where Self: 'a;
+
+        fn bind(&self, addr: SocketAddr) -> Result<Self::Listener<'_>, Self::Error> {
+            Ok(StdTcpListener(net::TcpListener::bind(addr)?, *self))
+        }
+    }
+
+    impl UdpBind for StdStack {
+        type Error = io::Error;
+
+        type Socket<'a> = StdUdpSocket where Self: 'a;
+
+        fn bind(&self, addr: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+            let socket = net::UdpSocket::bind(addr)?;
+
+            socket.set_read_timeout(self.timeout)?;
+            socket.set_write_timeout(self.timeout)?;
+
+            Ok(StdUdpSocket(socket))
+        }
+    }
+
+    pub struct StdTcpConnection(pub net::TcpStream);
+
+    impl crate::io::ErrorType for StdTcpConnection {
+        type Error = io::Error;
+    }
+
+    impl crate::io::Read for StdTcpConnection {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            io::Read::read(&mut self.0, buf).map_err(error)
+        }
+    }
+
+    impl crate::io::Write for StdTcpConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            io::Write::write(&mut self.0, buf).map_err(error)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            io::Write::flush(&mut self.0).map_err(error)
+        }
+    }
+
+    /// A listener of `StdStack`, whose accepted connections get the timeout of the stack
+    ///
+    /// `accept` itself always blocks until a connection comes in.
+    pub struct StdTcpListener(pub net::TcpListener, StdStack);
+
+    impl TcpAccept for StdTcpListener {
+        type Error = io::Error;
+
+        type Connection<'a> = StdTcpConnection where Self: 'a;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+            self.0.local_addr()
+        }
+
+        fn accept(&self) -> Result<(Self::Connection<'_>, SocketAddr), Self::Error> {
+            let (stream, addr) = self.0.accept().map_err(error)?;
+
+            Ok((self.1.stream(stream)?, addr))
+        }
+    }
+
+    pub struct StdUdpSocket(pub net::UdpSocket);
+
+    impl UdpSocket for StdUdpSocket {
+        type Error = io::Error;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+            self.0.local_addr()
+        }
+
+        fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, Self::Error> {
+            self.0.send_to(buf, addr).map_err(error)
+        }
+
+        fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            self.0.recv_from(buf).map_err(error)
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use crate::io::asynch::{Error, Read, Write};
+    use crate::ipv4::SocketAddr;
+
+    pub trait TcpConnect {
+        type Error: Error;
+
+        type Connection<'a>: Read<Error = Self::Error> + Write<Error = Self::Error>
+        where
+            Self: 'a;
+
+        async fn connect(&self, host: &str, port: u16)
+            -> Result<Self::Connection<'_>, Self::Error>;
+    }
+
+    impl<T> TcpConnect for &T
+    where
+        T: TcpConnect,
+    {
+        type Error = T::Error;
+
+        type Connection<'a> = T::Connection<'a> where Self: 'a;
+
+        async fn connect(
+            &self,
+            host: &str,
+            port: u16,
+        ) -> Result<Self::Connection<'_>, Self::Error> {
+            (**self).connect(host, port).await
+        }
+    }
+
+    pub trait TcpListen {
+        type Error: Error;
+
+        type Listener<'a>: TcpAccept<Error = Self::Error>
+        where
+            Self: 'a;
+
+        async fn bind(&self, addr: SocketAddr) -> Result<Self::Listener<'_>, Self::Error>;
+    }
+
+    impl<T> TcpListen for &T
+    where
+        T: TcpListen,
+    {
+        type Error = T::Error;
+
+        type Listener<'a> = T::Listener<'a> where Self: 'a;
+
+        async fn bind(&self, addr: SocketAddr) -> Result<Self::Listener<'_>, Self::Error> {
+            (**self).bind(addr).await
+        }
+    }
+
+    pub trait TcpAccept {
+        type Error: Error;
+
+        type Connection<'a>: Read<Error = Self::Error> + Write<Error = Self::Error>
+        where
+            Self: 'a;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error>;
+
+        async fn accept(&self) -> Result<(Self::Connection<'_>, SocketAddr), Self::Error>;
+    }
+
+    impl<T> TcpAccept for &T
+    where
+        T: TcpAccept,
+    {
+        type Error = T::Error;
+
+        type Connection<'a> = T::Connection<'a> where Self: 'a;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+            (**self).local_addr()
+        }
+
+        async fn accept(&self) -> Result<(Self::Connection<'_>, SocketAddr), Self::Error> {
+            (**self).accept().await
+        }
+    }
+
+    pub trait UdpBind {
+        type Error: Error;
+
+        type Socket<'a>: UdpSocket<Error = Self::Error>
+        where
+            Self: 'a;
+
+        async fn bind(&self, addr: SocketAddr) -> Result<Self::Socket<'_>, Self::Error>;
+    }
+
+    impl<T> UdpBind for &T
+    where
+        T: UdpBind,
+    {
+        type Error = T::Error;
+
+        type Socket<'a> = T::Socket<'a> where Self: 'a;
+
+        async fn bind(&self, addr: SocketAddr) -> Result<Self::Socket<'_>, Self::Error> {
+            (**self).bind(addr).await
+        }
+    }
+
+    pub trait UdpSocket {
+        type Error: Error;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error>;
+
+        async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, Self::Error>;
+
+        async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error>;
+    }
+
+    impl<T> UdpSocket for &T
+    where
+        T: UdpSocket,
+    {
+        type Error = T::Error;
+
+        fn local_addr(&self) -> Result<SocketAddr, Self::Error> {
+            (**self).local_addr()
+        }
+
+        async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> Result<usize, Self::Error> {
+            (**self).send_to(buf, addr).await
+        }
+
+        async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), Self::Error> {
+            (**self).recv_from(buf).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io;
+    use std::net::Shutdown;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::io::{Read, Write};
+    use crate::ipv4::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn loopback() -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)
+    }
+
+    /// Echoes everything read from the next incoming connection, until the peer shuts its side down
+    fn echo<A>(listener: &A) -> SocketAddr
+    where
+        A: TcpAccept,
+    {
+        let (mut connection, peer) = listener.accept().unwrap();
+        let mut buf = [0; 7];
+
+        loop {
+            let len = connection.read(&mut buf).unwrap();
+            if len == 0 {
+                break peer;
+            }
+
+            connection.write_all(&buf[..len]).unwrap();
+        }
+    }
+
+    #[test]
+    fn tcp_round_trip() {
+        let stack = StdStack::with_timeout(TIMEOUT);
+        let listener = TcpListen::bind(&stack, loopback()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::scope(|s| {
+            let server = s.spawn(|| echo(&listener));
+
+            let mut connection = stack.connect("127.0.0.1", port).unwrap();
+            let request = b"\x00\x01\x00\x00\x00\x06\x11\x03\x00\x6b\x00\x03";
+
+            connection.write_all(request).unwrap();
+            connection.flush().unwrap();
+
+            let mut response = [0; 12];
+            connection.read_exact(&mut response).unwrap();
+            assert_eq!(&response, request);
+
+            connection.0.shutdown(Shutdown::Write).unwrap();
+
+            // Closed by the peer
+            assert_eq!(connection.read(&mut response).unwrap(), 0);
+
+            let peer = server.join().unwrap();
+            assert_eq!(peer, connection.0.local_addr().unwrap());
+        });
+    }
+
+    #[test]
+    fn tcp_connect_refused() {
+        let stack = StdStack::with_timeout(TIMEOUT);
+
+        let port = TcpListen::bind(&stack, loopback())
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        // Nothing listens on the port anymore
+        let result = stack.connect("127.0.0.1", port);
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(io::ErrorKind::ConnectionRefused)
+        );
+    }
+
+    #[test]
+    fn tcp_timeout() {
+        let stack = StdStack::with_timeout(Duration::from_millis(50));
+        let listener = TcpListen::bind(&stack, loopback()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let _connection = stack.connect("127.0.0.1", port).unwrap();
+        let (mut accepted, _) = listener.accept().unwrap();
+
+        let result = accepted.read(&mut [0; 8]);
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(io::ErrorKind::TimedOut)
+        );
+    }
+
+    #[test]
+    fn udp_round_trip() {
+        let stack = StdStack::with_timeout(TIMEOUT);
+
+        let server = UdpBind::bind(&stack, loopback()).unwrap();
+        let client = UdpBind::bind(&stack, loopback()).unwrap();
+
+        let server_addr = server.local_addr().unwrap();
+        let client_addr = client.local_addr().unwrap();
+
+        assert_eq!(client.send_to(b"discover", server_addr).unwrap(), 8);
+
+        let mut buf = [0; 64];
+        let (len, from) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"discover");
+        assert_eq!(from, client_addr);
+
+        server.send_to(b"device 42", from).unwrap();
+
+        let (len, from) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"device 42");
+        assert_eq!(from, server_addr);
+
+        // The part of a datagram which does not fit is discarded, rather than received with the next one
+        client.send_to(b"0123456789", server_addr).unwrap();
+        client.send_to(b"next", server_addr).unwrap();
+
+        let (len, _) = server.recv_from(&mut buf[..4]).unwrap();
+        assert_eq!(&buf[..len], b"0123");
+
+        let (len, _) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"next");
+    }
+
+    #[test]
+    fn udp_timeout() {
+        let stack = StdStack::with_timeout(Duration::from_millis(50));
+        let socket = UdpBind::bind(&stack, loopback()).unwrap();
+
+        let result = socket.recv_from(&mut [0; 8]);
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(io::ErrorKind::TimedOut)
+        );
+    }
+}
@@ -1,14 +1,86 @@
 use core::any::Any;
+use core::convert::{TryFrom, TryInto};
 use core::fmt::{self, Debug};
+use core::ops::ControlFlow;
 
 #[cfg(feature = "use_serde")]
 use serde::{de::DeserializeOwned, Serialize};
 
+#[cfg(feature = "alloc")]
+pub mod heap;
+
+/// The maximum length of the keys removed by `StorageBase::remove_all`
+pub const MAX_REMOVE_ALL_KEY_LEN: usize = 64;
+
 pub trait StorageBase {
     type Error: Debug;
 
     fn contains(&self, name: &str) -> Result<bool, Self::Error>;
     fn remove(&mut self, name: &str) -> Result<bool, Self::Error>;
+
+    /// Calls `f` with each key starting with `prefix` (all keys, if `prefix` is empty), in no particular order,
+    /// until `f` returns `ControlFlow::Break`.
+    ///
+    /// Returns `false` if the storage does not support enumerating its keys.
+    fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        let _ = (prefix, f);
+
+        Ok(false)
+    }
+
+    /// Removes all keys starting with `prefix` and returns their number,
+    /// or `None` if the storage does not support enumerating its keys.
+    ///
+    /// Keys longer than `MAX_REMOVE_ALL_KEY_LEN` are not removed.
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        let mut removed = 0;
+
+        loop {
+            let mut key = None;
+
+            let supported = self.for_each_key(prefix, |name| {
+                if let Ok(name) = heapless::String::<MAX_REMOVE_ALL_KEY_LEN>::try_from(name) {
+                    key = Some(name);
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })?;
+
+            if !supported {
+                return Ok(None);
+            }
+
+            match key {
+                Some(key) if self.remove(&key)? => (),
+                _ => break,
+            }
+
+            removed += 1;
+        }
+
+        Ok(Some(removed))
+    }
+
+    /// Returns the number of keys, or `None` if the storage does not support enumerating its keys
+    fn count(&self) -> Result<Option<usize>, Self::Error> {
+        let mut count = 0;
+
+        let supported = self.for_each_key("", |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(supported.then_some(count))
+    }
+
+    /// Removes all keys; same as `remove_all("")`
+    fn clear(&mut self) -> Result<Option<usize>, Self::Error> {
+        self.remove_all("")
+    }
 }
 
 impl<S> StorageBase for &mut S
@@ -24,6 +96,25 @@ where
     fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
         (*self).remove(name)
     }
+
+    fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        (**self).for_each_key(prefix, f)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        (*self).remove_all(prefix)
+    }
+
+    fn count(&self) -> Result<Option<usize>, Self::Error> {
+        (**self).count()
+    }
+
+    fn clear(&mut self) -> Result<Option<usize>, Self::Error> {
+        (*self).clear()
+    }
 }
 
 #[cfg(feature = "use_serde")]
@@ -82,6 +173,29 @@ pub trait RawStorage: StorageBase {
     fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error>;
 
     fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error>;
+
+    /// Reads the part of the value starting at `offset` into `buf`, returning the number of bytes read,
+    /// or `None` if there is no such value. 0 is returned if `offset` is at or past the end of the value,
+    /// or if `buf` is empty.
+    ///
+    /// There is no default implementation, as reading the whole value instead would need a buffer as large
+    /// as the value, defeating the purpose of a partial read.
+    fn get_raw_at(
+        &self,
+        name: &str,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error>;
+
+    /// Applies all `operations` atomically, i.e. either all of them or none survive a power loss.
+    ///
+    /// Returns `false` if the storage does not support atomic updates, in which case `Transaction::commit`
+    /// falls back to journaling the operations.
+    fn apply_atomically(&mut self, operations: Operations<'_>) -> Result<bool, Self::Error> {
+        let _ = operations;
+
+        Ok(false)
+    }
 }
 
 impl<R> RawStorage for &mut R
@@ -99,12 +213,27 @@ where
     fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
         (**self).set_raw(name, buf)
     }
+
+    fn get_raw_at(
+        &self,
+        name: &str,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        (**self).get_raw_at(name, offset, buf)
+    }
+
+    fn apply_atomically(&mut self, operations: Operations<'_>) -> Result<bool, Self::Error> {
+        (**self).apply_atomically(operations)
+    }
 }
 
 #[cfg(feature = "use_serde")]
 pub trait SerDe {
     type Error: Debug;
 
+    /// Serializes `value` into `slice`, returning the serialized part of it.
+    /// Should return an error (rather than panic) if `value` does not fit.
     fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
     where
         T: Serialize;
@@ -141,6 +270,11 @@ where
 pub enum StorageError<R, S> {
     RawStorageError(R),
     SerdeError(S),
+    /// The stored value is larger than the buffer of the storage
+    BufferTooSmall {
+        len: usize,
+        capacity: usize,
+    },
 }
 
 impl<R, S> fmt::Display for StorageError<R, S>
@@ -152,6 +286,10 @@ where
         match self {
             Self::RawStorageError(e) => write!(f, "Storage error: {e}"),
             Self::SerdeError(e) => write!(f, "SerDe error: {e}"),
+            Self::BufferTooSmall { len, capacity } => write!(
+                f,
+                "Value of {len} bytes does not fit in a buffer of {capacity} bytes"
+            ),
         }
     }
 }
@@ -159,11 +297,21 @@ where
 #[cfg(feature = "std")]
 impl<R, S> std::error::Error for StorageError<R, S>
 where
-    R: std::error::Error,
-    S: std::error::Error,
+    R: std::error::Error + 'static,
+    S: std::error::Error + 'static,
 {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::RawStorageError(e) => Some(e),
+            Self::SerdeError(e) => Some(e),
+            Self::BufferTooSmall { .. } => None,
+        }
+    }
 }
 
+/// A `Storage` implementation on top of a `RawStorage`, serializing values with `S`.
+///
+/// Values are (de)serialized in a buffer of `N` bytes on the stack, so `N` should accommodate the largest value stored.
 #[cfg(feature = "use_serde")]
 pub struct StorageImpl<const N: usize, R, S> {
     raw_storage: R,
@@ -200,10 +348,62 @@ where
             .map_err(StorageError::RawStorageError)
     }
 
+    pub fn for_each_key<F>(
+        &self,
+        prefix: &str,
+        f: F,
+    ) -> Result<bool, StorageError<R::Error, S::Error>>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        self.raw_storage
+            .for_each_key(prefix, f)
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn remove_all(
+        &mut self,
+        prefix: &str,
+    ) -> Result<Option<usize>, StorageError<R::Error, S::Error>> {
+        self.raw_storage
+            .remove_all(prefix)
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn count(&self) -> Result<Option<usize>, StorageError<R::Error, S::Error>> {
+        self.raw_storage
+            .count()
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn clear(&mut self) -> Result<Option<usize>, StorageError<R::Error, S::Error>> {
+        self.raw_storage
+            .clear()
+            .map_err(StorageError::RawStorageError)
+    }
+
+    /// Starts a transaction, which can hold up to `B` bytes of (serialized) operations
+    pub fn transaction<const B: usize>(&mut self) -> StorageTransaction<'_, N, R, S, B> {
+        StorageTransaction {
+            transaction: Transaction::new(&mut self.raw_storage),
+            serde: &self.serde,
+        }
+    }
+
     pub fn get<T>(&self, name: &str) -> Result<Option<T>, StorageError<R::Error, S::Error>>
     where
         T: DeserializeOwned,
     {
+        if let Some(len) = self
+            .raw_storage
+            .len(name)
+            .map_err(StorageError::RawStorageError)?
+        {
+            if len > N {
+                return Err(StorageError::BufferTooSmall { len, capacity: N });
+            }
+        }
+
         let mut buf = [0_u8; N];
 
         if let Some(buf) = self
@@ -257,6 +457,25 @@ where
     fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
         StorageImpl::remove(self, name)
     }
+
+    fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        StorageImpl::for_each_key(self, prefix, f)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        StorageImpl::remove_all(self, prefix)
+    }
+
+    fn count(&self) -> Result<Option<usize>, Self::Error> {
+        StorageImpl::count(self)
+    }
+
+    fn clear(&mut self) -> Result<Option<usize>, Self::Error> {
+        StorageImpl::clear(self)
+    }
 }
 
 #[cfg(feature = "use_serde")]
@@ -289,6 +508,15 @@ struct Entry<'a> {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct NoSpaceError;
 
+impl fmt::Display for NoSpaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No space left")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NoSpaceError {}
+
 pub struct DynStorageImpl<'a, const N: usize>([Option<Entry<'a>>; N]);
 
 impl<'a, const N: usize> DynStorageImpl<'a, N> {
@@ -310,6 +538,36 @@ impl<'a, const N: usize> DynStorageImpl<'a, N> {
         }
     }
 
+    pub fn for_each_key<F>(&self, prefix: &str, mut f: F) -> Result<bool, NoSpaceError>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        for entry in self.0.iter().flatten() {
+            if entry.name.starts_with(prefix) && f(entry.name).is_break() {
+                break;
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, NoSpaceError> {
+        let mut removed = 0;
+
+        for place in self.0.iter_mut() {
+            if place
+                .as_ref()
+                .map(|entry| entry.name.starts_with(prefix))
+                .unwrap_or(false)
+            {
+                *place = None;
+                removed += 1;
+            }
+        }
+
+        Ok(Some(removed))
+    }
+
     #[allow(clippy::unnecessary_lazy_evaluations)]
     pub fn get(&self, name: &str) -> Result<Option<&'a dyn Any>, NoSpaceError> {
         Ok(self.0.iter().find_map(|entry| {
@@ -352,6 +610,17 @@ impl<'a, const N: usize> StorageBase for DynStorageImpl<'a, N> {
     fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
         DynStorageImpl::remove(self, name)
     }
+
+    fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        DynStorageImpl::for_each_key(self, prefix, f)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        DynStorageImpl::remove_all(self, prefix)
+    }
 }
 
 impl<'a, const N: usize> DynStorage<'a> for DynStorageImpl<'a, N> {
@@ -364,3 +633,1553 @@ impl<'a, const N: usize> DynStorage<'a> for DynStorageImpl<'a, N> {
         DynStorageImpl::set(self, name, value)
     }
 }
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NamespacedError<E> {
+    StorageError(E),
+    /// The namespaced key is longer than the maximum key length
+    KeyTooLong,
+}
+
+impl<E> fmt::Display for NamespacedError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(e) => write!(f, "Storage error: {e}"),
+            Self::KeyTooLong => write!(f, "Key too long"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for NamespacedError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StorageError(e) => Some(e),
+            Self::KeyTooLong => None,
+        }
+    }
+}
+
+/// A view of a storage, which prepends `<namespace>/` to all keys.
+///
+/// `N` is the maximum length of the keys of the underlying storage (15 by default, as is the case for e.g. the ESP-IDF NVS);
+/// keys which become longer once namespaced are refused with `NamespacedError::KeyTooLong`.
+///
+/// Works with both owned and borrowed (`&mut S`) storages, and views can be nested.
+pub struct Namespaced<S, const N: usize = 15> {
+    storage: S,
+    namespace: heapless::String<N>,
+}
+
+impl<S, const N: usize> Namespaced<S, N>
+where
+    S: StorageBase,
+{
+    pub fn new(storage: S, namespace: &str) -> Result<Self, NamespacedError<S::Error>> {
+        if namespace.len() + 1 >= N {
+            return Err(NamespacedError::KeyTooLong);
+        }
+
+        Ok(Self {
+            storage,
+            namespace: namespace.try_into().unwrap(),
+        })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    pub fn release(self) -> S {
+        self.storage
+    }
+
+    fn key(&self, name: &str) -> Result<heapless::String<N>, NamespacedError<S::Error>> {
+        let mut key = self.namespace.clone();
+
+        key.push('/')
+            .and_then(|_| key.push_str(name))
+            .map_err(|_| NamespacedError::KeyTooLong)?;
+
+        Ok(key)
+    }
+}
+
+impl<S, const N: usize> StorageBase for Namespaced<S, N>
+where
+    S: StorageBase,
+{
+    type Error = NamespacedError<S::Error>;
+
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        self.storage
+            .contains(&self.key(name)?)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        let key = self.key(name)?;
+
+        self.storage
+            .remove(&key)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn for_each_key<F>(&self, prefix: &str, mut f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        let prefix = self.key(prefix)?;
+        let namespace_len = self.namespace.len() + 1;
+
+        self.storage
+            .for_each_key(&prefix, |name| f(&name[namespace_len..]))
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        let prefix = self.key(prefix)?;
+
+        self.storage
+            .remove_all(&prefix)
+            .map_err(NamespacedError::StorageError)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<S, const N: usize> Storage for Namespaced<S, N>
+where
+    S: Storage,
+{
+    fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.storage
+            .get(&self.key(name)?)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+    where
+        T: Serialize,
+    {
+        let key = self.key(name)?;
+
+        self.storage
+            .set(&key, value)
+            .map_err(NamespacedError::StorageError)
+    }
+}
+
+impl<S, const N: usize> RawStorage for Namespaced<S, N>
+where
+    S: RawStorage,
+{
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        self.storage
+            .len(&self.key(name)?)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        self.storage
+            .get_raw(&self.key(name)?, buf)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        let key = self.key(name)?;
+
+        self.storage
+            .set_raw(&key, buf)
+            .map_err(NamespacedError::StorageError)
+    }
+
+    fn get_raw_at(
+        &self,
+        name: &str,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        self.storage
+            .get_raw_at(&self.key(name)?, offset, buf)
+            .map_err(NamespacedError::StorageError)
+    }
+}
+
+/// The key under which `Transaction::commit` journals the operations of a transaction,
+/// for storages which do not support atomic updates
+pub const JOURNAL_KEY: &str = "_txn";
+
+const JOURNAL_SET: u8 = 1;
+const JOURNAL_REMOVE: u8 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation<'a> {
+    Set(&'a str, &'a [u8]),
+    Remove(&'a str),
+}
+
+/// The operations of a transaction, in the order they were added
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Operations<'a>(&'a [u8]);
+
+impl<'a> Operations<'a> {
+    /// Parses a journal of operations, as stored under `JOURNAL_KEY`
+    pub fn parse(journal: &'a [u8]) -> Option<Self> {
+        let mut data = journal;
+
+        while !data.is_empty() {
+            data = Self::parse_operation(data)?.1;
+        }
+
+        Some(Self(journal))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Operation<'a>> {
+        let mut data = self.0;
+
+        core::iter::from_fn(move || {
+            let (operation, rest) = Self::parse_operation(data)?;
+            data = rest;
+
+            Some(operation)
+        })
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    fn parse_operation(data: &'a [u8]) -> Option<(Operation<'a>, &'a [u8])> {
+        let (&op, data) = data.split_first()?;
+        let (&len, data) = data.split_first()?;
+
+        let len = len as usize;
+        if data.len() < len {
+            return None;
+        }
+
+        let (name, data) = data.split_at(len);
+        let name = core::str::from_utf8(name).ok()?;
+
+        match op {
+            JOURNAL_SET => {
+                if data.len() < 2 {
+                    return None;
+                }
+
+                let (len, data) = data.split_at(2);
+                let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+
+                if data.len() < len {
+                    return None;
+                }
+
+                let (value, data) = data.split_at(len);
+
+                Some((Operation::Set(name, value), data))
+            }
+            JOURNAL_REMOVE => Some((Operation::Remove(name), data)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransactionError<E> {
+    StorageError(E),
+    /// The operation does not fit in the transaction
+    Full,
+    /// The journal found by `recover` is corrupted
+    InvalidJournal,
+}
+
+impl<E> fmt::Display for TransactionError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(e) => write!(f, "Storage error: {e}"),
+            Self::Full => write!(f, "Transaction full"),
+            Self::InvalidJournal => write!(f, "Invalid transaction journal"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for TransactionError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StorageError(e) => Some(e),
+            Self::Full | Self::InvalidJournal => None,
+        }
+    }
+}
+
+/// A set of `set_raw` and `remove` operations on a storage, applied together on `commit`.
+///
+/// The operations are kept in RAM (up to `N` bytes, including 2 or 4 bytes of overhead per operation)
+/// until then. Dropping the transaction without committing it discards them.
+///
+/// On commit, storages which support it apply the operations atomically (see `RawStorage::apply_atomically`).
+/// With all other storages, the operations are first written under `JOURNAL_KEY`, then applied in order,
+/// and finally the journal is removed. After a power loss, `recover` should be called to re-apply an
+/// interrupted transaction, so that either all or none of its operations are in effect.
+/// This relies on the writes of a single key being atomic.
+pub struct Transaction<'a, R, const N: usize = 256> {
+    storage: &'a mut R,
+    journal: heapless::Vec<u8, N>,
+}
+
+impl<'a, R, const N: usize> Transaction<'a, R, N>
+where
+    R: RawStorage,
+{
+    pub fn new(storage: &'a mut R) -> Self {
+        Self {
+            storage,
+            journal: heapless::Vec::new(),
+        }
+    }
+
+    pub fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<(), TransactionError<R::Error>> {
+        let len = u16::try_from(buf.len()).map_err(|_| TransactionError::Full)?;
+
+        self.push(JOURNAL_SET, name, &[&len.to_le_bytes(), buf])
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), TransactionError<R::Error>> {
+        self.push(JOURNAL_REMOVE, name, &[])
+    }
+
+    /// The storage, e.g. for reading the current values; these do not reflect the operations of the transaction
+    pub fn storage(&self) -> &R {
+        self.storage
+    }
+
+    pub fn operations(&self) -> Operations<'_> {
+        Operations(&self.journal)
+    }
+
+    pub fn commit(self) -> Result<(), TransactionError<R::Error>> {
+        let operations = Operations(&self.journal);
+
+        if operations.is_empty()
+            || self
+                .storage
+                .apply_atomically(operations)
+                .map_err(TransactionError::StorageError)?
+        {
+            return Ok(());
+        }
+
+        self.storage
+            .set_raw(JOURNAL_KEY, operations.as_bytes())
+            .map_err(TransactionError::StorageError)?;
+
+        apply(self.storage, operations)
+    }
+
+    /// Discards the operations of the transaction; same as dropping it
+    pub fn rollback(self) {}
+
+    fn push(
+        &mut self,
+        op: u8,
+        name: &str,
+        data: &[&[u8]],
+    ) -> Result<(), TransactionError<R::Error>> {
+        let len = self.journal.len();
+
+        let result = u8::try_from(name.len()).ok().and_then(|name_len| {
+            self.journal.push(op).ok()?;
+            self.journal.push(name_len).ok()?;
+            self.journal.extend_from_slice(name.as_bytes()).ok()?;
+
+            for data in data {
+                self.journal.extend_from_slice(data).ok()?;
+            }
+
+            Some(())
+        });
+
+        if result.is_none() {
+            self.journal.truncate(len);
+
+            return Err(TransactionError::Full);
+        }
+
+        Ok(())
+    }
+}
+
+/// Re-applies the transaction interrupted by e.g. a power loss, if any; should be called on startup
+/// for storages which do not support atomic updates.
+///
+/// `buf` should fit the journal, i.e. the transaction sizes in use. Returns `true` if a transaction was recovered.
+/// A corrupted journal is removed and reported as `TransactionError::InvalidJournal`.
+pub fn recover<R>(storage: &mut R, buf: &mut [u8]) -> Result<bool, TransactionError<R::Error>>
+where
+    R: RawStorage,
+{
+    let journal = match storage
+        .get_raw(JOURNAL_KEY, buf)
+        .map_err(TransactionError::StorageError)?
+    {
+        Some(journal) => journal,
+        None => return Ok(false),
+    };
+
+    if let Some(operations) = Operations::parse(journal) {
+        apply(storage, operations)?;
+
+        Ok(true)
+    } else {
+        storage
+            .remove(JOURNAL_KEY)
+            .map_err(TransactionError::StorageError)?;
+
+        Err(TransactionError::InvalidJournal)
+    }
+}
+
+fn apply<R>(storage: &mut R, operations: Operations<'_>) -> Result<(), TransactionError<R::Error>>
+where
+    R: RawStorage,
+{
+    for operation in operations.iter() {
+        match operation {
+            Operation::Set(name, value) => storage.set_raw(name, value),
+            Operation::Remove(name) => storage.remove(name),
+        }
+        .map_err(TransactionError::StorageError)?;
+    }
+
+    storage
+        .remove(JOURNAL_KEY)
+        .map_err(TransactionError::StorageError)?;
+
+    Ok(())
+}
+
+/// A transaction of (serialized) values, started with `StorageImpl::transaction`
+#[cfg(feature = "use_serde")]
+pub struct StorageTransaction<'a, const N: usize, R, S, const B: usize> {
+    transaction: Transaction<'a, R, B>,
+    serde: &'a S,
+}
+
+#[cfg(feature = "use_serde")]
+impl<'a, const N: usize, R, S, const B: usize> StorageTransaction<'a, N, R, S, B>
+where
+    R: RawStorage,
+    S: SerDe,
+{
+    pub fn set<T>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), StorageError<TransactionError<R::Error>, S::Error>>
+    where
+        T: Serialize,
+    {
+        let mut buf = [0_u8; N];
+
+        let buf = self
+            .serde
+            .serialize(&mut buf, value)
+            .map_err(StorageError::SerdeError)?;
+
+        self.transaction
+            .set_raw(name, buf)
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn remove(
+        &mut self,
+        name: &str,
+    ) -> Result<(), StorageError<TransactionError<R::Error>, S::Error>> {
+        self.transaction
+            .remove(name)
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn commit(self) -> Result<(), StorageError<TransactionError<R::Error>, S::Error>> {
+        self.transaction
+            .commit()
+            .map_err(StorageError::RawStorageError)
+    }
+
+    pub fn rollback(self) {}
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadError<E> {
+    StorageError(E),
+    NotFound,
+}
+
+impl<E> fmt::Display for ReadError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(e) => write!(f, "Storage error: {e}"),
+            Self::NotFound => write!(f, "Not found"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for ReadError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StorageError(e) => Some(e),
+            Self::NotFound => None,
+        }
+    }
+}
+
+impl<E> crate::io::Error for ReadError<E>
+where
+    E: Debug,
+{
+    fn kind(&self) -> crate::io::ErrorKind {
+        match self {
+            Self::StorageError(_) => crate::io::ErrorKind::Other,
+            Self::NotFound => crate::io::ErrorKind::NotFound,
+        }
+    }
+}
+
+/// Reads a stored value with `RawStorage::get_raw_at`, so that it can be e.g. copied into an HTTP response
+/// or an OTA update without a buffer as large as the value.
+///
+/// Reading a value which does not exist fails with `ReadError::NotFound`.
+pub struct StorageReader<'a, R> {
+    storage: &'a R,
+    name: &'a str,
+    offset: usize,
+}
+
+impl<'a, R> StorageReader<'a, R>
+where
+    R: RawStorage,
+{
+    pub const fn new(storage: &'a R, name: &'a str) -> Self {
+        Self::new_at(storage, name, 0)
+    }
+
+    pub const fn new_at(storage: &'a R, name: &'a str, offset: usize) -> Self {
+        Self {
+            storage,
+            name,
+            offset,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a, R> crate::io::ErrorType for StorageReader<'a, R>
+where
+    R: RawStorage,
+{
+    type Error = ReadError<R::Error>;
+}
+
+impl<'a, R> crate::io::Read for StorageReader<'a, R>
+where
+    R: RawStorage,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let read = self
+            .storage
+            .get_raw_at(self.name, self.offset, buf)
+            .map_err(ReadError::StorageError)?
+            .ok_or(ReadError::NotFound)?;
+
+        self.offset += read;
+
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::fmt::Debug;
+
+    #[cfg(feature = "use_serde")]
+    use serde::{de::DeserializeOwned, Serialize};
+
+    pub trait StorageBase {
+        type Error: Debug;
+
+        async fn contains(&self, name: &str) -> Result<bool, Self::Error>;
+        async fn remove(&mut self, name: &str) -> Result<bool, Self::Error>;
+    }
+
+    impl<S> StorageBase for &mut S
+    where
+        S: StorageBase,
+    {
+        type Error = S::Error;
+
+        async fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+            (**self).contains(name).await
+        }
+
+        async fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+            (*self).remove(name).await
+        }
+    }
+
+    #[cfg(feature = "use_serde")]
+    pub trait Storage: StorageBase {
+        async fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+        where
+            T: DeserializeOwned + Send + 'static;
+
+        async fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+        where
+            T: Serialize + Sync;
+    }
+
+    #[cfg(feature = "use_serde")]
+    impl<S> Storage for &mut S
+    where
+        S: Storage,
+    {
+        async fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+        where
+            T: DeserializeOwned + Send + 'static,
+        {
+            (**self).get(name).await
+        }
+
+        async fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+        where
+            T: Serialize + Sync,
+        {
+            (*self).set(name, value).await
+        }
+    }
+
+    pub trait RawStorage: StorageBase {
+        async fn len(&self, name: &str) -> Result<Option<usize>, Self::Error>;
+
+        async fn get_raw<'a>(
+            &self,
+            name: &str,
+            buf: &'a mut [u8],
+        ) -> Result<Option<&'a [u8]>, Self::Error>;
+
+        async fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error>;
+    }
+
+    impl<R> RawStorage for &mut R
+    where
+        R: RawStorage,
+    {
+        async fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+            (**self).len(name).await
+        }
+
+        async fn get_raw<'a>(
+            &self,
+            name: &str,
+            buf: &'a mut [u8],
+        ) -> Result<Option<&'a [u8]>, Self::Error> {
+            (**self).get_raw(name, buf).await
+        }
+
+        async fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+            (**self).set_raw(name, buf).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std", feature = "use_serde"))]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::test_json::JsonSerDe;
+
+    use super::heap::{InMemoryStorage, InMemoryStorageError};
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        ssid: String,
+        channel: u8,
+        hidden: bool,
+        peers: Vec<u16>,
+    }
+
+    fn config() -> Config {
+        Config {
+            ssid: "home \"5G\"".into(),
+            channel: 11,
+            hidden: false,
+            peers: vec![1, 300, 65535],
+        }
+    }
+
+    type JsonStorage = StorageImpl<128, InMemoryStorage, JsonSerDe>;
+
+    #[test]
+    fn storage_impl_round_trip() {
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        assert_eq!(storage.get::<Config>("wifi").unwrap(), None);
+        assert!(!storage.contains("wifi").unwrap());
+
+        storage.set("wifi", &config()).unwrap();
+        storage.set("boots", &42_u32).unwrap();
+        storage.set("name", &Some("sensor")).unwrap();
+
+        assert_eq!(storage.get::<Config>("wifi").unwrap(), Some(config()));
+        assert_eq!(storage.get::<u32>("boots").unwrap(), Some(42));
+        assert_eq!(
+            storage.get::<Option<String>>("name").unwrap(),
+            Some(Some("sensor".into()))
+        );
+
+        // The values are stored as serialized by the `SerDe`
+        let mut buf = [0; 16];
+        assert_eq!(
+            storage.raw_storage().get_raw("boots", &mut buf).unwrap(),
+            Some(&b"42"[..])
+        );
+
+        let mut changed = config();
+        changed.channel = 1;
+        changed.peers.clear();
+
+        storage.set("wifi", &changed).unwrap();
+        assert_eq!(storage.get::<Config>("wifi").unwrap(), Some(changed));
+
+        assert!(storage.remove("wifi").unwrap());
+        assert!(!storage.remove("wifi").unwrap());
+        assert_eq!(storage.get::<Config>("wifi").unwrap(), None);
+        assert_eq!(storage.count().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn storage_impl_as_storage() {
+        fn round_trip<S: Storage>(storage: &mut S) -> Option<Config> {
+            storage.set("wifi", &config()).ok()?;
+            storage.get("wifi").ok()?
+        }
+
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        assert_eq!(round_trip(&mut storage), Some(config()));
+        // Through `&mut S`, with a borrowed `SerDe`
+        let serde = JsonSerDe;
+        let mut storage = StorageImpl::<128, _, _>::new(InMemoryStorage::new(), &serde);
+        assert_eq!(round_trip(&mut &mut storage), Some(config()));
+    }
+
+    #[test]
+    fn storage_impl_value_too_large() {
+        let mut storage = StorageImpl::<16, _, _>::new(InMemoryStorage::new(), JsonSerDe);
+
+        // Stored by someone else, e.g. with a larger buffer
+        storage
+            .raw_storage_mut()
+            .set_raw("blob", &[b'1'; 17])
+            .unwrap();
+
+        let result = storage.get::<u64>("blob");
+        assert!(matches!(
+            result,
+            Err(StorageError::BufferTooSmall {
+                len: 17,
+                capacity: 16
+            })
+        ));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Value of 17 bytes does not fit in a buffer of 16 bytes"
+        );
+
+        // Exactly fits
+        storage
+            .raw_storage_mut()
+            .set_raw("blob", &[b'1'; 16])
+            .unwrap();
+        assert_eq!(
+            storage.get::<u64>("blob").unwrap(),
+            Some(1_111_111_111_111_111)
+        );
+
+        // Does not fit when serialized; nothing is stored
+        assert!(matches!(
+            storage.set("wifi", &config()),
+            Err(StorageError::SerdeError(_))
+        ));
+        assert!(!storage.contains("wifi").unwrap());
+    }
+
+    #[test]
+    fn storage_impl_invalid_value() {
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        storage.set("wifi", &"not a config").unwrap();
+
+        assert!(matches!(
+            storage.get::<Config>("wifi"),
+            Err(StorageError::SerdeError(_))
+        ));
+    }
+
+    #[test]
+    fn storage_impl_raw_storage_failure() {
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        storage.raw_storage_mut().fail_next_write();
+
+        assert!(matches!(
+            storage.set("boots", &1_u32),
+            Err(StorageError::RawStorageError(
+                InMemoryStorageError::Injected
+            ))
+        ));
+        assert_eq!(storage.get::<u32>("boots").unwrap(), None);
+    }
+
+    fn keys<S: StorageBase>(storage: &S, prefix: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+
+        storage
+            .for_each_key(prefix, |key| {
+                keys.push(key.to_string());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+
+        keys
+    }
+
+    #[test]
+    fn namespaces_are_isolated() {
+        let mut storage = InMemoryStorage::new().with_serde(JsonSerDe);
+
+        let mut wifi = Namespaced::<_>::new(&mut storage, "wifi").unwrap();
+        assert_eq!(wifi.namespace(), "wifi");
+
+        wifi.set("ssid", &"home").unwrap();
+        wifi.set("channel", &11_u8).unwrap();
+
+        // A namespace sharing a prefix with the other one
+        let mut wifi2 = Namespaced::<_>::new(&mut storage, "wifi2").unwrap();
+        wifi2.set("ssid", &"guest").unwrap();
+        assert!(!wifi2.contains("channel").unwrap());
+
+        let mut mqtt = Namespaced::<_>::new(&mut storage, "mqtt").unwrap();
+        mqtt.set("ssid", &"broker").unwrap();
+        assert_eq!(keys(&mqtt, ""), ["ssid"]);
+
+        assert_eq!(
+            keys(&storage, ""),
+            ["mqtt/ssid", "wifi/channel", "wifi/ssid", "wifi2/ssid"]
+        );
+
+        let mut wifi = Namespaced::<_>::new(&mut storage, "wifi").unwrap();
+        assert_eq!(wifi.get::<String>("ssid").unwrap().as_deref(), Some("home"));
+        assert_eq!(keys(&wifi, ""), ["channel", "ssid"]);
+        assert_eq!(keys(&wifi, "s"), ["ssid"]);
+
+        assert!(wifi.remove("ssid").unwrap());
+        assert!(!wifi.remove("ssid").unwrap());
+        assert_eq!(wifi.remove_all("").unwrap(), Some(1));
+
+        assert_eq!(keys(&storage, ""), ["mqtt/ssid", "wifi2/ssid"]);
+
+        let wifi2 = Namespaced::<_>::new(&mut storage, "wifi2").unwrap();
+        assert_eq!(
+            wifi2.get::<String>("ssid").unwrap().as_deref(),
+            Some("guest")
+        );
+    }
+
+    #[test]
+    fn namespaces_nested() {
+        let mut storage = InMemoryStorage::new().with_serde(JsonSerDe);
+
+        let app = Namespaced::<_, 32>::new(&mut storage, "app").unwrap();
+        let mut prefs = Namespaced::<_, 32>::new(app, "prefs").unwrap();
+
+        assert_eq!(prefs.namespace(), "prefs");
+        assert_eq!(prefs.storage().namespace(), "app");
+
+        prefs.set("theme", &"dark").unwrap();
+        assert_eq!(
+            prefs.get::<String>("theme").unwrap().as_deref(),
+            Some("dark")
+        );
+
+        // Owned views give the storage back
+        let app = prefs.release();
+        assert_eq!(keys(&app, ""), ["prefs/theme"]);
+
+        assert_eq!(keys(&storage, ""), ["app/prefs/theme"]);
+    }
+
+    #[test]
+    fn namespaced_raw_storage() {
+        let mut storage = InMemoryStorage::new();
+        let mut ota = Namespaced::<_>::new(&mut storage, "ota").unwrap();
+
+        ota.set_raw("state", b"pending").unwrap();
+        assert_eq!(ota.len("state").unwrap(), Some(7));
+
+        let mut buf = [0; 8];
+        assert_eq!(
+            ota.get_raw("state", &mut buf).unwrap(),
+            Some(&b"pending"[..])
+        );
+        assert_eq!(ota.get_raw_at("state", 3, &mut buf).unwrap(), Some(4));
+        assert_eq!(&buf[..4], b"ding");
+        assert_eq!(ota.len("other").unwrap(), None);
+
+        assert_eq!(storage.len("ota/state").unwrap(), Some(7));
+    }
+
+    #[test]
+    fn namespaced_key_too_long() {
+        let mut storage = InMemoryStorage::new().with_serde(JsonSerDe);
+
+        // 15 bytes at most, i.e. 10 for the keys of `wifi/`
+        let mut wifi = Namespaced::<_>::new(&mut storage, "wifi").unwrap();
+
+        assert!(wifi.set("0123456789", &1_u8).is_ok());
+        assert!(matches!(
+            wifi.set("0123456789a", &1_u8),
+            Err(NamespacedError::KeyTooLong)
+        ));
+        assert!(matches!(
+            wifi.get::<u8>("0123456789a"),
+            Err(NamespacedError::KeyTooLong)
+        ));
+        assert!(matches!(
+            wifi.contains("0123456789a"),
+            Err(NamespacedError::KeyTooLong)
+        ));
+        assert!(matches!(
+            wifi.remove("0123456789a"),
+            Err(NamespacedError::KeyTooLong)
+        ));
+
+        // Not truncated to an existing key
+        assert_eq!(keys(&storage, ""), ["wifi/0123456789"]);
+
+        // A namespace has to leave room for at least one character
+        assert!(Namespaced::<_>::new(&mut storage, "0123456789abc").is_ok());
+        assert!(matches!(
+            Namespaced::<_>::new(&mut storage, "0123456789abcd"),
+            Err(NamespacedError::KeyTooLong)
+        ));
+
+        // The limits of the underlying storage still apply
+        let mut storage = InMemoryStorage::with_limits(heap::Limits {
+            max_key_len: Some(8),
+            ..Default::default()
+        });
+
+        let mut wifi = Namespaced::<_, 32>::new(&mut storage, "wifi").unwrap();
+
+        assert!(matches!(
+            wifi.set_raw("channel", b"11"),
+            Err(NamespacedError::StorageError(
+                InMemoryStorageError::KeyTooLong
+            ))
+        ));
+        assert_eq!(
+            NamespacedError::<InMemoryStorageError>::KeyTooLong.to_string(),
+            "Key too long"
+        );
+    }
+
+    /// Only implements the required methods and `for_each_key`, so that the default `remove_all`,
+    /// `count` and `clear` are used
+    struct Enumerable(InMemoryStorage);
+
+    impl StorageBase for Enumerable {
+        type Error = InMemoryStorageError;
+
+        fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+            self.0.contains(name)
+        }
+
+        fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+            self.0.remove(name)
+        }
+
+        fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+        where
+            F: FnMut(&str) -> ControlFlow<()>,
+        {
+            self.0.for_each_key(prefix, f)
+        }
+    }
+
+    /// Only implements the required methods, so it cannot enumerate its keys
+    struct NotEnumerable(InMemoryStorage);
+
+    impl StorageBase for NotEnumerable {
+        type Error = InMemoryStorageError;
+
+        fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+            self.0.contains(name)
+        }
+
+        fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+            self.0.remove(name)
+        }
+    }
+
+    fn storage_with(keys: &[&str]) -> InMemoryStorage {
+        let mut storage = InMemoryStorage::new();
+
+        for key in keys {
+            storage.set_raw(key, b"1").unwrap();
+        }
+
+        storage
+    }
+
+    #[test]
+    fn for_each_key_prefix() {
+        let storage = storage_with(&["user/b", "user/a", "users", "sys/boots", "u"]);
+
+        assert_eq!(
+            keys(&storage, ""),
+            ["sys/boots", "u", "user/a", "user/b", "users"]
+        );
+        assert_eq!(keys(&storage, "user/"), ["user/a", "user/b"]);
+        assert_eq!(keys(&storage, "user"), ["user/a", "user/b", "users"]);
+        assert!(keys(&storage, "wifi/").is_empty());
+
+        // Stops once `f` breaks
+        let mut first = None;
+        assert!(storage
+            .for_each_key("user", |key| {
+                first = Some(key.to_string());
+                ControlFlow::Break(())
+            })
+            .unwrap());
+        assert_eq!(first.as_deref(), Some("user/a"));
+    }
+
+    #[test]
+    fn for_each_key_unsupported() {
+        let storage = NotEnumerable(storage_with(&["user/a"]));
+
+        assert!(!storage
+            .for_each_key("", |_| ControlFlow::Continue(()))
+            .unwrap());
+        assert!(keys(&storage, "").is_empty());
+    }
+
+    #[test]
+    fn remove_all_default() {
+        let long = "user/".to_string() + &"x".repeat(MAX_REMOVE_ALL_KEY_LEN);
+
+        let mut storage = Enumerable(storage_with(&[
+            "user/a",
+            "user/b",
+            "users",
+            "wifi/ssid",
+            &long,
+        ]));
+
+        assert_eq!(storage.remove_all("user/").unwrap(), Some(2));
+        // Keys longer than `MAX_REMOVE_ALL_KEY_LEN` are skipped
+        assert_eq!(keys(&storage, ""), [long.as_str(), "users", "wifi/ssid"]);
+        assert_eq!(storage.remove_all("user/").unwrap(), Some(0));
+        assert_eq!(storage.remove_all("").unwrap(), Some(2));
+        assert_eq!(keys(&storage, ""), [long.as_str()]);
+    }
+
+    #[test]
+    fn remove_all_unsupported() {
+        let mut storage = NotEnumerable(storage_with(&["user/a"]));
+
+        assert_eq!(storage.remove_all("user/").unwrap(), None);
+        assert!(storage.contains("user/a").unwrap());
+    }
+
+    #[test]
+    fn remove_all_dyn_storage() {
+        let mut storage = DynStorageImpl::<4>(Default::default());
+
+        storage.set("user/a", &1_u8).unwrap();
+        storage.set("user/b", &2_u8).unwrap();
+        storage.set("wifi", &3_u8).unwrap();
+
+        assert_eq!(keys(&storage, "user/").len(), 2);
+        assert_eq!(storage.remove_all("user/").unwrap(), Some(2));
+        assert_eq!(keys(&storage, ""), ["wifi"]);
+    }
+
+    fn value(storage: &InMemoryStorage, name: &str) -> Option<Vec<u8>> {
+        let mut buf = [0; 64];
+
+        storage
+            .get_raw(name, &mut buf)
+            .unwrap()
+            .map(|value| value.to_vec())
+    }
+
+    #[test]
+    fn transaction_atomic() {
+        let mut storage = storage_with(&["wifi/old"]);
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("wifi/ssid", b"home").unwrap();
+        transaction.set_raw("wifi/pass", b"secret").unwrap();
+        transaction.remove("wifi/old").unwrap();
+
+        // Nothing is applied until committed
+        assert_eq!(value(transaction.storage(), "wifi/ssid"), None);
+        assert_eq!(
+            transaction.operations().iter().collect::<Vec<_>>(),
+            [
+                Operation::Set("wifi/ssid", b"home"),
+                Operation::Set("wifi/pass", b"secret"),
+                Operation::Remove("wifi/old"),
+            ]
+        );
+
+        transaction.commit().unwrap();
+
+        assert_eq!(keys(&storage, ""), ["wifi/pass", "wifi/ssid"]);
+        assert_eq!(value(&storage, "wifi/ssid").as_deref(), Some(&b"home"[..]));
+    }
+
+    #[test]
+    fn transaction_atomic_failure() {
+        let mut storage = storage_with(&["wifi/old"]);
+        storage.fail_next_write();
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("wifi/ssid", b"home").unwrap();
+        transaction.remove("wifi/old").unwrap();
+
+        assert!(matches!(
+            transaction.commit(),
+            Err(TransactionError::StorageError(
+                InMemoryStorageError::Injected
+            ))
+        ));
+        assert_eq!(keys(&storage, ""), ["wifi/old"]);
+    }
+
+    #[test]
+    fn transaction_rollback() {
+        let mut storage = InMemoryStorage::new();
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("a", b"1").unwrap();
+        transaction.rollback();
+
+        {
+            let mut transaction = Transaction::<_>::new(&mut storage);
+            transaction.set_raw("b", b"2").unwrap();
+        }
+
+        assert!(keys(&storage, "").is_empty());
+
+        // An empty transaction does not write anything
+        storage.fail_next_write();
+        Transaction::<_>::new(&mut storage).commit().unwrap();
+    }
+
+    #[test]
+    fn transaction_journaled() {
+        let mut storage = storage_with(&["wifi/old"]).with_atomic_updates(false);
+
+        // The journal and the first operation are written, and then the power goes down
+        storage.fail_after_writes(2);
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("wifi/ssid", b"home").unwrap();
+        transaction.set_raw("wifi/pass", b"secret").unwrap();
+        transaction.remove("wifi/old").unwrap();
+
+        assert!(matches!(
+            transaction.commit(),
+            Err(TransactionError::StorageError(
+                InMemoryStorageError::Injected
+            ))
+        ));
+        assert_eq!(keys(&storage, ""), [JOURNAL_KEY, "wifi/old", "wifi/ssid"]);
+
+        // After the "reboot"
+        storage.clear_failures();
+
+        let mut buf = [0; 64];
+        assert!(recover(&mut storage, &mut buf).unwrap());
+        assert_eq!(keys(&storage, ""), ["wifi/pass", "wifi/ssid"]);
+        assert_eq!(
+            value(&storage, "wifi/pass").as_deref(),
+            Some(&b"secret"[..])
+        );
+
+        assert!(!recover(&mut storage, &mut buf).unwrap());
+    }
+
+    #[test]
+    fn transaction_journaled_complete() {
+        let mut storage = InMemoryStorage::new().with_atomic_updates(false);
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("a", b"1").unwrap();
+        transaction.set_raw("b", b"2").unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(keys(&storage, ""), ["a", "b"]);
+
+        // The journal itself fails to be written, so nothing changes
+        storage.fail_next_write();
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.remove("a").unwrap();
+
+        assert!(transaction.commit().is_err());
+        assert_eq!(keys(&storage, ""), ["a", "b"]);
+    }
+
+    #[test]
+    fn transaction_full() {
+        let mut storage = InMemoryStorage::new();
+
+        let mut transaction = Transaction::<_, 16>::new(&mut storage);
+
+        // 2 + 4 + 2 + 4 bytes
+        transaction.set_raw("ssid", b"home").unwrap();
+        assert!(matches!(
+            transaction.set_raw("pass", b"x"),
+            Err(TransactionError::Full)
+        ));
+        // 2 + 2 bytes
+        transaction.remove("ab").unwrap();
+        assert!(matches!(
+            transaction.remove("c"),
+            Err(TransactionError::Full)
+        ));
+
+        // The refused operations were not partially added
+        assert_eq!(
+            transaction.operations().iter().collect::<Vec<_>>(),
+            [Operation::Set("ssid", b"home"), Operation::Remove("ab")]
+        );
+
+        let mut transaction = Transaction::<_, 1024>::new(&mut storage);
+        assert!(matches!(
+            transaction.remove(&"x".repeat(256)),
+            Err(TransactionError::Full)
+        ));
+        assert!(transaction.operations().is_empty());
+    }
+
+    #[test]
+    fn transaction_invalid_journal() {
+        let mut transaction_storage = InMemoryStorage::new();
+
+        let mut transaction = Transaction::<_>::new(&mut transaction_storage);
+        transaction.set_raw("ssid", b"home").unwrap();
+
+        let journal = transaction.operations().as_bytes().to_vec();
+
+        assert_eq!(
+            Operations::parse(&journal)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            [Operation::Set("ssid", b"home")]
+        );
+        assert!(Operations::parse(&journal[..journal.len() - 1]).is_none());
+        assert!(Operations::parse(&[3, 0]).is_none());
+
+        let mut storage = InMemoryStorage::new();
+        storage
+            .set_raw(JOURNAL_KEY, &journal[..journal.len() - 1])
+            .unwrap();
+
+        let mut buf = [0; 64];
+        assert!(matches!(
+            recover(&mut storage, &mut buf),
+            Err(TransactionError::InvalidJournal)
+        ));
+        assert!(keys(&storage, "").is_empty());
+    }
+
+    #[test]
+    fn storage_transaction() {
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        storage.set("boots", &1_u32).unwrap();
+
+        let mut transaction = storage.transaction::<256>();
+        transaction.set("wifi", &config()).unwrap();
+        transaction.set("channel", &11_u8).unwrap();
+        transaction.remove("boots").unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(storage.get::<Config>("wifi").unwrap(), Some(config()));
+        assert_eq!(storage.get::<u8>("channel").unwrap(), Some(11));
+        assert!(!storage.contains("boots").unwrap());
+
+        let mut transaction = storage.transaction::<8>();
+        assert!(matches!(
+            transaction.set("wifi", &config()),
+            Err(StorageError::RawStorageError(TransactionError::Full))
+        ));
+        transaction.rollback();
+    }
+
+    fn blob() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn get_raw_at<R: RawStorage>(storage: R, offset: usize, buf: &mut [u8]) -> Option<usize> {
+        storage.get_raw_at("blob", offset, buf).unwrap()
+    }
+
+    #[test]
+    fn partial_reads() {
+        let blob = blob();
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("blob", &blob).unwrap();
+
+        assert_eq!(storage.len("blob").unwrap(), Some(1000));
+        assert_eq!(storage.len("other").unwrap(), None);
+
+        let mut buf = [0; 64];
+
+        assert_eq!(storage.get_raw_at("blob", 0, &mut buf).unwrap(), Some(64));
+        assert_eq!(&buf[..], &blob[..64]);
+        assert_eq!(storage.get_raw_at("blob", 990, &mut buf).unwrap(), Some(10));
+        assert_eq!(&buf[..10], &blob[990..]);
+        assert_eq!(get_raw_at(&mut storage, 500, &mut buf[..3]), Some(3));
+        assert_eq!(&buf[..3], &blob[500..503]);
+
+        // At or past the end, or into an empty buffer
+        assert_eq!(storage.get_raw_at("blob", 1000, &mut buf).unwrap(), Some(0));
+        assert_eq!(storage.get_raw_at("blob", 2000, &mut buf).unwrap(), Some(0));
+        assert_eq!(storage.get_raw_at("blob", 0, &mut []).unwrap(), Some(0));
+
+        assert_eq!(storage.get_raw_at("other", 0, &mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn storage_reader() {
+        let blob = blob();
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("blob", &blob).unwrap();
+
+        let mut reader = StorageReader::new(&storage, "blob");
+        let mut read = Vec::new();
+        let mut buf = [0; 64];
+
+        loop {
+            let len = crate::io::Read::read(&mut reader, &mut buf).unwrap();
+
+            if len == 0 {
+                break;
+            }
+
+            read.extend_from_slice(&buf[..len]);
+        }
+
+        assert_eq!(read, blob);
+        assert_eq!(reader.offset(), 1000);
+
+        let mut reader = StorageReader::new_at(&storage, "blob", 995);
+        assert_eq!(crate::io::Read::read(&mut reader, &mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], &blob[995..]);
+        assert_eq!(crate::io::Read::read(&mut reader, &mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn storage_reader_not_found() {
+        let storage = InMemoryStorage::new();
+        let mut reader = StorageReader::new(&storage, "blob");
+
+        let error = crate::io::Read::read(&mut reader, &mut [0; 8]).unwrap_err();
+
+        assert!(matches!(error, ReadError::NotFound));
+        assert_eq!(
+            crate::io::Error::kind(&error),
+            crate::io::ErrorKind::NotFound
+        );
+        assert_eq!(reader.offset(), 0);
+    }
+
+    fn clear<S: StorageBase>(mut storage: S) -> Option<usize> {
+        storage.clear().unwrap()
+    }
+
+    #[test]
+    fn count_and_clear() {
+        let mut storage = Enumerable(storage_with(&["a", "b/c", "b/d"]));
+
+        assert_eq!(storage.count().unwrap(), Some(3));
+        assert_eq!(clear(&mut storage), Some(3));
+        assert_eq!(storage.count().unwrap(), Some(0));
+        assert_eq!(storage.clear().unwrap(), Some(0));
+
+        let mut storage = NotEnumerable(storage_with(&["a"]));
+
+        assert_eq!(storage.count().unwrap(), None);
+        assert_eq!(storage.clear().unwrap(), None);
+        assert!(storage.contains("a").unwrap());
+    }
+
+    #[test]
+    fn count_and_clear_namespaced() {
+        let mut storage = storage_with(&["wifi/ssid", "wifi/pass", "wifi2/ssid", "boots"]);
+
+        let mut wifi = Namespaced::<_>::new(&mut storage, "wifi").unwrap();
+
+        assert_eq!(wifi.count().unwrap(), Some(2));
+        assert_eq!(wifi.clear().unwrap(), Some(2));
+        assert_eq!(wifi.count().unwrap(), Some(0));
+
+        assert_eq!(keys(&storage, ""), ["boots", "wifi2/ssid"]);
+    }
+
+    #[test]
+    fn count_and_clear_storage_impl() {
+        let mut storage = JsonStorage::new(InMemoryStorage::new(), JsonSerDe);
+
+        storage.set("a", &1_u8).unwrap();
+        storage.set("b", &2_u8).unwrap();
+
+        assert_eq!(StorageBase::count(&storage).unwrap(), Some(2));
+        assert_eq!(clear(&mut storage), Some(2));
+        assert_eq!(storage.count().unwrap(), Some(0));
+
+        storage.set("a", &1_u8).unwrap();
+        storage.raw_storage_mut().fail_next_write();
+
+        assert!(matches!(
+            storage.clear(),
+            Err(StorageError::RawStorageError(
+                InMemoryStorageError::Injected
+            ))
+        ));
+        assert_eq!(storage.count().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn count_dyn_storage() {
+        let mut storage = DynStorageImpl::<4>(Default::default());
+
+        storage.set("a", &1_u8).unwrap();
+        storage.set("b", &2_u8).unwrap();
+
+        assert_eq!(StorageBase::count(&storage).unwrap(), Some(2));
+        assert_eq!(StorageBase::clear(&mut storage).unwrap(), Some(2));
+        assert_eq!(StorageBase::count(&storage).unwrap(), Some(0));
+    }
+
+    /// The displayed sources of `error`, from the outermost one
+    fn sources(error: &dyn std::error::Error) -> Vec<String> {
+        let mut sources = Vec::new();
+        let mut source = error.source();
+
+        while let Some(error) = source {
+            sources.push(error.to_string());
+            source = error.source();
+        }
+
+        sources
+    }
+
+    type RawError = StorageError<InMemoryStorageError, fmt::Error>;
+
+    #[test]
+    fn sources_chain() {
+        let raw = RawError::RawStorageError(InMemoryStorageError::Injected);
+        let serde = RawError::SerdeError(fmt::Error);
+        let too_small = RawError::BufferTooSmall {
+            len: 8,
+            capacity: 4,
+        };
+
+        assert_eq!(sources(&raw), ["Injected failure"]);
+        assert_eq!(sources(&serde), [fmt::Error.to_string()]);
+        assert!(sources(&too_small).is_empty());
+
+        // The wrapping errors chain down to the raw storage error
+        let namespaced = NamespacedError::StorageError(raw);
+        assert_eq!(
+            sources(&namespaced),
+            ["Storage error: Injected failure", "Injected failure"]
+        );
+
+        let transaction = TransactionError::StorageError(namespaced);
+        assert_eq!(sources(&transaction).len(), 3);
+        assert!(std::error::Error::source(&transaction)
+            .unwrap()
+            .downcast_ref::<NamespacedError<RawError>>()
+            .is_some());
+
+        let read = ReadError::StorageError(InMemoryStorageError::Full);
+        assert_eq!(sources(&read), ["Storage full"]);
+
+        assert!(sources(&NamespacedError::<RawError>::KeyTooLong).is_empty());
+        assert!(sources(&TransactionError::<RawError>::Full).is_empty());
+        assert!(sources(&TransactionError::<RawError>::InvalidJournal).is_empty());
+        assert!(sources(&ReadError::<RawError>::NotFound).is_empty());
+    }
+
+    #[test]
+    fn no_space() {
+        let error: &dyn std::error::Error = &NoSpaceError;
+
+        assert_eq!(error.to_string(), "No space left");
+        assert!(error.source().is_none());
+    }
+}
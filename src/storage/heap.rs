@@ -0,0 +1,579 @@
+//! A storage keeping its values on the heap, for tests and host builds.
+//!
+//! Besides the storage traits, it can emulate the limits of a flash storage (number of entries, key length)
+//! and inject write failures.
+
+use core::fmt;
+use core::ops::{Bound, ControlFlow};
+
+extern crate alloc;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "use_serde")]
+use alloc::{format, vec};
+
+#[cfg(feature = "use_serde")]
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{Operation, Operations, RawStorage, StorageBase};
+#[cfg(feature = "use_serde")]
+use super::{SerDe, Storage};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InMemoryStorageError {
+    /// The maximum number of entries is reached
+    Full,
+    KeyTooLong,
+    ValueTooLarge,
+    /// The write was failed with `InMemoryStorage::fail_next_write` or `InMemoryStorage::fail_after_writes`
+    Injected,
+    SerdeError(String),
+}
+
+impl fmt::Display for InMemoryStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "Storage full"),
+            Self::KeyTooLong => write!(f, "Key too long"),
+            Self::ValueTooLarge => write!(f, "Value too large"),
+            Self::Injected => write!(f, "Injected failure"),
+            Self::SerdeError(e) => write!(f, "SerDe error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InMemoryStorageError {}
+
+/// The limits emulated by an `InMemoryStorage`; all unlimited by default
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Limits {
+    pub max_entries: Option<usize>,
+    pub max_key_len: Option<usize>,
+    pub max_value_len: Option<usize>,
+}
+
+/// The contents of an `InMemoryStorage`, as returned by `InMemoryStorage::snapshot`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot(BTreeMap<String, Vec<u8>>);
+
+/// A `RawStorage` (and with a `SerDe`, also a `Storage`) keeping its values in a map on the heap.
+///
+/// Keys are enumerated in their lexicographic order, and transactions are applied atomically,
+/// unless disabled with `with_atomic_updates(false)`.
+pub struct InMemoryStorage<S = ()> {
+    entries: BTreeMap<String, Vec<u8>>,
+    limits: Limits,
+    atomic_updates: bool,
+    fail_next_write: bool,
+    fail_after_writes: Option<usize>,
+    serde: S,
+}
+
+impl InMemoryStorage {
+    pub const fn new() -> Self {
+        Self::with_limits(Limits {
+            max_entries: None,
+            max_key_len: None,
+            max_value_len: None,
+        })
+    }
+
+    pub const fn with_limits(limits: Limits) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            limits,
+            atomic_updates: true,
+            fail_next_write: false,
+            fail_after_writes: None,
+            serde: (),
+        }
+    }
+}
+
+impl Default for InMemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> InMemoryStorage<S> {
+    /// Makes the storage a typed `Storage`, serializing values with `serde`
+    pub fn with_serde<T>(self, serde: T) -> InMemoryStorage<T> {
+        InMemoryStorage {
+            entries: self.entries,
+            limits: self.limits,
+            atomic_updates: self.atomic_updates,
+            fail_next_write: self.fail_next_write,
+            fail_after_writes: self.fail_after_writes,
+            serde,
+        }
+    }
+
+    /// Whether `RawStorage::apply_atomically` is supported (the default), so that transactions
+    /// are journaled when disabled
+    pub fn with_atomic_updates(mut self, atomic_updates: bool) -> Self {
+        self.atomic_updates = atomic_updates;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Fails the next write (`set`, `set_raw`, `remove` or a transaction) with `InMemoryStorageError::Injected`
+    pub fn fail_next_write(&mut self) {
+        self.fail_next_write = true;
+    }
+
+    /// Fails the writes following the next `writes` writes, until `clear_failures` is called
+    pub fn fail_after_writes(&mut self, writes: usize) {
+        self.fail_after_writes = Some(writes);
+    }
+
+    pub fn clear_failures(&mut self) {
+        self.fail_next_write = false;
+        self.fail_after_writes = None;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.entries.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.entries = snapshot.0.clone();
+    }
+
+    fn write(&mut self) -> Result<(), InMemoryStorageError> {
+        if self.fail_next_write {
+            self.fail_next_write = false;
+
+            return Err(InMemoryStorageError::Injected);
+        }
+
+        match self.fail_after_writes.as_mut() {
+            Some(0) => Err(InMemoryStorageError::Injected),
+            Some(writes) => {
+                *writes -= 1;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check(&self, name: &str, len: usize, entries: usize) -> Result<(), InMemoryStorageError> {
+        if matches!(self.limits.max_key_len, Some(max) if name.len() > max) {
+            Err(InMemoryStorageError::KeyTooLong)
+        } else if matches!(self.limits.max_value_len, Some(max) if len > max) {
+            Err(InMemoryStorageError::ValueTooLarge)
+        } else if matches!(self.limits.max_entries, Some(max) if entries > max) {
+            Err(InMemoryStorageError::Full)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn insert(&mut self, name: &str, buf: &[u8]) -> Result<bool, InMemoryStorageError> {
+        let entries = self.entries.len() + usize::from(!self.entries.contains_key(name));
+        self.check(name, buf.len(), entries)?;
+        self.write()?;
+
+        Ok(self
+            .entries
+            .insert(name.to_owned(), buf.to_owned())
+            .is_some())
+    }
+}
+
+impl<S> StorageBase for InMemoryStorage<S> {
+    type Error = InMemoryStorageError;
+
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        Ok(self.entries.contains_key(name))
+    }
+
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        self.write()?;
+
+        Ok(self.entries.remove(name).is_some())
+    }
+
+    fn for_each_key<F>(&self, prefix: &str, mut f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        for name in self
+            .entries
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(name, _)| name)
+        {
+            if !name.starts_with(prefix) || f(name).is_break() {
+                break;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        self.write()?;
+
+        let len = self.entries.len();
+        self.entries.retain(|name, _| !name.starts_with(prefix));
+
+        Ok(Some(len - self.entries.len()))
+    }
+
+    fn count(&self) -> Result<Option<usize>, Self::Error> {
+        Ok(Some(self.entries.len()))
+    }
+}
+
+impl<S> RawStorage for InMemoryStorage<S> {
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        Ok(self.entries.get(name).map(Vec::len))
+    }
+
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        match self.entries.get(name) {
+            Some(value) if value.len() > buf.len() => Err(InMemoryStorageError::ValueTooLarge),
+            Some(value) => {
+                buf[..value.len()].copy_from_slice(value);
+
+                Ok(Some(&buf[..value.len()]))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        self.insert(name, buf)
+    }
+
+    fn get_raw_at(
+        &self,
+        name: &str,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        Ok(self.entries.get(name).map(|value| {
+            let value = value.get(offset..).unwrap_or(&[]);
+            let len = value.len().min(buf.len());
+
+            buf[..len].copy_from_slice(&value[..len]);
+
+            len
+        }))
+    }
+
+    fn apply_atomically(&mut self, operations: Operations<'_>) -> Result<bool, Self::Error> {
+        if !self.atomic_updates {
+            return Ok(false);
+        }
+
+        let mut entries = self.entries.clone();
+
+        for operation in operations.iter() {
+            match operation {
+                Operation::Set(name, value) => {
+                    let len = entries.len() + usize::from(!entries.contains_key(name));
+                    self.check(name, value.len(), len)?;
+
+                    entries.insert(name.to_owned(), value.to_owned());
+                }
+                Operation::Remove(name) => {
+                    entries.remove(name);
+                }
+            }
+        }
+
+        self.write()?;
+        self.entries = entries;
+
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<S> Storage for InMemoryStorage<S>
+where
+    S: SerDe,
+{
+    fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.entries
+            .get(name)
+            .map(|value| {
+                self.serde
+                    .deserialize(value)
+                    .map_err(|e| InMemoryStorageError::SerdeError(format!("{e:?}")))
+            })
+            .transpose()
+    }
+
+    fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+    where
+        T: Serialize,
+    {
+        let mut buf = vec![0; self.limits.max_value_len.unwrap_or(4096)];
+
+        let value = self
+            .serde
+            .serialize(&mut buf, value)
+            .map_err(|e| InMemoryStorageError::SerdeError(format!("{e:?}")))?;
+
+        let value: Vec<u8> = value.to_owned();
+
+        self.insert(name, &value)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::storage::Transaction;
+
+    use super::*;
+
+    fn keys<S>(storage: &InMemoryStorage<S>) -> Vec<String> {
+        let mut keys = Vec::new();
+
+        storage
+            .for_each_key("", |key| {
+                keys.push(key.to_owned());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+
+        keys
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let mut storage = InMemoryStorage::default();
+        let mut buf = [0; 8];
+
+        assert!(!storage.set_raw("key", b"value").unwrap());
+        assert!(storage.set_raw("key", b"other").unwrap());
+        assert_eq!(
+            storage.get_raw("key", &mut buf).unwrap(),
+            Some(&b"other"[..])
+        );
+        assert_eq!(
+            storage.get_raw("key", &mut buf[..4]),
+            Err(InMemoryStorageError::ValueTooLarge)
+        );
+        assert_eq!(storage.get_raw("none", &mut buf).unwrap(), None);
+
+        assert_eq!(storage.count().unwrap(), Some(1));
+        assert!(storage.remove("key").unwrap());
+        assert!(!storage.remove("key").unwrap());
+        assert_eq!(storage.count().unwrap(), Some(0));
+    }
+
+    #[test]
+    fn limits() {
+        let mut storage = InMemoryStorage::with_limits(Limits {
+            max_entries: Some(2),
+            max_key_len: Some(4),
+            max_value_len: Some(3),
+        });
+
+        assert_eq!(storage.limits().max_entries, Some(2));
+
+        storage.set_raw("a", b"1").unwrap();
+        storage.set_raw("b", b"2").unwrap();
+
+        assert_eq!(storage.set_raw("c", b"3"), Err(InMemoryStorageError::Full));
+        // Replacing an entry does not need a new one
+        assert!(storage.set_raw("b", b"22").unwrap());
+        assert_eq!(
+            storage.set_raw("b", b"2222"),
+            Err(InMemoryStorageError::ValueTooLarge)
+        );
+
+        storage.remove("a").unwrap();
+
+        assert_eq!(
+            storage.set_raw("ccccc", b"3"),
+            Err(InMemoryStorageError::KeyTooLong)
+        );
+        storage.set_raw("cccc", b"333").unwrap();
+
+        assert_eq!(keys(&storage), ["b", "cccc"]);
+
+        // Transactions are checked as a whole
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.remove("b").unwrap();
+        transaction.set_raw("d", b"4").unwrap();
+        transaction.set_raw("e", b"5").unwrap();
+
+        assert!(matches!(
+            transaction.commit(),
+            Err(crate::storage::TransactionError::StorageError(
+                InMemoryStorageError::Full
+            ))
+        ));
+        assert_eq!(keys(&storage), ["b", "cccc"]);
+    }
+
+    #[test]
+    fn fail_next_write() {
+        let mut storage = InMemoryStorage::new();
+
+        storage.fail_next_write();
+
+        assert_eq!(
+            storage.set_raw("a", b"1"),
+            Err(InMemoryStorageError::Injected)
+        );
+        assert!(!storage.contains("a").unwrap());
+
+        // Only the next one
+        storage.set_raw("a", b"1").unwrap();
+
+        storage.fail_next_write();
+        assert_eq!(storage.remove("a"), Err(InMemoryStorageError::Injected));
+        storage.fail_next_write();
+        assert_eq!(storage.remove_all(""), Err(InMemoryStorageError::Injected));
+        assert!(storage.contains("a").unwrap());
+
+        // Reads never fail
+        storage.fail_next_write();
+        assert_eq!(storage.len("a").unwrap(), Some(1));
+        storage.clear_failures();
+        storage.remove("a").unwrap();
+    }
+
+    #[test]
+    fn fail_after_writes() {
+        let mut storage = InMemoryStorage::new();
+
+        storage.fail_after_writes(2);
+
+        storage.set_raw("a", b"1").unwrap();
+        storage.set_raw("b", b"2").unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(
+                storage.set_raw("c", b"3"),
+                Err(InMemoryStorageError::Injected)
+            );
+        }
+
+        storage.clear_failures();
+        storage.set_raw("c", b"3").unwrap();
+
+        assert_eq!(keys(&storage), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("a", b"1").unwrap();
+        storage.set_raw("b", b"2").unwrap();
+
+        let snapshot = storage.snapshot();
+        assert_eq!(snapshot, storage.snapshot());
+
+        storage.remove("a").unwrap();
+        storage.set_raw("b", b"22").unwrap();
+        storage.set_raw("c", b"3").unwrap();
+        assert_ne!(snapshot, storage.snapshot());
+
+        storage.restore(&snapshot);
+
+        let mut buf = [0; 2];
+        assert_eq!(keys(&storage), ["a", "b"]);
+        assert_eq!(storage.get_raw("b", &mut buf).unwrap(), Some(&b"2"[..]));
+
+        // Snapshots can set up other storages
+        let mut other = InMemoryStorage::new();
+        other.restore(&snapshot);
+        assert_eq!(other.snapshot(), snapshot);
+
+        assert_eq!(InMemoryStorage::new().snapshot(), Snapshot::default());
+    }
+
+    #[test]
+    fn atomic_updates_disabled() {
+        let mut storage = InMemoryStorage::new().with_atomic_updates(false);
+
+        // Only the journal gets written
+        storage.fail_after_writes(1);
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("a", b"1").unwrap();
+
+        assert!(transaction.commit().is_err());
+        assert_eq!(keys(&storage), [crate::storage::JOURNAL_KEY]);
+    }
+
+    #[test]
+    fn remove_all() {
+        let mut storage = InMemoryStorage::new();
+
+        for key in ["user/a", "user/b", "users", "wifi"] {
+            storage.set_raw(key, b"1").unwrap();
+        }
+
+        assert_eq!(storage.remove_all("user/").unwrap(), Some(2));
+        assert_eq!(keys(&storage), ["users", "wifi"]);
+        assert_eq!(storage.clear().unwrap(), Some(2));
+        assert_eq!(storage.count().unwrap(), Some(0));
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn typed() {
+        use crate::storage::Storage;
+
+        let mut storage = InMemoryStorage::with_limits(Limits {
+            max_value_len: Some(8),
+            ..Default::default()
+        });
+
+        storage.set_raw("raw", b"7").unwrap();
+
+        // The entries are kept
+        let mut storage = storage.with_serde(crate::test_json::JsonSerDe);
+
+        assert_eq!(storage.get::<u32>("raw").unwrap(), Some(7));
+
+        storage.set("boots", &42_u32).unwrap();
+        assert_eq!(storage.get::<u32>("boots").unwrap(), Some(42));
+        assert_eq!(storage.get::<u32>("none").unwrap(), None);
+
+        // Larger than the maximum value length
+        assert!(matches!(
+            storage.set("name", &"a long name"),
+            Err(InMemoryStorageError::SerdeError(_))
+        ));
+
+        storage.set("name", &"short").unwrap();
+        assert!(matches!(
+            storage.get::<u32>("name"),
+            Err(InMemoryStorageError::SerdeError(_))
+        ));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(InMemoryStorageError::Full.to_string(), "Storage full");
+        assert_eq!(
+            InMemoryStorageError::Injected.to_string(),
+            "Injected failure"
+        );
+        assert_eq!(
+            InMemoryStorageError::SerdeError("EOF".into()).to_string(),
+            "SerDe error: EOF"
+        );
+    }
+}
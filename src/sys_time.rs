@@ -1,5 +1,9 @@
+use core::fmt::Debug;
 use core::time::Duration;
 
+/// The wall clock, i.e. the time since the UNIX epoch
+///
+/// It might be stepped (e.g. by SNTP), so it is not suitable for measuring durations: see `Monotonic`.
 pub trait SystemTime {
     fn now(&self) -> Duration;
 }
@@ -12,3 +16,364 @@ where
         (*self).now()
     }
 }
+
+/// A clock which never goes backwards, for measuring durations (timeouts, debounce windows, etc.)
+///
+/// Its epoch is arbitrary, typically the boot of the device, so its readings are only meaningful relative to each other.
+pub trait Monotonic {
+    fn now(&self) -> Duration;
+}
+
+impl<M> Monotonic for &M
+where
+    M: Monotonic,
+{
+    fn now(&self) -> Duration {
+        (*self).now()
+    }
+}
+
+/// Uses a `SystemTime` as a `Monotonic` clock, for wall clocks which are known not to be stepped
+/// (e.g. because they are never synced, and start at 0 on boot)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AssumeMonotonic<S>(pub S);
+
+impl<S> Monotonic for AssumeMonotonic<S>
+where
+    S: SystemTime,
+{
+    fn now(&self) -> Duration {
+        self.0.now()
+    }
+}
+
+/// The monotonic clock of the standard library, i.e. `std::time::Instant`
+///
+/// Its epoch is the first time any `StdMonotonic` was read.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StdMonotonic;
+
+#[cfg(feature = "std")]
+impl Monotonic for StdMonotonic {
+    fn now(&self) -> Duration {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+
+        EPOCH.get_or_init(std::time::Instant::now).elapsed()
+    }
+}
+
+/// A step of the wall clock, as reported to the subscribers of `SystemTimeSteps`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Step {
+    /// The time since the UNIX epoch right before the step
+    pub from: Duration,
+    /// The time since the UNIX epoch the clock was stepped to
+    pub to: Duration,
+}
+
+impl Step {
+    /// The size of the step in microseconds, positive when the clock was stepped forward
+    pub fn delta(&self) -> i64 {
+        self.to.as_micros() as i64 - self.from.as_micros() as i64
+    }
+}
+
+/// Sets the wall clock, e.g. from a time entered by the user or from a GNSS module
+pub trait SystemTimeSetter {
+    type Error: Debug;
+
+    /// Steps the clock to `now`, the time since the UNIX epoch, and reports the step to the subscribers of `SystemTimeSteps`
+    fn set_time(&self, now: Duration) -> Result<(), Self::Error>;
+
+    /// Slews the clock by `delta` microseconds (positive for advancing it), for small corrections
+    ///
+    /// The clock stays continuous, so no step is reported.
+    fn adjust(&self, delta: i64) -> Result<(), Self::Error>;
+}
+
+impl<S> SystemTimeSetter for &S
+where
+    S: SystemTimeSetter,
+{
+    type Error = S::Error;
+
+    fn set_time(&self, now: Duration) -> Result<(), Self::Error> {
+        (*self).set_time(now)
+    }
+
+    fn adjust(&self, delta: i64) -> Result<(), Self::Error> {
+        (*self).adjust(delta)
+    }
+}
+
+/// Reports the steps of the wall clock, so that whatever depends on it (TLS, schedulers, `utils::wallclock`) can react
+pub trait SystemTimeSteps {
+    type Error: Debug;
+
+    type Subscription<'a>
+    where
+        Self: 'a;
+
+    /// Calls `callback` on each step of the clock, until the subscription is dropped
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(Step) + Send + 'static;
+}
+
+impl<S> SystemTimeSteps for &S
+where
+    S: SystemTimeSteps,
+{
+    type Error = S::Error;
+
+    type Subscription<'a> = S::Subscription<'a> where Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(Step) + Send + 'static,
+    {
+        (**self).subscribe(callback)
+    }
+}
+
+#[cfg(feature = "std")]
+pub use simulated::*;
+
+#[cfg(feature = "std")]
+mod simulated {
+    use core::convert::Infallible;
+    use core::time::Duration;
+
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::{Step, SystemTime, SystemTimeSetter, SystemTimeSteps};
+
+    type Callback = Box<dyn FnMut(Step) + Send>;
+
+    #[derive(Default)]
+    struct State {
+        /// Microseconds, relative to the host clock
+        offset: AtomicI64,
+        subscribers: Mutex<(usize, Vec<(usize, Callback)>)>,
+    }
+
+    /// A settable wall clock for host tests, which follows the host clock plus an offset, without ever setting the host clock
+    ///
+    /// Clones share the same offset and subscribers.
+    #[derive(Clone, Default)]
+    pub struct SimulatedSystemTime(Arc<State>);
+
+    impl SimulatedSystemTime {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        fn host_now() -> Duration {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+        }
+
+        fn offset_by(time: Duration, micros: i64) -> Duration {
+            let delta = Duration::from_micros(micros.unsigned_abs());
+
+            if micros < 0 {
+                time.saturating_sub(delta)
+            } else {
+                time + delta
+            }
+        }
+    }
+
+    impl SystemTime for SimulatedSystemTime {
+        fn now(&self) -> Duration {
+            Self::offset_by(Self::host_now(), self.0.offset.load(Ordering::SeqCst))
+        }
+    }
+
+    impl SystemTimeSetter for SimulatedSystemTime {
+        type Error = Infallible;
+
+        fn set_time(&self, now: Duration) -> Result<(), Self::Error> {
+            let host = Self::host_now();
+            let offset = now.as_micros() as i64 - host.as_micros() as i64;
+
+            let step = Step {
+                from: Self::offset_by(host, self.0.offset.swap(offset, Ordering::SeqCst)),
+                to: now,
+            };
+
+            if step.from != step.to {
+                for (_, callback) in &mut self.0.subscribers.lock().unwrap().1 {
+                    callback(step);
+                }
+            }
+
+            Ok(())
+        }
+
+        fn adjust(&self, delta: i64) -> Result<(), Self::Error> {
+            self.0.offset.fetch_add(delta, Ordering::SeqCst);
+
+            Ok(())
+        }
+    }
+
+    impl SystemTimeSteps for SimulatedSystemTime {
+        type Error = Infallible;
+
+        type Subscription<'a> = SimulatedSubscription where Self: 'a;
+
+        fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+        where
+            F: FnMut(Step) + Send + 'static,
+        {
+            let mut subscribers = self.0.subscribers.lock().unwrap();
+
+            let id = subscribers.0;
+            subscribers.0 += 1;
+            subscribers.1.push((id, Box::new(callback)));
+
+            Ok(SimulatedSubscription(self.0.clone(), id))
+        }
+    }
+
+    /// Unsubscribes from the steps of a `SimulatedSystemTime` when dropped
+    pub struct SimulatedSubscription(Arc<State>, usize);
+
+    impl Drop for SimulatedSubscription {
+        fn drop(&mut self) {
+            self.0
+                .subscribers
+                .lock()
+                .unwrap()
+                .1
+                .retain(|(id, _)| *id != self.1);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn std_monotonic() {
+        let start = StdMonotonic.now();
+
+        std::thread::sleep(Duration::from_millis(20));
+
+        let now = StdMonotonic.now();
+        assert!(now >= start + Duration::from_millis(20));
+
+        // All the readings share the same epoch
+        let other = StdMonotonic;
+        assert!(other.now() >= now);
+    }
+
+    #[test]
+    fn assume_monotonic() {
+        let clock = SimulatedSystemTime::new();
+        clock.set_time(Duration::from_secs(1_700_000_000)).unwrap();
+
+        let monotonic = AssumeMonotonic(&clock);
+
+        let now = Monotonic::now(&monotonic);
+        assert!(now >= Duration::from_secs(1_700_000_000));
+        assert!(now < Duration::from_secs(1_700_000_060));
+
+        // Trusted as is, even when the clock is stepped back
+        clock.set_time(Duration::from_secs(60)).unwrap();
+        assert!(Monotonic::now(&monotonic) < now);
+    }
+
+    fn near(time: Duration, expected: Duration) -> bool {
+        time >= expected && time < expected + Duration::from_secs(5)
+    }
+
+    #[test]
+    fn simulated_set() {
+        let clock = SimulatedSystemTime::new();
+
+        let host = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        assert!(near(SystemTime::now(&clock), host));
+
+        clock.set_time(Duration::from_secs(1_000)).unwrap();
+        assert!(near(SystemTime::now(&clock), Duration::from_secs(1_000)));
+
+        // Follows the host clock from there on
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(SystemTime::now(&clock) >= Duration::from_millis(1_000_020));
+
+        // Clones share the offset
+        let other = clock.clone();
+        other.set_time(Duration::from_secs(5_000)).unwrap();
+        assert!(near(SystemTime::now(&clock), Duration::from_secs(5_000)));
+
+        // Saturates at the epoch
+        clock.set_time(Duration::ZERO).unwrap();
+        clock.adjust(-1_000_000_000).unwrap();
+        assert_eq!(SystemTime::now(&clock), Duration::ZERO);
+    }
+
+    #[test]
+    fn simulated_adjust() {
+        let clock = SimulatedSystemTime::new();
+        clock.set_time(Duration::from_secs(1_000)).unwrap();
+
+        clock.adjust(-500_000_000).unwrap();
+        assert!(near(SystemTime::now(&clock), Duration::from_secs(500)));
+
+        clock.adjust(2_000_000_000).unwrap();
+        assert!(near(SystemTime::now(&clock), Duration::from_secs(2_500)));
+    }
+
+    #[test]
+    fn simulated_steps() {
+        let clock = SimulatedSystemTime::new();
+        clock.set_time(Duration::from_secs(1_000)).unwrap();
+
+        let steps = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let subscription = {
+            let steps = steps.clone();
+
+            clock
+                .subscribe(move |step| steps.lock().unwrap().push(step))
+                .unwrap()
+        };
+
+        clock.set_time(Duration::from_secs(400)).unwrap();
+        clock.set_time(Duration::from_secs(2_000)).unwrap();
+
+        // Slewing is not a step
+        clock.adjust(1_000_000).unwrap();
+
+        {
+            let steps = steps.lock().unwrap();
+
+            assert_eq!(steps.len(), 2);
+
+            assert!(near(steps[0].from, Duration::from_secs(1_000)));
+            assert_eq!(steps[0].to, Duration::from_secs(400));
+            assert!(steps[0].delta() <= -600_000_000 && steps[0].delta() > -605_000_000);
+
+            assert!(near(steps[1].from, Duration::from_secs(400)));
+            assert_eq!(steps[1].to, Duration::from_secs(2_000));
+            assert!(steps[1].delta() > 1_595_000_000 && steps[1].delta() <= 1_600_000_000);
+        }
+
+        // Not reported once unsubscribed
+        drop(subscription);
+        clock.set_time(Duration::from_secs(100)).unwrap();
+
+        assert_eq!(steps.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+//! An in-memory event bus for tests
+//!
+//! The payloads posted are recorded, and delivered to the subscribers right away, from the posting thread.
+
+use core::time::Duration;
+
+use std::sync::{Arc, Mutex};
+
+use crate::event_bus::{ErrorType, EventBus, Postbox};
+
+type Subscribers<P> = Vec<(usize, Box<dyn FnMut(&P) + Send>)>;
+
+struct State<P> {
+    posted: Vec<P>,
+    /// Refuse the posts (with `Ok(false)`) once that many payloads were posted
+    capacity: Option<usize>,
+    fail: bool,
+    next_id: usize,
+}
+
+pub struct MockBus<P> {
+    state: Mutex<State<P>>,
+    subscribers: Arc<Mutex<Subscribers<P>>>,
+}
+
+impl<P> MockBus<P> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                posted: Vec::new(),
+                capacity: None,
+                fail: false,
+                next_id: 0,
+            }),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn set_capacity(&self, capacity: Option<usize>) {
+        self.state.lock().unwrap().capacity = capacity;
+    }
+
+    /// Fail the posts and the subscriptions
+    pub fn set_fail(&self, fail: bool) {
+        self.state.lock().unwrap().fail = fail;
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+}
+
+impl<P> MockBus<P>
+where
+    P: Clone,
+{
+    pub fn posted(&self) -> Vec<P> {
+        self.state.lock().unwrap().posted.clone()
+    }
+}
+
+impl<P> Default for MockBus<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> ErrorType for MockBus<P> {
+    type Error = ();
+}
+
+impl<P> Postbox<P> for MockBus<P>
+where
+    P: Clone,
+{
+    fn post(&self, payload: &P, _wait: Option<Duration>) -> Result<bool, Self::Error> {
+        {
+            let mut state = self.state.lock().unwrap();
+
+            if state.fail {
+                return Err(());
+            }
+
+            if matches!(state.capacity, Some(capacity) if state.posted.len() >= capacity) {
+                return Ok(false);
+            }
+
+            state.posted.push(payload.clone());
+        }
+
+        for (_, subscriber) in self.subscribers.lock().unwrap().iter_mut() {
+            subscriber(payload);
+        }
+
+        Ok(true)
+    }
+}
+
+impl<P> EventBus<P> for MockBus<P> {
+    type Subscription<'a> = MockSubscription<P> where Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + Send + 'static,
+    {
+        let id = {
+            let mut state = self.state.lock().unwrap();
+
+            if state.fail {
+                return Err(());
+            }
+
+            state.next_id += 1;
+            state.next_id
+        };
+
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push((id, Box::new(callback)));
+
+        Ok(MockSubscription(self.subscribers.clone(), id))
+    }
+}
+
+/// Unsubscribes when dropped
+pub struct MockSubscription<P>(Arc<Mutex<Subscribers<P>>>, usize);
+
+impl<P> Drop for MockSubscription<P> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().retain(|(id, _)| *id != self.1);
+    }
+}
@@ -0,0 +1,520 @@
+//! In-memory HTTP connections for tests
+//!
+//! `MockRequest` is the server side of a single request, whose response is recorded.
+//! `MockServer` is the client side of the connections to a server, which answers each request
+//! with the `MockResponse` returned by its closure, and records the requests.
+
+use crate::http::client;
+use crate::http::server;
+use crate::http::{Headers, Method, Query, Status};
+use crate::io::{ErrorKind, ErrorType, Read, Write};
+
+fn find<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn owned(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// A body being read, in chunks of at most `chunk` bytes
+pub struct MockBody {
+    data: Vec<u8>,
+    read: usize,
+    pub chunk: usize,
+}
+
+impl MockBody {
+    fn new(data: &[u8]) -> Self {
+        Self {
+            data: data.to_vec(),
+            read: 0,
+            chunk: usize::MAX,
+        }
+    }
+}
+
+impl ErrorType for MockBody {
+    type Error = ErrorKind;
+}
+
+impl Read for MockBody {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = buf.len().min(self.chunk).min(self.data.len() - self.read);
+
+        buf[..len].copy_from_slice(&self.data[self.read..self.read + len]);
+        self.read += len;
+
+        Ok(len)
+    }
+}
+
+/// The method, URI and headers of a request
+pub struct RequestHead {
+    pub method: Method,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Query for RequestHead {
+    fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    fn method(&self) -> Method {
+        self.method
+    }
+}
+
+impl Headers for RequestHead {
+    fn header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+}
+
+/// The server side of a request
+pub struct MockRequest {
+    pub head: RequestHead,
+    pub body: MockBody,
+    /// The status of the response, once initiated
+    pub status: Option<u16>,
+    pub message: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub response: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn new(method: Method, uri: &str) -> Self {
+        Self {
+            head: RequestHead {
+                method,
+                uri: uri.into(),
+                headers: Vec::new(),
+            },
+            body: MockBody::new(&[]),
+            status: None,
+            message: None,
+            headers: Vec::new(),
+            response: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.head.headers.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Sets the body, along with its `Content-Length`
+    pub fn body(self, body: &[u8]) -> Self {
+        let mut request = self.header("Content-Length", body.len());
+        request.body = MockBody::new(body);
+
+        request
+    }
+
+    /// The header of the response named `name`
+    pub fn response_header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+
+    pub fn response_str(&self) -> &str {
+        core::str::from_utf8(&self.response).unwrap()
+    }
+}
+
+impl Query for MockRequest {
+    fn uri(&self) -> &str {
+        self.head.uri()
+    }
+
+    fn method(&self) -> Method {
+        self.head.method()
+    }
+}
+
+impl Headers for MockRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.head.header(name)
+    }
+}
+
+impl ErrorType for MockRequest {
+    type Error = ErrorKind;
+}
+
+impl Read for MockRequest {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.body.read(buf)
+    }
+}
+
+impl Write for MockRequest {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        assert!(self.status.is_some(), "Response not initiated");
+
+        self.response.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl server::Connection for MockRequest {
+    type Headers = RequestHead;
+
+    type Read = MockBody;
+
+    type RawConnectionError = ErrorKind;
+
+    type RawConnection = Self;
+
+    fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+        (&self.head, &mut self.body)
+    }
+
+    fn initiate_response(
+        &mut self,
+        status: u16,
+        message: Option<&str>,
+        headers: &[(&str, &str)],
+    ) -> Result<(), Self::Error> {
+        assert!(self.status.is_none(), "Response already initiated");
+
+        self.status = Some(status);
+        self.message = message.map(Into::into);
+        self.headers = owned(headers);
+
+        Ok(())
+    }
+
+    fn is_response_initiated(&self) -> bool {
+        self.status.is_some()
+    }
+
+    fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+        Err(ErrorKind::Unsupported)
+    }
+}
+
+/// A request received by a `MockServer`
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: Method,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Recorded {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+}
+
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: &[u8]) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_vec(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.into(), value.to_string()));
+        self
+    }
+}
+
+/// The status and headers of a response
+pub struct ResponseHead {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Status for ResponseHead {
+    fn status(&self) -> u16 {
+        self.status
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Headers for ResponseHead {
+    fn header(&self, name: &str) -> Option<&str> {
+        find(&self.headers, name)
+    }
+}
+
+/// The client side of the connections to a server answering with `F`
+pub struct MockServer<F> {
+    server: F,
+    pub requests: Vec<Recorded>,
+    head: Option<ResponseHead>,
+    body: MockBody,
+}
+
+impl<F> MockServer<F>
+where
+    F: FnMut(&Recorded) -> MockResponse,
+{
+    pub fn new(server: F) -> client::Client<Self> {
+        client::Client::wrap(Self {
+            server,
+            requests: Vec::new(),
+            head: None,
+            body: MockBody::new(&[]),
+        })
+    }
+
+    fn head(&self) -> &ResponseHead {
+        self.head.as_ref().expect("Response not initiated")
+    }
+}
+
+impl<F> Status for MockServer<F>
+where
+    F: FnMut(&Recorded) -> MockResponse,
+{
+    fn status(&self) -> u16 {
+        self.head().status()
+    }
+
+    fn status_message(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<F> Headers for MockServer<F>
+where
+    F: FnMut(&Recorded) -> MockResponse,
+{
+    fn header(&self, name: &str) -> Option<&str> {
+        self.head().header(name)
+    }
+}
+
+impl<F> ErrorType for MockServer<F> {
+    type Error = ErrorKind;
+}
+
+impl<F> Read for MockServer<F> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.body.read(buf)
+    }
+}
+
+impl<F> Write for MockServer<F> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let request = self.requests.last_mut().expect("Request not initiated");
+        request.body.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<F> client::Connection for MockServer<F>
+where
+    F: FnMut(&Recorded) -> MockResponse,
+{
+    type Headers = ResponseHead;
+
+    type Read = MockBody;
+
+    type RawConnectionError = ErrorKind;
+
+    type RawConnection = Self;
+
+    fn initiate_request(
+        &mut self,
+        method: Method,
+        uri: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), Self::Error> {
+        self.requests.push(Recorded {
+            method,
+            uri: uri.into(),
+            headers: owned(headers),
+            body: Vec::new(),
+        });
+        self.head = None;
+
+        Ok(())
+    }
+
+    fn is_request_initiated(&self) -> bool {
+        self.head.is_none() && !self.requests.is_empty()
+    }
+
+    fn initiate_response(&mut self) -> Result<(), Self::Error> {
+        let response = (self.server)(self.requests.last().unwrap());
+
+        self.head = Some(ResponseHead {
+            status: response.status,
+            headers: response.headers,
+        });
+        self.body = MockBody::new(&response.body);
+
+        Ok(())
+    }
+
+    fn is_response_initiated(&self) -> bool {
+        self.head.is_some()
+    }
+
+    fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+        (
+            self.head.as_ref().expect("Response not initiated"),
+            &mut self.body,
+        )
+    }
+
+    fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+        Err(ErrorKind::Unsupported)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod asynch {
+    use crate::http::client::asynch as client;
+    use crate::http::server::asynch as server;
+    use crate::http::Method;
+    use crate::io::asynch::{Read, Write};
+    use crate::io::ErrorKind;
+
+    use super::{
+        MockBody, MockRequest, MockResponse, MockServer, Recorded, RequestHead, ResponseHead,
+    };
+
+    impl Read for MockBody {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            crate::io::Read::read(self, buf)
+        }
+    }
+
+    impl Read for MockRequest {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            crate::io::Read::read(self, buf)
+        }
+    }
+
+    impl Write for MockRequest {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            crate::io::Write::write(self, buf)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl server::Connection for MockRequest {
+        type Headers = RequestHead;
+
+        type Read = MockBody;
+
+        type RawConnectionError = ErrorKind;
+
+        type RawConnection = Self;
+
+        fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+            crate::http::server::Connection::split(self)
+        }
+
+        async fn initiate_response(
+            &mut self,
+            status: u16,
+            message: Option<&str>,
+            headers: &[(&str, &str)],
+        ) -> Result<(), Self::Error> {
+            crate::http::server::Connection::initiate_response(self, status, message, headers)
+        }
+
+        fn is_response_initiated(&self) -> bool {
+            self.status.is_some()
+        }
+
+        fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+            Err(ErrorKind::Unsupported)
+        }
+    }
+
+    impl<F> Read for MockServer<F> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            crate::io::Read::read(self, buf)
+        }
+    }
+
+    impl<F> Write for MockServer<F> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            crate::io::Write::write(self, buf)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl<F> client::Connection for MockServer<F>
+    where
+        F: FnMut(&Recorded) -> MockResponse,
+    {
+        type Headers = ResponseHead;
+
+        type Read = MockBody;
+
+        type RawConnectionError = ErrorKind;
+
+        type RawConnection = Self;
+
+        async fn initiate_request(
+            &mut self,
+            method: Method,
+            uri: &str,
+            headers: &[(&str, &str)],
+        ) -> Result<(), Self::Error> {
+            crate::http::client::Connection::initiate_request(self, method, uri, headers)
+        }
+
+        fn is_request_initiated(&self) -> bool {
+            crate::http::client::Connection::is_request_initiated(self)
+        }
+
+        async fn initiate_response(&mut self) -> Result<(), Self::Error> {
+            crate::http::client::Connection::initiate_response(self)
+        }
+
+        fn is_response_initiated(&self) -> bool {
+            self.head.is_some()
+        }
+
+        fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+            crate::http::client::Connection::split(self)
+        }
+
+        fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+            Err(ErrorKind::Unsupported)
+        }
+    }
+}
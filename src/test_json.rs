@@ -0,0 +1,878 @@
+//! A minimal JSON serializer and deserializer, so that the tests can (de)serialize without depending on `serde_json`
+//!
+//! Numbers are integers or floats, strings support the escape sequences of `json_io` (plus `\uXXXX` outside
+//! of the surrogates), and enums are externally tagged, as with `serde_json`.
+//!
+//! `to_vec_compact` and `from_slice_compact` report the format as not human-readable, as a binary format would,
+//! so that the types with a compact representation can be tested in it as well.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use serde::de::value::{Error, MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::storage::SerDe;
+use crate::utils::json_io::BorrowingDe;
+
+pub fn to_vec<T>(value: &T) -> Vec<u8>
+where
+    T: Serialize + ?Sized,
+{
+    let mut out = Vec::new();
+
+    value.serialize(&mut Serializer(&mut out)).unwrap();
+
+    out
+}
+
+pub fn from_slice<'a, T>(data: &'a [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    let data = core::str::from_utf8(data).map_err(de::Error::custom)?;
+
+    let mut parser = Parser(data);
+    let value = parser.value()?;
+
+    if !parser.0.trim_start().is_empty() {
+        return Err(de::Error::custom("Trailing characters"));
+    }
+
+    T::deserialize(value)
+}
+
+std::thread_local! {
+    static COMPACT: Cell<bool> = const { Cell::new(false) };
+}
+
+fn human_readable() -> bool {
+    !COMPACT.with(Cell::get)
+}
+
+fn compact<R>(f: impl FnOnce() -> R) -> R {
+    COMPACT.with(|compact| compact.set(true));
+    let result = f();
+    COMPACT.with(|compact| compact.set(false));
+
+    result
+}
+
+pub fn to_vec_compact<T>(value: &T) -> Vec<u8>
+where
+    T: Serialize + ?Sized,
+{
+    compact(|| to_vec(value))
+}
+
+pub fn from_slice_compact<'a, T>(data: &'a [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    compact(|| from_slice(data))
+}
+
+/// A `SerDe` and `BorrowingDe` over the JSON of this module
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonSerDe;
+
+impl SerDe for JsonSerDe {
+    type Error = Error;
+
+    fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
+    where
+        T: Serialize,
+    {
+        let data = to_vec(value);
+
+        let slice = slice
+            .get_mut(..data.len())
+            .ok_or_else(|| <Error as ser::Error>::custom("Buffer too small"))?;
+        slice.copy_from_slice(&data);
+
+        Ok(slice)
+    }
+
+    fn deserialize<T>(&self, slice: &[u8]) -> Result<T, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        from_slice(slice)
+    }
+}
+
+impl BorrowingDe for JsonSerDe {
+    type Error = Error;
+
+    fn deserialize<'a, T>(&self, slice: &'a [u8]) -> Result<T, Self::Error>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(slice)
+    }
+}
+
+struct Serializer<'a>(&'a mut Vec<u8>);
+
+impl Serializer<'_> {
+    fn str(&mut self, s: &str) {
+        self.0.push(b'"');
+
+        for c in s.chars() {
+            match c {
+                '"' => self.0.extend_from_slice(b"\\\""),
+                '\\' => self.0.extend_from_slice(b"\\\\"),
+                '\n' => self.0.extend_from_slice(b"\\n"),
+                '\r' => self.0.extend_from_slice(b"\\r"),
+                '\t' => self.0.extend_from_slice(b"\\t"),
+                c if (c as u32) < 0x20 => {
+                    self.0.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes())
+                }
+                c => self
+                    .0
+                    .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        self.0.push(b'"');
+    }
+
+    fn display(&mut self, value: impl core::fmt::Display) {
+        self.0.extend_from_slice(value.to_string().as_bytes());
+    }
+}
+
+/// Serializes the elements of an array or the fields of an object, with the separators between them
+struct Compound<'a, 'b> {
+    ser: &'a mut Serializer<'b>,
+    first: bool,
+    close: &'static [u8],
+}
+
+impl Compound<'_, '_> {
+    fn separator(&mut self) {
+        if !core::mem::replace(&mut self.first, false) {
+            self.ser.0.push(b',');
+        }
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.separator();
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T>(&mut self, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.separator();
+        self.ser.str(key);
+        self.ser.0.push(b':');
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.ser.0.extend_from_slice(self.close);
+
+        Ok(())
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, 'b>;
+    type SerializeTuple = Compound<'a, 'b>;
+    type SerializeTupleStruct = Compound<'a, 'b>;
+    type SerializeTupleVariant = Compound<'a, 'b>;
+    type SerializeMap = Compound<'a, 'b>;
+    type SerializeStruct = Compound<'a, 'b>;
+    type SerializeStructVariant = Compound<'a, 'b>;
+
+    fn is_human_readable(&self) -> bool {
+        human_readable()
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.display(v);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.display(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.display(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.display(v);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        v.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.0.extend_from_slice(b"null");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.push(b'{');
+        self.str(variant);
+        self.0.push(b':');
+        value.serialize(&mut *self)?;
+        self.0.push(b'}');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.0.push(b'[');
+
+        Ok(Compound {
+            ser: self,
+            first: true,
+            close: b"]",
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.0.push(b'{');
+        self.str(variant);
+        self.0.extend_from_slice(b":[");
+
+        Ok(Compound {
+            ser: self,
+            first: true,
+            close: b"]}",
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.0.push(b'{');
+
+        Ok(Compound {
+            ser: self,
+            first: true,
+            close: b"}",
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.0.push(b'{');
+        self.str(variant);
+        self.0.extend_from_slice(b":{");
+
+        Ok(Compound {
+            ser: self,
+            first: true,
+            close: b"}}",
+        })
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeMap for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        // Keys are serialized as JSON as well, and have to be strings
+        let key = to_vec(key);
+
+        if key.first() != Some(&b'"') {
+            return Err(ser::Error::custom("Keys must be strings"));
+        }
+
+        self.separator();
+        self.ser.0.extend_from_slice(&key);
+        self.ser.0.push(b':');
+
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+/// A parsed JSON value, borrowing the strings without escape sequences from the document
+enum Value<'a> {
+    Null,
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Str(Cow<'a, str>),
+    Array(Vec<Value<'a>>),
+    Object(Vec<(Cow<'a, str>, Value<'a>)>),
+}
+
+struct Parser<'a>(&'a str);
+
+impl<'a> Parser<'a> {
+    fn value(&mut self) -> Result<Value<'a>, Error> {
+        self.0 = self.0.trim_start();
+
+        let value = match self.0.as_bytes().first() {
+            Some(b'n') => self.keyword("null", Value::Null)?,
+            Some(b't') => self.keyword("true", Value::Bool(true))?,
+            Some(b'f') => self.keyword("false", Value::Bool(false))?,
+            Some(b'"') => Value::Str(self.string()?),
+            Some(b'[') => {
+                self.0 = &self.0[1..];
+
+                let mut elements = Vec::new();
+
+                if !self.try_expect(']') {
+                    loop {
+                        elements.push(self.value()?);
+
+                        if self.try_expect(']') {
+                            break;
+                        }
+
+                        self.expect(',')?;
+                    }
+                }
+
+                Value::Array(elements)
+            }
+            Some(b'{') => {
+                self.0 = &self.0[1..];
+
+                let mut fields = Vec::new();
+
+                if !self.try_expect('}') {
+                    loop {
+                        self.0 = self.0.trim_start();
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        fields.push((key, self.value()?));
+
+                        if self.try_expect('}') {
+                            break;
+                        }
+
+                        self.expect(',')?;
+                    }
+                }
+
+                Value::Object(fields)
+            }
+            Some(_) => self.number()?,
+            None => return Err(de::Error::custom("Unexpected end of the document")),
+        };
+
+        Ok(value)
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value<'a>) -> Result<Value<'a>, Error> {
+        self.0 = self
+            .0
+            .strip_prefix(keyword)
+            .ok_or_else(|| <Error as de::Error>::custom("Invalid keyword"))?;
+
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value<'a>, Error> {
+        let len = self
+            .0
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(self.0.len());
+
+        let (number, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        if let Ok(number) = number.parse() {
+            Ok(Value::Unsigned(number))
+        } else if let Ok(number) = number.parse() {
+            Ok(Value::Signed(number))
+        } else {
+            number
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| de::Error::custom("Invalid number"))
+        }
+    }
+
+    fn string(&mut self) -> Result<Cow<'a, str>, Error> {
+        self.expect('"')?;
+
+        let end = self
+            .0
+            .find(['"', '\\'])
+            .ok_or_else(|| <Error as de::Error>::custom("Unterminated string"))?;
+
+        if self.0.as_bytes()[end] == b'"' {
+            let s = &self.0[..end];
+            self.0 = &self.0[end + 1..];
+
+            return Ok(Cow::Borrowed(s));
+        }
+
+        let mut s = String::from(&self.0[..end]);
+        let mut chars = self.0[end..].chars();
+
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => {
+                    let c = match chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).collect();
+
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| {
+                                    <Error as de::Error>::custom("Invalid escape sequence")
+                                })?
+                        }
+                        _ => return Err(de::Error::custom("Invalid escape sequence")),
+                    };
+
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+                None => return Err(de::Error::custom("Unterminated string")),
+            }
+        }
+
+        self.0 = chars.as_str();
+
+        Ok(Cow::Owned(s))
+    }
+
+    fn try_expect(&mut self, c: char) -> bool {
+        self.0 = self.0.trim_start();
+
+        if let Some(rest) = self.0.strip_prefix(c) {
+            self.0 = rest;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.try_expect(c) {
+            Ok(())
+        } else {
+            Err(de::Error::custom(format!("Expected '{c}'")))
+        }
+    }
+}
+
+fn visit_str<'de, V>(s: Cow<'de, str>, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    match s {
+        Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+        Cow::Owned(s) => visitor.visit_string(s),
+    }
+}
+
+/// A string, which can be borrowed from the document
+struct Str<'a>(Cow<'a, str>);
+
+impl<'de> IntoDeserializer<'de, Error> for Str<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Str<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visit_str(self.0, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        human_readable()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Null => visitor.visit_unit(),
+            Self::Bool(v) => visitor.visit_bool(v),
+            Self::Unsigned(v) => visitor.visit_u64(v),
+            Self::Signed(v) => visitor.visit_i64(v),
+            Self::Float(v) => visitor.visit_f64(v),
+            Self::Str(s) => visit_str(s, visitor),
+            Self::Array(elements) => {
+                let mut seq = SeqDeserializer::new(elements.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+
+                Ok(value)
+            }
+            Self::Object(fields) => {
+                let mut map = MapDeserializer::new(fields.into_iter().map(|(k, v)| (Str(k), v)));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        human_readable()
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Self::Str(variant) => visitor.visit_enum(Str(variant)),
+            Self::Object(fields) if fields.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(
+                    fields.into_iter().map(|(k, v)| (Str(k), v)),
+                )))
+            }
+            _ => Err(de::Error::custom("Expected an enum")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for Str<'de> {
+    type Error = Error;
+    type Variant = UnitOnly;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, UnitOnly), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self).map(|value| (value, UnitOnly))
+    }
+}
+
+/// The variant of an enum serialized as a string, which has no content
+struct UnitOnly;
+
+impl<'de> de::VariantAccess<'de> for UnitOnly {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(de::Error::custom("Expected a unit variant"))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom("Expected a unit variant"))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom("Expected a unit variant"))
+    }
+}
@@ -0,0 +1,92 @@
+//! A network interface whose state is set by the tests
+
+use std::sync::Mutex;
+
+use crate::ipv4::{self, Ipv4Addr};
+use crate::ipv6;
+use crate::netif::{Interface, Kind};
+
+#[derive(Default)]
+struct State {
+    ip: Option<ipv4::ClientSettings>,
+    ipv6: Option<ipv6::ClientSettings>,
+    fail: bool,
+}
+
+pub struct MockInterface {
+    name: &'static str,
+    kind: Kind,
+    state: Mutex<State>,
+}
+
+impl MockInterface {
+    /// Creates an interface which is down
+    pub fn new(name: &'static str, kind: Kind) -> Self {
+        Self {
+            name,
+            kind,
+            state: Mutex::new(Default::default()),
+        }
+    }
+
+    /// The settings of an interface with the IP `192.168.<subnet>.<host>`
+    pub fn settings(subnet: u8, host: u8) -> ipv4::ClientSettings {
+        ipv4::ClientSettings {
+            ip: Ipv4Addr::new(192, 168, subnet, host),
+            subnet: ipv4::Subnet {
+                gateway: Ipv4Addr::new(192, 168, subnet, 1),
+                mask: ipv4::Mask(24),
+            },
+            dns: Some(Ipv4Addr::new(192, 168, subnet, 1)),
+            secondary_dns: None,
+        }
+    }
+
+    /// Brings the interface up with `ip`, or down with `None`
+    pub fn set_ip(&self, ip: Option<ipv4::ClientSettings>) {
+        self.state.lock().unwrap().ip = ip;
+    }
+
+    pub fn set_ipv6(&self, ipv6: Option<ipv6::ClientSettings>) {
+        self.state.lock().unwrap().ipv6 = ipv6;
+    }
+
+    /// Fail all the calls
+    pub fn set_fail(&self, fail: bool) {
+        self.state.lock().unwrap().fail = fail;
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&State) -> R) -> Result<R, ()> {
+        let state = self.state.lock().unwrap();
+
+        if state.fail {
+            Err(())
+        } else {
+            Ok(f(&state))
+        }
+    }
+}
+
+impl Interface for MockInterface {
+    type Error = ();
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    fn is_up(&self) -> Result<bool, Self::Error> {
+        self.with_state(|state| state.ip.is_some())
+    }
+
+    fn ip_info(&self) -> Result<Option<ipv4::ClientSettings>, Self::Error> {
+        self.with_state(|state| state.ip)
+    }
+
+    fn ipv6_info(&self) -> Result<Option<ipv6::ClientSettings>, Self::Error> {
+        self.with_state(|state| state.ipv6)
+    }
+}
@@ -0,0 +1,316 @@
+//! An in-memory `Ota` for tests
+//!
+//! The update slot keeps the data written so far when an update is dropped without being completed
+//! or aborted (as if the device rebooted), so that the update can be resumed with `Ota::resume_update`.
+
+use core::convert::TryInto;
+
+use crate::io::{ErrorKind, ErrorType, Write};
+use crate::ota::{
+    Digest, FirmwareInfo, Ota, OtaUpdate, OtaUpdateFinished, ResumedUpdate, Slot, SlotState,
+};
+use crate::utils::digest::Crc32;
+
+#[derive(Default)]
+pub struct MockOta {
+    /// The data written to the update slot
+    pub image: Vec<u8>,
+    /// Whether an update was initiated, but neither completed nor aborted
+    pub interrupted: bool,
+    /// The data of the last completed update
+    pub completed: Option<Vec<u8>>,
+    /// Whether the completed update was also activated
+    pub activated: bool,
+    pub aborts: usize,
+    /// The size of the sectors, to which the offset of a resumed update is rounded down
+    pub sector: usize,
+    /// Fail the writes once the update slot contains that many bytes
+    pub fail_at: Option<usize>,
+    /// Accept at most that many bytes per write
+    pub max_write: Option<usize>,
+    /// The firmware version of the running slot, which defaults to its label
+    pub running_version: Option<&'static str>,
+}
+
+impl MockOta {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn slot(&self, label: &str, state: SlotState) -> Slot {
+        let version = match self.running_version {
+            Some(version) if label == "running" => version,
+            _ => label,
+        };
+
+        Slot {
+            label: label.try_into().unwrap(),
+            state,
+            firmware: Some(FirmwareInfo {
+                version: version.try_into().unwrap(),
+                released: "".try_into().unwrap(),
+                description: None,
+                signature: None,
+                download_id: None,
+            }),
+            running: label == "running",
+            bootable: true,
+        }
+    }
+}
+
+impl ErrorType for MockOta {
+    type Error = ErrorKind;
+}
+
+impl Ota for MockOta {
+    type Update<'a> = MockUpdate<'a> where Self: 'a;
+
+    fn get_boot_slot(&self) -> Result<Slot, Self::Error> {
+        Ok(self.slot("running", SlotState::Valid))
+    }
+
+    fn get_running_slot(&self) -> Result<Slot, Self::Error> {
+        Ok(self.slot("running", SlotState::Valid))
+    }
+
+    fn get_update_slot(&self) -> Result<Slot, Self::Error> {
+        Ok(self.slot("update", SlotState::Unknown))
+    }
+
+    fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn factory_reset(&mut self) -> Result<(), Self::Error> {
+        Err(ErrorKind::Unsupported)
+    }
+
+    fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error> {
+        self.image.clear();
+        self.interrupted = true;
+
+        Ok(MockUpdate(self))
+    }
+
+    fn resume_update(&mut self) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+        if !self.interrupted {
+            return Ok(None);
+        }
+
+        let offset = self
+            .image
+            .len()
+            .checked_div(self.sector)
+            .map_or(self.image.len(), |sectors| sectors * self.sector);
+
+        self.image.truncate(offset);
+
+        Ok(Some(ResumedUpdate {
+            update: MockUpdate(self),
+            offset: offset as u64,
+        }))
+    }
+
+    fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
+        ErrorKind::Unsupported
+    }
+}
+
+pub struct MockUpdate<'a>(&'a mut MockOta);
+
+impl ErrorType for MockUpdate<'_> {
+    type Error = ErrorKind;
+}
+
+impl Write for MockUpdate<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.0.fail_at.map(|at| self.0.image.len() >= at) == Some(true) {
+            return Err(ErrorKind::Other);
+        }
+
+        let len = buf.len().min(self.0.max_write.unwrap_or(usize::MAX));
+
+        self.0.image.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<'a> OtaUpdate for MockUpdate<'a> {
+    type OtaUpdateFinished = MockFinished<'a>;
+
+    fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+        self.0.interrupted = false;
+        self.0.completed = Some(self.0.image.clone());
+
+        Ok(MockFinished(self.0))
+    }
+
+    fn complete(self) -> Result<(), Self::Error> {
+        self.finish()?.activate()
+    }
+
+    fn abort(self) -> Result<(), Self::Error> {
+        self.0.interrupted = false;
+        self.0.aborts += 1;
+        self.0.image.clear();
+
+        Ok(())
+    }
+
+    fn written(&self) -> Option<u64> {
+        Some(self.0.image.len() as u64)
+    }
+}
+
+pub struct MockFinished<'a>(&'a mut MockOta);
+
+impl ErrorType for MockFinished<'_> {
+    type Error = ErrorKind;
+}
+
+impl OtaUpdateFinished for MockFinished<'_> {
+    fn activate(self) -> Result<(), Self::Error> {
+        self.0.activated = true;
+
+        Ok(())
+    }
+}
+
+/// CRC-32 stretched to the output size of SHA-256, so that the verification is tested without the `sha256` feature
+pub struct PaddedCrc32(Crc32);
+
+impl Digest for PaddedCrc32 {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self) -> Self::Output {
+        let mut output = [0; 32];
+        output[..4].copy_from_slice(&self.0.finalize());
+
+        output
+    }
+}
+
+pub fn padded_crc32() -> PaddedCrc32 {
+    PaddedCrc32(Crc32::new())
+}
+
+#[cfg(feature = "nightly")]
+mod asynch {
+    use crate::io::asynch::{Read, Write};
+    use crate::ota::asynch::{Ota, OtaUpdate, OtaUpdateFinished, ResumedUpdate, Slot};
+    use crate::utils::io::asynch::{copy_with_progress, CopyError};
+
+    use super::{MockFinished, MockOta, MockUpdate};
+
+    impl Ota for MockOta {
+        type Update<'a> = MockUpdate<'a> where Self: 'a;
+
+        async fn get_boot_slot(&self) -> Result<Slot, Self::Error> {
+            crate::ota::Ota::get_boot_slot(self)
+        }
+
+        async fn get_running_slot(&self) -> Result<Slot, Self::Error> {
+            crate::ota::Ota::get_running_slot(self)
+        }
+
+        async fn get_update_slot(&self) -> Result<Slot, Self::Error> {
+            crate::ota::Ota::get_update_slot(self)
+        }
+
+        async fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
+            crate::ota::Ota::is_factory_reset_supported(self)
+        }
+
+        async fn factory_reset(&mut self) -> Result<(), Self::Error> {
+            crate::ota::Ota::factory_reset(self)
+        }
+
+        async fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error> {
+            crate::ota::Ota::initiate_update(self)
+        }
+
+        async fn resume_update(
+            &mut self,
+        ) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+            crate::ota::Ota::resume_update(self)
+        }
+
+        async fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
+            crate::ota::Ota::mark_running_slot_valid(self)
+        }
+
+        async fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
+            crate::ota::Ota::mark_running_slot_invalid_and_reboot(self)
+        }
+    }
+
+    impl Write for MockUpdate<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            crate::io::Write::write(self, buf)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            crate::io::Write::flush(self)
+        }
+    }
+
+    impl<'a> OtaUpdate for MockUpdate<'a> {
+        type OtaUpdateFinished = MockFinished<'a>;
+
+        async fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+            crate::ota::OtaUpdate::finish(self)
+        }
+
+        async fn complete(self) -> Result<(), Self::Error> {
+            crate::ota::OtaUpdate::complete(self)
+        }
+
+        async fn abort(self) -> Result<(), Self::Error> {
+            crate::ota::OtaUpdate::abort(self)
+        }
+
+        fn written(&self) -> Option<u64> {
+            crate::ota::OtaUpdate::written(self)
+        }
+
+        async fn update<R>(
+            mut self,
+            read: R,
+            progress: impl Fn(u64, u64),
+        ) -> Result<(), CopyError<R::Error, Self::Error>>
+        where
+            R: Read,
+        {
+            let mut buf = [0_u8; 64];
+
+            match copy_with_progress(read, &mut self, &mut buf, progress).await {
+                Ok(_) => OtaUpdate::complete(self).await.map_err(CopyError::Write),
+                Err(e) => {
+                    OtaUpdate::abort(self).await.map_err(CopyError::Write)?;
+
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    impl OtaUpdateFinished for MockFinished<'_> {
+        async fn activate(self) -> Result<(), Self::Error> {
+            crate::ota::OtaUpdateFinished::activate(self)
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! A `TimerService` driven by a manual clock, for tests
+//!
+//! Time only passes with `advance`, which calls the callbacks of the timers expiring in the meantime,
+//! in the order of their deadlines. `expire` lets the time pass without calling the callbacks yet,
+//! as if their expiries were in flight, and `run` calls them later on.
+
+use core::convert::Infallible;
+use core::time::Duration;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::sys_time::{Monotonic, SystemTime};
+use crate::timer::{ErrorType, OnceTimer, PeriodicTimer, Timer, TimerCapabilities, TimerService};
+
+struct Slot {
+    /// `None` while the callback is being called
+    callback: Option<Box<dyn FnMut() + Send>>,
+    deadline: Option<Duration>,
+    period: Option<Duration>,
+}
+
+#[derive(Default)]
+struct State {
+    now: Duration,
+    /// Indexed by timer; `None` once the timer is dropped
+    slots: Vec<Option<Slot>>,
+    /// The timers whose expiries are in flight
+    expired: VecDeque<usize>,
+}
+
+#[derive(Clone, Default)]
+pub struct MockTimerService {
+    state: Arc<Mutex<State>>,
+    capabilities: TimerCapabilities,
+}
+
+impl MockTimerService {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_capabilities(mut self, capabilities: TimerCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn now(&self) -> Duration {
+        self.state.lock().unwrap().now
+    }
+
+    /// Steps the clock to `now`, without expiring any timer
+    pub fn set_now(&self, now: Duration) {
+        self.state.lock().unwrap().now = now;
+    }
+
+    /// How many timers are scheduled
+    pub fn scheduled(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .slots
+            .iter()
+            .flatten()
+            .filter(|slot| slot.deadline.is_some())
+            .count()
+    }
+
+    /// Lets `duration` pass, calling the callbacks of the timers expiring in the meantime
+    pub fn advance(&self, duration: Duration) {
+        let target = self.now() + duration;
+
+        while let Some(id) = self.expire_next(target) {
+            self.call(id);
+        }
+
+        self.set_now(target);
+    }
+
+    /// Lets `duration` pass, leaving the expiries of the timers in flight until `run` is called
+    pub fn expire(&self, duration: Duration) {
+        let target = self.now() + duration;
+
+        while let Some(id) = self.expire_next(target) {
+            self.state.lock().unwrap().expired.push_back(id);
+        }
+
+        self.set_now(target);
+    }
+
+    /// Calls the callbacks of the expiries in flight
+    pub fn run(&self) {
+        loop {
+            let id = self.state.lock().unwrap().expired.pop_front();
+
+            match id {
+                Some(id) => self.call(id),
+                None => break,
+            }
+        }
+    }
+
+    fn expire_next(&self, target: Duration) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+
+        let (id, deadline) = state
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| Some((id, slot.as_ref()?.deadline?)))
+            .filter(|(_, deadline)| *deadline <= target)
+            .min_by_key(|(_, deadline)| *deadline)?;
+
+        state.now = state.now.max(deadline);
+
+        let slot = state.slots[id].as_mut().unwrap();
+        slot.deadline = slot.period.map(|period| deadline + period);
+
+        Some(id)
+    }
+
+    fn call(&self, id: usize) {
+        let callback = self.state.lock().unwrap().slots[id]
+            .as_mut()
+            .and_then(|slot| slot.callback.take());
+
+        if let Some(mut callback) = callback {
+            callback();
+
+            if let Some(slot) = self.state.lock().unwrap().slots[id].as_mut() {
+                slot.callback = Some(callback);
+            }
+        }
+    }
+}
+
+impl Monotonic for MockTimerService {
+    fn now(&self) -> Duration {
+        MockTimerService::now(self)
+    }
+}
+
+impl SystemTime for MockTimerService {
+    fn now(&self) -> Duration {
+        MockTimerService::now(self)
+    }
+}
+
+impl ErrorType for MockTimerService {
+    type Error = Infallible;
+}
+
+impl TimerService for MockTimerService {
+    type Timer<'a> = MockTimer where Self: 'a;
+
+    fn timer<F>(&self, callback: F) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+
+        state.slots.push(Some(Slot {
+            callback: Some(Box::new(callback)),
+            deadline: None,
+            period: None,
+        }));
+
+        Ok(MockTimer {
+            state: self.state.clone(),
+            id: state.slots.len() - 1,
+        })
+    }
+
+    fn capabilities(&self) -> TimerCapabilities {
+        self.capabilities
+    }
+}
+
+pub struct MockTimer {
+    state: Arc<Mutex<State>>,
+    id: usize,
+}
+
+impl MockTimer {
+    fn schedule(&mut self, duration: Duration, period: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+
+        let deadline = state.now + duration;
+
+        let slot = state.slots[self.id].as_mut().unwrap();
+        slot.deadline = Some(deadline);
+        // A zero period would expire forever without the time passing
+        slot.period = period.filter(|period| !period.is_zero());
+    }
+}
+
+impl Drop for MockTimer {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().slots[self.id] = None;
+    }
+}
+
+impl ErrorType for MockTimer {
+    type Error = Infallible;
+}
+
+impl Timer for MockTimer {
+    fn is_scheduled(&self) -> Result<bool, Self::Error> {
+        Ok(self.state.lock().unwrap().slots[self.id]
+            .as_ref()
+            .unwrap()
+            .deadline
+            .is_some())
+    }
+
+    fn cancel(&mut self) -> Result<bool, Self::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let slot = state.slots[self.id].as_mut().unwrap();
+        slot.period = None;
+
+        Ok(slot.deadline.take().is_some())
+    }
+}
+
+impl OnceTimer for MockTimer {
+    fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.schedule(duration, None);
+
+        Ok(())
+    }
+}
+
+impl PeriodicTimer for MockTimer {
+    fn every(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.schedule(duration, Some(duration));
+
+        Ok(())
+    }
+}
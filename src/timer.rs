@@ -2,6 +2,8 @@ use core::fmt::Debug;
 use core::result::Result;
 use core::time::Duration;
 
+use crate::sys_time::SystemTime;
+
 pub trait ErrorType {
     type Error: Debug;
 }
@@ -20,10 +22,149 @@ where
     type Error = E::Error;
 }
 
+/// What a periodic timer does when the receiver of its ticks falls behind by one or more periods
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub enum MissedTickPolicy {
+    /// Yields the missed ticks one after the other, as fast as they are received
+    #[default]
+    Burst,
+    /// Yields the missed ticks as one, keeping the schedule
+    Skip,
+    /// Yields the missed ticks as one, and restarts the schedule from then on
+    Delay,
+}
+
+/// The context the callbacks of a timer service are called from
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub enum CallbackContext {
+    #[default]
+    Unknown,
+    Task,
+    Isr,
+}
+
+/// What a timer service can schedule, as returned by `TimerService::capabilities`
+///
+/// `None` means unknown.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub struct TimerCapabilities {
+    /// The smallest duration, and the granularity of the durations the timers can be scheduled with
+    pub resolution: Option<Duration>,
+    /// The largest duration the timers can be scheduled with
+    pub max_duration: Option<Duration>,
+    pub callback_context: CallbackContext,
+}
+
+impl TimerCapabilities {
+    pub const UNKNOWN: Self = Self {
+        resolution: None,
+        max_duration: None,
+        callback_context: CallbackContext::Unknown,
+    };
+
+    /// Checks that timers can be scheduled with `duration` exactly
+    pub fn check(&self, duration: Duration) -> Result<(), CapabilityError> {
+        if let Some(resolution) = self.resolution {
+            if duration < resolution {
+                return Err(CapabilityError::BelowResolution {
+                    duration,
+                    resolution,
+                });
+            }
+
+            if !resolution.is_zero() && duration.as_nanos() % resolution.as_nanos() != 0 {
+                return Err(CapabilityError::NotMultipleOfResolution {
+                    duration,
+                    resolution,
+                });
+            }
+        }
+
+        if let Some(max_duration) = self.max_duration {
+            if duration > max_duration {
+                return Err(CapabilityError::AboveMaximum {
+                    duration,
+                    max_duration,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `duration` clamped between the resolution and the maximum duration
+    pub fn clamp(&self, duration: Duration) -> Duration {
+        let duration = self
+            .resolution
+            .map_or(duration, |resolution| duration.max(resolution));
+
+        self.max_duration
+            .map_or(duration, |max_duration| duration.min(max_duration))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CapabilityError {
+    BelowResolution {
+        duration: Duration,
+        resolution: Duration,
+    },
+    NotMultipleOfResolution {
+        duration: Duration,
+        resolution: Duration,
+    },
+    AboveMaximum {
+        duration: Duration,
+        max_duration: Duration,
+    },
+}
+
+impl core::fmt::Display for CapabilityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BelowResolution {
+                duration,
+                resolution,
+            } => write!(
+                f,
+                "Duration {duration:?} is below the resolution {resolution:?}"
+            ),
+            Self::NotMultipleOfResolution {
+                duration,
+                resolution,
+            } => write!(
+                f,
+                "Duration {duration:?} is not a multiple of the resolution {resolution:?}"
+            ),
+            Self::AboveMaximum {
+                duration,
+                max_duration,
+            } => write!(
+                f,
+                "Duration {duration:?} is above the maximum {max_duration:?}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapabilityError {}
+
 #[must_use]
 pub trait Timer: ErrorType {
     fn is_scheduled(&self) -> Result<bool, Self::Error>;
 
+    /// Cancels the timer, returning whether a pending expiry was cancelled.
+    ///
+    /// Returns `false` if the timer was not scheduled, or if its (last) expiry is already happening,
+    /// in which case the callback might still be called once.
     fn cancel(&mut self) -> Result<bool, Self::Error>;
 }
 
@@ -41,7 +182,25 @@ where
 }
 #[must_use]
 pub trait OnceTimer: Timer {
+    /// Schedules the callback to be called once, after `duration`.
+    ///
+    /// If the timer is already scheduled, it is rescheduled, i.e. the pending expiry is cancelled.
     fn after(&mut self, duration: Duration) -> Result<(), Self::Error>;
+
+    /// Schedules the callback to be called once, when `clock` reaches `deadline`.
+    ///
+    /// `clock` might be a monotonic source returning the time since boot, or the wall clock.
+    /// If the deadline has already passed, the callback is called as soon as possible.
+    ///
+    /// The default implementation schedules the timer with `after` for the time remaining at the moment of the call,
+    /// so the callback might be called early or late if the clock is stepped in the meantime.
+    /// See `utils::timer::AlignedSchedule` for a schedule which re-checks the clock on expiry.
+    fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), Self::Error>
+    where
+        C: SystemTime,
+    {
+        self.after(deadline.saturating_sub(clock.now()))
+    }
 }
 
 impl<O> OnceTimer for &mut O
@@ -51,10 +210,18 @@ where
     fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
         (*self).after(duration)
     }
+
+    fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), Self::Error>
+    where
+        C: SystemTime,
+    {
+        (*self).at(clock, deadline)
+    }
 }
 
 #[must_use]
 pub trait PeriodicTimer: Timer {
+    /// Schedules the callback to be called every `duration`, rescheduling the timer if it is already scheduled.
     fn every(&mut self, duration: Duration) -> Result<(), Self::Error>;
 }
 
@@ -75,6 +242,24 @@ pub trait TimerService: ErrorType {
     fn timer<F>(&self, callback: F) -> Result<Self::Timer<'_>, Self::Error>
     where
         F: FnMut() + Send + 'static;
+
+    /// Returns what the timers of the service can schedule, by default unknown
+    fn capabilities(&self) -> TimerCapabilities {
+        TimerCapabilities::UNKNOWN
+    }
+
+    /// Creates a timer which calls `callback` with the `data` it carries, on each expiry or tick.
+    ///
+    /// As the callback is a plain function rather than a capturing closure, services can keep `data` inline
+    /// in the timer, without allocating. The default implementation moves `data` into the closure passed to `timer`.
+    fn timer_with<D>(&self, data: D, callback: fn(&mut D)) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        D: Send + 'static,
+    {
+        let mut data = data;
+
+        self.timer(move || callback(&mut data))
+    }
 }
 
 impl<S> TimerService for &S
@@ -89,6 +274,17 @@ where
     {
         (*self).timer(callback)
     }
+
+    fn capabilities(&self) -> TimerCapabilities {
+        (*self).capabilities()
+    }
+
+    fn timer_with<D>(&self, data: D, callback: fn(&mut D)) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        D: Send + 'static,
+    {
+        (*self).timer_with(data, callback)
+    }
 }
 
 impl<S> TimerService for &mut S
@@ -103,17 +299,53 @@ where
     {
         (**self).timer(callback)
     }
+
+    fn capabilities(&self) -> TimerCapabilities {
+        (**self).capabilities()
+    }
+
+    fn timer_with<D>(&self, data: D, callback: fn(&mut D)) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        D: Send + 'static,
+    {
+        (**self).timer_with(data, callback)
+    }
 }
 
 #[cfg(feature = "nightly")]
 pub mod asynch {
     use core::time::Duration;
 
-    pub use super::ErrorType;
+    use crate::sys_time::SystemTime;
+
+    pub use super::{
+        CallbackContext, CapabilityError, ErrorType, MissedTickPolicy, TimerCapabilities,
+    };
 
     #[must_use]
     pub trait OnceTimer: ErrorType {
+        /// Completes after `duration`. Dropping the future cancels the timer,
+        /// and calling `after` again reschedules it.
         async fn after(&mut self, duration: Duration) -> Result<(), Self::Error>;
+
+        /// Completes when `clock` reaches `deadline`.
+        ///
+        /// The default implementation waits with `after` and re-checks the clock on expiry,
+        /// waiting again for the remaining time if the clock was stepped back in the meantime.
+        async fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), Self::Error>
+        where
+            C: SystemTime,
+        {
+            loop {
+                let now = clock.now();
+
+                if now >= deadline {
+                    break Ok(());
+                }
+
+                self.after(deadline - now).await?;
+            }
+        }
     }
 
     impl<O> OnceTimer for &mut O
@@ -123,6 +355,13 @@ pub mod asynch {
         async fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
             (*self).after(duration).await
         }
+
+        async fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), Self::Error>
+        where
+            C: SystemTime,
+        {
+            (*self).at(clock, deadline).await
+        }
     }
 
     pub trait Clock {
@@ -151,8 +390,7 @@ pub mod asynch {
     where
         P: PeriodicTimer,
     {
-        type Clock<'a>
-        = P::Clock<'a> where Self: 'a;
+        type Clock<'a> = P::Clock<'a> where Self: 'a;
 
         fn every(&mut self, duration: Duration) -> Result<Self::Clock<'_>, Self::Error> {
             (*self).every(duration)
@@ -165,6 +403,11 @@ pub mod asynch {
             Self: 'a;
 
         async fn timer(&self) -> Result<Self::Timer<'_>, Self::Error>;
+
+        /// Returns what the timers of the service can schedule, by default unknown
+        fn capabilities(&self) -> TimerCapabilities {
+            TimerCapabilities::UNKNOWN
+        }
     }
 
     impl<T> TimerService for &T
@@ -176,6 +419,10 @@ pub mod asynch {
         async fn timer(&self) -> Result<Self::Timer<'_>, Self::Error> {
             (*self).timer().await
         }
+
+        fn capabilities(&self) -> TimerCapabilities {
+            (*self).capabilities()
+        }
     }
 
     impl<T> TimerService for &mut T
@@ -187,5 +434,225 @@ pub mod asynch {
         async fn timer(&self) -> Result<Self::Timer<'_>, Self::Error> {
             (**self).timer().await
         }
+
+        fn capabilities(&self) -> TimerCapabilities {
+            (**self).capabilities()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::test_timer::MockTimerService;
+
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn counting_timer<S>(service: &S) -> (S::Timer<'_>, Arc<AtomicUsize>)
+    where
+        S: TimerService,
+    {
+        let fires = Arc::new(AtomicUsize::new(0));
+
+        let timer = {
+            let fires = fires.clone();
+
+            service
+                .timer(move || {
+                    fires.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap()
+        };
+
+        (timer, fires)
+    }
+
+    #[test]
+    fn cancel() {
+        let service = MockTimerService::new();
+        let (mut timer, fires) = counting_timer(&service);
+
+        assert!(!timer.cancel().unwrap());
+
+        timer.after(MS * 10).unwrap();
+        assert!(timer.is_scheduled().unwrap());
+        assert!(timer.cancel().unwrap());
+        assert!(!timer.is_scheduled().unwrap());
+
+        service.advance(MS * 10);
+        assert_eq!(fires.load(Ordering::SeqCst), 0);
+
+        // An expiry in flight can no longer be cancelled
+        timer.after(MS * 10).unwrap();
+        service.expire(MS * 10);
+        assert!(!timer.cancel().unwrap());
+
+        service.run();
+        assert_eq!(fires.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reschedule() {
+        let service = MockTimerService::new();
+        let (mut timer, fires) = counting_timer(&service);
+
+        timer.after(MS * 10).unwrap();
+        service.advance(MS * 5);
+
+        // Rescheduling replaces the pending expiry
+        timer.after(MS * 10).unwrap();
+        service.advance(MS * 5);
+        assert_eq!(fires.load(Ordering::SeqCst), 0);
+
+        service.advance(MS * 5);
+        assert_eq!(fires.load(Ordering::SeqCst), 1);
+
+        timer.every(MS * 10).unwrap();
+        service.advance(MS * 30);
+        assert_eq!(fires.load(Ordering::SeqCst), 4);
+
+        timer.after(MS * 10).unwrap();
+        service.advance(MS * 30);
+        assert_eq!(fires.load(Ordering::SeqCst), 5);
+        assert!(!timer.is_scheduled().unwrap());
+    }
+
+    #[test]
+    fn at() {
+        let service = MockTimerService::new();
+        let clock = MockTimerService::new();
+        let (mut timer, fires) = counting_timer(&service);
+
+        clock.set_now(MS * 30);
+
+        timer.at(&clock, MS * 100).unwrap();
+
+        service.advance(MS * 69);
+        assert_eq!(fires.load(Ordering::SeqCst), 0);
+
+        service.advance(MS);
+        assert_eq!(fires.load(Ordering::SeqCst), 1);
+
+        // A deadline which has already passed expires as soon as possible
+        timer.at(&clock, MS * 10).unwrap();
+
+        service.advance(Duration::ZERO);
+        assert_eq!(fires.load(Ordering::SeqCst), 2);
+    }
+
+    fn timer_with_counter<S>(service: &S, counter: Arc<AtomicUsize>) -> S::Timer<'_>
+    where
+        S: TimerService,
+    {
+        service
+            .timer_with((0, counter), |(fires, counter)| {
+                *fires += 1;
+                counter.store(*fires, Ordering::SeqCst);
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn timer_with() {
+        let service = MockTimerService::new();
+        let fires = Arc::new(AtomicUsize::new(0));
+
+        // Through the forwarding implementation
+        let forwarded = &service;
+        let mut timer = timer_with_counter(&forwarded, fires.clone());
+
+        timer.every(MS * 10).unwrap();
+
+        service.advance(MS * 30);
+        assert_eq!(fires.load(Ordering::SeqCst), 3);
+
+        // The carried value persists when the timer is rescheduled
+        timer.after(MS * 10).unwrap();
+
+        service.advance(MS * 30);
+        assert_eq!(fires.load(Ordering::SeqCst), 4);
+    }
+
+    const CAPABILITIES: TimerCapabilities = TimerCapabilities {
+        resolution: Some(Duration::from_millis(10)),
+        max_duration: Some(Duration::from_secs(60)),
+        callback_context: CallbackContext::Task,
+    };
+
+    #[test]
+    fn capabilities_check() {
+        assert_eq!(CAPABILITIES.check(MS * 10), Ok(()));
+        assert_eq!(CAPABILITIES.check(MS * 120), Ok(()));
+        assert_eq!(CAPABILITIES.check(Duration::from_secs(60)), Ok(()));
+
+        assert_eq!(
+            CAPABILITIES.check(MS * 5),
+            Err(CapabilityError::BelowResolution {
+                duration: MS * 5,
+                resolution: MS * 10,
+            })
+        );
+        assert_eq!(
+            CAPABILITIES.check(MS * 15),
+            Err(CapabilityError::NotMultipleOfResolution {
+                duration: MS * 15,
+                resolution: MS * 10,
+            })
+        );
+        assert_eq!(
+            CAPABILITIES.check(Duration::from_secs(61)),
+            Err(CapabilityError::AboveMaximum {
+                duration: Duration::from_secs(61),
+                max_duration: Duration::from_secs(60),
+            })
+        );
+
+        assert_eq!(TimerCapabilities::UNKNOWN.check(Duration::ZERO), Ok(()));
+        assert_eq!(TimerCapabilities::UNKNOWN.check(Duration::MAX), Ok(()));
+    }
+
+    #[test]
+    fn capabilities_clamp() {
+        assert_eq!(CAPABILITIES.clamp(MS), MS * 10);
+        assert_eq!(CAPABILITIES.clamp(MS * 15), MS * 15);
+        assert_eq!(
+            CAPABILITIES.clamp(Duration::from_secs(3600)),
+            Duration::from_secs(60)
+        );
+
+        assert_eq!(TimerCapabilities::UNKNOWN.clamp(MS), MS);
+    }
+
+    fn capabilities_of<S>(service: S) -> TimerCapabilities
+    where
+        S: TimerService,
+    {
+        service.capabilities()
+    }
+
+    #[test]
+    fn capabilities() {
+        assert_eq!(TimerCapabilities::default(), TimerCapabilities::UNKNOWN);
+        assert_eq!(
+            MockTimerService::new().capabilities(),
+            TimerCapabilities::UNKNOWN
+        );
+
+        let service = MockTimerService::new().with_capabilities(CAPABILITIES);
+
+        // Through the forwarding implementation
+        assert_eq!(capabilities_of(&service), CAPABILITIES);
+    }
+
+    #[test]
+    fn capability_display() {
+        assert_eq!(
+            CAPABILITIES.check(MS * 5).unwrap_err().to_string(),
+            "Duration 5ms is below the resolution 10ms"
+        );
     }
 }
@@ -1,6 +1,25 @@
 #[cfg(feature = "asyncify")]
 pub mod asyncify;
+pub mod backoff;
+pub mod captive;
+pub mod debounce;
+pub mod digest;
+pub mod event_bus;
+pub mod failover;
 pub mod http;
 pub mod io;
+#[cfg(feature = "use_serde")]
+pub mod json_io;
 pub mod mqtt;
 pub mod mutex;
+pub mod once;
+pub mod ota;
+pub mod ota_mqtt;
+pub mod ota_server;
+#[cfg(feature = "use_serde")]
+pub mod rest;
+pub mod storage;
+pub mod time;
+pub mod timer;
+pub mod wallclock;
+pub mod ws;
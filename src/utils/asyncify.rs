@@ -1,14 +1,36 @@
 use core::future::Future;
 
+#[cfg(target_has_atomic = "ptr")]
+pub mod channel;
+#[cfg(target_has_atomic = "ptr")]
+pub mod executor;
 #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
 pub mod event_bus;
 #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
 pub mod mqtt;
+#[cfg(target_has_atomic = "ptr")]
+pub mod mutex;
 #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod ota;
+#[cfg(target_has_atomic = "ptr")]
+pub mod oneshot;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod ping;
+pub mod select;
+pub mod signal;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod sntp;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod storage;
+#[cfg(target_has_atomic = "ptr")]
 pub mod timer;
 #[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub mod unblocker;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
 pub mod ws;
 
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub use blocker::*;
 #[cfg(feature = "alloc")]
 pub use blocking_unblocker::*;
 
@@ -111,7 +133,7 @@ mod blocking_unblocker {
     pub struct BlockingUnblocker(());
 
     impl BlockingUnblocker {
-        pub fn unblock<'a, F, T>(&'a self, f: F) -> BlockingFuture<T>
+        pub fn unblock<'a, F, T>(&'a self, f: F) -> BlockingFuture<'a, T>
         where
             F: FnOnce() -> T + Send + 'a,
             T: Send + 'a,
@@ -179,3 +201,279 @@ mod blocking_unblocker {
     // Temporary, until this issue in Rust nightly is fixed: https://github.com/rust-lang/rust/issues/117602
     unsafe impl<'a, T> Send for BlockingFuture<'a, T> where T: Send + 'a {}
 }
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+mod blocker {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll};
+    use core::time::Duration;
+
+    extern crate alloc;
+
+    use alloc::sync::Arc;
+    use alloc::task::Wake;
+
+    use crate::sys_time::Monotonic;
+    use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
+
+    struct Parker<CV>
+    where
+        CV: RawCondvar,
+    {
+        notified: Mutex<CV::RawMutex, bool>,
+        condvar: Condvar<CV>,
+    }
+
+    impl<CV> Parker<CV>
+    where
+        CV: RawCondvar,
+    {
+        fn new() -> Self {
+            Self {
+                notified: Mutex::new(false),
+                condvar: Condvar::new(),
+            }
+        }
+
+        /// Waits until woken, or until `timeout` (if any) elapses
+        fn park(&self, timeout: Option<Duration>) {
+            let mut notified = self.notified.lock();
+
+            // A wake which happened while polling is not lost, as it is recorded in `notified`
+            if !*notified {
+                notified = if let Some(timeout) = timeout {
+                    self.condvar.wait_timeout(notified, timeout).0
+                } else {
+                    self.condvar.wait(notified)
+                };
+            }
+
+            *notified = false;
+        }
+    }
+
+    impl<CV> Wake for Parker<CV>
+    where
+        CV: RawCondvar + Send + Sync,
+        CV::RawMutex: Send + Sync,
+    {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            *self.notified.lock() = true;
+            self.condvar.notify_one();
+        }
+    }
+
+    /// Runs `fut` to completion on the current thread, which is parked on a `Condvar` of `CV` while the future is pending
+    ///
+    /// Calls can be nested, e.g. from within a future which is itself run with `block_on_with`,
+    /// but the inner call blocks the outer future until it completes, so it must not wait
+    /// for something the outer future is supposed to do.
+    pub fn block_on_with<CV, F>(fut: F) -> F::Output
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+        F: Future,
+    {
+        let parker = Arc::new(Parker::<CV>::new());
+        let waker = parker.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut fut = pin!(fut);
+
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                break output;
+            }
+
+            parker.park(None);
+        }
+    }
+
+    /// Like `block_on_with`, but gives up (dropping `fut`) and returns `None` if `fut` does not complete
+    /// within `timeout`, as measured by `clock`
+    pub fn block_on_timeout_with<CV, C, F>(
+        fut: F,
+        clock: &C,
+        timeout: Duration,
+    ) -> Option<F::Output>
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+        C: Monotonic,
+        F: Future,
+    {
+        let deadline = clock.now() + timeout;
+
+        let parker = Arc::new(Parker::<CV>::new());
+        let waker = parker.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut fut = pin!(fut);
+
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                break Some(output);
+            }
+
+            let now = clock.now();
+
+            if now >= deadline {
+                break None;
+            }
+
+            parker.park(Some(deadline - now));
+        }
+    }
+
+    /// Runs `fut` to completion on the current thread; see `block_on_with`
+    #[cfg(feature = "std")]
+    pub fn block_on<F>(fut: F) -> F::Output
+    where
+        F: Future,
+    {
+        block_on_with::<crate::utils::mutex::StdRawCondvar, _>(fut)
+    }
+
+    /// Runs `fut` to completion on the current thread, or gives up after `timeout`; see `block_on_timeout_with`
+    #[cfg(feature = "std")]
+    pub fn block_on_timeout<F>(fut: F, timeout: Duration) -> Option<F::Output>
+    where
+        F: Future,
+    {
+        block_on_timeout_with::<crate::utils::mutex::StdRawCondvar, _, _>(
+            fut,
+            &crate::sys_time::StdMonotonic,
+            timeout,
+        )
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::{pending, poll_fn, ready};
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use core::task::{Poll, Waker};
+    use core::time::Duration;
+
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use crate::test_timer::MockTimerService;
+    use crate::utils::mutex::StdRawCondvar;
+
+    use super::*;
+
+    /// Sets its flag once dropped
+    struct DropFlag<'a>(&'a AtomicBool);
+
+    impl Drop for DropFlag<'_> {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wake_during_poll() {
+        let polls = AtomicUsize::new(0);
+
+        // The future wakes itself before returning `Pending`, so the wake happens before the thread parks
+        let output = block_on_with::<StdRawCondvar, _>(poll_fn(|cx| {
+            if polls.fetch_add(1, Ordering::SeqCst) < 3 {
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            } else {
+                Poll::Ready(1)
+            }
+        }));
+
+        assert_eq!(output, 1);
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn woken_from_thread() {
+        let done = AtomicBool::new(false);
+        let waker = Mutex::new(None::<Waker>);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+
+                done.store(true, Ordering::SeqCst);
+
+                if let Some(waker) = waker.lock().unwrap().take() {
+                    waker.wake();
+                }
+            });
+
+            block_on(poll_fn(|cx| {
+                *waker.lock().unwrap() = Some(cx.waker().clone());
+
+                if done.load(Ordering::SeqCst) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            }));
+        });
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(block_on(async { block_on(ready(1)) + 1 }), 2);
+    }
+
+    #[test]
+    fn timeout() {
+        assert_eq!(block_on_timeout(ready(1), Duration::from_secs(5)), Some(1));
+
+        let dropped = AtomicBool::new(false);
+
+        let start = Instant::now();
+
+        let output = block_on_timeout(
+            async {
+                let _flag = DropFlag(&dropped);
+
+                pending::<u32>().await
+            },
+            Duration::from_millis(20),
+        );
+
+        // The future is dropped when giving up on it
+        assert_eq!(output, None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn timeout_clock() {
+        let clock = MockTimerService::new();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+
+                clock.set_now(Duration::from_secs(1));
+            });
+
+            let start = Instant::now();
+
+            // The parkings time out well before the timeout expires as per the clock
+            let output = block_on_timeout_with::<StdRawCondvar, _, _>(
+                pending::<u32>(),
+                &clock,
+                Duration::from_millis(5),
+            );
+
+            assert_eq!(output, None);
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        });
+    }
+}
@@ -0,0 +1,564 @@
+use core::fmt;
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+
+use crate::event_bus::{ErrorType, TryReceiver, TryRecvError, TrySendError, TrySender};
+use crate::utils::mutex::{Mutex, RawMutex};
+
+/// The error returned by `Sender` once all receivers are gone, and by `Receiver` once the channel is empty
+/// and all senders are gone
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Channel closed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Closed {}
+
+/// The wakers of the tasks waiting on one side of the channel
+///
+/// They are taken out of the state of the channel to be woken, and only woken once the channel is unlocked,
+/// so that the woken tasks do not have to wait for the lock of the waking one.
+struct Wakers<const W: usize>(heapless::Vec<Waker, W>);
+
+impl<const W: usize> Wakers<W> {
+    const fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+
+    /// Registers the waker of `cx`, returning the wakers to wake to make room for it
+    #[must_use]
+    fn register(&mut self, cx: &Context<'_>) -> Self {
+        if self.0.iter().any(|waker| waker.will_wake(cx.waker())) {
+            return Self::new();
+        }
+
+        // More tasks are waiting than can be tracked: wake them all, so that none is lost
+        let evicted = if self.0.is_full() {
+            self.take()
+        } else {
+            Self::new()
+        };
+
+        let _ = self.0.push(cx.waker().clone());
+
+        evicted
+    }
+
+    #[must_use]
+    fn take(&mut self) -> Self {
+        core::mem::replace(self, Self::new())
+    }
+
+    fn wake(self) {
+        for waker in self.0 {
+            waker.wake();
+        }
+    }
+}
+
+struct ChannelState<P, const N: usize, const W: usize> {
+    queue: heapless::Deque<P, N>,
+    senders_wakers: Wakers<W>,
+    receivers_wakers: Wakers<W>,
+    senders: usize,
+    receivers: usize,
+}
+
+/// A bounded, multi-producer multi-consumer FIFO channel of up to `N` payloads
+///
+/// The channel needs no allocations, and can be placed in a `static`. It is used through the
+/// (cloneable) `Sender` and `Receiver` halves returned by `split`.
+///
+/// `send` waits until there is room in the channel. When room becomes available, all waiting senders
+/// are woken, and whichever of them is polled first gets the slot (and likewise for the receivers when a payload
+/// is sent), so there is no fairness between the senders, but a sender which is dropped while waiting never
+/// holds back the others.
+///
+/// Up to `W` waiting tasks are tracked on each side; when more are waiting, the tracked ones are woken
+/// to make room, and have to re-register, so `W` should be sized to the number of tasks using each side.
+pub struct Channel<R, P, const N: usize, const W: usize = 4>(Mutex<R, ChannelState<P, N, W>>)
+where
+    R: RawMutex;
+
+impl<R, P, const N: usize, const W: usize> Channel<R, P, N, W>
+where
+    R: RawMutex,
+{
+    pub const fn new() -> Self {
+        Self(Mutex::new(ChannelState {
+            queue: heapless::Deque::new(),
+            senders_wakers: Wakers::new(),
+            receivers_wakers: Wakers::new(),
+            senders: 0,
+            receivers: 0,
+        }))
+    }
+
+    /// Returns a new `Sender` and a new `Receiver` of the channel
+    pub fn split(&self) -> (Sender<'_, R, P, N, W>, Receiver<'_, R, P, N, W>) {
+        let mut state = self.0.lock();
+
+        state.senders += 1;
+        state.receivers += 1;
+
+        (Sender(self), Receiver(self))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().queue.is_empty()
+    }
+}
+
+impl<R, P, const N: usize, const W: usize> Default for Channel<R, P, N, W>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sending half of a `Channel`
+pub struct Sender<'a, R, P, const N: usize, const W: usize>(&'a Channel<R, P, N, W>)
+where
+    R: RawMutex;
+
+impl<'a, R, P, const N: usize, const W: usize> Sender<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    pub async fn send(&self, value: P) -> Result<(), Closed> {
+        let mut value = Some(value);
+
+        poll_fn(|cx| {
+            let mut state = self.0 .0.lock();
+
+            let (result, wakers) = if state.receivers == 0 {
+                (Poll::Ready(Err(Closed)), Wakers::new())
+            } else if state.queue.is_full() {
+                (Poll::Pending, state.senders_wakers.register(cx))
+            } else {
+                if let Some(value) = value.take() {
+                    let _ = state.queue.push_back(value);
+                }
+
+                (Poll::Ready(Ok(())), state.receivers_wakers.take())
+            };
+
+            drop(state);
+
+            wakers.wake();
+
+            result
+        })
+        .await
+    }
+
+    /// Sends `value` if there is room for it, waking the receivers waiting in `recv`, or else returns it
+    pub fn try_send(&self, value: P) -> Result<(), TrySendError<P>> {
+        let mut state = self.0 .0.lock();
+
+        if state.receivers == 0 {
+            return Err(TrySendError::Closed(value));
+        }
+
+        state.queue.push_back(value).map_err(TrySendError::Full)?;
+
+        let wakers = state.receivers_wakers.take();
+
+        drop(state);
+
+        wakers.wake();
+
+        Ok(())
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> Clone for Sender<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    fn clone(&self) -> Self {
+        self.0 .0.lock().senders += 1;
+
+        Self(self.0)
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> Drop for Sender<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock();
+
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            let wakers = state.receivers_wakers.take();
+
+            drop(state);
+
+            wakers.wake();
+        }
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> ErrorType for Sender<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    type Error = Closed;
+}
+
+impl<'a, R, P, const N: usize, const W: usize> TrySender for Sender<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    type Data = P;
+
+    fn try_send(&mut self, data: Self::Data) -> Result<(), TrySendError<Self::Data>> {
+        Sender::try_send(self, data)
+    }
+}
+
+/// The receiving half of a `Channel`
+pub struct Receiver<'a, R, P, const N: usize, const W: usize>(&'a Channel<R, P, N, W>)
+where
+    R: RawMutex;
+
+impl<'a, R, P, const N: usize, const W: usize> Receiver<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    /// Receives the next payload; fails once the channel is empty and all senders are gone
+    pub async fn recv(&self) -> Result<P, Closed> {
+        poll_fn(|cx| {
+            let mut state = self.0 .0.lock();
+
+            let (result, wakers) = if let Some(value) = state.queue.pop_front() {
+                (Poll::Ready(Ok(value)), state.senders_wakers.take())
+            } else if state.senders == 0 {
+                (Poll::Ready(Err(Closed)), Wakers::new())
+            } else {
+                (Poll::Pending, state.receivers_wakers.register(cx))
+            };
+
+            drop(state);
+
+            wakers.wake();
+
+            result
+        })
+        .await
+    }
+
+    /// Receives the next payload if there is one, waking the senders waiting in `send`
+    pub fn try_recv(&self) -> Result<P, TryRecvError> {
+        let mut state = self.0 .0.lock();
+
+        if let Some(value) = state.queue.pop_front() {
+            let wakers = state.senders_wakers.take();
+
+            drop(state);
+
+            wakers.wake();
+
+            Ok(value)
+        } else if state.senders == 0 {
+            Err(TryRecvError::Closed)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> Clone for Receiver<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    fn clone(&self) -> Self {
+        self.0 .0.lock().receivers += 1;
+
+        Self(self.0)
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> Drop for Receiver<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock();
+
+        state.receivers -= 1;
+
+        if state.receivers == 0 {
+            let wakers = state.senders_wakers.take();
+
+            drop(state);
+
+            wakers.wake();
+        }
+    }
+}
+
+impl<'a, R, P, const N: usize, const W: usize> ErrorType for Receiver<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    type Error = Closed;
+}
+
+impl<'a, R, P, const N: usize, const W: usize> TryReceiver for Receiver<'a, R, P, N, W>
+where
+    R: RawMutex,
+{
+    type Data = P;
+
+    fn try_recv(&mut self) -> Result<Self::Data, TryRecvError> {
+        Receiver::try_recv(self)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use crate::event_bus::asynch;
+    use crate::utils::mutex::RawMutex;
+
+    use super::{Receiver, Sender};
+
+    impl<'a, R, P, const N: usize, const W: usize> asynch::Sender for Sender<'a, R, P, N, W>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            Sender::send(self, value).await
+        }
+    }
+
+    impl<'a, R, P, const N: usize, const W: usize> asynch::Receiver for Receiver<'a, R, P, N, W>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            Receiver::recv(self).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::Future;
+    use core::pin::{pin, Pin};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    const TASKS: u32 = 4;
+    const VALUES: u32 = 1000;
+
+    /// Sends `VALUES` payloads from each of `TASKS` producer threads to as many consumer threads,
+    /// returning what each consumer received
+    fn stress<const N: usize, const W: usize>() -> Vec<Vec<u32>> {
+        let channel = Channel::<StdRawMutex, u32, N, W>::new();
+
+        let (sender, receiver) = channel.split();
+
+        std::thread::scope(|scope| {
+            let consumers = (0..TASKS)
+                .map(|_| {
+                    let receiver = receiver.clone();
+
+                    scope.spawn(move || {
+                        let mut received = Vec::new();
+
+                        while let Ok(value) = block_on(receiver.recv()) {
+                            received.push(value);
+                        }
+
+                        received
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for producer in 0..TASKS {
+                let sender = sender.clone();
+
+                scope.spawn(move || {
+                    for value in 0..VALUES {
+                        block_on(sender.send(producer * VALUES + value)).unwrap();
+                    }
+                });
+            }
+
+            // The consumers are done once all the producers are, and their senders dropped
+            drop(sender);
+            drop(receiver);
+
+            consumers
+                .into_iter()
+                .map(|consumer| consumer.join().unwrap())
+                .collect()
+        })
+    }
+
+    fn check(received: Vec<Vec<u32>>) {
+        // The payloads of each producer are received in the order they were sent, by each of the consumers
+        for values in &received {
+            for producer in 0..TASKS {
+                let values = values
+                    .iter()
+                    .filter(|value| **value / VALUES == producer)
+                    .collect::<Vec<_>>();
+
+                assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+
+        let mut received = received.concat();
+        received.sort_unstable();
+
+        assert_eq!(received, (0..TASKS * VALUES).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn mpmc_threads() {
+        check(stress::<4, 4>());
+    }
+
+    #[test]
+    fn mpmc_threads_untracked_wakers() {
+        // Fewer wakers are tracked than there are tasks waiting on either side
+        check(stress::<1, 1>());
+    }
+
+    #[test]
+    fn closed() {
+        let channel = Channel::<StdRawMutex, u32, 2>::new();
+
+        let (sender, receiver) = channel.split();
+
+        sender.try_send(1).unwrap();
+        sender.try_send(2).unwrap();
+        assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(channel.len(), 2);
+
+        drop(sender);
+
+        // The payloads still queued are received before the channel reports being closed
+        assert_eq!(block_on(receiver.recv()), Ok(1));
+        assert_eq!(receiver.try_recv(), Ok(2));
+        assert_eq!(block_on(receiver.recv()), Err(Closed));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+
+        drop(receiver);
+
+        let (sender, receiver) = channel.split();
+
+        drop(receiver);
+
+        assert_eq!(block_on(sender.send(1)), Err(Closed));
+    }
+
+    /// Sends as many payloads as there is room for, returning the first one refused
+    fn fill<S>(mut sender: S, values: core::ops::Range<u32>) -> TrySendError<u32>
+    where
+        S: TrySender<Data = u32>,
+    {
+        for value in values {
+            if let Err(error) = sender.try_send(value) {
+                return error;
+            }
+        }
+
+        panic!("All the payloads were sent");
+    }
+
+    fn drain<T>(mut receiver: T) -> (Vec<u32>, TryRecvError)
+    where
+        T: TryReceiver<Data = u32>,
+    {
+        let mut received = Vec::new();
+
+        loop {
+            match receiver.try_recv() {
+                Ok(value) => received.push(value),
+                Err(error) => break (received, error),
+            }
+        }
+    }
+
+    #[test]
+    fn try_traits() {
+        let channel = Channel::<StdRawMutex, u32, 3>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let (mut sender, mut receiver) = channel.split();
+
+        {
+            let mut recv = pin!(receiver.recv());
+            assert!(poll(recv.as_mut(), &waker).is_pending());
+
+            // Sending without waiting still wakes the receivers waiting
+            assert_eq!(fill(&mut sender, 0..10), TrySendError::Full(3));
+            assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+
+            assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(0)));
+        }
+
+        assert_eq!(drain(&mut receiver), (vec![1, 2], TryRecvError::Empty));
+
+        drop(sender);
+        assert_eq!(drain(&mut receiver), (vec![], TryRecvError::Closed));
+
+        drop(receiver);
+
+        // The payload refused by a closed channel is handed back
+        let (sender, _) = channel.split();
+
+        let error = fill(sender, 10..20);
+        assert_eq!(error, TrySendError::Closed(10));
+        assert_eq!(error.into_inner(), 10);
+    }
+}
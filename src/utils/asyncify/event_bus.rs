@@ -1,5 +1,5 @@
-use core::fmt::Debug;
-use core::future::Future;
+use core::fmt::{self, Debug};
+use core::future::{poll_fn, Future};
 use core::marker::PhantomData;
 use core::pin::Pin;
 use core::task::{Context, Poll, Waker};
@@ -7,10 +7,11 @@ use core::time::Duration;
 
 extern crate alloc;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use crate::event_bus::ErrorType;
 use crate::utils::asyncify::Unblocker;
-use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
+use crate::utils::mutex::{Condvar, Mutex, RawCondvar, RawMutex};
 
 #[allow(unused_imports)]
 #[cfg(feature = "nightly")]
@@ -99,11 +100,75 @@ where
     _pd: PhantomData<fn() -> E>,
 }
 
+type SharedSubscriptionState<CV, P> = (
+    Mutex<<CV as RawCondvar>::RawMutex, SubscriptionState<P>>,
+    Condvar<CV>,
+);
+
+/// Delivers the payloads of an event bus callback to an `AsyncSubscription`
+struct SubscriptionCallback<CV, P>(alloc::sync::Weak<SharedSubscriptionState<CV, P>>)
+where
+    CV: RawCondvar;
+
+impl<CV, P> SubscriptionCallback<CV, P>
+where
+    CV: RawCondvar,
+    P: Clone,
+{
+    fn call(&self, payload: &P) {
+        if let Some(state) = self.0.upgrade() {
+            let pair: &SharedSubscriptionState<CV, P> = &state;
+
+            let (mut state, condvar) = (pair.0.lock(), &pair.1);
+
+            while state.value.is_some() {
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+
+                state = condvar.wait(state);
+            }
+
+            state.value = Some(payload.clone());
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<CV, P, S, E> AsyncSubscription<CV, P, S, E>
+where
+    CV: RawCondvar,
+    P: Send,
+{
+    fn new<F>(subscribe: F) -> Result<Self, E>
+    where
+        F: FnOnce(SubscriptionCallback<CV, P>) -> Result<S, E>,
+    {
+        let state = Arc::new((
+            Mutex::new(SubscriptionState {
+                value: None,
+                waker: None,
+            }),
+            Condvar::new(),
+        ));
+
+        let subscription = subscribe(SubscriptionCallback(Arc::downgrade(&state)))?;
+
+        Ok(Self {
+            state,
+            _subscription: subscription,
+            _pd: PhantomData,
+        })
+    }
+}
+
 impl<CV, P, S, E> AsyncSubscription<CV, P, S, E>
 where
     CV: RawCondvar + Send + Sync,
     CV::RawMutex: Send + Sync,
-    S: Send,
     P: Clone + Send,
 {
     pub async fn recv(&self) -> Result<P, E> {
@@ -115,15 +180,13 @@ struct NextFuture<'a, CV, P, S, E>(&'a AsyncSubscription<CV, P, S, E>)
 where
     CV: RawCondvar + Send + Sync,
     CV::RawMutex: Send + Sync,
-    P: Clone + Send,
-    S: Send;
+    P: Clone + Send;
 
 impl<'a, CV, P, S, E> Drop for NextFuture<'a, CV, P, S, E>
 where
     CV: RawCondvar + Send + Sync,
     CV::RawMutex: Send + Sync,
     P: Clone + Send,
-    S: Send,
 {
     fn drop(&mut self) {
         let mut state = self.0.state.0.lock();
@@ -136,7 +199,6 @@ where
     CV: RawCondvar + Send + Sync,
     CV::RawMutex: Send + Sync,
     P: Clone + Send,
-    S: Send,
 {
     type Output = Result<P, E>;
 
@@ -188,46 +250,77 @@ where
         P: Clone + Send + 'static,
         E: crate::event_bus::EventBus<P>,
     {
-        let state = Arc::new((
-            Mutex::new(SubscriptionState {
-                value: None,
-                waker: None,
-            }),
-            Condvar::new(),
-        ));
-
-        let subscription_state = Arc::downgrade(&state);
-
-        let subscription = self.event_bus.subscribe(move |payload| {
-            if let Some(state) = subscription_state.upgrade() {
-                let pair: &(Mutex<CV::RawMutex, _>, Condvar<CV>) = &state;
-
-                let (mut state, condvar) = (pair.0.lock(), &pair.1);
+        AsyncSubscription::new(|callback| {
+            self.event_bus
+                .subscribe(move |payload: &P| callback.call(payload))
+        })
+    }
+}
 
-                while state.value.is_some() {
-                    if let Some(waker) = state.waker.take() {
-                        waker.wake();
-                    }
+/// An adapter exposing a `PinnedEventBus` with the same async subscriptions as `AsyncEventBus`
+///
+/// The payloads are delivered by a callback running in the pinned context of the bus, which also wakes
+/// the subscription; the futures of the subscription are polled wherever the executor runs them.
+/// While the previous payload has not been received, the callback blocks the pinned context,
+/// so the subscription should not be polled from that same context.
+///
+/// Dropping the subscription drops the subscription of the pinned bus, deregistering its callback.
+pub struct AsyncPinnedEventBus<CV, E> {
+    event_bus: E,
+    _condvar_type: PhantomData<fn() -> CV>,
+}
 
-                    state = condvar.wait(state);
-                }
+impl<CV, E> AsyncPinnedEventBus<CV, E> {
+    pub const fn new(event_bus: E) -> Self {
+        Self {
+            event_bus,
+            _condvar_type: PhantomData,
+        }
+    }
+}
 
-                state.value = Some(payload.clone());
+#[allow(clippy::type_complexity)]
+impl<CV, E> AsyncPinnedEventBus<CV, E>
+where
+    CV: RawCondvar + Send + Sync + 'static,
+    CV::RawMutex: Send + Sync + 'static,
+{
+    pub fn subscribe<P>(
+        &self,
+    ) -> Result<AsyncSubscription<CV, P, E::Subscription<'_>, E::Error>, E::Error>
+    where
+        P: Clone + Send + 'static,
+        E: crate::event_bus::PinnedEventBus<P>,
+    {
+        AsyncSubscription::new(|callback| {
+            self.event_bus
+                .subscribe(move |payload: &P| callback.call(payload))
+        })
+    }
+}
 
-                if let Some(waker) = state.waker.take() {
-                    waker.wake();
-                }
-            }
-        })?;
+impl<CV, E> Clone for AsyncPinnedEventBus<CV, E>
+where
+    E: Clone,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.event_bus.clone())
+    }
+}
 
-        Ok(AsyncSubscription {
-            state,
-            _subscription: subscription,
-            _pd: PhantomData,
-        })
+impl<CV, E> AsyncWrapper<E> for AsyncPinnedEventBus<CV, E> {
+    fn new(sync: E) -> Self {
+        AsyncPinnedEventBus::new(sync)
     }
 }
 
+impl<CV, E> ErrorType for AsyncPinnedEventBus<CV, E>
+where
+    E: ErrorType,
+{
+    type Error = E::Error;
+}
+
 impl<U, CV, E> AsyncEventBus<U, CV, E>
 where
     U: Clone,
@@ -299,109 +392,1125 @@ impl<CV, P, S, E> ErrorType for AsyncSubscription<CV, P, S, E>
 where
     CV: RawCondvar,
     P: Send,
-    S: Send,
     E: Debug,
 {
     type Error = E;
 }
 
-#[cfg(feature = "nightly")]
-mod async_traits_impl {
-    use core::fmt::Debug;
-
-    use crate::event_bus::asynch::{EventBus, PostboxProvider, Receiver, Sender};
-    use crate::utils::asyncify::Unblocker;
-    use crate::utils::mutex::RawCondvar;
+/// The error returned by `BoundedPostbox` and `BoundedPostboxReceiver` once the other side is gone
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PostboxClosed;
 
-    use super::{AsyncEventBus, AsyncPostbox, AsyncSubscription};
+impl fmt::Display for PostboxClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Postbox closed")
+    }
+}
 
-    impl<U, P, PB> Sender for AsyncPostbox<U, P, PB>
-    where
-        U: Unblocker,
-        P: Clone + Send + 'static,
-        PB: crate::event_bus::Postbox<P> + Clone + Send + Sync,
-        PB::Error: Send,
-    {
-        type Data = P;
+#[cfg(feature = "std")]
+impl std::error::Error for PostboxClosed {}
 
-        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
-            let value = value;
-            let blocking_postbox = self.blocking_postbox.clone();
+struct BoundedState<P, const N: usize> {
+    queue: heapless::Deque<P, N>,
+    senders_wakers: Vec<Waker>,
+    receiver_waker: Option<Waker>,
+    senders: usize,
+    receiver: bool,
+}
 
-            self.unblocker
-                .unblock(move || blocking_postbox.post(&value, None).map(|_| ()))
-                .await
+impl<P, const N: usize> BoundedState<P, N> {
+    fn wake_senders(&mut self) {
+        for waker in self.senders_wakers.drain(..) {
+            waker.wake();
         }
     }
+}
 
-    impl<P, PB> Sender for AsyncPostbox<(), P, PB>
-    where
-        P: Send,
-        PB: crate::event_bus::Postbox<P>,
-    {
-        type Data = P;
+/// The producing side of a queue of up to `N` payloads, created with `bounded_postbox`
+///
+/// `send` waits until there is room in the queue, rather than failing or blocking.
+/// When room becomes available, all waiting senders are woken, and whichever of them is polled first
+/// gets the slot, so there is no fairness between the senders.
+pub struct BoundedPostbox<R, P, const N: usize>(Arc<Mutex<R, BoundedState<P, N>>>)
+where
+    R: RawMutex;
 
-        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
-            AsyncPostbox::send_blocking(self, value, Some(core::time::Duration::MAX))
-        }
+/// The consuming side of a queue created with `bounded_postbox`
+pub struct BoundedPostboxReceiver<R, P, const N: usize>(Arc<Mutex<R, BoundedState<P, N>>>)
+where
+    R: RawMutex;
+
+/// Creates a queue of up to `N` payloads, for async producers feeding e.g. a (blocking) event bus
+/// at the pace of `BoundedPostboxReceiver::forward`
+pub fn bounded_postbox<R, P, const N: usize>(
+) -> (BoundedPostbox<R, P, N>, BoundedPostboxReceiver<R, P, N>)
+where
+    R: RawMutex,
+{
+    let state = Arc::new(Mutex::new(BoundedState {
+        queue: heapless::Deque::new(),
+        senders_wakers: Vec::new(),
+        receiver_waker: None,
+        senders: 1,
+        receiver: true,
+    }));
+
+    (BoundedPostbox(state.clone()), BoundedPostboxReceiver(state))
+}
+
+impl<R, P, const N: usize> BoundedPostbox<R, P, N>
+where
+    R: RawMutex,
+{
+    pub async fn send(&self, value: P) -> Result<(), PostboxClosed> {
+        let mut value = Some(value);
+
+        poll_fn(|cx| {
+            let mut state = self.0.lock();
+
+            if !state.receiver {
+                return Poll::Ready(Err(PostboxClosed));
+            }
+
+            if !state.queue.is_full() {
+                if let Some(value) = value.take() {
+                    let _ = state.queue.push_back(value);
+                }
+
+                if let Some(waker) = state.receiver_waker.take() {
+                    waker.wake();
+                }
+
+                Poll::Ready(Ok(()))
+            } else {
+                if !state
+                    .senders_wakers
+                    .iter()
+                    .any(|waker| waker.will_wake(cx.waker()))
+                {
+                    state.senders_wakers.push(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+        })
+        .await
     }
 
-    impl<CV, P, S, E> Receiver for AsyncSubscription<CV, P, S, E>
-    where
-        CV: RawCondvar + Send + Sync,
-        CV::RawMutex: Send + Sync,
-        S: Send,
-        P: Clone + Send,
-        E: Debug,
-    {
-        type Data = P;
+    /// Queues `value` if there is room for it, or else returns it
+    pub fn try_send(&self, value: P) -> Result<Result<(), P>, PostboxClosed> {
+        let mut state = self.0.lock();
 
-        async fn recv(&self) -> Result<Self::Data, Self::Error> {
-            AsyncSubscription::recv(self).await
+        if !state.receiver {
+            return Err(PostboxClosed);
         }
-    }
 
-    impl<U, CV, P, E> EventBus<P> for AsyncEventBus<U, CV, E>
-    where
-        CV: RawCondvar + Send + Sync + 'static,
-        CV::RawMutex: Send + Sync + 'static,
-        P: Clone + Send + 'static,
-        E: crate::event_bus::EventBus<P>,
-        for<'a> E::Subscription<'a>: Send,
-    {
-        type Subscription<'a> = AsyncSubscription<CV, P, E::Subscription<'a>, E::Error> where Self: 'a;
+        let result = state.queue.push_back(value);
 
-        async fn subscribe(&self) -> Result<Self::Subscription<'_>, Self::Error> {
-            AsyncEventBus::subscribe(self)
+        if result.is_ok() {
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
         }
+
+        Ok(result)
     }
+}
 
-    impl<U, CV, P, E> PostboxProvider<P> for AsyncEventBus<U, CV, E>
-    where
-        U: Unblocker + Clone,
-        CV: RawCondvar + Send + Sync + 'static,
-        P: Clone + Send + 'static,
-        for<'a> E::Postbox<'a>: Clone + Send + Sync,
-        E: crate::event_bus::PostboxProvider<P>,
-        Self::Error: Send + Sync + 'static,
-    {
-        type Postbox<'a> = AsyncPostbox<U, P, E::Postbox<'a>> where Self: 'a;
+impl<R, P, const N: usize> Clone for BoundedPostbox<R, P, N>
+where
+    R: RawMutex,
+{
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
 
-        async fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error> {
-            AsyncEventBus::postbox(self)
+        Self(self.0.clone())
+    }
+}
+
+impl<R, P, const N: usize> Drop for BoundedPostbox<R, P, N>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
         }
     }
+}
+
+impl<R, P, const N: usize> BoundedPostboxReceiver<R, P, N>
+where
+    R: RawMutex,
+{
+    /// Receives the next payload; fails once the queue is empty and all senders are gone
+    pub async fn recv(&self) -> Result<P, PostboxClosed> {
+        poll_fn(|cx| {
+            let mut state = self.0.lock();
+
+            if let Some(value) = state.queue.pop_front() {
+                state.wake_senders();
+
+                Poll::Ready(Ok(value))
+            } else if state.senders == 0 {
+                Poll::Ready(Err(PostboxClosed))
+            } else {
+                state.receiver_waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        })
+        .await
+    }
 
-    impl<CV, P, E> PostboxProvider<P> for AsyncEventBus<(), CV, E>
+    /// Forwards the payloads to `postbox` until all senders are gone
+    pub async fn forward<U, PB>(&self, postbox: &AsyncPostbox<U, P, PB>) -> Result<(), PB::Error>
     where
-        CV: RawCondvar + Send + Sync + 'static,
+        U: Unblocker,
         P: Send + 'static,
-        E: crate::event_bus::PostboxProvider<P>,
+        PB: crate::event_bus::Postbox<P> + Sync,
+        PB::Error: Send + 'static,
     {
-        type Postbox<'a> = AsyncPostbox<(), P, E::Postbox<'a>> where Self: 'a;
+        while let Ok(value) = self.recv().await {
+            postbox.send(value).await?;
+        }
 
-        async fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error> {
-            AsyncEventBus::postbox(self)
+        Ok(())
+    }
+}
+
+impl<R, P, const N: usize> Drop for BoundedPostboxReceiver<R, P, N>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+
+        state.receiver = false;
+        state.wake_senders();
+    }
+}
+
+impl<R, P, const N: usize> ErrorType for BoundedPostbox<R, P, N>
+where
+    R: RawMutex,
+{
+    type Error = PostboxClosed;
+}
+
+impl<R, P, const N: usize> ErrorType for BoundedPostboxReceiver<R, P, N>
+where
+    R: RawMutex,
+{
+    type Error = PostboxClosed;
+}
+
+/// What a bounded queue of events (of a `Broadcaster` subscriber, or of a `SubscriptionReceiver`) does with an event
+/// when it is full
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OverflowPolicy {
+    /// Drops the oldest event of the queue to make room
+    #[default]
+    DropOldest,
+    /// Drops the event, and fails the next `recv` of the subscriber with `Overflowed`
+    Error,
+}
+
+/// The error returned by `BroadcastSubscription::recv` and `SubscriptionReceiver::recv` when events were dropped
+/// under `OverflowPolicy::Error`
+///
+/// Contains the number of events dropped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Overflowed(pub usize);
+
+impl fmt::Display for Overflowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Overflowed, {} events dropped", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Overflowed {}
+
+struct EventQueue<P, const Q: usize> {
+    policy: OverflowPolicy,
+    queue: heapless::Deque<P, Q>,
+    overflowed: usize,
+    waker: Option<Waker>,
+}
+
+impl<P, const Q: usize> EventQueue<P, Q> {
+    const fn new(policy: OverflowPolicy) -> Self {
+        Self {
+            policy,
+            queue: heapless::Deque::new(),
+            overflowed: 0,
+            waker: None,
         }
     }
+
+    fn push(&mut self, payload: P) {
+        if self.queue.is_full() {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    self.queue.pop_front();
+                }
+                OverflowPolicy::Error => {
+                    self.overflowed += 1;
+                    return;
+                }
+            }
+        }
+
+        let _ = self.queue.push_back(payload);
+
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &Context<'_>) -> Poll<Result<P, Overflowed>> {
+        if self.overflowed > 0 {
+            let overflowed = self.overflowed;
+            self.overflowed = 0;
+
+            Poll::Ready(Err(Overflowed(overflowed)))
+        } else if let Some(payload) = self.queue.pop_front() {
+            Poll::Ready(Ok(payload))
+        } else {
+            self.waker = Some(cx.waker().clone());
+
+            Poll::Pending
+        }
+    }
+}
+
+struct BroadcastSlot<P, const Q: usize> {
+    subscribed: bool,
+    queue: EventQueue<P, Q>,
+}
+
+/// Clones each of the events posted to it into the queues (of `Q` events each) of up to `N` async subscribers
+///
+/// The broadcaster is a (blocking) `Postbox`, so it can be fed e.g. by the subscription callback of an event bus.
+/// The subscribers are isolated from each other: a slow subscriber only overflows its own queue.
+pub struct Broadcaster<R, P, const N: usize, const Q: usize = 8>(
+    Mutex<R, [BroadcastSlot<P, Q>; N]>,
+)
+where
+    R: RawMutex;
+
+impl<R, P, const N: usize, const Q: usize> Broadcaster<R, P, N, Q>
+where
+    R: RawMutex,
+{
+    pub fn new() -> Self {
+        Self(Mutex::new(core::array::from_fn(|_| BroadcastSlot {
+            subscribed: false,
+            queue: EventQueue::new(OverflowPolicy::DropOldest),
+        })))
+    }
+
+    /// Subscribes to the events posted from now on, or returns `None` if all `N` slots are taken
+    pub fn subscribe(
+        &self,
+        policy: OverflowPolicy,
+    ) -> Option<BroadcastSubscription<'_, R, P, N, Q>> {
+        let mut slots = self.0.lock();
+
+        let index = slots.iter().position(|slot| !slot.subscribed)?;
+
+        let slot = &mut slots[index];
+        slot.subscribed = true;
+        slot.queue = EventQueue::new(policy);
+
+        Some(BroadcastSubscription {
+            broadcaster: self,
+            index,
+        })
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.0.lock().iter().filter(|slot| slot.subscribed).count()
+    }
+
+    pub fn broadcast(&self, payload: &P)
+    where
+        P: Clone,
+    {
+        let mut slots = self.0.lock();
+
+        for slot in slots.iter_mut().filter(|slot| slot.subscribed) {
+            slot.queue.push(payload.clone());
+        }
+    }
+}
+
+impl<R, P, const N: usize, const Q: usize> Default for Broadcaster<R, P, N, Q>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, P, const N: usize, const Q: usize> ErrorType for Broadcaster<R, P, N, Q>
+where
+    R: RawMutex,
+{
+    type Error = core::convert::Infallible;
+}
+
+impl<R, P, const N: usize, const Q: usize> crate::event_bus::Postbox<P> for Broadcaster<R, P, N, Q>
+where
+    R: RawMutex,
+    P: Clone,
+{
+    fn post(&self, payload: &P, _wait: Option<Duration>) -> Result<bool, Self::Error> {
+        self.broadcast(payload);
+
+        Ok(true)
+    }
+}
+
+/// A subscription to a `Broadcaster`; dropping it frees its slot
+pub struct BroadcastSubscription<'a, R, P, const N: usize, const Q: usize>
+where
+    R: RawMutex,
+{
+    broadcaster: &'a Broadcaster<R, P, N, Q>,
+    index: usize,
+}
+
+impl<'a, R, P, const N: usize, const Q: usize> BroadcastSubscription<'a, R, P, N, Q>
+where
+    R: RawMutex,
+{
+    pub async fn recv(&self) -> Result<P, Overflowed> {
+        poll_fn(|cx| self.broadcaster.0.lock()[self.index].queue.poll_recv(cx)).await
+    }
+}
+
+impl<'a, R, P, const N: usize, const Q: usize> Drop for BroadcastSubscription<'a, R, P, N, Q>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut slots = self.broadcaster.0.lock();
+        let slot = &mut slots[self.index];
+
+        slot.subscribed = false;
+        slot.queue = EventQueue::new(OverflowPolicy::DropOldest);
+    }
+}
+
+impl<'a, R, P, const N: usize, const Q: usize> ErrorType for BroadcastSubscription<'a, R, P, N, Q>
+where
+    R: RawMutex,
+{
+    type Error = Overflowed;
+}
+
+/// An async receiver of the events of a (blocking) event bus subscription, buffering up to `Q` events
+///
+/// Unlike `AsyncSubscription`, the callback of the subscription never blocks: when the buffer is full,
+/// the event is handled as per the `OverflowPolicy` of the receiver.
+/// Dropping the receiver drops the subscription.
+pub struct SubscriptionReceiver<R, P, S, const Q: usize = 8>
+where
+    R: RawMutex,
+{
+    queue: Arc<Mutex<R, EventQueue<P, Q>>>,
+    _subscription: S,
+}
+
+impl<R, P, S, const Q: usize> SubscriptionReceiver<R, P, S, Q>
+where
+    R: RawMutex,
+{
+    pub async fn recv(&self) -> Result<P, Overflowed> {
+        poll_fn(|cx| self.queue.lock().poll_recv(cx)).await
+    }
+}
+
+impl<R, P, S, const Q: usize> ErrorType for SubscriptionReceiver<R, P, S, Q>
+where
+    R: RawMutex,
+{
+    type Error = Overflowed;
+}
+
+/// Subscribes to `event_bus`, returning an async receiver of its events
+pub fn subscription_receiver<R, P, B, const Q: usize>(
+    event_bus: &B,
+    policy: OverflowPolicy,
+) -> Result<SubscriptionReceiver<R, P, B::Subscription<'_>, Q>, B::Error>
+where
+    R: RawMutex + Send + Sync + 'static,
+    P: Clone + Send + 'static,
+    B: crate::event_bus::EventBus<P>,
+{
+    let queue = Arc::new(Mutex::new(EventQueue::new(policy)));
+
+    let subscription = {
+        let queue = queue.clone();
+
+        event_bus.subscribe(move |payload: &P| queue.lock().push(payload.clone()))?
+    };
+
+    Ok(SubscriptionReceiver {
+        queue,
+        _subscription: subscription,
+    })
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use core::fmt::Debug;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use core::time::Duration;
+
+    use crate::event_bus::asynch::{EventBus, PostboxProvider, Receiver, Sender};
+    use crate::event_bus::{ErrorType, Postbox};
+    use crate::utils::asyncify::Unblocker;
+    use crate::utils::mutex::{RawCondvar, RawMutex};
+
+    use super::{
+        AsyncEventBus, AsyncPinnedEventBus, AsyncPostbox, AsyncSubscription, BoundedPostbox,
+        BoundedPostboxReceiver, BroadcastSubscription, SubscriptionReceiver,
+    };
+
+    impl<'a, R, P, const N: usize, const Q: usize> Receiver for BroadcastSubscription<'a, R, P, N, Q>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            BroadcastSubscription::recv(self).await
+        }
+    }
+
+    impl<R, P, S, const Q: usize> Receiver for SubscriptionReceiver<R, P, S, Q>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            SubscriptionReceiver::recv(self).await
+        }
+    }
+
+    const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(core::ptr::null(), &NOOP_VTABLE),
+        |_| (),
+        |_| (),
+        |_| (),
+    );
+
+    /// A (blocking) `Postbox` posting to an async `Sender`
+    ///
+    /// Posting never blocks, regardless of the `wait` duration: the send of the payload is polled once,
+    /// and if the sender cannot take the payload right away (e.g. because it is full), the send is dropped
+    /// together with the payload, and `post` returns `Ok(false)`.
+    pub struct SenderPostbox<S>(S);
+
+    impl<S> SenderPostbox<S> {
+        pub const fn new(sender: S) -> Self {
+            Self(sender)
+        }
+
+        pub fn sender(&self) -> &S {
+            &self.0
+        }
+
+        pub fn release(self) -> S {
+            self.0
+        }
+    }
+
+    impl<S> ErrorType for SenderPostbox<S>
+    where
+        S: ErrorType,
+    {
+        type Error = S::Error;
+    }
+
+    impl<S> Postbox<S::Data> for SenderPostbox<S>
+    where
+        S: Sender,
+        S::Data: Clone,
+    {
+        fn post(&self, payload: &S::Data, _wait: Option<Duration>) -> Result<bool, Self::Error> {
+            // Safety: the vtable functions do nothing with the (null) data pointer
+            let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &NOOP_VTABLE)) };
+
+            let send = pin!(self.0.send(payload.clone()));
+
+            match send.poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(result) => result.map(|_| true),
+                Poll::Pending => Ok(false),
+            }
+        }
+    }
+
+    impl<R, P, const N: usize> Sender for BoundedPostbox<R, P, N>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            BoundedPostbox::send(self, value).await
+        }
+    }
+
+    impl<R, P, const N: usize> Receiver for BoundedPostboxReceiver<R, P, N>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            BoundedPostboxReceiver::recv(self).await
+        }
+    }
+
+    impl<U, P, PB> Sender for AsyncPostbox<U, P, PB>
+    where
+        U: Unblocker,
+        P: Clone + Send + 'static,
+        PB: crate::event_bus::Postbox<P> + Clone + Send + Sync,
+        PB::Error: Send,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            let value = value;
+            let blocking_postbox = self.blocking_postbox.clone();
+
+            self.unblocker
+                .unblock(move || blocking_postbox.post(&value, None).map(|_| ()))
+                .await
+        }
+    }
+
+    impl<P, PB> Sender for AsyncPostbox<(), P, PB>
+    where
+        P: Send,
+        PB: crate::event_bus::Postbox<P>,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            AsyncPostbox::send_blocking(self, value, Some(core::time::Duration::MAX))
+        }
+    }
+
+    impl<CV, P, S, E> Receiver for AsyncSubscription<CV, P, S, E>
+    where
+        CV: RawCondvar + Send + Sync,
+        CV::RawMutex: Send + Sync,
+        P: Clone + Send,
+        E: Debug,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            AsyncSubscription::recv(self).await
+        }
+    }
+
+    impl<U, CV, P, E> EventBus<P> for AsyncEventBus<U, CV, E>
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+        P: Clone + Send + 'static,
+        E: crate::event_bus::EventBus<P>,
+        for<'a> E::Subscription<'a>: Send,
+    {
+        type Subscription<'a> = AsyncSubscription<CV, P, E::Subscription<'a>, E::Error> where Self: 'a;
+
+        async fn subscribe(&self) -> Result<Self::Subscription<'_>, Self::Error> {
+            AsyncEventBus::subscribe(self)
+        }
+    }
+
+    impl<CV, P, E> EventBus<P> for AsyncPinnedEventBus<CV, E>
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+        P: Clone + Send + 'static,
+        E: crate::event_bus::PinnedEventBus<P>,
+    {
+        type Subscription<'a> = AsyncSubscription<CV, P, E::Subscription<'a>, E::Error> where Self: 'a;
+
+        async fn subscribe(&self) -> Result<Self::Subscription<'_>, Self::Error> {
+            AsyncPinnedEventBus::subscribe(self)
+        }
+    }
+
+    impl<U, CV, P, E> PostboxProvider<P> for AsyncEventBus<U, CV, E>
+    where
+        U: Unblocker + Clone,
+        CV: RawCondvar + Send + Sync + 'static,
+        P: Clone + Send + 'static,
+        for<'a> E::Postbox<'a>: Clone + Send + Sync,
+        E: crate::event_bus::PostboxProvider<P>,
+        Self::Error: Send + Sync + 'static,
+    {
+        type Postbox<'a> = AsyncPostbox<U, P, E::Postbox<'a>> where Self: 'a;
+
+        async fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error> {
+            AsyncEventBus::postbox(self)
+        }
+    }
+
+    impl<CV, P, E> PostboxProvider<P> for AsyncEventBus<(), CV, E>
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        P: Send + 'static,
+        E: crate::event_bus::PostboxProvider<P>,
+    {
+        type Postbox<'a> = AsyncPostbox<(), P, E::Postbox<'a>> where Self: 'a;
+
+        async fn postbox(&self) -> Result<Self::Postbox<'_>, Self::Error> {
+            AsyncEventBus::postbox(self)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::cell::RefCell;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use std::rc::Rc;
+    use std::task::Wake;
+
+    use crate::test_event_bus::MockBus;
+    use crate::utils::asyncify::{block_on, blocking_unblocker};
+    use crate::utils::mutex::{StdRawCondvar, StdRawMutex};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl CountingWaker {
+        fn wakes(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    type PinnedSubscribers = Rc<RefCell<Vec<(usize, Box<dyn FnMut(&u32)>)>>>;
+
+    /// A single-threaded pinned bus, calling its subscribers from `post`
+    #[derive(Default)]
+    struct PinnedBus {
+        subscribers: PinnedSubscribers,
+        next_id: RefCell<usize>,
+    }
+
+    impl PinnedBus {
+        fn post(&self, payload: u32) {
+            for (_, subscriber) in self.subscribers.borrow_mut().iter_mut() {
+                subscriber(&payload);
+            }
+        }
+
+        fn subscribers(&self) -> usize {
+            self.subscribers.borrow().len()
+        }
+    }
+
+    impl ErrorType for PinnedBus {
+        type Error = ();
+    }
+
+    impl crate::event_bus::PinnedEventBus<u32> for PinnedBus {
+        type Subscription<'a> = PinnedSubscription where Self: 'a;
+
+        fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+        where
+            F: FnMut(&u32) + 'static,
+        {
+            let id = {
+                let mut next_id = self.next_id.borrow_mut();
+                *next_id += 1;
+                *next_id
+            };
+
+            self.subscribers.borrow_mut().push((id, Box::new(callback)));
+
+            Ok(PinnedSubscription(self.subscribers.clone(), id))
+        }
+    }
+
+    struct PinnedSubscription(PinnedSubscribers, usize);
+
+    impl Drop for PinnedSubscription {
+        fn drop(&mut self) {
+            self.0.borrow_mut().retain(|(id, _)| *id != self.1);
+        }
+    }
+
+    #[test]
+    fn pinned_subscription() {
+        let bus = AsyncPinnedEventBus::<StdRawCondvar, _>::new(PinnedBus::default());
+        let waker = Arc::new(CountingWaker::default());
+
+        let subscription = bus.subscribe::<u32>().unwrap();
+        assert_eq!(bus.event_bus.subscribers(), 1);
+
+        for value in 1..=3 {
+            let mut recv = pin!(subscription.recv());
+            assert!(poll(recv.as_mut(), &waker).is_pending());
+
+            // The callback runs in the context of the bus, and wakes the pending receive
+            bus.event_bus.post(value);
+            assert_eq!(waker.wakes(), value as usize);
+
+            assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(value)));
+        }
+
+        // A payload posted with no receive pending is kept for the next one
+        bus.event_bus.post(4);
+        assert_eq!(waker.wakes(), 3);
+        assert_eq!(block_on(subscription.recv()), Ok(4));
+
+        core::mem::drop(subscription);
+        assert_eq!(bus.event_bus.subscribers(), 0);
+    }
+
+    #[test]
+    fn bounded_postbox_backpressure() {
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 2>();
+        let waker = Arc::new(CountingWaker::default());
+        let other_waker = Arc::new(CountingWaker::default());
+
+        block_on(postbox.send(1)).unwrap();
+        block_on(postbox.send(2)).unwrap();
+
+        {
+            let other = postbox.clone();
+
+            let mut send = pin!(postbox.send(3));
+            let mut other_send = pin!(other.send(4));
+
+            assert!(poll(send.as_mut(), &waker).is_pending());
+            assert!(poll(other_send.as_mut(), &other_waker).is_pending());
+
+            // Both senders are woken when room frees up, and the first one polled gets the slot
+            assert_eq!(block_on(receiver.recv()), Ok(1));
+            assert_eq!((waker.wakes(), other_waker.wakes()), (1, 1));
+
+            assert!(poll(other_send.as_mut(), &other_waker).is_ready());
+            assert!(poll(send.as_mut(), &waker).is_pending());
+
+            assert_eq!(block_on(receiver.recv()), Ok(2));
+            assert_eq!(waker.wakes(), 2);
+            assert!(poll(send.as_mut(), &waker).is_ready());
+        }
+
+        assert_eq!(block_on(receiver.recv()), Ok(4));
+        assert_eq!(block_on(receiver.recv()), Ok(3));
+
+        {
+            let mut recv = pin!(receiver.recv());
+            assert!(poll(recv.as_mut(), &waker).is_pending());
+
+            assert_eq!(postbox.try_send(5), Ok(Ok(())));
+            assert_eq!(waker.wakes(), 3);
+            assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(5)));
+        }
+    }
+
+    #[test]
+    fn bounded_postbox_try_send() {
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 1>();
+
+        assert_eq!(postbox.try_send(1), Ok(Ok(())));
+        assert_eq!(postbox.try_send(2), Ok(Err(2)));
+
+        assert_eq!(block_on(receiver.recv()), Ok(1));
+        assert_eq!(postbox.try_send(3), Ok(Ok(())));
+    }
+
+    #[test]
+    fn bounded_postbox_closed() {
+        let waker = Arc::new(CountingWaker::default());
+
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 1>();
+
+        postbox.try_send(1).unwrap().unwrap();
+
+        {
+            let mut send = pin!(postbox.send(2));
+            assert!(poll(send.as_mut(), &waker).is_pending());
+
+            core::mem::drop(receiver);
+
+            assert_eq!(waker.wakes(), 1);
+            assert_eq!(poll(send.as_mut(), &waker), Poll::Ready(Err(PostboxClosed)));
+        }
+
+        assert_eq!(postbox.try_send(3), Err(PostboxClosed));
+
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 2>();
+        let other = postbox.clone();
+
+        postbox.try_send(1).unwrap().unwrap();
+        core::mem::drop(postbox);
+
+        let mut recv = pin!(receiver.recv());
+        assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(1)));
+
+        let mut recv = pin!(receiver.recv());
+        assert!(poll(recv.as_mut(), &waker).is_pending());
+
+        // Once the last sender is gone, the receiver fails when the queue is empty
+        core::mem::drop(other);
+
+        assert_eq!(waker.wakes(), 2);
+        assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Err(PostboxClosed)));
+    }
+
+    #[test]
+    fn bounded_postbox_forward() {
+        let bus = MockBus::new();
+        let bus_postbox = AsyncPostbox::new(blocking_unblocker(), &bus);
+
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 4>();
+
+        for value in 1..=3 {
+            postbox.try_send(value).unwrap().unwrap();
+        }
+
+        core::mem::drop(postbox);
+
+        block_on(receiver.forward(&bus_postbox)).unwrap();
+        assert_eq!(bus.posted(), [1, 2, 3]);
+
+        // The errors of the bus stop the forwarding
+        let (postbox, receiver) = bounded_postbox::<StdRawMutex, u32, 4>();
+
+        postbox.try_send(4).unwrap().unwrap();
+        bus.set_fail(true);
+
+        assert!(block_on(receiver.forward(&bus_postbox)).is_err());
+    }
+
+    #[test]
+    fn broadcast() {
+        let broadcaster = Broadcaster::<StdRawMutex, u32, 2, 4>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let first = broadcaster.subscribe(OverflowPolicy::DropOldest).unwrap();
+        let second = broadcaster.subscribe(OverflowPolicy::DropOldest).unwrap();
+
+        assert!(broadcaster.subscribe(OverflowPolicy::DropOldest).is_none());
+        assert_eq!(broadcaster.subscribers(), 2);
+
+        {
+            let mut recv = pin!(first.recv());
+            assert!(poll(recv.as_mut(), &waker).is_pending());
+
+            assert!(crate::event_bus::Postbox::post(&broadcaster, &1, None).unwrap());
+
+            assert_eq!(waker.wakes(), 1);
+            assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(1)));
+        }
+
+        broadcaster.broadcast(&2);
+
+        assert_eq!(block_on(first.recv()), Ok(2));
+        assert_eq!(block_on(second.recv()), Ok(1));
+        assert_eq!(block_on(second.recv()), Ok(2));
+
+        // Dropping a subscription frees its slot, for a subscriber of the events posted from then on
+        core::mem::drop(second);
+        assert_eq!(broadcaster.subscribers(), 1);
+
+        let third = broadcaster.subscribe(OverflowPolicy::DropOldest).unwrap();
+
+        broadcaster.broadcast(&3);
+
+        assert_eq!(block_on(first.recv()), Ok(3));
+        assert_eq!(block_on(third.recv()), Ok(3));
+    }
+
+    #[test]
+    fn broadcast_overflow() {
+        let broadcaster = Broadcaster::<StdRawMutex, u32, 2, 2>::default();
+        let waker = Arc::new(CountingWaker::default());
+
+        let dropping = broadcaster.subscribe(OverflowPolicy::DropOldest).unwrap();
+        let failing = broadcaster.subscribe(OverflowPolicy::Error).unwrap();
+
+        for value in 1..=5 {
+            broadcaster.broadcast(&value);
+        }
+
+        assert_eq!(block_on(dropping.recv()), Ok(4));
+        assert_eq!(block_on(dropping.recv()), Ok(5));
+
+        // The first receive reports the events dropped, and the next ones the events queued before the overflow
+        assert_eq!(block_on(failing.recv()), Err(Overflowed(3)));
+        assert_eq!(block_on(failing.recv()), Ok(1));
+        assert_eq!(block_on(failing.recv()), Ok(2));
+
+        let mut recv = pin!(failing.recv());
+        assert!(poll(recv.as_mut(), &waker).is_pending());
+    }
+
+    /// Receives the events already queued, returning them (or the overflows) in order
+    fn drain<R, S, const Q: usize>(
+        receiver: &SubscriptionReceiver<R, u32, S, Q>,
+        waker: &Arc<CountingWaker>,
+    ) -> Vec<Result<u32, Overflowed>>
+    where
+        R: RawMutex,
+    {
+        let mut received = Vec::new();
+
+        while let Poll::Ready(result) = poll(pin!(receiver.recv()), waker) {
+            received.push(result);
+        }
+
+        received
+    }
+
+    #[test]
+    fn subscription_receiver_slow_consumer() {
+        let bus = MockBus::<u32>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let dropping =
+            subscription_receiver::<StdRawMutex, _, _, 8>(&bus, OverflowPolicy::DropOldest)
+                .unwrap();
+        let failing =
+            subscription_receiver::<StdRawMutex, _, _, 8>(&bus, OverflowPolicy::Error).unwrap();
+
+        assert_eq!(bus.subscribers(), 2);
+
+        let mut dropping_received = Vec::new();
+        let mut failing_received = Vec::new();
+
+        for value in 1..=100 {
+            crate::event_bus::Postbox::post(&bus, &value, None).unwrap();
+
+            // The consumer only keeps up with one event out of five
+            if value % 5 == 0 {
+                dropping_received.push(block_on(dropping.recv()));
+                failing_received.push(block_on(failing.recv()));
+            }
+        }
+
+        let dropping_tail = drain(&dropping, &waker);
+        assert_eq!(dropping_tail, (94..=100).map(Ok).collect::<Vec<_>>());
+
+        dropping_received.extend(dropping_tail);
+        failing_received.extend(drain(&failing, &waker));
+
+        // Whatever the policy, the events received are in the order they were posted
+        for received in [&dropping_received, &failing_received] {
+            let values = received.iter().filter_map(|result| result.ok());
+
+            assert!(values.clone().zip(values.skip(1)).all(|(a, b)| a < b));
+        }
+
+        // Under `Error`, every event is either received or reported as dropped
+        let accounted: usize = failing_received
+            .iter()
+            .map(|result| match result {
+                Ok(_) => 1,
+                Err(Overflowed(dropped)) => *dropped,
+            })
+            .sum();
+
+        assert_eq!(accounted, 100);
+        assert!(failing_received.iter().any(Result::is_err));
+
+        core::mem::drop(dropping);
+        core::mem::drop(failing);
+        assert_eq!(bus.subscribers(), 0);
+
+        bus.set_fail(true);
+        assert!(
+            subscription_receiver::<StdRawMutex, _, _, 8>(&bus, OverflowPolicy::Error).is_err()
+        );
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn sender_postbox_slow_consumer() {
+        let (sender, receiver) = bounded_postbox::<StdRawMutex, u32, 4>();
+        let waker = Arc::new(CountingWaker::default());
+
+        let postbox = SenderPostbox::new(sender);
+
+        let mut received = Vec::new();
+        let mut refused = 0;
+
+        for value in 1..=100 {
+            // A full sender refuses the post right away, and the producer retries after the consumer caught up
+            while !crate::event_bus::Postbox::post(&postbox, &value, None).unwrap() {
+                refused += 1;
+
+                received.push(block_on(receiver.recv()).unwrap());
+            }
+
+            if value % 3 == 0 {
+                received.push(block_on(receiver.recv()).unwrap());
+            }
+        }
+
+        while let Poll::Ready(value) = poll(pin!(receiver.recv()), &waker) {
+            received.push(value.unwrap());
+        }
+
+        assert_eq!(received, (1..=100).collect::<Vec<_>>());
+        assert!(refused > 0);
+
+        core::mem::drop(receiver);
+
+        assert_eq!(
+            crate::event_bus::Postbox::post(&postbox, &101, None),
+            Err(PostboxClosed)
+        );
+    }
 }
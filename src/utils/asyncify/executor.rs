@@ -0,0 +1,482 @@
+//! A minimal executor, for running the futures of the `asyncify` adapters (and any others) concurrently
+//! on a single thread, without allocations
+//!
+//! The runnable flags of the tasks live in `TaskSlots`, which has to be `'static` (e.g. a `static`),
+//! as the wakers handed out to the futures might outlive the executor. The futures themselves are owned by
+//! the caller, typically pinned on the stack with `core::pin::pin!`, and only borrowed by the executor.
+//!
+//! E.g. with `static SLOTS: TaskSlots<StdWait, 3> = TaskSlots::new(StdWait::new());`, a `LocalExecutor::new(&SLOTS)`
+//! can `spawn(pin!(task))` up to three tasks, and `run` them to completion.
+
+use core::cell::RefCell;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+
+/// How `LocalExecutor::run` waits for work, once none of its tasks is runnable
+///
+/// `notify` is called by the wakers of the tasks, possibly from other threads or from interrupt handlers,
+/// and has to make the next (or the ongoing) `wait` return, even if it is called before `wait`, as otherwise
+/// a wake-up happening right before the executor goes to sleep would be missed. On Cortex-M for example,
+/// `wait` can be `WFE` and `notify` `SEV`, as the event register latches the notification.
+pub trait Wait {
+    fn wait(&self);
+
+    fn notify(&self);
+}
+
+impl<W> Wait for &W
+where
+    W: Wait,
+{
+    fn wait(&self) {
+        (*self).wait()
+    }
+
+    fn notify(&self) {
+        (*self).notify()
+    }
+}
+
+/// Never sleeps, i.e. makes `LocalExecutor::run` busy-loop while none of its tasks is runnable
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Spin;
+
+impl Wait for Spin {
+    fn wait(&self) {
+        core::hint::spin_loop();
+    }
+
+    fn notify(&self) {}
+}
+
+/// Sleeps on a STD Condvar
+#[cfg(feature = "std")]
+pub struct StdWait {
+    notified: std::sync::Mutex<bool>,
+    condvar: std::sync::Condvar,
+}
+
+#[cfg(feature = "std")]
+impl StdWait {
+    pub const fn new() -> Self {
+        Self {
+            notified: std::sync::Mutex::new(false),
+            condvar: std::sync::Condvar::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdWait {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Wait for StdWait {
+    fn wait(&self) {
+        let mut notified = self
+            .notified
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        while !*notified {
+            notified = self
+                .condvar
+                .wait(notified)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+
+        *notified = false;
+    }
+
+    fn notify(&self) {
+        *self
+            .notified
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = true;
+
+        self.condvar.notify_one();
+    }
+}
+
+/// The error returned by `LocalExecutor::spawn` when all of its `TASKS` slots are taken
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpawnError;
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No free task slot")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SpawnError {}
+
+struct Slot<W> {
+    runnable: AtomicBool,
+    wait: AtomicPtr<W>,
+}
+
+impl<W> Slot<W>
+where
+    W: Wait + Sync + 'static,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        runnable: AtomicBool::new(false),
+        wait: AtomicPtr::new(ptr::null_mut()),
+    };
+
+    const VTABLE: RawWakerVTable =
+        RawWakerVTable::new(Self::clone, Self::wake, Self::wake, Self::drop);
+
+    fn waker(&'static self) -> Waker {
+        unsafe { Waker::from_raw(RawWaker::new(self as *const _ as *const (), &Self::VTABLE)) }
+    }
+
+    unsafe fn clone(data: *const ()) -> RawWaker {
+        RawWaker::new(data, &Self::VTABLE)
+    }
+
+    unsafe fn wake(data: *const ()) {
+        let slot = &*(data as *const Self);
+
+        slot.runnable.store(true, Ordering::Release);
+
+        if let Some(wait) = slot.wait.load(Ordering::Acquire).as_ref() {
+            wait.notify();
+        }
+    }
+
+    unsafe fn drop(_data: *const ()) {}
+}
+
+/// The runnable flags of the `TASKS` tasks of a `LocalExecutor`, and its `Wait` hook
+///
+/// Only one executor at a time should use a given `TaskSlots`, or else the executors will poll their tasks spuriously.
+pub struct TaskSlots<W, const TASKS: usize> {
+    slots: [Slot<W>; TASKS],
+    wait: W,
+}
+
+impl<W, const TASKS: usize> TaskSlots<W, TASKS>
+where
+    W: Wait + Sync + 'static,
+{
+    pub const fn new(wait: W) -> Self {
+        Self {
+            slots: [Slot::INIT; TASKS],
+            wait,
+        }
+    }
+}
+
+type Task<'a> = Pin<&'a mut dyn Future<Output = ()>>;
+
+/// Polls up to `TASKS` futures on the thread calling `run`, whenever they are woken
+///
+/// The futures do not need to be `Send`, and can borrow from the stack, as long as they outlive the executor.
+pub struct LocalExecutor<'a, W, const TASKS: usize>
+where
+    W: 'static,
+{
+    slots: &'static TaskSlots<W, TASKS>,
+    tasks: [RefCell<Option<Task<'a>>>; TASKS],
+}
+
+impl<'a, W, const TASKS: usize> LocalExecutor<'a, W, TASKS>
+where
+    W: Wait + Sync + 'static,
+{
+    pub fn new(slots: &'static TaskSlots<W, TASKS>) -> Self {
+        for slot in &slots.slots {
+            slot.runnable.store(false, Ordering::Relaxed);
+            slot.wait
+                .store(&slots.wait as *const _ as *mut _, Ordering::Release);
+        }
+
+        Self {
+            slots,
+            tasks: core::array::from_fn(|_| RefCell::new(None)),
+        }
+    }
+
+    /// Takes `task` in a free slot, so that the next or the ongoing `run` polls it to completion
+    ///
+    /// Can also be called by the tasks themselves, e.g. with `'static` futures they allocated.
+    pub fn spawn(&self, task: Task<'a>) -> Result<(), SpawnError> {
+        for (index, slot) in self.tasks.iter().enumerate() {
+            // The task being polled, if any, is borrowed
+            if let Ok(mut slot) = slot.try_borrow_mut() {
+                if slot.is_none() {
+                    *slot = Some(task);
+
+                    self.slots.slots[index]
+                        .runnable
+                        .store(true, Ordering::Release);
+
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(SpawnError)
+    }
+
+    /// The number of tasks which did not complete yet
+    pub fn len(&self) -> usize {
+        self.tasks
+            .iter()
+            .filter(|task| task.try_borrow().map(|task| task.is_some()).unwrap_or(true))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Polls the runnable tasks once, returning whether any task is left
+    pub fn poll_once(&self) -> bool {
+        for (task, slot) in self.tasks.iter().zip(&self.slots.slots) {
+            // Clear before polling, so that a wake-up during the poll is not lost,
+            // and for free slots too, which might still be woken by the wakers of their completed tasks
+            let runnable = slot.runnable.swap(false, Ordering::AcqRel);
+
+            let mut task = task.borrow_mut();
+
+            let Some(future) = task.as_mut() else {
+                continue;
+            };
+
+            if runnable {
+                let waker = slot.waker();
+
+                if future
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                    .is_ready()
+                {
+                    *task = None;
+                }
+            }
+        }
+
+        // Tasks might have been spawned during the polls, in slots polled already
+        !self.is_empty()
+    }
+
+    /// Polls the tasks whenever they are woken, until all of them completed, waiting with `W` in between
+    pub fn run(&self) {
+        while self.poll_once() {
+            if !self.is_runnable() {
+                self.slots.wait.wait();
+            }
+        }
+    }
+
+    fn is_runnable(&self) -> bool {
+        self.slots
+            .slots
+            .iter()
+            .any(|slot| slot.runnable.load(Ordering::Acquire))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::cell::{Cell, RefCell};
+    use core::future::{pending, poll_fn};
+    use core::pin::pin;
+    use core::sync::atomic::AtomicUsize;
+    use core::task::Poll;
+    use core::time::Duration;
+
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn yield_now() -> impl Future<Output = ()> {
+        let mut yielded = false;
+
+        poll_fn(move |cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            }
+        })
+    }
+
+    #[test]
+    fn interleaved() {
+        static SLOTS: TaskSlots<Spin, 2> = TaskSlots::new(Spin);
+
+        let events = RefCell::new(Vec::new());
+
+        let task = |name| {
+            let events = &events;
+
+            async move {
+                for round in 0..3 {
+                    events.borrow_mut().push((name, round));
+                    yield_now().await;
+                }
+            }
+        };
+
+        let executor = LocalExecutor::new(&SLOTS);
+
+        let mut first = pin!(task("first"));
+        let mut second = pin!(task("second"));
+
+        executor.spawn(first.as_mut()).unwrap();
+        executor.spawn(second.as_mut()).unwrap();
+        assert_eq!(executor.len(), 2);
+
+        executor.run();
+        assert!(executor.is_empty());
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                ("first", 0),
+                ("second", 0),
+                ("first", 1),
+                ("second", 1),
+                ("first", 2),
+                ("second", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn slots_taken() {
+        static SLOTS: TaskSlots<Spin, 2> = TaskSlots::new(Spin);
+
+        let executor = LocalExecutor::new(&SLOTS);
+
+        let first = pin!(yield_now());
+        let second = pin!(yield_now());
+        let third = pin!(yield_now());
+        let fourth = pin!(yield_now());
+
+        executor.spawn(first).unwrap();
+        executor.spawn(second).unwrap();
+        assert_eq!(executor.spawn(third), Err(SpawnError));
+
+        executor.run();
+
+        // Free again once the tasks completed
+        executor.spawn(fourth).unwrap();
+        executor.run();
+        assert!(executor.is_empty());
+    }
+
+    #[test]
+    fn polled_when_woken() {
+        static SLOTS: TaskSlots<Spin, 2> = TaskSlots::new(Spin);
+
+        let polls = Cell::new(0);
+
+        let executor = LocalExecutor::new(&SLOTS);
+
+        let mut task = pin!(async {
+            poll_fn(|_| {
+                polls.set(polls.get() + 1);
+
+                Poll::<()>::Pending
+            })
+            .await
+        });
+
+        executor.spawn(task.as_mut()).unwrap();
+
+        // Never woken, so only polled once after being spawned
+        assert!(executor.poll_once());
+        assert!(executor.poll_once());
+        assert_eq!(polls.get(), 1);
+    }
+
+    #[test]
+    fn spawned_by_task() {
+        static SLOTS: TaskSlots<Spin, 2> = TaskSlots::new(Spin);
+
+        let done = Cell::new(false);
+
+        let executor = LocalExecutor::new(&SLOTS);
+
+        let inner = pin!(async {
+            yield_now().await;
+            done.set(true);
+        });
+        let other = pin!(pending::<()>());
+
+        let mut outer = pin!(async {
+            executor.spawn(inner).unwrap();
+
+            // The slot of the task being polled is taken
+            assert_eq!(executor.spawn(other), Err(SpawnError));
+        });
+
+        executor.spawn(outer.as_mut()).unwrap();
+        executor.run();
+
+        assert!(done.get());
+    }
+
+    #[test]
+    fn woken_from_thread() {
+        static SLOTS: TaskSlots<StdWait, 1> = TaskSlots::new(StdWait::new());
+
+        let wakes = AtomicUsize::new(0);
+        let waker = Mutex::new(None::<Waker>);
+
+        let executor = LocalExecutor::new(&SLOTS);
+
+        let mut task = pin!(poll_fn(|cx| {
+            if wakes.load(Ordering::SeqCst) == 3 {
+                Poll::Ready(())
+            } else {
+                *waker.lock().unwrap() = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        }));
+
+        executor.spawn(task.as_mut()).unwrap();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..3 {
+                    std::thread::sleep(Duration::from_millis(10));
+
+                    let waker = loop {
+                        if let Some(waker) = waker.lock().unwrap().take() {
+                            break waker;
+                        }
+
+                        std::thread::yield_now();
+                    };
+
+                    wakes.fetch_add(1, Ordering::SeqCst);
+                    waker.wake();
+                }
+            });
+
+            // Sleeps on the condvar while the task is not runnable
+            executor.run();
+        });
+
+        assert_eq!(wakes.load(Ordering::SeqCst), 3);
+    }
+}
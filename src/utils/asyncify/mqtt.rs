@@ -235,7 +235,7 @@ pub mod client {
             }
 
             if let Some(AsyncConnState::Waiting(waker)) =
-                mem::replace(&mut *state, Some(AsyncConnState::Received(event)))
+                state.replace(AsyncConnState::Received(event))
             {
                 waker.wake();
             }
@@ -313,6 +313,7 @@ pub mod client {
         use crate::mqtt::client::asynch::{Client, Connection, MessageId, Publish, QoS};
         use crate::mqtt::client::ErrorType;
         use crate::utils::asyncify::mqtt::client::Event;
+        use crate::utils::asyncify::unblocker::{Bounded, TryUnblocker, UnblockError};
         use crate::utils::asyncify::Unblocker;
         use crate::utils::mutex::{Mutex, RawCondvar, RawMutex};
 
@@ -342,7 +343,7 @@ pub mod client {
                 let client = self.0.clone();
 
                 self.1
-                    .unblock(move || client.lock().subscribe(&topic, qos))
+                    .unblock(move || client.lock_with(|client| client.subscribe(&topic, qos)))
                     .await
             }
 
@@ -351,7 +352,7 @@ pub mod client {
                 let client = self.0.clone();
 
                 self.1
-                    .unblock(move || client.lock().unsubscribe(&topic))
+                    .unblock(move || client.lock_with(|client| client.unsubscribe(&topic)))
                     .await
             }
         }
@@ -376,11 +377,72 @@ pub mod client {
                 let client = self.0.clone();
 
                 self.1
-                    .unblock(move || client.lock().publish(&topic, qos, retain, &payload))
+                    .unblock(move || {
+                        client.lock_with(|client| client.publish(&topic, qos, retain, &payload))
+                    })
                     .await
             }
         }
 
+        impl<U, R, C> Client for AsyncClient<Bounded<U>, Arc<Mutex<R, C>>>
+        where
+            U: TryUnblocker,
+            R: RawMutex + Send + Sync + 'static,
+            C: crate::mqtt::client::Client + Send + 'static,
+            C::Error: From<UnblockError> + Send + Sync + 'static,
+        {
+            async fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<MessageId, C::Error> {
+                let topic: String = topic.to_owned();
+                let client = self.0.clone();
+
+                self.1
+                     .0
+                    .try_unblock_prio(self.1 .1, move || {
+                        client.lock_with(|client| client.subscribe(&topic, qos))
+                    })
+                    .await?
+            }
+
+            async fn unsubscribe(&mut self, topic: &str) -> Result<MessageId, C::Error> {
+                let topic: String = topic.to_owned();
+                let client = self.0.clone();
+
+                self.1
+                     .0
+                    .try_unblock_prio(self.1 .1, move || {
+                        client.lock_with(|client| client.unsubscribe(&topic))
+                    })
+                    .await?
+            }
+        }
+
+        impl<U, R, C> Publish for AsyncClient<Bounded<U>, Arc<Mutex<R, C>>>
+        where
+            U: TryUnblocker,
+            R: RawMutex + Send + Sync + 'static,
+            C: crate::mqtt::client::Publish + Send + 'static,
+            C::Error: From<UnblockError> + Send + Sync + 'static,
+        {
+            async fn publish(
+                &mut self,
+                topic: &str,
+                qos: QoS,
+                retain: bool,
+                payload: &[u8],
+            ) -> Result<MessageId, C::Error> {
+                let topic: String = topic.to_owned();
+                let payload: Vec<u8> = payload.to_owned();
+                let client = self.0.clone();
+
+                self.1
+                     .0
+                    .try_unblock_prio(self.1 .1, move || {
+                        client.lock_with(|client| client.publish(&topic, qos, retain, &payload))
+                    })
+                    .await?
+            }
+        }
+
         impl<E, P> ErrorType for AsyncClient<(), Blocking<E, P>>
         where
             E: ErrorType,
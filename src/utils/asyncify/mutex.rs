@@ -0,0 +1,499 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use crate::utils::mutex::{Mutex, RawMutex};
+
+/// The error returned by `AsyncMutex::lock` when the queue of the tasks waiting for the lock is full
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WaitQueueFull;
+
+impl fmt::Display for WaitQueueFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Wait queue full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WaitQueueFull {}
+
+/// A task waiting for an `AsyncMutex`
+pub struct Waiter {
+    ticket: usize,
+    waker: Waker,
+}
+
+/// The queue of the tasks waiting for an `AsyncMutex`, in the order they started waiting
+///
+/// Implemented by `heapless::Vec` for a fixed capacity, and by `alloc::vec::Vec` to grow as needed.
+pub trait WaitQueue {
+    const INIT: Self; // A workaround for not having const fns in traits yet.
+
+    /// Appends `waiter`, or returns it if the queue is full
+    fn push(&mut self, waiter: Waiter) -> Result<(), Waiter>;
+
+    fn waiters(&mut self) -> &mut [Waiter];
+
+    fn remove(&mut self, index: usize) -> Waiter;
+}
+
+impl<const N: usize> WaitQueue for heapless::Vec<Waiter, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = heapless::Vec::new();
+
+    fn push(&mut self, waiter: Waiter) -> Result<(), Waiter> {
+        heapless::Vec::push(self, waiter)
+    }
+
+    fn waiters(&mut self) -> &mut [Waiter] {
+        self
+    }
+
+    fn remove(&mut self, index: usize) -> Waiter {
+        heapless::Vec::remove(self, index)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl WaitQueue for alloc::vec::Vec<Waiter> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = alloc::vec::Vec::new();
+
+    fn push(&mut self, waiter: Waiter) -> Result<(), Waiter> {
+        alloc::vec::Vec::push(self, waiter);
+
+        Ok(())
+    }
+
+    fn waiters(&mut self) -> &mut [Waiter] {
+        self
+    }
+
+    fn remove(&mut self, index: usize) -> Waiter {
+        alloc::vec::Vec::remove(self, index)
+    }
+}
+
+struct LockState<Q> {
+    locked: bool,
+    next_ticket: usize,
+    queue: Q,
+}
+
+impl<Q> LockState<Q>
+where
+    Q: WaitQueue,
+{
+    fn wake_first(&mut self) {
+        if let Some(waiter) = self.queue.waiters().first() {
+            waiter.waker.wake_by_ref();
+        }
+    }
+}
+
+/// A mutex which can be held across `.await` points, as tasks waiting for it are suspended rather than blocked
+///
+/// The state of the lock is kept behind a (blocking) `Mutex`, which is only held briefly.
+/// The waiting tasks are queued in `Q` and get the lock in FIFO order: when the lock is released,
+/// only the first of them is woken. A task dropping its `lock` future while waiting leaves the queue.
+///
+/// By default up to 8 tasks can wait at a time, and `lock` fails with `WaitQueueFull` beyond that.
+/// With the `alloc` feature, `Q` can be an `alloc::vec::Vec<Waiter>` instead, for a queue which grows as needed.
+pub struct AsyncMutex<R, T, Q = heapless::Vec<Waiter, 8>>
+where
+    R: RawMutex,
+{
+    state: Mutex<R, LockState<Q>>,
+    data: UnsafeCell<T>,
+}
+
+impl<R, T, Q> AsyncMutex<R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: Mutex::new(LockState {
+                locked: false,
+                next_ticket: 0,
+                queue: Q::INIT,
+            }),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> LockFuture<'_, R, T, Q> {
+        LockFuture {
+            mutex: self,
+            ticket: None,
+        }
+    }
+
+    /// Takes the lock if it is free and no task is waiting for it
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, R, T, Q>> {
+        let mut state = self.state.lock();
+
+        if state.locked || !state.queue.waiters().is_empty() {
+            None
+        } else {
+            state.locked = true;
+
+            Some(AsyncMutexGuard(self))
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+unsafe impl<R, T, Q> Sync for AsyncMutex<R, T, Q>
+where
+    R: RawMutex + Send + Sync,
+    T: Send,
+    Q: Send,
+{
+}
+unsafe impl<R, T, Q> Send for AsyncMutex<R, T, Q>
+where
+    R: RawMutex + Send + Sync,
+    T: Send,
+    Q: Send,
+{
+}
+
+/// The future returned by `AsyncMutex::lock`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct LockFuture<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    mutex: &'a AsyncMutex<R, T, Q>,
+    ticket: Option<usize>,
+}
+
+impl<'a, R, T, Q> Future for LockFuture<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    type Output = Result<AsyncMutexGuard<'a, R, T, Q>, WaitQueueFull>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mutex = self.mutex;
+        let mut guard = mutex.state.lock();
+        let state = &mut *guard;
+
+        if let Some(ticket) = self.ticket {
+            let waiters = state.queue.waiters();
+
+            if !state.locked && waiters.first().map(|waiter| waiter.ticket) == Some(ticket) {
+                state.queue.remove(0);
+                state.locked = true;
+                self.ticket = None;
+
+                return Poll::Ready(Ok(AsyncMutexGuard(mutex)));
+            }
+
+            if let Some(waiter) = waiters.iter_mut().find(|waiter| waiter.ticket == ticket) {
+                if !waiter.waker.will_wake(cx.waker()) {
+                    waiter.waker = cx.waker().clone();
+                }
+            }
+
+            Poll::Pending
+        } else if !state.locked && state.queue.waiters().is_empty() {
+            state.locked = true;
+
+            Poll::Ready(Ok(AsyncMutexGuard(mutex)))
+        } else {
+            let ticket = state.next_ticket;
+
+            let waiter = Waiter {
+                ticket,
+                waker: cx.waker().clone(),
+            };
+
+            if state.queue.push(waiter).is_err() {
+                return Poll::Ready(Err(WaitQueueFull));
+            }
+
+            state.next_ticket = ticket.wrapping_add(1);
+            self.ticket = Some(ticket);
+
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, R, T, Q> Drop for LockFuture<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            let mut state = self.mutex.state.lock();
+
+            if let Some(index) = state
+                .queue
+                .waiters()
+                .iter()
+                .position(|waiter| waiter.ticket == ticket)
+            {
+                state.queue.remove(index);
+
+                if index == 0 && !state.locked {
+                    // This task was woken to take the lock, so pass the turn on
+                    state.wake_first();
+                }
+            }
+        }
+    }
+}
+
+pub struct AsyncMutexGuard<'a, R, T, Q>(&'a AsyncMutex<R, T, Q>)
+where
+    R: RawMutex,
+    Q: WaitQueue;
+
+impl<'a, R, T, Q> Drop for AsyncMutexGuard<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+
+        state.locked = false;
+        state.wake_first();
+    }
+}
+
+impl<'a, R, T, Q> Deref for AsyncMutexGuard<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.0.data.get().as_ref().unwrap() }
+    }
+}
+
+impl<'a, R, T, Q> DerefMut for AsyncMutexGuard<'a, R, T, Q>
+where
+    R: RawMutex,
+    Q: WaitQueue,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.0.data.get().as_mut().unwrap() }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl CountingWaker {
+        fn wakes(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    fn wakers<const N: usize>() -> [Arc<CountingWaker>; N] {
+        core::array::from_fn(|_| Arc::new(CountingWaker::default()))
+    }
+
+    fn wakes(wakers: &[Arc<CountingWaker>]) -> Vec<usize> {
+        wakers.iter().map(|waker| waker.wakes()).collect()
+    }
+
+    #[test]
+    fn fifo() {
+        let mutex = AsyncMutex::<StdRawMutex, u32>::new(0);
+        let wakers = wakers::<3>();
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut first = pin!(mutex.lock());
+        let mut second = pin!(mutex.lock());
+        let mut third = pin!(mutex.lock());
+
+        assert!(poll(first.as_mut(), &wakers[0]).is_pending());
+        assert!(poll(second.as_mut(), &wakers[1]).is_pending());
+        assert!(poll(third.as_mut(), &wakers[2]).is_pending());
+
+        // Only the first waiter is woken, and the lock is kept for it
+        drop(guard);
+        assert_eq!(wakes(&wakers), [1, 0, 0]);
+
+        assert!(mutex.try_lock().is_none());
+        assert!(poll(third.as_mut(), &wakers[2]).is_pending());
+
+        let Poll::Ready(Ok(guard)) = poll(first.as_mut(), &wakers[0]) else {
+            panic!("The first waiter should have the lock");
+        };
+
+        drop(guard);
+        assert_eq!(wakes(&wakers), [1, 1, 0]);
+
+        let Poll::Ready(Ok(guard)) = poll(second.as_mut(), &wakers[1]) else {
+            panic!("The second waiter should have the lock");
+        };
+
+        drop(guard);
+        assert_eq!(wakes(&wakers), [1, 1, 1]);
+
+        assert!(matches!(
+            poll(third.as_mut(), &wakers[2]),
+            Poll::Ready(Ok(_))
+        ));
+    }
+
+    #[test]
+    fn dropped_after_wake() {
+        let mutex = AsyncMutex::<StdRawMutex, u32>::new(0);
+        let wakers = wakers::<2>();
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut second = pin!(mutex.lock());
+
+        {
+            let mut first = pin!(mutex.lock());
+
+            assert!(poll(first.as_mut(), &wakers[0]).is_pending());
+            assert!(poll(second.as_mut(), &wakers[1]).is_pending());
+
+            drop(guard);
+            assert_eq!(wakers[0].wakes(), 1);
+        }
+
+        // The first waiter was woken, but dropped its future without taking the lock,
+        // so the turn is passed on to the next one
+        assert_eq!(wakers[1].wakes(), 1);
+        assert!(matches!(
+            poll(second.as_mut(), &wakers[1]),
+            Poll::Ready(Ok(_))
+        ));
+    }
+
+    #[test]
+    fn dropped_while_waiting() {
+        let mutex = AsyncMutex::<StdRawMutex, u32>::new(0);
+        let wakers = wakers::<2>();
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut third = pin!(mutex.lock());
+
+        {
+            let mut second = pin!(mutex.lock());
+
+            assert!(poll(second.as_mut(), &wakers[0]).is_pending());
+            assert!(poll(third.as_mut(), &wakers[1]).is_pending());
+        }
+
+        // A waiter leaving before its turn does not wake anyone
+        assert_eq!(wakers[1].wakes(), 0);
+
+        drop(guard);
+        assert_eq!(wakers[1].wakes(), 1);
+        assert!(matches!(
+            poll(third.as_mut(), &wakers[1]),
+            Poll::Ready(Ok(_))
+        ));
+    }
+
+    #[test]
+    fn wait_queue_full() {
+        let mutex = AsyncMutex::<StdRawMutex, u32, heapless::Vec<Waiter, 1>>::new(0);
+        let wakers = wakers::<2>();
+
+        let _guard = mutex.try_lock().unwrap();
+
+        let mut first = pin!(mutex.lock());
+        assert!(poll(first.as_mut(), &wakers[0]).is_pending());
+
+        assert!(matches!(
+            poll(pin!(mutex.lock()), &wakers[1]),
+            Poll::Ready(Err(WaitQueueFull))
+        ));
+
+        // The alloc queue grows instead
+        let mutex = AsyncMutex::<StdRawMutex, u32, Vec<Waiter>>::new(0);
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut waiters = (0..20).map(|_| Box::pin(mutex.lock())).collect::<Vec<_>>();
+
+        for waiter in &mut waiters {
+            assert!(poll(waiter.as_mut(), &wakers[0]).is_pending());
+        }
+
+        drop(guard);
+
+        for waiter in &mut waiters {
+            let Poll::Ready(Ok(guard)) = poll(waiter.as_mut(), &wakers[0]) else {
+                panic!("The waiters should get the lock in turn");
+            };
+
+            drop(guard);
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let mutex = AsyncMutex::<StdRawMutex, u32>::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        *block_on(mutex.lock()).unwrap() += 1;
+                    }
+                });
+            }
+        });
+
+        assert_eq!(mutex.into_inner(), 4000);
+    }
+}
@@ -0,0 +1,367 @@
+use core::fmt;
+use core::future::poll_fn;
+use core::ops::Deref;
+use core::task::{Context, Poll, Waker};
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+use crate::event_bus::ErrorType;
+use crate::utils::mutex::{Mutex, RawMutex};
+
+/// The error returned by `Receiver::recv` when the `Sender` was dropped without sending a value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Canceled;
+
+impl fmt::Display for Canceled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Canceled")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Canceled {}
+
+struct OneshotState<T> {
+    value: Option<T>,
+    sender: bool,
+    receiver: bool,
+    waker: Option<Waker>,
+}
+
+/// The slot of a oneshot channel, for a single value sent by a task to another
+///
+/// The slot needs no allocations: `split` it in place into a `Sender` and a `Receiver` borrowing it,
+/// or use `channel` to get a `Sender` and a `Receiver` sharing a slot behind an `Arc`.
+pub struct Oneshot<R, T>(Mutex<R, OneshotState<T>>)
+where
+    R: RawMutex;
+
+impl<R, T> Oneshot<R, T>
+where
+    R: RawMutex,
+{
+    pub const fn new() -> Self {
+        Self(Mutex::new(OneshotState {
+            value: None,
+            sender: true,
+            receiver: true,
+            waker: None,
+        }))
+    }
+
+    /// Returns the `Sender` and the `Receiver` of the slot, which is emptied first
+    pub fn split(&mut self) -> (Sender<'_, R, T>, Receiver<'_, R, T>) {
+        *self = Self::new();
+
+        (
+            Sender(OneshotRef::Borrowed(self)),
+            Receiver(OneshotRef::Borrowed(self)),
+        )
+    }
+}
+
+impl<R, T> Default for Oneshot<R, T>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the `Sender` and the `Receiver` of a new oneshot channel
+#[cfg(feature = "alloc")]
+pub fn channel<R, T>() -> (Sender<'static, R, T>, Receiver<'static, R, T>)
+where
+    R: RawMutex,
+{
+    let oneshot = Arc::new(Oneshot::new());
+
+    (
+        Sender(OneshotRef::Shared(oneshot.clone())),
+        Receiver(OneshotRef::Shared(oneshot)),
+    )
+}
+
+enum OneshotRef<'a, R, T>
+where
+    R: RawMutex,
+{
+    #[cfg(feature = "alloc")]
+    Shared(Arc<Oneshot<R, T>>),
+    Borrowed(&'a Oneshot<R, T>),
+}
+
+impl<'a, R, T> Deref for OneshotRef<'a, R, T>
+where
+    R: RawMutex,
+{
+    type Target = Oneshot<R, T>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Shared(oneshot) => oneshot,
+            Self::Borrowed(oneshot) => oneshot,
+        }
+    }
+}
+
+/// The sending half of a oneshot channel, consumed by `send`
+pub struct Sender<'a, R, T>(OneshotRef<'a, R, T>)
+where
+    R: RawMutex;
+
+impl<'a, R, T> Sender<'a, R, T>
+where
+    R: RawMutex,
+{
+    /// Sends `value` to the receiver, or returns it if the receiver was dropped
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut state = self.0 .0.lock();
+
+        if !state.receiver {
+            return Err(value);
+        }
+
+        state.value = Some(value);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver was dropped, in which case sending is pointless
+    pub fn is_canceled(&self) -> bool {
+        !self.0 .0.lock().receiver
+    }
+}
+
+impl<'a, R, T> Drop for Sender<'a, R, T>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock();
+
+        state.sender = false;
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The receiving half of a oneshot channel
+pub struct Receiver<'a, R, T>(OneshotRef<'a, R, T>)
+where
+    R: RawMutex;
+
+impl<'a, R, T> Receiver<'a, R, T>
+where
+    R: RawMutex,
+{
+    /// Receives the value, or fails with `Canceled` if the sender was dropped without sending it
+    pub async fn recv(&self) -> Result<T, Canceled> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// The poll-based variant of `recv`, for use in hand-written futures
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, Canceled>> {
+        let mut state = self.0 .0.lock();
+
+        if let Some(value) = state.value.take() {
+            Poll::Ready(Ok(value))
+        } else if !state.sender {
+            Poll::Ready(Err(Canceled))
+        } else {
+            state.waker = Some(cx.waker().clone());
+
+            Poll::Pending
+        }
+    }
+
+    /// Returns the value if it was sent, `None` if it was not sent yet, or fails with `Canceled`
+    pub fn try_recv(&self) -> Result<Option<T>, Canceled> {
+        let mut state = self.0 .0.lock();
+
+        if let Some(value) = state.value.take() {
+            Ok(Some(value))
+        } else if !state.sender {
+            Err(Canceled)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a, R, T> Drop for Receiver<'a, R, T>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock();
+
+        state.receiver = false;
+        state.value = None;
+    }
+}
+
+impl<'a, R, T> ErrorType for Receiver<'a, R, T>
+where
+    R: RawMutex,
+{
+    type Error = Canceled;
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use crate::event_bus::asynch;
+    use crate::utils::mutex::RawMutex;
+
+    use super::Receiver;
+
+    impl<'a, R, T> asynch::Receiver for Receiver<'a, R, T>
+    where
+        R: RawMutex,
+        T: Send,
+    {
+        type Data = T;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            Receiver::recv(self).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::Future;
+    use core::pin::{pin, Pin};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl CountingWaker {
+        fn wakes(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn send() {
+        let mut oneshot = Oneshot::<StdRawMutex, u32>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let (sender, receiver) = oneshot.split();
+
+        let mut recv = pin!(receiver.recv());
+        assert!(poll(recv.as_mut(), &waker).is_pending());
+        assert_eq!(receiver.try_recv(), Ok(None));
+
+        assert!(!sender.is_canceled());
+        assert_eq!(sender.send(1), Ok(()));
+
+        assert_eq!(waker.wakes(), 1);
+        assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Ok(1)));
+    }
+
+    #[test]
+    fn split_again() {
+        let mut oneshot = Oneshot::<StdRawMutex, u32>::new();
+
+        {
+            let (sender, _receiver) = oneshot.split();
+
+            sender.send(1).unwrap();
+        }
+
+        // The value left in the slot is not received by the next receiver
+        let (_sender, receiver) = oneshot.split();
+        assert_eq!(receiver.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn canceled() {
+        let (sender, receiver) = channel::<StdRawMutex, u32>();
+        let waker = Arc::new(CountingWaker::default());
+
+        {
+            let mut recv = pin!(receiver.recv());
+            assert!(poll(recv.as_mut(), &waker).is_pending());
+
+            // Dropping the sender wakes the receiver, which fails
+            drop(sender);
+
+            assert_eq!(waker.wakes(), 1);
+            assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(Err(Canceled)));
+        }
+
+        assert_eq!(receiver.try_recv(), Err(Canceled));
+
+        let (sender, receiver) = channel::<StdRawMutex, u32>();
+
+        drop(receiver);
+
+        assert!(sender.is_canceled());
+        assert_eq!(sender.send(1), Err(1));
+    }
+
+    #[test]
+    fn receiver_dropped() {
+        let value = Arc::new(());
+
+        let (sender, receiver) = channel::<StdRawMutex, Arc<()>>();
+
+        sender.send(value.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        // The value sent but not received is dropped with the receiver
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn send_from_thread() {
+        let (sender, receiver) = channel::<StdRawMutex, u32>();
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                std::thread::sleep(core::time::Duration::from_millis(10));
+
+                sender.send(1).unwrap();
+            });
+
+            assert_eq!(block_on(receiver.recv()), Ok(1));
+        });
+    }
+}
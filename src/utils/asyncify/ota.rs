@@ -0,0 +1,585 @@
+use crate::ota::{Ota, OtaUpdate, OtaUpdateFinished, ResumedUpdate, Slot};
+use crate::utils::asyncify::Unblocker;
+
+#[allow(unused_imports)]
+#[cfg(feature = "nightly")]
+pub use async_traits_impl::*;
+
+use super::UnblockingAsyncWrapper;
+
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+pub struct AsyncOta<U, O> {
+    unblocker: U,
+    ota: O,
+    chunk_size: usize,
+}
+
+impl<U, O> AsyncOta<U, O> {
+    pub const fn new(unblocker: U, ota: O) -> Self {
+        Self {
+            unblocker,
+            ota,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Sets the maximum size of the chunks handed off to the unblocker by `AsyncOtaUpdate::write`
+    pub const fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = if chunk_size > 0 { chunk_size } else { 1 };
+        self
+    }
+}
+
+impl<U, O> AsyncOta<U, O>
+where
+    U: Unblocker,
+    O: Ota + Send + Sync,
+    O::Error: Send,
+{
+    pub async fn get_boot_slot(&self) -> Result<Slot, O::Error> {
+        let ota = &self.ota;
+
+        self.unblocker.unblock(move || ota.get_boot_slot()).await
+    }
+
+    pub async fn get_running_slot(&self) -> Result<Slot, O::Error> {
+        let ota = &self.ota;
+
+        self.unblocker.unblock(move || ota.get_running_slot()).await
+    }
+
+    pub async fn get_update_slot(&self) -> Result<Slot, O::Error> {
+        let ota = &self.ota;
+
+        self.unblocker.unblock(move || ota.get_update_slot()).await
+    }
+
+    pub async fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, O::Error> {
+        let ota = &self.ota;
+
+        self.unblocker.unblock(move || ota.get_slots(slots)).await
+    }
+
+    pub async fn is_factory_reset_supported(&self) -> Result<bool, O::Error> {
+        let ota = &self.ota;
+
+        self.unblocker
+            .unblock(move || ota.is_factory_reset_supported())
+            .await
+    }
+
+    pub async fn factory_reset(&mut self) -> Result<(), O::Error> {
+        let ota = &mut self.ota;
+
+        self.unblocker.unblock(move || ota.factory_reset()).await
+    }
+
+    pub async fn initiate_update<'a>(
+        &'a mut self,
+    ) -> Result<AsyncOtaUpdate<'a, U, O::Update<'a>>, O::Error>
+    where
+        O::Update<'a>: Send,
+    {
+        let unblocker = &self.unblocker;
+        let ota = &mut self.ota;
+
+        let update = unblocker.unblock(move || ota.initiate_update()).await?;
+
+        Ok(AsyncOtaUpdate {
+            unblocker,
+            update,
+            chunk_size: self.chunk_size,
+        })
+    }
+
+    pub async fn resume_update<'a>(
+        &'a mut self,
+    ) -> Result<Option<ResumedUpdate<AsyncOtaUpdate<'a, U, O::Update<'a>>>>, O::Error>
+    where
+        O::Update<'a>: Send,
+    {
+        let unblocker = &self.unblocker;
+        let ota = &mut self.ota;
+        let chunk_size = self.chunk_size;
+
+        let resumed = unblocker.unblock(move || ota.resume_update()).await?;
+
+        Ok(resumed.map(|resumed| ResumedUpdate {
+            update: AsyncOtaUpdate {
+                unblocker,
+                update: resumed.update,
+                chunk_size,
+            },
+            offset: resumed.offset,
+        }))
+    }
+
+    pub async fn mark_running_slot_valid(&mut self) -> Result<(), O::Error> {
+        let ota = &mut self.ota;
+
+        self.unblocker
+            .unblock(move || ota.mark_running_slot_valid())
+            .await
+    }
+
+    pub async fn mark_running_slot_invalid_and_reboot(&mut self) -> O::Error {
+        let ota = &mut self.ota;
+
+        self.unblocker
+            .unblock(move || ota.mark_running_slot_invalid_and_reboot())
+            .await
+    }
+}
+
+impl<U, O> UnblockingAsyncWrapper<U, O> for AsyncOta<U, O> {
+    fn new(unblocker: U, sync: O) -> Self {
+        AsyncOta::new(unblocker, sync)
+    }
+}
+
+/// An update in progress, initiated with `AsyncOta::initiate_update`.
+///
+/// Writes are handed off to the unblocker in chunks of (at most) the configured chunk size,
+/// so that a long flash erase does not monopolize the unblocker for the whole buffer.
+///
+/// Dropping a `write` future before it resolves cancels the hand-off of the remaining chunks,
+/// however chunks which were already handed off might or might not have been written.
+/// The update should then be aborted.
+pub struct AsyncOtaUpdate<'a, U, T> {
+    unblocker: &'a U,
+    update: T,
+    chunk_size: usize,
+}
+
+impl<'a, U, T> AsyncOtaUpdate<'a, U, T>
+where
+    U: Unblocker,
+    T: OtaUpdate + Send + 'a,
+    T::Error: Send,
+    T::OtaUpdateFinished: Send,
+{
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, T::Error> {
+        for chunk in buf.chunks(self.chunk_size) {
+            let update = &mut self.update;
+
+            self.unblocker
+                .unblock(move || update.write_all(chunk))
+                .await?;
+        }
+
+        Ok(buf.len())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), T::Error> {
+        let update = &mut self.update;
+
+        self.unblocker.unblock(move || update.flush()).await
+    }
+
+    pub async fn finish(
+        self,
+    ) -> Result<AsyncOtaUpdateFinished<'a, U, T::OtaUpdateFinished>, T::Error> {
+        let unblocker = self.unblocker;
+        let update = self.update;
+
+        let finished = unblocker.unblock(move || update.finish()).await?;
+
+        Ok(AsyncOtaUpdateFinished {
+            unblocker,
+            finished,
+        })
+    }
+
+    pub async fn complete(self) -> Result<(), T::Error> {
+        let update = self.update;
+
+        self.unblocker.unblock(move || update.complete()).await
+    }
+
+    pub async fn abort(self) -> Result<(), T::Error> {
+        let update = self.update;
+
+        self.unblocker.unblock(move || update.abort()).await
+    }
+
+    pub fn written(&self) -> Option<u64> {
+        self.update.written()
+    }
+}
+
+pub struct AsyncOtaUpdateFinished<'a, U, F> {
+    unblocker: &'a U,
+    finished: F,
+}
+
+impl<'a, U, F> AsyncOtaUpdateFinished<'a, U, F>
+where
+    U: Unblocker,
+    F: OtaUpdateFinished + Send + 'a,
+    F::Error: Send,
+{
+    pub async fn activate(self) -> Result<(), F::Error> {
+        let finished = self.finished;
+
+        self.unblocker.unblock(move || finished.activate()).await
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use crate::io::asynch::{ErrorType, Read, Write};
+    use crate::ota::asynch::{Ota, OtaUpdate, OtaUpdateFinished, ResumedUpdate, Slot};
+    use crate::utils::asyncify::Unblocker;
+    use crate::utils::io::asynch::*;
+
+    use super::{AsyncOta, AsyncOtaUpdate, AsyncOtaUpdateFinished};
+
+    impl<U, O> ErrorType for AsyncOta<U, O>
+    where
+        O: ErrorType,
+    {
+        type Error = O::Error;
+    }
+
+    impl<U, O> Ota for AsyncOta<U, O>
+    where
+        U: Unblocker,
+        O: crate::ota::Ota + Send + Sync,
+        O::Error: Send,
+        for<'a> O::Update<'a>: Send,
+        for<'a> <O::Update<'a> as crate::ota::OtaUpdate>::OtaUpdateFinished: Send,
+        for<'a> <<O::Update<'a> as crate::ota::OtaUpdate>::OtaUpdateFinished as ErrorType>::Error:
+            Send,
+    {
+        type Update<'a> = AsyncOtaUpdate<'a, U, O::Update<'a>> where Self: 'a;
+
+        async fn get_boot_slot(&self) -> Result<Slot, Self::Error> {
+            AsyncOta::get_boot_slot(self).await
+        }
+
+        async fn get_running_slot(&self) -> Result<Slot, Self::Error> {
+            AsyncOta::get_running_slot(self).await
+        }
+
+        async fn get_update_slot(&self) -> Result<Slot, Self::Error> {
+            AsyncOta::get_update_slot(self).await
+        }
+
+        async fn get_slots(&self, slots: &mut [Slot]) -> Result<usize, Self::Error> {
+            AsyncOta::get_slots(self, slots).await
+        }
+
+        async fn is_factory_reset_supported(&self) -> Result<bool, Self::Error> {
+            AsyncOta::is_factory_reset_supported(self).await
+        }
+
+        async fn factory_reset(&mut self) -> Result<(), Self::Error> {
+            AsyncOta::factory_reset(self).await
+        }
+
+        async fn initiate_update(&mut self) -> Result<Self::Update<'_>, Self::Error> {
+            AsyncOta::initiate_update(self).await
+        }
+
+        async fn resume_update(
+            &mut self,
+        ) -> Result<Option<ResumedUpdate<Self::Update<'_>>>, Self::Error> {
+            AsyncOta::resume_update(self).await
+        }
+
+        async fn mark_running_slot_valid(&mut self) -> Result<(), Self::Error> {
+            AsyncOta::mark_running_slot_valid(self).await
+        }
+
+        async fn mark_running_slot_invalid_and_reboot(&mut self) -> Self::Error {
+            AsyncOta::mark_running_slot_invalid_and_reboot(self).await
+        }
+    }
+
+    impl<U, T> ErrorType for AsyncOtaUpdate<'_, U, T>
+    where
+        T: ErrorType,
+    {
+        type Error = T::Error;
+    }
+
+    impl<'a, U, T> Write for AsyncOtaUpdate<'a, U, T>
+    where
+        U: Unblocker,
+        T: crate::ota::OtaUpdate + Send + 'a,
+        T::Error: Send,
+        T::OtaUpdateFinished: Send,
+        <T::OtaUpdateFinished as ErrorType>::Error: Send,
+    {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            AsyncOtaUpdate::write(self, buf).await
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            AsyncOtaUpdate::flush(self).await
+        }
+    }
+
+    impl<'a, U, T> OtaUpdate for AsyncOtaUpdate<'a, U, T>
+    where
+        U: Unblocker,
+        T: crate::ota::OtaUpdate + Send + 'a,
+        T::Error: Send,
+        T::OtaUpdateFinished: Send,
+        <T::OtaUpdateFinished as ErrorType>::Error: Send,
+    {
+        type OtaUpdateFinished = AsyncOtaUpdateFinished<'a, U, T::OtaUpdateFinished>;
+
+        async fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+            AsyncOtaUpdate::finish(self).await
+        }
+
+        async fn complete(self) -> Result<(), Self::Error> {
+            AsyncOtaUpdate::complete(self).await
+        }
+
+        async fn abort(self) -> Result<(), Self::Error> {
+            AsyncOtaUpdate::abort(self).await
+        }
+
+        fn written(&self) -> Option<u64> {
+            AsyncOtaUpdate::written(self)
+        }
+
+        async fn update<R>(
+            mut self,
+            read: R,
+            progress: impl Fn(u64, u64),
+        ) -> Result<(), CopyError<R::Error, Self::Error>>
+        where
+            R: Read,
+            Self: Sized,
+        {
+            let mut buf = [0_u8; 64];
+
+            match copy_with_progress(read, &mut self, &mut buf, progress).await {
+                Ok(_) => AsyncOtaUpdate::complete(self)
+                    .await
+                    .map_err(CopyError::Write),
+                Err(e) => {
+                    AsyncOtaUpdate::abort(self)
+                        .await
+                        .map_err(CopyError::Write)?;
+
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    impl<U, F> ErrorType for AsyncOtaUpdateFinished<'_, U, F>
+    where
+        F: ErrorType,
+    {
+        type Error = F::Error;
+    }
+
+    impl<'a, U, F> OtaUpdateFinished for AsyncOtaUpdateFinished<'a, U, F>
+    where
+        U: Unblocker,
+        F: crate::ota::OtaUpdateFinished + Send + 'a,
+        F::Error: Send,
+    {
+        async fn activate(self) -> Result<(), Self::Error> {
+            AsyncOtaUpdateFinished::activate(self).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::io::ErrorKind;
+    use crate::ota::SlotState;
+    use crate::test_ota::MockOta;
+    use crate::utils::asyncify::{block_on, blocking_unblocker, BlockingUnblocker, Unblocker};
+
+    use super::*;
+
+    /// Counts the computations handed off to it
+    struct CountingUnblocker(AtomicUsize, BlockingUnblocker);
+
+    impl CountingUnblocker {
+        fn new() -> Self {
+            Self(AtomicUsize::new(0), blocking_unblocker())
+        }
+
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Unblocker for CountingUnblocker {
+        type UnblockFuture<'a, F, T>
+            = <BlockingUnblocker as Unblocker>::UnblockFuture<'a, F, T>
+        where
+            Self: 'a,
+            F: Send + 'a,
+            T: Send + 'a;
+
+        fn unblock<'a, F, T>(&'a self, f: F) -> Self::UnblockFuture<'a, F, T>
+        where
+            F: FnOnce() -> T + Send + 'a,
+            T: Send + 'a,
+        {
+            self.0.fetch_add(1, Ordering::SeqCst);
+
+            self.1.unblock(f)
+        }
+    }
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn update_chunked() {
+        let image = image(250);
+        let unblocker = CountingUnblocker::new();
+        let mut ota = AsyncOta::new(&unblocker, MockOta::new()).with_chunk_size(100);
+
+        block_on(async {
+            let mut update = ota.initiate_update().await.unwrap();
+
+            assert_eq!(update.write(&image).await, Ok(250));
+            assert_eq!(update.written(), Some(250));
+
+            update.complete().await.unwrap();
+        });
+
+        // Initiating, 3 chunks and completing
+        assert_eq!(unblocker.count(), 5);
+        assert_eq!(ota.ota.completed.as_deref(), Some(&image[..]));
+        assert!(ota.ota.activated);
+    }
+
+    #[test]
+    fn update_short_writes() {
+        let image = image(300);
+        let mut ota = AsyncOta::new(
+            blocking_unblocker(),
+            MockOta {
+                max_write: Some(7),
+                ..Default::default()
+            },
+        );
+
+        block_on(async {
+            let mut update = ota.initiate_update().await.unwrap();
+
+            // Each chunk is written fully, even if the update accepts less per write
+            assert_eq!(update.write(&image).await, Ok(300));
+
+            update.finish().await.unwrap().activate().await.unwrap();
+        });
+
+        assert_eq!(ota.ota.completed.as_deref(), Some(&image[..]));
+        assert!(ota.ota.activated);
+    }
+
+    #[test]
+    fn update_zero_chunk_size() {
+        let unblocker = CountingUnblocker::new();
+        let mut ota = AsyncOta::new(&unblocker, MockOta::new()).with_chunk_size(0);
+
+        block_on(async {
+            let mut update = ota.initiate_update().await.unwrap();
+
+            assert_eq!(update.write(b"abc").await, Ok(3));
+
+            update.abort().await.unwrap();
+        });
+
+        // Initiating, 3 chunks of 1 byte and aborting
+        assert_eq!(unblocker.count(), 5);
+        assert_eq!(ota.ota.aborts, 1);
+        assert_eq!(ota.ota.completed, None);
+    }
+
+    #[test]
+    fn update_write_failure() {
+        let image = image(300);
+        let mut ota = AsyncOta::new(
+            blocking_unblocker(),
+            MockOta {
+                fail_at: Some(150),
+                ..Default::default()
+            },
+        )
+        .with_chunk_size(100);
+
+        block_on(async {
+            let mut update = ota.initiate_update().await.unwrap();
+
+            assert_eq!(update.write(&image).await, Err(ErrorKind::Other));
+
+            update.abort().await.unwrap();
+        });
+
+        assert_eq!(ota.ota.aborts, 1);
+        assert!(ota.ota.image.is_empty());
+    }
+
+    #[test]
+    fn update_resumed() {
+        let image = image(3000);
+        let mut ota = AsyncOta::new(
+            blocking_unblocker(),
+            MockOta {
+                sector: 1024,
+                ..Default::default()
+            },
+        );
+
+        block_on(async {
+            assert!(ota.resume_update().await.unwrap().is_none());
+
+            // Not completed or aborted, as if the device rebooted
+            {
+                let mut update = ota.initiate_update().await.unwrap();
+
+                update.write(&image[..2500]).await.unwrap();
+            }
+
+            let ResumedUpdate { mut update, offset } = ota.resume_update().await.unwrap().unwrap();
+
+            assert_eq!(offset, 2048);
+            assert_eq!(update.written(), Some(2048));
+
+            update.write(&image[offset as usize..]).await.unwrap();
+            update.complete().await.unwrap();
+        });
+
+        assert_eq!(ota.ota.completed.as_deref(), Some(&image[..]));
+    }
+
+    #[test]
+    fn slots() {
+        let mut ota = AsyncOta::new(blocking_unblocker(), MockOta::new());
+
+        block_on(async {
+            assert_eq!(ota.get_running_slot().await.unwrap().label, "running");
+            assert_eq!(
+                ota.get_update_slot().await.unwrap().state,
+                SlotState::Unknown
+            );
+
+            let mut slots: [Slot; 3] = Default::default();
+
+            // The boot slot is the running one
+            assert_eq!(ota.get_slots(&mut slots).await, Ok(2));
+            assert_eq!(slots[1].label, "update");
+
+            assert_eq!(ota.is_factory_reset_supported().await, Ok(false));
+            assert_eq!(ota.factory_reset().await, Err(ErrorKind::Unsupported));
+            assert_eq!(ota.mark_running_slot_valid().await, Ok(()));
+        });
+    }
+}
@@ -0,0 +1,496 @@
+use core::future::Future;
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+extern crate alloc;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use crate::ipv4;
+use crate::ping::{Configuration, Event, Reply, Summary};
+use crate::utils::asyncify::Unblocker;
+use crate::utils::mutex::{Mutex, RawMutex};
+
+#[allow(unused_imports)]
+#[cfg(feature = "nightly")]
+pub use async_traits_impl::*;
+
+use super::UnblockingAsyncWrapper;
+
+struct RepliesState {
+    replies: VecDeque<Reply>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+type SessionFuture<'a, E> = Pin<Box<dyn Future<Output = Result<Summary, E>> + Send + 'a>>;
+
+/// The replies of a ping session executed by `AsyncPing`.
+///
+/// Dropping the replies aborts the blocking session once its next echo completes.
+pub struct AsyncReplies<'a, R, E>
+where
+    R: RawMutex,
+{
+    state: Arc<Mutex<R, RepliesState>>,
+    session: Option<SessionFuture<'a, E>>,
+    result: Option<Result<Summary, E>>,
+}
+
+impl<'a, R, E> AsyncReplies<'a, R, E>
+where
+    R: RawMutex,
+{
+    #[allow(clippy::should_implement_trait)]
+    pub async fn next(&mut self) -> Option<Result<Event, E>> {
+        NextFuture(self).await
+    }
+}
+
+impl<R, E> Drop for AsyncReplies<'_, R, E>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+
+        state.closed = true;
+        state.replies.clear();
+        state.waker = None;
+    }
+}
+
+struct NextFuture<'r, 'a, R, E>(&'r mut AsyncReplies<'a, R, E>)
+where
+    R: RawMutex;
+
+impl<R, E> Future for NextFuture<'_, '_, R, E>
+where
+    R: RawMutex,
+{
+    type Output = Option<Result<Event, E>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let replies = &mut *self.0;
+
+        {
+            let mut state = replies.state.lock();
+
+            if let Some(reply) = state.replies.pop_front() {
+                return Poll::Ready(Some(Ok(Event::Reply(reply))));
+            }
+
+            state.waker = Some(cx.waker().clone());
+        }
+
+        if let Some(session) = replies.session.as_mut() {
+            if let Poll::Ready(result) = session.as_mut().poll(cx) {
+                replies.session = None;
+                replies.result = Some(result);
+
+                // Replies might have been posted right before the session completed
+                if let Some(reply) = replies.state.lock().replies.pop_front() {
+                    return Poll::Ready(Some(Ok(Event::Reply(reply))));
+                }
+            } else {
+                return Poll::Pending;
+            }
+        }
+
+        Poll::Ready(
+            replies
+                .result
+                .take()
+                .map(|result| result.map(Event::Summary)),
+        )
+    }
+}
+
+pub struct AsyncPing<U, R, P> {
+    unblocker: U,
+    ping: P,
+    _raw_mutex: PhantomData<fn() -> R>,
+}
+
+impl<U, R, P> AsyncPing<U, R, P> {
+    pub const fn new(unblocker: U, ping: P) -> Self {
+        Self {
+            unblocker,
+            ping,
+            _raw_mutex: PhantomData,
+        }
+    }
+}
+
+impl<U, R, P> AsyncPing<U, R, P>
+where
+    U: Unblocker,
+    R: RawMutex + Send + Sync,
+    P: crate::ping::Ping + Send,
+    P::Error: Send,
+{
+    pub fn ping_replies(
+        &mut self,
+        ip: ipv4::Ipv4Addr,
+        conf: &Configuration,
+    ) -> AsyncReplies<'_, R, P::Error> {
+        let state = Arc::new(Mutex::new(RepliesState {
+            replies: VecDeque::new(),
+            waker: None,
+            closed: false,
+        }));
+
+        let conf = conf.clone();
+        let ping = &mut self.ping;
+
+        let session = {
+            let state = state.clone();
+
+            self.unblocker.unblock(move || {
+                ping.ping_details(ip, &conf, move |_, reply| {
+                    let mut state = state.lock();
+
+                    if state.closed {
+                        return ControlFlow::Break(());
+                    }
+
+                    state.replies.push_back(reply.clone());
+
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+
+                    ControlFlow::Continue(())
+                })
+            })
+        };
+
+        AsyncReplies {
+            state,
+            session: Some(Box::pin(session)),
+            result: None,
+        }
+    }
+}
+
+impl<U, R, P> UnblockingAsyncWrapper<U, P> for AsyncPing<U, R, P> {
+    fn new(unblocker: U, sync: P) -> Self {
+        AsyncPing::new(unblocker, sync)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use core::fmt::Debug;
+
+    use crate::ipv4;
+    use crate::ping::asynch::{Configuration, Event, PingReplies, Replies};
+    use crate::utils::asyncify::Unblocker;
+    use crate::utils::mutex::RawMutex;
+
+    use super::{AsyncPing, AsyncReplies};
+
+    impl<'a, R, E> Replies for AsyncReplies<'a, R, E>
+    where
+        R: RawMutex,
+        E: Debug,
+    {
+        type Error = E;
+
+        async fn next(&mut self) -> Option<Result<Event, Self::Error>> {
+            AsyncReplies::next(self).await
+        }
+    }
+
+    impl<U, R, P> PingReplies for AsyncPing<U, R, P>
+    where
+        U: Unblocker,
+        R: RawMutex + Send + Sync,
+        P: crate::ping::Ping + Send,
+        P::Error: Send,
+    {
+        type Error = P::Error;
+
+        type Replies<'a> = AsyncReplies<'a, R, P::Error> where Self: 'a;
+
+        async fn ping_replies(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            conf: &Configuration,
+        ) -> Result<Self::Replies<'_>, Self::Error> {
+            Ok(AsyncPing::ping_replies(self, ip, conf))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::Future;
+    use core::ops::ControlFlow;
+    use core::pin::Pin;
+    use core::task::{Context, Poll, Waker};
+    use core::time::Duration;
+
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+
+    use crate::ipv4::Ipv4Addr;
+    use crate::ping::{Configuration, Event, Info, Ping, Reply, Summary};
+    use crate::utils::asyncify::{block_on, blocking_unblocker, Unblocker};
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::AsyncPing;
+
+    /// Replies with the scripted round-trip times (`None` being a timeout), `interval` apart
+    struct MockPing {
+        rtts: &'static [Option<u64>],
+        interval: Duration,
+        echoes: Arc<AtomicU32>,
+    }
+
+    impl MockPing {
+        fn new(rtts: &'static [Option<u64>]) -> Self {
+            Self {
+                rtts,
+                interval: Duration::ZERO,
+                echoes: Arc::new(AtomicU32::new(0)),
+            }
+        }
+    }
+
+    impl Ping for MockPing {
+        type Error = ();
+
+        fn ping(&mut self, ip: Ipv4Addr, conf: &Configuration) -> Result<Summary, ()> {
+            self.ping_details(ip, conf, |_, _| ControlFlow::Continue(()))
+        }
+
+        fn ping_details<F: FnMut(&Summary, &Reply) -> ControlFlow<()> + Send>(
+            &mut self,
+            ip: Ipv4Addr,
+            _conf: &Configuration,
+            mut reply_callback: F,
+        ) -> Result<Summary, ()> {
+            let mut summary = Summary::default();
+
+            for (seqno, rtt) in self.rtts.iter().enumerate() {
+                thread::sleep(self.interval);
+
+                let reply = match rtt {
+                    Some(rtt) => Reply::Success(Info {
+                        addr: ip,
+                        seqno: seqno as u32,
+                        ttl: 64,
+                        elapsed_time: Duration::from_millis(*rtt),
+                        recv_len: 64,
+                    }),
+                    None => Reply::Timeout,
+                };
+
+                self.echoes.fetch_add(1, Ordering::SeqCst);
+                summary.transmitted += 1;
+
+                if let Reply::Success(info) = &reply {
+                    summary.received += 1;
+                    summary.time += info.elapsed_time;
+                }
+
+                if reply_callback(&summary, &reply).is_break() {
+                    break;
+                }
+            }
+
+            Ok(summary)
+        }
+    }
+
+    struct ThreadState<T> {
+        result: Option<T>,
+        waker: Option<Waker>,
+    }
+
+    /// Runs each computation on its own thread, which is joined when the returned future is dropped
+    struct ThreadUnblocker;
+
+    struct ThreadFuture<T> {
+        state: Arc<Mutex<ThreadState<T>>>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Unblocker for ThreadUnblocker {
+        type UnblockFuture<'a, F, T>
+            = ThreadFuture<T>
+        where
+            Self: 'a,
+            F: Send + 'a,
+            T: Send + 'a;
+
+        fn unblock<'a, F, T>(&'a self, f: F) -> Self::UnblockFuture<'a, F, T>
+        where
+            F: FnOnce() -> T + Send + 'a,
+            T: Send + 'a,
+        {
+            let state = Arc::new(Mutex::new(ThreadState {
+                result: None,
+                waker: None,
+            }));
+
+            let computation: Box<dyn FnOnce() + Send + 'a> = {
+                let state = state.clone();
+
+                Box::new(move || {
+                    let result = f();
+
+                    let mut state = state.lock().unwrap();
+
+                    state.result = Some(result);
+
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                })
+            };
+
+            // SAFETY: The thread is joined when the future is dropped, so the computation does not outlive the
+            // borrows it captures, as long as the future is not leaked (which these tests don't do)
+            let computation: Box<dyn FnOnce() + Send + 'static> =
+                unsafe { core::mem::transmute(computation) };
+
+            ThreadFuture {
+                state,
+                thread: Some(thread::spawn(computation)),
+            }
+        }
+    }
+
+    impl<T> Future for ThreadFuture<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut state = self.state.lock().unwrap();
+
+            if let Some(result) = state.result.take() {
+                Poll::Ready(result)
+            } else {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    impl<T> Drop for ThreadFuture<T> {
+        fn drop(&mut self) {
+            if let Some(thread) = self.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+
+    fn rtt(event: &Event) -> Option<Duration> {
+        match event {
+            Event::Reply(Reply::Success(info)) => Some(info.elapsed_time),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn replies_then_summary() {
+        let mut ping = AsyncPing::<_, StdRawMutex, _>::new(
+            blocking_unblocker(),
+            MockPing::new(&[Some(10), None, Some(20), Some(30)]),
+        );
+
+        let events = block_on(async {
+            let mut replies = ping.ping_replies(Ipv4Addr::LOCALHOST, &Default::default());
+            let mut events = std::vec::Vec::new();
+
+            while let Some(event) = replies.next().await {
+                events.push(event.unwrap());
+            }
+
+            events
+        });
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(rtt(&events[0]), Some(Duration::from_millis(10)));
+        assert_eq!(events[1], Event::Reply(Reply::Timeout));
+        assert_eq!(rtt(&events[2]), Some(Duration::from_millis(20)));
+        assert_eq!(rtt(&events[3]), Some(Duration::from_millis(30)));
+        assert_eq!(
+            events[4],
+            Event::Summary(Summary {
+                transmitted: 4,
+                received: 3,
+                time: Duration::from_millis(60),
+            })
+        );
+    }
+
+    #[test]
+    fn replies_from_unblocked_session() {
+        let mut ping = AsyncPing::<_, StdRawMutex, _>::new(ThreadUnblocker, {
+            let mut ping = MockPing::new(&[Some(10), None, Some(20), Some(30)]);
+            ping.interval = Duration::from_millis(5);
+            ping
+        });
+
+        let events = block_on(async {
+            let mut replies = ping.ping_replies(Ipv4Addr::LOCALHOST, &Default::default());
+            let mut events = std::vec::Vec::new();
+
+            while let Some(event) = replies.next().await {
+                events.push(event.unwrap());
+            }
+
+            events
+        });
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[1], Event::Reply(Reply::Timeout));
+        assert!(matches!(
+            events[4],
+            Event::Summary(Summary {
+                transmitted: 4,
+                received: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn drop_aborts_session() {
+        const RTTS: &[Option<u64>] = &[Some(1); 100];
+
+        let mut mock = MockPing::new(RTTS);
+        mock.interval = Duration::from_millis(5);
+
+        let echoes = mock.echoes.clone();
+
+        let mut ping = AsyncPing::<_, StdRawMutex, _>::new(ThreadUnblocker, mock);
+
+        block_on(async {
+            let mut replies = ping.ping_replies(Ipv4Addr::LOCALHOST, &Default::default());
+
+            for _ in 0..2 {
+                assert!(matches!(
+                    replies.next().await,
+                    Some(Ok(Event::Reply(Reply::Success(_))))
+                ));
+            }
+
+            // Dropping the replies joins the session, which stops at its next echo
+        });
+
+        let echoes = echoes.load(Ordering::SeqCst);
+
+        assert!(echoes >= 2);
+        assert!(echoes < RTTS.len() as u32 / 2, "{} echoes", echoes);
+    }
+}
@@ -0,0 +1,201 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Either<A, B> {
+    First(A),
+    Second(B),
+}
+
+/// The future returned by `select2`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select2<A, B> {
+    first: Option<A>,
+    second: Option<B>,
+}
+
+/// Waits for the first of two futures to complete, returning its output together with the other future,
+/// which is not polled any further, and can be awaited again or dropped
+///
+/// If both futures are ready when polled, `first` wins.
+///
+/// The futures have to be `Unpin`, so that the other one can be returned as-is;
+/// futures which are not can be pinned first, e.g. with `core::pin::pin!`.
+pub fn select2<A, B>(first: A, second: B) -> Select2<A, B>
+where
+    A: Future + Unpin,
+    B: Future + Unpin,
+{
+    Select2 {
+        first: Some(first),
+        second: Some(second),
+    }
+}
+
+impl<A, B> Future for Select2<A, B>
+where
+    A: Future + Unpin,
+    B: Future + Unpin,
+{
+    type Output = Either<(A::Output, B), (A, B::Output)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let first = self
+            .first
+            .as_mut()
+            .expect("Select2 polled after completion");
+
+        if let Poll::Ready(output) = Pin::new(first).poll(cx) {
+            let second = self.second.take().unwrap();
+            self.first = None;
+
+            return Poll::Ready(Either::First((output, second)));
+        }
+
+        let second = self.second.as_mut().unwrap();
+
+        if let Poll::Ready(output) = Pin::new(second).poll(cx) {
+            let first = self.first.take().unwrap();
+            self.second = None;
+
+            return Poll::Ready(Either::Second((first, output)));
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Waits for the first of two receivers to receive a payload
+///
+/// If both receive a payload when polled, `first` wins. The receive of the other receiver is dropped,
+/// so receivers which lose their payload when a receive is dropped mid-way should not be used.
+#[cfg(feature = "nightly")]
+pub async fn select_recv<A, B>(
+    first: &A,
+    second: &B,
+) -> Either<Result<A::Data, A::Error>, Result<B::Data, B::Error>>
+where
+    A: crate::event_bus::asynch::Receiver,
+    B: crate::event_bus::asynch::Receiver,
+{
+    let first = core::pin::pin!(first.recv());
+    let second = core::pin::pin!(second.recv());
+
+    match select2(first, second).await {
+        Either::First((output, _)) => Either::First(output),
+        Either::Second((_, output)) => Either::Second(output),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::{pending, ready};
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Waker;
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+    use crate::utils::asyncify::oneshot::channel;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn first_wins() {
+        assert!(matches!(
+            block_on(select2(ready(1), ready(2))),
+            Either::First((1, _))
+        ));
+
+        assert!(matches!(
+            block_on(select2(pending::<u32>(), ready(2))),
+            Either::Second((_, 2))
+        ));
+    }
+
+    #[test]
+    fn other_future() {
+        let (first_sender, first_receiver) = channel::<StdRawMutex, u32>();
+        let (second_sender, second_receiver) = channel::<StdRawMutex, u32>();
+
+        let waker = Arc::new(CountingWaker::default());
+
+        let first = pin!(first_receiver.recv());
+        let second = pin!(second_receiver.recv());
+
+        let mut select = pin!(select2(first, second));
+        assert!(poll(select.as_mut(), &waker).is_pending());
+
+        // Both futures were polled with the waker of the select
+        second_sender.send(2).unwrap();
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+
+        let first = match poll(select.as_mut(), &waker) {
+            Poll::Ready(Either::Second((first, output))) => {
+                assert_eq!(output, Ok(2));
+
+                first
+            }
+            _ => panic!("The second future should have completed"),
+        };
+
+        // The other future is handed back as it was, and can still complete
+        first_sender.send(1).unwrap();
+        assert_eq!(block_on(first), Ok(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Select2 polled after completion")]
+    fn polled_after_completion() {
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut select = pin!(select2(ready(1), ready(2)));
+
+        let _ = poll(select.as_mut(), &waker);
+        let _ = poll(select.as_mut(), &waker);
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn select_recv() {
+        let (first_sender, first_receiver) = channel::<StdRawMutex, u32>();
+        let (second_sender, second_receiver) = channel::<StdRawMutex, u32>();
+
+        second_sender.send(2).unwrap();
+
+        assert_eq!(
+            block_on(super::select_recv(&first_receiver, &second_receiver)),
+            Either::Second(Ok(2))
+        );
+
+        drop(first_sender);
+
+        assert_eq!(
+            block_on(super::select_recv(&first_receiver, &second_receiver)),
+            Either::First(Err(crate::utils::asyncify::oneshot::Canceled))
+        );
+    }
+}
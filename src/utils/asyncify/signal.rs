@@ -0,0 +1,209 @@
+use core::future::poll_fn;
+use core::task::{Context, Poll, Waker};
+
+use crate::utils::mutex::{Mutex, RawMutex};
+
+struct SignalState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A single-slot cell, for signaling the latest value of something from blocking code to a task
+///
+/// `signal` never blocks for longer than it takes to lock the mutex, so it can be called from callbacks
+/// (and from ISRs, with a `RawMutex` based on a critical section). A value not taken yet is overwritten,
+/// so the waiting task only ever sees the latest one.
+///
+/// The signal needs no allocations, and can be put in a static. It has a single waker,
+/// so only one task should wait on it at a time.
+pub struct Signal<R, T>(Mutex<R, SignalState<T>>)
+where
+    R: RawMutex;
+
+impl<R, T> Signal<R, T>
+where
+    R: RawMutex,
+{
+    pub const fn new() -> Self {
+        Self(Mutex::new(SignalState {
+            value: None,
+            waker: None,
+        }))
+    }
+
+    /// Stores `value`, overwriting the one not taken yet (if any), and wakes the waiting task
+    pub fn signal(&self, value: T) {
+        let mut state = self.0.lock();
+
+        state.value = Some(value);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Waits until a value is signaled, and takes it
+    pub async fn wait(&self) -> T {
+        poll_fn(|cx| self.poll_wait(cx)).await
+    }
+
+    /// The poll-based variant of `wait`, for use in hand-written futures
+    pub fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.0.lock();
+
+        if let Some(value) = state.value.take() {
+            Poll::Ready(value)
+        } else {
+            match &state.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => (),
+                _ => state.waker = Some(cx.waker().clone()),
+            }
+
+            Poll::Pending
+        }
+    }
+
+    /// Takes the signaled value, if any, without waiting
+    pub fn try_take(&self) -> Option<T> {
+        self.0.lock().value.take()
+    }
+
+    /// Whether a value was signaled, and not taken yet
+    pub fn is_signaled(&self) -> bool {
+        self.0.lock().value.is_some()
+    }
+
+    /// Drops the signaled value, if any
+    pub fn reset(&self) {
+        self.0.lock().value = None;
+    }
+}
+
+impl<R, T> Default for Signal<R, T>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl CountingWaker {
+        fn wakes(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn waker() -> (Arc<CountingWaker>, Waker) {
+        let counter = Arc::new(CountingWaker::default());
+
+        (counter.clone(), counter.into())
+    }
+
+    #[test]
+    fn latest_wins() {
+        let signal = Signal::<StdRawMutex, u32>::new();
+
+        assert!(!signal.is_signaled());
+        assert_eq!(signal.try_take(), None);
+
+        signal.signal(1);
+        signal.signal(2);
+        assert!(signal.is_signaled());
+
+        // Only the latest is kept, and taken once
+        assert_eq!(block_on(signal.wait()), 2);
+        assert_eq!(signal.try_take(), None);
+
+        signal.signal(3);
+        assert_eq!(signal.try_take(), Some(3));
+        assert!(!signal.is_signaled());
+
+        signal.signal(4);
+        signal.reset();
+        assert_eq!(signal.try_take(), None);
+    }
+
+    #[test]
+    fn wakes_waiter() {
+        let signal = Signal::<StdRawMutex, u32>::new();
+
+        let (counter, waker) = waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut wait = pin!(signal.wait());
+
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.wakes(), 0);
+
+        signal.signal(1);
+        assert_eq!(counter.wakes(), 1);
+
+        // The waker is taken by the wake, so the task is woken once until it polls again
+        signal.signal(2);
+        assert_eq!(counter.wakes(), 1);
+
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(2));
+    }
+
+    #[test]
+    fn single_waker() {
+        let signal = Signal::<StdRawMutex, u32>::new();
+
+        let (first, first_waker) = waker();
+        let (second, second_waker) = waker();
+
+        assert!(signal
+            .poll_wait(&mut Context::from_waker(&first_waker))
+            .is_pending());
+        assert!(signal
+            .poll_wait(&mut Context::from_waker(&second_waker))
+            .is_pending());
+
+        // Only the waker of the last poll is woken
+        signal.signal(1);
+
+        assert_eq!(first.wakes(), 0);
+        assert_eq!(second.wakes(), 1);
+    }
+
+    #[test]
+    fn signaled_from_thread() {
+        static SIGNAL: Signal<StdRawMutex, u32> = Signal::new();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+
+                SIGNAL.signal(7);
+            });
+
+            assert_eq!(block_on(SIGNAL.wait()), 7);
+        });
+    }
+}
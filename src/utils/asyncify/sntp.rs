@@ -0,0 +1,332 @@
+use core::marker::PhantomData;
+use core::time::Duration;
+
+extern crate alloc;
+use alloc::sync::Arc;
+
+use crate::sntp::{Configuration, Sntp, SyncStatus};
+use crate::sys_time::SystemTimeSteps;
+use crate::utils::mutex::RawMutex;
+
+#[allow(unused_imports)]
+#[cfg(feature = "nightly")]
+pub use async_traits_impl::*;
+
+use super::signal::Signal;
+use super::AsyncWrapper;
+
+/// An async SNTP service on top of a blocking one, which is notified of the completed syncs with `Sntp::subscribe`
+///
+/// None of the blocking calls wait for the network, so no unblocker is needed.
+pub struct AsyncSntp<R, S> {
+    sntp: S,
+    _raw_mutex: PhantomData<fn() -> R>,
+}
+
+impl<R, S> AsyncSntp<R, S> {
+    pub const fn new(sntp: S) -> Self {
+        Self {
+            sntp,
+            _raw_mutex: PhantomData,
+        }
+    }
+
+    pub fn blocking(&self) -> &S {
+        &self.sntp
+    }
+}
+
+impl<R, S> AsyncSntp<R, S>
+where
+    R: RawMutex + Send + Sync + 'static,
+    S: Sntp,
+{
+    pub fn get_configuration(&self) -> Result<Configuration, S::Error> {
+        self.sntp.get_configuration()
+    }
+
+    pub fn set_configuration(&mut self, conf: &Configuration) -> Result<(), S::Error> {
+        self.sntp.set_configuration(conf)
+    }
+
+    pub fn sync_status(&self) -> Result<SyncStatus, S::Error> {
+        self.sntp.sync_status()
+    }
+
+    /// Waits until the clock is synced, returning the time it was last synced at
+    pub async fn wait_synced(&self) -> Result<Duration, S::Error> {
+        let state = Self::state();
+
+        // Subscribe before checking the status, so that a sync completing in between is not missed
+        let _subscription = self.sntp.subscribe(Self::notifier(&state))?;
+
+        if let SyncStatus::Completed { last_synced_at } = self.sntp.sync_status()? {
+            return Ok(last_synced_at);
+        }
+
+        Ok(Self::wait(&state).await)
+    }
+
+    /// Like `wait_synced`, but also completes when the clock is set by other means (e.g. by the user, or from GNSS),
+    /// as reported by `steps`, returning the time the clock was set to in that case
+    pub async fn wait_synced_or_set<T>(&self, steps: &T) -> Result<Duration, S::Error>
+    where
+        T: SystemTimeSteps,
+        S::Error: From<T::Error>,
+    {
+        let state = Self::state();
+
+        let _subscription = self.sntp.subscribe(Self::notifier(&state))?;
+
+        let notifier = Self::notifier(&state);
+        let _steps_subscription = steps.subscribe(move |step| notifier(step.to))?;
+
+        if let SyncStatus::Completed { last_synced_at } = self.sntp.sync_status()? {
+            return Ok(last_synced_at);
+        }
+
+        Ok(Self::wait(&state).await)
+    }
+
+    fn state() -> Arc<Signal<R, Duration>> {
+        Arc::new(Signal::new())
+    }
+
+    fn notifier(state: &Arc<Signal<R, Duration>>) -> impl Fn(Duration) + Send + 'static {
+        let state = state.clone();
+
+        move |synced_at| state.signal(synced_at)
+    }
+
+    async fn wait(state: &Signal<R, Duration>) -> Duration {
+        state.wait().await
+    }
+}
+
+impl<R, S> AsyncWrapper<S> for AsyncSntp<R, S> {
+    fn new(sync: S) -> Self {
+        AsyncSntp::new(sync)
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    use core::time::Duration;
+
+    use crate::sntp::asynch::{Configuration, Sntp, SyncStatus};
+    use crate::utils::mutex::RawMutex;
+
+    use super::AsyncSntp;
+
+    impl<R, S> Sntp for AsyncSntp<R, S>
+    where
+        R: RawMutex + Send + Sync + 'static,
+        S: crate::sntp::Sntp,
+    {
+        type Error = S::Error;
+
+        async fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+            AsyncSntp::get_configuration(self)
+        }
+
+        async fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+            AsyncSntp::set_configuration(self, conf)
+        }
+
+        async fn sync_status(&self) -> Result<SyncStatus, Self::Error> {
+            AsyncSntp::sync_status(self)
+        }
+
+        async fn wait_synced(&self) -> Result<Duration, Self::Error> {
+            AsyncSntp::wait_synced(self).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::Infallible;
+    use core::future::Future;
+    use core::pin::{pin, Pin};
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use std::sync::Mutex;
+
+    use crate::sys_time::{SimulatedSystemTime, SystemTimeSetter};
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    type Subscribers = Vec<(usize, Box<dyn FnMut(Duration) + Send>)>;
+
+    /// An SNTP service whose syncs only complete with `complete`
+    #[derive(Default)]
+    struct MockSntp {
+        status: Mutex<Option<Duration>>,
+        subscribers: Arc<Mutex<Subscribers>>,
+        next_id: Mutex<usize>,
+    }
+
+    impl MockSntp {
+        fn complete(&self, synced_at: Duration) {
+            *self.status.lock().unwrap() = Some(synced_at);
+
+            for (_, subscriber) in self.subscribers.lock().unwrap().iter_mut() {
+                subscriber(synced_at);
+            }
+        }
+
+        fn subscribers(&self) -> usize {
+            self.subscribers.lock().unwrap().len()
+        }
+    }
+
+    struct MockSubscription(Arc<Mutex<Subscribers>>, usize);
+
+    impl Drop for MockSubscription {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().retain(|(id, _)| *id != self.1);
+        }
+    }
+
+    impl Sntp for MockSntp {
+        type Error = Infallible;
+
+        type Subscription<'a> = MockSubscription where Self: 'a;
+
+        fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+            Ok(Default::default())
+        }
+
+        fn set_configuration(&mut self, _conf: &Configuration) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn sync_status(&self) -> Result<SyncStatus, Self::Error> {
+            Ok(match *self.status.lock().unwrap() {
+                Some(last_synced_at) => SyncStatus::Completed { last_synced_at },
+                None => SyncStatus::Reset,
+            })
+        }
+
+        fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+        where
+            F: FnMut(Duration) + Send + 'static,
+        {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+
+            self.subscribers
+                .lock()
+                .unwrap()
+                .push((*next_id, Box::new(callback)));
+
+            Ok(MockSubscription(self.subscribers.clone(), *next_id))
+        }
+    }
+
+    const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(core::ptr::null(), &NOOP_VTABLE),
+        |_| (),
+        |_| (),
+        |_| (),
+    );
+
+    fn poll<F>(future: Pin<&mut F>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        // Safety: the vtable functions do nothing with the (null) data pointer
+        let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &NOOP_VTABLE)) };
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    const SYNCED_AT: Duration = Duration::from_secs(1_700_000_000);
+
+    #[test]
+    fn synced_already() {
+        let sntp = AsyncSntp::<StdRawMutex, _>::new(MockSntp::default());
+        sntp.blocking().complete(SYNCED_AT);
+
+        assert!(sntp.sync_status().unwrap().is_synced());
+        assert_eq!(poll(pin!(sntp.wait_synced())), Poll::Ready(Ok(SYNCED_AT)));
+        assert_eq!(sntp.blocking().subscribers(), 0);
+    }
+
+    #[test]
+    fn waits_sync() {
+        let sntp = AsyncSntp::<StdRawMutex, _>::new(MockSntp::default());
+
+        {
+            let mut wait = pin!(sntp.wait_synced());
+
+            assert!(poll(wait.as_mut()).is_pending());
+            assert_eq!(sntp.blocking().subscribers(), 1);
+
+            sntp.blocking().complete(SYNCED_AT);
+
+            assert_eq!(poll(wait.as_mut()), Poll::Ready(Ok(SYNCED_AT)));
+        }
+
+        // Unsubscribed once done, or when the wait is dropped
+        assert_eq!(sntp.blocking().subscribers(), 0);
+
+        let sntp = AsyncSntp::<StdRawMutex, _>::new(MockSntp::default());
+
+        {
+            let mut wait = pin!(sntp.wait_synced());
+            assert!(poll(wait.as_mut()).is_pending());
+        }
+
+        assert_eq!(sntp.blocking().subscribers(), 0);
+    }
+
+    #[test]
+    fn synced_or_set() {
+        let clock = SimulatedSystemTime::new();
+
+        // Set by other means
+        let sntp = AsyncSntp::<StdRawMutex, _>::new(MockSntp::default());
+
+        {
+            let mut wait = pin!(sntp.wait_synced_or_set(&clock));
+            assert!(poll(wait.as_mut()).is_pending());
+
+            clock.set_time(SYNCED_AT).unwrap();
+
+            assert_eq!(poll(wait.as_mut()), Poll::Ready(Ok(SYNCED_AT)));
+        }
+
+        // Synced
+        {
+            let mut wait = pin!(sntp.wait_synced_or_set(&clock));
+            assert!(poll(wait.as_mut()).is_pending());
+
+            sntp.blocking().complete(SYNCED_AT * 2);
+
+            assert_eq!(poll(wait.as_mut()), Poll::Ready(Ok(SYNCED_AT * 2)));
+        }
+
+        assert_eq!(sntp.blocking().subscribers(), 0);
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use crate::sntp::asynch::Sntp;
+        use crate::utils::asyncify::block_on;
+        use crate::utils::asyncify::sntp::AsyncSntp;
+        use crate::utils::mutex::StdRawMutex;
+
+        use super::{MockSntp, SYNCED_AT};
+
+        #[test]
+        fn wait_synced_async() {
+            let sntp = AsyncSntp::<StdRawMutex, _>::new(MockSntp::default());
+            sntp.blocking().complete(SYNCED_AT);
+
+            assert!(block_on(Sntp::sync_status(&sntp)).unwrap().is_synced());
+            assert_eq!(block_on(Sntp::wait_synced(&sntp)), Ok(SYNCED_AT));
+        }
+    }
+}
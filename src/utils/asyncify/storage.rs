@@ -0,0 +1,376 @@
+extern crate alloc;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(feature = "use_serde")]
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::storage::{RawStorage, StorageBase};
+use crate::utils::asyncify::Unblocker;
+use crate::utils::mutex::{Mutex, MutexGuard, RawMutex};
+
+#[allow(unused_imports)]
+#[cfg(feature = "nightly")]
+pub use async_traits_impl::*;
+
+use super::UnblockingAsyncWrapper;
+
+/// An async storage, executing the operations of a blocking storage with an unblocker.
+///
+/// Keys and raw values are copied before being handed off to the unblocker, so that nothing borrowed
+/// by the caller is accessed once the future is dropped. `contains_borrowed` and `remove_borrowed` skip
+/// the copying of the key, and typed values are passed to `set` by reference.
+pub struct AsyncStorage<U, W>(W, U);
+
+impl<U, W> AsyncStorage<U, W> {
+    pub const fn new(unblocker: U, storage: W) -> Self {
+        Self(storage, unblocker)
+    }
+}
+
+impl<U, R, S> AsyncStorage<U, Arc<Mutex<R, S>>>
+where
+    U: Unblocker,
+    R: RawMutex + Send + Sync + 'static,
+    S: StorageBase + Send + 'static,
+    S::Error: Send + 'static,
+{
+    /// Locks the blocking storage, for the (rare) cases where it has to be accessed synchronously.
+    /// Blocks while an operation is being executed by the unblocker.
+    pub fn blocking(&self) -> MutexGuard<'_, R, S> {
+        self.0.lock()
+    }
+
+    pub async fn contains(&self, name: &str) -> Result<bool, S::Error> {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+
+        self.1.unblock(move || storage.lock().contains(&name)).await
+    }
+
+    pub async fn remove(&mut self, name: &str) -> Result<bool, S::Error> {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+
+        self.1.unblock(move || storage.lock().remove(&name)).await
+    }
+
+    pub async fn contains_borrowed(&self, name: &str) -> Result<bool, S::Error> {
+        let storage = &self.0;
+
+        self.1.unblock(move || storage.lock().contains(name)).await
+    }
+
+    pub async fn remove_borrowed(&mut self, name: &str) -> Result<bool, S::Error> {
+        let storage = &self.0;
+
+        self.1.unblock(move || storage.lock().remove(name)).await
+    }
+}
+
+impl<U, R, S> AsyncStorage<U, Arc<Mutex<R, S>>>
+where
+    U: Unblocker,
+    R: RawMutex + Send + Sync + 'static,
+    S: RawStorage + Send + 'static,
+    S::Error: Send + 'static,
+{
+    pub async fn len(&self, name: &str) -> Result<Option<usize>, S::Error> {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+
+        self.1.unblock(move || storage.lock().len(&name)).await
+    }
+
+    pub async fn get_raw<'a>(
+        &self,
+        name: &str,
+        buf: &'a mut [u8],
+    ) -> Result<Option<&'a [u8]>, S::Error> {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+        let buf_len = buf.len();
+
+        let value = self
+            .1
+            .unblock(move || {
+                let mut value: Vec<u8> = vec![0; buf_len];
+
+                let len = storage
+                    .lock()
+                    .get_raw(&name, &mut value)?
+                    .map(|value| value.len());
+
+                Ok(len.map(|len| {
+                    value.truncate(len);
+                    value
+                }))
+            })
+            .await?;
+
+        Ok(value.map(move |value| {
+            buf[..value.len()].copy_from_slice(&value);
+
+            &buf[..value.len()]
+        }))
+    }
+
+    pub async fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, S::Error> {
+        let name: String = name.to_owned();
+        let buf: Vec<u8> = buf.to_owned();
+        let storage = self.0.clone();
+
+        self.1
+            .unblock(move || storage.lock().set_raw(&name, &buf))
+            .await
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<U, R, S> AsyncStorage<U, Arc<Mutex<R, S>>>
+where
+    U: Unblocker,
+    R: RawMutex + Send + Sync + 'static,
+    S: crate::storage::Storage + Send + 'static,
+    S::Error: Send + 'static,
+{
+    pub async fn get<T>(&self, name: &str) -> Result<Option<T>, S::Error>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+
+        self.1.unblock(move || storage.lock().get(&name)).await
+    }
+
+    pub async fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, S::Error>
+    where
+        T: Serialize + Sync,
+    {
+        let name: String = name.to_owned();
+        let storage = self.0.clone();
+
+        self.1
+            .unblock(move || storage.lock().set(&name, value))
+            .await
+    }
+}
+
+impl<U, W> Clone for AsyncStorage<U, W>
+where
+    U: Clone,
+    W: Clone,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), self.1.clone())
+    }
+}
+
+#[allow(clippy::arc_with_non_send_sync)]
+impl<U, R, S> UnblockingAsyncWrapper<U, S> for AsyncStorage<U, Arc<Mutex<R, S>>>
+where
+    R: RawMutex,
+{
+    fn new(unblocker: U, sync: S) -> Self {
+        AsyncStorage::new(unblocker, Arc::new(Mutex::new(sync)))
+    }
+}
+
+#[cfg(feature = "nightly")]
+mod async_traits_impl {
+    extern crate alloc;
+    use alloc::sync::Arc;
+
+    #[cfg(feature = "use_serde")]
+    use serde::{de::DeserializeOwned, Serialize};
+
+    #[cfg(feature = "use_serde")]
+    use crate::storage::asynch::Storage;
+    use crate::storage::asynch::{RawStorage, StorageBase};
+    use crate::utils::asyncify::Unblocker;
+    use crate::utils::mutex::{Mutex, RawMutex};
+
+    use super::AsyncStorage;
+
+    impl<U, R, S> StorageBase for AsyncStorage<U, Arc<Mutex<R, S>>>
+    where
+        U: Unblocker,
+        R: RawMutex + Send + Sync + 'static,
+        S: crate::storage::StorageBase + Send + 'static,
+        S::Error: Send + 'static,
+    {
+        type Error = S::Error;
+
+        async fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+            AsyncStorage::contains(self, name).await
+        }
+
+        async fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+            AsyncStorage::remove(self, name).await
+        }
+    }
+
+    #[cfg(feature = "use_serde")]
+    impl<U, R, S> Storage for AsyncStorage<U, Arc<Mutex<R, S>>>
+    where
+        U: Unblocker,
+        R: RawMutex + Send + Sync + 'static,
+        S: crate::storage::Storage + Send + 'static,
+        S::Error: Send + 'static,
+    {
+        async fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+        where
+            T: DeserializeOwned + Send + 'static,
+        {
+            AsyncStorage::get(self, name).await
+        }
+
+        async fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+        where
+            T: Serialize + Sync,
+        {
+            AsyncStorage::set(self, name, value).await
+        }
+    }
+
+    impl<U, R, S> RawStorage for AsyncStorage<U, Arc<Mutex<R, S>>>
+    where
+        U: Unblocker,
+        R: RawMutex + Send + Sync + 'static,
+        S: crate::storage::RawStorage + Send + 'static,
+        S::Error: Send + 'static,
+    {
+        async fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+            AsyncStorage::len(self, name).await
+        }
+
+        async fn get_raw<'a>(
+            &self,
+            name: &str,
+            buf: &'a mut [u8],
+        ) -> Result<Option<&'a [u8]>, Self::Error> {
+            AsyncStorage::get_raw(self, name, buf).await
+        }
+
+        async fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+            AsyncStorage::set_raw(self, name, buf).await
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::storage::heap::{InMemoryStorage, InMemoryStorageError};
+    use crate::utils::asyncify::{block_on, blocking_unblocker, BlockingUnblocker};
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    type Storage<S = ()> =
+        AsyncStorage<BlockingUnblocker, Arc<Mutex<StdRawMutex, InMemoryStorage<S>>>>;
+
+    fn storage() -> Storage {
+        UnblockingAsyncWrapper::new(blocking_unblocker(), InMemoryStorage::new())
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let mut storage = storage();
+
+        block_on(async {
+            assert!(!storage.contains("blob").await.unwrap());
+            assert!(!storage.set_raw("blob", b"value").await.unwrap());
+            assert!(storage.set_raw("blob", b"other").await.unwrap());
+            assert!(storage.contains("blob").await.unwrap());
+            assert_eq!(storage.len("blob").await.unwrap(), Some(5));
+
+            let mut buf = [0; 8];
+            assert_eq!(
+                storage.get_raw("blob", &mut buf).await.unwrap(),
+                Some(&b"other"[..])
+            );
+            assert_eq!(storage.get_raw("none", &mut buf).await.unwrap(), None);
+
+            // The copy handed off to the unblocker is as large as the buffer
+            assert_eq!(
+                storage.get_raw("blob", &mut buf[..4]).await,
+                Err(InMemoryStorageError::ValueTooLarge)
+            );
+
+            assert!(storage.remove("blob").await.unwrap());
+            assert!(!storage.remove("blob").await.unwrap());
+            assert_eq!(storage.len("blob").await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn borrowed() {
+        let mut storage = storage();
+
+        block_on(async {
+            storage.set_raw("key", b"1").await.unwrap();
+
+            assert!(storage.contains_borrowed("key").await.unwrap());
+            assert!(storage.remove_borrowed("key").await.unwrap());
+            assert!(!storage.contains_borrowed("key").await.unwrap());
+            assert!(!storage.remove_borrowed("key").await.unwrap());
+        });
+    }
+
+    #[test]
+    fn shared() {
+        let mut storage = storage();
+        let other = storage.clone();
+
+        block_on(async {
+            storage.set_raw("key", b"1").await.unwrap();
+
+            assert!(other.contains("key").await.unwrap());
+        });
+
+        // Through the blocking escape hatch
+        assert!(other.blocking().contains("key").unwrap());
+
+        other.blocking().fail_next_write();
+
+        assert_eq!(
+            block_on(storage.set_raw("key", b"2")),
+            Err(InMemoryStorageError::Injected)
+        );
+        assert_eq!(block_on(storage.len("key")).unwrap(), Some(1));
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn typed_round_trip() {
+        let mut storage: Storage<crate::test_json::JsonSerDe> = AsyncStorage::new(
+            blocking_unblocker(),
+            Arc::new(Mutex::new(
+                InMemoryStorage::new().with_serde(crate::test_json::JsonSerDe),
+            )),
+        );
+
+        block_on(async {
+            assert_eq!(storage.get::<u32>("boots").await.unwrap(), None);
+
+            storage.set("boots", &42_u32).await.unwrap();
+            storage.set("name", &"sensor").await.unwrap();
+
+            assert_eq!(storage.get::<u32>("boots").await.unwrap(), Some(42));
+            assert_eq!(
+                storage.get::<String>("name").await.unwrap().as_deref(),
+                Some("sensor")
+            );
+
+            let mut buf = [0; 4];
+            assert_eq!(
+                storage.get_raw("boots", &mut buf).await.unwrap(),
+                Some(&b"42"[..])
+            );
+        });
+    }
+}
@@ -1,36 +1,46 @@
 use core::future::Future;
+use core::ops::Deref;
 use core::pin::Pin;
 use core::result::Result;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicIsize, Ordering};
 use core::task::{Context, Poll};
 use core::time::Duration;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "alloc")]
 use alloc::sync::Arc;
 
 use atomic_waker::AtomicWaker;
 
+use crate::sys_time::SystemTime;
+use crate::timer::{MissedTickPolicy, TimerCapabilities};
+
 #[allow(unused_imports)]
 #[cfg(feature = "nightly")]
 pub use async_traits_impl::*;
 
 use super::AsyncWrapper;
 
-struct TimerSignal {
+/// The state shared between an `AsyncTimer` and the callback of its blocking timer.
+///
+/// `AsyncTimerService::timer` allocates it, while `AsyncTimerService::timer_static` uses one provided by the caller,
+/// typically placed in a `static`.
+pub struct TimerSignal {
     waker: AtomicWaker,
-    ticks: AtomicUsize,
+    // Negative when the tick of an expiry which could not be cancelled is still to come
+    ticks: AtomicIsize,
 }
 
 impl TimerSignal {
-    const fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             waker: AtomicWaker::new(),
-            ticks: AtomicUsize::new(0),
+            ticks: AtomicIsize::new(0),
         }
     }
 
     fn reset(&self) {
-        self.ticks.store(0, Ordering::SeqCst);
         self.waker.take();
     }
 
@@ -39,53 +49,224 @@ impl TimerSignal {
         self.waker.wake();
     }
 
+    fn clear(&self) {
+        self.ticks.store(0, Ordering::SeqCst);
+    }
+
+    fn discard_tick(&self) {
+        self.ticks.fetch_sub(1, Ordering::SeqCst);
+    }
+
     fn poll_wait(&self, cx: &Context<'_>) -> Poll<usize> {
+        self.poll_take(cx, usize::MAX)
+    }
+
+    /// Takes up to `max` ticks
+    fn poll_take(&self, cx: &Context<'_>, max: usize) -> Poll<usize> {
         self.waker.register(cx.waker());
 
-        let data = self.ticks.swap(0, Ordering::SeqCst);
+        let taken = self
+            .ticks
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |ticks| {
+                (ticks > 0).then(|| ticks - (ticks as usize).min(max) as isize)
+            });
 
-        if data > 0 {
-            Poll::Ready(data)
-        } else {
-            Poll::Pending
+        match taken {
+            Ok(ticks) => Poll::Ready((ticks as usize).min(max)),
+            Err(_) => Poll::Pending,
+        }
+    }
+}
+
+impl Default for TimerSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum SignalRef {
+    #[cfg(feature = "alloc")]
+    Shared(Arc<TimerSignal>),
+    Static(&'static TimerSignal),
+}
+
+impl Deref for SignalRef {
+    type Target = TimerSignal;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Shared(signal) => signal,
+            Self::Static(signal) => signal,
         }
     }
 }
 
 pub struct AsyncTimer<T> {
     timer: T,
-    signal: Arc<TimerSignal>,
+    signal: SignalRef,
+    capabilities: TimerCapabilities,
     duration: Option<Duration>,
+    armed: bool,
+    policy: MissedTickPolicy,
+}
+
+impl<T> AsyncTimer<T> {
+    fn wrap(timer: T, signal: SignalRef, capabilities: TimerCapabilities) -> Self {
+        Self {
+            timer,
+            signal,
+            capabilities,
+            duration: None,
+            armed: false,
+            policy: MissedTickPolicy::Burst,
+        }
+    }
+
+    /// The capabilities of the timer service the timer was created with
+    ///
+    /// Periodic durations are passed as-is to the timer service, so they should be checked against these beforehand.
+    pub fn capabilities(&self) -> TimerCapabilities {
+        self.capabilities
+    }
+
+    pub fn missed_tick_policy(&self) -> MissedTickPolicy {
+        self.policy
+    }
+
+    /// Sets the policy for the periodic ticks missed by `tick`/`next_tick`; `MissedTickPolicy::Burst` by default
+    pub fn set_missed_tick_policy(&mut self, policy: MissedTickPolicy) {
+        self.policy = policy;
+    }
 }
 
 impl<T> AsyncTimer<T>
 where
-    T: crate::timer::OnceTimer + Send,
+    T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
 {
+    /// Waits for `duration`; dropping the future cancels the timer.
+    ///
+    /// Durations above the maximum duration of the timer service are waited for in several steps.
     pub async fn after(&mut self, duration: Duration) -> Result<(), T::Error> {
-        self.timer.cancel()?;
+        let max_duration = self
+            .capabilities
+            .max_duration
+            .filter(|max_duration| !max_duration.is_zero());
 
-        self.signal.reset();
-        self.duration = None;
+        let mut remaining = duration;
+
+        loop {
+            let step = max_duration.map_or(remaining, |max_duration| remaining.min(max_duration));
+
+            self.cancel()?;
+
+            self.signal.reset();
+            self.duration = None;
 
-        TimerFuture(self, Some(duration)).await;
+            TimerFuture(self, Some(step)).await;
 
-        Ok(())
+            remaining -= step;
+
+            if remaining.is_zero() {
+                break Ok(());
+            }
+        }
     }
 
+    /// Waits until `clock` reaches `deadline`, re-checking the clock on expiry
+    pub async fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), T::Error>
+    where
+        C: SystemTime,
+    {
+        loop {
+            let now = clock.now();
+
+            if now >= deadline {
+                break Ok(());
+            }
+
+            self.after(deadline - now).await?;
+        }
+    }
+
+    /// Starts ticking every `duration`; the ticks are received with `tick` or `next_tick`
     pub fn every(&mut self, duration: Duration) -> Result<&'_ mut Self, T::Error> {
-        self.timer.cancel()?;
+        self.cancel()?;
 
         self.signal.reset();
         self.duration = Some(duration);
 
+        self.timer.every(duration)?;
+        self.armed = true;
+
         Ok(self)
     }
 
     pub async fn tick(&mut self) {
-        self.signal.reset();
+        self.next_tick().await.unwrap();
+    }
+
+    /// Waits for the next periodic tick, and returns the number of periods it accounts for:
+    /// 1, unless ticks were missed and coalesced as per the missed tick policy.
+    ///
+    /// Waits forever if `every` was not called.
+    pub async fn next_tick(&mut self) -> Result<usize, T::Error> {
+        TickFuture(self).await
+    }
+
+    /// Cancels the timer, returning whether a pending expiry was cancelled.
+    ///
+    /// If the expiry could not be cancelled because it is already happening, its tick is discarded
+    /// whenever it arrives, so that it does not complete a subsequent `after` or `tick` prematurely.
+    /// The tick of a periodic expiry in flight does not complete a subsequent `after` either,
+    /// but it might be received as the first tick of a subsequent `every`.
+    pub fn cancel(&mut self) -> Result<bool, T::Error> {
+        let cancelled = self.timer.cancel()?;
+
+        if self.armed {
+            if self.duration.is_some() {
+                // The ticks of the periodic timer which were not received yet are stale
+                self.signal.clear();
+            } else if !cancelled {
+                self.signal.discard_tick();
+            }
+        }
+
+        let cancelled = self.armed && cancelled;
+        self.armed = false;
+
+        Ok(cancelled)
+    }
+}
+
+struct TickFuture<'a, T>(&'a mut AsyncTimer<T>);
+
+impl<'a, T> Future for TickFuture<'a, T>
+where
+    T: crate::timer::PeriodicTimer,
+{
+    type Output = Result<usize, T::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let timer = &mut *self.0;
+
+        let max = match timer.policy {
+            MissedTickPolicy::Burst => 1,
+            _ => usize::MAX,
+        };
+
+        match timer.signal.poll_take(cx, max) {
+            Poll::Ready(ticks) => {
+                if ticks > 1 && timer.policy == MissedTickPolicy::Delay {
+                    if let Some(duration) = timer.duration {
+                        timer.timer.every(duration)?;
+                    }
+                }
 
-        TimerFuture(self, self.duration).await
+                Poll::Ready(Ok(ticks))
+            }
+            Poll::Pending => Poll::Pending,
+        }
     }
 }
 
@@ -98,7 +279,15 @@ where
     T: crate::timer::Timer,
 {
     fn drop(&mut self) {
-        self.0.timer.cancel().unwrap();
+        if self.0.armed {
+            let cancelled = self.0.timer.cancel().unwrap();
+
+            if !cancelled {
+                self.0.signal.discard_tick();
+            }
+
+            self.0.armed = false;
+        }
     }
 }
 
@@ -111,9 +300,18 @@ where
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         if let Some(duration) = self.1.take() {
             self.0.timer.after(duration).unwrap();
+            self.0.armed = true;
         }
 
         if self.0.signal.poll_wait(cx).is_ready() {
+            // A one-shot timer is no longer scheduled once it has expired, so a tick received while it still is
+            // is a stale one, e.g. of a periodic schedule which was cancelled while its expiry was in flight
+            if self.0.timer.is_scheduled().unwrap() {
+                return Poll::Pending;
+            }
+
+            self.0.armed = false;
+
             Poll::Ready(())
         } else {
             Poll::Pending
@@ -121,6 +319,60 @@ where
     }
 }
 
+/// An `AsyncTimer` carrying a value, created with `AsyncTimerService::timer_with`
+pub struct AsyncTimerWith<T, D> {
+    timer: AsyncTimer<T>,
+    data: D,
+}
+
+impl<T, D> AsyncTimerWith<T, D> {
+    pub fn data(&self) -> &D {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut D {
+        &mut self.data
+    }
+
+    pub fn timer(&mut self) -> &mut AsyncTimer<T> {
+        &mut self.timer
+    }
+
+    pub fn release(self) -> (AsyncTimer<T>, D) {
+        (self.timer, self.data)
+    }
+}
+
+impl<T, D> AsyncTimerWith<T, D>
+where
+    T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
+{
+    /// Waits for `duration`, and returns the carried value
+    pub async fn after(&mut self, duration: Duration) -> Result<&mut D, T::Error> {
+        self.timer.after(duration).await?;
+
+        Ok(&mut self.data)
+    }
+
+    /// Starts ticking every `duration`; the ticks are received with `next_tick`
+    pub fn every(&mut self, duration: Duration) -> Result<&'_ mut Self, T::Error> {
+        self.timer.every(duration)?;
+
+        Ok(self)
+    }
+
+    /// Waits for the next periodic tick, and returns the number of periods it accounts for, along with the carried value
+    pub async fn next_tick(&mut self) -> Result<(usize, &mut D), T::Error> {
+        let ticks = self.timer.next_tick().await?;
+
+        Ok((ticks, &mut self.data))
+    }
+
+    pub fn cancel(&mut self) -> Result<bool, T::Error> {
+        self.timer.cancel()
+    }
+}
+
 pub struct AsyncTimerService<T>(T);
 
 impl<T> AsyncTimerService<T> {
@@ -143,23 +395,49 @@ where
     T: crate::timer::TimerService,
     for<'a> T::Timer<'a>: Send,
 {
+    #[cfg(feature = "alloc")]
     pub fn timer(&self) -> Result<AsyncTimer<T::Timer<'_>>, T::Error> {
         let signal = Arc::new(TimerSignal::new());
 
-        let timer = {
-            let signal = Arc::downgrade(&signal);
+        let timer = self.0.timer_with(Arc::downgrade(&signal), |signal| {
+            if let Some(signal) = signal.upgrade() {
+                signal.tick();
+            }
+        })?;
 
-            self.0.timer(move || {
-                if let Some(signal) = signal.upgrade() {
-                    signal.tick();
-                }
-            })?
-        };
+        Ok(AsyncTimer::wrap(
+            timer,
+            SignalRef::Shared(signal),
+            self.0.capabilities(),
+        ))
+    }
+
+    /// Creates a timer using `signal` for its state, so that neither creating nor scheduling it allocates.
+    ///
+    /// `signal` must not be used by another timer at the same time.
+    pub fn timer_static(
+        &self,
+        signal: &'static TimerSignal,
+    ) -> Result<AsyncTimer<T::Timer<'_>>, T::Error> {
+        // Ticks left over by a previous timer using the same signal
+        signal.clear();
+        signal.reset();
 
-        Ok(AsyncTimer {
+        let timer = self.0.timer_with(signal, |signal| signal.tick())?;
+
+        Ok(AsyncTimer::wrap(
             timer,
-            signal,
-            duration: None,
+            SignalRef::Static(signal),
+            self.0.capabilities(),
+        ))
+    }
+
+    /// Creates a timer carrying `data`, which its futures return on completion
+    #[cfg(feature = "alloc")]
+    pub fn timer_with<D>(&self, data: D) -> Result<AsyncTimerWith<T::Timer<'_>, D>, T::Error> {
+        Ok(AsyncTimerWith {
+            timer: self.timer()?,
+            data,
         })
     }
 }
@@ -175,9 +453,10 @@ mod async_traits_impl {
     use core::result::Result;
     use core::time::Duration;
 
-    extern crate alloc;
-
-    use crate::timer::asynch::{Clock, ErrorType, OnceTimer, PeriodicTimer, TimerService};
+    use crate::sys_time::SystemTime;
+    use crate::timer::asynch::{Clock, ErrorType, OnceTimer, PeriodicTimer};
+    #[cfg(feature = "alloc")]
+    use crate::timer::asynch::{TimerCapabilities, TimerService};
 
     use super::{AsyncTimer, AsyncTimerService};
 
@@ -190,16 +469,23 @@ mod async_traits_impl {
 
     impl<T> OnceTimer for AsyncTimer<T>
     where
-        T: crate::timer::OnceTimer + Send,
+        T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
     {
         async fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
             AsyncTimer::after(self, duration).await
         }
+
+        async fn at<C>(&mut self, clock: &C, deadline: Duration) -> Result<(), Self::Error>
+        where
+            C: SystemTime,
+        {
+            AsyncTimer::at(self, clock, deadline).await
+        }
     }
 
     impl<T> PeriodicTimer for AsyncTimer<T>
     where
-        T: crate::timer::OnceTimer + Send,
+        T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
     {
         type Clock<'a> = &'a mut Self where Self: 'a;
 
@@ -210,7 +496,7 @@ mod async_traits_impl {
 
     impl<'a, T> Clock for &'a mut AsyncTimer<T>
     where
-        T: crate::timer::OnceTimer + Send,
+        T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
     {
         async fn tick(&mut self) {
             AsyncTimer::tick(self).await
@@ -224,6 +510,7 @@ mod async_traits_impl {
         type Error = T::Error;
     }
 
+    #[cfg(feature = "alloc")]
     impl<T> TimerService for AsyncTimerService<T>
     where
         T: crate::timer::TimerService,
@@ -234,11 +521,15 @@ mod async_traits_impl {
         async fn timer(&self) -> Result<Self::Timer<'_>, Self::Error> {
             AsyncTimerService::timer(self)
         }
+
+        fn capabilities(&self) -> TimerCapabilities {
+            crate::timer::TimerService::capabilities(&self.0)
+        }
     }
 
     impl<T> embedded_hal_async::delay::DelayUs for AsyncTimer<T>
     where
-        T: crate::timer::OnceTimer + Send,
+        T: crate::timer::OnceTimer + crate::timer::PeriodicTimer + Send,
     {
         async fn delay_us(&mut self, us: u32) {
             AsyncTimer::after(self, Duration::from_micros(us as _))
@@ -253,3 +544,375 @@ mod async_traits_impl {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Waker;
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::test_timer::MockTimerService;
+
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn after() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS * 9);
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS);
+            assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+            assert!(poll(after.as_mut(), &waker).is_ready());
+        }
+
+        assert_eq!(service.scheduled(), 0);
+    }
+
+    #[test]
+    fn after_dropped() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+
+            assert!(poll(after.as_mut(), &waker).is_pending());
+            assert_eq!(service.scheduled(), 1);
+        }
+
+        assert_eq!(service.scheduled(), 0);
+
+        service.advance(MS * 10);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn after_rescheduled_with_expiry_in_flight() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            // The expiry can no longer be cancelled when the wait is dropped
+            service.expire(MS * 10);
+        }
+
+        service.run();
+
+        let mut after = pin!(timer.after(MS * 10));
+
+        // The tick of the previous expiry does not complete the new wait
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.advance(MS * 10);
+        assert!(poll(after.as_mut(), &waker).is_ready());
+    }
+
+    #[test]
+    fn cancel() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        assert!(!timer.cancel().unwrap());
+
+        timer.every(MS * 10).unwrap();
+        assert!(timer.cancel().unwrap());
+        assert!(!timer.cancel().unwrap());
+
+        service.advance(MS * 100);
+
+        let mut tick = pin!(timer.next_tick());
+        assert!(poll(tick.as_mut(), &waker).is_pending());
+    }
+
+    fn ticks(policy: MissedTickPolicy, elapsed: Duration) -> (Vec<usize>, Duration) {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+        timer.set_missed_tick_policy(policy);
+        timer.every(MS * 10).unwrap();
+
+        service.advance(elapsed);
+
+        let mut ticks = Vec::new();
+
+        loop {
+            let mut tick = pin!(timer.next_tick());
+
+            match poll(tick.as_mut(), &waker) {
+                Poll::Ready(received) => ticks.push(received.unwrap()),
+                Poll::Pending => break,
+            }
+        }
+
+        // How long until the next tick
+        let mut next = MS;
+
+        loop {
+            service.advance(MS);
+
+            if poll(pin!(timer.next_tick()), &waker).is_ready() {
+                break (ticks, next);
+            }
+
+            next += MS;
+        }
+    }
+
+    #[test]
+    fn missed_ticks_burst() {
+        let (ticks, next) = ticks(MissedTickPolicy::Burst, MS * 35);
+
+        assert_eq!(ticks, [1, 1, 1]);
+        assert_eq!(next, MS * 5);
+    }
+
+    #[test]
+    fn missed_ticks_skip() {
+        let (ticks, next) = ticks(MissedTickPolicy::Skip, MS * 35);
+
+        assert_eq!(ticks, [3]);
+        assert_eq!(next, MS * 5);
+    }
+
+    #[test]
+    fn missed_ticks_delay() {
+        let (ticks, next) = ticks(MissedTickPolicy::Delay, MS * 35);
+
+        assert_eq!(ticks, [3]);
+        assert_eq!(next, MS * 10);
+    }
+
+    #[test]
+    fn missed_ticks_none() {
+        for policy in [
+            MissedTickPolicy::Burst,
+            MissedTickPolicy::Skip,
+            MissedTickPolicy::Delay,
+        ] {
+            let (ticks, next) = ticks(policy, MS * 15);
+
+            assert_eq!(ticks, [1]);
+            assert_eq!(next, MS * 5);
+        }
+    }
+
+    #[test]
+    fn after_with_periodic_tick_in_flight() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        // The stale tick arrives before the wait cancels the periodic schedule
+        timer.every(MS * 10).unwrap();
+        service.expire(MS * 10);
+        service.run();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS * 10);
+            assert!(poll(after.as_mut(), &waker).is_ready());
+        }
+
+        // The stale tick arrives once the wait has cancelled the periodic schedule, and re-armed the timer
+        timer.every(MS * 10).unwrap();
+        service.expire(MS * 10);
+
+        let mut after = pin!(timer.after(MS * 10));
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.run();
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.advance(MS * 9);
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.advance(MS);
+        assert!(poll(after.as_mut(), &waker).is_ready());
+    }
+
+    #[test]
+    fn at_with_clock_stepped_back() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let clock = MockTimerService::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+
+        {
+            let mut at = pin!(timer.at(&clock, MS * 100));
+            assert!(poll(at.as_mut(), &waker).is_pending());
+
+            // The clock is found behind the deadline on expiry, so the remaining time is waited for
+            service.advance(MS * 100);
+            clock.set_now(MS * 60);
+            assert!(poll(at.as_mut(), &waker).is_pending());
+
+            service.advance(MS * 39);
+            clock.set_now(MS * 99);
+            assert!(poll(at.as_mut(), &waker).is_pending());
+
+            service.advance(MS);
+            clock.set_now(MS * 100);
+            assert!(poll(at.as_mut(), &waker).is_ready());
+        }
+
+        // A deadline which has already passed completes without arming the timer
+        let mut at = pin!(timer.at(&clock, MS * 50));
+        assert!(poll(at.as_mut(), &waker).is_ready());
+        assert_eq!(service.scheduled(), 0);
+    }
+
+    #[test]
+    fn timer_with() {
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer_with(0_u32).unwrap();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS * 10);
+
+            match poll(after.as_mut(), &waker) {
+                Poll::Ready(data) => *data.unwrap() += 1,
+                Poll::Pending => panic!("not expired"),
+            }
+        }
+
+        timer.every(MS * 10).unwrap();
+        service.advance(MS * 10);
+
+        match poll(pin!(timer.next_tick()), &waker) {
+            Poll::Ready(tick) => {
+                let (ticks, data) = tick.unwrap();
+
+                assert_eq!(ticks, 1);
+                *data += 1;
+            }
+            Poll::Pending => panic!("no tick"),
+        }
+
+        assert_eq!(*timer.data(), 2);
+        assert!(timer.cancel().unwrap());
+
+        let (_, data) = timer.release();
+        assert_eq!(data, 2);
+    }
+
+    #[test]
+    fn timer_static_reused() {
+        static SIGNAL: TimerSignal = TimerSignal::new();
+
+        let service = MockTimerService::new();
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        {
+            let mut timer = async_service.timer_static(&SIGNAL).unwrap();
+
+            timer.every(MS * 10).unwrap();
+            service.advance(MS * 20);
+        }
+
+        // The ticks left over by the previous timer are not received by the next one
+        let mut timer = async_service.timer_static(&SIGNAL).unwrap();
+
+        timer.every(MS * 10).unwrap();
+        assert!(poll(pin!(timer.next_tick()), &waker).is_pending());
+
+        service.advance(MS * 10);
+        assert!(poll(pin!(timer.next_tick()), &waker).is_ready());
+    }
+
+    #[test]
+    fn after_above_max_duration() {
+        let capabilities = TimerCapabilities {
+            max_duration: Some(MS * 100),
+            ..TimerCapabilities::UNKNOWN
+        };
+
+        let service = MockTimerService::new().with_capabilities(capabilities);
+        let async_service = AsyncTimerService::new(service.clone());
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut timer = async_service.timer().unwrap();
+        assert_eq!(timer.capabilities(), capabilities);
+
+        let mut after = pin!(timer.after(MS * 250));
+
+        // Waited for in steps of at most the maximum duration
+        for _ in 0..2 {
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS * 99);
+            assert!(poll(after.as_mut(), &waker).is_pending());
+
+            service.advance(MS);
+        }
+
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.advance(MS * 49);
+        assert!(poll(after.as_mut(), &waker).is_pending());
+
+        service.advance(MS);
+        assert!(poll(after.as_mut(), &waker).is_ready());
+    }
+}
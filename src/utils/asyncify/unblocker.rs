@@ -0,0 +1,854 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+
+use crate::utils::mutex::{Condvar, Mutex, RawCondvar, RawMutex};
+#[cfg(feature = "std")]
+use crate::utils::mutex::{StdRawCondvar, StdRawMutex};
+
+use super::oneshot::{self, Receiver};
+
+/// The error returned by `TryUnblocker::try_unblock` when the computation was rejected
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UnblockError {
+    /// The job queue of the unblocker is full
+    QueueFull,
+    /// The unblocker was shut down before the computation could run
+    Shutdown,
+    /// The computation panicked
+    Panicked,
+}
+
+impl fmt::Display for UnblockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::QueueFull => write!(f, "Unblocker queue full"),
+            Self::Shutdown => write!(f, "Unblocker shut down"),
+            Self::Panicked => write!(f, "Unblocked computation panicked"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnblockError {}
+
+/// The priority of a computation, for unblockers which support it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// Like `Unblocker`, but for unblockers which can reject a computation, rather than queueing it unboundedly
+///
+/// The computations have to be `'static`, as they might outlive the returned future.
+///
+/// This is also why the unblockers running their computations on other threads, like `ThreadPoolUnblocker`,
+/// do not implement `Unblocker`, whose computations may borrow for the lifetime of the returned future:
+/// the future can be leaked (`core::mem::forget` is safe), and the computation would then run on the other thread
+/// after the borrows ended. Waiting for the computation when the future is dropped does not prevent that.
+pub trait TryUnblocker {
+    type TryUnblockFuture<F, T>: Future<Output = Result<T, UnblockError>> + Send
+    where
+        F: Send + 'static,
+        T: Send + 'static;
+
+    fn try_unblock<F, T>(&self, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static;
+
+    /// Like `try_unblock`, but with a priority hint; unblockers which do not support priorities ignore it
+    fn try_unblock_prio<F, T>(&self, priority: Priority, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let _ = priority;
+
+        self.try_unblock(f)
+    }
+}
+
+impl<U> TryUnblocker for &U
+where
+    U: TryUnblocker,
+{
+    type TryUnblockFuture<F, T> = U::TryUnblockFuture<F, T> where F: Send + 'static, T: Send + 'static;
+
+    fn try_unblock<F, T>(&self, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        (*self).try_unblock(f)
+    }
+
+    fn try_unblock_prio<F, T>(&self, priority: Priority, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        (*self).try_unblock_prio(priority, f)
+    }
+}
+
+impl<U> TryUnblocker for &mut U
+where
+    U: TryUnblocker,
+{
+    type TryUnblockFuture<F, T> = U::TryUnblockFuture<F, T> where F: Send + 'static, T: Send + 'static;
+
+    fn try_unblock<F, T>(&self, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        (**self).try_unblock(f)
+    }
+
+    fn try_unblock_prio<F, T>(&self, priority: Priority, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        (**self).try_unblock_prio(priority, f)
+    }
+}
+
+/// Marks a `TryUnblocker` to be used by the asyncified wrappers (e.g. the MQTT `AsyncClient`),
+/// which then report its rejections through their own error types
+///
+/// The computations of the wrapper are unblocked with the given priority.
+#[derive(Clone, Debug)]
+pub struct Bounded<U>(pub U, pub Priority);
+
+impl<U> Bounded<U> {
+    /// Wraps `unblocker`, with the computations of the wrapper unblocked with `Priority::Normal`
+    pub const fn new(unblocker: U) -> Self {
+        Self(unblocker, Priority::Normal)
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A queued computation is promoted once this many computations of higher priorities were run ahead of it
+const PROMOTE_AFTER: usize = 4;
+
+struct UnblockerState<const N: usize> {
+    // Indexed by `Priority`
+    jobs: [heapless::Deque<Job, N>; 3],
+    passed_over: [usize; 3],
+    finishing: bool,
+    shutdown: bool,
+}
+
+impl<const N: usize> UnblockerState<N> {
+    fn next_job(&mut self) -> Option<Job> {
+        let jobs = &self.jobs;
+        let passed_over = &self.passed_over;
+
+        let index = (0..jobs.len())
+            .find(|&index| !jobs[index].is_empty() && passed_over[index] >= PROMOTE_AFTER)
+            .or_else(|| (0..jobs.len()).find(|&index| !jobs[index].is_empty()))?;
+
+        self.passed_over[index] = 0;
+
+        for lower in index + 1..self.jobs.len() {
+            if !self.jobs[lower].is_empty() {
+                self.passed_over[lower] += 1;
+            }
+        }
+
+        self.jobs[index].pop_front()
+    }
+}
+
+/// A `TryUnblocker` queueing up to `N` computations of each `Priority`, which are run by the worker(s) calling `run`
+///
+/// When the queue is full, `try_unblock` fails with `UnblockError::QueueFull` instead of queueing the computation.
+/// `shutdown` stops the workers once they are done with their current computation, and fails the
+/// computations still queued with `UnblockError::Shutdown`, while `finish` lets the workers complete
+/// the queued computations first. With the `std` feature, a computation which panics fails with
+/// `UnblockError::Panicked`, and the worker running it carries on.
+///
+/// The computations of higher priorities are run first, those of the same priority in FIFO order.
+/// A computation of a lower priority is however not starved: once 4 computations of higher priorities
+/// were run ahead of it, it is run next (after those of other priorities promoted likewise).
+/// `try_unblock` queues computations with `Priority::Normal`.
+pub struct BoundedUnblocker<CV, const N: usize>
+where
+    CV: RawCondvar,
+{
+    state: Mutex<CV::RawMutex, UnblockerState<N>>,
+    state_changed: Condvar<CV>,
+}
+
+impl<CV, const N: usize> BoundedUnblocker<CV, N>
+where
+    CV: RawCondvar,
+{
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(UnblockerState {
+                jobs: [
+                    heapless::Deque::new(),
+                    heapless::Deque::new(),
+                    heapless::Deque::new(),
+                ],
+                passed_over: [0; 3],
+                finishing: false,
+                shutdown: false,
+            }),
+            state_changed: Condvar::new(),
+        }
+    }
+
+    /// Runs the queued computations until the unblocker is shut down, or finished and out of computations
+    ///
+    /// Meant to be called by one or more worker threads.
+    pub fn run(&self) {
+        let mut state = self.state.lock();
+
+        loop {
+            if state.shutdown {
+                break;
+            }
+
+            if let Some(job) = state.next_job() {
+                drop(state);
+
+                job();
+
+                state = self.state.lock();
+            } else if state.finishing {
+                break;
+            } else {
+                state = self.state_changed.wait(state);
+            }
+        }
+    }
+
+    /// Shuts the unblocker down: the workers return from `run` once done with their current computation,
+    /// and the queued and any future computations fail with `UnblockError::Shutdown`
+    pub fn shutdown(&self) {
+        let mut state = self.state.lock();
+
+        state.shutdown = true;
+
+        // Dropping the jobs drops their senders, which completes their futures
+        for jobs in &mut state.jobs {
+            while jobs.pop_front().is_some() {}
+        }
+
+        self.state_changed.notify_all();
+    }
+
+    /// Finishes the unblocker: any future computations fail with `UnblockError::Shutdown`,
+    /// and the workers return from `run` once the queued computations are done
+    pub fn finish(&self) {
+        let mut state = self.state.lock();
+
+        state.finishing = true;
+
+        self.state_changed.notify_all();
+    }
+
+    /// Returns `true` if the unblocker was shut down or finished, so that it no longer accepts computations
+    pub fn is_shutdown(&self) -> bool {
+        let state = self.state.lock();
+
+        state.shutdown || state.finishing
+    }
+
+    /// The number of computations waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.state
+            .lock()
+            .jobs
+            .iter()
+            .map(heapless::Deque::len)
+            .sum()
+    }
+
+    pub fn try_unblock<F, T>(&self, f: F) -> TryUnblockFuture<CV::RawMutex, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+    {
+        self.try_unblock_prio(Priority::Normal, f)
+    }
+
+    pub fn try_unblock_prio<F, T>(
+        &self,
+        priority: Priority,
+        f: F,
+    ) -> TryUnblockFuture<CV::RawMutex, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+        CV::RawMutex: Send + Sync + 'static,
+    {
+        let mut state = self.state.lock();
+
+        if state.shutdown || state.finishing {
+            return TryUnblockFuture::rejected(UnblockError::Shutdown);
+        }
+
+        let jobs = &mut state.jobs[priority as usize];
+
+        if jobs.is_full() {
+            return TryUnblockFuture::rejected(UnblockError::QueueFull);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+
+        let job: Job = Box::new(move || {
+            let _ = sender.send(run_contained(f));
+        });
+
+        if jobs.push_back(job).is_err() {
+            unreachable!();
+        }
+
+        self.state_changed.notify_one();
+
+        TryUnblockFuture(TryUnblockState::Queued(receiver))
+    }
+}
+
+#[cfg(feature = "std")]
+fn run_contained<F, T>(f: F) -> Result<T, UnblockError>
+where
+    F: FnOnce() -> T,
+{
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|_| UnblockError::Panicked)
+}
+
+#[cfg(not(feature = "std"))]
+fn run_contained<F, T>(f: F) -> Result<T, UnblockError>
+where
+    F: FnOnce() -> T,
+{
+    Ok(f())
+}
+
+impl<CV, const N: usize> Default for BoundedUnblocker<CV, N>
+where
+    CV: RawCondvar,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<CV, const N: usize> TryUnblocker for BoundedUnblocker<CV, N>
+where
+    CV: RawCondvar,
+    CV::RawMutex: Send + Sync + 'static,
+{
+    type TryUnblockFuture<F, T> = TryUnblockFuture<CV::RawMutex, T> where F: Send + 'static, T: Send + 'static;
+
+    fn try_unblock<F, T>(&self, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        BoundedUnblocker::try_unblock(self, f)
+    }
+
+    fn try_unblock_prio<F, T>(&self, priority: Priority, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        BoundedUnblocker::try_unblock_prio(self, priority, f)
+    }
+}
+
+enum TryUnblockState<R, T>
+where
+    R: RawMutex + 'static,
+    T: 'static,
+{
+    Rejected(Option<UnblockError>),
+    Queued(Receiver<'static, R, Result<T, UnblockError>>),
+}
+
+/// The future returned by `BoundedUnblocker::try_unblock` and `ThreadPoolUnblocker::try_unblock`
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TryUnblockFuture<R, T>(TryUnblockState<R, T>)
+where
+    R: RawMutex + 'static,
+    T: 'static;
+
+impl<R, T> TryUnblockFuture<R, T>
+where
+    R: RawMutex + 'static,
+    T: 'static,
+{
+    fn rejected(error: UnblockError) -> Self {
+        Self(TryUnblockState::Rejected(Some(error)))
+    }
+}
+
+impl<R, T> Future for TryUnblockFuture<R, T>
+where
+    R: RawMutex + 'static,
+    T: 'static,
+{
+    type Output = Result<T, UnblockError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.get_mut().0 {
+            TryUnblockState::Rejected(error) => Poll::Ready(Err(error
+                .take()
+                .expect("TryUnblockFuture polled after completion"))),
+            TryUnblockState::Queued(receiver) => receiver
+                .poll_recv(cx)
+                .map(|result| result.unwrap_or(Err(UnblockError::Shutdown))),
+        }
+    }
+}
+
+/// A `TryUnblocker` running its computations on a pool of STD threads, with up to `N` queued computations
+/// of each `Priority`
+///
+/// This is a `BoundedUnblocker` with its own worker threads. Dropping the pool finishes it:
+/// the queued computations are completed, and then the worker threads are joined.
+///
+/// The pool only implements `TryUnblocker`, and not `Unblocker`, as explained with `TryUnblocker`.
+#[cfg(feature = "std")]
+pub struct ThreadPoolUnblocker<const N: usize> {
+    unblocker: Arc<BoundedUnblocker<StdRawCondvar, N>>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "std")]
+impl<const N: usize> ThreadPoolUnblocker<N> {
+    /// Starts a pool of `workers` threads
+    pub fn new(workers: usize) -> std::io::Result<Self> {
+        let mut pool = Self {
+            unblocker: Arc::new(BoundedUnblocker::new()),
+            workers: Vec::with_capacity(workers),
+        };
+
+        for index in 0..workers {
+            let unblocker = pool.unblocker.clone();
+
+            let worker = std::thread::Builder::new()
+                .name(format!("unblocker-{index}"))
+                .spawn(move || unblocker.run())?;
+
+            pool.workers.push(worker);
+        }
+
+        Ok(pool)
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// The number of computations waiting for a worker thread
+    pub fn queued(&self) -> usize {
+        self.unblocker.queued()
+    }
+
+    /// Shuts the pool down without waiting for the queued computations, which fail with `UnblockError::Shutdown`
+    pub fn shutdown(&self) {
+        self.unblocker.shutdown();
+    }
+
+    pub fn try_unblock<F, T>(&self, f: F) -> TryUnblockFuture<StdRawMutex, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.unblocker.try_unblock(f)
+    }
+
+    pub fn try_unblock_prio<F, T>(
+        &self,
+        priority: Priority,
+        f: F,
+    ) -> TryUnblockFuture<StdRawMutex, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.unblocker.try_unblock_prio(priority, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const N: usize> Drop for ThreadPoolUnblocker<N> {
+    fn drop(&mut self) {
+        self.unblocker.finish();
+
+        let current = std::thread::current().id();
+
+        for worker in self.workers.drain(..) {
+            // A worker dropping the pool cannot join itself
+            if worker.thread().id() != current {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const N: usize> TryUnblocker for ThreadPoolUnblocker<N> {
+    type TryUnblockFuture<F, T> = TryUnblockFuture<StdRawMutex, T> where F: Send + 'static, T: Send + 'static;
+
+    fn try_unblock<F, T>(&self, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        ThreadPoolUnblocker::try_unblock(self, f)
+    }
+
+    fn try_unblock_prio<F, T>(&self, priority: Priority, f: F) -> Self::TryUnblockFuture<F, T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        ThreadPoolUnblocker::try_unblock_prio(self, priority, f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Waker;
+
+    use std::sync::Mutex as StdMutex;
+    use std::task::Wake;
+
+    use crate::utils::asyncify::block_on;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    type Unblocker<const N: usize> = BoundedUnblocker<StdRawCondvar, N>;
+
+    #[test]
+    fn queue_full() {
+        let unblocker = Unblocker::<2>::new();
+
+        let first = unblocker.try_unblock(|| 1);
+        let second = unblocker.try_unblock(|| 2);
+
+        assert_eq!(
+            block_on(unblocker.try_unblock(|| 3)),
+            Err(UnblockError::QueueFull)
+        );
+        assert_eq!(unblocker.queued(), 2);
+
+        // The queue of each priority is bounded on its own
+        let high = unblocker.try_unblock_prio(Priority::High, || 4);
+
+        unblocker.finish();
+        unblocker.run();
+
+        assert_eq!(block_on(first), Ok(1));
+        assert_eq!(block_on(second), Ok(2));
+        assert_eq!(block_on(high), Ok(4));
+    }
+
+    #[test]
+    fn shutdown() {
+        let unblocker = Unblocker::<4>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let mut queued = Box::pin(unblocker.try_unblock(|| 1));
+        assert!(poll(queued.as_mut(), &waker).is_pending());
+
+        // The queued computations are dropped, which wakes their futures
+        unblocker.shutdown();
+        assert!(unblocker.is_shutdown());
+        assert_eq!(unblocker.queued(), 0);
+
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            poll(queued.as_mut(), &waker),
+            Poll::Ready(Err(UnblockError::Shutdown))
+        );
+
+        assert_eq!(
+            block_on(unblocker.try_unblock(|| 2)),
+            Err(UnblockError::Shutdown)
+        );
+
+        // The workers return right away
+        unblocker.run();
+    }
+
+    #[test]
+    fn finish() {
+        let unblocker = Unblocker::<4>::new();
+
+        let queued = unblocker.try_unblock(|| 1);
+
+        unblocker.finish();
+        assert!(unblocker.is_shutdown());
+
+        assert_eq!(
+            block_on(unblocker.try_unblock(|| 2)),
+            Err(UnblockError::Shutdown)
+        );
+
+        // The computations queued before are still run
+        unblocker.run();
+        assert_eq!(block_on(queued), Ok(1));
+    }
+
+    #[test]
+    fn contained_panic() {
+        let unblocker = Unblocker::<4>::new();
+
+        let panicking = unblocker.try_unblock(|| -> u32 { panic!("Panicking computation") });
+        let next = unblocker.try_unblock(|| 2);
+
+        unblocker.finish();
+        unblocker.run();
+
+        // The worker carries on with the next computation
+        assert_eq!(block_on(panicking), Err(UnblockError::Panicked));
+        assert_eq!(block_on(next), Ok(2));
+    }
+
+    #[test]
+    fn priorities() {
+        let unblocker = Unblocker::<8>::new();
+        let order = Arc::new(StdMutex::new(Vec::new()));
+
+        let mut futures = Vec::new();
+
+        let mut queue = |priority, name: &'static str| {
+            let order = order.clone();
+
+            futures.push(
+                unblocker.try_unblock_prio(priority, move || order.lock().unwrap().push(name)),
+            );
+        };
+
+        queue(Priority::Low, "L1");
+        queue(Priority::Normal, "N1");
+
+        for name in ["H1", "H2", "H3", "H4", "H5", "H6"] {
+            queue(Priority::High, name);
+        }
+
+        queue(Priority::Normal, "N2");
+
+        unblocker.finish();
+        unblocker.run();
+
+        for future in futures {
+            block_on(future).unwrap();
+        }
+
+        // Once `PROMOTE_AFTER` computations of higher priorities were run ahead of them,
+        // the lower priority ones are run next, in the order of their priorities
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["H1", "H2", "H3", "H4", "N1", "L1", "H5", "H6", "N2"]
+        );
+    }
+
+    /// Keeps the single worker of `pool` busy until the returned sender is dropped or sent to
+    fn occupy<const N: usize>(
+        pool: &ThreadPoolUnblocker<N>,
+    ) -> (
+        std::sync::mpsc::Sender<()>,
+        TryUnblockFuture<StdRawMutex, ()>,
+    ) {
+        let (gate, gated) = std::sync::mpsc::channel::<()>();
+        let (started, starting) = std::sync::mpsc::channel::<()>();
+
+        let occupied = pool.try_unblock(move || {
+            started.send(()).unwrap();
+
+            let _ = gated.recv();
+        });
+
+        starting.recv().unwrap();
+
+        (gate, occupied)
+    }
+
+    #[test]
+    fn pool() {
+        let pool = ThreadPoolUnblocker::<4>::new(2).unwrap();
+        assert_eq!(pool.workers(), 2);
+
+        let name = block_on(pool.try_unblock(|| std::thread::current().name().map(String::from)));
+        assert!(name.unwrap().unwrap().starts_with("unblocker-"));
+
+        // Through the trait, and with the two workers running at the same time
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        let futures = (0..2)
+            .map(|_| {
+                let barrier = barrier.clone();
+
+                TryUnblocker::try_unblock(&pool, move || barrier.wait().is_leader())
+            })
+            .collect::<Vec<_>>();
+
+        let leaders = futures
+            .into_iter()
+            .map(|future| block_on(future).unwrap())
+            .filter(|leader| *leader)
+            .count();
+
+        assert_eq!(leaders, 1);
+    }
+
+    #[test]
+    fn pool_contained_panic() {
+        let pool = ThreadPoolUnblocker::<4>::new(1).unwrap();
+
+        assert_eq!(
+            block_on(pool.try_unblock(|| -> u32 { panic!("Panicking computation") })),
+            Err(UnblockError::Panicked)
+        );
+
+        // The single worker survived the panic
+        assert_eq!(block_on(pool.try_unblock(|| 2)), Ok(2));
+    }
+
+    #[test]
+    fn pool_drop() {
+        let pool = ThreadPoolUnblocker::<4>::new(1).unwrap();
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let (gate, occupied) = occupy(&pool);
+
+        let futures = (0..4)
+            .map(|_| {
+                let done = done.clone();
+
+                pool.try_unblock(move || done.fetch_add(1, Ordering::SeqCst))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(pool.queued(), 4);
+
+        drop(gate);
+
+        // Dropping the pool completes the queued computations before joining the worker
+        drop(pool);
+        assert_eq!(done.load(Ordering::SeqCst), 4);
+
+        assert_eq!(block_on(occupied), Ok(()));
+
+        for future in futures {
+            assert!(block_on(future).is_ok());
+        }
+    }
+
+    #[test]
+    fn pool_shutdown() {
+        let pool = ThreadPoolUnblocker::<4>::new(1).unwrap();
+
+        let (gate, occupied) = occupy(&pool);
+
+        let queued = pool.try_unblock(|| 1);
+
+        pool.shutdown();
+
+        assert_eq!(block_on(queued), Err(UnblockError::Shutdown));
+        assert_eq!(
+            block_on(pool.try_unblock(|| 2)),
+            Err(UnblockError::Shutdown)
+        );
+
+        // The computation running is not interrupted
+        gate.send(()).unwrap();
+        assert_eq!(block_on(occupied), Ok(()));
+    }
+
+    #[test]
+    fn pool_promotion() {
+        let pool = ThreadPoolUnblocker::<8>::new(1).unwrap();
+        let order = Arc::new(StdMutex::new(Vec::new()));
+
+        let (gate, occupied) = occupy(&pool);
+
+        let mut futures = Vec::new();
+
+        let mut queue = |priority, name: &'static str| {
+            let order = order.clone();
+
+            futures.push(pool.try_unblock_prio(priority, move || order.lock().unwrap().push(name)));
+        };
+
+        queue(Priority::Low, "L1");
+
+        for name in ["H1", "H2", "H3", "H4", "H5"] {
+            queue(Priority::High, name);
+        }
+
+        drop(gate);
+
+        block_on(occupied).unwrap();
+
+        for future in futures {
+            block_on(future).unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), ["H1", "H2", "H3", "H4", "L1", "H5"]);
+    }
+
+    #[test]
+    fn pool_dropped_by_worker() {
+        let pool = Arc::new(ThreadPoolUnblocker::<4>::new(1).unwrap());
+
+        let (gate, occupied) = occupy(&pool);
+
+        let last = {
+            let last_reference = pool.clone();
+
+            pool.try_unblock(move || drop(last_reference))
+        };
+
+        // The worker drops the last reference to the pool, and does not try to join itself
+        drop(pool);
+        drop(gate);
+
+        assert_eq!(block_on(occupied), Ok(()));
+        assert_eq!(block_on(last), Ok(()));
+    }
+}
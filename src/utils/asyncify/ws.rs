@@ -11,7 +11,7 @@ pub mod server {
 
     use heapless;
 
-    use crate::log::svc_log;
+    use crate::log::{debug, svc_log};
     use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
     use crate::ws::{callback_server::*, *};
 
@@ -39,7 +39,7 @@ pub mod server {
                 "Sending data (frame_type={:?}, frame_len={}) to WS connection {:?}",
                 frame_type,
                 frame_data.len(),
-                self.sender.session()
+                debug(&self.sender.session())
             );
 
             self.sender.send(frame_type, frame_data)
@@ -65,7 +65,7 @@ pub mod server {
                 "Sending data (frame_type={:?}, frame_len={}) to WS connection {:?}",
                 frame_type,
                 frame_data.len(),
-                self.sender.session()
+                debug(&self.sender.session())
             );
 
             let mut sender = self.sender.clone();
@@ -199,7 +199,7 @@ pub mod server {
         }
     }
 
-    impl<'a, U, C, S> Future for &'a AsyncAcceptor<U, C, S>
+    impl<U, C, S> Future for &AsyncAcceptor<U, C, S>
     where
         U: Clone,
         C: RawCondvar + Send + Sync,
@@ -292,7 +292,7 @@ pub mod server {
             if connection.is_new() {
                 let session = connection.session();
 
-                svc_log!(info, "New WS connection {:?}", session);
+                svc_log!(info, "New WS connection {:?}", debug(&session));
 
                 if !self.process_accept(session, connection) {
                     return connection.send(FrameType::Close, &[]);
@@ -309,7 +309,7 @@ pub mod server {
                     let conn = self.connections.swap_remove(index);
 
                     Self::process_receive_close(&conn.receiver_state);
-                    svc_log!(info, "Closed WS connection {:?}", session);
+                    svc_log!(info, "Closed WS connection {:?}", debug(&session));
                 }
             } else {
                 let session = connection.session();
@@ -320,7 +320,7 @@ pub mod server {
                     "Incoming data (frame_type={:?}, frame_len={}) from WS connection {:?}",
                     frame_type,
                     len,
-                    session
+                    debug(&session)
                 );
 
                 if let Some(connection) = self
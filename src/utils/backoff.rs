@@ -0,0 +1,295 @@
+//! Exponential backoff, for spacing the retries of reconnects and requests
+//!
+//! Each delay is the previous one multiplied by `Configuration::factor_percent`, starting at `Configuration::initial`
+//! and capped at `Configuration::max`. With a PRNG hook, the delays are also shortened by a random share
+//! of up to `Configuration::jitter_percent`, so that devices which lost their connection at the same time
+//! do not all retry at the same time. The hook keeps the sequence deterministic for a given seed.
+
+use core::fmt;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// The first delay
+    pub initial: Duration,
+    /// How much each delay grows over the previous one, in percent (e.g. 200 for doubling, 100 for a constant delay)
+    pub factor_percent: u32,
+    /// The cap of the delays
+    pub max: Duration,
+    /// The maximum share of a delay cut off randomly, in percent (0 to 100)
+    pub jitter_percent: u8,
+    /// The number of delays after which the backoff is exhausted, if any
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            factor_percent: 200,
+            max: Duration::from_secs(60),
+            jitter_percent: 0,
+            max_attempts: None,
+        }
+    }
+}
+
+/// The error returned by `Backoff::next_delay` once `Configuration::max_attempts` delays were returned
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Exhausted;
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Exhausted")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Exhausted {}
+
+/// A xorshift PRNG, usable as the jitter hook of a `Backoff`
+///
+/// The seed must not be 0, which is a fixed point.
+pub fn xorshift32(mut x: u32) -> u32 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+
+    x
+}
+
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    conf: Configuration,
+    current: Duration,
+    attempts: u32,
+    prng: Option<fn(u32) -> u32>,
+    state: u32,
+}
+
+impl Backoff {
+    pub fn new(conf: Configuration) -> Self {
+        Self {
+            current: conf.initial.min(conf.max),
+            conf,
+            attempts: 0,
+            prng: None,
+            state: 0,
+        }
+    }
+
+    /// Randomizes the delays with `prng`, which is called with `seed` first, and then with its previous output
+    pub fn with_jitter(mut self, prng: fn(u32) -> u32, seed: u32) -> Self {
+        self.prng = Some(prng);
+        self.state = seed;
+        self
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.conf
+    }
+
+    /// The number of delays returned since the creation or the last `reset`
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Returns the delay to wait for before the next attempt, or `Exhausted` if no attempt is left
+    pub fn next_delay(&mut self) -> Result<Duration, Exhausted> {
+        if self
+            .conf
+            .max_attempts
+            .map(|max_attempts| self.attempts >= max_attempts)
+            .unwrap_or(false)
+        {
+            return Err(Exhausted);
+        }
+
+        self.attempts += 1;
+
+        let delay = self.current;
+
+        self.current = delay
+            .checked_mul(self.conf.factor_percent)
+            .map(|next| next / 100)
+            .unwrap_or(self.conf.max)
+            .min(self.conf.max);
+
+        Ok(self.jittered(delay))
+    }
+
+    /// Starts over from the initial delay, typically after a successful attempt
+    pub fn reset(&mut self) {
+        self.current = self.conf.initial.min(self.conf.max);
+        self.attempts = 0;
+    }
+
+    fn jittered(&mut self, delay: Duration) -> Duration {
+        let Some(prng) = self.prng else {
+            return delay;
+        };
+
+        self.state = prng(self.state);
+
+        let max_jitter = delay.as_micros() * self.conf.jitter_percent.min(100) as u128 / 100;
+        let jitter = max_jitter * self.state as u128 / u32::MAX as u128;
+
+        delay - Duration::from_micros(jitter as u64)
+    }
+}
+
+/// Yields the delays until the backoff is exhausted
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_delay().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    fn conf(max_attempts: Option<u32>, jitter_percent: u8) -> Configuration {
+        Configuration {
+            max_attempts,
+            jitter_percent,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delays() {
+        let mut backoff = Backoff::new(conf(None, 0));
+
+        let delays: heapless::Vec<_, 9> = backoff.by_ref().take(9).collect();
+
+        // Doubling, up to the cap
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 16, 32, 60, 60, 60]
+                .iter()
+                .map(|secs| SEC * *secs)
+                .collect::<heapless::Vec<_, 9>>()
+        );
+        assert_eq!(backoff.attempts(), 9);
+
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert_eq!(backoff.next_delay(), Ok(SEC));
+
+        // Growing by half
+        let mut backoff = Backoff::new(Configuration {
+            initial: Duration::from_millis(100),
+            factor_percent: 150,
+            ..Default::default()
+        });
+
+        assert_eq!(backoff.next(), Some(Duration::from_millis(100)));
+        assert_eq!(backoff.next(), Some(Duration::from_millis(150)));
+        assert_eq!(backoff.next(), Some(Duration::from_millis(225)));
+
+        // Constant
+        let mut backoff = Backoff::new(Configuration {
+            factor_percent: 100,
+            ..Default::default()
+        });
+
+        assert!(backoff.by_ref().take(5).all(|delay| delay == SEC));
+    }
+
+    #[test]
+    fn capped() {
+        // The initial delay is capped too
+        let mut backoff = Backoff::new(Configuration {
+            initial: SEC * 120,
+            ..Default::default()
+        });
+
+        assert_eq!(backoff.next(), Some(SEC * 60));
+        assert_eq!(backoff.next(), Some(SEC * 60));
+
+        // Does not overflow
+        let mut backoff = Backoff::new(Configuration {
+            initial: Duration::MAX / 2,
+            factor_percent: u32::MAX,
+            max: Duration::MAX,
+            ..Default::default()
+        });
+
+        assert_eq!(backoff.next(), Some(Duration::MAX / 2));
+        assert_eq!(backoff.next(), Some(Duration::MAX));
+        assert_eq!(backoff.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn exhausted() {
+        let mut backoff = Backoff::new(conf(Some(3), 0));
+
+        assert_eq!(backoff.by_ref().count(), 3);
+        assert_eq!(backoff.next_delay(), Err(Exhausted));
+        assert_eq!(backoff.attempts(), 3);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Ok(SEC));
+
+        assert_eq!(Backoff::new(conf(Some(0), 0)).next_delay(), Err(Exhausted));
+    }
+
+    #[test]
+    fn jitter() {
+        let delays = |seed| -> heapless::Vec<Duration, 8> {
+            Backoff::new(conf(None, 50))
+                .with_jitter(xorshift32, seed)
+                .take(8)
+                .collect()
+        };
+
+        // The same for the same seed
+        assert_eq!(delays(1), delays(1));
+        assert_ne!(delays(1), delays(2));
+
+        // Shortened by up to half
+        let plain: heapless::Vec<Duration, 8> = Backoff::new(conf(None, 0)).take(8).collect();
+
+        for (jittered, plain) in delays(0xdead_beef).iter().zip(&plain) {
+            assert!(*jittered <= *plain);
+            assert!(*jittered >= *plain / 2);
+        }
+
+        // Cut off by the share of the PRNG output, and does not compound
+        assert_eq!(delays(1)[0], SEC - Duration::from_micros(31));
+        assert!(delays(1).iter().skip(6).all(|delay| *delay >= SEC * 30));
+
+        // No jitter without a PRNG, or with 0%
+        let mut backoff = Backoff::new(conf(None, 0)).with_jitter(xorshift32, 1);
+        assert_eq!(backoff.next(), Some(SEC));
+        assert_eq!(backoff.next(), Some(SEC * 2));
+    }
+
+    #[test]
+    fn prng() {
+        // The PRNG is called with the seed, and then with its previous output
+        let mut state = 1;
+        let mut outputs = [0; 4];
+
+        for output in &mut outputs {
+            state = xorshift32(state);
+            *output = state;
+        }
+
+        assert_eq!(outputs, [270369, 67634689, 2647435461, 307599695]);
+
+        // 0 is a fixed point
+        assert_eq!(xorshift32(0), 0);
+    }
+}
@@ -0,0 +1,1283 @@
+//! Helpers for captive portals, i.e. the provisioning pages served by a device in Wifi AP mode
+
+pub mod portal_http {
+    //! An HTTP server middleware which makes phones and laptops joining the AP pop their sign-in sheet
+    //!
+    //! Operating systems detect captive portals by probing well-known URLs (e.g. `/generate_204`),
+    //! and show their captive portal UI when the answer is not the one expected from the Internet.
+    //! `CaptivePortal` answers all of these probes, as well as any request for a host other than the portal,
+    //! with a 302 redirect to the portal, while passing the requests for the portal itself on to its handler.
+    //!
+    //! As there is no portable handler registry, the composed handler is expected to be registered
+    //! by the application for all `GET` requests, e.g. `CaptivePortal::new(..).compose(portal_handler)`.
+    //! This only works if the DNS queries of the clients are answered with the IP of the AP as well,
+    //! e.g. by `super::portal_dns`.
+
+    use crate::http::server::{Connection, Handler, HandlerResult, Middleware, Request};
+    use crate::http::{headers, Headers, Query};
+    use crate::ipv4::Ipv4Addr;
+
+    /// The paths probed for detecting captive portals, per operating system
+    pub const PROBE_PATHS: &[&str] = &[
+        // Android, ChromeOS
+        "/generate_204",
+        "/gen_204",
+        // iOS, macOS
+        "/hotspot-detect.html",
+        "/library/test/success.html",
+        // Windows
+        "/connecttest.txt",
+        "/ncsi.txt",
+        "/redirect",
+        // Firefox
+        "/canonical.html",
+        "/success.txt",
+    ];
+
+    /// The headers preventing the probes and redirects from being cached by the clients, once provisioning finished
+    pub const NO_CACHE_HEADERS: [(&str, &str); 3] = [
+        ("Cache-Control", "no-cache, no-store, must-revalidate"),
+        ("Pragma", "no-cache"),
+        ("Expires", "0"),
+    ];
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Options<'a> {
+        /// The URL the clients are redirected to, e.g. `http://192.168.71.1/`
+        pub portal_url: &'a str,
+        /// The IP of the device on the AP network, which is considered a portal host as well
+        pub ap_ip: Ipv4Addr,
+    }
+
+    /// Returns whether `uri` is one of the `PROBE_PATHS`, ignoring its query
+    pub fn is_probe(uri: &str) -> bool {
+        let path = uri.split('?').next().unwrap_or_default();
+
+        PROBE_PATHS
+            .iter()
+            .any(|probe| probe.eq_ignore_ascii_case(path))
+    }
+
+    pub struct CaptivePortal<'a> {
+        options: Options<'a>,
+    }
+
+    impl<'a> CaptivePortal<'a> {
+        pub const fn new(options: Options<'a>) -> Self {
+            Self { options }
+        }
+
+        pub fn options(&self) -> &Options<'a> {
+            &self.options
+        }
+
+        /// Returns whether `host` (the `Host` header of a request, possibly with a port) is the portal itself
+        pub fn is_portal_host(&self, host: &str) -> bool {
+            let host = strip_port(host);
+
+            host.eq_ignore_ascii_case(strip_port(url_host(self.options.portal_url)))
+                || host.parse::<Ipv4Addr>().ok() == Some(self.options.ap_ip)
+        }
+
+        /// Returns whether the request should be passed on to the portal handler, rather than redirected
+        ///
+        /// Requests without a `Host` header can only be for the device itself, so they are passed on as well.
+        pub fn is_pass_through<Q>(&self, request: &Q) -> bool
+        where
+            Q: Query + Headers,
+        {
+            !is_probe(request.uri())
+                && request
+                    .host()
+                    .map(|host| self.is_portal_host(host))
+                    .unwrap_or(true)
+        }
+
+        fn redirect<C>(&self, connection: &mut C) -> HandlerResult
+        where
+            C: Connection,
+        {
+            Request::wrap(connection).into_response(
+                302,
+                Some("Found"),
+                &redirect_headers(self.options.portal_url),
+            )?;
+
+            Ok(())
+        }
+    }
+
+    impl<'a, C> Middleware<C> for CaptivePortal<'a>
+    where
+        C: Connection,
+    {
+        fn handle<H>(&self, connection: &mut C, handler: &H) -> HandlerResult
+        where
+            H: Handler<C>,
+        {
+            if self.is_pass_through(connection) {
+                handler.handle(connection)
+            } else {
+                self.redirect(connection)
+            }
+        }
+    }
+
+    fn redirect_headers(portal_url: &str) -> [(&str, &str); 5] {
+        let [cache_control, pragma, expires] = NO_CACHE_HEADERS;
+
+        [
+            headers::location(portal_url),
+            cache_control,
+            pragma,
+            expires,
+            ("Content-Length", "0"),
+        ]
+    }
+
+    fn url_host(url: &str) -> &str {
+        let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+
+        url.split(['/', '?', '#']).next().unwrap_or_default()
+    }
+
+    fn strip_port(host: &str) -> &str {
+        // Leave IPv6 literals, which contain colons, as they are
+        if host.starts_with('[') {
+            host.split_inclusive(']').next().unwrap_or(host)
+        } else {
+            host.split(':').next().unwrap_or(host)
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    pub mod asynch {
+        use crate::http::server::asynch::{
+            Connection, Handler, HandlerResult, Middleware, Request,
+        };
+
+        use super::redirect_headers;
+
+        pub use super::{is_probe, Options, NO_CACHE_HEADERS, PROBE_PATHS};
+
+        /// The async flavor of `super::CaptivePortal`
+        pub struct CaptivePortal<'a>(super::CaptivePortal<'a>);
+
+        impl<'a> CaptivePortal<'a> {
+            pub const fn new(options: Options<'a>) -> Self {
+                Self(super::CaptivePortal::new(options))
+            }
+
+            pub fn options(&self) -> &Options<'a> {
+                self.0.options()
+            }
+
+            pub fn is_portal_host(&self, host: &str) -> bool {
+                self.0.is_portal_host(host)
+            }
+        }
+
+        impl<'a, C> Middleware<C> for CaptivePortal<'a>
+        where
+            C: Connection,
+        {
+            async fn handle<H>(&self, connection: &mut C, handler: &H) -> HandlerResult
+            where
+                H: Handler<C>,
+            {
+                if self.0.is_pass_through(connection) {
+                    handler.handle(connection).await
+                } else {
+                    Request::wrap(connection)
+                        .into_response(
+                            302,
+                            Some("Found"),
+                            &redirect_headers(self.0.options.portal_url),
+                        )
+                        .await?;
+
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    #[cfg(all(test, feature = "std"))]
+    mod tests {
+        use crate::http::server::{FnHandler, Handler, Middleware};
+        use crate::http::Method;
+        use crate::io::Write;
+        use crate::test_http::MockRequest;
+
+        use super::*;
+
+        const OPTIONS: Options<'static> = Options {
+            portal_url: "http://setup.local:8080/wifi?step=1",
+            ap_ip: Ipv4Addr::new(192, 168, 71, 1),
+        };
+
+        /// Answers with the portal page
+        fn portal(request: Request<&mut MockRequest>) -> HandlerResult {
+            request.into_ok_response()?.write_all(b"portal")?;
+
+            Ok(())
+        }
+
+        fn ok(result: HandlerResult) {
+            if let Err(e) = result {
+                panic!("Handler failed: {}", e);
+            }
+        }
+
+        fn handle(mut request: MockRequest) -> MockRequest {
+            let handler = CaptivePortal::new(OPTIONS).compose(FnHandler::new(portal));
+
+            ok(handler.handle(&mut request));
+
+            request
+        }
+
+        fn get(uri: &str, host: Option<&str>) -> MockRequest {
+            let request = MockRequest::new(Method::Get, uri);
+
+            match host {
+                Some(host) => request.header("Host", host),
+                None => request,
+            }
+        }
+
+        fn assert_redirected(request: &MockRequest) {
+            assert_eq!(request.status, Some(302));
+            assert_eq!(request.message.as_deref(), Some("Found"));
+            assert_eq!(
+                request.response_header("Location"),
+                Some(OPTIONS.portal_url)
+            );
+            assert_eq!(request.response_header("Content-Length"), Some("0"));
+
+            for (name, value) in NO_CACHE_HEADERS {
+                assert_eq!(request.response_header(name), Some(value));
+            }
+
+            assert!(request.response.is_empty());
+        }
+
+        fn assert_passed(request: &MockRequest) {
+            assert_eq!(request.status, Some(200));
+            assert_eq!(request.response_str(), "portal");
+        }
+
+        #[test]
+        fn probes() {
+            for path in PROBE_PATHS {
+                assert!(is_probe(path));
+            }
+
+            assert!(is_probe("/GENERATE_204"));
+            assert!(is_probe("/hotspot-detect.html?x=1"));
+
+            assert!(!is_probe("/"));
+            assert!(!is_probe("/generate_204/more"));
+            assert!(!is_probe("/wifi?next=/generate_204"));
+        }
+
+        #[test]
+        fn portal_hosts() {
+            let portal = CaptivePortal::new(OPTIONS);
+
+            assert!(portal.is_portal_host("setup.local"));
+            assert!(portal.is_portal_host("SETUP.local:8080"));
+            assert!(portal.is_portal_host("192.168.71.1"));
+            assert!(portal.is_portal_host("192.168.71.1:80"));
+
+            assert!(!portal.is_portal_host("connectivitycheck.gstatic.com"));
+            assert!(!portal.is_portal_host("192.168.71.2"));
+            assert!(!portal.is_portal_host("setup.local.example.com"));
+            assert!(!portal.is_portal_host("[::1]:80"));
+
+            // Without a scheme, path or port
+            let portal = CaptivePortal::new(Options {
+                portal_url: "portal",
+                ..OPTIONS
+            });
+
+            assert!(portal.is_portal_host("portal:80"));
+            assert!(!portal.is_portal_host("setup.local"));
+        }
+
+        #[test]
+        fn redirects() {
+            // The probes, even for the portal itself
+            assert_redirected(&handle(get("/generate_204", Some("clients3.google.com"))));
+            assert_redirected(&handle(get("/hotspot-detect.html", Some("setup.local"))));
+            assert_redirected(&handle(get("/ncsi.txt", None)));
+
+            // The other hosts
+            assert_redirected(&handle(get("/", Some("example.com"))));
+            assert_redirected(&handle(get("/wifi", Some("192.168.71.2"))));
+        }
+
+        #[test]
+        fn passes_through() {
+            assert_passed(&handle(get("/wifi", Some("setup.local:8080"))));
+            assert_passed(&handle(get("/", Some("192.168.71.1"))));
+
+            // Without a `Host`, the request can only be for the device
+            assert_passed(&handle(get("/", None)));
+
+            let request = MockRequest::new(Method::Post, "/wifi")
+                .header("Host", "setup.local")
+                .body(b"ssid=home");
+            assert_passed(&handle(request));
+        }
+
+        #[cfg(feature = "nightly")]
+        mod asynch {
+            use crate::http::server::asynch::{Handler, Middleware, Request};
+            use crate::http::server::HandlerResult;
+            use crate::http::Method;
+            use crate::io::asynch::Write;
+            use crate::test_http::MockRequest;
+            use crate::utils::asyncify::block_on;
+            use crate::utils::captive::portal_http::asynch::CaptivePortal;
+
+            use super::{assert_passed, assert_redirected, get, ok, OPTIONS};
+
+            struct Portal;
+
+            impl Handler<MockRequest> for Portal {
+                async fn handle(&self, connection: &mut MockRequest) -> HandlerResult {
+                    Request::wrap(connection)
+                        .into_ok_response()
+                        .await?
+                        .write_all(b"portal")
+                        .await?;
+
+                    Ok(())
+                }
+            }
+
+            fn handle(mut request: MockRequest) -> MockRequest {
+                let handler = CaptivePortal::new(OPTIONS).compose(Portal);
+
+                ok(block_on(handler.handle(&mut request)));
+
+                request
+            }
+
+            #[test]
+            fn portal_async() {
+                assert_redirected(&handle(get("/generate_204", Some("setup.local"))));
+                assert_redirected(&handle(get("/", Some("example.com"))));
+
+                assert_passed(&handle(get("/wifi", Some("setup.local"))));
+                assert_passed(&handle(MockRequest::new(Method::Get, "/")));
+            }
+        }
+    }
+}
+
+pub mod portal_dns {
+    //! A DNS responder which answers the queries of the clients joining the AP with the IP of the portal
+    //!
+    //! Together with `super::portal_http`, this makes the clients resolve any host to the portal, and
+    //! thus detect the captive portal. The responder is independent of the UDP stack: each datagram received
+    //! on port 53 is passed to `CaptiveDns::reply`, and the reply it encodes is sent back to the sender,
+    //! e.g. with `crate::socket::UdpSocket`.
+    //!
+    //! Only A queries are answered with the portal IP; all other query types get an empty NOERROR answer,
+    //! so that e.g. AAAA queries make the clients fall back to IPv4, rather than failing the lookup.
+    //!
+    //! The parser only trusts the lengths of the query after checking them against the datagram, so any
+    //! datagram, however truncated or hostile, either gets a well-formed reply (FORMERR if it cannot be parsed)
+    //! or none at all, if it is not even a DNS query.
+
+    use core::convert::TryInto;
+    use core::fmt;
+    use core::time::Duration;
+
+    use crate::ipv4::Ipv4Addr;
+
+    /// The default TTL of the answers, short enough for the clients not to keep them once provisioning is over
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+    /// The UDP payload size advertised in the replies to the queries with EDNS, which avoids IP fragmentation
+    pub const MAX_EDNS_UDP_LEN: usize = 1232;
+
+    /// The maximum length of the replies to the queries without EDNS
+    const MAX_UDP_LEN: usize = 512;
+
+    const HEADER_LEN: usize = 12;
+    const MAX_NAME_LEN: usize = 255;
+
+    const TYPE_A: u16 = 1;
+    const TYPE_OPT: u16 = 41;
+    const CLASS_IN: u16 = 1;
+
+    const FLAG_QR: u16 = 0x8000;
+    const FLAG_AA: u16 = 0x0400;
+    const FLAG_TC: u16 = 0x0200;
+    const FLAG_RD: u16 = 0x0100;
+    const FLAG_RA: u16 = 0x0080;
+
+    const RCODE_FORMERR: u8 = 1;
+    const RCODE_NOTIMP: u8 = 4;
+    /// The upper bits of the 12-bit RCODE, which are in the OPT record
+    const EXTENDED_RCODE_BADVERS: u8 = 1;
+
+    /// The answer for the names in `Options::excluded`
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Negative {
+        /// NXDOMAIN, i.e. the name does not exist
+        NxDomain,
+        /// REFUSED, i.e. the responder does not answer for the name
+        Refused,
+    }
+
+    impl Negative {
+        fn rcode(&self) -> u8 {
+            match self {
+                Self::NxDomain => 3,
+                Self::Refused => 5,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Options<'a> {
+        /// The IP of the device on the AP network, which the A queries are answered with
+        pub ap_ip: Ipv4Addr,
+        /// The TTL of the answers, in whole seconds
+        pub ttl: Duration,
+        /// The domains, including their subdomains, which are answered with `excluded_answer` instead of the portal IP,
+        /// e.g. for the connectivity checks of a vendor which should not detect the portal
+        pub excluded: &'a [&'a str],
+        pub excluded_answer: Negative,
+        /// Whether the queried name is echoed with its case as received, for the clients randomizing it
+        /// to detect spoofed answers (DNS 0x20), or lowercased
+        pub preserve_case: bool,
+    }
+
+    impl<'a> Options<'a> {
+        pub const fn new(ap_ip: Ipv4Addr) -> Self {
+            Self {
+                ap_ip,
+                ttl: DEFAULT_TTL,
+                excluded: &[],
+                excluded_answer: Negative::NxDomain,
+                preserve_case: true,
+            }
+        }
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum DnsError {
+        /// The datagram is not a DNS query, i.e. it is shorter than a DNS header or is a response,
+        /// so no reply is to be sent
+        Malformed,
+        /// The reply does not fit the buffer
+        BufferTooSmall,
+    }
+
+    impl fmt::Display for DnsError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Malformed => write!(f, "Malformed DNS query"),
+                Self::BufferTooSmall => write!(f, "Buffer too small for the DNS reply"),
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for DnsError {}
+
+    pub struct CaptiveDns<'a> {
+        options: Options<'a>,
+    }
+
+    impl<'a> CaptiveDns<'a> {
+        pub const fn new(options: Options<'a>) -> Self {
+            Self { options }
+        }
+
+        pub fn options(&self) -> &Options<'a> {
+            &self.options
+        }
+
+        /// Returns whether `name` (e.g. `connectivitycheck.gstatic.com`) is one of the `Options::excluded` domains
+        /// or one of their subdomains
+        pub fn is_excluded(&self, name: &str) -> bool {
+            is_excluded(self.options.excluded, name.as_bytes())
+        }
+
+        /// Encodes the reply to the DNS `query` into `buf`, returning its length
+        ///
+        /// The reply is at most 512 bytes long, or as long as the UDP payload size advertised by the query
+        /// with EDNS, up to `MAX_EDNS_UDP_LEN`; if it does not fit, the answer is left out and the reply is marked
+        /// as truncated, so that the client retries over TCP. Queries which cannot be parsed are answered
+        /// with FORMERR, and those with the other opcodes with NOTIMP.
+        pub fn reply(&self, query: &[u8], buf: &mut [u8]) -> Result<usize, DnsError> {
+            let header = query.get(..HEADER_LEN).ok_or(DnsError::Malformed)?;
+
+            let flags = u16::from_be_bytes([header[2], header[3]]);
+
+            // Never answer responses, which could make two responders answer each other forever
+            if flags & FLAG_QR != 0 {
+                return Err(DnsError::Malformed);
+            }
+
+            let opcode = (flags >> 11) & 0xf;
+
+            if opcode != 0 {
+                return header_only(buf, header, RCODE_NOTIMP, false);
+            }
+
+            let query = match Query::parse(query) {
+                Ok(query) => query,
+                Err(_) => return header_only(buf, header, RCODE_FORMERR, false),
+            };
+
+            let (rcode, answer) = if query.edns.map(|edns| edns.version > 0) == Some(true) {
+                (0, false)
+            } else if is_excluded(self.options.excluded, query.dotted()) {
+                (self.options.excluded_answer.rcode(), false)
+            } else {
+                (
+                    0,
+                    query.query_type == TYPE_A && query.query_class == CLASS_IN,
+                )
+            };
+
+            let limit = query.edns.map_or(MAX_UDP_LEN, |edns| {
+                (edns.udp_len as usize).clamp(MAX_UDP_LEN, MAX_EDNS_UDP_LEN)
+            });
+
+            let len = buf.len().min(limit);
+            let buf = &mut buf[..len];
+
+            self.encode(buf, &query, rcode, answer, false)
+                .or_else(|_| self.encode(buf, &query, rcode, false, true))
+                .or_else(|_| header_only(buf, query.header, rcode, true))
+        }
+
+        fn encode(
+            &self,
+            buf: &mut [u8],
+            query: &Query<'_>,
+            rcode: u8,
+            answer: bool,
+            truncated: bool,
+        ) -> Result<usize, DnsError> {
+            let mut writer = Writer::new(buf);
+
+            writer.header(
+                query.header,
+                rcode,
+                truncated,
+                [1, answer as u16, query.edns.is_some() as u16],
+            )?;
+
+            if self.options.preserve_case {
+                writer.write(query.name)?;
+            } else {
+                for byte in query.name {
+                    writer.write(&[byte.to_ascii_lowercase()])?;
+                }
+            }
+
+            writer.write(&query.query_type.to_be_bytes())?;
+            writer.write(&query.query_class.to_be_bytes())?;
+
+            if answer {
+                let ttl: u32 = self.options.ttl.as_secs().try_into().unwrap_or(u32::MAX);
+
+                // The name is a pointer to the one of the question
+                writer.write(&[0xc0, HEADER_LEN as u8])?;
+                writer.write(&TYPE_A.to_be_bytes())?;
+                writer.write(&CLASS_IN.to_be_bytes())?;
+                writer.write(&ttl.to_be_bytes())?;
+                writer.write(&4_u16.to_be_bytes())?;
+                writer.write(&self.options.ap_ip.octets())?;
+            }
+
+            if let Some(edns) = query.edns {
+                let extended_rcode = if edns.version > 0 {
+                    EXTENDED_RCODE_BADVERS
+                } else {
+                    0
+                };
+
+                // The root name, our payload size as the class, the extended RCODE and version 0 as the TTL,
+                // and no options
+                writer.write(&[0])?;
+                writer.write(&TYPE_OPT.to_be_bytes())?;
+                writer.write(&(MAX_EDNS_UDP_LEN as u16).to_be_bytes())?;
+                writer.write(&[extended_rcode, 0, 0, 0])?;
+                writer.write(&0_u16.to_be_bytes())?;
+            }
+
+            Ok(writer.len)
+        }
+    }
+
+    /// The EDNS OPT record of a query
+    #[derive(Copy, Clone)]
+    struct Edns {
+        udp_len: u16,
+        version: u8,
+    }
+
+    /// A standard query with a single question
+    struct Query<'q> {
+        header: &'q [u8],
+        /// The name of the question as encoded, including its terminating empty label
+        name: &'q [u8],
+        dotted: [u8; MAX_NAME_LEN],
+        dotted_len: usize,
+        query_type: u16,
+        query_class: u16,
+        edns: Option<Edns>,
+    }
+
+    impl<'q> Query<'q> {
+        fn parse(data: &'q [u8]) -> Result<Self, DnsError> {
+            let header = data.get(..HEADER_LEN).ok_or(DnsError::Malformed)?;
+
+            let count = |offset: usize| u16::from_be_bytes([header[offset], header[offset + 1]]);
+
+            if count(4) != 1 {
+                return Err(DnsError::Malformed);
+            }
+
+            let (name, dotted, dotted_len) = parse_name(&data[HEADER_LEN..])?;
+
+            let offset = HEADER_LEN + name.len();
+            let question = data.get(offset..offset + 4).ok_or(DnsError::Malformed)?;
+
+            // The answer and authority records are unexpected in a query, but harmless
+            let edns = parse_edns(
+                &data[offset + 4..],
+                count(6) as usize + count(8) as usize,
+                count(10) as usize,
+            )?;
+
+            Ok(Self {
+                header,
+                name,
+                dotted,
+                dotted_len,
+                query_type: u16::from_be_bytes([question[0], question[1]]),
+                query_class: u16::from_be_bytes([question[2], question[3]]),
+                edns,
+            })
+        }
+
+        fn dotted(&self) -> &[u8] {
+            &self.dotted[..self.dotted_len]
+        }
+    }
+
+    /// Encodes a reply to the query with header `query`, which has no sections
+    fn header_only(
+        buf: &mut [u8],
+        query: &[u8],
+        rcode: u8,
+        truncated: bool,
+    ) -> Result<usize, DnsError> {
+        let mut writer = Writer::new(buf);
+
+        writer.header(query, rcode, truncated, [0; 3])?;
+
+        Ok(writer.len)
+    }
+
+    /// Parses the name at the start of `data`, returning it as encoded (including its terminating empty label),
+    /// as well as dotted (e.g. `example.com`) along with the length of the latter
+    ///
+    /// Compressed names are not accepted, as the name of a query is the first one of the message.
+    fn parse_name(data: &[u8]) -> Result<(&[u8], [u8; MAX_NAME_LEN], usize), DnsError> {
+        let mut dotted = [0; MAX_NAME_LEN];
+        let mut dotted_len = 0;
+        let mut offset = 0;
+
+        loop {
+            let len = *data.get(offset).ok_or(DnsError::Malformed)? as usize;
+
+            if len == 0 {
+                return Ok((&data[..offset + 1], dotted, dotted_len));
+            }
+
+            // Lengths of 64 and above are compression pointers or reserved
+            if len > 63 || offset + 1 + len >= MAX_NAME_LEN {
+                return Err(DnsError::Malformed);
+            }
+
+            let label = data
+                .get(offset + 1..offset + 1 + len)
+                .ok_or(DnsError::Malformed)?;
+
+            if dotted_len > 0 {
+                dotted[dotted_len] = b'.';
+                dotted_len += 1;
+            }
+
+            dotted[dotted_len..dotted_len + len].copy_from_slice(label);
+            dotted_len += len;
+
+            offset += 1 + len;
+        }
+    }
+
+    /// Returns the length of the possibly compressed name at the start of `data`
+    fn skip_name(data: &[u8]) -> Result<usize, DnsError> {
+        let mut offset = 0;
+
+        loop {
+            let len = *data.get(offset).ok_or(DnsError::Malformed)? as usize;
+
+            match len {
+                0 => return Ok(offset + 1),
+                // A pointer ends the name, and is not followed, as the name is skipped
+                0xc0..=0xff if offset + 2 <= data.len() => return Ok(offset + 2),
+                1..=63 if offset + 1 + len < MAX_NAME_LEN => offset += 1 + len,
+                _ => return Err(DnsError::Malformed),
+            }
+        }
+    }
+
+    /// Skips the `skipped` records at the start of `data`, and returns the OPT record among the `additional` ones
+    /// which follow them, if any
+    fn parse_edns(
+        mut data: &[u8],
+        skipped: usize,
+        additional: usize,
+    ) -> Result<Option<Edns>, DnsError> {
+        let mut edns = None;
+
+        for index in 0..skipped + additional {
+            let name_len = skip_name(data)?;
+            let record = data
+                .get(name_len..name_len + 10)
+                .ok_or(DnsError::Malformed)?;
+
+            let record_type = u16::from_be_bytes([record[0], record[1]]);
+            let data_len = u16::from_be_bytes([record[8], record[9]]) as usize;
+
+            if data.len() < name_len + 10 + data_len {
+                return Err(DnsError::Malformed);
+            }
+
+            if index >= skipped && record_type == TYPE_OPT {
+                // There is at most one OPT record, and its name is the root
+                if edns.is_some() || name_len != 1 {
+                    return Err(DnsError::Malformed);
+                }
+
+                edns = Some(Edns {
+                    udp_len: u16::from_be_bytes([record[2], record[3]]),
+                    version: record[5],
+                });
+            }
+
+            data = &data[name_len + 10 + data_len..];
+        }
+
+        Ok(edns)
+    }
+
+    fn is_excluded(excluded: &[&str], name: &[u8]) -> bool {
+        let name = name.strip_suffix(b".").unwrap_or(name);
+
+        excluded.iter().any(|domain| {
+            let domain = domain.as_bytes();
+            let domain = domain.strip_suffix(b".").unwrap_or(domain);
+
+            if name.len() == domain.len() {
+                name.eq_ignore_ascii_case(domain)
+            } else {
+                name.len() > domain.len()
+                    && name[name.len() - domain.len() - 1] == b'.'
+                    && name[name.len() - domain.len()..].eq_ignore_ascii_case(domain)
+            }
+        })
+    }
+
+    struct Writer<'b> {
+        buf: &'b mut [u8],
+        len: usize,
+    }
+
+    impl<'b> Writer<'b> {
+        fn new(buf: &'b mut [u8]) -> Self {
+            Self { buf, len: 0 }
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<(), DnsError> {
+            self.buf
+                .get_mut(self.len..self.len + data.len())
+                .ok_or(DnsError::BufferTooSmall)?
+                .copy_from_slice(data);
+
+            self.len += data.len();
+
+            Ok(())
+        }
+
+        /// Writes the header of the reply to the query with header `query`, with the number of
+        /// questions, answers and additional records in `counts`
+        fn header(
+            &mut self,
+            query: &[u8],
+            rcode: u8,
+            truncated: bool,
+            counts: [u16; 3],
+        ) -> Result<(), DnsError> {
+            let flags = u16::from_be_bytes([query[2], query[3]]);
+            let flags = FLAG_QR
+                | (flags & (0xf << 11))
+                | FLAG_AA
+                | if truncated { FLAG_TC } else { 0 }
+                | (flags & FLAG_RD)
+                | FLAG_RA
+                | rcode as u16;
+
+            let [questions, answers, additional] = counts;
+
+            self.write(&query[..2])?;
+            self.write(&flags.to_be_bytes())?;
+            self.write(&questions.to_be_bytes())?;
+            self.write(&answers.to_be_bytes())?;
+            self.write(&0_u16.to_be_bytes())?;
+            self.write(&additional.to_be_bytes())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const AP_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 71, 1);
+
+        /// An A query for `connectivitycheck.gstatic.com`, with recursion desired
+        const QUERY_A: &[u8] = &[
+            0x3c, 0x5e, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x11, 0x63,
+            0x6f, 0x6e, 0x6e, 0x65, 0x63, 0x74, 0x69, 0x76, 0x69, 0x74, 0x79, 0x63, 0x68, 0x65,
+            0x63, 0x6b, 0x07, 0x67, 0x73, 0x74, 0x61, 0x74, 0x69, 0x63, 0x03, 0x63, 0x6f, 0x6d,
+            0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+
+        /// An A query for `cAPtIve.APPle.cOm`, i.e. with the case randomized
+        const QUERY_A_0X20: &[u8] = &[
+            0x77, 0xd0, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x63,
+            0x41, 0x50, 0x74, 0x49, 0x76, 0x65, 0x05, 0x41, 0x50, 0x50, 0x6c, 0x65, 0x03, 0x63,
+            0x4f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+
+        /// An AAAA query for `captive.apple.com`
+        const QUERY_AAAA: &[u8] = &[
+            0x8a, 0x11, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x63,
+            0x61, 0x70, 0x74, 0x69, 0x76, 0x65, 0x05, 0x61, 0x70, 0x70, 0x6c, 0x65, 0x03, 0x63,
+            0x6f, 0x6d, 0x00, 0x00, 0x1c, 0x00, 0x01,
+        ];
+
+        /// A PTR query for `1.71.168.192.in-addr.arpa`, i.e. the reverse lookup of `AP_IP`
+        const QUERY_PTR: &[u8] = &[
+            0x00, 0x42, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x31,
+            0x02, 0x37, 0x31, 0x03, 0x31, 0x36, 0x38, 0x03, 0x31, 0x39, 0x32, 0x07, 0x69, 0x6e,
+            0x2d, 0x61, 0x64, 0x64, 0x72, 0x04, 0x61, 0x72, 0x70, 0x61, 0x00, 0x00, 0x0c, 0x00,
+            0x01,
+        ];
+
+        fn reply(options: Options<'_>, query: &[u8]) -> Vec<u8> {
+            let mut buf = [0; 512];
+
+            let len = CaptiveDns::new(options).reply(query, &mut buf).unwrap();
+
+            buf[..len].to_vec()
+        }
+
+        /// The header of the reply to `query`, with the response, authoritative and recursion available flags set
+        fn header(query: &[u8], rcode: u8, answers: u8) -> Vec<u8> {
+            vec![
+                query[0],
+                query[1],
+                0x85,
+                0x80 | rcode,
+                0,
+                1,
+                0,
+                answers,
+                0,
+                0,
+                0,
+                0,
+            ]
+        }
+
+        #[test]
+        fn reply_a() {
+            let mut expected = header(QUERY_A, 0, 1);
+            expected.extend_from_slice(&QUERY_A[12..]);
+            expected.extend_from_slice(&[
+                0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x04, 192, 168,
+                71, 1,
+            ]);
+
+            assert_eq!(reply(Options::new(AP_IP), QUERY_A), expected);
+
+            let options = Options {
+                ttl: Duration::from_secs(300),
+                ..Options::new(AP_IP)
+            };
+
+            let reply = reply(options, QUERY_A);
+            assert_eq!(
+                &reply[reply.len() - 10..reply.len() - 6],
+                &[0, 0, 0x01, 0x2c]
+            );
+        }
+
+        #[test]
+        fn reply_a_case() {
+            let reply_preserved = reply(Options::new(AP_IP), QUERY_A_0X20);
+
+            assert_eq!(&reply_preserved[..12], &header(QUERY_A_0X20, 0, 1)[..]);
+            assert_eq!(
+                &reply_preserved[12..QUERY_A_0X20.len()],
+                &QUERY_A_0X20[12..]
+            );
+
+            let options = Options {
+                preserve_case: false,
+                ..Options::new(AP_IP)
+            };
+
+            let reply_lowercased = reply(options, QUERY_A_0X20);
+
+            assert_eq!(
+                reply_lowercased[12..QUERY_A_0X20.len()],
+                QUERY_A_0X20[12..].to_ascii_lowercase()
+            );
+            assert_eq!(
+                &reply_lowercased[QUERY_A_0X20.len()..],
+                &reply_preserved[QUERY_A_0X20.len()..]
+            );
+        }
+
+        #[test]
+        fn reply_aaaa() {
+            let mut expected = header(QUERY_AAAA, 0, 0);
+            expected.extend_from_slice(&QUERY_AAAA[12..]);
+
+            assert_eq!(reply(Options::new(AP_IP), QUERY_AAAA), expected);
+        }
+
+        #[test]
+        fn reply_ptr() {
+            let mut expected = header(QUERY_PTR, 0, 0);
+            expected.extend_from_slice(&QUERY_PTR[12..]);
+
+            assert_eq!(reply(Options::new(AP_IP), QUERY_PTR), expected);
+        }
+
+        #[test]
+        fn reply_excluded() {
+            let options = Options {
+                excluded: &["gstatic.com."],
+                ..Options::new(AP_IP)
+            };
+
+            let mut expected = header(QUERY_A, 3, 0);
+            expected.extend_from_slice(&QUERY_A[12..]);
+
+            assert_eq!(reply(options.clone(), QUERY_A), expected);
+
+            // Other names are answered as usual
+            assert_eq!(reply(options.clone(), QUERY_A_0X20)[7], 1);
+
+            let options = Options {
+                excluded_answer: Negative::Refused,
+                ..options
+            };
+
+            assert_eq!(reply(options, QUERY_A)[3], 0x85);
+        }
+
+        #[test]
+        fn excluded_domains() {
+            let dns = CaptiveDns::new(Options {
+                excluded: &["gstatic.com", "Apple.com."],
+                ..Options::new(AP_IP)
+            });
+
+            assert!(dns.is_excluded("gstatic.com"));
+            assert!(dns.is_excluded("connectivitycheck.GSTATIC.com."));
+            assert!(dns.is_excluded("captive.apple.com"));
+            assert!(!dns.is_excluded("notgstatic.com"));
+            assert!(!dns.is_excluded("com"));
+            assert!(!dns.is_excluded(""));
+        }
+
+        #[test]
+        fn reply_unanswered() {
+            let dns = CaptiveDns::new(Options::new(AP_IP));
+            let mut buf = [0; 512];
+
+            // A response
+            let response = reply(Options::new(AP_IP), QUERY_A);
+            assert_eq!(dns.reply(&response, &mut buf), Err(DnsError::Malformed));
+
+            // Shorter than a header, so without an ID to reply to
+            for len in 0..12 {
+                assert_eq!(
+                    dns.reply(&QUERY_A[..len], &mut buf),
+                    Err(DnsError::Malformed)
+                );
+            }
+
+            assert_eq!(
+                dns.reply(QUERY_A, &mut buf[..11]),
+                Err(DnsError::BufferTooSmall)
+            );
+        }
+
+        #[test]
+        fn reply_format_failure() {
+            let formerr = [0x3c, 0x5e, 0x85, 0x81, 0, 0, 0, 0, 0, 0, 0, 0];
+
+            // Truncated
+            for len in 12..QUERY_A.len() {
+                assert_eq!(reply(Options::new(AP_IP), &QUERY_A[..len]), formerr);
+            }
+
+            // Two questions
+            let mut query = QUERY_A.to_vec();
+            query[5] = 2;
+            assert_eq!(reply(Options::new(AP_IP), &query), formerr);
+
+            // A compressed name, pointing at itself
+            let mut query = QUERY_A[..12].to_vec();
+            query.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01]);
+            assert_eq!(reply(Options::new(AP_IP), &query), formerr);
+
+            // A label longer than 63 bytes
+            let mut query = QUERY_A[..12].to_vec();
+            query.push(64);
+            query.extend_from_slice(&[b'a'; 64]);
+            query.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x01]);
+            assert_eq!(reply(Options::new(AP_IP), &query), formerr);
+
+            // A name longer than 255 bytes
+            let mut query = QUERY_A[..12].to_vec();
+            for _ in 0..5 {
+                query.push(50);
+                query.extend_from_slice(&[b'a'; 50]);
+            }
+            query.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x01]);
+            assert_eq!(reply(Options::new(AP_IP), &query), formerr);
+
+            // An additional record running past the end of the datagram
+            let mut query = QUERY_ANDROID.to_vec();
+            query[QUERY_ANDROID.len() - 1] = 1;
+            assert_eq!(
+                &reply(Options::new(AP_IP), &query)[..4],
+                &[0xd6, 0xe1, 0x85, 0x81]
+            );
+
+            // Two OPT records
+            let mut query = QUERY_ANDROID.to_vec();
+            query[11] = 2;
+            query.extend_from_slice(&QUERY_ANDROID[QUERY_ANDROID.len() - 11..]);
+            assert_eq!(
+                &reply(Options::new(AP_IP), &query)[..4],
+                &[0xd6, 0xe1, 0x85, 0x81]
+            );
+        }
+
+        /// An A query of an Android 13 client, with an EDNS OPT record advertising a UDP payload size of 1232 bytes
+        const QUERY_ANDROID: &[u8] = &[
+            0xd6, 0xe1, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x11, 0x63,
+            0x6f, 0x6e, 0x6e, 0x65, 0x63, 0x74, 0x69, 0x76, 0x69, 0x74, 0x79, 0x63, 0x68, 0x65,
+            0x63, 0x6b, 0x07, 0x67, 0x73, 0x74, 0x61, 0x74, 0x69, 0x63, 0x03, 0x63, 0x6f, 0x6d,
+            0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x29, 0x04, 0xd0, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+
+        #[test]
+        fn reply_edns() {
+            let mut expected = vec![0xd6, 0xe1, 0x85, 0x80, 0, 1, 0, 1, 0, 0, 0, 1];
+            expected.extend_from_slice(&QUERY_ANDROID[12..QUERY_ANDROID.len() - 11]);
+            expected.extend_from_slice(&[
+                0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x04, 192, 168,
+                71, 1,
+            ]);
+            expected.extend_from_slice(&[0x00, 0x00, 0x29, 0x04, 0xd0, 0, 0, 0, 0, 0, 0]);
+
+            assert_eq!(reply(Options::new(AP_IP), QUERY_ANDROID), expected);
+
+            // The options of the OPT record (here a cookie) and the DO bit are ignored
+            let mut query = QUERY_ANDROID.to_vec();
+            let len = query.len();
+            query[len - 4] = 0x80;
+            query[len - 1] = 12;
+            query.extend_from_slice(&[0x00, 0x0a, 0x00, 0x08, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+            assert_eq!(reply(Options::new(AP_IP), &query), expected);
+
+            // The answer and authority records are skipped, even if compressed
+            let mut query = QUERY_ANDROID[..QUERY_ANDROID.len() - 11].to_vec();
+            query[7] = 1;
+            query.extend_from_slice(&[
+                0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x04, 10, 0, 0, 1,
+            ]);
+            query.extend_from_slice(&QUERY_ANDROID[QUERY_ANDROID.len() - 11..]);
+
+            assert_eq!(reply(Options::new(AP_IP), &query), expected);
+        }
+
+        #[test]
+        fn reply_edns_bad_version() {
+            let mut query = QUERY_ANDROID.to_vec();
+            let len = query.len();
+            query[len - 5] = 1;
+
+            let mut expected = vec![0xd6, 0xe1, 0x85, 0x80, 0, 1, 0, 0, 0, 0, 0, 1];
+            expected.extend_from_slice(&QUERY_ANDROID[12..QUERY_ANDROID.len() - 11]);
+            expected.extend_from_slice(&[0x00, 0x00, 0x29, 0x04, 0xd0, 1, 0, 0, 0, 0, 0]);
+
+            assert_eq!(reply(Options::new(AP_IP), &query), expected);
+        }
+
+        #[test]
+        fn reply_truncated() {
+            let dns = CaptiveDns::new(Options::new(AP_IP));
+            let full = reply(Options::new(AP_IP), QUERY_A);
+
+            let mut buf = [0; 512];
+
+            // Without the answer
+            let len = dns.reply(QUERY_A, &mut buf[..full.len() - 1]).unwrap();
+
+            assert_eq!(len, QUERY_A.len());
+            assert_eq!(&buf[..4], &[0x3c, 0x5e, 0x87, 0x80]);
+            assert_eq!(&buf[4..8], &[0, 1, 0, 0]);
+            assert_eq!(&buf[12..len], &QUERY_A[12..]);
+
+            // Without the question either
+            let len = dns.reply(QUERY_A, &mut buf[..QUERY_A.len() - 1]).unwrap();
+
+            assert_eq!(
+                &buf[..len],
+                &[0x3c, 0x5e, 0x87, 0x80, 0, 0, 0, 0, 0, 0, 0, 0]
+            );
+
+            // A truncated EDNS reply keeps the OPT record
+            let len = dns
+                .reply(QUERY_ANDROID, &mut buf[..QUERY_ANDROID.len() + 15])
+                .unwrap();
+
+            assert_eq!(len, QUERY_ANDROID.len());
+            assert_eq!(
+                &buf[..12],
+                &[0xd6, 0xe1, 0x87, 0x80, 0, 1, 0, 0, 0, 0, 0, 1]
+            );
+        }
+
+        #[test]
+        fn reply_capped() {
+            // A name of 253 bytes, the longest one
+            let mut query = QUERY_A[..12].to_vec();
+            for _ in 0..4 {
+                query.push(62);
+                query.extend_from_slice(&[b'a'; 62]);
+            }
+            query.extend_from_slice(&[1, b'a', 0x00, 0x00, 0x01, 0x00, 0x01]);
+
+            let len = reply(Options::new(AP_IP), &query).len();
+            assert_eq!(len, query.len() + 16);
+
+            // Advertising a payload size below 512 bytes, or above the one supported, has no effect
+            for udp_len in [0_u16, 100, 0xffff] {
+                let mut query = QUERY_ANDROID.to_vec();
+                let len = query.len();
+                query[len - 8..len - 6].copy_from_slice(&udp_len.to_be_bytes());
+
+                let mut buf = vec![0; 0x10000];
+                let len = CaptiveDns::new(Options::new(AP_IP))
+                    .reply(&query, &mut buf)
+                    .unwrap();
+
+                assert_eq!(&buf[..len], &reply(Options::new(AP_IP), QUERY_ANDROID)[..]);
+            }
+        }
+
+        /// A xorshift PRNG, for reproducible garbage
+        struct Garbage(u32);
+
+        impl Garbage {
+            fn next(&mut self) -> u8 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 17;
+                self.0 ^= self.0 << 5;
+
+                self.0 as u8
+            }
+        }
+
+        fn assert_well_formed(query: &[u8]) {
+            let mut buf = [0; 1500];
+
+            match CaptiveDns::new(Options::new(AP_IP)).reply(query, &mut buf) {
+                Ok(len) => {
+                    assert!((12..=512).contains(&len));
+                    assert_eq!(&buf[..2], &query[..2]);
+                    assert_eq!(buf[2] & 0x80, 0x80);
+                }
+                Err(e) => {
+                    assert_eq!(e, DnsError::Malformed);
+                    assert!(query.len() < 12 || query[2] & 0x80 != 0);
+                }
+            }
+        }
+
+        #[test]
+        fn reply_garbage() {
+            let mut garbage = Garbage(0x2545_f491);
+
+            for _ in 0..10_000 {
+                let len = garbage.next() as usize * 2;
+                let query = (0..len).map(|_| garbage.next()).collect::<Vec<_>>();
+
+                assert_well_formed(&query);
+            }
+
+            // Queries with a few bytes corrupted, and truncated at random
+            for _ in 0..10_000 {
+                let mut query = QUERY_ANDROID.to_vec();
+
+                for _ in 0..3 {
+                    let index = garbage.next() as usize % query.len();
+                    query[index] = garbage.next();
+                }
+
+                query.truncate(garbage.next() as usize % (query.len() + 1));
+
+                assert_well_formed(&query);
+            }
+        }
+
+        #[test]
+        fn reply_not_implemented() {
+            // An inverse query (opcode 1)
+            let mut query = QUERY_A.to_vec();
+            query[2] |= 1 << 3;
+
+            assert_eq!(
+                reply(Options::new(AP_IP), &query),
+                [0x3c, 0x5e, 0x8d, 0x84, 0, 0, 0, 0, 0, 0, 0, 0]
+            );
+        }
+    }
+}
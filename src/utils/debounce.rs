@@ -0,0 +1,635 @@
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "alloc")]
+use crate::sys_time::Monotonic;
+#[cfg(feature = "alloc")]
+use crate::timer::{OnceTimer, TimerService};
+#[cfg(feature = "alloc")]
+use crate::utils::mutex::{Mutex, RawMutex};
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DebounceError<S, T> {
+    SourceError(S),
+    TimerError(T),
+}
+
+impl<S, T> fmt::Display for DebounceError<S, T>
+where
+    S: fmt::Display,
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SourceError(e) => write!(f, "Source error: {e}"),
+            Self::TimerError(e) => write!(f, "Timer error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S, T> std::error::Error for DebounceError<S, T>
+where
+    S: fmt::Display + fmt::Debug,
+    T: fmt::Display + fmt::Debug,
+{
+}
+
+#[cfg(feature = "alloc")]
+struct DebounceState<P> {
+    pending: Option<P>,
+    armed: bool,
+    skip: usize,
+    callback: Box<dyn FnMut(P) + Send>,
+}
+
+/// Reports the last value fed with `input`, once no new value was fed for `window`
+///
+/// The state is kept behind `Mutex`es, so `input` can be called concurrently, e.g. from a GPIO interrupt task.
+/// The callback is called from the timer service, with the state locked, so it must not feed the debouncer.
+#[cfg(feature = "alloc")]
+pub struct Debouncer<R, T, P>
+where
+    R: RawMutex,
+{
+    timer: Mutex<R, T>,
+    state: Arc<Mutex<R, DebounceState<P>>>,
+    window: Duration,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, R, T, P> Debouncer<R, T, P>
+where
+    R: RawMutex + Send + Sync + 'static,
+    T: OnceTimer + 'a,
+    P: Send + 'static,
+{
+    pub fn new<S, F>(timer_service: &'a S, window: Duration, callback: F) -> Result<Self, T::Error>
+    where
+        S: TimerService<Timer<'a> = T, Error = T::Error>,
+        F: FnMut(P) + Send + 'static,
+    {
+        let window = timer_service.capabilities().clamp(window);
+
+        let state = Arc::new(Mutex::new(DebounceState {
+            pending: None,
+            armed: false,
+            skip: 0,
+            callback: Box::new(callback),
+        }));
+
+        let timer = {
+            let state = state.clone();
+
+            timer_service.timer(move || {
+                let mut state = state.lock();
+
+                if state.skip > 0 {
+                    // The expiry of a timer which was rescheduled in the meantime
+                    state.skip -= 1;
+                } else {
+                    state.armed = false;
+
+                    if let Some(value) = state.pending.take() {
+                        (state.callback)(value);
+                    }
+                }
+            })?
+        };
+
+        Ok(Self {
+            timer: Mutex::new(timer),
+            state,
+            window,
+        })
+    }
+
+    /// The window, clamped to the capabilities of the timer service
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Feeds a new value, restarting the window
+    pub fn input(&self, value: P) -> Result<(), T::Error> {
+        let mut timer = self.timer.lock();
+
+        let cancelled = timer.cancel()?;
+
+        {
+            let mut state = self.state.lock();
+
+            if state.armed && !cancelled {
+                state.skip += 1;
+            }
+
+            state.pending = Some(value);
+            state.armed = true;
+        }
+
+        timer.after(self.window)
+    }
+}
+
+#[cfg(feature = "alloc")]
+struct ThrottleState<P> {
+    last: Option<Duration>,
+    pending: Option<P>,
+    deadline: Option<Duration>,
+    callback: Box<dyn FnMut(P) + Send>,
+}
+
+/// Reports at most one of the values fed with `input` per `window`
+///
+/// A value fed while the window of the last reported value is still open is dropped, unless the throttler is `trailing`,
+/// in which case the last such value is reported once the window closes, opening a new one.
+///
+/// The state is kept behind `Mutex`es, so `input` can be called concurrently, e.g. from a GPIO interrupt task.
+/// The callback is called with the state locked, so it must not feed the throttler.
+#[cfg(feature = "alloc")]
+pub struct Throttler<R, T, C, P>
+where
+    R: RawMutex,
+{
+    timer: Mutex<R, T>,
+    clock: C,
+    state: Arc<Mutex<R, ThrottleState<P>>>,
+    window: Duration,
+    trailing: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, R, T, C, P> Throttler<R, T, C, P>
+where
+    R: RawMutex + Send + Sync + 'static,
+    T: OnceTimer + 'a,
+    C: Monotonic,
+    P: Send + 'static,
+{
+    pub fn new<S, F>(
+        timer_service: &'a S,
+        clock: C,
+        window: Duration,
+        trailing: bool,
+        callback: F,
+    ) -> Result<Self, T::Error>
+    where
+        S: TimerService<Timer<'a> = T, Error = T::Error>,
+        F: FnMut(P) + Send + 'static,
+    {
+        let window = timer_service.capabilities().clamp(window);
+
+        let state = Arc::new(Mutex::new(ThrottleState {
+            last: None,
+            pending: None,
+            deadline: None,
+            callback: Box::new(callback),
+        }));
+
+        let timer = {
+            let state = state.clone();
+
+            timer_service.timer(move || {
+                let mut state = state.lock();
+
+                let deadline = state.deadline.take();
+
+                if let Some(value) = state.pending.take() {
+                    state.last = deadline;
+                    (state.callback)(value);
+                }
+            })?
+        };
+
+        Ok(Self {
+            timer: Mutex::new(timer),
+            clock,
+            state,
+            window,
+            trailing,
+        })
+    }
+
+    /// The window, clamped to the capabilities of the timer service
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn is_trailing(&self) -> bool {
+        self.trailing
+    }
+
+    /// Feeds a new value, which is reported right away if no window is open
+    pub fn input(&self, value: P) -> Result<(), T::Error> {
+        let mut timer = self.timer.lock();
+
+        let now = self.clock.now();
+
+        let arm = {
+            let mut state = self.state.lock();
+
+            let open = state.pending.is_some()
+                || state
+                    .last
+                    .map(|last| now < last + self.window)
+                    .unwrap_or(false);
+
+            if !open {
+                state.last = Some(now);
+                (state.callback)(value);
+
+                None
+            } else if self.trailing {
+                state.pending = Some(value);
+
+                if state.deadline.is_none() {
+                    let deadline = state.last.unwrap_or(now) + self.window;
+                    state.deadline = Some(deadline);
+
+                    Some(deadline.saturating_sub(now))
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        };
+
+        if let Some(duration) = arm {
+            timer.after(duration)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::future::{poll_fn, Future};
+    use core::pin::pin;
+    use core::task::Poll;
+    use core::time::Duration;
+
+    use crate::event_bus::asynch::Receiver;
+    use crate::sys_time::Monotonic;
+    use crate::timer::asynch::OnceTimer;
+
+    pub use super::DebounceError;
+
+    enum Either<A, B> {
+        First(A),
+        Second(B),
+    }
+
+    async fn select<A, B>(first: A, second: B) -> Either<A::Output, B::Output>
+    where
+        A: Future,
+        B: Future,
+    {
+        let mut first = pin!(first);
+        let mut second = pin!(second);
+
+        poll_fn(|cx| {
+            if let Poll::Ready(output) = first.as_mut().poll(cx) {
+                Poll::Ready(Either::First(output))
+            } else if let Poll::Ready(output) = second.as_mut().poll(cx) {
+                Poll::Ready(Either::Second(output))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Receives the values of `source`, yielding a value once no new value was received for `window`
+    ///
+    /// The pending receive of `source` is dropped whenever the window closes, so `source` should not lose
+    /// values when that happens.
+    pub struct Debouncer<S, T> {
+        source: S,
+        timer: T,
+        window: Duration,
+    }
+
+    impl<S, T> Debouncer<S, T>
+    where
+        S: Receiver,
+        T: OnceTimer,
+    {
+        pub const fn new(source: S, timer: T, window: Duration) -> Self {
+            Self {
+                source,
+                timer,
+                window,
+            }
+        }
+
+        pub fn window(&self) -> Duration {
+            self.window
+        }
+
+        pub fn release(self) -> (S, T) {
+            (self.source, self.timer)
+        }
+
+        pub async fn recv(&mut self) -> Result<S::Data, DebounceError<S::Error, T::Error>> {
+            let mut value = self
+                .source
+                .recv()
+                .await
+                .map_err(DebounceError::SourceError)?;
+
+            loop {
+                match select(self.source.recv(), self.timer.after(self.window)).await {
+                    Either::First(received) => {
+                        value = received.map_err(DebounceError::SourceError)?
+                    }
+                    Either::Second(expired) => {
+                        expired.map_err(DebounceError::TimerError)?;
+
+                        break Ok(value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Receives the values of `source`, yielding at most one value per `window`
+    ///
+    /// A value received while the window of the last yielded value is still open is dropped, unless the throttler is `trailing`,
+    /// in which case the last such value is yielded once the window closes, opening a new one.
+    pub struct Throttler<S, T, C> {
+        source: S,
+        timer: T,
+        clock: C,
+        window: Duration,
+        trailing: bool,
+        last: Option<Duration>,
+    }
+
+    impl<S, T, C> Throttler<S, T, C>
+    where
+        S: Receiver,
+        T: OnceTimer,
+        C: Monotonic,
+    {
+        pub const fn new(source: S, timer: T, clock: C, window: Duration, trailing: bool) -> Self {
+            Self {
+                source,
+                timer,
+                clock,
+                window,
+                trailing,
+                last: None,
+            }
+        }
+
+        pub fn window(&self) -> Duration {
+            self.window
+        }
+
+        pub fn is_trailing(&self) -> bool {
+            self.trailing
+        }
+
+        pub fn release(self) -> (S, T, C) {
+            (self.source, self.timer, self.clock)
+        }
+
+        pub async fn recv(&mut self) -> Result<S::Data, DebounceError<S::Error, T::Error>> {
+            let mut pending = None;
+
+            loop {
+                let now = self.clock.now();
+
+                let remaining = self
+                    .last
+                    .map(|last| (last + self.window).saturating_sub(now))
+                    .unwrap_or(Duration::ZERO);
+
+                if remaining == Duration::ZERO {
+                    let value = if let Some(value) = pending.take() {
+                        value
+                    } else {
+                        self.source
+                            .recv()
+                            .await
+                            .map_err(DebounceError::SourceError)?
+                    };
+
+                    self.last = Some(self.clock.now());
+
+                    break Ok(value);
+                }
+
+                if self.trailing {
+                    match select(self.source.recv(), self.timer.after(remaining)).await {
+                        Either::First(received) => {
+                            pending = Some(received.map_err(DebounceError::SourceError)?)
+                        }
+                        Either::Second(expired) => {
+                            expired.map_err(DebounceError::TimerError)?;
+
+                            if pending.is_none() {
+                                self.last = None;
+                            }
+                        }
+                    }
+                } else {
+                    let value = self
+                        .source
+                        .recv()
+                        .await
+                        .map_err(DebounceError::SourceError)?;
+
+                    let now = self.clock.now();
+
+                    if self
+                        .last
+                        .map(|last| now >= last + self.window)
+                        .unwrap_or(true)
+                    {
+                        self.last = Some(now);
+
+                        break Ok(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use crate::test_timer::MockTimerService;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn reported() -> (Arc<StdMutex<Vec<u32>>>, impl FnMut(u32) + Send + 'static) {
+        let reported = Arc::new(StdMutex::new(Vec::new()));
+
+        let callback = {
+            let reported = reported.clone();
+
+            move |value| reported.lock().unwrap().push(value)
+        };
+
+        (reported, callback)
+    }
+
+    #[test]
+    fn debounce() {
+        let service = MockTimerService::new();
+        let (reported, callback) = reported();
+
+        let debouncer = Debouncer::<StdRawMutex, _, _>::new(&service, MS * 10, callback).unwrap();
+
+        debouncer.input(1).unwrap();
+        service.advance(MS * 5);
+        debouncer.input(2).unwrap();
+        service.advance(MS * 5);
+        debouncer.input(3).unwrap();
+
+        service.advance(MS * 9);
+        assert!(reported.lock().unwrap().is_empty());
+
+        service.advance(MS);
+        assert_eq!(*reported.lock().unwrap(), [3]);
+
+        service.advance(MS * 100);
+        assert_eq!(*reported.lock().unwrap(), [3]);
+
+        debouncer.input(4).unwrap();
+        service.advance(MS * 10);
+        assert_eq!(*reported.lock().unwrap(), [3, 4]);
+    }
+
+    #[test]
+    fn debounce_with_expiry_in_flight() {
+        let service = MockTimerService::new();
+        let (reported, callback) = reported();
+
+        let debouncer = Debouncer::<StdRawMutex, _, _>::new(&service, MS * 10, callback).unwrap();
+
+        debouncer.input(1).unwrap();
+
+        // The expiry can no longer be cancelled when the next value is fed
+        service.expire(MS * 10);
+        debouncer.input(2).unwrap();
+
+        service.run();
+        assert!(reported.lock().unwrap().is_empty());
+
+        service.advance(MS * 10);
+        assert_eq!(*reported.lock().unwrap(), [2]);
+    }
+
+    #[test]
+    fn throttle() {
+        let service = MockTimerService::new();
+        let (reported, callback) = reported();
+
+        let throttler = Throttler::<StdRawMutex, _, _, _>::new(
+            &service,
+            service.clone(),
+            MS * 10,
+            false,
+            callback,
+        )
+        .unwrap();
+
+        assert!(!throttler.is_trailing());
+
+        throttler.input(1).unwrap();
+        assert_eq!(*reported.lock().unwrap(), [1]);
+
+        service.advance(MS * 5);
+        throttler.input(2).unwrap();
+
+        service.advance(MS * 5);
+        throttler.input(3).unwrap();
+
+        service.advance(MS * 100);
+        assert_eq!(*reported.lock().unwrap(), [1, 3]);
+        assert_eq!(service.scheduled(), 0);
+    }
+
+    #[test]
+    fn throttle_trailing() {
+        let service = MockTimerService::new();
+        let (reported, callback) = reported();
+
+        let throttler = Throttler::<StdRawMutex, _, _, _>::new(
+            &service,
+            service.clone(),
+            MS * 10,
+            true,
+            callback,
+        )
+        .unwrap();
+
+        assert!(throttler.is_trailing());
+
+        throttler.input(1).unwrap();
+
+        service.advance(MS * 3);
+        throttler.input(2).unwrap();
+        service.advance(MS * 3);
+        throttler.input(3).unwrap();
+
+        service.advance(MS * 3);
+        assert_eq!(*reported.lock().unwrap(), [1]);
+
+        // The last value dropped is reported when the window closes, opening a new one
+        service.advance(MS);
+        assert_eq!(*reported.lock().unwrap(), [1, 3]);
+
+        service.advance(MS * 2);
+        throttler.input(4).unwrap();
+
+        service.advance(MS * 7);
+        assert_eq!(*reported.lock().unwrap(), [1, 3]);
+
+        service.advance(MS);
+        assert_eq!(*reported.lock().unwrap(), [1, 3, 4]);
+
+        service.advance(MS * 15);
+        throttler.input(5).unwrap();
+        assert_eq!(*reported.lock().unwrap(), [1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn windows_clamped() {
+        let service = MockTimerService::new().with_capabilities(crate::timer::TimerCapabilities {
+            resolution: Some(MS * 10),
+            max_duration: Some(MS * 1000),
+            callback_context: Default::default(),
+        });
+
+        let debouncer = Debouncer::<StdRawMutex, _, u32>::new(&service, MS, |_| ()).unwrap();
+        assert_eq!(debouncer.window(), MS * 10);
+
+        let throttler = Throttler::<StdRawMutex, _, _, u32>::new(
+            &service,
+            service.clone(),
+            MS * 5000,
+            false,
+            |_| (),
+        )
+        .unwrap();
+        assert_eq!(throttler.window(), MS * 1000);
+    }
+}
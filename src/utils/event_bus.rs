@@ -0,0 +1,1555 @@
+pub mod static_bus;
+
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+use crate::event_bus::{ErrorType, EventBus, Postbox};
+#[cfg(feature = "alloc")]
+use crate::sys_time::Monotonic;
+#[cfg(feature = "alloc")]
+use crate::timer::{OnceTimer, TimerService};
+#[cfg(feature = "alloc")]
+use crate::utils::mutex::{Condvar, Mutex, RawCondvar, RawMutex};
+
+/// A topic of a `Topics` bus, identified by the (typically zero-sized) type implementing it
+pub trait Topic: 'static {
+    type Payload: Copy + Send + 'static;
+
+    const NAME: &'static str;
+}
+
+/// The default capacity of the payloads of a `RawEnvelope`, in bytes
+pub const DEFAULT_ENVELOPE_CAPACITY: usize = 32;
+
+struct Fits<T, const N: usize>(PhantomData<T>);
+
+impl<T, const N: usize> Fits<T, N> {
+    const OK: () = assert!(
+        size_of::<T>() <= N,
+        "The payload of the topic does not fit in the envelope"
+    );
+}
+
+/// The payload of the underlying bus of `Topics`: a copy of the payload of a topic, tagged with the topic
+///
+/// Payloads larger than `N` bytes fail to compile.
+#[derive(Copy, Clone)]
+pub struct RawEnvelope<const N: usize = DEFAULT_ENVELOPE_CAPACITY> {
+    topic: TypeId,
+    name: &'static str,
+    data: [MaybeUninit<u8>; N],
+}
+
+impl<const N: usize> RawEnvelope<N> {
+    pub fn new<K>(payload: &K::Payload) -> Self
+    where
+        K: Topic,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = Fits::<K::Payload, N>::OK;
+
+        let mut data = [MaybeUninit::uninit(); N];
+
+        // Safety: the payload fits in `data`, as per the assertion above
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                payload as *const K::Payload as *const MaybeUninit<u8>,
+                data.as_mut_ptr(),
+                size_of::<K::Payload>(),
+            );
+        }
+
+        Self {
+            topic: TypeId::of::<K>(),
+            name: K::NAME,
+            data,
+        }
+    }
+
+    pub fn topic_name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is<K>(&self) -> bool
+    where
+        K: Topic,
+    {
+        self.topic == TypeId::of::<K>()
+    }
+
+    /// Returns the payload, if the envelope belongs to topic `K`
+    pub fn get<K>(&self) -> Option<K::Payload>
+    where
+        K: Topic,
+    {
+        if self.is::<K>() {
+            // Safety: the envelope was created by `new::<K>`, which copied a `K::Payload` in `data`
+            Some(unsafe { core::ptr::read_unaligned(self.data.as_ptr() as *const K::Payload) })
+        } else {
+            None
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for RawEnvelope<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawEnvelope")
+            .field("topic", &self.name)
+            .finish()
+    }
+}
+
+/// A bus carrying several topics, each with its own payload type, over a single `EventBus<RawEnvelope<N>>`
+///
+/// Posting or subscribing with a payload other than the one of the topic fails to compile.
+pub struct Topics<B, const N: usize = DEFAULT_ENVELOPE_CAPACITY>(B);
+
+impl<B, const N: usize> Topics<B, N> {
+    pub const fn new(bus: B) -> Self {
+        Self(bus)
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.0
+    }
+
+    pub fn release(self) -> B {
+        self.0
+    }
+
+    pub fn post<K>(&self, payload: &K::Payload, wait: Option<Duration>) -> Result<bool, B::Error>
+    where
+        K: Topic,
+        B: Postbox<RawEnvelope<N>>,
+    {
+        self.0.post(&RawEnvelope::new::<K>(payload), wait)
+    }
+
+    /// Subscribes `callback` to the payloads of topic `K`
+    pub fn subscribe<K, F>(&self, mut callback: F) -> Result<B::Subscription<'_>, B::Error>
+    where
+        K: Topic,
+        B: EventBus<RawEnvelope<N>>,
+        F: FnMut(&K::Payload) + Send + 'static,
+    {
+        self.0.subscribe(move |envelope: &RawEnvelope<N>| {
+            if let Some(payload) = envelope.get::<K>() {
+                callback(&payload);
+            }
+        })
+    }
+}
+
+/// The error of an adapter, which is either the error of the adapted postbox or bus, or the error of its closure
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EitherError<E1, E2> {
+    First(E1),
+    Second(E2),
+}
+
+impl<E1, E2> fmt::Display for EitherError<E1, E2>
+where
+    E1: fmt::Display,
+    E2: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First(e) => write!(f, "{e}"),
+            Self::Second(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E1, E2> std::error::Error for EitherError<E1, E2>
+where
+    E1: fmt::Display + fmt::Debug,
+    E2: fmt::Display + fmt::Debug,
+{
+}
+
+impl<E1, E2> EitherError<E1, E2> {
+    pub fn map_first<F, E>(self, f: F) -> EitherError<E, E2>
+    where
+        F: FnOnce(E1) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError::First(f(e)),
+            Self::Second(e) => EitherError::Second(e),
+        }
+    }
+
+    pub fn map_second<F, E>(self, f: F) -> EitherError<E1, E>
+    where
+        F: FnOnce(E2) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError::First(e),
+            Self::Second(e) => EitherError::Second(f(e)),
+        }
+    }
+}
+
+impl<E1, E2, E3> EitherError<EitherError<E1, E2>, E3> {
+    /// Flattens the error of two nested adapters, e.g. of a `MappedPostbox` of a `MappedPostbox`
+    pub fn flatten(self) -> EitherError3<E1, E2, E3> {
+        self.into()
+    }
+}
+
+/// The error of three nested adapters, as a flat alternative to `EitherError<EitherError<E1, E2>, E3>`
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EitherError3<E1, E2, E3> {
+    First(E1),
+    Second(E2),
+    Third(E3),
+}
+
+impl<E1, E2, E3> EitherError3<E1, E2, E3> {
+    pub fn map_first<F, E>(self, f: F) -> EitherError3<E, E2, E3>
+    where
+        F: FnOnce(E1) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError3::First(f(e)),
+            Self::Second(e) => EitherError3::Second(e),
+            Self::Third(e) => EitherError3::Third(e),
+        }
+    }
+
+    pub fn map_second<F, E>(self, f: F) -> EitherError3<E1, E, E3>
+    where
+        F: FnOnce(E2) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError3::First(e),
+            Self::Second(e) => EitherError3::Second(f(e)),
+            Self::Third(e) => EitherError3::Third(e),
+        }
+    }
+
+    pub fn map_third<F, E>(self, f: F) -> EitherError3<E1, E2, E>
+    where
+        F: FnOnce(E3) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError3::First(e),
+            Self::Second(e) => EitherError3::Second(e),
+            Self::Third(e) => EitherError3::Third(f(e)),
+        }
+    }
+}
+
+impl<E1, E2, E3> From<EitherError<EitherError<E1, E2>, E3>> for EitherError3<E1, E2, E3> {
+    fn from(e: EitherError<EitherError<E1, E2>, E3>) -> Self {
+        match e {
+            EitherError::First(EitherError::First(e)) => Self::First(e),
+            EitherError::First(EitherError::Second(e)) => Self::Second(e),
+            EitherError::Second(e) => Self::Third(e),
+        }
+    }
+}
+
+impl<E1, E2, E3> fmt::Display for EitherError3<E1, E2, E3>
+where
+    E1: fmt::Display,
+    E2: fmt::Display,
+    E3: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First(e) => write!(f, "{e}"),
+            Self::Second(e) => write!(f, "{e}"),
+            Self::Third(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E1, E2, E3> std::error::Error for EitherError3<E1, E2, E3>
+where
+    E1: fmt::Display + fmt::Debug,
+    E2: fmt::Display + fmt::Debug,
+    E3: fmt::Display + fmt::Debug,
+{
+}
+
+/// The error of four nested adapters, as a flat alternative to `EitherError<EitherError<EitherError<E1, E2>, E3>, E4>`
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EitherError4<E1, E2, E3, E4> {
+    First(E1),
+    Second(E2),
+    Third(E3),
+    Fourth(E4),
+}
+
+impl<E1, E2, E3, E4> EitherError4<E1, E2, E3, E4> {
+    pub fn map_first<F, E>(self, f: F) -> EitherError4<E, E2, E3, E4>
+    where
+        F: FnOnce(E1) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError4::First(f(e)),
+            Self::Second(e) => EitherError4::Second(e),
+            Self::Third(e) => EitherError4::Third(e),
+            Self::Fourth(e) => EitherError4::Fourth(e),
+        }
+    }
+
+    pub fn map_second<F, E>(self, f: F) -> EitherError4<E1, E, E3, E4>
+    where
+        F: FnOnce(E2) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError4::First(e),
+            Self::Second(e) => EitherError4::Second(f(e)),
+            Self::Third(e) => EitherError4::Third(e),
+            Self::Fourth(e) => EitherError4::Fourth(e),
+        }
+    }
+
+    pub fn map_third<F, E>(self, f: F) -> EitherError4<E1, E2, E, E4>
+    where
+        F: FnOnce(E3) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError4::First(e),
+            Self::Second(e) => EitherError4::Second(e),
+            Self::Third(e) => EitherError4::Third(f(e)),
+            Self::Fourth(e) => EitherError4::Fourth(e),
+        }
+    }
+
+    pub fn map_fourth<F, E>(self, f: F) -> EitherError4<E1, E2, E3, E>
+    where
+        F: FnOnce(E4) -> E,
+    {
+        match self {
+            Self::First(e) => EitherError4::First(e),
+            Self::Second(e) => EitherError4::Second(e),
+            Self::Third(e) => EitherError4::Third(e),
+            Self::Fourth(e) => EitherError4::Fourth(f(e)),
+        }
+    }
+}
+
+impl<E1, E2, E3, E4> From<EitherError<EitherError3<E1, E2, E3>, E4>>
+    for EitherError4<E1, E2, E3, E4>
+{
+    fn from(e: EitherError<EitherError3<E1, E2, E3>, E4>) -> Self {
+        match e {
+            EitherError::First(EitherError3::First(e)) => Self::First(e),
+            EitherError::First(EitherError3::Second(e)) => Self::Second(e),
+            EitherError::First(EitherError3::Third(e)) => Self::Third(e),
+            EitherError::Second(e) => Self::Fourth(e),
+        }
+    }
+}
+
+impl<E1, E2, E3, E4> From<EitherError<EitherError<EitherError<E1, E2>, E3>, E4>>
+    for EitherError4<E1, E2, E3, E4>
+{
+    fn from(e: EitherError<EitherError<EitherError<E1, E2>, E3>, E4>) -> Self {
+        e.map_first(EitherError3::from).into()
+    }
+}
+
+impl<E1, E2, E3, E4> fmt::Display for EitherError4<E1, E2, E3, E4>
+where
+    E1: fmt::Display,
+    E2: fmt::Display,
+    E3: fmt::Display,
+    E4: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First(e) => write!(f, "{e}"),
+            Self::Second(e) => write!(f, "{e}"),
+            Self::Third(e) => write!(f, "{e}"),
+            Self::Fourth(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E1, E2, E3, E4> std::error::Error for EitherError4<E1, E2, E3, E4>
+where
+    E1: fmt::Display + fmt::Debug,
+    E2: fmt::Display + fmt::Debug,
+    E3: fmt::Display + fmt::Debug,
+    E4: fmt::Display + fmt::Debug,
+{
+}
+
+/// A postbox posting only the payloads matching its predicate
+///
+/// The payloads which do not match are dropped, and reported as posted.
+pub struct FilteredPostbox<PB, F> {
+    postbox: PB,
+    predicate: F,
+}
+
+impl<PB, F> FilteredPostbox<PB, F> {
+    pub const fn new(postbox: PB, predicate: F) -> Self {
+        Self { postbox, predicate }
+    }
+
+    pub fn release(self) -> PB {
+        self.postbox
+    }
+}
+
+impl<PB, F> ErrorType for FilteredPostbox<PB, F>
+where
+    PB: ErrorType,
+{
+    type Error = PB::Error;
+}
+
+impl<P, PB, F> Postbox<P> for FilteredPostbox<PB, F>
+where
+    PB: Postbox<P>,
+    F: Fn(&P) -> bool,
+{
+    fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error> {
+        if (self.predicate)(payload) {
+            self.postbox.post(payload, wait)
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+/// A postbox posting its payloads of type `P`, converted by a fallible mapping
+pub struct MappedPostbox<PB, F, P> {
+    postbox: PB,
+    map: F,
+    _payload: PhantomData<fn(&P)>,
+}
+
+impl<PB, F, P> MappedPostbox<PB, F, P> {
+    pub const fn new(postbox: PB, map: F) -> Self {
+        Self {
+            postbox,
+            map,
+            _payload: PhantomData,
+        }
+    }
+
+    pub fn release(self) -> PB {
+        self.postbox
+    }
+}
+
+impl<PB, F, P, Q, E> ErrorType for MappedPostbox<PB, F, P>
+where
+    PB: ErrorType,
+    F: Fn(&P) -> Result<Q, E>,
+    E: fmt::Debug,
+{
+    type Error = EitherError<PB::Error, E>;
+}
+
+impl<PB, F, P, Q, E> Postbox<P> for MappedPostbox<PB, F, P>
+where
+    PB: Postbox<Q>,
+    F: Fn(&P) -> Result<Q, E>,
+    E: fmt::Debug,
+{
+    fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error> {
+        let payload = (self.map)(payload).map_err(EitherError::Second)?;
+
+        self.postbox
+            .post(&payload, wait)
+            .map_err(EitherError::First)
+    }
+}
+
+/// Subscribes `callback` to the payloads of `bus` matching `predicate`
+pub fn subscribe_filtered<B, P, F, C>(
+    bus: &B,
+    predicate: F,
+    mut callback: C,
+) -> Result<B::Subscription<'_>, B::Error>
+where
+    B: EventBus<P>,
+    F: Fn(&P) -> bool + Send + 'static,
+    C: FnMut(&P) + Send + 'static,
+{
+    bus.subscribe(move |payload: &P| {
+        if predicate(payload) {
+            callback(payload);
+        }
+    })
+}
+
+/// Subscribes `callback` to the payloads of `bus` which `map` converts, skipping those for which it returns `None`
+pub fn subscribe_map<B, P, Q, F, C>(
+    bus: &B,
+    map: F,
+    mut callback: C,
+) -> Result<B::Subscription<'_>, B::Error>
+where
+    B: EventBus<P>,
+    F: Fn(&P) -> Option<Q> + Send + 'static,
+    C: FnMut(&Q) + Send + 'static,
+{
+    bus.subscribe(move |payload: &P| {
+        if let Some(payload) = map(payload) {
+            callback(&payload);
+        }
+    })
+}
+
+#[cfg(feature = "alloc")]
+struct PollState<P, const Q: usize> {
+    queue: heapless::Deque<P, Q>,
+    dropped: usize,
+}
+
+#[cfg(feature = "alloc")]
+type SharedPollState<CV, P, const Q: usize> = (
+    Mutex<<CV as RawCondvar>::RawMutex, PollState<P, Q>>,
+    Condvar<CV>,
+);
+
+/// A subscription buffering up to `Q` of the payloads of a bus, to be received (with a timeout) rather than
+/// handled in a callback
+///
+/// When the buffer is full, the oldest payload is dropped. The times of the timeouts are taken from `clock`,
+/// so that spurious wakeups of the condvar are not reported as timeouts.
+/// Dropping the subscription drops the subscription of the bus.
+#[cfg(feature = "alloc")]
+pub struct PollableSubscription<CV, P, S, C, const Q: usize = 8>
+where
+    CV: RawCondvar,
+{
+    state: Arc<SharedPollState<CV, P, Q>>,
+    clock: C,
+    _subscription: S,
+}
+
+#[cfg(feature = "alloc")]
+impl<CV, P, S, C, const Q: usize> PollableSubscription<CV, P, S, C, Q>
+where
+    CV: RawCondvar,
+    C: Monotonic,
+{
+    pub fn try_recv(&self) -> Option<P> {
+        self.state.0.lock().queue.pop_front()
+    }
+
+    pub fn recv(&self) -> P {
+        let (mutex, condvar) = &*self.state;
+
+        let mut state = mutex.lock();
+
+        loop {
+            if let Some(payload) = state.queue.pop_front() {
+                break payload;
+            }
+
+            state = condvar.wait(state);
+        }
+    }
+
+    /// Returns the next payload, or `None` if none was posted within `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<P> {
+        let (mutex, condvar) = &*self.state;
+
+        let deadline = self.clock.now() + timeout;
+
+        let mut state = mutex.lock();
+
+        loop {
+            if let Some(payload) = state.queue.pop_front() {
+                break Some(payload);
+            }
+
+            let now = self.clock.now();
+
+            if now >= deadline {
+                break None;
+            }
+
+            state = condvar.wait_timeout(state, deadline - now).0;
+        }
+    }
+
+    /// How many payloads were dropped so far, because the buffer was full
+    pub fn dropped(&self) -> usize {
+        self.state.0.lock().dropped
+    }
+}
+
+/// Subscribes to `bus`, returning a subscription whose payloads are received with `recv` or `recv_timeout`
+#[cfg(feature = "alloc")]
+pub fn subscribe_pollable<CV, P, B, C, const Q: usize>(
+    bus: &B,
+    clock: C,
+) -> Result<PollableSubscription<CV, P, B::Subscription<'_>, C, Q>, B::Error>
+where
+    CV: RawCondvar + Send + Sync + 'static,
+    CV::RawMutex: Send + Sync + 'static,
+    P: Clone + Send + 'static,
+    B: EventBus<P>,
+{
+    let state: Arc<SharedPollState<CV, P, Q>> = Arc::new((
+        Mutex::new(PollState {
+            queue: heapless::Deque::new(),
+            dropped: 0,
+        }),
+        Condvar::new(),
+    ));
+
+    let subscription = {
+        let state = state.clone();
+
+        bus.subscribe(move |payload: &P| {
+            let (mutex, condvar) = &*state;
+
+            let mut state = mutex.lock();
+
+            if state.queue.is_full() {
+                state.queue.pop_front();
+                state.dropped += 1;
+            }
+
+            let _ = state.queue.push_back(payload.clone());
+
+            condvar.notify_all();
+        })?
+    };
+
+    Ok(PollableSubscription {
+        state,
+        clock,
+        _subscription: subscription,
+    })
+}
+
+/// A payload forwarded by a `CoalescingPostbox`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Coalesced<P> {
+    pub payload: P,
+    /// How many earlier payloads (with the same key) this payload replaced
+    pub coalesced: usize,
+}
+
+#[cfg(feature = "alloc")]
+struct CoalesceState<P, K, const N: usize> {
+    pending: heapless::Vec<(K, Coalesced<P>), N>,
+    armed: bool,
+}
+
+#[cfg(feature = "alloc")]
+struct CoalesceShared<R, PB, P, K, const N: usize>
+where
+    R: RawMutex,
+{
+    postbox: PB,
+    state: Mutex<R, CoalesceState<P, K, N>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<R, PB, P, K, const N: usize> CoalesceShared<R, PB, P, K, N>
+where
+    R: RawMutex,
+    PB: Postbox<Coalesced<P>>,
+{
+    fn forward(&self, wait: Option<Duration>) -> Result<(), PB::Error> {
+        let pending = core::mem::take(&mut self.state.lock().pending);
+
+        for (_, payload) in &pending {
+            self.postbox.post(payload, wait)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A postbox holding the payloads posted to it for `interval`, and then forwarding only the most recent one
+/// of each key (up to `N` keys) to the wrapped postbox, as a `Coalesced` payload
+///
+/// The interval starts with the first payload posted while none is pending. Posting a payload
+/// with a new key while `N` keys are pending forwards the pending payloads right away, as does `flush`.
+/// The errors of the wrapped postbox when forwarding on the expiry of the interval are dropped.
+#[cfg(feature = "alloc")]
+pub struct CoalescingPostbox<R, T, PB, P, K, F, const N: usize = 8>
+where
+    R: RawMutex,
+{
+    timer: Mutex<R, T>,
+    shared: Arc<CoalesceShared<R, PB, P, K, N>>,
+    key: F,
+    interval: Duration,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, R, T, PB, P, const N: usize> CoalescingPostbox<R, T, PB, P, (), fn(&P), N>
+where
+    R: RawMutex + Send + Sync + 'static,
+    T: OnceTimer + 'a,
+    PB: Postbox<Coalesced<P>> + Send + Sync + 'static,
+    P: Copy + Send + 'static,
+{
+    /// Creates a postbox forwarding only the most recent of all the payloads posted during an interval
+    pub fn new<S>(timer_service: &'a S, postbox: PB, interval: Duration) -> Result<Self, T::Error>
+    where
+        S: TimerService<Timer<'a> = T, Error = T::Error>,
+    {
+        Self::with_key(timer_service, postbox, interval, |_| ())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, R, T, PB, P, K, F, const N: usize> CoalescingPostbox<R, T, PB, P, K, F, N>
+where
+    R: RawMutex + Send + Sync + 'static,
+    T: OnceTimer + 'a,
+    PB: Postbox<Coalesced<P>> + Send + Sync + 'static,
+    P: Copy + Send + 'static,
+    K: PartialEq + Send + 'static,
+    F: Fn(&P) -> K,
+{
+    /// Creates a postbox forwarding the most recent payload of each of the keys returned by `key`
+    pub fn with_key<S>(
+        timer_service: &'a S,
+        postbox: PB,
+        interval: Duration,
+        key: F,
+    ) -> Result<Self, T::Error>
+    where
+        S: TimerService<Timer<'a> = T, Error = T::Error>,
+    {
+        let interval = timer_service.capabilities().clamp(interval);
+
+        let shared = Arc::new(CoalesceShared {
+            postbox,
+            state: Mutex::new(CoalesceState {
+                pending: heapless::Vec::new(),
+                armed: false,
+            }),
+        });
+
+        let timer = {
+            let shared = shared.clone();
+
+            timer_service.timer(move || {
+                shared.state.lock().armed = false;
+
+                let _ = shared.forward(None);
+            })?
+        };
+
+        Ok(Self {
+            timer: Mutex::new(timer),
+            shared,
+            key,
+            interval,
+        })
+    }
+
+    /// The interval, clamped to the capabilities of the timer service
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn postbox(&self) -> &PB {
+        &self.shared.postbox
+    }
+
+    /// Forwards the pending payloads right away
+    pub fn flush(&self) -> Result<(), PB::Error> {
+        self.shared.forward(None)
+    }
+
+    fn coalesce(&self, payload: &P, wait: Option<Duration>) -> Result<(), CoalesceError<PB, T>> {
+        let key = (self.key)(payload);
+
+        let arm = loop {
+            let mut state = self.shared.state.lock();
+
+            if let Some((_, pending)) = state.pending.iter_mut().find(|(k, _)| *k == key) {
+                pending.payload = *payload;
+                pending.coalesced += 1;
+            } else if state.pending.is_full() {
+                drop(state);
+
+                self.shared.forward(wait).map_err(EitherError::First)?;
+
+                continue;
+            } else {
+                let _ = state.pending.push((
+                    key,
+                    Coalesced {
+                        payload: *payload,
+                        coalesced: 0,
+                    },
+                ));
+            }
+
+            break !core::mem::replace(&mut state.armed, true);
+        };
+
+        if arm {
+            self.timer
+                .lock()
+                .after(self.interval)
+                .map_err(EitherError::Second)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+type CoalesceError<PB, T> =
+    EitherError<<PB as ErrorType>::Error, <T as crate::timer::ErrorType>::Error>;
+
+#[cfg(feature = "alloc")]
+impl<R, T, PB, P, K, F, const N: usize> ErrorType for CoalescingPostbox<R, T, PB, P, K, F, N>
+where
+    R: RawMutex,
+    T: OnceTimer,
+    PB: ErrorType,
+{
+    type Error = CoalesceError<PB, T>;
+}
+
+#[cfg(feature = "alloc")]
+impl<R, T, PB, P, K, F, const N: usize> Postbox<P> for CoalescingPostbox<R, T, PB, P, K, F, N>
+where
+    R: RawMutex + Send + Sync + 'static,
+    T: OnceTimer,
+    PB: Postbox<Coalesced<P>> + Send + Sync + 'static,
+    P: Copy + Send + 'static,
+    K: PartialEq + Send + 'static,
+    F: Fn(&P) -> K,
+{
+    /// Holds the payload until the interval expires; `wait` only applies when the pending payloads
+    /// are forwarded to make room for the key of the payload
+    fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error> {
+        self.coalesce(payload, wait).map(|_| true)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::fmt::Debug;
+
+    use crate::event_bus::asynch::{ErrorType, Receiver, Sender};
+
+    pub use super::{EitherError, EitherError3, EitherError4, FilteredPostbox, MappedPostbox};
+
+    #[cfg(feature = "alloc")]
+    pub use super::{Coalesced, CoalescingPostbox};
+
+    #[cfg(feature = "alloc")]
+    impl<R, T, PB, P, K, F, const N: usize> Sender for CoalescingPostbox<R, T, PB, P, K, F, N>
+    where
+        R: crate::utils::mutex::RawMutex + Send + Sync + 'static,
+        T: crate::timer::OnceTimer,
+        PB: crate::event_bus::Postbox<Coalesced<P>> + Send + Sync + 'static,
+        P: Copy + Send + 'static,
+        K: PartialEq + Send + 'static,
+        F: Fn(&P) -> K,
+    {
+        type Data = P;
+
+        /// Never waits, as the payload is only held until the interval expires
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            self.coalesce(&value, None)
+        }
+    }
+
+    impl<PB, F> Sender for FilteredPostbox<PB, F>
+    where
+        PB: Sender,
+        F: Fn(&PB::Data) -> bool,
+    {
+        type Data = PB::Data;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            if (self.predicate)(&value) {
+                self.postbox.send(value).await
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl<PB, F, P, E> Sender for MappedPostbox<PB, F, P>
+    where
+        PB: Sender,
+        F: Fn(&P) -> Result<PB::Data, E>,
+        P: Send,
+        E: Debug,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            let value = (self.map)(&value).map_err(EitherError::Second)?;
+
+            self.postbox.send(value).await.map_err(EitherError::First)
+        }
+    }
+
+    /// A receiver skipping the payloads not matching its predicate
+    pub struct FilteredReceiver<R, F> {
+        receiver: R,
+        predicate: F,
+    }
+
+    impl<R, F> FilteredReceiver<R, F> {
+        pub const fn new(receiver: R, predicate: F) -> Self {
+            Self {
+                receiver,
+                predicate,
+            }
+        }
+
+        pub fn release(self) -> R {
+            self.receiver
+        }
+    }
+
+    impl<R, F> ErrorType for FilteredReceiver<R, F>
+    where
+        R: ErrorType,
+    {
+        type Error = R::Error;
+    }
+
+    impl<R, F> Receiver for FilteredReceiver<R, F>
+    where
+        R: Receiver,
+        F: Fn(&R::Data) -> bool,
+    {
+        type Data = R::Data;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            loop {
+                let value = self.receiver.recv().await?;
+
+                if (self.predicate)(&value) {
+                    break Ok(value);
+                }
+            }
+        }
+    }
+
+    /// A receiver converting its payloads with a fallible mapping
+    pub struct MappedReceiver<R, F> {
+        receiver: R,
+        map: F,
+    }
+
+    impl<R, F> MappedReceiver<R, F> {
+        pub const fn new(receiver: R, map: F) -> Self {
+            Self { receiver, map }
+        }
+
+        pub fn release(self) -> R {
+            self.receiver
+        }
+    }
+
+    impl<R, F, Q, E> ErrorType for MappedReceiver<R, F>
+    where
+        R: Receiver,
+        F: Fn(R::Data) -> Result<Q, E>,
+        E: Debug,
+    {
+        type Error = EitherError<R::Error, E>;
+    }
+
+    impl<R, F, Q, E> Receiver for MappedReceiver<R, F>
+    where
+        R: Receiver,
+        F: Fn(R::Data) -> Result<Q, E>,
+        Q: Send,
+        E: Debug,
+    {
+        type Data = Q;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            let value = self.receiver.recv().await.map_err(EitherError::First)?;
+
+            (self.map)(value).map_err(EitherError::Second)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::TryFrom;
+
+    use std::sync::{Arc, Mutex as StdMutex};
+
+    use crate::sys_time::StdMonotonic;
+    use crate::test_event_bus::MockBus;
+    use crate::test_timer::MockTimerService;
+    use crate::utils::mutex::{StdRawCondvar, StdRawMutex};
+
+    use super::*;
+
+    struct Temperature;
+
+    impl Topic for Temperature {
+        type Payload = f32;
+
+        const NAME: &'static str = "temperature";
+    }
+
+    struct Button;
+
+    impl Topic for Button {
+        type Payload = (u8, bool);
+
+        const NAME: &'static str = "button";
+    }
+
+    fn collect<T>() -> (Arc<StdMutex<Vec<T>>>, impl FnMut(&T) + Send + 'static)
+    where
+        T: Clone + Send + 'static,
+    {
+        let collected = Arc::new(StdMutex::new(Vec::new()));
+
+        let callback = {
+            let collected = collected.clone();
+
+            move |payload: &T| collected.lock().unwrap().push(payload.clone())
+        };
+
+        (collected, callback)
+    }
+
+    #[test]
+    fn envelope() {
+        let envelope = RawEnvelope::<8>::new::<Button>(&(3, true));
+
+        assert!(envelope.is::<Button>());
+        assert!(!envelope.is::<Temperature>());
+        assert_eq!(envelope.get::<Button>(), Some((3, true)));
+        assert_eq!(envelope.get::<Temperature>(), None);
+        assert_eq!(envelope.topic_name(), "button");
+        assert_eq!(
+            format!("{envelope:?}"),
+            r#"RawEnvelope { topic: "button" }"#
+        );
+    }
+
+    #[test]
+    fn topics() {
+        let topics = Topics::<_, 8>::new(MockBus::new());
+
+        let (temperatures, callback) = collect();
+        let _temperatures = topics.subscribe::<Temperature, _>(callback).unwrap();
+
+        let (buttons, callback) = collect();
+        let buttons_subscription = topics.subscribe::<Button, _>(callback).unwrap();
+
+        assert!(topics.post::<Temperature>(&21.5, None).unwrap());
+        assert!(topics.post::<Button>(&(1, true), None).unwrap());
+        assert!(topics.post::<Temperature>(&22.0, None).unwrap());
+
+        assert_eq!(*temperatures.lock().unwrap(), [21.5, 22.0]);
+        assert_eq!(*buttons.lock().unwrap(), [(1, true)]);
+
+        let topics_posted = topics
+            .bus()
+            .posted()
+            .iter()
+            .map(RawEnvelope::topic_name)
+            .collect::<Vec<_>>();
+        assert_eq!(topics_posted, ["temperature", "button", "temperature"]);
+
+        core::mem::drop(buttons_subscription);
+        assert_eq!(topics.bus().subscribers(), 1);
+
+        // The results of the bus are passed through
+        topics.bus().set_capacity(Some(3));
+        assert!(!topics.post::<Button>(&(2, false), None).unwrap());
+
+        topics.bus().set_fail(true);
+        assert!(topics.post::<Button>(&(2, false), None).is_err());
+        assert!(topics.subscribe::<Button, _>(|_| ()).is_err());
+
+        assert_eq!(topics.release().posted().len(), 3);
+    }
+
+    #[test]
+    fn filtered_postbox() {
+        let bus = MockBus::new();
+        let postbox = FilteredPostbox::new(&bus, |value: &u32| value % 2 == 0);
+
+        for value in 1..=4 {
+            // The payloads which do not match are reported as posted
+            assert!(postbox.post(&value, None).unwrap());
+        }
+
+        bus.set_capacity(Some(2));
+
+        assert!(postbox.post(&5, None).unwrap());
+        assert!(!postbox.post(&6, None).unwrap());
+
+        assert_eq!(postbox.release().posted(), [2, 4]);
+    }
+
+    #[test]
+    fn mapped_postbox() {
+        let bus = MockBus::new();
+        let postbox = MappedPostbox::new(&bus, |value: &i32| u8::try_from(*value));
+
+        assert!(postbox.post(&42, None).unwrap());
+        assert!(matches!(
+            postbox.post(&-1, None),
+            Err(EitherError::Second(_))
+        ));
+
+        bus.set_fail(true);
+        assert!(matches!(
+            postbox.post(&1, None),
+            Err(EitherError::First(()))
+        ));
+
+        assert_eq!(bus.posted(), [42_u8]);
+    }
+
+    #[test]
+    fn nested_mapped_postboxes() {
+        let bus = MockBus::new();
+        let inner = MappedPostbox::new(&bus, |value: &i32| u8::try_from(*value));
+        let postbox = MappedPostbox::new(inner, |value: &i64| i32::try_from(*value));
+
+        assert!(postbox.post(&42, None).unwrap());
+
+        // The error of the outer mapping is the last one once flattened
+        assert!(matches!(
+            postbox.post(&i64::MAX, None).map_err(EitherError::flatten),
+            Err(EitherError3::Third(_))
+        ));
+        assert!(matches!(
+            postbox.post(&-1, None).map_err(EitherError::flatten),
+            Err(EitherError3::Second(_))
+        ));
+
+        bus.set_fail(true);
+        assert!(matches!(
+            postbox.post(&1, None).map_err(EitherError::flatten),
+            Err(EitherError3::First(()))
+        ));
+
+        assert_eq!(bus.posted(), [42_u8]);
+    }
+
+    type Error3 = EitherError3<u8, &'static str, bool>;
+    type Error4 = EitherError4<u8, &'static str, bool, char>;
+
+    #[test]
+    fn either3() {
+        let map = |e: Error3| {
+            e.map_first(u32::from)
+                .map_second(str::len)
+                .map_third(u8::from)
+        };
+
+        assert!(matches!(map(Error3::First(1)), EitherError3::First(1_u32)));
+        assert!(matches!(
+            map(Error3::Second("four")),
+            EitherError3::Second(4)
+        ));
+        assert!(matches!(map(Error3::Third(true)), EitherError3::Third(1)));
+
+        let nested = |e| Error3::from(e);
+
+        assert!(matches!(
+            nested(EitherError::First(EitherError::First(1))),
+            EitherError3::First(1)
+        ));
+        assert!(matches!(
+            nested(EitherError::First(EitherError::Second("second"))),
+            EitherError3::Second("second")
+        ));
+        assert!(matches!(
+            nested(EitherError::Second(false)),
+            EitherError3::Third(false)
+        ));
+
+        let error: &dyn std::error::Error = &Error3::Second("failed");
+        assert_eq!(error.to_string(), "failed");
+    }
+
+    #[test]
+    fn either4() {
+        let map = |e: Error4| {
+            e.map_first(u32::from)
+                .map_second(str::len)
+                .map_third(u8::from)
+                .map_fourth(u32::from)
+        };
+
+        assert!(matches!(map(Error4::First(1)), EitherError4::First(1_u32)));
+        assert!(matches!(
+            map(Error4::Second("four")),
+            EitherError4::Second(4)
+        ));
+        assert!(matches!(map(Error4::Third(true)), EitherError4::Third(1)));
+        assert!(matches!(map(Error4::Fourth('a')), EitherError4::Fourth(97)));
+
+        // From the error of three nested adapters, either flattened already or not
+        let flattened = |e| Error4::from(e);
+
+        assert!(matches!(
+            flattened(EitherError::First(EitherError3::Third(true))),
+            EitherError4::Third(true)
+        ));
+        assert!(matches!(
+            flattened(EitherError::Second('a')),
+            EitherError4::Fourth('a')
+        ));
+
+        let nested = |e| Error4::from(e);
+
+        assert!(matches!(
+            nested(EitherError::First(EitherError::First(EitherError::First(
+                1
+            )))),
+            EitherError4::First(1)
+        ));
+        assert!(matches!(
+            nested(EitherError::First(EitherError::First(EitherError::Second(
+                "second"
+            )))),
+            EitherError4::Second("second")
+        ));
+        assert!(matches!(
+            nested(EitherError::First(EitherError::Second(true))),
+            EitherError4::Third(true)
+        ));
+        assert!(matches!(
+            nested(EitherError::Second('a')),
+            EitherError4::Fourth('a')
+        ));
+
+        let error: &dyn std::error::Error = &Error4::Fourth('z');
+        assert_eq!(error.to_string(), "z");
+    }
+
+    #[test]
+    fn subscribe_adapters() {
+        let bus = MockBus::new();
+
+        let (even, callback) = collect();
+        let _even = subscribe_filtered(&bus, |value: &u32| value % 2 == 0, callback).unwrap();
+
+        let (halves, callback) = collect();
+        let _halves = subscribe_map(
+            &bus,
+            |value: &u32| (value % 2 == 0).then(|| value / 2),
+            callback,
+        )
+        .unwrap();
+
+        for value in 1..=6 {
+            bus.post(&value, None).unwrap();
+        }
+
+        assert_eq!(*even.lock().unwrap(), [2, 4, 6]);
+        assert_eq!(*halves.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[cfg(all(feature = "nightly", feature = "asyncify"))]
+    #[test]
+    fn async_adapters() {
+        use crate::event_bus::asynch::{Receiver, Sender};
+        use crate::utils::asyncify::block_on;
+        use crate::utils::asyncify::event_bus::bounded_postbox;
+        use crate::utils::mutex::StdRawMutex;
+
+        let (sender, receiver) = bounded_postbox::<StdRawMutex, i32, 8>();
+
+        let sender = FilteredPostbox::new(sender, |value: &i32| *value != 0);
+
+        block_on(async {
+            for value in [0, 1, 0, -2, 4, 300] {
+                sender.send(value).await.unwrap();
+            }
+        });
+
+        let receiver = asynch::MappedReceiver::new(
+            asynch::FilteredReceiver::new(receiver, |value: &i32| *value > 0),
+            u8::try_from,
+        );
+
+        assert_eq!(block_on(receiver.recv()).unwrap(), 1);
+        assert_eq!(block_on(receiver.recv()).unwrap(), 4);
+        assert!(matches!(
+            block_on(receiver.recv()),
+            Err(EitherError::Second(_))
+        ));
+    }
+
+    #[test]
+    fn pollable_subscription() {
+        let bus = MockBus::<u32>::new();
+
+        let subscription =
+            subscribe_pollable::<StdRawCondvar, u32, _, _, 4>(&bus, StdMonotonic).unwrap();
+
+        assert_eq!(subscription.try_recv(), None);
+
+        bus.post(&1, None).unwrap();
+        assert_eq!(subscription.try_recv(), Some(1));
+
+        // The oldest payloads are dropped once the buffer is full
+        for value in 2..=7 {
+            bus.post(&value, None).unwrap();
+        }
+
+        assert_eq!(subscription.dropped(), 2);
+
+        for value in 4..=7 {
+            assert_eq!(subscription.recv(), value);
+        }
+
+        assert_eq!(subscription.try_recv(), None);
+
+        core::mem::drop(subscription);
+        assert_eq!(bus.subscribers(), 0);
+    }
+
+    #[test]
+    fn pollable_subscription_producer_thread() {
+        let bus = MockBus::<u32>::new();
+
+        let subscription =
+            subscribe_pollable::<StdRawCondvar, u32, _, _, 8>(&bus, StdMonotonic).unwrap();
+
+        // Timed out
+        let start = std::time::Instant::now();
+        assert_eq!(subscription.recv_timeout(Duration::from_millis(20)), None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // Received
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for value in 1..=3 {
+                    std::thread::sleep(Duration::from_millis(10));
+
+                    bus.post(&value, None).unwrap();
+                }
+            });
+
+            for value in 1..=3 {
+                assert_eq!(
+                    subscription.recv_timeout(Duration::from_secs(5)),
+                    Some(value)
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn pollable_subscription_clock() {
+        let bus = MockBus::<u32>::new();
+        let clock = MockTimerService::new();
+
+        let subscription =
+            subscribe_pollable::<StdRawCondvar, u32, _, _, 8>(&bus, clock.clone()).unwrap();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+
+                bus.post(&1, None).unwrap();
+            });
+
+            // The condvar waits time out well before the payload is posted, but the deadline
+            // is only reached as per the clock, which does not move
+            assert_eq!(subscription.recv_timeout(Duration::from_millis(5)), Some(1));
+        });
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+
+                clock.set_now(Duration::from_secs(1));
+            });
+
+            assert_eq!(subscription.recv_timeout(Duration::from_millis(500)), None);
+        });
+    }
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    /// A postbox recording the payloads forwarded to it, for the lifetime of the test
+    fn forwarded() -> &'static MockBus<Coalesced<u32>> {
+        Box::leak(Box::default())
+    }
+
+    #[test]
+    fn coalescing_burst() {
+        let service = MockTimerService::new();
+        let sink = forwarded();
+
+        let postbox =
+            CoalescingPostbox::<StdRawMutex, _, _, _, _, _>::new(&service, sink, INTERVAL).unwrap();
+
+        for value in 0..1000_u32 {
+            postbox.post(&value, None).unwrap();
+        }
+
+        // The timer is only armed by the first payload of the interval
+        assert_eq!(service.scheduled(), 1);
+        assert!(sink.posted().is_empty());
+
+        service.advance(INTERVAL);
+
+        assert_eq!(
+            sink.posted(),
+            [Coalesced {
+                payload: 999,
+                coalesced: 999
+            }]
+        );
+
+        postbox.post(&1000, None).unwrap();
+        service.advance(INTERVAL);
+
+        assert_eq!(
+            sink.posted()[1..],
+            [Coalesced {
+                payload: 1000,
+                coalesced: 0
+            }]
+        );
+        assert_eq!(service.scheduled(), 0);
+    }
+
+    #[test]
+    fn coalescing_keys() {
+        let service = MockTimerService::new();
+        let sink = forwarded();
+
+        let postbox = CoalescingPostbox::<StdRawMutex, _, _, _, _, _>::with_key(
+            &service,
+            sink,
+            INTERVAL,
+            |value: &u32| value % 3,
+        )
+        .unwrap();
+
+        for value in 0..1000_u32 {
+            postbox.post(&value, None).unwrap();
+        }
+
+        service.advance(INTERVAL);
+
+        // In the order the keys were first posted
+        assert_eq!(
+            sink.posted(),
+            [
+                Coalesced {
+                    payload: 999,
+                    coalesced: 333
+                },
+                Coalesced {
+                    payload: 997,
+                    coalesced: 332
+                },
+                Coalesced {
+                    payload: 998,
+                    coalesced: 332
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn coalescing_flush() {
+        let service = MockTimerService::new().with_capabilities(crate::timer::TimerCapabilities {
+            resolution: Some(INTERVAL * 2),
+            ..Default::default()
+        });
+        let sink = forwarded();
+
+        let postbox = CoalescingPostbox::<StdRawMutex, _, _, _, _, _, 2>::with_key(
+            &service,
+            sink,
+            INTERVAL,
+            |value: &u32| *value,
+        )
+        .unwrap();
+
+        assert_eq!(postbox.interval(), INTERVAL * 2);
+
+        postbox.post(&1, None).unwrap();
+        postbox.post(&2, None).unwrap();
+        assert!(sink.posted().is_empty());
+
+        // A third key makes room by forwarding the two pending ones
+        postbox.post(&3, None).unwrap();
+        assert_eq!(
+            sink.posted().iter().map(|c| c.payload).collect::<Vec<_>>(),
+            [1, 2]
+        );
+
+        postbox.flush().unwrap();
+        assert_eq!(
+            sink.posted().iter().map(|c| c.payload).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        // Nothing is left to forward on the expiry of the interval
+        service.advance(INTERVAL * 2);
+        assert_eq!(sink.posted().len(), 3);
+
+        sink.set_fail(true);
+
+        postbox.post(&4, None).unwrap();
+        assert_eq!(postbox.flush(), Err(()));
+    }
+
+    #[cfg(all(feature = "nightly", feature = "asyncify"))]
+    #[test]
+    fn coalescing_send() {
+        use crate::event_bus::asynch::Sender;
+        use crate::utils::asyncify::block_on;
+
+        let service = MockTimerService::new();
+        let sink = forwarded();
+
+        let postbox =
+            CoalescingPostbox::<StdRawMutex, _, _, _, _, _>::new(&service, sink, INTERVAL).unwrap();
+
+        block_on(async {
+            for value in 0..1000_u32 {
+                postbox.send(value).await.unwrap();
+            }
+        });
+
+        service.advance(INTERVAL);
+
+        assert_eq!(
+            sink.posted(),
+            [Coalesced {
+                payload: 999,
+                coalesced: 999
+            }]
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use core::fmt;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+use crate::event_bus::{ErrorType, Postbox};
+use crate::utils::mutex::{Mutex, RawMutex};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StaticBusError {
+    /// All the subscriber slots of the bus are taken
+    NoFreeSlot,
+    /// The payload was not delivered to this many subscribers, because their queues were full
+    Overflow(usize),
+}
+
+impl fmt::Display for StaticBusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoFreeSlot => write!(f, "No free subscriber slot"),
+            Self::Overflow(missed) => {
+                write!(f, "Overflow, {missed} subscribers missed the payload")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StaticBusError {}
+
+#[cfg(feature = "alloc")]
+type Callback<P> = Box<dyn FnMut(&P) + Send>;
+
+struct Slot<P, const DEPTH: usize> {
+    subscribed: bool,
+    queue: heapless::Deque<P, DEPTH>,
+    waker: Option<Waker>,
+    #[cfg(feature = "alloc")]
+    callback: Option<Callback<P>>,
+}
+
+impl<P, const DEPTH: usize> Slot<P, DEPTH> {
+    const FREE: Self = Self {
+        subscribed: false,
+        queue: heapless::Deque::new(),
+        waker: None,
+        #[cfg(feature = "alloc")]
+        callback: None,
+    };
+}
+
+/// An event bus with `SUBS` subscriber slots, each queueing up to `DEPTH` payloads, which needs no allocations
+/// and can be placed in a `static`
+///
+/// Posting never blocks, regardless of the `wait` duration, so it can be done from an ISR, provided that `R`
+/// is a raw mutex which is safe to lock there (e.g. one based on a critical section).
+/// The payloads queued for a subscriber are received with `StaticSubscription::try_recv`
+/// or with the async `StaticSubscription::recv`.
+///
+/// With the `alloc` feature, the bus is also a (blocking) `EventBus`, whose callbacks are called from
+/// the posting context, with the bus locked, so they must not post to or subscribe to the bus.
+pub struct StaticEventBus<R, P, const SUBS: usize, const DEPTH: usize>(
+    Mutex<R, [Slot<P, DEPTH>; SUBS]>,
+)
+where
+    R: RawMutex;
+
+impl<R, P, const SUBS: usize, const DEPTH: usize> StaticEventBus<R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    pub const fn new() -> Self {
+        Self(Mutex::new([Slot::FREE; SUBS]))
+    }
+
+    /// Subscribes to the payloads posted from now on
+    pub fn subscribe_queue(
+        &self,
+    ) -> Result<StaticSubscription<'_, R, P, SUBS, DEPTH>, StaticBusError> {
+        self.take_slot(|_| ())
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.0.lock().iter().filter(|slot| slot.subscribed).count()
+    }
+
+    /// Delivers a clone of `payload` to all subscribers, failing with `StaticBusError::Overflow`
+    /// if some of them did not get it because their queues were full
+    pub fn post_payload(&self, payload: &P) -> Result<(), StaticBusError>
+    where
+        P: Clone,
+    {
+        let mut slots = self.0.lock();
+
+        let mut missed = 0;
+
+        for slot in slots.iter_mut().filter(|slot| slot.subscribed) {
+            #[cfg(feature = "alloc")]
+            if let Some(callback) = slot.callback.as_mut() {
+                callback(payload);
+                continue;
+            }
+
+            if slot.queue.push_back(payload.clone()).is_err() {
+                missed += 1;
+            } else if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+
+        if missed > 0 {
+            Err(StaticBusError::Overflow(missed))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn take_slot<F>(
+        &self,
+        init: F,
+    ) -> Result<StaticSubscription<'_, R, P, SUBS, DEPTH>, StaticBusError>
+    where
+        F: FnOnce(&mut Slot<P, DEPTH>),
+    {
+        let mut slots = self.0.lock();
+
+        let index = slots
+            .iter()
+            .position(|slot| !slot.subscribed)
+            .ok_or(StaticBusError::NoFreeSlot)?;
+
+        let slot = &mut slots[index];
+        slot.subscribed = true;
+        init(slot);
+
+        Ok(StaticSubscription { bus: self, index })
+    }
+}
+
+impl<R, P, const SUBS: usize, const DEPTH: usize> Default for StaticEventBus<R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, P, const SUBS: usize, const DEPTH: usize> ErrorType for StaticEventBus<R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    type Error = StaticBusError;
+}
+
+impl<R, P, const SUBS: usize, const DEPTH: usize> Postbox<P> for StaticEventBus<R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+    P: Clone,
+{
+    fn post(&self, payload: &P, _wait: Option<Duration>) -> Result<bool, Self::Error> {
+        self.post_payload(payload).map(|_| true)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<R, P, const SUBS: usize, const DEPTH: usize> crate::event_bus::EventBus<P>
+    for StaticEventBus<R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    type Subscription<'a> = StaticSubscription<'a, R, P, SUBS, DEPTH> where Self: 'a;
+
+    fn subscribe<F>(&self, callback: F) -> Result<Self::Subscription<'_>, Self::Error>
+    where
+        F: FnMut(&P) + Send + 'static,
+    {
+        self.take_slot(move |slot| slot.callback = Some(Box::new(callback)))
+    }
+}
+
+/// A subscription to a `StaticEventBus`; dropping it frees its slot
+pub struct StaticSubscription<'a, R, P, const SUBS: usize, const DEPTH: usize>
+where
+    R: RawMutex,
+{
+    bus: &'a StaticEventBus<R, P, SUBS, DEPTH>,
+    index: usize,
+}
+
+impl<'a, R, P, const SUBS: usize, const DEPTH: usize> StaticSubscription<'a, R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    pub fn try_recv(&self) -> Option<P> {
+        self.bus.0.lock()[self.index].queue.pop_front()
+    }
+
+    pub async fn recv(&self) -> P {
+        poll_fn(|cx| {
+            let mut slots = self.bus.0.lock();
+            let slot = &mut slots[self.index];
+
+            if let Some(payload) = slot.queue.pop_front() {
+                Poll::Ready(payload)
+            } else {
+                slot.waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<'a, R, P, const SUBS: usize, const DEPTH: usize> Drop
+    for StaticSubscription<'a, R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        self.bus.0.lock()[self.index] = Slot::FREE;
+    }
+}
+
+impl<'a, R, P, const SUBS: usize, const DEPTH: usize> ErrorType
+    for StaticSubscription<'a, R, P, SUBS, DEPTH>
+where
+    R: RawMutex,
+{
+    type Error = StaticBusError;
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use crate::event_bus::asynch::{EventBus, Receiver, Sender};
+    use crate::utils::mutex::RawMutex;
+
+    pub use super::{StaticBusError, StaticEventBus, StaticSubscription};
+
+    impl<R, P, const SUBS: usize, const DEPTH: usize> Sender for StaticEventBus<R, P, SUBS, DEPTH>
+    where
+        R: RawMutex,
+        P: Clone + Send,
+    {
+        type Data = P;
+
+        async fn send(&self, value: Self::Data) -> Result<(), Self::Error> {
+            self.post_payload(&value)
+        }
+    }
+
+    impl<'a, R, P, const SUBS: usize, const DEPTH: usize> Receiver
+        for StaticSubscription<'a, R, P, SUBS, DEPTH>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Data = P;
+
+        async fn recv(&self) -> Result<Self::Data, Self::Error> {
+            Ok(StaticSubscription::recv(self).await)
+        }
+    }
+
+    impl<R, P, const SUBS: usize, const DEPTH: usize> EventBus<P> for StaticEventBus<R, P, SUBS, DEPTH>
+    where
+        R: RawMutex,
+        P: Send,
+    {
+        type Subscription<'a> = StaticSubscription<'a, R, P, SUBS, DEPTH> where Self: 'a;
+
+        async fn subscribe(&self) -> Result<Self::Subscription<'_>, Self::Error> {
+            self.subscribe_queue()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::future::Future;
+    use core::pin::{pin, Pin};
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use core::task::Context;
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use crate::event_bus::EventBus;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(future: Pin<&mut F>, waker: &Arc<CountingWaker>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn static_bus() {
+        static BUS: StaticEventBus<StdRawMutex, u32, 2, 4> = StaticEventBus::new();
+
+        let first = BUS.subscribe_queue().unwrap();
+        let second = BUS.subscribe_queue().unwrap();
+
+        assert_eq!(
+            BUS.subscribe_queue().err(),
+            Some(StaticBusError::NoFreeSlot)
+        );
+        assert_eq!(BUS.subscribers(), 2);
+
+        for value in 1..=4 {
+            assert_eq!(BUS.post(&value, None), Ok(true));
+        }
+
+        assert_eq!(first.try_recv(), Some(1));
+
+        // Only the second subscriber, whose queue is full, misses the payload
+        assert_eq!(BUS.post(&5, None), Err(StaticBusError::Overflow(1)));
+
+        assert_eq!(
+            core::iter::from_fn(|| first.try_recv()).collect::<Vec<_>>(),
+            [2, 3, 4, 5]
+        );
+        assert_eq!(
+            core::iter::from_fn(|| second.try_recv()).collect::<Vec<_>>(),
+            [1, 2, 3, 4]
+        );
+
+        // Dropping a subscription frees its slot, and its queue
+        assert_eq!(BUS.post(&6, None), Ok(true));
+
+        drop(second);
+        assert_eq!(BUS.subscribers(), 1);
+
+        let third = BUS.subscribe_queue().unwrap();
+        assert_eq!(third.try_recv(), None);
+        assert_eq!(first.try_recv(), Some(6));
+    }
+
+    #[test]
+    fn static_bus_recv() {
+        let bus = StaticEventBus::<StdRawMutex, u32, 1, 4>::new();
+        let waker = Arc::new(CountingWaker::default());
+
+        let subscription = bus.subscribe_queue().unwrap();
+
+        let mut recv = pin!(subscription.recv());
+        assert!(poll(recv.as_mut(), &waker).is_pending());
+
+        bus.post_payload(&1).unwrap();
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+
+        assert_eq!(poll(recv.as_mut(), &waker), Poll::Ready(1));
+    }
+
+    #[test]
+    fn static_bus_callbacks() {
+        let bus = StaticEventBus::<StdRawMutex, u32, 2, 1>::new();
+
+        let received = Arc::new(AtomicUsize::new(0));
+
+        let subscription = {
+            let received = received.clone();
+
+            bus.subscribe(move |value: &u32| {
+                received.fetch_add(*value as usize, Ordering::SeqCst);
+            })
+            .unwrap()
+        };
+
+        // The payloads are handed to the callbacks, and never queued
+        for value in 1..=10 {
+            bus.post_payload(&value).unwrap();
+        }
+
+        assert_eq!(received.load(Ordering::SeqCst), 55);
+
+        drop(subscription);
+        assert_eq!(bus.subscribers(), 0);
+    }
+
+    #[test]
+    fn static_bus_threads() {
+        const PRODUCERS: u32 = 4;
+        const POSTS: u32 = 250;
+
+        static BUS: StaticEventBus<StdRawMutex, u32, 2, 16> = StaticEventBus::new();
+
+        let called = Arc::new(AtomicUsize::new(0));
+
+        let _callback = {
+            let called = called.clone();
+
+            BUS.subscribe(move |_: &u32| {
+                called.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap()
+        };
+
+        let subscription = BUS.subscribe_queue().unwrap();
+
+        let done = AtomicBool::new(false);
+        let missed = AtomicUsize::new(0);
+
+        let received = std::thread::scope(|scope| {
+            let consumer = scope.spawn(|| {
+                let mut received = Vec::new();
+
+                loop {
+                    let finished = done.load(Ordering::SeqCst);
+
+                    match subscription.try_recv() {
+                        Some(value) => received.push(value),
+                        None if finished => break received,
+                        None => std::thread::yield_now(),
+                    }
+                }
+            });
+
+            let producers = (0..PRODUCERS)
+                .map(|producer| {
+                    let missed = &missed;
+
+                    scope.spawn(move || {
+                        for value in 0..POSTS {
+                            if let Err(error) = BUS.post_payload(&(producer * POSTS + value)) {
+                                assert_eq!(error, StaticBusError::Overflow(1));
+
+                                missed.fetch_add(1, Ordering::SeqCst);
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for producer in producers {
+                producer.join().unwrap();
+            }
+
+            done.store(true, Ordering::SeqCst);
+
+            consumer.join().unwrap()
+        });
+
+        assert_eq!(called.load(Ordering::SeqCst), (PRODUCERS * POSTS) as usize);
+        assert_eq!(
+            received.len() + missed.load(Ordering::SeqCst),
+            (PRODUCERS * POSTS) as usize
+        );
+
+        // The payloads of each producer are received in the order they were posted
+        for producer in 0..PRODUCERS {
+            let values = received
+                .iter()
+                .filter(|value| **value / POSTS == producer)
+                .collect::<Vec<_>>();
+
+            assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! Failing over between network interfaces, e.g. from Ethernet to Wifi when the cable is pulled, and back
+//!
+//! `ConnectivityManager` keeps the interfaces in the order of preference, and designates the most preferred
+//! healthy one as the active interface. An interface is healthy when it is up with its IP configured and,
+//! if `Configuration::probe` is set, when the target of the probe answers pings while that interface is active.
+//!
+//! Failing over to a less preferred interface is immediate, but switching back to a more preferred one waits
+//! for it to stay healthy for `Configuration::hold_time`, so that a flapping link does not flap the active
+//! interface as well.
+//!
+//! Like `utils::timer::AlignedSchedule`, the manager does not keep time itself, and is driven with the current
+//! time instead: `poll` is to be called on the events of the drivers and periodically, and `probe` periodically.
+//! Interfaces of different types can be managed together as `&dyn netif::Interface` with the same error.
+
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::event_bus::{ErrorType, Postbox};
+use crate::ipv4;
+use crate::netif::{self, Interface, Monitor};
+use crate::ping::{self, Ping};
+use crate::utils::event_bus::EitherError;
+
+/// The index of an interface in the list given to `ConnectivityManager::new`, i.e. its priority
+pub type InterfaceId = usize;
+
+/// The reachability probe of the active interface
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Probe {
+    /// The host pinged, which should be routed through the active interface (e.g. by making it the default one
+    /// on `Event::ActiveChanged`)
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub target: ipv4::Ipv4Addr,
+    /// The time between the probes
+    pub interval: Duration,
+    /// The probe passes if any of the pings is answered
+    pub ping: ping::Configuration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// How long a more preferred interface has to stay healthy before the manager switches back to it
+    pub hold_time: Duration,
+    pub probe: Option<Probe>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            hold_time: Duration::from_secs(30),
+            probe: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Event {
+    /// A change of an interface, as reported by its `netif::Monitor`
+    Interface(InterfaceId, netif::Event),
+    /// The probe failed on the active interface, which is not healthy until it stays up for the hold time
+    Unreachable(InterfaceId),
+    ActiveChanged {
+        previous: Option<InterfaceId>,
+        active: Option<InterfaceId>,
+    },
+}
+
+struct Entry<I> {
+    interface: I,
+    monitor: Monitor,
+    /// Since when the interface is up with its IP configured
+    up_since: Option<Duration>,
+    unreachable: bool,
+}
+
+impl<I> Entry<I> {
+    fn is_usable(&self) -> bool {
+        self.up_since.is_some() && !self.unreachable
+    }
+
+    fn is_held(&self, now: Duration, hold_time: Duration) -> bool {
+        self.up_since
+            .map(|since| now.saturating_sub(since) >= hold_time)
+            .unwrap_or(false)
+    }
+}
+
+/// Posts the events of the monitor of an interface as `Event::Interface`
+struct Tagged<'a, P> {
+    id: InterfaceId,
+    postbox: &'a P,
+}
+
+impl<P> ErrorType for Tagged<'_, P>
+where
+    P: ErrorType,
+{
+    type Error = P::Error;
+}
+
+impl<P> Postbox<netif::Event> for Tagged<'_, P>
+where
+    P: Postbox<Event>,
+{
+    fn post(&self, payload: &netif::Event, wait: Option<Duration>) -> Result<bool, Self::Error> {
+        self.postbox
+            .post(&Event::Interface(self.id, *payload), wait)
+    }
+}
+
+/// Designates the active one of `N` network interfaces, in the order of preference
+pub struct ConnectivityManager<I, const N: usize> {
+    entries: [Entry<I>; N],
+    conf: Configuration,
+    active: Option<InterfaceId>,
+    next_probe: Option<Duration>,
+}
+
+impl<I, const N: usize> ConnectivityManager<I, N>
+where
+    I: Interface,
+{
+    /// Creates a manager without an active interface until the first `poll`
+    pub fn new(interfaces: [I; N], conf: Configuration) -> Self {
+        Self {
+            entries: interfaces.map(|interface| Entry {
+                interface,
+                monitor: Monitor::new(),
+                up_since: None,
+                unreachable: false,
+            }),
+            conf,
+            active: None,
+            next_probe: None,
+        }
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.conf
+    }
+
+    pub fn interface(&self, id: InterfaceId) -> Option<&I> {
+        self.entries.get(id).map(|entry| &entry.interface)
+    }
+
+    pub fn release(self) -> [I; N] {
+        self.entries.map(|entry| entry.interface)
+    }
+
+    pub fn active(&self) -> Option<InterfaceId> {
+        self.active
+    }
+
+    pub fn active_interface(&self) -> Option<&I> {
+        self.active.and_then(|id| self.interface(id))
+    }
+
+    /// The IPv4 settings of the active interface, as found by the last `poll`
+    pub fn active_ip_info(&self) -> Option<&ipv4::ClientSettings> {
+        self.active
+            .and_then(|id| self.entries[id].monitor.ip_info())
+    }
+
+    /// Whether `id` is up with its IP configured, and did not fail the probe since
+    pub fn is_healthy(&self, id: InterfaceId) -> bool {
+        self.entries.get(id).map(Entry::is_usable).unwrap_or(false)
+    }
+
+    /// When `probe` is to be called next, if probing is enabled and there is an active interface
+    pub fn next_probe(&self) -> Option<Duration> {
+        self.next_probe
+    }
+
+    /// Checks the interfaces and switches the active one if needed, returning it
+    ///
+    /// The changes of the interfaces are posted to `postbox`, followed by `Event::ActiveChanged`
+    /// if the active interface changed. Switching back to a more preferred interface happens on the first poll
+    /// after its hold time, so this is to be called periodically as well, and not only on the events of the drivers.
+    pub fn poll<P>(
+        &mut self,
+        now: Duration,
+        postbox: &P,
+    ) -> Result<Option<InterfaceId>, EitherError<I::Error, P::Error>>
+    where
+        P: Postbox<Event>,
+    {
+        for (id, entry) in self.entries.iter_mut().enumerate() {
+            entry
+                .monitor
+                .check(&entry.interface, &Tagged { id, postbox })?;
+
+            if entry.monitor.is_up() && entry.monitor.ip_info().is_some() {
+                if entry.up_since.is_none() {
+                    entry.up_since = Some(now);
+                }
+            } else {
+                entry.up_since = None;
+                entry.unreachable = false;
+            }
+        }
+
+        self.select(now, postbox).map_err(EitherError::Second)
+    }
+
+    /// Pings the target of `Configuration::probe` through the active interface if the probe is due,
+    /// failing over if it is not answered, and returns the active interface
+    pub fn probe<P, B>(
+        &mut self,
+        now: Duration,
+        ping: &mut P,
+        postbox: &B,
+    ) -> Result<Option<InterfaceId>, EitherError<P::Error, B::Error>>
+    where
+        P: Ping,
+        B: Postbox<Event>,
+    {
+        let (Some(probe), Some(active), Some(next_probe)) =
+            (&self.conf.probe, self.active, self.next_probe)
+        else {
+            return Ok(self.active);
+        };
+
+        if now < next_probe {
+            return Ok(self.active);
+        }
+
+        self.next_probe = Some(now + probe.interval);
+
+        let summary = ping
+            .ping(probe.target, &probe.ping)
+            .map_err(EitherError::First)?;
+
+        if summary.received == 0 {
+            let entry = &mut self.entries[active];
+
+            entry.unreachable = true;
+            // Restart the hold time, so that the interface is switched back to only once it stays up again
+            entry.up_since = entry.up_since.map(|_| now);
+
+            postbox
+                .post(&Event::Unreachable(active), None)
+                .map_err(EitherError::Second)?;
+        }
+
+        self.select(now, postbox).map_err(EitherError::Second)
+    }
+
+    fn select<P>(&mut self, now: Duration, postbox: &P) -> Result<Option<InterfaceId>, P::Error>
+    where
+        P: Postbox<Event>,
+    {
+        let hold_time = self.conf.hold_time;
+
+        for entry in &mut self.entries {
+            if entry.unreachable && entry.is_held(now, hold_time) {
+                entry.unreachable = false;
+            }
+        }
+
+        let active = match self.active {
+            Some(active) if self.entries[active].is_usable() => self.entries[..active]
+                .iter()
+                .position(|entry| entry.is_usable() && entry.is_held(now, hold_time))
+                .or(Some(active)),
+            _ => self
+                .entries
+                .iter()
+                .position(Entry::is_usable)
+                // Better an interface which failed the probe than none at all
+                .or_else(|| {
+                    self.entries
+                        .iter()
+                        .position(|entry| entry.up_since.is_some())
+                }),
+        };
+
+        if active != self.active {
+            let previous = core::mem::replace(&mut self.active, active);
+
+            self.next_probe = match (&self.conf.probe, active) {
+                (Some(_), Some(_)) => Some(now),
+                _ => None,
+            };
+
+            postbox.post(&Event::ActiveChanged { previous, active }, None)?;
+        }
+
+        Ok(active)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::ops::ControlFlow;
+
+    use crate::netif::Kind;
+    use crate::test_event_bus::MockBus;
+    use crate::test_netif::MockInterface;
+
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    const ETH: InterfaceId = 0;
+    const WIFI: InterfaceId = 1;
+
+    type Manager<'a> = ConnectivityManager<&'a MockInterface, 2>;
+
+    struct Network {
+        eth: MockInterface,
+        wifi: MockInterface,
+        bus: MockBus<Event>,
+    }
+
+    impl Network {
+        fn new() -> Self {
+            let network = Self {
+                eth: MockInterface::new("eth0", Kind::Ethernet),
+                wifi: MockInterface::new("sta", Kind::Wifi),
+                bus: MockBus::new(),
+            };
+
+            network.eth.set_ip(Some(MockInterface::settings(1, 10)));
+            network.wifi.set_ip(Some(MockInterface::settings(2, 10)));
+
+            network
+        }
+
+        fn manager(&self, conf: Configuration) -> Manager<'_> {
+            ConnectivityManager::new([&self.eth, &self.wifi], conf)
+        }
+
+        fn set_eth(&self, up: bool) {
+            self.eth.set_ip(up.then(|| MockInterface::settings(1, 10)));
+        }
+
+        fn poll(&self, manager: &mut Manager<'_>, secs: u32) -> Option<InterfaceId> {
+            manager.poll(SEC * secs, &self.bus).unwrap()
+        }
+
+        fn probe(
+            &self,
+            manager: &mut Manager<'_>,
+            ping: &mut MockPing,
+            secs: u32,
+        ) -> Option<InterfaceId> {
+            manager.probe(SEC * secs, ping, &self.bus).unwrap()
+        }
+
+        fn switches(&self) -> Vec<(Option<InterfaceId>, Option<InterfaceId>)> {
+            self.bus
+                .posted()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::ActiveChanged { previous, active } => Some((previous, active)),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    /// Answers the pings while `answered`
+    struct MockPing {
+        answered: bool,
+        pings: usize,
+    }
+
+    impl Ping for MockPing {
+        type Error = ();
+
+        fn ping(
+            &mut self,
+            _ip: ipv4::Ipv4Addr,
+            conf: &ping::Configuration,
+        ) -> Result<ping::Summary, Self::Error> {
+            self.pings += 1;
+
+            Ok(ping::Summary {
+                transmitted: conf.count,
+                received: if self.answered { conf.count } else { 0 },
+                time: Duration::ZERO,
+            })
+        }
+
+        fn ping_details<F: FnMut(&ping::Summary, &ping::Reply) -> ControlFlow<()> + Send>(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            conf: &ping::Configuration,
+            _reply_callback: F,
+        ) -> Result<ping::Summary, Self::Error> {
+            self.ping(ip, conf)
+        }
+    }
+
+    #[test]
+    fn fail_over() {
+        let network = Network::new();
+        let mut manager = network.manager(Default::default());
+
+        // The most preferred interface, without waiting for the hold time on the first poll
+        assert_eq!(network.poll(&mut manager, 0), Some(ETH));
+        assert_eq!(
+            manager.active_ip_info(),
+            Some(&MockInterface::settings(1, 10))
+        );
+
+        // Immediately
+        network.set_eth(false);
+        assert_eq!(network.poll(&mut manager, 10), Some(WIFI));
+        assert!(!manager.is_healthy(ETH));
+
+        network.wifi.set_ip(None);
+        assert_eq!(network.poll(&mut manager, 11), None);
+
+        assert_eq!(
+            network.switches(),
+            [
+                (None, Some(ETH)),
+                (Some(ETH), Some(WIFI)),
+                (Some(WIFI), None)
+            ]
+        );
+
+        // The monitor events are posted before the switch
+        assert_eq!(
+            network.bus.posted()[..3],
+            [
+                Event::Interface(ETH, netif::Event::Up),
+                Event::Interface(
+                    ETH,
+                    netif::Event::IpChanged(Some(MockInterface::settings(1, 10)))
+                ),
+                Event::Interface(WIFI, netif::Event::Up),
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_time() {
+        let network = Network::new();
+        let mut manager = network.manager(Configuration {
+            hold_time: SEC * 30,
+            probe: None,
+        });
+
+        network.poll(&mut manager, 0);
+
+        network.set_eth(false);
+        assert_eq!(network.poll(&mut manager, 10), Some(WIFI));
+
+        network.set_eth(true);
+        assert_eq!(network.poll(&mut manager, 20), Some(WIFI));
+        assert!(manager.is_healthy(ETH));
+
+        assert_eq!(network.poll(&mut manager, 49), Some(WIFI));
+        assert_eq!(network.poll(&mut manager, 50), Some(ETH));
+
+        assert_eq!(
+            network.switches(),
+            [
+                (None, Some(ETH)),
+                (Some(ETH), Some(WIFI)),
+                (Some(WIFI), Some(ETH))
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_time_flapping() {
+        let network = Network::new();
+        let mut manager = network.manager(Configuration {
+            hold_time: SEC * 30,
+            probe: None,
+        });
+
+        network.poll(&mut manager, 0);
+
+        network.set_eth(false);
+        network.poll(&mut manager, 10);
+
+        // Each time the link comes back, the hold time starts over
+        for secs in [20, 40, 60] {
+            network.set_eth(true);
+            assert_eq!(network.poll(&mut manager, secs), Some(WIFI));
+
+            network.set_eth(false);
+            assert_eq!(network.poll(&mut manager, secs + 15), Some(WIFI));
+        }
+
+        network.set_eth(true);
+        assert_eq!(network.poll(&mut manager, 80), Some(WIFI));
+        assert_eq!(network.poll(&mut manager, 109), Some(WIFI));
+        assert_eq!(network.poll(&mut manager, 110), Some(ETH));
+
+        assert_eq!(network.switches().len(), 3);
+    }
+
+    #[test]
+    fn hold_time_probe() {
+        let network = Network::new();
+        let mut manager = network.manager(Configuration {
+            hold_time: SEC * 30,
+            probe: Some(Probe {
+                target: ipv4::Ipv4Addr::new(8, 8, 8, 8),
+                interval: SEC * 10,
+                ping: Default::default(),
+            }),
+        });
+
+        let mut ping = MockPing {
+            answered: true,
+            pings: 0,
+        };
+
+        network.poll(&mut manager, 0);
+        assert_eq!(manager.next_probe(), Some(Duration::ZERO));
+
+        assert_eq!(network.probe(&mut manager, &mut ping, 0), Some(ETH));
+        assert_eq!(manager.next_probe(), Some(SEC * 10));
+
+        // Not due yet
+        assert_eq!(network.probe(&mut manager, &mut ping, 5), Some(ETH));
+        assert_eq!(ping.pings, 1);
+
+        // Unreachable through Ethernet, although it stays up
+        ping.answered = false;
+        assert_eq!(network.probe(&mut manager, &mut ping, 10), Some(WIFI));
+        assert!(network.bus.posted().contains(&Event::Unreachable(ETH)));
+        assert!(!manager.is_healthy(ETH));
+
+        // Switched back once the hold time passed since the probe failed
+        assert_eq!(network.poll(&mut manager, 39), Some(WIFI));
+        assert_eq!(network.poll(&mut manager, 40), Some(ETH));
+        assert!(manager.is_healthy(ETH));
+
+        // Probed right away on the newly active interface
+        assert_eq!(manager.next_probe(), Some(SEC * 40));
+
+        ping.answered = true;
+        assert_eq!(network.probe(&mut manager, &mut ping, 40), Some(ETH));
+        assert_eq!(ping.pings, 3);
+        assert_eq!(manager.next_probe(), Some(SEC * 50));
+    }
+}
@@ -6,6 +6,17 @@ pub enum HeaderSetError {
     TooManyHeaders,
 }
 
+impl core::fmt::Display for HeaderSetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManyHeaders => write!(f, "Too many headers"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeaderSetError {}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Headers<'b, const N: usize = 64>([(&'b str, &'b str); N]);
@@ -515,7 +526,7 @@ pub mod server {
                     })
                 {
                     Ok(f(&mut entry.data))
-                } else if let Some(entry) = data.iter_mut().find(|entry| entry.id == "") {
+                } else if let Some(entry) = data.iter_mut().find(|entry| entry.id.is_empty()) {
                     entry.id = session_id.try_into().unwrap();
                     entry.data = Default::default();
                     entry.timeout = self.default_session_timeout;
@@ -577,3 +588,31 @@ pub mod server {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_full() {
+        let mut headers = Headers::<2>::new();
+
+        headers
+            .set("Host", "example.com")
+            .set("Connection", "close");
+        // Replacing a header does not take a slot
+        headers.try_set("host", "example.org").unwrap();
+
+        let error = headers
+            .try_set("Content-Length", "0")
+            .map(|_| ())
+            .unwrap_err();
+
+        assert!(matches!(error, HeaderSetError::TooManyHeaders));
+        assert_eq!(error.to_string(), "Too many headers");
+        assert!(std::error::Error::source(&error).is_none());
+
+        assert_eq!(headers.host(), Some("example.org"));
+        assert_eq!(headers.iter().count(), 2);
+    }
+}
@@ -1,6 +1,7 @@
 use embedded_io::Error;
 
-use crate::io::{Read, Write};
+use crate::io::{BufRead, ErrorType, Read, Write};
+use crate::utils::digest::Digest;
 
 pub fn try_read_full<R: Read>(mut read: R, buf: &mut [u8]) -> Result<usize, (R::Error, usize)> {
     let mut offset = 0;
@@ -25,6 +26,12 @@ pub fn try_read_full<R: Read>(mut read: R, buf: &mut [u8]) -> Result<usize, (R::
 pub enum CopyError<R, W> {
     Read(R),
     Write(W),
+    /// The reader reached its end after the given number of bytes, before the requested length was copied
+    UnexpectedEof(u64),
+    /// The writer accepted no more bytes
+    WriteZero,
+    /// The copy was cancelled after the given number of bytes
+    Cancelled(u64),
 }
 
 impl<R: core::fmt::Debug, W: core::fmt::Debug> core::fmt::Display for CopyError<R, W> {
@@ -46,18 +53,41 @@ where
         match self {
             Self::Read(e) => e.kind(),
             Self::Write(e) => e.kind(),
+            Self::UnexpectedEof(_) => embedded_io::ErrorKind::Other,
+            Self::WriteZero => embedded_io::ErrorKind::WriteZero,
+            Self::Cancelled(_) => embedded_io::ErrorKind::Interrupted,
         }
     }
 }
 
+/// Copies everything from `read` to `write` until `read` reaches its end, returning the number of bytes copied
+///
+/// `buf` is the intermediate buffer, and must not be empty.
 pub fn copy<R, W>(read: R, write: W, buf: &mut [u8]) -> Result<u64, CopyError<R::Error, W::Error>>
 where
     R: Read,
     W: Write,
 {
-    copy_len(read, write, buf, u64::MAX)
+    copy_with_progress(read, write, buf, |_, _| {})
 }
 
+/// Like `copy`, reporting the progress as (copied, remaining) after each chunk
+pub fn copy_with_progress<R, W, P>(
+    read: R,
+    write: W,
+    buf: &mut [u8],
+    progress: P,
+) -> Result<u64, CopyError<R::Error, W::Error>>
+where
+    R: Read,
+    W: Write,
+    P: Fn(u64, u64),
+{
+    copy_up_to(read, write, buf, u64::MAX, false, progress)
+}
+
+/// Copies exactly `len` bytes from `read` to `write`, failing with `CopyError::UnexpectedEof`
+/// if `read` reaches its end before
 pub fn copy_len<R, W>(
     read: R,
     write: W,
@@ -71,11 +101,28 @@ where
     copy_len_with_progress(read, write, buf, len, |_, _| {})
 }
 
+/// Like `copy_len`, reporting the progress as (copied, remaining) after each chunk
 pub fn copy_len_with_progress<R, W, P>(
+    read: R,
+    write: W,
+    buf: &mut [u8],
+    len: u64,
+    progress: P,
+) -> Result<u64, CopyError<R::Error, W::Error>>
+where
+    R: Read,
+    W: Write,
+    P: Fn(u64, u64),
+{
+    copy_up_to(read, write, buf, len, true, progress)
+}
+
+fn copy_up_to<R, W, P>(
     mut read: R,
     mut write: W,
     buf: &mut [u8],
     mut len: u64,
+    exact: bool,
     progress: P,
 ) -> Result<u64, CopyError<R::Error, W::Error>>
 where
@@ -83,19 +130,35 @@ where
     W: Write,
     P: Fn(u64, u64),
 {
+    assert!(!buf.is_empty(), "The copy buffer must not be empty");
+
     let mut copied = 0;
 
     while len > 0 {
         progress(copied, len);
 
-        let size_read = read.read(buf).map_err(CopyError::Read)?;
+        let chunk = chunk_len(buf, len);
+
+        let size_read = read.read(&mut buf[..chunk]).map_err(CopyError::Read)?;
         if size_read == 0 {
+            if exact {
+                return Err(CopyError::UnexpectedEof(copied));
+            }
+
             break;
         }
 
-        write
-            .write_all(&buf[0..size_read])
-            .map_err(CopyError::Write)?;
+        let mut data = &buf[..size_read];
+
+        // Not `write_all`, which panics if the writer accepts no more bytes
+        while !data.is_empty() {
+            let size_written = write.write(data).map_err(CopyError::Write)?;
+            if size_written == 0 {
+                return Err(CopyError::WriteZero);
+            }
+
+            data = &data[size_written..];
+        }
 
         copied += size_read as u64;
         len -= size_read as u64;
@@ -106,91 +169,2443 @@ where
     Ok(copied)
 }
 
-#[cfg(feature = "nightly")]
-pub mod asynch {
-    use crate::io::asynch::{Read, Write};
+fn chunk_len(buf: &[u8], len: u64) -> usize {
+    if len < buf.len() as u64 {
+        len as usize
+    } else {
+        buf.len()
+    }
+}
 
-    pub use super::CopyError;
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadUntilError<E> {
+    Read(E),
+    /// The output buffer filled up before the delimiter was found
+    TooLong,
+}
 
-    pub async fn try_read_full<R: Read>(
-        mut read: R,
-        buf: &mut [u8],
-    ) -> Result<usize, (R::Error, usize)> {
-        let mut offset = 0;
-        let mut size = 0;
+impl<E: core::fmt::Debug> core::fmt::Display for ReadUntilError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
 
-        loop {
-            let size_read = read.read(&mut buf[offset..]).await.map_err(|e| (e, size))?;
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug> std::error::Error for ReadUntilError<E> {}
 
-            offset += size_read;
-            size += size_read;
+impl<E> Error for ReadUntilError<E>
+where
+    E: Error,
+{
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::Read(e) => e.kind(),
+            Self::TooLong => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
 
-            if size_read == 0 || size == buf.len() {
-                break;
-            }
+/// Reads into `out` up to and including `delim`, returning the number of bytes read
+///
+/// When `read` reaches its end first, the bytes read so far are returned, without the delimiter.
+/// Fails with `ReadUntilError::TooLong` if `out` fills up before the delimiter is found;
+/// the bytes in `out` are then consumed from `read` nevertheless.
+pub fn read_until<R: BufRead>(
+    mut read: R,
+    delim: u8,
+    out: &mut [u8],
+) -> Result<usize, ReadUntilError<R::Error>> {
+    let mut size = 0;
+
+    loop {
+        let available = read.fill_buf().map_err(ReadUntilError::Read)?;
+        if available.is_empty() {
+            break Ok(size);
         }
 
-        Ok(size)
+        let (len, found) = scan_until(available, delim, &mut out[size..]);
+
+        read.consume(len);
+        size += len;
+
+        if found {
+            break Ok(size);
+        } else if size == out.len() {
+            break Err(ReadUntilError::TooLong);
+        }
     }
+}
 
-    pub async fn copy<R, W>(
-        read: R,
-        write: W,
-        buf: &mut [u8],
-    ) -> Result<u64, CopyError<R::Error, W::Error>>
-    where
-        R: Read,
-        W: Write,
-    {
-        copy_len(read, write, buf, u64::MAX).await
+/// Copies from `available` what fits into `out`, up to and including `delim`,
+/// returning the number of bytes copied and whether `delim` was found
+fn scan_until(available: &[u8], delim: u8, out: &mut [u8]) -> (usize, bool) {
+    let max = available.len().min(out.len());
+
+    let (len, found) = match available[..max].iter().position(|byte| *byte == delim) {
+        Some(index) => (index + 1, true),
+        None => (max, false),
+    };
+
+    out[..len].copy_from_slice(&available[..len]);
+
+    (len, found)
+}
+
+/// Adds an `N`-bytes buffer to a reader, so that reading it in small pieces (e.g. when parsing a protocol)
+/// does not result in as many reads of the underlying reader
+///
+/// Implements `BufRead` (usable with `read_until`), as well as the async `Read` and `BufRead`
+/// when the underlying reader is async.
+pub struct BufReader<R, const N: usize> {
+    read: R,
+    buf: [u8; N],
+    pos: usize,
+    filled: usize,
+}
+
+impl<R, const N: usize> BufReader<R, N> {
+    pub const fn new(read: R) -> Self {
+        assert!(N > 0, "The buffer must not be empty");
+
+        Self {
+            read,
+            buf: [0; N],
+            pos: 0,
+            filled: 0,
+        }
     }
 
-    pub async fn copy_len<R, W>(
-        read: R,
-        write: W,
-        buf: &mut [u8],
-        len: u64,
-    ) -> Result<u64, CopyError<R::Error, W::Error>>
-    where
-        R: Read,
-        W: Write,
-    {
-        copy_len_with_progress(read, write, buf, len, |_, _| {}).await
+    /// The data read from the underlying reader, but not yet consumed
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
     }
 
-    pub async fn copy_len_with_progress<R, W, P>(
-        mut read: R,
-        mut write: W,
-        buf: &mut [u8],
-        mut len: u64,
-        progress: P,
-    ) -> Result<u64, CopyError<R::Error, W::Error>>
-    where
-        R: Read,
-        W: Write,
-        P: Fn(u64, u64),
-    {
-        let mut copied = 0;
+    pub fn get_ref(&self) -> &R {
+        &self.read
+    }
 
-        while len > 0 {
-            progress(copied, len);
+    /// Note that reading directly from the underlying reader skips the data still in the buffer.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.read
+    }
 
-            let size_read = read.read(buf).await.map_err(CopyError::Read)?;
-            if size_read == 0 {
-                break;
-            }
+    /// Returns the underlying reader; the data still in the buffer is lost.
+    pub fn into_inner(self) -> R {
+        self.read
+    }
 
-            write
-                .write_all(&buf[0..size_read])
-                .await
-                .map_err(CopyError::Write)?;
+    fn refilled(&mut self, filled: usize) -> &[u8] {
+        self.pos = 0;
+        self.filled = filled;
 
-            copied += size_read as u64;
-            len -= size_read as u64;
+        self.buffer()
+    }
+
+    fn read_buffered(&mut self, out: &mut [u8]) -> usize {
+        let buffer = self.buffer();
+        let len = buffer.len().min(out.len());
+
+        out[..len].copy_from_slice(&buffer[..len]);
+        self.pos += len;
+
+        len
+    }
+
+    /// With nothing buffered, reads into a buffer at least as large as the internal one go to the underlying reader
+    fn reads_through(&self, out: &[u8]) -> bool {
+        self.pos == self.filled && out.len() >= N
+    }
+}
+
+impl<R, const N: usize> ErrorType for BufReader<R, N>
+where
+    R: ErrorType,
+{
+    type Error = R::Error;
+}
+
+impl<R, const N: usize> Read for BufReader<R, N>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.reads_through(buf) {
+            return self.read.read(buf);
         }
 
-        progress(copied, len);
+        self.fill_buf()?;
 
-        Ok(copied)
+        Ok(self.read_buffered(buf))
+    }
+}
+
+impl<R, const N: usize> BufRead for BufReader<R, N>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        if self.pos == self.filled {
+            let filled = self.read.read(&mut self.buf)?;
+
+            Ok(self.refilled(filled))
+        } else {
+            Ok(self.buffer())
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+}
+
+/// Limits a reader to `limit` bytes, after which it reports its end, as returned by `take`
+///
+/// Implements `BufRead` when the underlying reader does, as well as the async `Read` and `BufRead`
+/// when the underlying reader is async.
+pub struct Take<R> {
+    read: R,
+    remaining: u64,
+}
+
+/// Returns a reader of the first `limit` bytes of `read` (or less, if `read` reaches its end before)
+pub fn take<R>(read: R, limit: u64) -> Take<R> {
+    Take {
+        read,
+        remaining: limit,
+    }
+}
+
+impl<R> Take<R> {
+    /// The number of bytes which can still be read before the limit is reached
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Sets the number of bytes which can still be read, regardless of how many were read so far
+    pub fn set_remaining(&mut self, remaining: u64) {
+        self.remaining = remaining;
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.read
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.read
+    }
+
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+
+    fn limited<'a>(&self, buf: &'a mut [u8]) -> &'a mut [u8] {
+        let len = chunk_len(buf, self.remaining);
+
+        &mut buf[..len]
+    }
+
+    fn advanced(&mut self, len: usize) -> usize {
+        self.remaining -= len as u64;
+
+        len
+    }
+}
+
+impl<R> ErrorType for Take<R>
+where
+    R: ErrorType,
+{
+    type Error = R::Error;
+}
+
+impl<R> Read for Take<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+
+        let buf = self.limited(buf);
+        let len = self.read.read(buf)?;
+
+        Ok(self.advanced(len))
+    }
+}
+
+impl<R> BufRead for Take<R>
+where
+    R: BufRead,
+{
+    fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        if self.remaining == 0 {
+            return Ok(&[]);
+        }
+
+        let buf = self.read.fill_buf()?;
+
+        Ok(&buf[..chunk_len(buf, self.remaining)])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = if (amt as u64) < self.remaining {
+            amt
+        } else {
+            self.remaining as usize
+        };
+
+        self.read.consume(amt);
+        self.advanced(amt);
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChainError<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<A: core::fmt::Debug, B: core::fmt::Debug> core::fmt::Display for ChainError<A, B> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: core::fmt::Debug, B: core::fmt::Debug> std::error::Error for ChainError<A, B> {}
+
+impl<A, B> Error for ChainError<A, B>
+where
+    A: Error,
+    B: Error,
+{
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::First(e) => e.kind(),
+            Self::Second(e) => e.kind(),
+        }
+    }
+}
+
+/// Reads one reader to its end, and then another, as returned by `chain`
+///
+/// Also implements the async `Read` when both readers are async.
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+    first_done: bool,
+}
+
+/// Returns a reader of everything in `first`, followed by everything in `second`
+///
+/// E.g. `chain(&buf[..], read)` puts back the bytes already read into `buf` in front of the rest of `read`.
+pub fn chain<A, B>(first: A, second: B) -> Chain<A, B> {
+    Chain {
+        first,
+        second,
+        first_done: false,
+    }
+}
+
+impl<A, B> Chain<A, B> {
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.first, &mut self.second)
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A, B> ErrorType for Chain<A, B>
+where
+    A: ErrorType,
+    B: ErrorType,
+{
+    type Error = ChainError<A::Error, B::Error>;
+}
+
+impl<A, B> Read for Chain<A, B>
+where
+    A: Read,
+    B: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if !self.first_done {
+            let len = self.first.read(buf).map_err(ChainError::First)?;
+
+            // An empty `buf` tells nothing about the end of `first`
+            if len > 0 || buf.is_empty() {
+                return Ok(len);
+            }
+
+            self.first_done = true;
+        }
+
+        self.second.read(buf).map_err(ChainError::Second)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LineError<E> {
+    Read(E),
+    /// The line, of the given length in bytes, is not valid UTF-8; its bytes are available with `Lines::raw_line`
+    Utf8(usize),
+    /// The line does not fit in the buffer; the part which did is consumed
+    TooLong,
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for LineError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: core::fmt::Debug> std::error::Error for LineError<E> {}
+
+impl<E> Error for LineError<E>
+where
+    E: Error,
+{
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::Read(e) => e.kind(),
+            Self::Utf8(_) | Self::TooLong => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+enum LineStep {
+    More,
+    Done,
+    TooLong,
+}
+
+/// The line being assembled by `Lines`
+struct LineBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    cr: bool,
+}
+
+impl<const N: usize> LineBuf<N> {
+    const fn new() -> Self {
+        assert!(N > 0, "The buffer must not be empty");
+
+        Self {
+            buf: [0; N],
+            len: 0,
+            cr: false,
+        }
+    }
+
+    fn start(&mut self) {
+        self.len = 0;
+        self.cr = false;
+    }
+
+    /// Takes from `available` (which must not be empty) what belongs to the line,
+    /// returning the number of bytes taken
+    fn feed(&mut self, available: &[u8]) -> (usize, LineStep) {
+        if self.cr {
+            // The line filled the buffer, and was followed by CR
+            return match available[0] {
+                b'\n' => (1, LineStep::Done),
+                _ => (0, LineStep::TooLong),
+            };
+        }
+
+        if self.len == N {
+            // The line filled the buffer, so it only fits if its terminator comes next
+            return match available[0] {
+                b'\n' => (1, LineStep::Done),
+                b'\r' => {
+                    self.cr = true;
+
+                    (1, LineStep::More)
+                }
+                _ => (0, LineStep::TooLong),
+            };
+        }
+
+        let (len, found) = scan_until(available, b'\n', &mut self.buf[self.len..]);
+
+        self.len += len;
+
+        if found {
+            (len, LineStep::Done)
+        } else {
+            (len, LineStep::More)
+        }
+    }
+
+    fn raw(&self) -> &[u8] {
+        let line = &self.buf[..self.len];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+
+        line.strip_suffix(b"\r").unwrap_or(line)
+    }
+
+    fn line<E>(&self, eof: bool) -> Result<Option<&str>, LineError<E>> {
+        if eof && self.len == 0 && !self.cr {
+            return Ok(None);
+        }
+
+        let raw = self.raw();
+
+        core::str::from_utf8(raw)
+            .map(Some)
+            .map_err(|_| LineError::Utf8(raw.len()))
+    }
+}
+
+/// Reads a `BufRead` (e.g. a `BufReader`) line by line, into a buffer of `N` bytes
+///
+/// Lines are terminated by LF or CRLF, neither of which is part of the returned line, nor counts
+/// against `N`. A last line which is not terminated is returned as well.
+pub struct Lines<R, const N: usize> {
+    read: R,
+    line: LineBuf<N>,
+}
+
+impl<R, const N: usize> Lines<R, N> {
+    pub const fn new(read: R) -> Self {
+        Self {
+            read,
+            line: LineBuf::new(),
+        }
+    }
+
+    /// The bytes of the line read last, without its terminator
+    ///
+    /// Useful after `LineError::Utf8`, or `LineError::TooLong` for the part of the line which was read.
+    pub fn raw_line(&self) -> &[u8] {
+        self.line.raw()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.read
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.read
+    }
+
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+}
+
+impl<R, const N: usize> Lines<R, N>
+where
+    R: BufRead,
+{
+    /// Reads the next line, returning `None` once `read` reaches its end
+    ///
+    /// After `LineError::TooLong`, the next call returns the rest of the line, unless it is
+    /// discarded with `skip_line` first.
+    pub fn next_line(&mut self) -> Result<Option<&str>, LineError<R::Error>> {
+        self.line.start();
+
+        loop {
+            let available = self.read.fill_buf().map_err(LineError::Read)?;
+            if available.is_empty() {
+                break self.line.line(true);
+            }
+
+            let (len, step) = self.line.feed(available);
+
+            self.read.consume(len);
+
+            match step {
+                LineStep::More => (),
+                LineStep::Done => break self.line.line(false),
+                LineStep::TooLong => break Err(LineError::TooLong),
+            }
+        }
+    }
+
+    /// Discards the rest of the current line, up to and including its terminator
+    pub fn skip_line(&mut self) -> Result<(), R::Error> {
+        loop {
+            let available = self.read.fill_buf()?;
+            if available.is_empty() {
+                break Ok(());
+            }
+
+            match available.iter().position(|byte| *byte == b'\n') {
+                Some(index) => {
+                    self.read.consume(index + 1);
+
+                    break Ok(());
+                }
+                None => {
+                    let len = available.len();
+
+                    self.read.consume(len);
+                }
+            }
+        }
+    }
+}
+
+/// Implements `core::fmt::Write` over an `io::Write`, e.g. for `write!`-ing straight into an HTTP response
+///
+/// The formatted output is collected in an `N`-bytes buffer, which is written out when full,
+/// on `flush` and on drop. As `fmt::Error` carries no payload, the error of the underlying writer
+/// is kept by the adapter, to be retrieved with `take_error` (or `finish`) after formatting failed.
+///
+/// Going the other way, `io::Write::write_fmt` formats into an `io::Write` directly.
+pub struct FmtWriteAdapter<'a, W, const N: usize = 64>
+where
+    W: Write,
+{
+    write: &'a mut W,
+    buf: [u8; N],
+    len: usize,
+    error: Option<W::Error>,
+}
+
+impl<'a, W, const N: usize> FmtWriteAdapter<'a, W, N>
+where
+    W: Write,
+{
+    pub fn new(write: &'a mut W) -> Self {
+        Self {
+            write,
+            buf: [0; N],
+            len: 0,
+            error: None,
+        }
+    }
+
+    /// Returns the error of the underlying writer which made formatting fail, if any
+    ///
+    /// Once it is taken, the adapter can be used again.
+    pub fn take_error(&mut self) -> Option<W::Error> {
+        self.error.take()
+    }
+
+    /// Writes out the buffered output, and flushes the underlying writer
+    pub fn flush(&mut self) -> Result<(), W::Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        self.write_buffered()?;
+        self.write.flush()
+    }
+
+    /// Flushes the adapter, returning the error of the underlying writer which made formatting fail, if any
+    pub fn finish(mut self) -> Result<(), W::Error> {
+        self.flush()
+    }
+
+    fn write_buffered(&mut self) -> Result<(), W::Error> {
+        let len = core::mem::replace(&mut self.len, 0);
+
+        self.write.write_all(&self.buf[..len])
+    }
+
+    fn stash<T>(&mut self, result: Result<T, W::Error>) -> core::fmt::Result {
+        result.map(|_| ()).map_err(|e| {
+            self.error = Some(e);
+
+            core::fmt::Error
+        })
+    }
+}
+
+impl<'a, W, const N: usize> core::fmt::Write for FmtWriteAdapter<'a, W, N>
+where
+    W: Write,
+{
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.error.is_some() {
+            return Err(core::fmt::Error);
+        }
+
+        let data = s.as_bytes();
+
+        if self.len + data.len() > N {
+            let result = self.write_buffered();
+            self.stash(result)?;
+        }
+
+        if data.len() >= N {
+            let result = self.write.write_all(data);
+            self.stash(result)
+        } else {
+            self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+            self.len += data.len();
+
+            Ok(())
+        }
+    }
+}
+
+impl<'a, W, const N: usize> Drop for FmtWriteAdapter<'a, W, N>
+where
+    W: Write,
+{
+    fn drop(&mut self) {
+        if self.error.is_none() {
+            let _ = self.write_buffered();
+        }
+    }
+}
+
+/// Passes the data written to `W` through `D`, e.g. for computing the checksum of an upload while it is written
+///
+/// Only the data actually accepted by `W` is hashed.
+pub struct HashingWrite<W, D> {
+    write: W,
+    digest: D,
+}
+
+impl<W, D> HashingWrite<W, D>
+where
+    D: Digest,
+{
+    pub const fn new(write: W, digest: D) -> Self {
+        Self { write, digest }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.write
+    }
+
+    /// Returns the underlying writer, dropping the digest
+    pub fn into_inner(self) -> W {
+        self.write
+    }
+
+    /// Returns the underlying writer, together with the digest of the data written to it
+    pub fn finalize(self) -> (W, D::Output) {
+        (self.write, self.digest.finalize())
+    }
+}
+
+impl<W, D> ErrorType for HashingWrite<W, D>
+where
+    W: ErrorType,
+{
+    type Error = W::Error;
+}
+
+impl<W, D> Write for HashingWrite<W, D>
+where
+    W: Write,
+    D: Digest,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let size = self.write.write(buf)?;
+
+        self.digest.update(&buf[..size]);
+
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write.flush()
+    }
+}
+
+/// Passes the data read from `R` through `D`, e.g. for computing the checksum of a download while it is read
+pub struct HashingRead<R, D> {
+    read: R,
+    digest: D,
+}
+
+impl<R, D> HashingRead<R, D>
+where
+    D: Digest,
+{
+    pub const fn new(read: R, digest: D) -> Self {
+        Self { read, digest }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.read
+    }
+
+    /// Returns the underlying reader, dropping the digest
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+
+    /// Returns the underlying reader, together with the digest of the data read from it
+    pub fn finalize(self) -> (R, D::Output) {
+        (self.read, self.digest.finalize())
+    }
+}
+
+impl<R, D> ErrorType for HashingRead<R, D>
+where
+    R: ErrorType,
+{
+    type Error = R::Error;
+}
+
+impl<R, D> Read for HashingRead<R, D>
+where
+    R: Read,
+    D: Digest,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let size = self.read.read(buf)?;
+
+        self.digest.update(&buf[..size]);
+
+        Ok(size)
+    }
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::future::poll_fn;
+    use core::task::{Poll, Waker};
+
+    use crate::io::asynch::{BufRead, Read, Write};
+    use crate::utils::digest::Digest;
+    use crate::utils::mutex::{Mutex, RawMutex};
+
+    use super::{chunk_len, scan_until, LineBuf, LineStep};
+
+    pub use super::{
+        chain, take, BufReader, Chain, ChainError, CopyError, HashingRead, HashingWrite, LineError,
+        ReadUntilError, Take,
+    };
+
+    struct CancelState {
+        cancelled: bool,
+        wakers: heapless::Vec<Waker, 4>,
+    }
+
+    impl CancelState {
+        fn wake(&mut self) {
+            while let Some(waker) = self.wakers.pop() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// A flag for asking async operations (like `copy_cancellable`) to stop, which can be shared between tasks
+    ///
+    /// Besides being checked with `is_cancelled`, the cancellation can be awaited with `cancelled`,
+    /// by up to 4 tasks at a time.
+    pub struct CancelToken<R>(Mutex<R, CancelState>)
+    where
+        R: RawMutex;
+
+    impl<R> CancelToken<R>
+    where
+        R: RawMutex,
+    {
+        pub const fn new() -> Self {
+            Self(Mutex::new(CancelState {
+                cancelled: false,
+                wakers: heapless::Vec::new(),
+            }))
+        }
+
+        /// Cancels the token, waking the tasks waiting in `cancelled`
+        pub fn cancel(&self) {
+            let mut state = self.0.lock();
+
+            state.cancelled = true;
+            state.wake();
+        }
+
+        pub fn is_cancelled(&self) -> bool {
+            self.0.lock().cancelled
+        }
+
+        /// Makes the token usable again after it was cancelled
+        pub fn reset(&self) {
+            self.0.lock().cancelled = false;
+        }
+
+        /// Waits until the token is cancelled
+        pub async fn cancelled(&self) {
+            poll_fn(|cx| {
+                let mut state = self.0.lock();
+
+                if state.cancelled {
+                    Poll::Ready(())
+                } else {
+                    if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                        if let Err(waker) = state.wakers.push(cx.waker().clone()) {
+                            // Too many waiters: wake the others, so that none is lost
+                            state.wake();
+
+                            state.wakers.push(waker).unwrap();
+                        }
+                    }
+
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+
+    impl<R> Default for CancelToken<R>
+    where
+        R: RawMutex,
+    {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    pub async fn try_read_full<R: Read>(
+        mut read: R,
+        buf: &mut [u8],
+    ) -> Result<usize, (R::Error, usize)> {
+        let mut offset = 0;
+        let mut size = 0;
+
+        loop {
+            let size_read = read.read(&mut buf[offset..]).await.map_err(|e| (e, size))?;
+
+            offset += size_read;
+            size += size_read;
+
+            if size_read == 0 || size == buf.len() {
+                break;
+            }
+        }
+
+        Ok(size)
+    }
+
+    pub async fn copy<R, W>(
+        read: R,
+        write: W,
+        buf: &mut [u8],
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+    {
+        copy_with_progress(read, write, buf, |_, _| {}).await
+    }
+
+    pub async fn copy_with_progress<R, W, P>(
+        read: R,
+        write: W,
+        buf: &mut [u8],
+        progress: P,
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+        P: Fn(u64, u64),
+    {
+        copy_up_to(read, write, buf, u64::MAX, false, || false, progress).await
+    }
+
+    pub async fn copy_len<R, W>(
+        read: R,
+        write: W,
+        buf: &mut [u8],
+        len: u64,
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+    {
+        copy_len_with_progress(read, write, buf, len, |_, _| {}).await
+    }
+
+    pub async fn copy_len_with_progress<R, W, P>(
+        read: R,
+        write: W,
+        buf: &mut [u8],
+        len: u64,
+        progress: P,
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+        P: Fn(u64, u64),
+    {
+        copy_up_to(read, write, buf, len, true, || false, progress).await
+    }
+
+    /// Copies everything from `read` to `write` like `copy`, unless `cancel` is cancelled first
+    ///
+    /// `cancel` is checked before reading each chunk, so a chunk which is already read is always written
+    /// in full, and the copy then fails with `CopyError::Cancelled` and the number of bytes copied.
+    /// `progress` is called with the number of bytes copied so far, before reading each chunk and at the end.
+    pub async fn copy_cancellable<R, W, M, P>(
+        read: R,
+        write: W,
+        buf: &mut [u8],
+        cancel: &CancelToken<M>,
+        mut progress: P,
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+        M: RawMutex,
+        P: FnMut(u64),
+    {
+        let cancelled = || cancel.is_cancelled();
+
+        copy_up_to(read, write, buf, u64::MAX, false, cancelled, |copied, _| {
+            progress(copied)
+        })
+        .await
+    }
+
+    async fn copy_up_to<R, W, C, P>(
+        mut read: R,
+        mut write: W,
+        buf: &mut [u8],
+        mut len: u64,
+        exact: bool,
+        cancelled: C,
+        mut progress: P,
+    ) -> Result<u64, CopyError<R::Error, W::Error>>
+    where
+        R: Read,
+        W: Write,
+        C: Fn() -> bool,
+        P: FnMut(u64, u64),
+    {
+        assert!(!buf.is_empty(), "The copy buffer must not be empty");
+
+        let mut copied = 0;
+
+        while len > 0 {
+            progress(copied, len);
+
+            if cancelled() {
+                return Err(CopyError::Cancelled(copied));
+            }
+
+            let chunk = chunk_len(buf, len);
+
+            let size_read = read
+                .read(&mut buf[..chunk])
+                .await
+                .map_err(CopyError::Read)?;
+            if size_read == 0 {
+                if exact {
+                    return Err(CopyError::UnexpectedEof(copied));
+                }
+
+                break;
+            }
+
+            let mut data = &buf[..size_read];
+
+            while !data.is_empty() {
+                let size_written = write.write(data).await.map_err(CopyError::Write)?;
+                if size_written == 0 {
+                    return Err(CopyError::WriteZero);
+                }
+
+                data = &data[size_written..];
+            }
+
+            copied += size_read as u64;
+            len -= size_read as u64;
+        }
+
+        progress(copied, len);
+
+        Ok(copied)
+    }
+
+    pub async fn read_until<R: BufRead>(
+        mut read: R,
+        delim: u8,
+        out: &mut [u8],
+    ) -> Result<usize, ReadUntilError<R::Error>> {
+        let mut size = 0;
+
+        loop {
+            let available = read.fill_buf().await.map_err(ReadUntilError::Read)?;
+            if available.is_empty() {
+                break Ok(size);
+            }
+
+            let (len, found) = scan_until(available, delim, &mut out[size..]);
+
+            read.consume(len);
+            size += len;
+
+            if found {
+                break Ok(size);
+            } else if size == out.len() {
+                break Err(ReadUntilError::TooLong);
+            }
+        }
+    }
+
+    impl<R, const N: usize> Read for BufReader<R, N>
+    where
+        R: Read,
+    {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.reads_through(buf) {
+                return self.read.read(buf).await;
+            }
+
+            BufRead::fill_buf(self).await?;
+
+            Ok(self.read_buffered(buf))
+        }
+    }
+
+    impl<R, const N: usize> BufRead for BufReader<R, N>
+    where
+        R: Read,
+    {
+        async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+            if self.pos == self.filled {
+                let filled = self.read.read(&mut self.buf).await?;
+
+                Ok(self.refilled(filled))
+            } else {
+                Ok(self.buffer())
+            }
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.pos = (self.pos + amt).min(self.filled);
+        }
+    }
+
+    impl<R> Read for Take<R>
+    where
+        R: Read,
+    {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+
+            let buf = self.limited(buf);
+            let len = self.read.read(buf).await?;
+
+            Ok(self.advanced(len))
+        }
+    }
+
+    impl<R> BufRead for Take<R>
+    where
+        R: BufRead,
+    {
+        async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+            if self.remaining == 0 {
+                return Ok(&[]);
+            }
+
+            let buf = self.read.fill_buf().await?;
+
+            Ok(&buf[..chunk_len(buf, self.remaining)])
+        }
+
+        fn consume(&mut self, amt: usize) {
+            let amt = if (amt as u64) < self.remaining {
+                amt
+            } else {
+                self.remaining as usize
+            };
+
+            self.read.consume(amt);
+            self.advanced(amt);
+        }
+    }
+
+    impl<A, B> Read for Chain<A, B>
+    where
+        A: Read,
+        B: Read,
+    {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if !self.first_done {
+                let len = self.first.read(buf).await.map_err(ChainError::First)?;
+
+                if len > 0 || buf.is_empty() {
+                    return Ok(len);
+                }
+
+                self.first_done = true;
+            }
+
+            self.second.read(buf).await.map_err(ChainError::Second)
+        }
+    }
+
+    /// The async flavor of `super::Lines`, for reading an async `BufRead` line by line
+    pub struct Lines<R, const N: usize> {
+        read: R,
+        line: LineBuf<N>,
+    }
+
+    impl<R, const N: usize> Lines<R, N> {
+        pub const fn new(read: R) -> Self {
+            Self {
+                read,
+                line: LineBuf::new(),
+            }
+        }
+
+        pub fn raw_line(&self) -> &[u8] {
+            self.line.raw()
+        }
+
+        pub fn get_ref(&self) -> &R {
+            &self.read
+        }
+
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.read
+        }
+
+        pub fn into_inner(self) -> R {
+            self.read
+        }
+    }
+
+    impl<R, const N: usize> Lines<R, N>
+    where
+        R: BufRead,
+    {
+        pub async fn next_line(&mut self) -> Result<Option<&str>, LineError<R::Error>> {
+            self.line.start();
+
+            loop {
+                let available = self.read.fill_buf().await.map_err(LineError::Read)?;
+                if available.is_empty() {
+                    break self.line.line(true);
+                }
+
+                let (len, step) = self.line.feed(available);
+
+                self.read.consume(len);
+
+                match step {
+                    LineStep::More => (),
+                    LineStep::Done => break self.line.line(false),
+                    LineStep::TooLong => break Err(LineError::TooLong),
+                }
+            }
+        }
+
+        pub async fn skip_line(&mut self) -> Result<(), R::Error> {
+            loop {
+                let available = self.read.fill_buf().await?;
+                if available.is_empty() {
+                    break Ok(());
+                }
+
+                match available.iter().position(|byte| *byte == b'\n') {
+                    Some(index) => {
+                        self.read.consume(index + 1);
+
+                        break Ok(());
+                    }
+                    None => {
+                        let len = available.len();
+
+                        self.read.consume(len);
+                    }
+                }
+            }
+        }
+    }
+
+    impl<W, D> Write for HashingWrite<W, D>
+    where
+        W: Write,
+        D: Digest,
+    {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let size = self.write.write(buf).await?;
+
+            self.digest.update(&buf[..size]);
+
+            Ok(size)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            self.write.flush().await
+        }
+    }
+
+    impl<R, D> Read for HashingRead<R, D>
+    where
+        R: Read,
+        D: Digest,
+    {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let size = self.read.read(buf).await?;
+
+            self.digest.update(&buf[..size]);
+
+            Ok(size)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::cell::RefCell;
+
+    use crate::io::ErrorKind;
+
+    use super::*;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    /// A reader returning at most `chunk` bytes per read, and failing once `fail_at` bytes were read
+    struct Dribble<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        fail_at: Option<usize>,
+        read: usize,
+        reads: usize,
+    }
+
+    impl<'a> Dribble<'a> {
+        fn new(data: &'a [u8], chunk: usize) -> Self {
+            Self {
+                data,
+                chunk,
+                fail_at: None,
+                read: 0,
+                reads: 0,
+            }
+        }
+
+        fn failing_at(self, fail_at: usize) -> Self {
+            Self {
+                fail_at: Some(fail_at),
+                ..self
+            }
+        }
+
+        fn rest(&self) -> &'a [u8] {
+            &self.data[self.read..]
+        }
+    }
+
+    impl ErrorType for Dribble<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for Dribble<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.reads += 1;
+
+            if self.fail_at.map(|at| self.read >= at).unwrap_or(false) {
+                return Err(ErrorKind::ConnectionReset);
+            }
+
+            let len = buf
+                .len()
+                .min(self.chunk)
+                .min(self.data.len() - self.read)
+                .min(self.fail_at.unwrap_or(usize::MAX) - self.read);
+
+            buf[..len].copy_from_slice(&self.data[self.read..self.read + len]);
+            self.read += len;
+
+            Ok(len)
+        }
+    }
+
+    /// A writer accepting at most `max_write` bytes per write, which fails once it holds `fail_at` bytes,
+    /// and accepts nothing more once it holds `full_at` bytes
+    #[derive(Default)]
+    struct Sink {
+        data: Vec<u8>,
+        max_write: Option<usize>,
+        fail_at: Option<usize>,
+        full_at: Option<usize>,
+        writes: usize,
+    }
+
+    impl Sink {
+        fn new(max_write: usize) -> Self {
+            Self {
+                max_write: Some(max_write),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl ErrorType for Sink {
+        type Error = ErrorKind;
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.writes += 1;
+
+            if self
+                .fail_at
+                .map(|at| self.data.len() >= at)
+                .unwrap_or(false)
+            {
+                return Err(ErrorKind::Other);
+            }
+
+            let len = buf
+                .len()
+                .min(self.max_write.unwrap_or(usize::MAX))
+                .min(self.full_at.unwrap_or(usize::MAX) - self.data.len());
+
+            self.data.extend_from_slice(&buf[..len]);
+
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn copy_short_reads_and_writes() {
+        let data = data(1000);
+
+        for (chunk, max_write, buf_len) in [(7, 3, 16), (1, 1000, 5), (1000, 1, 1), (64, 64, 64)] {
+            let mut read = Dribble::new(&data, chunk);
+            let mut write = Sink::new(max_write);
+
+            let mut buf = vec![0; buf_len];
+
+            assert_eq!(copy(&mut read, &mut write, &mut buf).unwrap(), 1000);
+            assert_eq!(write.data, data);
+        }
+    }
+
+    #[test]
+    fn copy_len_exact() {
+        let data = data(1000);
+
+        let mut read = Dribble::new(&data, 7);
+        let mut write = Sink::new(5);
+        let progress = RefCell::new(Vec::new());
+
+        assert_eq!(
+            copy_len_with_progress(&mut read, &mut write, &mut [0; 256], 600, |copied, left| {
+                progress.borrow_mut().push((copied, left))
+            })
+            .unwrap(),
+            600
+        );
+        assert_eq!(write.data, data[..600]);
+        // Nothing past the requested length is read
+        assert_eq!(read.rest(), &data[600..]);
+
+        let progress = progress.into_inner();
+        assert_eq!(progress.first(), Some(&(0, 600)));
+        assert_eq!(progress.last(), Some(&(600, 0)));
+        assert!(progress.iter().all(|(copied, left)| copied + left == 600));
+
+        // Nothing to copy
+        let reads = read.reads;
+        assert_eq!(copy_len(&mut read, &mut write, &mut [0; 4], 0).unwrap(), 0);
+        assert_eq!(read.reads, reads);
+    }
+
+    #[test]
+    fn copy_len_premature_eof() {
+        let data = data(100);
+
+        let mut write = Sink::new(16);
+
+        assert!(matches!(
+            copy_len(Dribble::new(&data, 30), &mut write, &mut [0; 64], 150),
+            Err(CopyError::UnexpectedEof(100))
+        ));
+        assert_eq!(write.data, data);
+
+        // `copy` takes the end as it comes
+        assert_eq!(copy(&data[..], Sink::new(16), &mut [0; 64]).unwrap(), 100);
+    }
+
+    #[test]
+    fn copy_read_failure() {
+        let data = data(100);
+
+        let mut write = Sink::new(16);
+
+        let result = copy(
+            Dribble::new(&data, 30).failing_at(50),
+            &mut write,
+            &mut [0; 64],
+        );
+
+        assert!(matches!(
+            result,
+            Err(CopyError::Read(ErrorKind::ConnectionReset))
+        ));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionReset);
+        // Everything read before the failure is written
+        assert_eq!(write.data, data[..50]);
+    }
+
+    #[test]
+    fn copy_write_failure() {
+        let data = data(100);
+
+        let mut write = Sink {
+            max_write: Some(16),
+            fail_at: Some(40),
+            ..Default::default()
+        };
+
+        let result = copy_len(&data[..], &mut write, &mut [0; 64], 100);
+
+        assert!(matches!(result, Err(CopyError::Write(ErrorKind::Other))));
+        assert_eq!(write.data, data[..48]);
+
+        let mut write = Sink {
+            full_at: Some(40),
+            ..Default::default()
+        };
+
+        let result = copy(&data[..], &mut write, &mut [0; 64]);
+
+        assert!(matches!(result, Err(CopyError::WriteZero)));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WriteZero);
+        assert_eq!(write.data, data[..40]);
+    }
+
+    #[test]
+    fn read_full() {
+        let data = data(100);
+
+        let mut buf = [0; 64];
+        assert_eq!(try_read_full(Dribble::new(&data, 7), &mut buf), Ok(64));
+        assert_eq!(buf[..], data[..64]);
+
+        let mut buf = [0; 128];
+        assert_eq!(try_read_full(Dribble::new(&data, 7), &mut buf), Ok(100));
+
+        assert_eq!(
+            try_read_full(Dribble::new(&data, 7).failing_at(30), &mut buf),
+            Err((ErrorKind::ConnectionReset, 30))
+        );
+    }
+
+    const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\n\r\nbody";
+
+    fn lines<R: BufRead>(mut read: R) -> Vec<Vec<u8>> {
+        let mut lines = Vec::new();
+        let mut out = [0; 32];
+
+        loop {
+            let len = read_until(&mut read, b'\n', &mut out).unwrap();
+            if len == 0 {
+                break lines;
+            }
+
+            lines.push(out[..len].to_vec());
+        }
+    }
+
+    fn buffered_lines<const N: usize>() {
+        let expected: Vec<Vec<u8>> = [
+            &b"GET / HTTP/1.1\r\n"[..],
+            b"Host: example.com\r\n",
+            b"Accept: */*\r\n",
+            b"\r\n",
+            b"body",
+        ]
+        .iter()
+        .map(|line| line.to_vec())
+        .collect();
+
+        for chunk in 1..=7 {
+            assert_eq!(
+                lines(BufReader::<_, N>::new(Dribble::new(REQUEST, chunk))),
+                expected,
+                "{} {}",
+                N,
+                chunk
+            );
+        }
+    }
+
+    #[test]
+    fn read_until_straddling_buffers() {
+        buffered_lines::<1>();
+        buffered_lines::<2>();
+        buffered_lines::<3>();
+        buffered_lines::<5>();
+        buffered_lines::<8>();
+        buffered_lines::<17>();
+        buffered_lines::<256>();
+    }
+
+    #[test]
+    fn read_until_too_long() {
+        let mut read = BufReader::<_, 4>::new(Dribble::new(REQUEST, 3));
+        let mut out = [0; 10];
+
+        assert!(matches!(
+            read_until(&mut read, b'\n', &mut out),
+            Err(ReadUntilError::TooLong)
+        ));
+        assert_eq!(&out, b"GET / HTTP");
+
+        // The rest of the line follows
+        assert_eq!(read_until(&mut read, b'\n', &mut out).unwrap(), 6);
+        assert_eq!(&out[..6], b"/1.1\r\n");
+
+        // Exactly fits
+        let mut out = [0; 19];
+        assert_eq!(read_until(&mut read, b'\n', &mut out).unwrap(), 19);
+        assert_eq!(&out, b"Host: example.com\r\n");
+    }
+
+    #[test]
+    fn read_until_read_failure() {
+        let mut read = BufReader::<_, 8>::new(Dribble::new(REQUEST, 5).failing_at(20));
+        let mut out = [0; 32];
+
+        assert_eq!(read_until(&mut read, b'\n', &mut out).unwrap(), 16);
+        assert!(matches!(
+            read_until(&mut read, b'\n', &mut out),
+            Err(ReadUntilError::Read(ErrorKind::ConnectionReset))
+        ));
+    }
+
+    #[test]
+    fn buf_reader_buffers() {
+        let data = data(64);
+
+        let mut read = BufReader::<_, 16>::new(Dribble::new(&data, 64));
+        let mut byte = [0];
+
+        for expected in &data {
+            assert_eq!(read.read(&mut byte).unwrap(), 1);
+            assert_eq!(byte[0], *expected);
+        }
+
+        assert_eq!(read.read(&mut byte).unwrap(), 0);
+        assert_eq!(read.get_ref().reads, 5);
+    }
+
+    #[test]
+    fn buf_reader_reads_through() {
+        let data = data(64);
+
+        let mut read = BufReader::<_, 16>::new(Dribble::new(&data, 64));
+        let mut buf = [0; 40];
+
+        // Nothing buffered, and a large buffer: read directly
+        assert_eq!(read.read(&mut buf).unwrap(), 40);
+        assert_eq!(buf[..], data[..40]);
+        assert!(read.buffer().is_empty());
+
+        // Buffered: the buffered data goes first
+        assert_eq!(read.read(&mut buf[..1]).unwrap(), 1);
+        assert_eq!(read.buffer(), &data[41..56]);
+        assert_eq!(read.read(&mut buf).unwrap(), 15);
+        assert_eq!(buf[..15], data[41..56]);
+
+        assert_eq!(read.read(&mut buf).unwrap(), 8);
+        assert_eq!(read.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn buf_reader_fill_and_consume() {
+        let data = data(20);
+
+        let mut read = BufReader::<_, 8>::new(Dribble::new(&data, 5));
+
+        assert_eq!(read.fill_buf().unwrap(), &data[..5]);
+        // Does not read more while something is buffered
+        assert_eq!(read.fill_buf().unwrap(), &data[..5]);
+
+        read.consume(3);
+        assert_eq!(read.buffer(), &data[3..5]);
+
+        // Consuming more than buffered is capped
+        read.consume(10);
+        assert!(read.buffer().is_empty());
+        assert_eq!(read.fill_buf().unwrap(), &data[5..10]);
+
+        assert_eq!(read.into_inner().read, 10);
+    }
+
+    #[test]
+    fn take_at_limit() {
+        let data = data(10);
+
+        let mut read = take(Dribble::new(&data, 3), 4);
+        let mut buf = [0; 16];
+
+        assert_eq!(read.read(&mut buf).unwrap(), 3);
+        assert_eq!(read.remaining(), 1);
+        assert_eq!(read.read(&mut buf).unwrap(), 1);
+        assert_eq!(read.remaining(), 0);
+
+        // The end, without reading the underlying reader any further
+        let reads = read.get_ref().reads;
+        assert_eq!(read.read(&mut buf).unwrap(), 0);
+        assert_eq!(read.get_ref().reads, reads);
+        assert_eq!(read.get_ref().rest(), &data[4..]);
+
+        read.set_remaining(100);
+        assert_eq!(copy(&mut read, Sink::new(2), &mut buf).unwrap(), 6);
+        // The underlying reader ended first
+        assert_eq!(read.remaining(), 94);
+
+        assert_eq!(take(&data[..], 0).read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn take_buffered() {
+        let data = data(20);
+
+        let mut read = take(BufReader::<_, 8>::new(Dribble::new(&data, 20)), 10);
+
+        assert_eq!(read.fill_buf().unwrap(), &data[..8]);
+        read.consume(6);
+        assert_eq!(read.remaining(), 4);
+
+        // Limited, even though more is buffered
+        assert_eq!(read.fill_buf().unwrap(), &data[6..8]);
+        read.consume(2);
+        assert_eq!(read.fill_buf().unwrap(), &data[8..10]);
+
+        // Consuming past the limit is capped
+        read.consume(5);
+        assert_eq!(read.remaining(), 0);
+        assert!(read.fill_buf().unwrap().is_empty());
+
+        let mut read = read.into_inner();
+        assert_eq!(read.fill_buf().unwrap(), &data[10..16]);
+    }
+
+    #[test]
+    fn chain_switches_at_end() {
+        let head = data(5);
+        let data = data(20);
+
+        let mut read = chain(&head[..], Dribble::new(&data[5..], 4));
+        let mut buf = [0; 3];
+
+        assert_eq!(read.read(&mut buf).unwrap(), 3);
+        // The rest of the first reader only, not topped up from the second one
+        assert_eq!(read.read(&mut buf).unwrap(), 2);
+        // An empty buffer does not switch
+        assert_eq!(read.read(&mut []).unwrap(), 0);
+        assert_eq!(read.get_ref().1.reads, 0);
+
+        let mut write = Sink::new(7);
+        assert_eq!(copy(&mut read, &mut write, &mut buf).unwrap(), 15);
+        assert_eq!(write.data, data[5..]);
+        assert_eq!(read.read(&mut buf).unwrap(), 0);
+
+        // An empty first reader
+        let mut write = Sink::new(7);
+        assert_eq!(
+            copy(chain(&[][..], &data[..]), &mut write, &mut buf).unwrap(),
+            20
+        );
+        assert_eq!(write.data, data);
+    }
+
+    #[test]
+    fn chain_failures() {
+        let data = data(20);
+
+        let mut read = chain(Dribble::new(&data, 4).failing_at(8), &data[..]);
+        let mut buf = [0; 16];
+
+        assert_eq!(read.read(&mut buf).unwrap(), 4);
+        assert_eq!(read.read(&mut buf).unwrap(), 4);
+
+        let result = read.read(&mut buf);
+        assert!(matches!(
+            result,
+            Err(ChainError::First(ErrorKind::ConnectionReset))
+        ));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionReset);
+
+        let mut read = chain(&data[..4], Dribble::new(&data, 4).failing_at(0));
+
+        assert_eq!(read.read(&mut buf).unwrap(), 4);
+        assert!(matches!(
+            read.read(&mut buf),
+            Err(ChainError::Second(ErrorKind::ConnectionReset))
+        ));
+    }
+
+    #[test]
+    fn chain_and_take() {
+        // The part of a body read along with the headers, followed by the rest of the body and the next request
+        let body = data(30);
+        let mut stream = body[10..].to_vec();
+        stream.extend_from_slice(b"GET / HTTP/1.1\r\n");
+
+        let mut connection = Dribble::new(&stream, 6);
+        let mut write = Sink::new(5);
+
+        let mut read = take(chain(&body[..10], &mut connection), 30);
+
+        assert_eq!(copy(&mut read, &mut write, &mut [0; 8]).unwrap(), 30);
+        assert_eq!(write.data, body);
+        assert_eq!(connection.rest(), b"GET / HTTP/1.1\r\n");
+    }
+
+    /// All the lines, with the errors as their `Debug` output
+    fn all_lines<R: BufRead, const N: usize>(
+        lines: &mut Lines<R, N>,
+    ) -> Vec<Result<String, String>> {
+        let mut all = Vec::new();
+
+        loop {
+            match lines.next_line() {
+                Ok(Some(line)) => all.push(Ok(line.to_string())),
+                Ok(None) => break all,
+                Err(e) => all.push(Err(format!("{:?}", e))),
+            }
+        }
+    }
+
+    fn dribbled_lines<const B: usize, const N: usize>(
+        data: &[u8],
+    ) -> Vec<Vec<Result<String, String>>> {
+        (1..=5)
+            .map(|chunk| {
+                all_lines(&mut Lines::<_, N>::new(BufReader::<_, B>::new(
+                    Dribble::new(data, chunk),
+                )))
+            })
+            .collect()
+    }
+
+    fn assert_lines<const N: usize>(data: &[u8], expected: &[Result<&str, &str>]) {
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|line| line.map(str::to_string).map_err(str::to_string))
+            .collect();
+
+        for lines in dribbled_lines::<1, N>(data)
+            .into_iter()
+            .chain(dribbled_lines::<3, N>(data))
+            .chain(dribbled_lines::<16, N>(data))
+        {
+            assert_eq!(lines, expected);
+        }
+    }
+
+    #[test]
+    fn lines_terminators() {
+        assert_lines::<16>(
+            b"one\ntwo\r\n\r\n\nthree",
+            &[Ok("one"), Ok("two"), Ok(""), Ok(""), Ok("three")],
+        );
+        // A terminated last line, and no line at all
+        assert_lines::<16>(b"one\r\n", &[Ok("one")]);
+        assert_lines::<16>(b"", &[]);
+        // A CR alone is part of the line
+        assert_lines::<16>(b"a\rb\n", &[Ok("a\rb")]);
+    }
+
+    #[test]
+    fn lines_filling_the_buffer() {
+        assert_lines::<4>(b"abcd\r\nefgh\nijkl", &[Ok("abcd"), Ok("efgh"), Ok("ijkl")]);
+        assert_lines::<1>(b"a\r\nb\n\n", &[Ok("a"), Ok("b"), Ok("")]);
+    }
+
+    #[test]
+    fn lines_too_long() {
+        // The rest of the line is returned next
+        assert_lines::<4>(
+            b"abcdefgh\nabcdefghi\nok",
+            &[
+                Err("TooLong"),
+                Ok("efgh"),
+                Err("TooLong"),
+                Err("TooLong"),
+                Ok("i"),
+                Ok("ok"),
+            ],
+        );
+    }
+
+    #[test]
+    fn lines_skipped() {
+        for chunk in 1..=5 {
+            let mut lines = Lines::<_, 4>::new(BufReader::<_, 3>::new(Dribble::new(
+                b"abcdefgh\r\nok\r\nabcd\rx\nlast",
+                chunk,
+            )));
+
+            assert!(matches!(lines.next_line(), Err(LineError::TooLong)));
+            assert_eq!(lines.raw_line(), b"abcd");
+            lines.skip_line().unwrap();
+
+            assert_eq!(lines.next_line().unwrap(), Some("ok"));
+
+            // The buffer is full, and the CR is not followed by LF
+            assert!(matches!(lines.next_line(), Err(LineError::TooLong)));
+            assert_eq!(lines.raw_line(), b"abcd");
+            lines.skip_line().unwrap();
+
+            assert_eq!(lines.next_line().unwrap(), Some("last"));
+            assert_eq!(lines.next_line().unwrap(), None);
+
+            // Nothing to skip at the end
+            lines.skip_line().unwrap();
+        }
+    }
+
+    #[test]
+    fn lines_invalid_utf8() {
+        let mut lines = Lines::<_, 8>::new(BufReader::<_, 3>::new(Dribble::new(
+            b"ok\n\xff\xfe\r\nnext",
+            2,
+        )));
+
+        assert_eq!(lines.next_line().unwrap(), Some("ok"));
+        assert!(matches!(lines.next_line(), Err(LineError::Utf8(2))));
+        assert_eq!(lines.raw_line(), b"\xff\xfe");
+        assert_eq!(lines.next_line().unwrap(), Some("next"));
+    }
+
+    #[test]
+    fn lines_read_failure() {
+        let mut lines = Lines::<_, 8>::new(BufReader::<_, 3>::new(
+            Dribble::new(b"ok\nlost\n", 2).failing_at(5),
+        ));
+
+        assert_eq!(lines.next_line().unwrap(), Some("ok"));
+        assert!(matches!(
+            lines.next_line(),
+            Err(LineError::Read(ErrorKind::ConnectionReset))
+        ));
+    }
+
+    #[test]
+    fn fmt_write_adapter() {
+        use core::fmt::Write as _;
+
+        let (name, id, text) = ("abc", 42, "a string longer than the buffer");
+
+        let mut write = Sink::new(3);
+        let mut adapter = FmtWriteAdapter::<_, 8>::new(&mut write);
+
+        write!(adapter, "{}-{:>5}|{}", name, id, text).unwrap();
+        adapter.finish().unwrap();
+
+        assert_eq!(
+            write.data,
+            format!("{}-{:>5}|{}", name, id, text).as_bytes()
+        );
+    }
+
+    #[test]
+    fn fmt_write_adapter_buffers() {
+        use core::fmt::Write as _;
+
+        let mut write = Sink::default();
+
+        {
+            let mut adapter = FmtWriteAdapter::<_>::new(&mut write);
+
+            for i in 0..10 {
+                write!(adapter, "{},", i).unwrap();
+            }
+
+            // Dropped without `finish`: written out nevertheless
+        }
+
+        assert_eq!(write.data, b"0,1,2,3,4,5,6,7,8,9,");
+        assert_eq!(write.writes, 1);
+
+        let mut adapter = FmtWriteAdapter::<_, 4>::new(&mut write);
+
+        adapter.write_str("ab").unwrap();
+        adapter.write_str("cd").unwrap();
+        adapter.write_str("e").unwrap();
+        adapter.flush().unwrap();
+        drop(adapter);
+
+        assert_eq!(&write.data[20..], b"abcde");
+        assert_eq!(write.writes, 3);
+    }
+
+    #[test]
+    fn fmt_write_adapter_failure() {
+        use core::fmt::Write as _;
+
+        let mut write = Sink {
+            fail_at: Some(4),
+            max_write: Some(2),
+            ..Default::default()
+        };
+
+        let mut adapter = FmtWriteAdapter::<_, 4>::new(&mut write);
+
+        assert!(adapter.write_str("abcdefgh").is_err());
+        // Kept until taken, so that formatting stops
+        assert!(adapter.write_str("x").is_err());
+        assert_eq!(adapter.take_error(), Some(ErrorKind::Other));
+        assert_eq!(adapter.take_error(), None);
+
+        assert!(adapter.write_str("abcd").is_err());
+        assert_eq!(adapter.finish(), Err(ErrorKind::Other));
+
+        assert_eq!(write.data, b"abcd");
+
+        // Not lost when dropped either
+        let mut write = Sink {
+            fail_at: Some(0),
+            ..Default::default()
+        };
+
+        let mut adapter = FmtWriteAdapter::<_, 4>::new(&mut write);
+
+        adapter.write_str("ab").unwrap();
+        assert_eq!(adapter.finish(), Err(ErrorKind::Other));
+    }
+
+    #[test]
+    fn write_fmt() {
+        let (name, id) = ("id", 0xbeef_u16);
+
+        let mut write = Sink::new(3);
+
+        write!(write, "{}: {:04x}", name, id).unwrap();
+        assert_eq!(write.data, b"id: beef");
+
+        let mut write = Sink {
+            fail_at: Some(4),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            write!(write, "{}: {:04x}", name, id),
+            Err(crate::io::WriteFmtError::Other(ErrorKind::Other))
+        ));
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The digest of `data`, written and read through the hashing wrappers in odd-sized chunks
+    fn streamed<D>(digest: impl Fn() -> D, data: &[u8]) -> String
+    where
+        D: Digest,
+        D::Output: AsRef<[u8]>,
+    {
+        let mut digests = Vec::new();
+
+        for chunk in [1, 3, 7, 61, 1000] {
+            let mut write = HashingWrite::new(Sink::new(chunk), digest());
+            copy(data, &mut write, &mut [0; 97]).unwrap();
+
+            let (write, output) = write.finalize();
+            assert_eq!(write.data, data);
+            digests.push(hex(output.as_ref()));
+
+            let mut read = HashingRead::new(Dribble::new(data, chunk), digest());
+            copy(&mut read, Sink::default(), &mut [0; 97]).unwrap();
+
+            let (read, output) = read.finalize();
+            assert!(read.rest().is_empty());
+            digests.push(hex(output.as_ref()));
+        }
+
+        assert!(digests.windows(2).all(|pair| pair[0] == pair[1]));
+
+        digests.pop().unwrap()
+    }
+
+    #[test]
+    fn crc32_vectors() {
+        use crate::utils::digest::Crc32;
+
+        assert_eq!(streamed(Crc32::new, b""), "00000000");
+        assert_eq!(streamed(Crc32::new, b"123456789"), "cbf43926");
+        assert_eq!(
+            streamed(Crc32::new, b"The quick brown fox jumps over the lazy dog"),
+            "414fa339"
+        );
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn sha256_vectors() {
+        use crate::utils::digest::Sha256;
+
+        assert_eq!(
+            streamed(Sha256::new, b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            streamed(Sha256::new, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Padded into a second block
+        assert_eq!(
+            streamed(
+                Sha256::new,
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            streamed(Sha256::new, &[b'a'; 100_000]),
+            "6d1cf22d7cc09b085dfc25ee1a1f3ae0265804c607bc2074ad253bcc82fd81ee"
+        );
+    }
+
+    #[test]
+    fn hashing_write_short_writes() {
+        use crate::utils::digest::Crc32;
+
+        let mut write = HashingWrite::new(Sink::new(4), Crc32::new());
+
+        // Only what the writer accepted is hashed
+        assert_eq!(write.write(b"123456789").unwrap(), 4);
+
+        let mut expected = Crc32::new();
+        expected.update(b"1234");
+
+        assert_eq!(write.get_ref().data, b"1234");
+        assert_eq!(write.finalize().1, expected.finalize());
+
+        // Failing writes hash nothing
+        let mut write = HashingWrite::new(
+            Sink {
+                fail_at: Some(0),
+                ..Default::default()
+            },
+            Crc32::new(),
+        );
+
+        assert_eq!(write.write(b"123456789"), Err(ErrorKind::Other));
+        assert_eq!(write.finalize().1, Crc32::new().finalize());
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use core::future::Future;
+        use core::pin::{pin, Pin};
+        use core::task::{Context, Poll, Waker};
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        use crate::io::asynch::{BufRead, Read, Write};
+        use crate::io::{ErrorKind, ErrorType};
+        use crate::utils::asyncify::block_on;
+        use crate::utils::io::asynch::{
+            chain, copy, copy_cancellable, copy_len, read_until, take, BufReader, CancelToken,
+            CopyError, LineError, Lines,
+        };
+        use crate::utils::mutex::StdRawMutex;
+
+        use super::{data, Dribble, Sink, REQUEST};
+
+        impl Read for Dribble<'_> {
+            async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                crate::io::Read::read(self, buf)
+            }
+        }
+
+        impl Write for Sink {
+            async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                crate::io::Write::write(self, buf)
+            }
+
+            async fn flush(&mut self) -> Result<(), Self::Error> {
+                crate::io::Write::flush(self)
+            }
+        }
+
+        #[test]
+        fn copy_async() {
+            let data = data(1000);
+
+            let mut write = Sink::new(3);
+            assert_eq!(
+                block_on(copy(Dribble::new(&data, 7), &mut write, &mut [0; 16])).unwrap(),
+                1000
+            );
+            assert_eq!(write.data, data);
+
+            let mut write = Sink::new(3);
+            assert!(matches!(
+                block_on(copy_len(
+                    Dribble::new(&data, 7),
+                    &mut write,
+                    &mut [0; 16],
+                    1001
+                )),
+                Err(CopyError::UnexpectedEof(1000))
+            ));
+
+            let mut write = Sink::new(3);
+            assert!(matches!(
+                block_on(copy(
+                    Dribble::new(&data, 7).failing_at(500),
+                    &mut write,
+                    &mut [0; 16]
+                )),
+                Err(CopyError::Read(ErrorKind::ConnectionReset))
+            ));
+            assert_eq!(write.data, data[..500]);
+        }
+
+        #[test]
+        fn read_until_async() {
+            async fn lines<R: BufRead>(mut read: R) -> Vec<usize> {
+                let mut lines = Vec::new();
+                let mut out = [0; 32];
+
+                loop {
+                    match read_until(&mut read, b'\n', &mut out).await.unwrap() {
+                        0 => break lines,
+                        len => lines.push(len),
+                    }
+                }
+            }
+
+            for chunk in 1..=7 {
+                let read = BufReader::<_, 5>::new(Dribble::new(REQUEST, chunk));
+
+                assert_eq!(block_on(lines(read)), [16, 19, 13, 2, 4]);
+            }
+        }
+
+        #[test]
+        fn take_and_chain_async() {
+            let data = data(30);
+
+            let mut write = Sink::new(5);
+            let mut read = take(chain(&data[..10], Dribble::new(&data[10..], 6)), 25);
+
+            assert_eq!(
+                block_on(copy(&mut read, &mut write, &mut [0; 8])).unwrap(),
+                25
+            );
+            assert_eq!(write.data, data[..25]);
+            assert_eq!(read.remaining(), 0);
+            assert_eq!(block_on(Read::read(&mut read, &mut [0; 8])).unwrap(), 0);
+
+            let mut read = take(BufReader::<_, 8>::new(&data[..]), 12);
+
+            assert_eq!(block_on(read.fill_buf()).unwrap(), &data[..8]);
+            read.consume(8);
+            assert_eq!(block_on(read.fill_buf()).unwrap(), &data[8..12]);
+        }
+
+        #[test]
+        fn lines_async() {
+            let read = BufReader::<_, 3>::new(Dribble::new(b"abcdefgh\r\nok\r\n\nlast", 2));
+            let mut lines = Lines::<_, 4>::new(read);
+
+            assert!(matches!(
+                block_on(lines.next_line()),
+                Err(LineError::TooLong)
+            ));
+            block_on(lines.skip_line()).unwrap();
+
+            for expected in [Some("ok"), Some(""), Some("last"), None] {
+                assert_eq!(block_on(lines.next_line()).unwrap(), expected);
+            }
+        }
+
+        /// Yields once, like a writer waiting for its peer
+        struct YieldNow(bool);
+
+        impl Future for YieldNow {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                if self.0 {
+                    Poll::Ready(())
+                } else {
+                    self.0 = true;
+                    cx.waker().wake_by_ref();
+
+                    Poll::Pending
+                }
+            }
+        }
+
+        /// A `Sink` yielding before each write
+        struct SlowSink(Sink);
+
+        impl ErrorType for SlowSink {
+            type Error = ErrorKind;
+        }
+
+        impl Write for SlowSink {
+            async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+                YieldNow(false).await;
+
+                crate::io::Write::write(&mut self.0, buf)
+            }
+
+            async fn flush(&mut self) -> Result<(), Self::Error> {
+                Ok(())
+            }
+        }
+
+        #[derive(Default)]
+        struct CountingWaker(AtomicUsize);
+
+        impl Wake for CountingWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        #[test]
+        fn copy_cancelled_mid_transfer() {
+            let data = data(100);
+            let cancel = CancelToken::<StdRawMutex>::new();
+
+            let mut write = SlowSink(Sink::new(3));
+            let mut buf = [0; 16];
+            let mut progress = Vec::new();
+
+            let waker = Waker::from(Arc::new(CountingWaker::default()));
+            let mut cx = Context::from_waker(&waker);
+
+            let result = {
+                let mut copy = pin!(copy_cancellable(
+                    Dribble::new(&data, 16),
+                    &mut write,
+                    &mut buf,
+                    &cancel,
+                    |copied| progress.push(copied),
+                ));
+
+                // Within the writes of the first chunk
+                assert!(copy.as_mut().poll(&mut cx).is_pending());
+                assert!(copy.as_mut().poll(&mut cx).is_pending());
+
+                cancel.cancel();
+
+                loop {
+                    if let Poll::Ready(result) = copy.as_mut().poll(&mut cx) {
+                        break result;
+                    }
+                }
+            };
+
+            // The chunk being written when cancelled is written in full
+            assert!(matches!(result, Err(CopyError::Cancelled(16))));
+            assert_eq!(write.0.data, data[..16]);
+            assert_eq!(progress, [0, 16]);
+        }
+
+        #[test]
+        fn copy_not_cancelled() {
+            let data = data(100);
+            let cancel = CancelToken::<StdRawMutex>::new();
+
+            let mut write = SlowSink(Sink::new(3));
+            let mut progress = Vec::new();
+
+            let copied = block_on(copy_cancellable(
+                Dribble::new(&data, 16),
+                &mut write,
+                &mut [0; 16],
+                &cancel,
+                |copied| progress.push(copied),
+            ));
+
+            assert_eq!(copied.unwrap(), 100);
+            assert_eq!(write.0.data, data);
+            // Before each read, including the one at the end, and once done
+            assert_eq!(progress, [0, 16, 32, 48, 64, 80, 96, 100, 100]);
+        }
+
+        #[test]
+        fn copy_cancelled_before_start() {
+            let data = data(100);
+            let cancel = CancelToken::<StdRawMutex>::default();
+
+            cancel.cancel();
+            assert!(cancel.is_cancelled());
+
+            let mut read = Dribble::new(&data, 16);
+            let mut write = SlowSink(Sink::new(3));
+
+            assert!(matches!(
+                block_on(copy_cancellable(
+                    &mut read,
+                    &mut write,
+                    &mut [0; 16],
+                    &cancel,
+                    |_| ()
+                )),
+                Err(CopyError::Cancelled(0))
+            ));
+            assert_eq!(read.reads, 0);
+
+            // Usable again once reset
+            cancel.reset();
+
+            assert_eq!(
+                block_on(copy_cancellable(
+                    &mut read,
+                    &mut write,
+                    &mut [0; 16],
+                    &cancel,
+                    |_| ()
+                ))
+                .unwrap(),
+                100
+            );
+        }
+
+        #[test]
+        fn cancel_token_wakes() {
+            let cancel = CancelToken::<StdRawMutex>::new();
+
+            let wakes = Arc::new(CountingWaker::default());
+            let waker = Waker::from(wakes.clone());
+            let mut cx = Context::from_waker(&waker);
+
+            let mut cancelled = pin!(cancel.cancelled());
+
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+            // Registered once only
+            assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+            cancel.cancel();
+
+            assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+            assert!(cancelled.as_mut().poll(&mut cx).is_ready());
+
+            // Already cancelled
+            block_on(cancel.cancelled());
+        }
     }
 }
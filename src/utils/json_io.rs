@@ -0,0 +1,1360 @@
+//! Writers of large JSON arrays and objects, which serialize one element at a time instead of the whole collection
+//!
+//! The elements are serialized with a `SerDe` producing JSON, into a scratch buffer which only needs to fit
+//! the largest of them. An element which fails to serialize is not written at all, so the document written
+//! so far stays valid, and the writer can be used further (e.g. to skip the element).
+//!
+//! A writer dropped without calling `finish` leaves its document unterminated, which the reader detects as invalid.
+//!
+//! Going the other way, `read` deserializes a JSON document from untrusted peers within the given `Limits`,
+//! and `read_buf`, `from_slice` and `from_message` do so into types borrowing from the document.
+//! Documents which fit a buffer as a whole are written with `write`.
+//!
+//! The `asynch` module has the same functions and writers for the async IO traits.
+
+use core::convert::Infallible;
+use core::fmt::{self, Debug};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::io::{Read, Write};
+use crate::mqtt::client::{Details, Message};
+use crate::storage::SerDe;
+
+/// A deserializer which can borrow from the data it deserializes, for zero-copy deserialization
+///
+/// The counterpart of `SerDe::deserialize` for types with `&str` or `&[u8]` fields,
+/// e.g. implemented with `serde_json::from_slice`.
+pub trait BorrowingDe {
+    type Error: Debug;
+
+    fn deserialize<'a, T>(&self, slice: &'a [u8]) -> Result<T, Self::Error>
+    where
+        T: Deserialize<'a>;
+}
+
+impl<D> BorrowingDe for &D
+where
+    D: BorrowingDe,
+{
+    type Error = D::Error;
+
+    fn deserialize<'a, T>(&self, slice: &'a [u8]) -> Result<T, Self::Error>
+    where
+        T: Deserialize<'a>,
+    {
+        (*self).deserialize(slice)
+    }
+}
+
+/// The limits enforced by `read` before the document is deserialized
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Limits {
+    /// The maximum length of the document, in bytes; the read buffer limits it as well
+    pub max_len: usize,
+    /// The maximum nesting of arrays and objects
+    pub max_depth: usize,
+    /// The maximum length of a string (including object keys), in bytes, as escaped in the document
+    pub max_string_len: usize,
+}
+
+impl Limits {
+    pub const fn new() -> Self {
+        Self {
+            max_len: 4096,
+            max_depth: 16,
+            max_string_len: 1024,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JsonReadError<R, S> {
+    Read(R),
+    /// The document is longer than `Limits::max_len` or the read buffer (e.g. for a 413 response)
+    TooLarge,
+    /// The document nests deeper than `Limits::max_depth`
+    TooDeep,
+    /// The document has a string longer than `Limits::max_string_len`
+    StringTooLong,
+    /// The document has unbalanced brackets or an unterminated string
+    Malformed,
+    /// The document is not valid JSON, or does not match the deserialized type
+    Deserialize(S),
+}
+
+impl<R: Debug, S: Debug> fmt::Display for JsonReadError<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Debug, S: Debug> std::error::Error for JsonReadError<R, S> {}
+
+/// Reads the whole JSON document from `read` into `buf`, and deserializes it with `serde` once it passed `limits`
+///
+/// The limits are checked on the raw document, without parsing it beyond its brackets and strings,
+/// so that the deserializer never sees a document violating them.
+pub fn read<R, S, T>(
+    read: R,
+    serde: S,
+    buf: &mut [u8],
+    limits: &Limits,
+) -> Result<T, JsonReadError<R::Error, S::Error>>
+where
+    R: Read,
+    S: SerDe,
+    T: DeserializeOwned,
+{
+    let len = fill(read, limited(buf, limits))?;
+
+    deserialize(serde, &buf[..len], limits)
+}
+
+/// Like `read`, but deserializes types borrowing from the document, which is read into `scratch`
+///
+/// This avoids allocating for the `&str` and `&[u8]` fields of `T`. A string cannot be borrowed
+/// if it has escape sequences though, as its unescaped form is not in the document: use `Cow<'b, str>`
+/// with `#[serde(borrow)]` for such fields, so that only the strings with escape sequences are allocated,
+/// if the deserializer supports this fallback.
+pub fn read_buf<'b, R, D, T>(
+    read: R,
+    de: D,
+    scratch: &'b mut [u8],
+    limits: &Limits,
+) -> Result<T, JsonReadError<R::Error, D::Error>>
+where
+    R: Read,
+    D: BorrowingDe,
+    T: Deserialize<'b>,
+{
+    let len = fill(read, limited(scratch, limits))?;
+
+    let scratch: &'b [u8] = scratch;
+
+    deserialize_borrowed(de, &scratch[..len], limits)
+}
+
+/// Deserializes the already received document `data`, borrowing from it
+///
+/// See `read_buf` for the strings with escape sequences.
+pub fn from_slice<'b, D, T>(
+    de: D,
+    data: &'b [u8],
+    limits: &Limits,
+) -> Result<T, JsonReadError<Infallible, D::Error>>
+where
+    D: BorrowingDe,
+    T: Deserialize<'b>,
+{
+    if data.len() > limits.max_len {
+        Err(JsonReadError::TooLarge)
+    } else {
+        deserialize_borrowed(de, data, limits)
+    }
+}
+
+/// Deserializes the payload of an MQTT message, borrowing from it
+///
+/// Only complete messages can be deserialized, so a message delivered in chunks
+/// (i.e. with `Details::InitialChunk` or `Details::SubsequentChunk`) fails with `JsonReadError::Malformed`.
+pub fn from_message<'b, M, D, T>(
+    de: D,
+    message: &'b M,
+    limits: &Limits,
+) -> Result<T, JsonReadError<Infallible, D::Error>>
+where
+    M: Message + ?Sized,
+    D: BorrowingDe,
+    T: Deserialize<'b>,
+{
+    if matches!(message.details(), Details::Complete) {
+        from_slice(de, message.data(), limits)
+    } else {
+        Err(JsonReadError::Malformed)
+    }
+}
+
+/// Reads the whole document from `read` into `buf`, returning its length
+fn fill<R, S>(mut read: R, buf: &mut [u8]) -> Result<usize, JsonReadError<R::Error, S>>
+where
+    R: Read,
+{
+    let mut len = 0;
+
+    while len < buf.len() {
+        let size = read.read(&mut buf[len..]).map_err(JsonReadError::Read)?;
+        if size == 0 {
+            break;
+        }
+
+        len += size;
+    }
+
+    if len == buf.len() && read.read(&mut [0]).map_err(JsonReadError::Read)? > 0 {
+        Err(JsonReadError::TooLarge)
+    } else {
+        Ok(len)
+    }
+}
+
+fn limited<'a>(buf: &'a mut [u8], limits: &Limits) -> &'a mut [u8] {
+    let len = buf.len().min(limits.max_len);
+
+    &mut buf[..len]
+}
+
+fn deserialize<R, S, T>(
+    serde: S,
+    data: &[u8],
+    limits: &Limits,
+) -> Result<T, JsonReadError<R, S::Error>>
+where
+    S: SerDe,
+    T: DeserializeOwned,
+{
+    check(data, limits)?;
+
+    serde.deserialize(data).map_err(JsonReadError::Deserialize)
+}
+
+fn deserialize_borrowed<'b, R, D, T>(
+    de: D,
+    data: &'b [u8],
+    limits: &Limits,
+) -> Result<T, JsonReadError<R, D::Error>>
+where
+    D: BorrowingDe,
+    T: Deserialize<'b>,
+{
+    check(data, limits)?;
+
+    de.deserialize(data).map_err(JsonReadError::Deserialize)
+}
+
+/// Checks the nesting and the strings of `data` against `limits`, in constant memory
+fn check<R, S>(data: &[u8], limits: &Limits) -> Result<(), JsonReadError<R, S>> {
+    let mut depth = 0;
+    let mut string_len = None;
+    let mut escape = false;
+
+    for byte in data {
+        if let Some(len) = string_len.as_mut() {
+            *len += 1;
+
+            if *len > limits.max_string_len + 1 {
+                return Err(JsonReadError::StringTooLong);
+            }
+
+            if escape {
+                escape = false;
+            } else if *byte == b'\\' {
+                escape = true;
+            } else if *byte == b'"' {
+                string_len = None;
+            }
+        } else {
+            match byte {
+                b'"' => string_len = Some(0),
+                b'[' | b'{' => {
+                    depth += 1;
+
+                    if depth > limits.max_depth {
+                        return Err(JsonReadError::TooDeep);
+                    }
+                }
+                b']' | b'}' => {
+                    if depth == 0 {
+                        return Err(JsonReadError::Malformed);
+                    }
+
+                    depth -= 1;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    if depth > 0 || string_len.is_some() {
+        Err(JsonReadError::Malformed)
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JsonWriteError<W, S> {
+    Write(W),
+    Serialize(S),
+}
+
+impl<W: Debug, S: Debug> fmt::Display for JsonWriteError<W, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Debug, S: Debug> std::error::Error for JsonWriteError<W, S> {}
+
+/// Serializes `value` with `serde` into `buf`, and writes it to `write`, returning the length of the document
+///
+/// `buf` needs to fit the whole document; for large arrays and objects, see `JsonArrayWriter` and `JsonObjectWriter`.
+pub fn write<W, S, T>(
+    mut write: W,
+    serde: S,
+    buf: &mut [u8],
+    value: &T,
+) -> Result<usize, JsonWriteError<W::Error, S::Error>>
+where
+    W: Write,
+    S: SerDe,
+    T: Serialize,
+{
+    let data = serde
+        .serialize(buf, value)
+        .map_err(JsonWriteError::Serialize)?;
+
+    write.write_all(data).map_err(JsonWriteError::Write)?;
+
+    Ok(data.len())
+}
+
+/// Tracks the separators of the array or object being written
+struct Framing {
+    open: &'static [u8],
+    empty: &'static [u8],
+    close: &'static [u8],
+    started: bool,
+}
+
+impl Framing {
+    const fn array() -> Self {
+        Self {
+            open: b"[",
+            empty: b"[]",
+            close: b"]",
+            started: false,
+        }
+    }
+
+    const fn object() -> Self {
+        Self {
+            open: b"{",
+            empty: b"{}",
+            close: b"}",
+            started: false,
+        }
+    }
+
+    /// What goes before the next element
+    fn next(&mut self) -> &'static [u8] {
+        if core::mem::replace(&mut self.started, true) {
+            b","
+        } else {
+            self.open
+        }
+    }
+
+    fn end(&self) -> &'static [u8] {
+        if self.started {
+            self.close
+        } else {
+            self.empty
+        }
+    }
+}
+
+/// A part of a string escaped as JSON, for writing object keys
+pub(crate) enum Piece<'a> {
+    Raw(&'a [u8]),
+    Escaped([u8; 6], usize),
+}
+
+impl<'a> Piece<'a> {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Raw(bytes) => bytes,
+            Self::Escaped(bytes, len) => &bytes[..*len],
+        }
+    }
+}
+
+/// Splits `s` into the pieces of its JSON escaped form (without the quotes)
+pub(crate) fn escaped(s: &str) -> impl Iterator<Item = Piece<'_>> {
+    let mut rest = s.as_bytes();
+    let mut escape = None;
+
+    core::iter::from_fn(move || {
+        if let Some(byte) = escape.take() {
+            return Some(escape_byte(byte));
+        }
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        let len = rest
+            .iter()
+            .position(|byte| *byte == b'"' || *byte == b'\\' || *byte < 0x20)
+            .unwrap_or(rest.len());
+
+        let (raw, tail) = rest.split_at(len);
+
+        rest = match tail.split_first() {
+            Some((byte, tail)) => {
+                escape = Some(*byte);
+                tail
+            }
+            None => tail,
+        };
+
+        if raw.is_empty() {
+            escape.take().map(escape_byte)
+        } else {
+            Some(Piece::Raw(raw))
+        }
+    })
+}
+
+fn escape_byte(byte: u8) -> Piece<'static> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    match byte {
+        b'"' => Piece::Escaped(*b"\\\"    ", 2),
+        b'\\' => Piece::Escaped(*b"\\\\    ", 2),
+        b'\n' => Piece::Escaped(*b"\\n    ", 2),
+        b'\r' => Piece::Escaped(*b"\\r    ", 2),
+        b'\t' => Piece::Escaped(*b"\\t    ", 2),
+        _ => Piece::Escaped(
+            [
+                b'\\',
+                b'u',
+                b'0',
+                b'0',
+                HEX[(byte >> 4) as usize],
+                HEX[(byte & 0x0f) as usize],
+            ],
+            6,
+        ),
+    }
+}
+
+/// Writes a JSON array into `W`, serializing its elements one at a time with `push`
+pub struct JsonArrayWriter<'a, W, S> {
+    write: W,
+    serde: S,
+    buf: &'a mut [u8],
+    framing: Framing,
+}
+
+impl<'a, W, S> JsonArrayWriter<'a, W, S>
+where
+    W: Write,
+    S: SerDe,
+{
+    /// Nothing is written until the first element is pushed, or the writer is finished
+    pub fn new(write: W, serde: S, buf: &'a mut [u8]) -> Self {
+        Self {
+            write,
+            serde,
+            buf,
+            framing: Framing::array(),
+        }
+    }
+
+    pub fn push<T>(&mut self, value: &T) -> Result<(), JsonWriteError<W::Error, S::Error>>
+    where
+        T: Serialize,
+    {
+        let data = self
+            .serde
+            .serialize(self.buf, value)
+            .map_err(JsonWriteError::Serialize)?;
+
+        self.write
+            .write_all(self.framing.next())
+            .map_err(JsonWriteError::Write)?;
+
+        self.write.write_all(data).map_err(JsonWriteError::Write)
+    }
+
+    /// Closes the array, returning the writer
+    pub fn finish(mut self) -> Result<W, W::Error> {
+        self.write.write_all(self.framing.end())?;
+
+        Ok(self.write)
+    }
+}
+
+/// Writes a JSON object into `W`, serializing its values one at a time with `field`
+pub struct JsonObjectWriter<'a, W, S> {
+    write: W,
+    serde: S,
+    buf: &'a mut [u8],
+    framing: Framing,
+}
+
+impl<'a, W, S> JsonObjectWriter<'a, W, S>
+where
+    W: Write,
+    S: SerDe,
+{
+    /// Nothing is written until the first field is added, or the writer is finished
+    pub fn new(write: W, serde: S, buf: &'a mut [u8]) -> Self {
+        Self {
+            write,
+            serde,
+            buf,
+            framing: Framing::object(),
+        }
+    }
+
+    /// Adds the field `name`; the name is escaped as necessary
+    pub fn field<T>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), JsonWriteError<W::Error, S::Error>>
+    where
+        T: Serialize,
+    {
+        let data = self
+            .serde
+            .serialize(self.buf, value)
+            .map_err(JsonWriteError::Serialize)?;
+
+        write_field(&mut self.write, self.framing.next(), name, data).map_err(JsonWriteError::Write)
+    }
+
+    /// Closes the object, returning the writer
+    pub fn finish(mut self) -> Result<W, W::Error> {
+        self.write.write_all(self.framing.end())?;
+
+        Ok(self.write)
+    }
+}
+
+fn write_field<W>(mut write: W, separator: &[u8], name: &str, data: &[u8]) -> Result<(), W::Error>
+where
+    W: Write,
+{
+    write.write_all(separator)?;
+    write.write_all(b"\"")?;
+
+    for piece in escaped(name) {
+        write.write_all(piece.bytes())?;
+    }
+
+    write.write_all(b"\":")?;
+    write.write_all(data)
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Serialize};
+
+    use crate::io::asynch::{Read, Write};
+    use crate::storage::SerDe;
+
+    use super::{deserialize, deserialize_borrowed, escaped, limited, Framing};
+
+    pub use super::{from_message, from_slice, BorrowingDe, JsonReadError, JsonWriteError, Limits};
+
+    pub async fn read<R, S, T>(
+        read: R,
+        serde: S,
+        buf: &mut [u8],
+        limits: &Limits,
+    ) -> Result<T, JsonReadError<R::Error, S::Error>>
+    where
+        R: Read,
+        S: SerDe,
+        T: DeserializeOwned,
+    {
+        let len = fill(read, limited(buf, limits)).await?;
+
+        deserialize(serde, &buf[..len], limits)
+    }
+
+    pub async fn read_buf<'b, R, D, T>(
+        read: R,
+        de: D,
+        scratch: &'b mut [u8],
+        limits: &Limits,
+    ) -> Result<T, JsonReadError<R::Error, D::Error>>
+    where
+        R: Read,
+        D: BorrowingDe,
+        T: Deserialize<'b>,
+    {
+        let len = fill(read, limited(scratch, limits)).await?;
+
+        let scratch: &'b [u8] = scratch;
+
+        deserialize_borrowed(de, &scratch[..len], limits)
+    }
+
+    pub async fn write<W, S, T>(
+        mut write: W,
+        serde: S,
+        buf: &mut [u8],
+        value: &T,
+    ) -> Result<usize, JsonWriteError<W::Error, S::Error>>
+    where
+        W: Write,
+        S: SerDe,
+        T: Serialize,
+    {
+        let data = serde
+            .serialize(buf, value)
+            .map_err(JsonWriteError::Serialize)?;
+
+        write
+            .write_all(data)
+            .await
+            .map_err(JsonWriteError::Write)?;
+
+        Ok(data.len())
+    }
+
+    async fn fill<R, S>(mut read: R, buf: &mut [u8]) -> Result<usize, JsonReadError<R::Error, S>>
+    where
+        R: Read,
+    {
+        let mut len = 0;
+
+        while len < buf.len() {
+            let size = read
+                .read(&mut buf[len..])
+                .await
+                .map_err(JsonReadError::Read)?;
+            if size == 0 {
+                break;
+            }
+
+            len += size;
+        }
+
+        if len == buf.len() && read.read(&mut [0]).await.map_err(JsonReadError::Read)? > 0 {
+            Err(JsonReadError::TooLarge)
+        } else {
+            Ok(len)
+        }
+    }
+
+    /// The async flavor of `super::JsonArrayWriter`
+    pub struct JsonArrayWriter<'a, W, S> {
+        write: W,
+        serde: S,
+        buf: &'a mut [u8],
+        framing: Framing,
+    }
+
+    impl<'a, W, S> JsonArrayWriter<'a, W, S>
+    where
+        W: Write,
+        S: SerDe,
+    {
+        pub fn new(write: W, serde: S, buf: &'a mut [u8]) -> Self {
+            Self {
+                write,
+                serde,
+                buf,
+                framing: Framing::array(),
+            }
+        }
+
+        pub async fn push<T>(&mut self, value: &T) -> Result<(), JsonWriteError<W::Error, S::Error>>
+        where
+            T: Serialize,
+        {
+            let data = self
+                .serde
+                .serialize(self.buf, value)
+                .map_err(JsonWriteError::Serialize)?;
+
+            self.write
+                .write_all(self.framing.next())
+                .await
+                .map_err(JsonWriteError::Write)?;
+
+            self.write
+                .write_all(data)
+                .await
+                .map_err(JsonWriteError::Write)
+        }
+
+        pub async fn finish(mut self) -> Result<W, W::Error> {
+            self.write.write_all(self.framing.end()).await?;
+
+            Ok(self.write)
+        }
+    }
+
+    /// The async flavor of `super::JsonObjectWriter`
+    pub struct JsonObjectWriter<'a, W, S> {
+        write: W,
+        serde: S,
+        buf: &'a mut [u8],
+        framing: Framing,
+    }
+
+    impl<'a, W, S> JsonObjectWriter<'a, W, S>
+    where
+        W: Write,
+        S: SerDe,
+    {
+        pub fn new(write: W, serde: S, buf: &'a mut [u8]) -> Self {
+            Self {
+                write,
+                serde,
+                buf,
+                framing: Framing::object(),
+            }
+        }
+
+        pub async fn field<T>(
+            &mut self,
+            name: &str,
+            value: &T,
+        ) -> Result<(), JsonWriteError<W::Error, S::Error>>
+        where
+            T: Serialize,
+        {
+            let data = self
+                .serde
+                .serialize(self.buf, value)
+                .map_err(JsonWriteError::Serialize)?;
+
+            write_field(&mut self.write, self.framing.next(), name, data)
+                .await
+                .map_err(JsonWriteError::Write)
+        }
+
+        pub async fn finish(mut self) -> Result<W, W::Error> {
+            self.write.write_all(self.framing.end()).await?;
+
+            Ok(self.write)
+        }
+    }
+
+    async fn write_field<W>(
+        mut write: W,
+        separator: &[u8],
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), W::Error>
+    where
+        W: Write,
+    {
+        write.write_all(separator).await?;
+        write.write_all(b"\"").await?;
+
+        for piece in escaped(name) {
+            write.write_all(piece.bytes()).await?;
+        }
+
+        write.write_all(b"\":").await?;
+        write.write_all(data).await
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::borrow::Cow;
+    use std::collections::BTreeMap;
+
+    use serde::de::IgnoredAny;
+    use serde::{Deserialize, Serialize};
+
+    use crate::io::{ErrorKind, ErrorType, Read, SliceWriteError};
+    use crate::mqtt::client::{InitialChunkData, MessageId};
+    use crate::test_json::JsonSerDe;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Command {
+        id: u32,
+        action: String,
+        args: Vec<i64>,
+    }
+
+    fn command() -> Command {
+        Command {
+            id: 7,
+            action: "set \"level\"".into(),
+            args: vec![-1, 0, 1 << 40],
+        }
+    }
+
+    const COMMAND: &[u8] = br#"{"id":7,"action":"set \"level\"","args":[-1,0,1099511627776]}"#;
+
+    /// A reader returning the document in chunks of at most `chunk` bytes
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> Chunked<'a> {
+        fn new(data: &'a [u8], chunk: usize) -> Self {
+            Self { data, chunk }
+        }
+    }
+
+    impl ErrorType for Chunked<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.chunk).min(self.data.len());
+
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn write_document() {
+        let mut out = Vec::new();
+
+        assert_eq!(
+            write(&mut out, JsonSerDe, &mut [0; 128], &command()).unwrap(),
+            COMMAND.len()
+        );
+        assert_eq!(out, COMMAND);
+
+        // Does not fit the buffer; nothing is written
+        let mut out = Vec::new();
+        assert!(matches!(
+            write(&mut out, JsonSerDe, &mut [0; 16], &command()),
+            Err(JsonWriteError::Serialize(_))
+        ));
+        assert!(out.is_empty());
+
+        let mut out = [0; 16];
+        assert!(matches!(
+            write(&mut out[..], JsonSerDe, &mut [0; 128], &command()),
+            Err(JsonWriteError::Write(SliceWriteError::Full))
+        ));
+    }
+
+    fn commands(count: u32) -> Vec<Command> {
+        (0..count).map(|id| Command { id, ..command() }).collect()
+    }
+
+    #[test]
+    fn array_writer() {
+        let mut out = Vec::new();
+        let mut buf = [0; 64];
+
+        let mut array = JsonArrayWriter::new(&mut out, JsonSerDe, &mut buf);
+
+        for command in commands(3) {
+            array.push(&command).unwrap();
+        }
+
+        // Only needs to fit the element; the one which does not is skipped
+        let large = Command {
+            action: "a".repeat(64),
+            ..command()
+        };
+        assert!(matches!(
+            array.push(&large),
+            Err(JsonWriteError::Serialize(_))
+        ));
+
+        array.push(&command()).unwrap();
+        array.finish().unwrap();
+
+        let mut expected = commands(3);
+        expected.push(command());
+
+        assert!(out.len() > 64);
+        assert_eq!(
+            from_slice::<_, Vec<Command>>(JsonSerDe, &out, &Limits::new()).unwrap(),
+            expected
+        );
+        assert_eq!(out, crate::test_json::to_vec(&expected));
+
+        let mut out = Vec::new();
+        JsonArrayWriter::new(&mut out, JsonSerDe, &mut buf)
+            .finish()
+            .unwrap();
+        assert_eq!(out, b"[]");
+
+        let mut out = [0; 16];
+        let mut array = JsonArrayWriter::new(&mut out[..], JsonSerDe, &mut buf);
+        assert!(matches!(
+            array.push(&command()),
+            Err(JsonWriteError::Write(SliceWriteError::Full))
+        ));
+    }
+
+    #[test]
+    fn object_writer() {
+        let mut out = Vec::new();
+        let mut buf = [0; 64];
+
+        let mut object = JsonObjectWriter::new(&mut out, JsonSerDe, &mut buf);
+
+        object.field("id", &7).unwrap();
+        object.field("say \"hi\"", &"hi").unwrap();
+        assert!(matches!(
+            object.field("args", &vec![0_u64; 64]),
+            Err(JsonWriteError::Serialize(_))
+        ));
+        object.field("tab\t\u{1}", &[true]).unwrap();
+        object.finish().unwrap();
+
+        assert_eq!(
+            out,
+            &br#"{"id":7,"say \"hi\"":"hi","tab\t\u0001":[true]}"#[..]
+        );
+
+        let fields =
+            from_slice::<_, BTreeMap<String, IgnoredAny>>(JsonSerDe, &out, &Limits::new()).unwrap();
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["id", "say \"hi\"", "tab\t\u{1}"]
+        );
+
+        let mut out = Vec::new();
+        JsonObjectWriter::new(&mut out, JsonSerDe, &mut buf)
+            .finish()
+            .unwrap();
+        assert_eq!(out, b"{}");
+    }
+
+    #[test]
+    fn writers_unfinished() {
+        let mut out = Vec::new();
+        let mut buf = [0; 64];
+
+        // Dropped without `finish`
+        {
+            let mut array = JsonArrayWriter::new(&mut out, JsonSerDe, &mut buf);
+            array.push(&1).unwrap();
+            array.push(&2).unwrap();
+        }
+
+        assert_eq!(out, b"[1,2");
+        assert!(matches!(
+            from_slice::<_, IgnoredAny>(JsonSerDe, &out, &Limits::new()),
+            Err(JsonReadError::Malformed)
+        ));
+
+        let mut out = Vec::new();
+
+        {
+            let mut object = JsonObjectWriter::new(&mut out, JsonSerDe, &mut buf);
+            object.field("key", &1).unwrap();
+        }
+
+        assert!(matches!(
+            from_slice::<_, IgnoredAny>(JsonSerDe, &out, &Limits::new()),
+            Err(JsonReadError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn read_chunked() {
+        for chunk in 1..=9 {
+            assert_eq!(
+                read::<_, _, Command>(
+                    Chunked::new(COMMAND, chunk),
+                    JsonSerDe,
+                    &mut [0; 128],
+                    &Limits::new()
+                )
+                .unwrap(),
+                command()
+            );
+        }
+    }
+
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut document = vec![b'['; depth];
+        document.resize(depth * 2, b']');
+
+        document
+    }
+
+    fn with_string(len: usize) -> Vec<u8> {
+        let mut document = br#"{"key":""#.to_vec();
+        document.resize(document.len() + len, b'a');
+        document.extend_from_slice(br#""}"#);
+
+        document
+    }
+
+    #[test]
+    fn read_too_deep() {
+        let document = nested(1000);
+
+        let result = read::<_, _, IgnoredAny>(
+            Chunked::new(&document, 64),
+            JsonSerDe,
+            &mut [0; 4096],
+            &Limits::new(),
+        );
+        assert!(matches!(result, Err(JsonReadError::TooDeep)));
+
+        // Rejected before the deserializer recurses into it
+        let result = from_slice::<_, IgnoredAny>(JsonSerDe, &document, &Limits::new());
+        assert!(matches!(result, Err(JsonReadError::TooDeep)));
+
+        // Within the limit; the brackets in strings do not count
+        let mut document = b"[\"[[[[\",".to_vec();
+        document.extend_from_slice(&nested(15));
+        document.push(b']');
+
+        from_slice::<_, IgnoredAny>(JsonSerDe, &document, &Limits::new()).unwrap();
+
+        let result = from_slice::<_, IgnoredAny>(JsonSerDe, &nested(17), &Limits::new());
+        assert!(matches!(result, Err(JsonReadError::TooDeep)));
+    }
+
+    #[test]
+    fn read_too_large() {
+        let document = with_string(1 << 20);
+
+        let result = read::<_, _, IgnoredAny>(
+            Chunked::new(&document, 1000),
+            JsonSerDe,
+            &mut [0; 8192],
+            &Limits::new(),
+        );
+        assert!(matches!(result, Err(JsonReadError::TooLarge)));
+
+        // The read buffer limits the document as well
+        let limits = Limits {
+            max_len: 2 << 20,
+            ..Limits::new()
+        };
+
+        let result = read::<_, _, IgnoredAny>(
+            Chunked::new(&document, 1000),
+            JsonSerDe,
+            &mut [0; 4096],
+            &limits,
+        );
+        assert!(matches!(result, Err(JsonReadError::TooLarge)));
+
+        let result = from_slice::<_, IgnoredAny>(JsonSerDe, &document, &Limits::new());
+        assert!(matches!(result, Err(JsonReadError::TooLarge)));
+
+        // A document filling the buffer exactly is not too large
+        let document = with_string(4096 - 10);
+        assert_eq!(document.len(), 4096);
+
+        read::<_, _, IgnoredAny>(
+            Chunked::new(&document, 1000),
+            JsonSerDe,
+            &mut [0; 4096],
+            &Limits {
+                max_string_len: 4096,
+                ..Limits::new()
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn read_string_too_long() {
+        let limits = Limits {
+            max_len: 2 << 20,
+            ..Limits::new()
+        };
+
+        let document = with_string(1 << 20);
+
+        let result = read::<_, _, IgnoredAny>(
+            Chunked::new(&document, 1000),
+            JsonSerDe,
+            &mut vec![0; 2 << 20],
+            &limits,
+        );
+        assert!(matches!(result, Err(JsonReadError::StringTooLong)));
+
+        // The escape sequences count with their escaped length
+        let document = br#"{"key":"\"\"\"\""}"#;
+        let limits = Limits {
+            max_string_len: 7,
+            ..Limits::new()
+        };
+
+        let result = from_slice::<_, IgnoredAny>(JsonSerDe, document, &limits);
+        assert!(matches!(result, Err(JsonReadError::StringTooLong)));
+
+        let limits = Limits {
+            max_string_len: 8,
+            ..Limits::new()
+        };
+
+        from_slice::<_, IgnoredAny>(JsonSerDe, document, &limits).unwrap();
+    }
+
+    #[test]
+    fn read_malformed() {
+        for document in [&b"[1, 2"[..], b"[1]]", b"{\"key\": \"value}", b"}{"] {
+            let result = from_slice::<_, IgnoredAny>(JsonSerDe, document, &Limits::new());
+            assert!(matches!(result, Err(JsonReadError::Malformed)));
+        }
+
+        let result = read::<_, _, IgnoredAny>(
+            Chunked::new(b"[1, [2]", 1),
+            JsonSerDe,
+            &mut [0; 64],
+            &Limits::new(),
+        );
+        assert!(matches!(result, Err(JsonReadError::Malformed)));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        label: Cow<'a, str>,
+    }
+
+    struct MockMessage {
+        data: Vec<u8>,
+        details: Details,
+    }
+
+    impl Message for MockMessage {
+        fn id(&self) -> MessageId {
+            0
+        }
+
+        fn topic(&self) -> Option<&str> {
+            Some("commands")
+        }
+
+        fn data(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn details(&self) -> &Details {
+            &self.details
+        }
+    }
+
+    #[test]
+    fn read_buf_borrowed() {
+        let document = br#"{"name":"sensor","label":"kitchen"}"#;
+        let mut scratch = [0; 64];
+        let range = scratch.as_ptr_range();
+
+        let value = read_buf::<_, _, Borrowed>(
+            Chunked::new(document, 5),
+            JsonSerDe,
+            &mut scratch,
+            &Limits::new(),
+        )
+        .unwrap();
+
+        assert_eq!(value.name, "sensor");
+        assert!(matches!(value.label, Cow::Borrowed("kitchen")));
+
+        // The strings point into the scratch buffer
+        assert!(range.contains(&value.name.as_ptr()));
+        assert!(range.contains(&value.label.as_ptr()));
+    }
+
+    #[test]
+    fn from_slice_escaped() {
+        let document = br#"{"name":"sensor","label":"living \"room\""}"#;
+
+        let value = from_slice::<_, Borrowed>(JsonSerDe, document, &Limits::new()).unwrap();
+
+        assert_eq!(value.name, "sensor");
+        assert!(matches!(value.label, Cow::Owned(ref label) if label == "living \"room\""));
+
+        // A `&str` cannot fall back to an owned string
+        let document = br#"{"name":"sensor \"2\"","label":"kitchen"}"#;
+
+        let result = from_slice::<_, Borrowed>(JsonSerDe, document, &Limits::new());
+        assert!(matches!(result, Err(JsonReadError::Deserialize(_))));
+    }
+
+    #[test]
+    fn from_message_complete() {
+        let message = MockMessage {
+            data: br#"{"name":"sensor","label":"kitchen"}"#.to_vec(),
+            details: Details::Complete,
+        };
+
+        let value = from_message::<_, _, Borrowed>(JsonSerDe, &message, &Limits::new()).unwrap();
+
+        assert_eq!(
+            value,
+            Borrowed {
+                name: "sensor",
+                label: "kitchen".into(),
+            }
+        );
+        assert!(message.data.as_ptr_range().contains(&value.name.as_ptr()));
+
+        let message = MockMessage {
+            details: Details::InitialChunk(InitialChunkData {
+                total_data_size: 100,
+            }),
+            ..message
+        };
+
+        let result = from_message::<_, _, Borrowed>(JsonSerDe, &message, &Limits::new());
+        assert!(matches!(result, Err(JsonReadError::Malformed)));
+
+        let message = MockMessage {
+            details: Details::Complete,
+            ..message
+        };
+
+        let limits = Limits {
+            max_len: 16,
+            ..Limits::new()
+        };
+
+        let result = from_message::<_, _, Borrowed>(JsonSerDe, &message, &limits);
+        assert!(matches!(result, Err(JsonReadError::TooLarge)));
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use crate::utils::asyncify::block_on;
+        use crate::utils::json_io::asynch::{
+            read, write, JsonArrayWriter, JsonObjectWriter, JsonReadError, JsonWriteError, Limits,
+        };
+
+        use super::{command, commands, Chunked, Command, JsonSerDe, COMMAND};
+
+        impl crate::io::asynch::Read for Chunked<'_> {
+            async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+                crate::io::Read::read(self, buf)
+            }
+        }
+
+        #[test]
+        fn read_chunked_async() {
+            let mut buf = [0; 128];
+            let limits = Limits::new();
+
+            for chunk in 1..=9 {
+                let read = read::<_, _, Command>(
+                    Chunked::new(COMMAND, chunk),
+                    JsonSerDe,
+                    &mut buf,
+                    &limits,
+                );
+
+                assert_eq!(block_on(read).unwrap(), command());
+            }
+        }
+
+        #[test]
+        fn read_limits_async() {
+            let limits = Limits {
+                max_len: COMMAND.len() - 1,
+                ..Limits::new()
+            };
+
+            let result = block_on(read::<_, _, Command>(
+                Chunked::new(COMMAND, 3),
+                JsonSerDe,
+                &mut [0; 128],
+                &limits,
+            ));
+            assert!(matches!(result, Err(JsonReadError::TooLarge)));
+
+            let limits = Limits {
+                max_depth: 0,
+                ..Limits::new()
+            };
+
+            let result = block_on(read::<_, _, Command>(
+                Chunked::new(COMMAND, 3),
+                JsonSerDe,
+                &mut [0; 128],
+                &limits,
+            ));
+            assert!(matches!(result, Err(JsonReadError::TooDeep)));
+
+            let result = block_on(read::<_, _, Command>(
+                Chunked::new(&COMMAND[..COMMAND.len() - 1], 3),
+                JsonSerDe,
+                &mut [0; 128],
+                &Limits::new(),
+            ));
+            assert!(matches!(result, Err(JsonReadError::Malformed)));
+        }
+
+        #[test]
+        fn write_async() {
+            let mut out = Vec::new();
+
+            let len = block_on(write(&mut out, JsonSerDe, &mut [0; 128], &command()));
+
+            assert_eq!(len.unwrap(), COMMAND.len());
+            assert_eq!(out, COMMAND);
+
+            let mut out = Vec::new();
+            let result = block_on(write(&mut out, JsonSerDe, &mut [0; 16], &command()));
+
+            assert!(matches!(result, Err(JsonWriteError::Serialize(_))));
+            assert!(out.is_empty());
+        }
+
+        #[test]
+        fn writers_async() {
+            let mut out = Vec::new();
+            let mut buf = [0; 64];
+
+            let mut array = JsonArrayWriter::new(&mut out, JsonSerDe, &mut buf);
+
+            block_on(async {
+                for command in commands(3) {
+                    array.push(&command).await.unwrap();
+                }
+
+                assert!(matches!(
+                    array.push(&vec![0_u64; 64]).await,
+                    Err(JsonWriteError::Serialize(_))
+                ));
+
+                array.finish().await.unwrap();
+            });
+
+            assert_eq!(out, crate::test_json::to_vec(&commands(3)));
+
+            let mut out = Vec::new();
+
+            let mut object = JsonObjectWriter::new(&mut out, JsonSerDe, &mut buf);
+
+            block_on(async {
+                object.field("id", &7).await.unwrap();
+                object.field("\"", &()).await.unwrap();
+                object.finish().await.unwrap();
+            });
+
+            assert_eq!(out, br#"{"id":7,"\"":null}"#);
+        }
+    }
+}
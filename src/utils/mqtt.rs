@@ -2,12 +2,14 @@
 pub mod client {
     use core::fmt::Debug;
     use core::mem;
+    use core::time::Duration;
 
     use alloc::sync::Arc;
 
     use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
 
     use crate::mqtt::client::{ErrorType, Event};
+    use crate::sys_time::Monotonic;
 
     pub struct ConnStateGuard<CV, S>
     where
@@ -61,6 +63,44 @@ pub mod client {
         }
     }
 
+    impl<CV, M, E> ConnStateGuard<CV, ConnState<M, E>>
+    where
+        CV: RawCondvar,
+    {
+        /// Waits for up to `timeout` for the event still pending (if any) to be taken by the `Connection`,
+        /// and then closes the state, dropping the event if it was still pending
+        ///
+        /// Returns `true` if no event was dropped.
+        pub fn close_timeout<C>(&self, clock: &C, timeout: Duration) -> bool
+        where
+            C: Monotonic,
+        {
+            let deadline = clock.now() + timeout;
+
+            let mut state = self.state.lock();
+
+            let drained = loop {
+                match &*state {
+                    Some(ConnState(Some(_))) => (),
+                    _ => break true,
+                }
+
+                let now = clock.now();
+
+                if now >= deadline {
+                    break false;
+                }
+
+                state = self.state_changed.wait_timeout(state, deadline - now).0;
+            };
+
+            *state = None;
+            self.state_changed.notify_all();
+
+            drained
+        }
+    }
+
     pub struct ConnState<M, E>(Option<Result<Event<M>, E>>);
 
     impl<M, E> Default for ConnState<M, E> {
@@ -156,3 +196,120 @@ pub mod client {
         }
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::time::Duration;
+
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use crate::mqtt::client::Event;
+    use crate::sys_time::{SimulatedSystemTime, StdMonotonic, SystemTimeSetter};
+    use crate::test_timer::MockTimerService;
+    use crate::utils::mutex::StdRawCondvar;
+
+    use super::client::*;
+
+    type State = ConnStateGuard<StdRawCondvar, ConnState<u32, ()>>;
+    type TestPostbox = Postbox<StdRawCondvar, u32, ()>;
+    type TestConnection = Connection<StdRawCondvar, u32, ()>;
+
+    fn connected() -> (Arc<State>, TestPostbox, TestConnection) {
+        let state = Arc::new(State::new_default());
+
+        (
+            state.clone(),
+            Postbox::new(state.clone()),
+            Connection::new(state),
+        )
+    }
+
+    #[test]
+    fn close_timeout_idle() {
+        let (state, _, mut connection) = connected();
+
+        assert!(state.close_timeout(&StdMonotonic, Duration::from_secs(5)));
+        assert_eq!(connection.next(), None);
+    }
+
+    #[test]
+    fn close_timeout_drained() {
+        let (state, mut postbox, mut connection) = connected();
+
+        postbox.post(Ok(Event::Received(1)));
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+
+                assert_eq!(connection.next(), Some(Ok(Event::Received(1))));
+            });
+
+            assert!(state.close_timeout(&StdMonotonic, Duration::from_secs(5)));
+        });
+
+        // Posting to a closed state does not wait
+        postbox.post(Ok(Event::Received(2)));
+        assert_eq!(connection.next(), None);
+    }
+
+    #[test]
+    fn close_timeout_expired() {
+        let (state, mut postbox, mut connection) = connected();
+
+        postbox.post(Ok(Event::Received(1)));
+
+        let start = Instant::now();
+
+        assert!(!state.close_timeout(&StdMonotonic, Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // The pending event was dropped
+        assert_eq!(connection.next(), None);
+    }
+
+    #[test]
+    fn close_timeout_clock() {
+        let (state, mut postbox, _connection) = connected();
+        let clock = MockTimerService::new();
+
+        postbox.post(Ok(Event::Received(1)));
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+
+                clock.set_now(Duration::from_secs(1));
+            });
+
+            // Only the clock tells when the timeout expires, however long the condvar waits actually take
+            let start = Instant::now();
+
+            assert!(!state.close_timeout(&clock, Duration::from_millis(5)));
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        });
+    }
+
+    #[test]
+    fn close_timeout_stepped() {
+        let (state, mut postbox, _connection) = connected();
+        let wall_clock = SimulatedSystemTime::new();
+
+        postbox.post(Ok(Event::Received(1)));
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(10));
+
+                wall_clock.set_time(Duration::from_secs(60)).unwrap();
+            });
+
+            // Stepping the wall clock back (e.g. on an SNTP sync) does not extend the wait
+            let start = Instant::now();
+
+            assert!(!state.close_timeout(&StdMonotonic, Duration::from_millis(50)));
+            assert!(start.elapsed() < Duration::from_secs(5));
+        });
+    }
+}
@@ -2,6 +2,9 @@ use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
 use core::time::Duration;
 
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, TryLockError};
+
 /// A raw Mutex trait for no_std environments. Prevents the introduction of dependency on STD for the `utils` module and its sub-modules.
 /// NOTE: Users are strongly advised to just depend on STD and use the STD Mutex in their code.
 pub trait RawMutex {
@@ -19,6 +22,15 @@ pub trait RawMutex {
     unsafe fn unlock(&self);
 }
 
+/// A `RawMutex` which can also be locked without blocking
+pub trait RawTryMutex: RawMutex {
+    /// Locks the mutex if it is not locked already, returning `true` if it did
+    ///
+    /// # Safety
+    /// - This method should NOT be called while the mutex is being waited on in a condvar
+    unsafe fn try_lock(&self) -> bool;
+}
+
 /// A raw Condvar trait for no_std environments. Prevents the introduction of dependency on STD for the `utils` module and its sub-modules.
 /// NOTE: Users are strongly advised to just depend on STD and use the STD Condvar in their code.
 pub trait RawCondvar {
@@ -30,6 +42,11 @@ pub trait RawCondvar {
     /// - This method should be called only when the mutex is already locked, and by the entity which locked the mutex
     unsafe fn wait(&self, mutex: &Self::RawMutex);
 
+    /// Like `wait`, but gives up after `duration`, returning `true` if the wait timed out
+    ///
+    /// As with the STD Condvar, the wait might also end early, without a notification (a spurious wakeup),
+    /// so callers should re-check their condition in a loop, against a deadline.
+    ///
     /// # Safety
     /// - This method should be called only when the mutex is already locked, and by the entity which locked the mutex
     unsafe fn wait_timeout(&self, mutex: &Self::RawMutex, duration: Duration) -> bool;
@@ -59,6 +76,32 @@ where
     pub fn lock(&self) -> MutexGuard<'_, R, T> {
         MutexGuard::new(self)
     }
+
+    /// Runs `f` with the mutex locked, so that the guard cannot outlive the call
+    ///
+    /// The mutex is unlocked even if `f` panics.
+    #[inline(always)]
+    pub fn lock_with<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&mut T) -> O,
+    {
+        f(&mut self.lock())
+    }
+}
+
+impl<R, T> Mutex<R, T>
+where
+    R: RawTryMutex,
+{
+    /// Locks the mutex if it is not locked already, without blocking
+    #[inline(always)]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, R, T>> {
+        if unsafe { self.0.try_lock() } {
+            Some(MutexGuard(self))
+        } else {
+            None
+        }
+    }
 }
 
 unsafe impl<R, T> Sync for Mutex<R, T>
@@ -158,6 +201,9 @@ where
         guard
     }
 
+    /// Waits for a notification for up to `duration`, returning the re-locked guard and `true` if the wait timed out
+    ///
+    /// Spurious wakeups are possible, as with `std::sync::Condvar::wait_timeout`.
     pub fn wait_timeout<'a, T>(
         &self,
         guard: MutexGuard<'a, V::RawMutex, T>,
@@ -189,6 +235,178 @@ where
     }
 }
 
+/// A raw RwLock trait for no_std environments, for data which is read often and written rarely.
+/// Platforms without a native one can use `MutexRawRwLock`, which wraps any `RawMutex`.
+pub trait RawRwLock {
+    const INIT: Self; // A workaround for not having const fns in traits yet.
+
+    fn new() -> Self;
+
+    /// # Safety
+    /// - This method should NOT be called by an entity which currently holds the lock for writing
+    unsafe fn read_lock(&self);
+
+    /// # Safety
+    /// - This method should only be called by an entity currently holding the lock for reading
+    unsafe fn read_unlock(&self);
+
+    /// # Safety
+    /// - This method should NOT be called by an entity which currently holds the lock
+    unsafe fn write_lock(&self);
+
+    /// # Safety
+    /// - This method should only be called by the entity currently holding the lock for writing
+    unsafe fn write_unlock(&self);
+}
+
+/// A degenerate `RawRwLock` over a `RawMutex`, where readers exclude each other as well
+pub struct MutexRawRwLock<M>(M);
+
+impl<M> RawRwLock for MutexRawRwLock<M>
+where
+    M: RawMutex,
+{
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self(M::INIT);
+
+    fn new() -> Self {
+        Self(M::new())
+    }
+
+    unsafe fn read_lock(&self) {
+        self.0.lock();
+    }
+
+    unsafe fn read_unlock(&self) {
+        self.0.unlock();
+    }
+
+    unsafe fn write_lock(&self) {
+        self.0.lock();
+    }
+
+    unsafe fn write_unlock(&self) {
+        self.0.unlock();
+    }
+}
+
+pub struct RwLock<R, T>(R, UnsafeCell<T>);
+
+impl<R, T> RwLock<R, T>
+where
+    R: RawRwLock,
+{
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self::wrap(R::INIT, data)
+    }
+
+    #[inline(always)]
+    pub const fn wrap(raw_rwlock: R, data: T) -> Self {
+        Self(raw_rwlock, UnsafeCell::new(data))
+    }
+
+    #[inline(always)]
+    pub fn read(&self) -> RwLockReadGuard<'_, R, T> {
+        unsafe {
+            self.0.read_lock();
+        }
+
+        RwLockReadGuard(self)
+    }
+
+    #[inline(always)]
+    pub fn write(&self) -> RwLockWriteGuard<'_, R, T> {
+        unsafe {
+            self.0.write_lock();
+        }
+
+        RwLockWriteGuard(self)
+    }
+}
+
+unsafe impl<R, T> Sync for RwLock<R, T>
+where
+    R: RawRwLock + Send + Sync,
+    T: Send + Sync,
+{
+}
+unsafe impl<R, T> Send for RwLock<R, T>
+where
+    R: RawRwLock + Send + Sync,
+    T: Send,
+{
+}
+
+pub struct RwLockReadGuard<'a, R, T>(&'a RwLock<R, T>)
+where
+    R: RawRwLock;
+
+impl<'a, R, T> Drop for RwLockReadGuard<'a, R, T>
+where
+    R: RawRwLock,
+{
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe {
+            self.0 .0.read_unlock();
+        }
+    }
+}
+
+impl<'a, R, T> Deref for RwLockReadGuard<'a, R, T>
+where
+    R: RawRwLock,
+{
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.0 .1.get().as_ref().unwrap() }
+    }
+}
+
+pub struct RwLockWriteGuard<'a, R, T>(&'a RwLock<R, T>)
+where
+    R: RawRwLock;
+
+impl<'a, R, T> Drop for RwLockWriteGuard<'a, R, T>
+where
+    R: RawRwLock,
+{
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe {
+            self.0 .0.write_unlock();
+        }
+    }
+}
+
+impl<'a, R, T> Deref for RwLockWriteGuard<'a, R, T>
+where
+    R: RawRwLock,
+{
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.0 .1.get().as_ref().unwrap() }
+    }
+}
+
+impl<'a, R, T> DerefMut for RwLockWriteGuard<'a, R, T>
+where
+    R: RawRwLock,
+{
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.0 .1.get().as_mut().unwrap() }
+    }
+}
+
+/// A `RawMutex` built on the STD Mutex
+///
+/// Unlike the STD Mutex, it is not poisoned by a panic of the thread holding it.
 #[cfg(feature = "std")]
 pub struct StdRawMutex(
     std::sync::Mutex<()>,
@@ -205,13 +423,39 @@ impl RawMutex for StdRawMutex {
     }
 
     unsafe fn lock(&self) {
-        let guard = core::mem::transmute(self.0.lock().unwrap());
+        let guard = core::mem::transmute::<
+            std::sync::MutexGuard<'_, ()>,
+            std::sync::MutexGuard<'static, ()>,
+        >(self.0.lock().unwrap_or_else(PoisonError::into_inner));
 
         *self.1.borrow_mut() = Some(guard);
     }
 
     unsafe fn unlock(&self) {
-        *self.1.borrow_mut() = None;
+        // Release the `RefCell` before the guard, or else the next owner of the mutex might find it still borrowed
+        let guard = self.1.borrow_mut().take();
+
+        drop(guard);
+    }
+}
+
+#[cfg(feature = "std")]
+impl RawTryMutex for StdRawMutex {
+    unsafe fn try_lock(&self) -> bool {
+        let guard = match self.0.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return false,
+        };
+
+        let guard = core::mem::transmute::<
+            std::sync::MutexGuard<'_, ()>,
+            std::sync::MutexGuard<'static, ()>,
+        >(guard);
+
+        *self.1.borrow_mut() = Some(guard);
+
+        true
     }
 }
 
@@ -244,7 +488,7 @@ impl RawCondvar for StdRawCondvar {
     unsafe fn wait(&self, mutex: &Self::RawMutex) {
         let guard = mutex.1.borrow_mut().take().unwrap();
 
-        let guard = self.0.wait(guard).unwrap();
+        let guard = self.0.wait(guard).unwrap_or_else(PoisonError::into_inner);
 
         *mutex.1.borrow_mut() = Some(guard);
     }
@@ -252,7 +496,10 @@ impl RawCondvar for StdRawCondvar {
     unsafe fn wait_timeout(&self, mutex: &Self::RawMutex, duration: Duration) -> bool {
         let guard = mutex.1.borrow_mut().take().unwrap();
 
-        let (guard, wtr) = self.0.wait_timeout(guard, duration).unwrap();
+        let (guard, wtr) = self
+            .0
+            .wait_timeout(guard, duration)
+            .unwrap_or_else(PoisonError::into_inner);
 
         *mutex.1.borrow_mut() = Some(guard);
 
@@ -267,3 +514,271 @@ impl RawCondvar for StdRawCondvar {
         self.0.notify_all();
     }
 }
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct StdRwLockState {
+    readers: usize,
+    writer: bool,
+    writers_waiting: usize,
+}
+
+/// A `RawRwLock` built on the STD Mutex and Condvar, which lets waiting writers in before new readers
+#[cfg(feature = "std")]
+pub struct StdRawRwLock(std::sync::Mutex<StdRwLockState>, std::sync::Condvar);
+
+#[cfg(feature = "std")]
+impl RawRwLock for StdRawRwLock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self(
+        std::sync::Mutex::new(StdRwLockState {
+            readers: 0,
+            writer: false,
+            writers_waiting: 0,
+        }),
+        std::sync::Condvar::new(),
+    );
+
+    fn new() -> Self {
+        Self(Default::default(), std::sync::Condvar::new())
+    }
+
+    unsafe fn read_lock(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        while state.writer || state.writers_waiting > 0 {
+            state = self.1.wait(state).unwrap();
+        }
+
+        state.readers += 1;
+    }
+
+    unsafe fn read_unlock(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        state.readers -= 1;
+
+        if state.readers == 0 {
+            self.1.notify_all();
+        }
+    }
+
+    unsafe fn write_lock(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        state.writers_waiting += 1;
+
+        while state.writer || state.readers > 0 {
+            state = self.1.wait(state).unwrap();
+        }
+
+        state.writers_waiting -= 1;
+        state.writer = true;
+    }
+
+    unsafe fn write_unlock(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        state.writer = false;
+
+        self.1.notify_all();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn std_raw_mutex_contention() {
+        let mutex = Mutex::<StdRawMutex, usize>::new(0);
+
+        // If the guard was released before the `RefCell` on unlock, the next owner of the mutex
+        // would now and then find the cell still borrowed, and panic
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10_000 {
+                        *mutex.lock() += 1;
+                    }
+                });
+            }
+        });
+
+        assert_eq!(*mutex.lock(), 80_000);
+    }
+
+    #[test]
+    fn std_raw_mutex_poisoned() {
+        let mutex = Mutex::<StdRawMutex, usize>::new(0);
+
+        std::thread::scope(|scope| {
+            let panicked = scope
+                .spawn(|| {
+                    mutex.lock_with(|value| {
+                        *value += 1;
+
+                        panic!("Panicking with the mutex locked");
+                    })
+                })
+                .join();
+
+            assert!(panicked.is_err());
+        });
+
+        // The mutex is unlocked while unwinding, and locking it again ignores the poisoning
+        assert_eq!(*mutex.lock(), 1);
+        assert_eq!(mutex.try_lock().map(|value| *value), Some(1));
+    }
+
+    /// Panics in another thread while holding `mutex`, poisoning the STD mutex underneath
+    fn poison<T>(mutex: &Mutex<StdRawMutex, T>)
+    where
+        T: Send,
+    {
+        std::thread::scope(|scope| {
+            let panicked = scope
+                .spawn(|| mutex.lock_with(|_| panic!("Poisoning the mutex")))
+                .join();
+
+            assert!(panicked.is_err());
+        });
+
+        assert!(mutex.0 .0.is_poisoned());
+    }
+
+    #[test]
+    fn try_lock() {
+        let mutex = Mutex::<StdRawMutex, u32>::new(0);
+
+        let guard = mutex.try_lock().unwrap();
+
+        assert!(mutex.try_lock().is_none());
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert!(mutex.try_lock().is_none()));
+        });
+
+        drop(guard);
+
+        assert_eq!(mutex.lock_with(|value| core::mem::replace(value, 1)), 0);
+        assert_eq!(mutex.try_lock().map(|value| *value), Some(1));
+    }
+
+    #[test]
+    fn try_lock_poisoned() {
+        let mutex = Mutex::<StdRawMutex, u32>::new(1);
+
+        poison(&mutex);
+
+        let guard = mutex.try_lock().unwrap();
+        assert_eq!(*guard, 1);
+        assert!(mutex.try_lock().is_none());
+
+        drop(guard);
+
+        assert_eq!(mutex.lock_with(|value| *value), 1);
+    }
+
+    #[test]
+    fn condvar_poisoned() {
+        let mutex = Mutex::<StdRawMutex, bool>::new(false);
+        let condvar = Condvar::<StdRawCondvar>::new();
+
+        poison(&mutex);
+
+        let (guard, timed_out) = condvar.wait_timeout(mutex.lock(), Duration::from_millis(1));
+        assert!(timed_out);
+
+        drop(guard);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                *mutex.lock() = true;
+                condvar.notify_all();
+            });
+
+            let mut notified = mutex.lock();
+
+            while !*notified {
+                notified = condvar.wait(notified);
+            }
+        });
+    }
+
+    /// Waits until `condition` holds for the state of `lock`
+    fn wait_for(lock: &StdRawRwLock, condition: impl Fn(&StdRwLockState) -> bool) {
+        while !condition(&lock.0.lock().unwrap()) {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn rwlock_readers() {
+        let lock = RwLock::<StdRawRwLock, u32>::new(1);
+        let barrier = std::sync::Barrier::new(2);
+
+        // Both readers hold the lock at the same time, or else they would never pass the barrier
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    let value = lock.read();
+
+                    barrier.wait();
+
+                    assert_eq!(*value, 1);
+                });
+            }
+        });
+
+        *lock.write() += 1;
+        assert_eq!(*lock.read(), 2);
+    }
+
+    #[test]
+    fn rwlock_writer_preference() {
+        let lock = RwLock::<StdRawRwLock, u32>::new(0);
+
+        let read = lock.read();
+
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(|| *lock.write() = 1);
+
+            wait_for(&lock.0, |state| state.writers_waiting == 1);
+
+            // While a writer waits, new readers wait too, even though the lock is only held for reading
+            let reader = scope.spawn(|| *lock.read());
+
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!reader.is_finished());
+            assert_eq!(lock.0 .0.lock().unwrap().readers, 1);
+
+            drop(read);
+
+            writer.join().unwrap();
+
+            // The writer got in before the reader
+            assert_eq!(reader.join().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn mutex_rwlock() {
+        let lock = RwLock::<MutexRawRwLock<StdRawMutex>, u32>::new(1);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1000 {
+                        let value = *lock.read();
+
+                        *lock.write() += 1;
+
+                        assert!(*lock.read() > value);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(*lock.read(), 4001);
+    }
+}
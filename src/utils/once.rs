@@ -0,0 +1,310 @@
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+use core::ops::Deref;
+
+use crate::utils::mutex::{Mutex, RawMutex};
+
+/// A cell which is written at most once, and from then on hands out shared references to its value
+///
+/// The cell needs no allocations and can be placed in a `static`. All accesses are serialized through
+/// a `Mutex` of `R`, so that under concurrent first access the initializer runs only once, while
+/// the other callers wait for it to complete.
+///
+/// The initializer must not access the cell it is initializing, as this would deadlock.
+pub struct OnceCell<R, T>
+where
+    R: RawMutex,
+{
+    lock: Mutex<R, ()>,
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<R, T> OnceCell<R, T>
+where
+    R: RawMutex,
+{
+    pub const fn new() -> Self {
+        Self {
+            lock: Mutex::new(()),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        let _guard = self.lock.lock();
+
+        self.value()
+    }
+
+    /// Sets the value, or returns `value` if the cell is already initialized
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let _guard = self.lock.lock();
+
+        if self.value().is_some() {
+            Err(value)
+        } else {
+            unsafe {
+                *self.value.get() = Some(value);
+            }
+
+            Ok(())
+        }
+    }
+
+    pub fn get_or_init<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        match self.get_or_try_init(|| Ok::<_, Infallible>(f())) {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Returns the value, initializing it with `f` first if the cell is empty
+    ///
+    /// If `f` fails, the cell stays empty, and the error is returned.
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let _guard = self.lock.lock();
+
+        if self.value().is_none() {
+            let value = f()?;
+
+            unsafe {
+                *self.value.get() = Some(value);
+            }
+        }
+
+        Ok(self.value().unwrap())
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    fn value(&self) -> Option<&T> {
+        // Once set, the value is never modified nor moved while the cell is borrowed
+        unsafe { self.value.get().as_ref().unwrap().as_ref() }
+    }
+}
+
+impl<R, T> Default for OnceCell<R, T>
+where
+    R: RawMutex,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<R, T> Sync for OnceCell<R, T>
+where
+    R: RawMutex + Send + Sync,
+    T: Send + Sync,
+{
+}
+unsafe impl<R, T> Send for OnceCell<R, T>
+where
+    R: RawMutex + Send + Sync,
+    T: Send,
+{
+}
+
+/// A value which is initialized with `F` on first access
+///
+/// Like `OnceCell`, it can be placed in a `static`, e.g. `Lazy::new(|| ...)` with a non-capturing closure.
+pub struct Lazy<R, T, F = fn() -> T>
+where
+    R: RawMutex,
+{
+    cell: OnceCell<R, T>,
+    init: UnsafeCell<Option<F>>,
+}
+
+impl<R, T, F> Lazy<R, T, F>
+where
+    R: RawMutex,
+    F: FnOnce() -> T,
+{
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| {
+            // Only called once, with the cell locked
+            let init = unsafe { this.init.get().as_mut().unwrap().take().unwrap() };
+
+            init()
+        })
+    }
+}
+
+impl<R, T, F> Deref for Lazy<R, T, F>
+where
+    R: RawMutex,
+    F: FnOnce() -> T,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Self::force(self)
+    }
+}
+
+unsafe impl<R, T, F> Sync for Lazy<R, T, F>
+where
+    R: RawMutex + Send + Sync,
+    T: Send + Sync,
+    F: Send,
+{
+}
+unsafe impl<R, T, F> Send for Lazy<R, T, F>
+where
+    R: RawMutex + Send + Sync,
+    T: Send,
+    F: Send,
+{
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    #[test]
+    fn racing_initializers() {
+        static CELL: OnceCell<StdRawMutex, usize> = OnceCell::new();
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let barrier = Barrier::new(2);
+
+        let values = thread::scope(|scope| {
+            let threads = [1, 2].map(|id| {
+                let barrier = &barrier;
+
+                scope.spawn(move || {
+                    barrier.wait();
+
+                    *CELL.get_or_init(|| {
+                        CALLS.fetch_add(1, Ordering::SeqCst);
+                        // Give the other thread the time to attempt the initialization as well
+                        thread::sleep(Duration::from_millis(50));
+
+                        id
+                    })
+                })
+            });
+
+            threads.map(|thread| thread.join().unwrap())
+        });
+
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(values[0], values[1]);
+        assert_eq!(CELL.get(), Some(&values[0]));
+        assert_eq!(CELL.set(3), Err(3));
+    }
+
+    #[test]
+    fn racing_fallible_initializers() {
+        let cell = OnceCell::<StdRawMutex, &str>::new();
+        let calls = AtomicUsize::new(0);
+        let barrier = Barrier::new(2);
+
+        let results = thread::scope(|scope| {
+            let threads = [Err("failed"), Ok("initialized")].map(|result| {
+                let (cell, calls, barrier) = (&cell, &calls, &barrier);
+
+                scope.spawn(move || {
+                    barrier.wait();
+
+                    cell.get_or_try_init(|| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(20));
+
+                        result
+                    })
+                    .copied()
+                })
+            });
+
+            threads.map(|thread| thread.join().unwrap())
+        });
+
+        // Either the failing initializer went first and the other one ran afterwards,
+        // or the successful one went first and the failing one never ran
+        match calls.load(Ordering::SeqCst) {
+            2 => assert_eq!(results, [Err("failed"), Ok("initialized")]),
+            1 => assert_eq!(results, [Ok("initialized"), Ok("initialized")]),
+            calls => panic!("{} calls", calls),
+        }
+
+        assert_eq!(cell.into_inner(), Some("initialized"));
+    }
+
+    #[test]
+    fn failed_init_leaves_cell_empty() {
+        let cell = OnceCell::<StdRawMutex, u32>::new();
+
+        assert_eq!(cell.get_or_try_init(|| Err("failed")), Err("failed"));
+        assert_eq!(cell.get(), None);
+
+        assert_eq!(cell.get_or_try_init(|| Ok::<_, ()>(1)), Ok(&1));
+        assert_eq!(
+            cell.get_or_try_init(|| -> Result<_, ()> { unreachable!() }),
+            Ok(&1)
+        );
+    }
+
+    #[test]
+    fn set() {
+        let cell = OnceCell::<StdRawMutex, String>::default();
+
+        assert_eq!(cell.get(), None);
+        assert_eq!(cell.set("first".into()), Ok(()));
+        assert_eq!(cell.set("second".into()), Err("second".into()));
+        assert_eq!(cell.get_or_init(|| unreachable!()), "first");
+        assert_eq!(cell.into_inner().as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn racing_lazy() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        fn init() -> Vec<u32> {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+
+            vec![1, 2, 3]
+        }
+
+        static LAZY: Lazy<StdRawMutex, Vec<u32>> = Lazy::new(init);
+
+        let barrier = Barrier::new(4);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    barrier.wait();
+
+                    assert_eq!(LAZY.len(), 3);
+                });
+            }
+        });
+
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(*Lazy::force(&LAZY), [1, 2, 3]);
+    }
+}
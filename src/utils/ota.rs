@@ -0,0 +1,1797 @@
+use core::cmp::Ordering;
+use core::convert::TryInto;
+use core::fmt::{self, Display, Formatter, Write as _};
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::http::client::{Client, Connection};
+use crate::http::Method;
+use crate::io::{Error, ErrorKind, ErrorType, Write};
+use crate::ota::{
+    Digest, FirmwareInfo, FirmwareInfoLoader, LoadResult, Ota, OtaUpdate, VerifyError,
+    VerifyingOtaUpdate,
+};
+
+const MAGIC: &[u8] = b"FWI1";
+
+const TAG_END: u8 = 0;
+const TAG_VERSION: u8 = 1;
+const TAG_RELEASED: u8 = 2;
+const TAG_DESCRIPTION: u8 = 3;
+const TAG_SIGNATURE: u8 = 4;
+const TAG_DOWNLOAD_ID: u8 = 5;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TlvError {
+    InvalidMagic,
+    InvalidRecord(u8),
+    TooLarge,
+    NotLoaded,
+}
+
+impl Display for TlvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "Invalid firmware info header magic"),
+            Self::InvalidRecord(tag) => write!(f, "Invalid firmware info record with tag {tag}"),
+            Self::TooLarge => write!(f, "Firmware info header too large"),
+            Self::NotLoaded => write!(f, "Firmware info not loaded yet"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TlvError {}
+
+impl Error for TlvError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::NotLoaded => ErrorKind::Other,
+            _ => ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// A reference `FirmwareInfoLoader` for a simple TLV header placed at the start of the image.
+///
+/// The header starts with the `FWI1` magic followed by records, each consisting of a tag byte,
+/// a length byte and the value. The header is terminated by a record with tag 0 and no length.
+/// Tags 1 to 5 are the version, release date, description, signature and download ID;
+/// the strings are UTF-8 encoded and unknown tags are skipped.
+///
+/// The header should fit in `N` bytes.
+pub struct TlvFirmwareInfoLoader<const N: usize = 512> {
+    buf: heapless::Vec<u8, N>,
+    info: Option<FirmwareInfo>,
+}
+
+impl<const N: usize> TlvFirmwareInfoLoader<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: heapless::Vec::new(),
+            info: None,
+        }
+    }
+
+    fn parse(data: &[u8]) -> Result<Option<FirmwareInfo>, TlvError> {
+        if data.len() < MAGIC.len() {
+            return if MAGIC.starts_with(data) {
+                Ok(None)
+            } else {
+                Err(TlvError::InvalidMagic)
+            };
+        }
+
+        if !data.starts_with(MAGIC) {
+            return Err(TlvError::InvalidMagic);
+        }
+
+        let mut info = FirmwareInfo {
+            version: heapless::String::new(),
+            released: heapless::String::new(),
+            description: None,
+            signature: None,
+            download_id: None,
+        };
+
+        let mut offset = MAGIC.len();
+
+        loop {
+            let Some(&tag) = data.get(offset) else {
+                return Ok(None);
+            };
+
+            if tag == TAG_END {
+                return Ok(Some(info));
+            }
+
+            let Some(&len) = data.get(offset + 1) else {
+                return Ok(None);
+            };
+
+            let start = offset + 2;
+            let end = start + len as usize;
+
+            let Some(value) = data.get(start..end) else {
+                return Ok(None);
+            };
+
+            Self::apply(&mut info, tag, value).map_err(|_| TlvError::InvalidRecord(tag))?;
+
+            offset = end;
+        }
+    }
+
+    fn apply(info: &mut FirmwareInfo, tag: u8, value: &[u8]) -> Result<(), ()> {
+        match tag {
+            TAG_VERSION => info.version = Self::string(value)?,
+            TAG_RELEASED => info.released = Self::string(value)?,
+            TAG_DESCRIPTION => info.description = Some(Self::string(value)?),
+            TAG_SIGNATURE => info.signature = Some(heapless::Vec::from_slice(value)?),
+            TAG_DOWNLOAD_ID => info.download_id = Some(Self::string(value)?),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn string<const M: usize>(value: &[u8]) -> Result<heapless::String<M>, ()> {
+        let mut string = heapless::String::new();
+
+        string.push_str(core::str::from_utf8(value).map_err(|_| ())?)?;
+
+        Ok(string)
+    }
+}
+
+impl<const N: usize> Default for TlvFirmwareInfoLoader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ErrorType for TlvFirmwareInfoLoader<N> {
+    type Error = TlvError;
+}
+
+impl<const N: usize> FirmwareInfoLoader for TlvFirmwareInfoLoader<N> {
+    fn load(&mut self, buf: &[u8]) -> Result<LoadResult, Self::Error> {
+        if self.info.is_some() {
+            return Ok(LoadResult::Loaded);
+        }
+
+        let len = buf.len().min(N - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]).unwrap();
+
+        match Self::parse(&self.buf)? {
+            Some(info) => {
+                self.info = Some(info);
+                self.buf.clear();
+
+                Ok(LoadResult::Loaded)
+            }
+            None if self.buf.is_full() => Err(TlvError::TooLarge),
+            None => Ok(LoadResult::LoadMore),
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.info.is_some()
+    }
+
+    fn get_info(&self) -> Result<FirmwareInfo, Self::Error> {
+        self.info.clone().ok_or(TlvError::NotLoaded)
+    }
+
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.info = None;
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DownloadError<H, O> {
+    Http(H),
+    Ota(O),
+    Status(u16),
+    /// The `Content-Range` of a partial response does not start at the offset the download is resumed from
+    InvalidRange,
+    /// More headers than the request can carry were supplied
+    TooManyHeaders,
+    /// The firmware info could not be loaded from the image
+    InvalidFirmware,
+    Policy(PolicyViolation),
+}
+
+impl<H: fmt::Debug, O: fmt::Debug> Display for DownloadError<H, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: fmt::Debug, O: fmt::Debug> std::error::Error for DownloadError<H, O> {}
+
+/// Downloads the firmware image at `uri` with an HTTP GET request and writes it into an OTA update.
+///
+/// If `Ota::resume_update` returns an interrupted update, the download is continued from the
+/// returned offset with a `Range` request. Servers which do not support ranges are also handled,
+/// by skipping the part of the image which was already written, but partial responses not starting
+/// at that offset fail with `DownloadError::InvalidRange`.
+///
+/// On network errors, updates which track the written size (i.e. `OtaUpdate::written` is not `None`)
+/// are not aborted, so that they can be resumed later. Returns the size of the downloaded image.
+///
+/// Redirects (with an absolute `Location`) are followed, as long as the location fits in `buf`.
+pub fn download<C, O>(
+    client: &mut Client<C>,
+    ota: &mut O,
+    uri: &str,
+    buf: &mut [u8],
+) -> Result<u64, DownloadError<C::Error, O::Error>>
+where
+    C: Connection,
+    O: Ota,
+{
+    download_with_headers(client, ota, uri, &[], buf)
+}
+
+/// Same as `download`, but sends `headers` with the request, e.g. an `Authorization` and an
+/// `Accept: application/octet-stream` header for downloading a release asset of a private repository.
+/// At most 7 headers are supported, or else `DownloadError::TooManyHeaders` is returned.
+///
+/// The headers are also sent to the locations the request is redirected to on the same origin (scheme, host and port),
+/// but not to other origins, which usually serve pre-signed URLs and might reject (or leak) the credentials.
+pub fn download_with_headers<C, O>(
+    client: &mut Client<C>,
+    ota: &mut O,
+    uri: &str,
+    headers: &[(&str, &str)],
+    buf: &mut [u8],
+) -> Result<u64, DownloadError<C::Error, O::Error>>
+where
+    C: Connection,
+    O: Ota,
+{
+    if let Some(resumed) = ota.resume_update().map_err(DownloadError::Ota)? {
+        return download_into(
+            client,
+            resumed.update,
+            uri,
+            headers,
+            resumed.offset,
+            buf,
+            None,
+        );
+    }
+
+    let update = ota.initiate_update().map_err(DownloadError::Ota)?;
+
+    download_into(client, update, uri, headers, 0, buf, None)
+}
+
+/// Same as `download`, but checks the firmware info loaded from the start of the image
+/// with `loader` against `policy` before writing anything into the update.
+///
+/// The firmware info should fit in `buf`. Resumed updates are not checked again.
+pub fn download_with_policy<C, O, L>(
+    client: &mut Client<C>,
+    ota: &mut O,
+    uri: &str,
+    headers: &[(&str, &str)],
+    buf: &mut [u8],
+    loader: &mut L,
+    policy: &UpdatePolicy,
+) -> Result<u64, DownloadError<C::Error, O::Error>>
+where
+    C: Connection,
+    O: Ota,
+    L: FirmwareInfoLoader,
+{
+    // Do not report the info of a previously loaded image, should the loader be reused
+    loader.reset();
+
+    if let Some(resumed) = ota.resume_update().map_err(DownloadError::Ota)? {
+        return download_into(
+            client,
+            resumed.update,
+            uri,
+            headers,
+            resumed.offset,
+            buf,
+            None,
+        );
+    }
+
+    let running = ota.get_running_slot().map_err(DownloadError::Ota)?.firmware;
+
+    let mut check = |data: &[u8]| match loader.load(data) {
+        Ok(LoadResult::Loaded) => {
+            let info = loader
+                .get_info()
+                .map_err(|_| DownloadError::InvalidFirmware)?;
+
+            policy
+                .check(running.as_ref(), &info)
+                .map_err(DownloadError::Policy)?;
+
+            Ok(true)
+        }
+        Ok(_) => Ok(false),
+        Err(_) => Err(DownloadError::InvalidFirmware),
+    };
+
+    let update = ota.initiate_update().map_err(DownloadError::Ota)?;
+
+    download_into(client, update, uri, headers, 0, buf, Some(&mut check))
+}
+
+/// Same as `download_with_headers`, but also checks that the SHA-256 digest of the image, as computed with `digest`,
+/// is `expected`, and only completes the update if it is.
+///
+/// As the digest of the part of an interrupted update which was written already is not known,
+/// updates are not resumed, but started over.
+pub fn download_verified<C, O, D>(
+    client: &mut Client<C>,
+    ota: &mut O,
+    uri: &str,
+    headers: &[(&str, &str)],
+    buf: &mut [u8],
+    digest: D,
+    expected: &[u8; 32],
+) -> Result<u64, DownloadError<C::Error, VerifyError<O::Error>>>
+where
+    C: Connection,
+    O: Ota,
+    D: Digest<Output = [u8; 32]>,
+{
+    let update = ota
+        .initiate_update()
+        .map_err(|e| DownloadError::Ota(VerifyError::Update(e)))?;
+
+    let update = Verified {
+        update: VerifyingOtaUpdate::new(update, digest),
+        expected,
+    };
+
+    download_into(client, update, uri, headers, 0, buf, None)
+}
+
+/// A `VerifyingOtaUpdate` which knows its expected digest, and can therefore be completed as any `OtaUpdate`
+struct Verified<'a, U, D> {
+    update: VerifyingOtaUpdate<U, D>,
+    expected: &'a [u8; 32],
+}
+
+impl<U, D> ErrorType for Verified<'_, U, D>
+where
+    U: ErrorType,
+{
+    type Error = VerifyError<U::Error>;
+}
+
+impl<U, D> Write for Verified<'_, U, D>
+where
+    U: Write,
+    D: Digest<Output = [u8; 32]>,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.update.write(buf).map_err(VerifyError::Update)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.update.flush().map_err(VerifyError::Update)
+    }
+}
+
+impl<U, D> OtaUpdate for Verified<'_, U, D>
+where
+    U: OtaUpdate,
+    D: Digest<Output = [u8; 32]>,
+{
+    type OtaUpdateFinished = U::OtaUpdateFinished;
+
+    fn finish(self) -> Result<Self::OtaUpdateFinished, Self::Error> {
+        self.update.finish(self.expected)
+    }
+
+    fn complete(self) -> Result<(), Self::Error> {
+        self.update.complete(self.expected)
+    }
+
+    fn abort(self) -> Result<(), Self::Error> {
+        self.update.abort().map_err(VerifyError::Update)
+    }
+}
+
+const MAX_HEADERS: usize = 8;
+const MAX_REDIRECTS: usize = 4;
+
+type Check<'a, H, O> = &'a mut dyn FnMut(&[u8]) -> Result<bool, DownloadError<H, O>>;
+
+/// Whether the absolute URIs `a` and `b` have the same scheme, host and port
+fn same_origin(a: &str, b: &str) -> bool {
+    fn origin(uri: &str) -> Option<&str> {
+        let start = uri.find("://")? + 3;
+        let end = uri[start..].find(['/', '?', '#']).map_or(uri.len(), |end| start + end);
+
+        Some(&uri[..end])
+    }
+
+    matches!((origin(a), origin(b)), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+}
+
+fn download_into<C, U>(
+    client: &mut Client<C>,
+    mut update: U,
+    uri: &str,
+    headers: &[(&str, &str)],
+    offset: u64,
+    buf: &mut [u8],
+    mut check: Option<Check<'_, C::Error, U::Error>>,
+) -> Result<u64, DownloadError<C::Error, U::Error>>
+where
+    C: Connection,
+    U: OtaUpdate,
+{
+    let mut range = heapless::String::<32>::new();
+    write!(range, "bytes={offset}-").unwrap();
+
+    let mut transfer = || {
+        let connection = client.connection();
+
+        // The length of the redirect location, which is kept at the start of `buf`
+        let mut location = None;
+        let mut redirects = 0;
+
+        let status = loop {
+            let mut request_headers = heapless::Vec::<_, MAX_HEADERS>::new();
+
+            let redirected_uri = location.map(|len| core::str::from_utf8(&buf[..len]).unwrap());
+
+            if redirected_uri.map_or(true, |location| same_origin(uri, location)) {
+                request_headers
+                    .extend_from_slice(headers)
+                    .map_err(|_| DownloadError::TooManyHeaders)?;
+            }
+
+            if offset > 0 {
+                request_headers
+                    .push(("Range", range.as_str()))
+                    .map_err(|_| DownloadError::TooManyHeaders)?;
+            }
+
+            let uri = redirected_uri.unwrap_or(uri);
+
+            connection
+                .initiate_request(Method::Get, uri, &request_headers)
+                .map_err(DownloadError::Http)?;
+            connection
+                .initiate_response()
+                .map_err(DownloadError::Http)?;
+
+            let status = connection.status();
+
+            if matches!(status, 301 | 302 | 303 | 307 | 308) && redirects < MAX_REDIRECTS {
+                let target = connection
+                    .header("Location")
+                    .filter(|target| target.len() <= buf.len())
+                    .ok_or(DownloadError::Status(status))?;
+
+                buf[..target.len()].copy_from_slice(target.as_bytes());
+
+                location = Some(target.len());
+                redirects += 1;
+            } else {
+                break status;
+            }
+        };
+
+        let mut skip = match status {
+            206 if offset > 0 => {
+                let start = connection
+                    .header("Content-Range")
+                    .and_then(range_start)
+                    .ok_or(DownloadError::InvalidRange)?;
+
+                if start != offset {
+                    return Err(DownloadError::InvalidRange);
+                }
+
+                0
+            }
+            200 => offset,
+            status => return Err(DownloadError::Status(status)),
+        };
+
+        let mut size = offset;
+
+        // Data is only written once the check (if any) passed, so it is kept in the buffer until then
+        let mut filled = 0;
+
+        loop {
+            let len = connection
+                .read(&mut buf[filled..])
+                .map_err(DownloadError::Http)?;
+
+            if len == 0 {
+                if check.is_some() {
+                    break Err(DownloadError::InvalidFirmware);
+                }
+
+                break Ok(size);
+            }
+
+            if let Some(pending) = check.as_mut() {
+                let checked = pending(&buf[filled..filled + len])?;
+
+                filled += len;
+
+                if !checked {
+                    if filled == buf.len() {
+                        break Err(DownloadError::InvalidFirmware);
+                    }
+
+                    continue;
+                }
+
+                check = None;
+            } else {
+                filled = len;
+            }
+
+            let skipped = skip.min(filled as u64) as usize;
+            skip -= skipped as u64;
+
+            update
+                .write_all(&buf[skipped..filled])
+                .map_err(DownloadError::Ota)?;
+
+            size += (filled - skipped) as u64;
+            filled = 0;
+        }
+    };
+
+    match transfer() {
+        Ok(size) => {
+            update.complete().map_err(DownloadError::Ota)?;
+
+            Ok(size)
+        }
+        Err(DownloadError::Http(e)) if update.written().is_some() => Err(DownloadError::Http(e)),
+        Err(e) => {
+            update.abort().map_err(DownloadError::Ota)?;
+
+            Err(e)
+        }
+    }
+}
+
+/// The first byte position of a `Content-Range` header, e.g. 100 for `bytes 100-199/200`
+fn range_start(content_range: &str) -> Option<u64> {
+    let (start, _) = content_range
+        .trim()
+        .strip_prefix("bytes ")?
+        .trim_start()
+        .split_once('-')?;
+
+    start.parse().ok()
+}
+
+/// A semantic version, as per <https://semver.org>.
+///
+/// Parsing is lenient in that a leading `v`, as well as missing minor and patch numbers are accepted.
+/// The build metadata is ignored when comparing versions.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Version<'a> {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<&'a str>,
+    pub build: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    pub fn parse(version: &'a str) -> Result<Self, &'static str> {
+        let version = version.trim();
+        let version = version
+            .strip_prefix('v')
+            .or_else(|| version.strip_prefix('V'))
+            .unwrap_or(version);
+
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => (version, Some(build)),
+            None => (version, None),
+        };
+
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (version, Some(pre)),
+            None => (version, None),
+        };
+
+        if pre.map(|pre| !Self::is_valid_pre(pre)).unwrap_or(false) {
+            return Err("Invalid pre-release identifiers");
+        }
+
+        let mut numbers = version.split('.');
+
+        let major = Self::number(numbers.next())?.ok_or("Missing major version")?;
+        let minor = Self::number(numbers.next())?.unwrap_or(0);
+        let patch = Self::number(numbers.next())?.unwrap_or(0);
+
+        if numbers.next().is_some() {
+            return Err("Too many version numbers");
+        }
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre,
+            build,
+        })
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some()
+    }
+
+    fn number(number: Option<&str>) -> Result<Option<u64>, &'static str> {
+        number
+            .map(|number| {
+                if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                    number.parse().map_err(|_| "Version number too large")
+                } else {
+                    Err("Invalid version number")
+                }
+            })
+            .transpose()
+    }
+
+    fn is_valid_pre(pre: &str) -> bool {
+        pre.split('.').all(|identifier| {
+            !identifier.is_empty()
+                && identifier
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+    }
+
+    fn cmp_pre(pre: &str, other: &str) -> Ordering {
+        let mut identifiers = pre.split('.');
+        let mut other_identifiers = other.split('.');
+
+        loop {
+            match (identifiers.next(), other_identifiers.next()) {
+                (Some(identifier), Some(other)) => {
+                    let ordering = match (identifier.parse::<u64>(), other.parse::<u64>()) {
+                        (Ok(number), Ok(other)) => number.cmp(&other),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => identifier.cmp(other),
+                    };
+
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (None, None) => return Ordering::Equal,
+            }
+        }
+    }
+}
+
+impl PartialEq for Version<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version<'_> {}
+
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then_with(|| match (self.pre, other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(pre), Some(other)) => Self::cmp_pre(pre, other),
+            })
+    }
+}
+
+impl Display for Version<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(pre) = self.pre {
+            write!(f, "-{pre}")?;
+        }
+
+        if let Some(build) = self.build {
+            write!(f, "+{build}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The release channel an update is selected from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel<'a> {
+    /// Releases only
+    Stable,
+    /// Releases and all pre-releases
+    Prerelease,
+    /// Releases and the pre-releases whose first pre-release identifier matches the name
+    /// (e.g. `beta` for `1.0.0-beta.2`)
+    Named(&'a str),
+}
+
+impl Channel<'_> {
+    pub fn accepts(&self, version: &Version<'_>) -> bool {
+        match (self, version.pre) {
+            (_, None) => true,
+            (Self::Stable, Some(_)) => false,
+            (Self::Prerelease, Some(_)) => true,
+            (Self::Named(name), Some(pre)) => pre.split('.').next() == Some(*name),
+        }
+    }
+}
+
+/// Checks whether `candidate` is a newer version than the running firmware, in the given channel
+pub fn is_update_available(
+    running: &FirmwareInfo,
+    candidate: &str,
+    channel: &Channel<'_>,
+) -> Result<bool, &'static str> {
+    let running = Version::parse(&running.version)?;
+    let candidate = Version::parse(candidate)?;
+
+    Ok(channel.accepts(&candidate) && candidate > running)
+}
+
+/// The rules an update has to comply with, e.g. for anti-rollback protection
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct UpdatePolicy {
+    /// The minimum version allowed, regardless of the running version
+    pub min_version: Option<heapless::String<24>>,
+    /// Whether the running version can be flashed again
+    pub allow_same_version: bool,
+    /// Whether versions older than the running version (but not older than `min_version`) are allowed
+    pub allow_downgrade: bool,
+}
+
+impl UpdatePolicy {
+    /// Checks the candidate firmware against the policy; `running` is `None` if the running firmware is not known
+    pub fn check(
+        &self,
+        running: Option<&FirmwareInfo>,
+        candidate: &FirmwareInfo,
+    ) -> Result<(), PolicyViolation> {
+        self.check_version(
+            running.map(|running| running.version.as_str()),
+            &candidate.version,
+        )
+    }
+
+    pub fn check_version(
+        &self,
+        running: Option<&str>,
+        candidate: &str,
+    ) -> Result<(), PolicyViolation> {
+        let violation = |kind| PolicyViolation {
+            kind,
+            running: running.and_then(|running| running.try_into().ok()),
+            candidate: candidate.try_into().unwrap_or_default(),
+        };
+
+        let candidate_version = Version::parse(candidate)
+            .map_err(|_| violation(PolicyViolationKind::InvalidVersion))?;
+
+        if let Some(min_version) = &self.min_version {
+            let min_version = Version::parse(min_version)
+                .map_err(|_| violation(PolicyViolationKind::InvalidVersion))?;
+
+            if candidate_version < min_version {
+                return Err(violation(PolicyViolationKind::BelowMinimum));
+            }
+        }
+
+        if let Some(running) = running {
+            let running_version = Version::parse(running)
+                .map_err(|_| violation(PolicyViolationKind::InvalidVersion))?;
+
+            if candidate_version == running_version && !self.allow_same_version {
+                return Err(violation(PolicyViolationKind::SameVersion));
+            }
+
+            if candidate_version < running_version && !self.allow_downgrade {
+                return Err(violation(PolicyViolationKind::Downgrade));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum PolicyViolationKind {
+    InvalidVersion,
+    BelowMinimum,
+    SameVersion,
+    Downgrade,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PolicyViolation {
+    pub kind: PolicyViolationKind,
+    pub running: Option<heapless::String<24>>,
+    pub candidate: heapless::String<24>,
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let running = self.running.as_deref().unwrap_or("(unknown)");
+
+        match self.kind {
+            PolicyViolationKind::InvalidVersion => write!(
+                f,
+                "Invalid version (candidate: {}, running: {running})",
+                self.candidate
+            ),
+            PolicyViolationKind::BelowMinimum => {
+                write!(f, "Version {} is below the minimum version", self.candidate)
+            }
+            PolicyViolationKind::SameVersion => {
+                write!(f, "Version {} is already running", self.candidate)
+            }
+            PolicyViolationKind::Downgrade => write!(
+                f,
+                "Version {} is older than the running version {running}",
+                self.candidate
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PolicyViolation {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::http::{Headers, Status};
+    use crate::io::Read;
+    use crate::test_ota::MockOta;
+
+    use super::*;
+
+    type Requests = Vec<(String, Vec<(String, String)>)>;
+
+    struct MockResponse {
+        status: u16,
+        headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+        /// Fail the reads (as if the network went down) once that many bytes were read
+        fail_at: Option<usize>,
+        /// Return at most that many bytes per read
+        max_read: Option<usize>,
+    }
+
+    impl MockResponse {
+        fn new(status: u16, body: &[u8]) -> Self {
+            Self {
+                status,
+                headers: Vec::new(),
+                body: body.to_vec(),
+                fail_at: None,
+                max_read: None,
+            }
+        }
+
+        fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+            self.headers.push((name, value.to_string()));
+            self
+        }
+    }
+
+    /// The status and headers of the response being read
+    struct MockHead {
+        status: u16,
+        headers: Vec<(&'static str, String)>,
+    }
+
+    impl Status for MockHead {
+        fn status(&self) -> u16 {
+            self.status
+        }
+
+        fn status_message(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    impl Headers for MockHead {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// The body of the response being read
+    #[derive(Default)]
+    struct MockBody {
+        body: Vec<u8>,
+        fail_at: Option<usize>,
+        max_read: Option<usize>,
+        read: usize,
+    }
+
+    impl ErrorType for MockBody {
+        type Error = ErrorKind;
+    }
+
+    impl Read for MockBody {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.fail_at.map(|at| self.read >= at).unwrap_or(false) {
+                return Err(ErrorKind::ConnectionReset);
+            }
+
+            let len = buf
+                .len()
+                .min(self.body.len() - self.read)
+                .min(self.fail_at.unwrap_or(usize::MAX) - self.read)
+                .min(self.max_read.unwrap_or(usize::MAX));
+
+            buf[..len].copy_from_slice(&self.body[self.read..self.read + len]);
+            self.read += len;
+
+            Ok(len)
+        }
+    }
+
+    /// An HTTP client connection, which answers each request with the response returned by `server`
+    /// for its URI and headers, and records the requests
+    struct MockConnection<F> {
+        server: F,
+        requests: Requests,
+        head: Option<MockHead>,
+        body: MockBody,
+    }
+
+    impl<F> MockConnection<F>
+    where
+        F: FnMut(&str, &[(String, String)]) -> MockResponse,
+    {
+        fn new(server: F) -> Client<Self> {
+            Client::wrap(Self {
+                server,
+                requests: Vec::new(),
+                head: None,
+                body: MockBody::default(),
+            })
+        }
+
+        fn head(&self) -> &MockHead {
+            self.head.as_ref().expect("Response not initiated")
+        }
+    }
+
+    impl<F> Status for MockConnection<F>
+    where
+        F: FnMut(&str, &[(String, String)]) -> MockResponse,
+    {
+        fn status(&self) -> u16 {
+            self.head().status()
+        }
+
+        fn status_message(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    impl<F> Headers for MockConnection<F>
+    where
+        F: FnMut(&str, &[(String, String)]) -> MockResponse,
+    {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.head().header(name)
+        }
+    }
+
+    impl<F> ErrorType for MockConnection<F> {
+        type Error = ErrorKind;
+    }
+
+    impl<F> Read for MockConnection<F>
+    where
+        F: FnMut(&str, &[(String, String)]) -> MockResponse,
+    {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.body.read(buf)
+        }
+    }
+
+    impl<F> Write for MockConnection<F> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl<F> Connection for MockConnection<F>
+    where
+        F: FnMut(&str, &[(String, String)]) -> MockResponse,
+    {
+        type Headers = MockHead;
+
+        type Read = MockBody;
+
+        type RawConnectionError = ErrorKind;
+
+        type RawConnection = Self;
+
+        fn initiate_request(
+            &mut self,
+            _method: Method,
+            uri: &str,
+            headers: &[(&str, &str)],
+        ) -> Result<(), Self::Error> {
+            self.requests.push((
+                uri.to_string(),
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            ));
+            self.head = None;
+
+            Ok(())
+        }
+
+        fn is_request_initiated(&self) -> bool {
+            self.head.is_none() && !self.requests.is_empty()
+        }
+
+        fn initiate_response(&mut self) -> Result<(), Self::Error> {
+            let (uri, headers) = self.requests.last().unwrap();
+
+            let response = (self.server)(uri, headers);
+
+            self.head = Some(MockHead {
+                status: response.status,
+                headers: response.headers,
+            });
+            self.body = MockBody {
+                body: response.body,
+                fail_at: response.fail_at,
+                max_read: response.max_read,
+                read: 0,
+            };
+
+            Ok(())
+        }
+
+        fn is_response_initiated(&self) -> bool {
+            self.head.is_some()
+        }
+
+        fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+            (
+                self.head.as_ref().expect("Response not initiated"),
+                &mut self.body,
+            )
+        }
+
+        fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+            Err(ErrorKind::Unsupported)
+        }
+    }
+
+    const URI: &str = "http://example.com/firmware.bin";
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Serves `image`, honoring `Range` requests if `ranges` is set
+    fn server(
+        image: &[u8],
+        ranges: bool,
+    ) -> impl FnMut(&str, &[(String, String)]) -> MockResponse + '_ {
+        move |_, headers| match header(headers, "Range").filter(|_| ranges) {
+            Some(range) => {
+                let start: usize = range
+                    .strip_prefix("bytes=")
+                    .and_then(|range| range.strip_suffix('-'))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+
+                MockResponse::new(206, &image[start..]).header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, image.len() - 1, image.len()),
+                )
+            }
+            None => MockResponse::new(200, image),
+        }
+    }
+
+    #[test]
+    fn download_image() {
+        let image = image(3000);
+        let mut ota = MockOta::new();
+        let mut buf = [0; 256];
+
+        let mut client = MockConnection::new(server(&image, true));
+
+        assert!(matches!(
+            download(&mut client, &mut ota, URI, &mut buf),
+            Ok(3000)
+        ));
+        assert_eq!(client.connection().requests[0].1, []);
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+    }
+
+    #[test]
+    fn mock_response_split() {
+        let image = image(300);
+        let mut buf = [0; 300];
+
+        let mut client = MockConnection::new(|_: &str, _: &[(String, String)]| {
+            MockResponse::new(200, &image).header("Content-Length", image.len())
+        });
+
+        let mut response = client.get(URI).unwrap().submit().unwrap();
+        let (headers, body) = response.split();
+
+        assert_eq!(headers.status(), 200);
+        assert_eq!(headers.content_len(), Some(300));
+        assert_eq!(crate::utils::io::try_read_full(body, &mut buf), Ok(300));
+        assert_eq!(&buf[..], &image[..]);
+    }
+
+    /// Interrupts the download of `image` after 80% of it, and then resumes it after a "reboot"
+    fn download_resumed(ranges: bool) -> Requests {
+        let image = image(4000);
+        let mut buf = [0; 256];
+
+        let mut ota = MockOta {
+            sector: 1024,
+            ..Default::default()
+        };
+
+        let mut client = MockConnection::new(|_: &str, _: &[(String, String)]| MockResponse {
+            fail_at: Some(3200),
+            ..MockResponse::new(200, &image)
+        });
+
+        assert!(matches!(
+            download(&mut client, &mut ota, URI, &mut buf),
+            Err(DownloadError::Http(ErrorKind::ConnectionReset))
+        ));
+
+        // Not aborted, so that it can be resumed
+        assert_eq!(ota.aborts, 0);
+        assert_eq!(ota.image.len(), 3200);
+        assert!(ota.interrupted);
+
+        // Only `ota` survives the reboot
+        let mut client = MockConnection::new(server(&image, ranges));
+
+        assert!(matches!(
+            download(&mut client, &mut ota, URI, &mut buf),
+            Ok(4000)
+        ));
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+        assert_eq!(ota.aborts, 0);
+
+        core::mem::take(&mut client.connection().requests)
+    }
+
+    #[test]
+    fn download_resumed_with_range() {
+        let requests = download_resumed(true);
+
+        // Continued from the end of the last fully flashed sector
+        assert_eq!(header(&requests[0].1, "Range"), Some("bytes=3072-"));
+    }
+
+    #[test]
+    fn download_resumed_without_range() {
+        let requests = download_resumed(false);
+
+        // The server sent the whole image again, and the part which was flashed already was skipped
+        assert_eq!(header(&requests[0].1, "Range"), Some("bytes=3072-"));
+    }
+
+    #[test]
+    fn download_resumed_invalid_range() {
+        let image = image(4000);
+        let mut buf = [0; 256];
+
+        let mut ota = MockOta::new();
+        ota.initiate_update()
+            .unwrap()
+            .write_all(&image[..1000])
+            .unwrap();
+
+        let mut client = MockConnection::new(|_: &str, _: &[(String, String)]| {
+            MockResponse::new(206, &image[500..]).header("Content-Range", "bytes 500-3999/4000")
+        });
+
+        assert!(matches!(
+            download(&mut client, &mut ota, URI, &mut buf),
+            Err(DownloadError::InvalidRange)
+        ));
+        assert_eq!(ota.aborts, 1);
+        assert!(ota.completed.is_none());
+
+        assert_eq!(range_start("bytes 500-3999/4000"), Some(500));
+        assert_eq!(range_start(" bytes  0-0/*"), Some(0));
+        assert_eq!(range_start("items 500-3999/4000"), None);
+        assert_eq!(range_start("bytes */4000"), None);
+    }
+
+    #[test]
+    fn download_redirected() {
+        let image = image(1000);
+        let mut buf = [0; 256];
+        let mut ota = MockOta::new();
+
+        let mut client = MockConnection::new(|uri: &str, _: &[(String, String)]| match uri {
+            URI => MockResponse::new(302, b"")
+                .header("Location", "HTTP://Example.com/releases/assets/1"),
+            "HTTP://Example.com/releases/assets/1" => {
+                MockResponse::new(302, b"").header("Location", "http://cdn.example.com/fw.bin")
+            }
+            _ => MockResponse::new(200, &image),
+        });
+
+        assert!(matches!(
+            download_with_headers(
+                &mut client,
+                &mut ota,
+                URI,
+                &[
+                    ("Authorization", "Bearer token"),
+                    ("Accept", "application/octet-stream")
+                ],
+                &mut buf
+            ),
+            Ok(1000)
+        ));
+
+        let requests = &client.connection().requests;
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            header(&requests[0].1, "Authorization"),
+            Some("Bearer token")
+        );
+        // Sent to the redirect location on the same origin
+        assert_eq!(requests[1].0, "HTTP://Example.com/releases/assets/1");
+        assert_eq!(
+            header(&requests[1].1, "Authorization"),
+            Some("Bearer token")
+        );
+        assert_eq!(
+            header(&requests[1].1, "Accept"),
+            Some("application/octet-stream")
+        );
+        // Not sent to the redirect location on another origin
+        assert_eq!(requests[2].0, "http://cdn.example.com/fw.bin");
+        assert_eq!(requests[2].1, []);
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+    }
+
+    #[test]
+    fn redirect_origin() {
+        assert!(same_origin(URI, "http://example.com"));
+        assert!(same_origin(URI, "http://EXAMPLE.com/other?query"));
+        assert!(same_origin(
+            "https://example.com:8443/a",
+            "https://example.com:8443#b"
+        ));
+        assert!(!same_origin(URI, "https://example.com/firmware.bin"));
+        assert!(!same_origin(URI, "http://example.com:8080/firmware.bin"));
+        assert!(!same_origin(
+            URI,
+            "http://example.com.evil.com/firmware.bin"
+        ));
+        assert!(!same_origin(URI, "http://cdn.example.com/firmware.bin"));
+        assert!(!same_origin(URI, "/firmware.bin"));
+    }
+
+    #[test]
+    fn download_failed() {
+        let mut buf = [0; 256];
+        let mut ota = MockOta::new();
+
+        let mut client =
+            MockConnection::new(|_: &str, _: &[(String, String)]| MockResponse::new(404, b""));
+
+        assert!(matches!(
+            download(&mut client, &mut ota, URI, &mut buf),
+            Err(DownloadError::Status(404))
+        ));
+        assert_eq!(ota.aborts, 1);
+
+        let headers = [("X", "x"); 9];
+        let mut client = MockConnection::new(server(b"", true));
+
+        assert!(matches!(
+            download_with_headers(&mut client, &mut ota, URI, &headers, &mut buf),
+            Err(DownloadError::TooManyHeaders)
+        ));
+        assert_eq!(ota.aborts, 2);
+    }
+
+    fn firmware(version: &str) -> FirmwareInfo {
+        FirmwareInfo {
+            version: version.try_into().unwrap(),
+            released: "".try_into().unwrap(),
+            description: None,
+            signature: None,
+            download_id: None,
+        }
+    }
+
+    fn version(version: &str) -> Version<'_> {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn version_precedence() {
+        // In increasing order, as per <https://semver.org/#spec-item-11>
+        let versions = [
+            "0.9.9",
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.2.0",
+            "1.9.0",
+            "1.10.0",
+            "1.10.1",
+            "2.0.0",
+        ];
+
+        for (index, lower) in versions.iter().enumerate() {
+            for higher in &versions[index + 1..] {
+                assert!(version(lower) < version(higher), "{} < {}", lower, higher);
+                assert!(version(higher) > version(lower), "{} > {}", higher, lower);
+            }
+        }
+    }
+
+    #[test]
+    fn version_parsing() {
+        let parsed = version(" v1.2.3-rc.1+build.5 ");
+
+        assert_eq!((parsed.major, parsed.minor, parsed.patch), (1, 2, 3));
+        assert_eq!(parsed.pre, Some("rc.1"));
+        assert_eq!(parsed.build, Some("build.5"));
+        assert!(parsed.is_prerelease());
+        assert_eq!(parsed.to_string(), "1.2.3-rc.1+build.5");
+
+        // Missing numbers are zero, and the build metadata is ignored
+        assert_eq!(version("V2"), version("2.0.0"));
+        assert_eq!(version("1.4"), version("1.4.0+abc"));
+        assert_eq!(version("1.4").to_string(), "1.4.0");
+
+        for invalid in [
+            "",
+            "v",
+            "1..2",
+            "1.2.3.4",
+            "1.x",
+            "-1.0.0",
+            "1.0.0-",
+            "1.0.0-beta..1",
+            "1.0.0-beta_1",
+            "99999999999999999999.0.0",
+        ] {
+            assert!(Version::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn channels() {
+        let release = version("1.1.0");
+        let beta = version("1.1.0-beta.3");
+        let nightly = version("1.1.0-nightly.20240101");
+
+        assert!(Channel::Stable.accepts(&release));
+        assert!(!Channel::Stable.accepts(&beta));
+
+        assert!(Channel::Prerelease.accepts(&release));
+        assert!(Channel::Prerelease.accepts(&beta));
+        assert!(Channel::Prerelease.accepts(&nightly));
+
+        assert!(Channel::Named("beta").accepts(&release));
+        assert!(Channel::Named("beta").accepts(&beta));
+        assert!(!Channel::Named("beta").accepts(&nightly));
+        assert!(!Channel::Named("bet").accepts(&beta));
+    }
+
+    #[test]
+    fn update_availability() {
+        let running = firmware("1.9.0");
+
+        assert_eq!(
+            is_update_available(&running, "1.10.0", &Channel::Stable),
+            Ok(true)
+        );
+        assert_eq!(
+            is_update_available(&running, "1.9.0", &Channel::Stable),
+            Ok(false)
+        );
+        assert_eq!(
+            is_update_available(&running, "1.8.5", &Channel::Stable),
+            Ok(false)
+        );
+
+        assert_eq!(
+            is_update_available(&running, "1.10.0-beta.1", &Channel::Stable),
+            Ok(false)
+        );
+        assert_eq!(
+            is_update_available(&running, "1.10.0-beta.1", &Channel::Named("beta")),
+            Ok(true)
+        );
+        // A pre-release of the running version is older than it
+        assert_eq!(
+            is_update_available(&running, "1.9.0-rc.1", &Channel::Prerelease),
+            Ok(false)
+        );
+
+        assert!(is_update_available(&running, "latest", &Channel::Stable).is_err());
+        assert!(is_update_available(&firmware("dev"), "1.0.0", &Channel::Stable).is_err());
+    }
+
+    fn violation(
+        kind: PolicyViolationKind,
+        running: Option<&str>,
+        candidate: &str,
+    ) -> PolicyViolation {
+        PolicyViolation {
+            kind,
+            running: running.map(|running| running.try_into().unwrap()),
+            candidate: candidate.try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn policy_min_version() {
+        let policy = UpdatePolicy {
+            min_version: Some("2.1.0".try_into().unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.check_version(Some("2.0.0"), "2.0.5"),
+            Err(violation(
+                PolicyViolationKind::BelowMinimum,
+                Some("2.0.0"),
+                "2.0.5"
+            ))
+        );
+        assert_eq!(
+            policy.check_version(None, "2.1.0-rc.1"),
+            Err(violation(
+                PolicyViolationKind::BelowMinimum,
+                None,
+                "2.1.0-rc.1"
+            ))
+        );
+        assert_eq!(policy.check_version(Some("2.0.0"), "2.1.0"), Ok(()));
+        assert_eq!(policy.check_version(None, "3.0.0"), Ok(()));
+
+        // Still enforced when downgrades are allowed
+        let policy = UpdatePolicy {
+            allow_downgrade: true,
+            ..policy
+        };
+
+        assert_eq!(
+            policy.check_version(Some("2.2.0"), "2.0.0"),
+            Err(violation(
+                PolicyViolationKind::BelowMinimum,
+                Some("2.2.0"),
+                "2.0.0"
+            ))
+        );
+        assert_eq!(policy.check_version(Some("2.2.0"), "2.1.1"), Ok(()));
+    }
+
+    #[test]
+    fn policy_same_version() {
+        let mut policy = UpdatePolicy::default();
+
+        assert_eq!(
+            policy.check_version(Some("1.4.0"), "v1.4.0+rebuild"),
+            Err(violation(
+                PolicyViolationKind::SameVersion,
+                Some("1.4.0"),
+                "v1.4.0+rebuild"
+            ))
+        );
+
+        policy.allow_same_version = true;
+
+        assert_eq!(
+            policy.check_version(Some("1.4.0"), "v1.4.0+rebuild"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn policy_downgrade() {
+        let mut policy = UpdatePolicy::default();
+
+        assert_eq!(
+            policy.check_version(Some("1.10.0"), "1.9.0"),
+            Err(violation(
+                PolicyViolationKind::Downgrade,
+                Some("1.10.0"),
+                "1.9.0"
+            ))
+        );
+        assert_eq!(
+            policy.check_version(Some("1.0.0"), "1.0.0-rc.2"),
+            Err(violation(
+                PolicyViolationKind::Downgrade,
+                Some("1.0.0"),
+                "1.0.0-rc.2"
+            ))
+        );
+        assert_eq!(policy.check_version(Some("1.9.0"), "1.10.0"), Ok(()));
+
+        policy.allow_downgrade = true;
+
+        assert_eq!(policy.check_version(Some("1.10.0"), "1.9.0"), Ok(()));
+        // Allowing downgrades does not allow flashing the same version
+        assert_eq!(
+            policy
+                .check_version(Some("1.10.0"), "1.10.0")
+                .map_err(|e| e.kind),
+            Err(PolicyViolationKind::SameVersion)
+        );
+    }
+
+    #[test]
+    fn policy_invalid_versions() {
+        let policy = UpdatePolicy {
+            min_version: Some("1.0.0".try_into().unwrap()),
+            ..Default::default()
+        };
+
+        for (running, candidate) in [(Some("1.0.0"), "latest"), (Some("dev"), "1.1.0")] {
+            assert_eq!(
+                policy.check_version(running, candidate),
+                Err(violation(
+                    PolicyViolationKind::InvalidVersion,
+                    running,
+                    candidate
+                ))
+            );
+        }
+
+        let policy = UpdatePolicy {
+            min_version: Some("one".try_into().unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.check_version(None, "1.1.0").map_err(|e| e.kind),
+            Err(PolicyViolationKind::InvalidVersion)
+        );
+
+        // Versions which do not fit in the violation are left empty
+        assert_eq!(
+            UpdatePolicy::default().check_version(None, &"x".repeat(30)),
+            Err(violation(PolicyViolationKind::InvalidVersion, None, ""))
+        );
+    }
+
+    #[test]
+    fn policy_violation_display() {
+        assert_eq!(
+            violation(PolicyViolationKind::Downgrade, Some("1.1.0"), "1.0.0").to_string(),
+            "Version 1.0.0 is older than the running version 1.1.0"
+        );
+        assert_eq!(
+            violation(PolicyViolationKind::InvalidVersion, None, "x").to_string(),
+            "Invalid version (candidate: x, running: (unknown))"
+        );
+        assert_eq!(
+            violation(PolicyViolationKind::BelowMinimum, None, "0.1.0").to_string(),
+            "Version 0.1.0 is below the minimum version"
+        );
+        assert_eq!(
+            violation(PolicyViolationKind::SameVersion, Some("1.0.0"), "1.0.0").to_string(),
+            "Version 1.0.0 is already running"
+        );
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn policy_serde() {
+        use crate::test_json::{from_slice, to_vec};
+
+        let policy = UpdatePolicy {
+            min_version: Some("2.1.0".try_into().unwrap()),
+            allow_same_version: true,
+            allow_downgrade: false,
+        };
+
+        let json = to_vec(&policy);
+
+        assert_eq!(
+            core::str::from_utf8(&json).unwrap(),
+            "{\"min_version\":\"2.1.0\",\"allow_same_version\":true,\"allow_downgrade\":false}"
+        );
+        assert_eq!(from_slice::<UpdatePolicy>(&json).unwrap(), policy);
+    }
+
+    /// An image starting with a TLV header with `version`
+    fn tlv_image(version: &str, len: usize) -> Vec<u8> {
+        let mut image = MAGIC.to_vec();
+
+        image.extend_from_slice(&[TAG_VERSION, version.len() as u8]);
+        image.extend_from_slice(version.as_bytes());
+        // An unknown tag
+        image.extend_from_slice(&[0x7f, 2, 0xaa, 0xbb]);
+        image.push(TAG_END);
+
+        image.extend_from_slice(&self::image(len));
+
+        image
+    }
+
+    #[test]
+    fn download_checked_by_policy() {
+        let mut buf = [0; 64];
+        let mut loader = TlvFirmwareInfoLoader::<64>::new();
+        let policy = UpdatePolicy::default();
+
+        let mut ota = MockOta {
+            running_version: Some("1.0.0"),
+            ..Default::default()
+        };
+
+        let image = tlv_image("1.1.0", 1000);
+        let mut client = MockConnection::new(server(&image, true));
+
+        assert!(matches!(
+            download_with_policy(&mut client, &mut ota, URI, &[], &mut buf, &mut loader, &policy),
+            Ok(size) if size == image.len() as u64
+        ));
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+        assert_eq!(loader.get_info().unwrap().version, "1.1.0");
+
+        // The loader is reused, and must not report the version of the previous image
+        let image = tlv_image("0.9.0", 1000);
+        let mut client = MockConnection::new(server(&image, true));
+
+        // Nothing must be written
+        ota.fail_at = Some(0);
+
+        assert!(matches!(
+            download_with_policy(
+                &mut client,
+                &mut ota,
+                URI,
+                &[],
+                &mut buf,
+                &mut loader,
+                &policy
+            ),
+            Err(DownloadError::Policy(PolicyViolation {
+                kind: PolicyViolationKind::Downgrade,
+                ..
+            }))
+        ));
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn download_checked_by_policy_header_in_parts() {
+        let mut buf = [0; 64];
+        let mut loader = TlvFirmwareInfoLoader::<64>::new();
+        let policy = UpdatePolicy::default();
+
+        let mut ota = MockOta {
+            running_version: Some("1.0.0"),
+            ..Default::default()
+        };
+
+        let image = tlv_image("1.0.1", 1000);
+
+        // The header is received one byte at a time
+        let mut client = MockConnection::new(|_: &str, _: &[(String, String)]| MockResponse {
+            max_read: Some(1),
+            ..MockResponse::new(200, &image)
+        });
+
+        assert!(download_with_policy(
+            &mut client,
+            &mut ota,
+            URI,
+            &[],
+            &mut buf,
+            &mut loader,
+            &policy
+        )
+        .is_ok());
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+    }
+
+    #[test]
+    fn download_invalid_firmware() {
+        let mut buf = [0; 64];
+        let mut loader = TlvFirmwareInfoLoader::<64>::new();
+        let policy = UpdatePolicy::default();
+
+        let mut ota = MockOta::new();
+
+        for image in [image(1000), MAGIC.to_vec(), tlv_image(&"1".repeat(100), 10)] {
+            let mut client = MockConnection::new(server(&image, true));
+
+            assert!(matches!(
+                download_with_policy(
+                    &mut client,
+                    &mut ota,
+                    URI,
+                    &[],
+                    &mut buf,
+                    &mut loader,
+                    &policy
+                ),
+                Err(DownloadError::InvalidFirmware)
+            ));
+        }
+
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 3);
+    }
+}
@@ -0,0 +1,1297 @@
+//! Firmware updates over MQTT.
+//!
+//! The firmware image is published as retained messages on `fw/<model>/<version>/<index>`
+//! topics, each carrying a chunk of the image, alongside a retained JSON manifest on the
+//! `fw/<model>/manifest` topic, e.g.:
+//!
+//! ```json
+//! {"version":"1.2.0","size":1048576,"chunk_size":4096,"sha256":"<64 hex digits>"}
+//! ```
+//!
+//! The chunks are requested in order by subscribing to one chunk topic at a time;
+//! duplicate and out-of-order messages are ignored. The progress is published as a JSON
+//! status on a topic chosen by the application.
+
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Display, Formatter, Write as _};
+
+use crate::mqtt::client::{Details, Message, QoS};
+use crate::ota::VerifyError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Manifest {
+    pub version: heapless::String<24>,
+    pub size: u64,
+    pub chunk_size: u32,
+    pub sha256: [u8; 32],
+}
+
+impl Manifest {
+    pub fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        let data = core::str::from_utf8(data).map_err(|_| "Manifest is not UTF-8")?;
+
+        let mut version = None;
+        let mut size = None;
+        let mut chunk_size = None;
+        let mut sha256 = None;
+
+        let mut parser = Parser(data.trim_start());
+
+        parser.expect('{')?;
+
+        if !parser.try_expect('}') {
+            loop {
+                let key = parser.string()?;
+                parser.expect(':')?;
+                let value = parser.value()?;
+
+                match key {
+                    "version" => {
+                        let mut string = heapless::String::new();
+                        string
+                            .push_str(value.as_str()?)
+                            .map_err(|_| "Version too long")?;
+
+                        version = Some(string);
+                    }
+                    "size" => size = Some(value.as_number()?),
+                    "chunk_size" => {
+                        chunk_size = Some(
+                            u32::try_from(value.as_number()?)
+                                .map_err(|_| "Chunk size too large")?,
+                        )
+                    }
+                    "sha256" => sha256 = Some(parse_hex(value.as_str()?)?),
+                    _ => (),
+                }
+
+                if parser.try_expect('}') {
+                    break;
+                }
+
+                parser.expect(',')?;
+            }
+        }
+
+        let chunk_size = chunk_size.ok_or("Missing chunk_size")?;
+        if chunk_size == 0 {
+            return Err("Invalid chunk_size");
+        }
+
+        let manifest = Self {
+            version: version.ok_or("Missing version")?,
+            size: size.ok_or("Missing size")?,
+            chunk_size,
+            sha256: sha256.ok_or("Missing sha256")?,
+        };
+
+        if manifest.checked_chunks().is_none() {
+            return Err("Too many chunks");
+        }
+
+        Ok(manifest)
+    }
+
+    /// The number of chunks of the image
+    ///
+    /// Saturates at `u32::MAX` for a manifest not returned by `parse`, with too many chunks to be counted.
+    pub fn chunks(&self) -> u32 {
+        self.checked_chunks().unwrap_or(u32::MAX)
+    }
+
+    fn checked_chunks(&self) -> Option<u32> {
+        let chunk_size = self.chunk_size as u64;
+
+        let chunks = self.size.checked_add(chunk_size.checked_sub(1)?)? / chunk_size;
+
+        u32::try_from(chunks).ok()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    Downloading,
+    Completed,
+    Failed,
+}
+
+/// Formats the JSON status published during the update
+pub fn status(
+    state: State,
+    version: &str,
+    received: u64,
+    total: u64,
+) -> Result<heapless::String<128>, fmt::Error> {
+    let state = match state {
+        State::Downloading => "downloading",
+        State::Completed => "completed",
+        State::Failed => "failed",
+    };
+
+    let mut status = heapless::String::new();
+
+    write!(
+        status,
+        "{{\"state\":\"{state}\",\"version\":\"{}\",\"received\":{received},\"total\":{total}}}",
+        version.escape_debug()
+    )?;
+
+    Ok(status)
+}
+
+pub fn manifest_topic(model: &str) -> Result<heapless::String<128>, fmt::Error> {
+    let mut topic = heapless::String::new();
+
+    write!(topic, "fw/{model}/manifest")?;
+
+    Ok(topic)
+}
+
+pub fn chunk_topic(
+    model: &str,
+    version: &str,
+    index: u32,
+) -> Result<heapless::String<128>, fmt::Error> {
+    let mut topic = heapless::String::new();
+
+    write!(topic, "fw/{model}/{version}/{index}")?;
+
+    Ok(topic)
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OtaMqttError<M, O> {
+    Mqtt(M),
+    Ota(O),
+    Manifest(&'static str),
+    Topic,
+    ChunkGap,
+    ChunkSize,
+    Mismatch,
+    Disconnected,
+}
+
+impl<M, O> From<VerifyError<O>> for OtaMqttError<M, O> {
+    fn from(e: VerifyError<O>) -> Self {
+        match e {
+            VerifyError::Mismatch => Self::Mismatch,
+            VerifyError::Update(e) => Self::Ota(e),
+        }
+    }
+}
+
+impl<M, O> From<fmt::Error> for OtaMqttError<M, O> {
+    fn from(_: fmt::Error) -> Self {
+        Self::Topic
+    }
+}
+
+impl<M: Debug, O: Debug> Display for OtaMqttError<M, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<M: Debug, O: Debug> std::error::Error for OtaMqttError<M, O> {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Received<'d> {
+    /// The message is not (a part of) the expected chunk
+    Ignored,
+    /// The data should be written into the update
+    Data {
+        data: &'d [u8],
+        chunk_completed: bool,
+    },
+}
+
+/// Tracks the chunks of the image received for an update
+pub struct ChunkReceiver<'a> {
+    manifest: &'a Manifest,
+    prefix: heapless::String<128>,
+    topic: heapless::String<128>,
+    index: u32,
+    offset: usize,
+    received: u64,
+}
+
+impl<'a> ChunkReceiver<'a> {
+    pub fn new(model: &str, manifest: &'a Manifest) -> Result<Self, fmt::Error> {
+        let mut prefix = heapless::String::new();
+        write!(prefix, "fw/{model}/{}/", manifest.version)?;
+
+        Ok(Self {
+            manifest,
+            topic: chunk_topic(model, &manifest.version, 0)?,
+            prefix,
+            index: 0,
+            offset: 0,
+            received: 0,
+        })
+    }
+
+    /// The topic of the next expected chunk
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.index >= self.manifest.chunks()
+    }
+
+    pub fn receive<'d, M, O>(
+        &mut self,
+        message: &'d impl Message,
+    ) -> Result<Received<'d>, OtaMqttError<M, O>> {
+        if self.is_completed() {
+            return Ok(Received::Ignored);
+        }
+
+        match (message.topic(), message.details()) {
+            (Some(topic), _) if topic == self.topic.as_str() => (),
+            // Only the first part of a chunk split by the client carries the topic
+            (None, Details::SubsequentChunk(_)) if self.offset > 0 => (),
+            _ => return Ok(Received::Ignored),
+        }
+
+        let data = message.data();
+
+        let (offset, total) = match message.details() {
+            Details::Complete => (0, data.len()),
+            Details::InitialChunk(chunk) => (0, chunk.total_data_size),
+            Details::SubsequentChunk(chunk) => (chunk.current_data_offset, chunk.total_data_size),
+        };
+
+        if offset < self.offset {
+            // A duplicate part of the chunk
+            return Ok(Received::Ignored);
+        } else if offset > self.offset {
+            return Err(OtaMqttError::ChunkGap);
+        }
+
+        let last = self.index + 1 == self.manifest.chunks();
+        let expected = if last {
+            self.manifest.size - self.index as u64 * self.manifest.chunk_size as u64
+        } else {
+            self.manifest.chunk_size as u64
+        };
+
+        if total as u64 != expected || offset + data.len() > total {
+            return Err(OtaMqttError::ChunkSize);
+        }
+
+        self.offset += data.len();
+        self.received += data.len() as u64;
+
+        let chunk_completed = self.offset == total;
+
+        if chunk_completed {
+            self.index += 1;
+            self.offset = 0;
+
+            if !self.is_completed() {
+                self.topic.clone_from(&self.prefix);
+                write!(self.topic, "{}", self.index)?;
+            }
+        }
+
+        Ok(Received::Data {
+            data,
+            chunk_completed,
+        })
+    }
+}
+
+const QOS: QoS = QoS::AtLeastOnce;
+
+/// Downloads the firmware image of `model` advertised in its manifest into an OTA update,
+/// verifying it with `digest` before completing the update.
+///
+/// `accept` is called with the manifest before the update is initiated, and can be used
+/// to e.g. refuse downgrades with `utils::ota::UpdatePolicy::check_version`; in that case `None` is returned.
+pub fn update<C, N, O, D>(
+    client: &mut C,
+    connection: &mut N,
+    ota: &mut O,
+    digest: D,
+    model: &str,
+    status_topic: &str,
+    accept: impl FnOnce(&Manifest) -> bool,
+) -> Result<Option<Manifest>, OtaMqttError<C::Error, O::Error>>
+where
+    C: crate::mqtt::client::Client + crate::mqtt::client::Publish,
+    N: crate::mqtt::client::Connection<Error = C::Error>,
+    for<'a> N::Message<'a>: Message,
+    O: crate::ota::Ota,
+    D: crate::ota::Digest<Output = [u8; 32]>,
+{
+    use crate::mqtt::client::Event;
+    use crate::ota::VerifyingOtaUpdate;
+
+    let manifest_topic = manifest_topic(model)?;
+
+    client
+        .subscribe(&manifest_topic, QOS)
+        .map_err(OtaMqttError::Mqtt)?;
+
+    let manifest = loop {
+        match connection.next() {
+            Some(Ok(Event::Received(message)))
+                if message.topic() == Some(manifest_topic.as_str()) =>
+            {
+                if !matches!(message.details(), Details::Complete) {
+                    break Err(OtaMqttError::Manifest("Manifest too large"));
+                }
+
+                break Manifest::parse(message.data()).map_err(OtaMqttError::Manifest);
+            }
+            Some(Ok(_)) => (),
+            Some(Err(e)) => break Err(OtaMqttError::Mqtt(e)),
+            None => break Err(OtaMqttError::Disconnected),
+        }
+    };
+
+    client
+        .unsubscribe(&manifest_topic)
+        .map_err(OtaMqttError::Mqtt)?;
+
+    let manifest = manifest?;
+
+    if !accept(&manifest) {
+        return Ok(None);
+    }
+
+    let mut update =
+        VerifyingOtaUpdate::new(ota.initiate_update().map_err(OtaMqttError::Ota)?, digest);
+
+    let mut receiver = ChunkReceiver::new(model, &manifest)?;
+
+    let result = (|| {
+        while !receiver.is_completed() {
+            let topic = heapless::String::<128>::try_from(receiver.topic()).unwrap();
+
+            client.subscribe(&topic, QOS).map_err(OtaMqttError::Mqtt)?;
+
+            loop {
+                match connection.next() {
+                    Some(Ok(Event::Received(message))) => {
+                        if let Received::Data {
+                            data,
+                            chunk_completed,
+                        } = receiver.receive(&message)?
+                        {
+                            crate::io::Write::write_all(&mut update, data)
+                                .map_err(OtaMqttError::Ota)?;
+
+                            if chunk_completed {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(_)) => (),
+                    Some(Err(e)) => return Err(OtaMqttError::Mqtt(e)),
+                    None => return Err(OtaMqttError::Disconnected),
+                }
+            }
+
+            client.unsubscribe(&topic).map_err(OtaMqttError::Mqtt)?;
+
+            publish_status(
+                client,
+                status_topic,
+                State::Downloading,
+                &manifest,
+                receiver.received(),
+            )?;
+        }
+
+        Ok(())
+    })();
+
+    let result = match result {
+        Ok(()) => update
+            .complete(&manifest.sha256)
+            .map_err(OtaMqttError::from),
+        Err(e) => {
+            let _ = update.abort();
+
+            Err(e)
+        }
+    };
+
+    let state = if result.is_ok() {
+        State::Completed
+    } else {
+        State::Failed
+    };
+
+    let published = publish_status(client, status_topic, state, &manifest, receiver.received());
+
+    result?;
+    published?;
+
+    Ok(Some(manifest))
+}
+
+fn publish_status<C, O>(
+    client: &mut C,
+    topic: &str,
+    state: State,
+    manifest: &Manifest,
+    received: u64,
+) -> Result<(), OtaMqttError<C::Error, O>>
+where
+    C: crate::mqtt::client::Publish,
+{
+    let status = status(state, &manifest.version, received, manifest.size)?;
+
+    client
+        .publish(topic, QOS, false, status.as_bytes())
+        .map_err(OtaMqttError::Mqtt)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "nightly")]
+pub mod asynch {
+    use core::convert::TryFrom;
+
+    use crate::io::asynch::Write;
+    use crate::mqtt::client::asynch::{Client, Connection, Event, Message, Publish};
+    use crate::ota::asynch::{Digest, Ota, VerifyingOtaUpdate};
+
+    pub use super::{
+        chunk_topic, manifest_topic, status, ChunkReceiver, Manifest, OtaMqttError, Received, State,
+    };
+
+    use super::{Details, QOS};
+
+    /// Same as `ota_mqtt::update`, for the async MQTT and OTA traits
+    pub async fn update<C, N, O, D>(
+        client: &mut C,
+        connection: &mut N,
+        ota: &mut O,
+        digest: D,
+        model: &str,
+        status_topic: &str,
+        accept: impl FnOnce(&Manifest) -> bool,
+    ) -> Result<Option<Manifest>, OtaMqttError<C::Error, O::Error>>
+    where
+        C: Client + Publish,
+        N: Connection<Error = C::Error>,
+        for<'a> N::Message<'a>: Message,
+        O: Ota,
+        D: Digest<Output = [u8; 32]>,
+    {
+        let manifest_topic = manifest_topic(model)?;
+
+        client
+            .subscribe(&manifest_topic, QOS)
+            .await
+            .map_err(OtaMqttError::Mqtt)?;
+
+        let manifest = loop {
+            match connection.next().await {
+                Some(Ok(Event::Received(message)))
+                    if message.topic() == Some(manifest_topic.as_str()) =>
+                {
+                    if !matches!(message.details(), Details::Complete) {
+                        break Err(OtaMqttError::Manifest("Manifest too large"));
+                    }
+
+                    break Manifest::parse(message.data()).map_err(OtaMqttError::Manifest);
+                }
+                Some(Ok(_)) => (),
+                Some(Err(e)) => break Err(OtaMqttError::Mqtt(e)),
+                None => break Err(OtaMqttError::Disconnected),
+            }
+        };
+
+        client
+            .unsubscribe(&manifest_topic)
+            .await
+            .map_err(OtaMqttError::Mqtt)?;
+
+        let manifest = manifest?;
+
+        if !accept(&manifest) {
+            return Ok(None);
+        }
+
+        let mut update = VerifyingOtaUpdate::new(
+            ota.initiate_update().await.map_err(OtaMqttError::Ota)?,
+            digest,
+        );
+
+        let mut receiver = ChunkReceiver::new(model, &manifest)?;
+
+        let result = async {
+            while !receiver.is_completed() {
+                let topic = heapless::String::<128>::try_from(receiver.topic()).unwrap();
+
+                client
+                    .subscribe(&topic, QOS)
+                    .await
+                    .map_err(OtaMqttError::Mqtt)?;
+
+                loop {
+                    match connection.next().await {
+                        Some(Ok(Event::Received(message))) => {
+                            if let Received::Data {
+                                data,
+                                chunk_completed,
+                            } = receiver.receive(&message)?
+                            {
+                                update.write_all(data).await.map_err(OtaMqttError::Ota)?;
+
+                                if chunk_completed {
+                                    break;
+                                }
+                            }
+                        }
+                        Some(Ok(_)) => (),
+                        Some(Err(e)) => return Err(OtaMqttError::Mqtt(e)),
+                        None => return Err(OtaMqttError::Disconnected),
+                    }
+                }
+
+                client
+                    .unsubscribe(&topic)
+                    .await
+                    .map_err(OtaMqttError::Mqtt)?;
+
+                publish_status(
+                    client,
+                    status_topic,
+                    State::Downloading,
+                    &manifest,
+                    receiver.received(),
+                )
+                .await?;
+            }
+
+            Ok(())
+        }
+        .await;
+
+        let result = match result {
+            Ok(()) => update
+                .complete(&manifest.sha256)
+                .await
+                .map_err(OtaMqttError::from),
+            Err(e) => {
+                let _ = update.abort().await;
+
+                Err(e)
+            }
+        };
+
+        let state = if result.is_ok() {
+            State::Completed
+        } else {
+            State::Failed
+        };
+
+        let published =
+            publish_status(client, status_topic, state, &manifest, receiver.received()).await;
+
+        result?;
+        published?;
+
+        Ok(Some(manifest))
+    }
+
+    async fn publish_status<C, O>(
+        client: &mut C,
+        topic: &str,
+        state: State,
+        manifest: &Manifest,
+        received: u64,
+    ) -> Result<(), OtaMqttError<C::Error, O>>
+    where
+        C: Publish,
+    {
+        let status = status(state, &manifest.version, received, manifest.size)?;
+
+        client
+            .publish(topic, QOS, false, status.as_bytes())
+            .await
+            .map_err(OtaMqttError::Mqtt)?;
+
+        Ok(())
+    }
+}
+
+enum Value<'a> {
+    String(&'a str),
+    Number(u64),
+    Other,
+}
+
+impl<'a> Value<'a> {
+    fn as_str(&self) -> Result<&'a str, &'static str> {
+        match self {
+            Self::String(s) => Ok(s),
+            _ => Err("Expected a string"),
+        }
+    }
+
+    fn as_number(&self) -> Result<u64, &'static str> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            _ => Err("Expected a number"),
+        }
+    }
+}
+
+/// A minimal parser for flat JSON objects with string and unsigned integer values
+struct Parser<'a>(&'a str);
+
+impl<'a> Parser<'a> {
+    fn try_expect(&mut self, c: char) -> bool {
+        if let Some(rest) = self.0.strip_prefix(c) {
+            self.0 = rest.trim_start();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), &'static str> {
+        if self.try_expect(c) {
+            Ok(())
+        } else {
+            Err("Invalid manifest JSON")
+        }
+    }
+
+    fn string(&mut self) -> Result<&'a str, &'static str> {
+        let rest = self.0.strip_prefix('"').ok_or("Expected a string")?;
+        let end = rest.find(['"', '\\']).ok_or("Unterminated string")?;
+
+        if rest[end..].starts_with('\\') {
+            return Err("Escapes are not supported");
+        }
+
+        self.0 = rest[end + 1..].trim_start();
+
+        Ok(&rest[..end])
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, &'static str> {
+        if self.0.starts_with('"') {
+            return self.string().map(Value::String);
+        }
+
+        let end = self
+            .0
+            .find(|c: char| c == ',' || c == '}' || c.is_whitespace())
+            .unwrap_or(self.0.len());
+
+        let (value, rest) = self.0.split_at(end);
+        self.0 = rest.trim_start();
+
+        match value {
+            "true" | "false" | "null" => Ok(Value::Other),
+            _ => value
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| "Invalid number"),
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<[u8; 32], &'static str> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err("Invalid SHA-256 digest");
+    }
+
+    let mut digest = [0; 32];
+
+    for (index, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+            .map_err(|_| "Invalid SHA-256 digest")?;
+    }
+
+    Ok(digest)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::TryInto;
+
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+    use std::rc::Rc;
+
+    use crate::io::ErrorKind;
+    use crate::mqtt::client::{
+        Client, Connection, ErrorType, Event, InitialChunkData, MessageId, Publish,
+        SubsequentChunkData,
+    };
+    use crate::ota::Digest;
+    use crate::test_ota::{padded_crc32, MockOta};
+
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct MockMessage {
+        topic: Option<String>,
+        data: Vec<u8>,
+        details: Details,
+    }
+
+    impl Message for MockMessage {
+        fn id(&self) -> MessageId {
+            0
+        }
+
+        fn topic(&self) -> Option<&str> {
+            self.topic.as_deref()
+        }
+
+        fn data(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn details(&self) -> &Details {
+            &self.details
+        }
+    }
+
+    fn complete(topic: &str, data: &[u8]) -> MockMessage {
+        MockMessage {
+            topic: Some(topic.to_string()),
+            data: data.to_vec(),
+            details: Details::Complete,
+        }
+    }
+
+    /// The part of a message split by the client, which is at `offset` in the message of size `total`
+    fn part(topic: &str, data: &[u8], offset: usize, total: usize) -> MockMessage {
+        if offset == 0 {
+            MockMessage {
+                topic: Some(topic.to_string()),
+                data: data.to_vec(),
+                details: Details::InitialChunk(InitialChunkData {
+                    total_data_size: total,
+                }),
+            }
+        } else {
+            MockMessage {
+                topic: None,
+                data: data.to_vec(),
+                details: Details::SubsequentChunk(SubsequentChunkData {
+                    current_data_offset: offset,
+                    total_data_size: total,
+                }),
+            }
+        }
+    }
+
+    /// Splits `data` in parts of `part_size`, as a client with a small buffer does
+    fn parts(topic: &str, data: &[u8], part_size: usize) -> Vec<MockMessage> {
+        data.chunks(part_size)
+            .enumerate()
+            .map(|(index, chunk)| part(topic, chunk, index * part_size, data.len()))
+            .collect()
+    }
+
+    /// A broker delivering the messages of a topic (which can also be stale or duplicate messages
+    /// of other topics) once it is subscribed
+    #[derive(Default)]
+    struct Broker {
+        retained: HashMap<String, Vec<MockMessage>>,
+        queue: VecDeque<MockMessage>,
+        subscriptions: Vec<String>,
+        published: Vec<(String, String)>,
+    }
+
+    struct MockClient(Rc<RefCell<Broker>>);
+
+    impl ErrorType for MockClient {
+        type Error = ();
+    }
+
+    impl Client for MockClient {
+        fn subscribe<'a>(&'a mut self, topic: &'a str, _qos: QoS) -> Result<MessageId, ()> {
+            let mut broker = self.0.borrow_mut();
+
+            let messages = broker.retained.get(topic).cloned().unwrap_or_default();
+
+            broker.queue.extend(messages);
+            broker.subscriptions.push(topic.to_string());
+
+            Ok(0)
+        }
+
+        fn unsubscribe<'a>(&'a mut self, topic: &'a str) -> Result<MessageId, ()> {
+            self.0.borrow_mut().subscriptions.retain(|t| t != topic);
+
+            Ok(0)
+        }
+    }
+
+    impl Publish for MockClient {
+        fn publish<'a>(
+            &'a mut self,
+            topic: &'a str,
+            _qos: QoS,
+            _retain: bool,
+            payload: &'a [u8],
+        ) -> Result<MessageId, ()> {
+            self.0.borrow_mut().published.push((
+                topic.to_string(),
+                String::from_utf8(payload.to_vec()).unwrap(),
+            ));
+
+            Ok(0)
+        }
+    }
+
+    struct MockConnection(Rc<RefCell<Broker>>, Option<MockMessage>);
+
+    impl ErrorType for MockConnection {
+        type Error = ();
+    }
+
+    impl Connection for MockConnection {
+        type Message<'a> = &'a MockMessage;
+
+        fn next(&mut self) -> Option<Result<Event<Self::Message<'_>>, ()>> {
+            self.1 = Some(self.0.borrow_mut().queue.pop_front()?);
+
+            Some(Ok(Event::Received(self.1.as_ref().unwrap())))
+        }
+    }
+
+    const MODEL: &str = "sensor";
+    const STATUS: &str = "sensor/1/ota";
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn hex(digest: &[u8]) -> String {
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn manifest_json(version: &str, image: &[u8], chunk_size: u32) -> String {
+        let mut digest = padded_crc32();
+        digest.update(image);
+
+        format!(
+            "{{\"version\":\"{version}\",\"size\":{},\"chunk_size\":{chunk_size},\"sha256\":\"{}\"}}",
+            image.len(),
+            hex(&digest.finalize())
+        )
+    }
+
+    /// A broker with the manifest of `image` and its chunks of 1000 bytes, each split in parts of 400 bytes
+    fn broker(image: &[u8]) -> Broker {
+        let mut broker = Broker::default();
+
+        let manifest_topic = manifest_topic(MODEL).unwrap().to_string();
+        let manifest = manifest_json("1.1.0", image, 1000);
+
+        broker.retained.insert(
+            manifest_topic.clone(),
+            vec![complete(&manifest_topic, manifest.as_bytes())],
+        );
+
+        for (index, chunk) in image.chunks(1000).enumerate() {
+            let topic = chunk_topic(MODEL, "1.1.0", index as u32)
+                .unwrap()
+                .to_string();
+
+            broker
+                .retained
+                .insert(topic.clone(), parts(&topic, chunk, 400));
+        }
+
+        broker
+    }
+
+    fn run(
+        broker: Broker,
+        ota: &mut MockOta,
+    ) -> (
+        Result<Option<Manifest>, OtaMqttError<(), ErrorKind>>,
+        Broker,
+    ) {
+        let broker = Rc::new(RefCell::new(broker));
+
+        let mut client = MockClient(broker.clone());
+        let mut connection = MockConnection(broker.clone(), None);
+
+        let result = update(
+            &mut client,
+            &mut connection,
+            ota,
+            padded_crc32(),
+            MODEL,
+            STATUS,
+            |manifest| manifest.version != "0.0.0",
+        );
+
+        drop((client, connection));
+
+        (result, Rc::try_unwrap(broker).ok().unwrap().into_inner())
+    }
+
+    #[test]
+    fn manifest() {
+        let digest = "00".repeat(31) + "ff";
+        let json = format!(
+            " {{ \"version\" : \"1.2.0\", \"size\": 10000,\"chunk_size\":4096, \"sha256\":\"{digest}\", \"latest\": true, \"notes\": null }}"
+        );
+
+        let manifest = Manifest::parse(json.as_bytes()).unwrap();
+
+        assert_eq!(manifest.version, "1.2.0");
+        assert_eq!(manifest.size, 10000);
+        assert_eq!(manifest.chunk_size, 4096);
+        assert_eq!(manifest.sha256[31], 0xff);
+        assert_eq!(manifest.chunks(), 3);
+
+        for (json, error) in [
+            ("{}", "Missing chunk_size"),
+            ("{\"chunk_size\":0}", "Invalid chunk_size"),
+            ("{\"chunk_size\":4294967296}", "Chunk size too large"),
+            ("{\"version\":1}", "Expected a string"),
+            ("{\"version\":\"1\\n\"}", "Escapes are not supported"),
+            ("{\"size\":\"1\"}", "Expected a number"),
+            ("{\"size\":-1}", "Invalid number"),
+            ("{\"sha256\":\"00\"}", "Invalid SHA-256 digest"),
+            ("[]", "Invalid manifest JSON"),
+            ("{\"size\":1", "Invalid manifest JSON"),
+        ] {
+            assert_eq!(Manifest::parse(json.as_bytes()), Err(error), "{}", json);
+        }
+
+        // More than `u32::MAX` chunks
+        let json = format!(
+            "{{\"version\":\"1\",\"size\":8589934592,\"chunk_size\":1,\"sha256\":\"{digest}\"}}"
+        );
+        assert_eq!(Manifest::parse(json.as_bytes()), Err("Too many chunks"));
+    }
+
+    #[test]
+    fn chunk_count() {
+        let manifest = |size, chunk_size| Manifest {
+            version: "1".try_into().unwrap(),
+            size,
+            chunk_size,
+            sha256: [0; 32],
+        };
+
+        assert_eq!(manifest(0, 4096).chunks(), 0);
+        assert_eq!(manifest(1, 4096).chunks(), 1);
+        assert_eq!(manifest(4096, 4096).chunks(), 1);
+        assert_eq!(manifest(4097, 4096).chunks(), 2);
+        assert_eq!(manifest(u64::MAX, u32::MAX).chunks(), u32::MAX);
+        assert_eq!(manifest(u64::MAX, 1).chunks(), u32::MAX);
+    }
+
+    #[test]
+    fn chunks_received_in_parts() {
+        let manifest = Manifest {
+            version: "1.1.0".try_into().unwrap(),
+            size: 2500,
+            chunk_size: 1000,
+            sha256: [0; 32],
+        };
+
+        let mut receiver = ChunkReceiver::new(MODEL, &manifest).unwrap();
+
+        let received = |receiver: &mut ChunkReceiver<'_>, message: &MockMessage| match receiver
+            .receive::<(), ()>(message)
+            .unwrap()
+        {
+            Received::Ignored => None,
+            Received::Data {
+                data,
+                chunk_completed,
+            } => Some((data.len(), chunk_completed)),
+        };
+
+        let chunk0 = "fw/sensor/1.1.0/0";
+        let chunk1 = "fw/sensor/1.1.0/1";
+        let chunk2 = "fw/sensor/1.1.0/2";
+
+        assert_eq!(receiver.topic(), chunk0);
+
+        // Out of order
+        assert_eq!(received(&mut receiver, &complete(chunk1, &[0; 1000])), None);
+        // A continuation part, before the first part was received
+        assert_eq!(
+            received(&mut receiver, &part(chunk0, &[0; 400], 400, 1000)),
+            None
+        );
+
+        assert_eq!(
+            received(&mut receiver, &part(chunk0, &[0; 400], 0, 1000)),
+            Some((400, false))
+        );
+        // Duplicate
+        assert_eq!(
+            received(&mut receiver, &part(chunk0, &[0; 400], 0, 1000)),
+            None
+        );
+        assert_eq!(
+            received(&mut receiver, &part(chunk0, &[0; 400], 400, 1000)),
+            Some((400, false))
+        );
+        assert_eq!(
+            received(&mut receiver, &part(chunk0, &[0; 200], 800, 1000)),
+            Some((200, true))
+        );
+
+        assert_eq!(receiver.topic(), chunk1);
+        assert_eq!(receiver.received(), 1000);
+
+        // Duplicate of the previous chunk
+        assert_eq!(received(&mut receiver, &complete(chunk0, &[0; 1000])), None);
+        assert_eq!(
+            received(&mut receiver, &complete(chunk1, &[0; 1000])),
+            Some((1000, true))
+        );
+
+        // The last chunk is shorter
+        assert!(matches!(
+            receiver.receive::<(), ()>(&complete(chunk2, &[0; 1000])),
+            Err(OtaMqttError::ChunkSize)
+        ));
+        assert_eq!(
+            received(&mut receiver, &complete(chunk2, &[0; 500])),
+            Some((500, true))
+        );
+
+        assert!(receiver.is_completed());
+        assert_eq!(receiver.received(), 2500);
+        assert_eq!(received(&mut receiver, &complete(chunk2, &[0; 500])), None);
+    }
+
+    #[test]
+    fn chunk_gap() {
+        let manifest = Manifest {
+            version: "1.1.0".try_into().unwrap(),
+            size: 2500,
+            chunk_size: 1000,
+            sha256: [0; 32],
+        };
+
+        let mut receiver = ChunkReceiver::new(MODEL, &manifest).unwrap();
+
+        let topic = "fw/sensor/1.1.0/0";
+
+        assert!(receiver
+            .receive::<(), ()>(&part(topic, &[0; 400], 0, 1000))
+            .is_ok());
+        assert!(matches!(
+            receiver.receive::<(), ()>(&part(topic, &[0; 200], 800, 1000)),
+            Err(OtaMqttError::ChunkGap)
+        ));
+    }
+
+    #[test]
+    fn update_image() {
+        let image = image(2500);
+        let mut broker = broker(&image);
+
+        // Stale and duplicate messages, delivered along with chunk 1
+        let chunk0 = chunk_topic(MODEL, "1.1.0", 0).unwrap().to_string();
+        let chunk1 = chunk_topic(MODEL, "1.1.0", 1).unwrap().to_string();
+
+        let messages = broker.retained.get_mut(&chunk1).unwrap();
+        messages.insert(0, complete(&chunk0, &image[..1000]));
+        messages.insert(2, messages[1].clone());
+
+        let mut ota = MockOta::new();
+
+        let (result, broker) = run(broker, &mut ota);
+
+        assert_eq!(result.unwrap().unwrap().version, "1.1.0");
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+        assert!(broker.subscriptions.is_empty());
+
+        let statuses: Vec<_> = broker
+            .published
+            .iter()
+            .map(|(topic, status)| {
+                assert_eq!(topic, STATUS);
+                status.as_str()
+            })
+            .collect();
+
+        assert_eq!(
+            statuses,
+            [
+                "{\"state\":\"downloading\",\"version\":\"1.1.0\",\"received\":1000,\"total\":2500}",
+                "{\"state\":\"downloading\",\"version\":\"1.1.0\",\"received\":2000,\"total\":2500}",
+                "{\"state\":\"downloading\",\"version\":\"1.1.0\",\"received\":2500,\"total\":2500}",
+                "{\"state\":\"completed\",\"version\":\"1.1.0\",\"received\":2500,\"total\":2500}",
+            ]
+        );
+    }
+
+    #[test]
+    fn update_corrupted_image() {
+        let image = image(2500);
+        let mut broker = broker(&image);
+
+        let chunk2 = chunk_topic(MODEL, "1.1.0", 2).unwrap().to_string();
+        broker.retained.get_mut(&chunk2).unwrap()[0].data[0] ^= 1;
+
+        let mut ota = MockOta::new();
+
+        let (result, broker) = run(broker, &mut ota);
+
+        assert!(matches!(result, Err(OtaMqttError::Mismatch)));
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+        assert!(broker
+            .published
+            .last()
+            .unwrap()
+            .1
+            .starts_with("{\"state\":\"failed\""));
+    }
+
+    #[test]
+    fn update_missing_part() {
+        let image = image(2500);
+        let mut broker = broker(&image);
+
+        let chunk1 = chunk_topic(MODEL, "1.1.0", 1).unwrap().to_string();
+        broker.retained.get_mut(&chunk1).unwrap().remove(1);
+
+        let mut ota = MockOta::new();
+
+        let (result, broker) = run(broker, &mut ota);
+
+        assert!(matches!(result, Err(OtaMqttError::ChunkGap)));
+        assert_eq!(ota.aborts, 1);
+        assert_eq!(
+            broker.published.last().unwrap().1,
+            "{\"state\":\"failed\",\"version\":\"1.1.0\",\"received\":1400,\"total\":2500}"
+        );
+    }
+
+    #[test]
+    fn update_disconnected() {
+        let image = image(2500);
+        let mut broker = broker(&image);
+
+        let chunk2 = chunk_topic(MODEL, "1.1.0", 2).unwrap().to_string();
+        broker.retained.remove(&chunk2);
+
+        let mut ota = MockOta::new();
+
+        let (result, _) = run(broker, &mut ota);
+
+        assert!(matches!(result, Err(OtaMqttError::Disconnected)));
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn update_not_accepted() {
+        let mut broker = Broker::default();
+
+        let manifest_topic = manifest_topic(MODEL).unwrap().to_string();
+        let manifest = manifest_json("0.0.0", b"", 1000);
+
+        broker.retained.insert(
+            manifest_topic.clone(),
+            vec![complete(&manifest_topic, manifest.as_bytes())],
+        );
+
+        let mut ota = MockOta::new();
+
+        let (result, broker) = run(broker, &mut ota);
+
+        assert!(matches!(result, Ok(None)));
+        assert!(!ota.interrupted);
+        assert!(broker.published.is_empty());
+        assert!(broker.subscriptions.is_empty());
+    }
+
+    #[cfg(feature = "nightly")]
+    impl crate::mqtt::client::asynch::Client for MockClient {
+        async fn subscribe(&mut self, topic: &str, qos: QoS) -> Result<MessageId, ()> {
+            Client::subscribe(self, topic, qos)
+        }
+
+        async fn unsubscribe(&mut self, topic: &str) -> Result<MessageId, ()> {
+            Client::unsubscribe(self, topic)
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    impl crate::mqtt::client::asynch::Publish for MockClient {
+        async fn publish(
+            &mut self,
+            topic: &str,
+            qos: QoS,
+            retain: bool,
+            payload: &[u8],
+        ) -> Result<MessageId, ()> {
+            Publish::publish(self, topic, qos, retain, payload)
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    impl crate::mqtt::client::asynch::Connection for MockConnection {
+        type Message<'a> = &'a MockMessage;
+
+        async fn next(&mut self) -> Option<Result<Event<Self::Message<'_>>, ()>> {
+            Connection::next(self)
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn update_image_async() {
+        use crate::utils::asyncify::block_on;
+
+        let image = image(2500);
+        let mut broker = broker(&image);
+
+        let chunk1 = chunk_topic(MODEL, "1.1.0", 1).unwrap().to_string();
+        let messages = broker.retained.get_mut(&chunk1).unwrap();
+        messages.insert(2, messages[1].clone());
+
+        let broker = Rc::new(RefCell::new(broker));
+
+        let mut ota = MockOta::new();
+
+        let result = block_on(asynch::update(
+            &mut MockClient(broker.clone()),
+            &mut MockConnection(broker.clone(), None),
+            &mut ota,
+            padded_crc32(),
+            MODEL,
+            STATUS,
+            |_| true,
+        ));
+
+        assert_eq!(result.unwrap().unwrap().version, "1.1.0");
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+        assert!(broker
+            .borrow()
+            .published
+            .last()
+            .unwrap()
+            .1
+            .starts_with("{\"state\":\"completed\""));
+    }
+}
@@ -0,0 +1,1074 @@
+//! HTTP server handlers for uploading firmware updates from a browser or with e.g. `curl`.
+//!
+//! As there is no portable handler registry, `OtaServer` only provides the handlers,
+//! which are expected to be registered by the application as follows:
+//! - `OtaServer::firmware_handler` for `POST` requests on `FIRMWARE_URI`
+//! - `OtaServer::status_handler` for `GET` requests on `STATUS_URI`
+//! - `OtaServer::rollback_handler` for `POST` requests on `ROLLBACK_URI`
+
+use core::fmt::{self, Write as _};
+
+use crate::http::server::{Connection, Handler, HandlerResult, Request};
+use crate::http::Headers;
+use crate::io::{Read, Write};
+use crate::ota::{Ota, OtaUpdate};
+use crate::utils::io::take;
+use crate::utils::mutex::{Mutex, RawMutex};
+
+pub const FIRMWARE_URI: &str = "/ota/firmware";
+pub const STATUS_URI: &str = "/ota/status";
+pub const ROLLBACK_URI: &str = "/ota/rollback";
+
+const MAX_BOUNDARY_LEN: usize = 70;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Options {
+    /// The maximum accepted size of the uploaded firmware image, in bytes
+    pub max_size: u64,
+    /// Whether `ROLLBACK_URI` is enabled
+    pub rollback: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_size: 4 * 1024 * 1024,
+            rollback: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UploadState {
+    Idle,
+    Updating { written: u64, total: Option<u64> },
+    Completed { written: u64 },
+    Failed { written: u64 },
+}
+
+impl UploadState {
+    pub fn is_updating(&self) -> bool {
+        matches!(self, Self::Updating { .. })
+    }
+}
+
+#[derive(Debug)]
+enum UploadError<E> {
+    BadRequest(&'static str),
+    TooLarge,
+    Update(&'static str),
+    Io(E),
+}
+
+struct Inner<O, const N: usize> {
+    ota: O,
+    buf: [u8; N],
+}
+
+/// Shared state of the OTA handlers.
+///
+/// Only one upload can be in progress at a time; concurrent upload attempts are rejected with 409.
+/// `N` is the size of the buffer used for streaming the uploaded image into the update.
+pub struct OtaServer<R, O, const N: usize = 2048>
+where
+    R: RawMutex,
+{
+    inner: Mutex<R, Inner<O, N>>,
+    state: Mutex<R, UploadState>,
+    options: Options,
+}
+
+impl<R, O, const N: usize> OtaServer<R, O, N>
+where
+    R: RawMutex + Send + Sync,
+    O: Ota + Send,
+{
+    pub const fn new(ota: O, options: Options) -> Self {
+        Self {
+            inner: Mutex::new(Inner { ota, buf: [0; N] }),
+            state: Mutex::new(UploadState::Idle),
+            options,
+        }
+    }
+
+    pub fn state(&self) -> UploadState {
+        *self.state.lock()
+    }
+
+    pub fn firmware_handler(&self) -> FirmwareHandler<'_, R, O, N> {
+        FirmwareHandler(self)
+    }
+
+    pub fn status_handler(&self) -> StatusHandler<'_, R, O, N> {
+        StatusHandler(self)
+    }
+
+    pub fn rollback_handler(&self) -> RollbackHandler<'_, R, O, N> {
+        RollbackHandler(self)
+    }
+
+    fn handle_firmware<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let mut request = Request::wrap(connection);
+
+        let total = request.content_len();
+
+        {
+            let mut state = self.state.lock();
+
+            if state.is_updating() {
+                drop(state);
+
+                request
+                    .into_response(409, Some("Conflict"), &[])?
+                    .write_all(b"An update is already in progress")?;
+
+                return Ok(());
+            }
+
+            *state = UploadState::Updating { written: 0, total };
+        }
+
+        let result = self.try_upload(&mut request, total);
+
+        let mut state = self.state.lock();
+        let written = match *state {
+            UploadState::Updating { written, .. } => written,
+            _ => 0,
+        };
+
+        match result {
+            Ok(()) => {
+                *state = UploadState::Completed { written };
+                drop(state);
+
+                request.into_ok_response()?.write_all(b"Update completed")?;
+
+                Ok(())
+            }
+            Err(UploadError::BadRequest(message)) => {
+                *state = UploadState::Failed { written };
+                drop(state);
+
+                request
+                    .into_response(400, Some("Bad Request"), &[])?
+                    .write_all(message.as_bytes())?;
+
+                Ok(())
+            }
+            Err(UploadError::Update(message)) => {
+                *state = UploadState::Failed { written };
+                drop(state);
+
+                request
+                    .into_response(500, Some("Internal Server Error"), &[])?
+                    .write_all(message.as_bytes())?;
+
+                Ok(())
+            }
+            Err(UploadError::TooLarge) => {
+                *state = UploadState::Failed { written };
+                drop(state);
+
+                request
+                    .into_response(413, Some("Payload Too Large"), &[])?
+                    .write_all(b"Firmware image too large")?;
+
+                Ok(())
+            }
+            Err(UploadError::Io(e)) => {
+                *state = UploadState::Failed { written };
+
+                Err(e.into())
+            }
+        }
+    }
+
+    fn try_upload<C>(
+        &self,
+        request: &mut Request<&mut C>,
+        total: Option<u64>,
+    ) -> Result<(), UploadError<C::Error>>
+    where
+        C: Connection,
+    {
+        if total
+            .map(|total| total > self.options.max_size)
+            .unwrap_or(false)
+        {
+            return Err(UploadError::TooLarge);
+        }
+
+        let mut delimiter = heapless::Vec::<u8, { MAX_BOUNDARY_LEN + 4 }>::new();
+
+        if let Some(content_type) = request.content_type() {
+            if let Some(boundary) = multipart_boundary(content_type) {
+                if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LEN {
+                    return Err(UploadError::BadRequest("Invalid multipart boundary"));
+                }
+
+                delimiter.extend_from_slice(b"\r\n--").unwrap();
+                delimiter.extend_from_slice(boundary.as_bytes()).unwrap();
+            }
+        }
+
+        let mut inner = self.inner.lock();
+        let Inner { ota, buf } = &mut *inner;
+
+        if !delimiter.is_empty() && buf.len() <= delimiter.len() * 2 {
+            return Err(UploadError::BadRequest("Upload buffer too small"));
+        }
+
+        let mut update = ota
+            .initiate_update()
+            .map_err(|_| UploadError::Update("Cannot initiate the update"))?;
+
+        let mut written = 0;
+
+        // Do not read past the body, should the connection carry more data after it
+        let mut body = take(request, total.unwrap_or(u64::MAX));
+
+        let result = if delimiter.is_empty() {
+            self.copy_raw(&mut body, &mut update, buf, &mut written)
+                .and_then(|()| {
+                    // The connection was closed before the whole body was received
+                    if total.map(|total| written < total).unwrap_or(false) {
+                        Err(UploadError::BadRequest("Truncated body"))
+                    } else {
+                        Ok(())
+                    }
+                })
+        } else {
+            self.copy_multipart(&mut body, &mut update, buf, &delimiter, &mut written)
+        };
+
+        match result {
+            Ok(()) => update
+                .complete()
+                .map_err(|_| UploadError::Update("Cannot complete the update")),
+            Err(e) => {
+                let _ = update.abort();
+
+                Err(e)
+            }
+        }
+    }
+
+    fn copy_raw<B, U>(
+        &self,
+        body: &mut B,
+        update: &mut U,
+        buf: &mut [u8],
+        written: &mut u64,
+    ) -> Result<(), UploadError<B::Error>>
+    where
+        B: Read,
+        U: OtaUpdate,
+    {
+        loop {
+            let len = body.read(buf).map_err(UploadError::Io)?;
+            if len == 0 {
+                break Ok(());
+            }
+
+            self.write(update, &buf[..len], written)?;
+        }
+    }
+
+    fn copy_multipart<B, U>(
+        &self,
+        body: &mut B,
+        update: &mut U,
+        buf: &mut [u8],
+        delimiter: &[u8],
+        written: &mut u64,
+    ) -> Result<(), UploadError<B::Error>>
+    where
+        B: Read,
+        U: OtaUpdate,
+    {
+        // The first boundary is not preceded by CRLF
+        let first = &delimiter[2..];
+
+        let mut filled = 0;
+
+        let mut start = loop {
+            if filled == buf.len() {
+                return Err(UploadError::BadRequest("Multipart headers too large"));
+            }
+
+            let len = body.read(&mut buf[filled..]).map_err(UploadError::Io)?;
+            if len == 0 {
+                return Err(UploadError::BadRequest("Truncated multipart body"));
+            }
+
+            filled += len;
+
+            if let Some(offset) = find(&buf[..filled], first) {
+                if let Some(end) = find(&buf[offset..filled], b"\r\n\r\n") {
+                    break offset + end + 4;
+                }
+            }
+        };
+
+        loop {
+            if let Some(end) = find(&buf[start..filled], delimiter) {
+                return self.write(update, &buf[start..start + end], written);
+            }
+
+            let safe = (filled - start).saturating_sub(delimiter.len() - 1);
+
+            self.write(update, &buf[start..start + safe], written)?;
+
+            buf.copy_within(start + safe..filled, 0);
+            filled -= start + safe;
+            start = 0;
+
+            let len = body.read(&mut buf[filled..]).map_err(UploadError::Io)?;
+            if len == 0 {
+                return Err(UploadError::BadRequest("Truncated multipart body"));
+            }
+
+            filled += len;
+        }
+    }
+
+    fn write<U, E>(
+        &self,
+        update: &mut U,
+        data: &[u8],
+        written: &mut u64,
+    ) -> Result<(), UploadError<E>>
+    where
+        U: OtaUpdate,
+    {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        *written += data.len() as u64;
+
+        if *written > self.options.max_size {
+            return Err(UploadError::TooLarge);
+        }
+
+        update
+            .write_all(data)
+            .map_err(|_| UploadError::Update("Cannot write the update"))?;
+
+        if let UploadState::Updating {
+            written: progress, ..
+        } = &mut *self.state.lock()
+        {
+            *progress = *written;
+        }
+
+        Ok(())
+    }
+
+    fn handle_status<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let (state, slot) = {
+            let state = self.state.lock();
+
+            // Do not wait for an in-flight update to complete; checking the state under its lock
+            // ensures that an upload cannot start in the meantime and hold the OTA
+            let slot = if state.is_updating() {
+                None
+            } else {
+                Some(self.inner.lock().ota.get_running_slot()?)
+            };
+
+            (*state, slot)
+        };
+
+        let mut status = heapless::String::<256>::new();
+
+        match state {
+            UploadState::Idle => write!(status, "{{\"state\":\"idle\""),
+            UploadState::Updating { written, total } => {
+                write!(status, "{{\"state\":\"updating\",\"written\":{written}")?;
+
+                if let Some(total) = total {
+                    write!(status, ",\"total\":{total}")?;
+                }
+
+                Ok(())
+            }
+            UploadState::Completed { written } => {
+                write!(status, "{{\"state\":\"completed\",\"written\":{written}")
+            }
+            UploadState::Failed { written } => {
+                write!(status, "{{\"state\":\"failed\",\"written\":{written}")
+            }
+        }?;
+
+        if let Some(slot) = slot {
+            write!(status, ",\"slot\":")?;
+            write_json_str(&mut status, &slot.label)?;
+
+            if let Some(firmware) = slot.firmware {
+                write!(status, ",\"version\":")?;
+                write_json_str(&mut status, &firmware.version)?;
+            }
+        }
+
+        write!(status, "}}")?;
+
+        Request::wrap(connection)
+            .into_response(200, Some("OK"), &[("Content-Type", "application/json")])?
+            .write_all(status.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn handle_rollback<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let request = Request::wrap(connection);
+
+        if !self.options.rollback {
+            request
+                .into_response(404, Some("Not Found"), &[])?
+                .write_all(b"Rollback is not enabled")?;
+
+            return Ok(());
+        }
+
+        let state = self.state.lock();
+
+        if state.is_updating() {
+            drop(state);
+
+            request
+                .into_response(409, Some("Conflict"), &[])?
+                .write_all(b"An update is in progress")?;
+
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock();
+
+        let mut response = request.into_ok_response()?;
+        response.write_all(b"Rolling back")?;
+        response.flush()?;
+
+        Err(inner.ota.mark_running_slot_invalid_and_reboot().into())
+    }
+}
+
+pub struct FirmwareHandler<'a, R, O, const N: usize>(&'a OtaServer<R, O, N>)
+where
+    R: RawMutex;
+
+impl<R, O, C, const N: usize> Handler<C> for FirmwareHandler<'_, R, O, N>
+where
+    R: RawMutex + Send + Sync,
+    O: Ota + Send,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_firmware(connection)
+    }
+}
+
+pub struct StatusHandler<'a, R, O, const N: usize>(&'a OtaServer<R, O, N>)
+where
+    R: RawMutex;
+
+impl<R, O, C, const N: usize> Handler<C> for StatusHandler<'_, R, O, N>
+where
+    R: RawMutex + Send + Sync,
+    O: Ota + Send,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_status(connection)
+    }
+}
+
+pub struct RollbackHandler<'a, R, O, const N: usize>(&'a OtaServer<R, O, N>)
+where
+    R: RawMutex;
+
+impl<R, O, C, const N: usize> Handler<C> for RollbackHandler<'_, R, O, N>
+where
+    R: RawMutex + Send + Sync,
+    O: Ota + Send,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_rollback(connection)
+    }
+}
+
+fn multipart_boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (name, value) = param.trim().split_once('=')?;
+
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn write_json_str(w: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    w.write_char('"')?;
+
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            c if c.is_control() => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+
+    w.write_char('"')
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::http::server::ErrorType;
+    use crate::http::{Method, Query};
+    use crate::io::ErrorKind;
+    use crate::test_ota::MockOta;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    struct Head {
+        method: Method,
+        uri: &'static str,
+        headers: Vec<(&'static str, String)>,
+    }
+
+    impl Query for Head {
+        fn uri(&self) -> &str {
+            self.uri
+        }
+
+        fn method(&self) -> Method {
+            self.method
+        }
+    }
+
+    impl Headers for Head {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// A request body, delivered in chunks of up to `chunk` bytes
+    struct Body<'a> {
+        data: Vec<u8>,
+        read: usize,
+        chunk: usize,
+        /// Fail the reads (as if the client went away) once that many bytes were read
+        fail_at: Option<usize>,
+        /// Called before each read with the number of bytes read so far
+        on_read: Option<Box<dyn FnMut(usize) + 'a>>,
+    }
+
+    impl ErrorType for Body<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for Body<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if let Some(on_read) = &mut self.on_read {
+                on_read(self.read);
+            }
+
+            if self.fail_at.map(|at| self.read >= at).unwrap_or(false) {
+                return Err(ErrorKind::ConnectionReset);
+            }
+
+            let len = buf
+                .len()
+                .min(self.chunk)
+                .min(self.data.len() - self.read)
+                .min(self.fail_at.unwrap_or(usize::MAX) - self.read);
+
+            buf[..len].copy_from_slice(&self.data[self.read..self.read + len]);
+            self.read += len;
+
+            Ok(len)
+        }
+    }
+
+    struct MockConnection<'a> {
+        head: Head,
+        body: Body<'a>,
+        status: Option<u16>,
+        response: Vec<u8>,
+    }
+
+    impl<'a> MockConnection<'a> {
+        fn new(method: Method, uri: &'static str, body: &[u8]) -> Self {
+            Self {
+                head: Head {
+                    method,
+                    uri,
+                    headers: Vec::new(),
+                },
+                body: Body {
+                    data: body.to_vec(),
+                    read: 0,
+                    chunk: 100,
+                    fail_at: None,
+                    on_read: None,
+                },
+                status: None,
+                response: Vec::new(),
+            }
+        }
+
+        fn upload(body: &[u8]) -> Self {
+            Self::new(Method::Post, FIRMWARE_URI, body).header("Content-Length", body.len())
+        }
+
+        fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+            self.head.headers.push((name, value.to_string()));
+            self
+        }
+
+        fn response(&self) -> (Option<u16>, &str) {
+            (self.status, core::str::from_utf8(&self.response).unwrap())
+        }
+    }
+
+    impl Query for MockConnection<'_> {
+        fn uri(&self) -> &str {
+            self.head.uri()
+        }
+
+        fn method(&self) -> Method {
+            self.head.method()
+        }
+    }
+
+    impl Headers for MockConnection<'_> {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.head.header(name)
+        }
+    }
+
+    impl ErrorType for MockConnection<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for MockConnection<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.body.read(buf)
+        }
+    }
+
+    impl Write for MockConnection<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            assert!(self.status.is_some(), "Response not initiated");
+
+            self.response.extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl<'a> Connection for MockConnection<'a> {
+        type Headers = Head;
+
+        type Read = Body<'a>;
+
+        type RawConnectionError = ErrorKind;
+
+        type RawConnection = Self;
+
+        fn split(&mut self) -> (&Self::Headers, &mut Self::Read) {
+            (&self.head, &mut self.body)
+        }
+
+        fn initiate_response(
+            &mut self,
+            status: u16,
+            _message: Option<&str>,
+            _headers: &[(&str, &str)],
+        ) -> Result<(), Self::Error> {
+            assert!(self.status.is_none(), "Response already initiated");
+
+            self.status = Some(status);
+
+            Ok(())
+        }
+
+        fn is_response_initiated(&self) -> bool {
+            self.status.is_some()
+        }
+
+        fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
+            Err(ErrorKind::Unsupported)
+        }
+    }
+
+    type Server<'a> = OtaServer<StdRawMutex, &'a mut MockOta>;
+
+    fn ok(result: HandlerResult) {
+        if let Err(e) = result {
+            panic!("Handler failed: {}", e);
+        }
+    }
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn raw_upload() {
+        let mut ota = MockOta::new();
+        let image = image(5000);
+
+        {
+            let server = Server::new(&mut ota, Default::default());
+            let mut connection = MockConnection::upload(&image);
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(connection.response(), (Some(200), "Update completed"));
+            assert_eq!(server.state(), UploadState::Completed { written: 5000 });
+        }
+
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+        assert!(ota.activated);
+    }
+
+    #[test]
+    fn multipart_upload() {
+        let mut ota = MockOta::new();
+
+        // Contains a prefix of the delimiter, which must not be mistaken for it
+        let mut image = image(600);
+        image[300..306].copy_from_slice(b"\r\n--Xy");
+
+        let mut body = b"--XyZ\r\nContent-Disposition: form-data; name=\"firmware\"; filename=\"fw.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n".to_vec();
+        body.extend_from_slice(&image);
+        body.extend_from_slice(b"\r\n--XyZ--\r\n");
+
+        {
+            let server = OtaServer::<StdRawMutex, _, 256>::new(&mut ota, Default::default());
+
+            let mut connection = MockConnection::upload(&body)
+                .header("Content-Type", "multipart/form-data; boundary=\"XyZ\"");
+            connection.body.chunk = 7;
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(connection.response(), (Some(200), "Update completed"));
+            assert_eq!(server.state(), UploadState::Completed { written: 600 });
+        }
+
+        assert_eq!(ota.completed.as_deref(), Some(&image[..]));
+    }
+
+    #[test]
+    fn multipart_upload_truncated() {
+        let mut ota = MockOta::new();
+
+        let mut body = b"--XyZ\r\n\r\n".to_vec();
+        body.extend_from_slice(&image(600));
+
+        {
+            let server = Server::new(&mut ota, Default::default());
+
+            let mut connection = MockConnection::upload(&body)
+                .header("Content-Type", "multipart/form-data; boundary=XyZ");
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(
+                connection.response(),
+                (Some(400), "Truncated multipart body")
+            );
+            assert!(matches!(server.state(), UploadState::Failed { .. }));
+        }
+
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn upload_aborted_mid_stream() {
+        let mut ota = MockOta::new();
+        let image = image(5000);
+
+        {
+            let server = Server::new(&mut ota, Default::default());
+
+            let mut connection = MockConnection::upload(&image);
+            connection.body.fail_at = Some(2000);
+
+            assert!(server.firmware_handler().handle(&mut connection).is_err());
+            assert_eq!(connection.response(), (None, ""));
+            assert_eq!(server.state(), UploadState::Failed { written: 2000 });
+        }
+
+        assert!(ota.completed.is_none());
+        assert!(ota.image.is_empty());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn upload_closed_mid_stream() {
+        let mut ota = MockOta::new();
+        let image = image(5000);
+
+        {
+            let server = Server::new(&mut ota, Default::default());
+
+            // The client declares more data than it sends
+            let mut connection = MockConnection::new(Method::Post, FIRMWARE_URI, &image[..3000])
+                .header("Content-Length", image.len());
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(connection.response(), (Some(400), "Truncated body"));
+            assert_eq!(server.state(), UploadState::Failed { written: 3000 });
+        }
+
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn upload_too_large() {
+        let options = Options {
+            max_size: 1000,
+            ..Default::default()
+        };
+
+        let mut ota = MockOta::new();
+
+        {
+            let server = Server::new(&mut ota, options.clone());
+            let mut connection = MockConnection::upload(&image(1001));
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(
+                connection.response(),
+                (Some(413), "Firmware image too large")
+            );
+        }
+
+        // Rejected upfront
+        assert_eq!(ota.aborts, 0);
+
+        {
+            let server = Server::new(&mut ota, options);
+
+            // Without a content length, the limit is enforced while streaming
+            let mut connection = MockConnection::new(Method::Post, FIRMWARE_URI, &image(1500));
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(
+                connection.response(),
+                (Some(413), "Firmware image too large")
+            );
+            assert_eq!(server.state(), UploadState::Failed { written: 1000 });
+        }
+
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn upload_write_failure() {
+        let mut ota = MockOta {
+            fail_at: Some(1000),
+            ..Default::default()
+        };
+
+        {
+            let server = Server::new(&mut ota, Default::default());
+            let mut connection = MockConnection::upload(&image(5000));
+
+            ok(server.firmware_handler().handle(&mut connection));
+
+            assert_eq!(
+                connection.response(),
+                (Some(500), "Cannot write the update")
+            );
+            assert!(matches!(server.state(), UploadState::Failed { .. }));
+        }
+
+        assert!(ota.completed.is_none());
+        assert_eq!(ota.aborts, 1);
+    }
+
+    #[test]
+    fn concurrent_requests_during_upload() {
+        let mut ota = MockOta::new();
+        let image = image(1000);
+
+        let options = Options {
+            rollback: true,
+            ..Default::default()
+        };
+
+        let server = Server::new(&mut ota, options);
+        let checked = core::cell::Cell::new(false);
+
+        let mut connection = MockConnection::upload(&image);
+        connection.body.on_read = Some(Box::new(|read| {
+            if read != 500 {
+                return;
+            }
+
+            let mut upload = MockConnection::upload(b"other");
+
+            ok(server.firmware_handler().handle(&mut upload));
+            assert_eq!(
+                upload.response(),
+                (Some(409), "An update is already in progress")
+            );
+
+            // The status does not wait for the upload to complete
+            let mut status = MockConnection::new(Method::Get, STATUS_URI, b"");
+
+            ok(server.status_handler().handle(&mut status));
+            assert_eq!(
+                status.response(),
+                (
+                    Some(200),
+                    "{\"state\":\"updating\",\"written\":500,\"total\":1000}"
+                )
+            );
+
+            let mut rollback = MockConnection::new(Method::Post, ROLLBACK_URI, b"");
+
+            ok(server.rollback_handler().handle(&mut rollback));
+            assert_eq!(rollback.response(), (Some(409), "An update is in progress"));
+
+            checked.set(true);
+        }));
+
+        ok(server.firmware_handler().handle(&mut connection));
+
+        assert!(checked.get());
+        assert_eq!(connection.response(), (Some(200), "Update completed"));
+
+        // Another upload is accepted once the previous one is over
+        let mut connection = MockConnection::upload(&image);
+
+        ok(server.firmware_handler().handle(&mut connection));
+
+        assert_eq!(connection.response(), (Some(200), "Update completed"));
+    }
+
+    #[test]
+    fn status() {
+        let mut ota = MockOta::new();
+        let server = Server::new(&mut ota, Default::default());
+
+        let mut connection = MockConnection::new(Method::Get, STATUS_URI, b"");
+
+        ok(server.status_handler().handle(&mut connection));
+
+        assert_eq!(
+            connection.response(),
+            (
+                Some(200),
+                "{\"state\":\"idle\",\"slot\":\"running\",\"version\":\"running\"}"
+            )
+        );
+
+        ok(server
+            .firmware_handler()
+            .handle(&mut MockConnection::upload(&image(10))));
+
+        let mut connection = MockConnection::new(Method::Get, STATUS_URI, b"");
+
+        ok(server.status_handler().handle(&mut connection));
+
+        assert_eq!(
+            connection.response(),
+            (
+                Some(200),
+                "{\"state\":\"completed\",\"written\":10,\"slot\":\"running\",\"version\":\"running\"}"
+            )
+        );
+    }
+
+    #[test]
+    fn rollback() {
+        let mut ota = MockOta::new();
+
+        let server = Server::new(&mut ota, Default::default());
+
+        let mut connection = MockConnection::new(Method::Post, ROLLBACK_URI, b"");
+
+        ok(server.rollback_handler().handle(&mut connection));
+
+        assert_eq!(
+            connection.response(),
+            (Some(404), "Rollback is not enabled")
+        );
+
+        let server = Server::new(
+            &mut ota,
+            Options {
+                rollback: true,
+                ..Default::default()
+            },
+        );
+
+        // `MockOta` cannot reboot, so the rollback fails after the response is sent
+        let mut connection = MockConnection::new(Method::Post, ROLLBACK_URI, b"");
+        assert!(server.rollback_handler().handle(&mut connection).is_err());
+
+        assert_eq!(connection.response(), (Some(200), "Rolling back"));
+    }
+
+    #[test]
+    fn boundaries() {
+        assert_eq!(
+            multipart_boundary("multipart/form-data; boundary=abc"),
+            Some("abc")
+        );
+        assert_eq!(
+            multipart_boundary("Multipart/Form-Data;charset=utf-8; Boundary=\"a b\""),
+            Some("a b")
+        );
+        assert_eq!(multipart_boundary("application/octet-stream"), None);
+        assert_eq!(multipart_boundary("multipart/form-data"), None);
+    }
+}
@@ -0,0 +1,876 @@
+//! Helpers for calling and serving REST APIs with JSON bodies
+
+use core::iter::once;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::json_io::{escaped, Piece};
+
+pub mod auth;
+pub mod ota;
+
+/// An RFC 7807 problem details object, for reporting errors as `application/problem+json`
+///
+/// Serving one with `server::send_problem` always responds with its `status`,
+/// so that the status of the response and the one in its body cannot disagree.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Problem<'a> {
+    /// The URI identifying the type of the problem; `about:blank` when there is nothing more to it than `status`
+    #[serde(rename = "type")]
+    pub type_uri: &'a str,
+    pub title: &'a str,
+    pub status: u16,
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<&'a str>,
+    /// The URI identifying this occurrence of the problem
+    #[serde(borrow, default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<&'a str>,
+}
+
+impl<'a> Problem<'a> {
+    pub const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    pub const fn new(status: u16, title: &'a str) -> Self {
+        Self {
+            type_uri: "about:blank",
+            title,
+            status,
+            detail: None,
+            instance: None,
+        }
+    }
+
+    pub const fn with_type(mut self, type_uri: &'a str) -> Self {
+        self.type_uri = type_uri;
+        self
+    }
+
+    pub const fn with_detail(mut self, detail: &'a str) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+
+    pub const fn with_instance(mut self, instance: &'a str) -> Self {
+        self.instance = Some(instance);
+        self
+    }
+
+    /// The length of the JSON form of the problem, in bytes
+    pub fn json_len(&self) -> usize {
+        self.json().map(|piece| piece.bytes().len()).sum()
+    }
+
+    /// The pieces of the JSON form of the problem, to be written in turn
+    fn json(&self) -> impl Iterator<Item = Piece<'_>> {
+        let field = |name, value: Option<&'a str>| {
+            value.into_iter().flat_map(move |value| {
+                once(Piece::Raw(name))
+                    .chain(escaped(value))
+                    .chain(once(Piece::Raw(b"\"")))
+            })
+        };
+
+        let mut status = [0; 6];
+        let mut len = 0;
+        let mut divisor = 10000;
+
+        while divisor > 0 {
+            let digit = (self.status / divisor % 10) as u8;
+
+            if len > 0 || digit > 0 || divisor == 1 {
+                status[len] = b'0' + digit;
+                len += 1;
+            }
+
+            divisor /= 10;
+        }
+
+        once(Piece::Raw(b"{"))
+            .chain(field(b"\"type\":\"", Some(self.type_uri)))
+            .chain(field(b",\"title\":\"", Some(self.title)))
+            .chain(once(Piece::Raw(b",\"status\":")))
+            .chain(once(Piece::Escaped(status, len)))
+            .chain(field(b",\"detail\":\"", self.detail))
+            .chain(field(b",\"instance\":\"", self.instance))
+            .chain(once(Piece::Raw(b"}")))
+    }
+}
+
+pub mod server {
+    //! Sending `Problem`s as the error responses of REST handlers
+
+    use crate::http::headers;
+    use crate::http::server::{Connection, Request};
+    use crate::io::Write;
+
+    use super::Problem;
+
+    /// Responds to `request` with `problem`, with its status and as `application/problem+json`
+    pub fn send_problem<C>(request: Request<C>, problem: &Problem<'_>) -> Result<(), C::Error>
+    where
+        C: Connection,
+    {
+        let mut len_buf = headers::ContentLenParseBuf::new();
+
+        let mut response = request.into_response(
+            problem.status,
+            None,
+            &[
+                headers::content_type(Problem::CONTENT_TYPE),
+                headers::content_len(problem.json_len() as u64, &mut len_buf),
+                headers::cache_control_no_cache(),
+            ],
+        )?;
+
+        for piece in problem.json() {
+            response.write_all(piece.bytes())?;
+        }
+
+        response.flush()
+    }
+
+    #[cfg(feature = "nightly")]
+    pub mod asynch {
+        use crate::http::headers;
+        use crate::http::server::asynch::{Connection, Request};
+        use crate::io::asynch::Write;
+
+        pub use super::super::Problem;
+
+        pub async fn send_problem<C>(
+            request: Request<C>,
+            problem: &Problem<'_>,
+        ) -> Result<(), C::Error>
+        where
+            C: Connection,
+        {
+            let mut len_buf = headers::ContentLenParseBuf::new();
+
+            let mut response = request
+                .into_response(
+                    problem.status,
+                    None,
+                    &[
+                        headers::content_type(Problem::CONTENT_TYPE),
+                        headers::content_len(problem.json_len() as u64, &mut len_buf),
+                        headers::cache_control_no_cache(),
+                    ],
+                )
+                .await?;
+
+            for piece in problem.json() {
+                response.write_all(piece.bytes()).await?;
+            }
+
+            response.flush().await
+        }
+    }
+}
+
+pub mod client {
+    //! Typed endpoints, which take care of formatting the URI, (de)serializing the bodies and checking the status
+    //!
+    //! An `Endpoint` describes a call of a REST API, and `call` makes it over an `http::client::Client`:
+    //!
+    //! `const GET_DEVICE: Endpoint<(), Device> = Endpoint::new(Method::Get, "/devices/{id}");`
+    //!
+    //! `let device = call(&mut client, &serde, "https://example.com/api", &GET_DEVICE, &[("id", id)], &(), &mut buf)?;`
+    //!
+    //! The bodies are (de)serialized with a `SerDe` producing JSON, and everything (the URI as well)
+    //! is formatted into a single scratch buffer, so no allocations are needed.
+
+    use core::convert::Infallible;
+    use core::fmt::{self, Debug};
+    use core::marker::PhantomData;
+
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use crate::http::client::{Client, Connection};
+    use crate::http::{headers, Method};
+    use crate::io::{Read, Write};
+    use crate::storage::SerDe;
+    use crate::utils::json_io::{self, JsonReadError, Limits};
+
+    const ACCEPT: (&str, &str) = ("Accept", "application/json");
+
+    /// The length of the body kept by `RestError::Status`
+    pub const BODY_SNIPPET_LEN: usize = 64;
+
+    /// A call of a REST API, taking a `Req` body and returning a `Resp` body
+    pub struct Endpoint<Req, Resp> {
+        pub method: Method,
+        /// The path of the endpoint, relative to the base URI of the API; `{param}` placeholders are substituted
+        pub path: &'static str,
+        /// The statuses of a successful call, whose body is deserialized as `Resp`
+        pub success: &'static [u16],
+        /// The limits the `Resp` body is read within
+        pub limits: Limits,
+        _types: PhantomData<fn(&Req) -> Resp>,
+    }
+
+    impl<Req, Resp> Endpoint<Req, Resp> {
+        /// An endpoint succeeding with the 200 status
+        pub const fn new(method: Method, path: &'static str) -> Self {
+            Self {
+                method,
+                path,
+                success: &[200],
+                limits: Limits::new(),
+                _types: PhantomData,
+            }
+        }
+
+        pub const fn with_success(mut self, success: &'static [u16]) -> Self {
+            self.success = success;
+            self
+        }
+
+        pub const fn with_limits(mut self, limits: Limits) -> Self {
+            self.limits = limits;
+            self
+        }
+
+        /// Whether a request body is sent, which is the case for the `POST`, `PUT` and `PATCH` methods
+        pub fn has_body(&self) -> bool {
+            matches!(self.method, Method::Post | Method::Put | Method::Patch)
+        }
+    }
+
+    #[derive(Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum RestError<E, S> {
+        Io(E),
+        /// The URI does not fit the buffer, or the path has a `{param}` without a value
+        Uri,
+        /// The request body could not be serialized, or does not fit the buffer
+        Serialize(S),
+        /// The call did not succeed; the body is truncated to `BODY_SNIPPET_LEN` bytes
+        Status {
+            status: u16,
+            body: heapless::Vec<u8, BODY_SNIPPET_LEN>,
+        },
+        /// The response body could not be deserialized as `Resp`, or exceeded the limits of the endpoint
+        Deserialize(JsonReadError<Infallible, S>),
+    }
+
+    impl<E, S> RestError<E, S> {
+        fn from_read(e: JsonReadError<E, S>) -> Self {
+            match e {
+                JsonReadError::Read(e) => Self::Io(e),
+                JsonReadError::TooLarge => Self::Deserialize(JsonReadError::TooLarge),
+                JsonReadError::TooDeep => Self::Deserialize(JsonReadError::TooDeep),
+                JsonReadError::StringTooLong => Self::Deserialize(JsonReadError::StringTooLong),
+                JsonReadError::Malformed => Self::Deserialize(JsonReadError::Malformed),
+                JsonReadError::Deserialize(e) => Self::Deserialize(JsonReadError::Deserialize(e)),
+            }
+        }
+    }
+
+    impl<E: Debug, S: Debug> fmt::Display for RestError<E, S> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{self:?}")
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl<E: Debug, S: Debug> std::error::Error for RestError<E, S> {}
+
+    /// Calls `endpoint` of the API at `base` (e.g. `https://example.com/api`), substituting `params` in its path
+    ///
+    /// `buf` holds the URI for the whole call, and the bodies following it in turn, so it needs to fit
+    /// the URI plus the largest of the request and response bodies. The request body is only sent
+    /// if the endpoint `has_body`.
+    pub fn call<C, S, Req, Resp>(
+        client: &mut Client<C>,
+        serde: S,
+        base: &str,
+        endpoint: &Endpoint<Req, Resp>,
+        params: &[(&str, &str)],
+        request: &Req,
+        buf: &mut [u8],
+    ) -> Result<Resp, RestError<C::Error, S::Error>>
+    where
+        C: Connection,
+        S: SerDe,
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let (uri, buf) = format_uri(buf, base, endpoint.path, params)?;
+
+        let mut len_buf = headers::ContentLenParseBuf::new();
+
+        let body = if endpoint.has_body() {
+            Some(
+                serde
+                    .serialize(buf, request)
+                    .map_err(RestError::Serialize)?,
+            )
+        } else {
+            None
+        };
+
+        let body_headers;
+        let headers: &[_] = if let Some(body) = body {
+            body_headers = [
+                headers::content_type("application/json"),
+                headers::content_len(body.len() as u64, &mut len_buf),
+                ACCEPT,
+            ];
+
+            &body_headers
+        } else {
+            &[ACCEPT]
+        };
+
+        let mut request = client
+            .request(endpoint.method, uri, headers)
+            .map_err(RestError::Io)?;
+
+        if let Some(body) = body {
+            request.write_all(body).map_err(RestError::Io)?;
+        }
+
+        let mut response = request.submit().map_err(RestError::Io)?;
+
+        let status = response.status();
+
+        if endpoint.success.contains(&status) {
+            json_io::read(&mut response, &serde, buf, &endpoint.limits)
+                .map_err(RestError::from_read)
+        } else {
+            let mut body = [0; BODY_SNIPPET_LEN];
+            let len = read_snippet(&mut response, &mut body).map_err(RestError::Io)?;
+
+            Err(RestError::Status {
+                status,
+                body: heapless::Vec::from_slice(&body[..len]).unwrap(),
+            })
+        }
+    }
+
+    fn read_snippet<R>(mut read: R, buf: &mut [u8]) -> Result<usize, R::Error>
+    where
+        R: Read,
+    {
+        let mut len = 0;
+
+        while len < buf.len() {
+            let size = read.read(&mut buf[len..])?;
+            if size == 0 {
+                break;
+            }
+
+            len += size;
+        }
+
+        Ok(len)
+    }
+
+    /// Formats the URI into the start of `buf`, returning it and the rest of `buf`
+    fn format_uri<'a, E, S>(
+        buf: &'a mut [u8],
+        base: &str,
+        path: &str,
+        params: &[(&str, &str)],
+    ) -> Result<(&'a str, &'a mut [u8]), RestError<E, S>> {
+        let mut len = 0;
+
+        let mut push = |buf: &mut [u8], bytes: &[u8]| {
+            let end = len + bytes.len();
+
+            buf.get_mut(len..end)
+                .ok_or(RestError::Uri)?
+                .copy_from_slice(bytes);
+            len = end;
+
+            Ok(())
+        };
+
+        push(buf, base.trim_end_matches('/').as_bytes())?;
+
+        let mut rest = path;
+
+        while let Some(start) = rest.find('{') {
+            push(buf, &rest.as_bytes()[..start])?;
+
+            let end = rest[start..].find('}').ok_or(RestError::Uri)? + start;
+            let name = &rest[start + 1..end];
+
+            let (_, value) = params
+                .iter()
+                .find(|(param, _)| *param == name)
+                .ok_or(RestError::Uri)?;
+
+            for byte in value.bytes() {
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    push(buf, &[byte])?;
+                } else {
+                    const HEX: &[u8] = b"0123456789ABCDEF";
+
+                    push(
+                        buf,
+                        &[b'%', HEX[(byte >> 4) as usize], HEX[(byte & 0x0f) as usize]],
+                    )?;
+                }
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        push(buf, rest.as_bytes())?;
+
+        let (uri, buf) = buf.split_at_mut(len);
+
+        // Only ASCII was pushed after the (UTF-8) base and path pieces
+        Ok((core::str::from_utf8(uri).unwrap(), buf))
+    }
+
+    #[cfg(feature = "nightly")]
+    pub mod asynch {
+        use serde::de::DeserializeOwned;
+        use serde::Serialize;
+
+        use crate::http::client::asynch::{Client, Connection};
+        use crate::http::headers;
+        use crate::io::asynch::{Read, Write};
+        use crate::storage::SerDe;
+        use crate::utils::json_io;
+
+        use super::{format_uri, ACCEPT};
+
+        pub use super::{Endpoint, RestError, BODY_SNIPPET_LEN};
+
+        pub async fn call<C, S, Req, Resp>(
+            client: &mut Client<C>,
+            serde: S,
+            base: &str,
+            endpoint: &Endpoint<Req, Resp>,
+            params: &[(&str, &str)],
+            request: &Req,
+            buf: &mut [u8],
+        ) -> Result<Resp, RestError<C::Error, S::Error>>
+        where
+            C: Connection,
+            S: SerDe,
+            Req: Serialize,
+            Resp: DeserializeOwned,
+        {
+            let (uri, buf) = format_uri(buf, base, endpoint.path, params)?;
+
+            let mut len_buf = headers::ContentLenParseBuf::new();
+
+            let body = if endpoint.has_body() {
+                Some(
+                    serde
+                        .serialize(buf, request)
+                        .map_err(RestError::Serialize)?,
+                )
+            } else {
+                None
+            };
+
+            let body_headers;
+            let headers: &[_] = if let Some(body) = body {
+                body_headers = [
+                    headers::content_type("application/json"),
+                    headers::content_len(body.len() as u64, &mut len_buf),
+                    ACCEPT,
+                ];
+
+                &body_headers
+            } else {
+                &[ACCEPT]
+            };
+
+            let mut request = client
+                .request(endpoint.method, uri, headers)
+                .await
+                .map_err(RestError::Io)?;
+
+            if let Some(body) = body {
+                request.write_all(body).await.map_err(RestError::Io)?;
+            }
+
+            let mut response = request.submit().await.map_err(RestError::Io)?;
+
+            let status = response.status();
+
+            if endpoint.success.contains(&status) {
+                json_io::asynch::read(&mut response, &serde, buf, &endpoint.limits)
+                    .await
+                    .map_err(RestError::from_read)
+            } else {
+                let mut body = [0; BODY_SNIPPET_LEN];
+                let len = read_snippet(&mut response, &mut body)
+                    .await
+                    .map_err(RestError::Io)?;
+
+                Err(RestError::Status {
+                    status,
+                    body: heapless::Vec::from_slice(&body[..len]).unwrap(),
+                })
+            }
+        }
+
+        async fn read_snippet<R>(mut read: R, buf: &mut [u8]) -> Result<usize, R::Error>
+        where
+            R: Read,
+        {
+            let mut len = 0;
+
+            while len < buf.len() {
+                let size = read.read(&mut buf[len..]).await?;
+                if size == 0 {
+                    break;
+                }
+
+                len += size;
+            }
+
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::http::server::Request;
+    use crate::http::Method;
+    use crate::io::ErrorKind;
+    use crate::test_http::{MockRequest, MockResponse, MockServer, Recorded};
+    use crate::test_json::JsonSerDe;
+    use crate::utils::json_io::{JsonReadError, Limits};
+
+    use super::client::{call, Endpoint, RestError, BODY_SNIPPET_LEN};
+    use super::server::send_problem;
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Device {
+        id: String,
+        name: String,
+        online: bool,
+    }
+
+    fn device() -> Device {
+        Device {
+            id: "a/b c".into(),
+            name: "Sensor".into(),
+            online: true,
+        }
+    }
+
+    const BASE: &str = "https://example.com/api/";
+
+    const GET_DEVICE: Endpoint<(), Device> = Endpoint::new(Method::Get, "/devices/{id}");
+    const PUT_DEVICE: Endpoint<Device, Device> =
+        Endpoint::new(Method::Put, "/devices/{id}").with_success(&[200, 201]);
+
+    type Result<T> = core::result::Result<T, RestError<ErrorKind, serde::de::value::Error>>;
+
+    /// Calls `endpoint` of a server answering with `response`, returning the result and the request
+    fn call_with<Req, Resp>(
+        endpoint: &Endpoint<Req, Resp>,
+        params: &[(&str, &str)],
+        request: &Req,
+        buf: &mut [u8],
+        response: impl Fn() -> MockResponse,
+    ) -> (Result<Resp>, Vec<Recorded>)
+    where
+        Req: Serialize,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let mut client = MockServer::new(|_: &Recorded| response());
+
+        let result = call(&mut client, JsonSerDe, BASE, endpoint, params, request, buf);
+
+        (result, client.connection().requests.clone())
+    }
+
+    fn json() -> MockResponse {
+        MockResponse::new(200, &crate::test_json::to_vec(&device()))
+            .header("Content-Type", "application/json")
+    }
+
+    #[test]
+    fn call_get() {
+        let (result, requests) =
+            call_with(&GET_DEVICE, &[("id", "a/b c")], &(), &mut [0; 256], json);
+
+        assert_eq!(result.unwrap(), device());
+
+        let [request] = &requests[..] else {
+            panic!("{:?}", requests);
+        };
+
+        // The parameters are percent-encoded, and there is no body
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.uri, "https://example.com/api/devices/a%2Fb%20c");
+        assert_eq!(request.header("Accept"), Some("application/json"));
+        assert_eq!(request.header("Content-Length"), None);
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn call_put() {
+        let created = || MockResponse::new(201, &crate::test_json::to_vec(&device()));
+
+        let (result, requests) = call_with(
+            &PUT_DEVICE,
+            &[("id", "1")],
+            &device(),
+            &mut [0; 256],
+            created,
+        );
+
+        assert_eq!(result.unwrap(), device());
+
+        let body = crate::test_json::to_vec(&device());
+        let len = body.len().to_string();
+
+        assert_eq!(requests[0].method, Method::Put);
+        assert_eq!(requests[0].uri, "https://example.com/api/devices/1");
+        assert_eq!(requests[0].header("Content-Type"), Some("application/json"));
+        assert_eq!(requests[0].header("Content-Length"), Some(len.as_str()));
+        assert_eq!(requests[0].body, body);
+
+        // The request body does not fit the buffer after the URI
+        let (result, requests) = call_with(
+            &PUT_DEVICE,
+            &[("id", "1")],
+            &device(),
+            &mut [0; 64],
+            created,
+        );
+
+        assert!(matches!(result, Err(RestError::Serialize(_))));
+        assert!(requests.is_empty());
+    }
+
+    #[test]
+    fn call_uri() {
+        for params in [&[][..], &[("name", "1")]] {
+            let (result, requests) = call_with(&GET_DEVICE, params, &(), &mut [0; 256], json);
+
+            assert!(matches!(result, Err(RestError::Uri)));
+            assert!(requests.is_empty());
+        }
+
+        // Does not fit the buffer
+        let (result, _) = call_with(&GET_DEVICE, &[("id", "1")], &(), &mut [0; 16], json);
+        assert!(matches!(result, Err(RestError::Uri)));
+
+        let unclosed = Endpoint::<(), Device>::new(Method::Get, "/devices/{id");
+        let (result, _) = call_with(&unclosed, &[("id", "1")], &(), &mut [0; 256], json);
+        assert!(matches!(result, Err(RestError::Uri)));
+    }
+
+    #[test]
+    fn call_status() {
+        let body = [b'x'; 100];
+
+        let (result, _) = call_with(&GET_DEVICE, &[("id", "1")], &(), &mut [0; 256], || {
+            MockResponse::new(404, &body)
+        });
+
+        let Err(RestError::Status { status, body }) = result else {
+            panic!("{:?}", result);
+        };
+
+        // Truncated
+        assert_eq!(status, 404);
+        assert_eq!(&body[..], &[b'x'; BODY_SNIPPET_LEN][..]);
+
+        // Not in the success statuses of the endpoint
+        let (result, _) = call_with(&GET_DEVICE, &[("id", "1")], &(), &mut [0; 256], || {
+            MockResponse::new(201, b"{}")
+        });
+        assert!(matches!(result, Err(RestError::Status { status: 201, .. })));
+    }
+
+    #[test]
+    fn call_deserialize() {
+        let (result, _) = call_with(&GET_DEVICE, &[("id", "1")], &(), &mut [0; 256], || {
+            MockResponse::new(200, b"{\"id\": \"1\"")
+        });
+        assert!(matches!(
+            result,
+            Err(RestError::Deserialize(JsonReadError::Malformed))
+        ));
+
+        let (result, _) = call_with(&GET_DEVICE, &[("id", "1")], &(), &mut [0; 256], || {
+            MockResponse::new(200, b"{\"id\": \"1\"}")
+        });
+        assert!(matches!(
+            result,
+            Err(RestError::Deserialize(JsonReadError::Deserialize(_)))
+        ));
+
+        // Within the limits of the endpoint
+        let limited =
+            Endpoint::<(), Device>::new(Method::Get, "/devices/{id}").with_limits(Limits {
+                max_len: 16,
+                ..Limits::new()
+            });
+
+        let (result, _) = call_with(&limited, &[("id", "1")], &(), &mut [0; 256], json);
+        assert!(matches!(
+            result,
+            Err(RestError::Deserialize(JsonReadError::TooLarge))
+        ));
+    }
+
+    const NOT_FOUND: Problem<'static> = Problem::new(404, "Not Found")
+        .with_type("https://example.com/problems/no-device")
+        .with_detail("No device \"a/b\"\n")
+        .with_instance("/devices/a%2Fb");
+
+    fn problem_json(problem: &Problem<'_>) -> String {
+        let json: Vec<u8> = problem
+            .json()
+            .flat_map(|piece| piece.bytes().to_vec())
+            .collect();
+
+        String::from_utf8(json).unwrap()
+    }
+
+    #[test]
+    fn problem_json_form() {
+        let problem = Problem::new(500, "Internal Server Error");
+
+        assert_eq!(
+            problem_json(&problem),
+            "{\"type\":\"about:blank\",\"title\":\"Internal Server Error\",\"status\":500}"
+        );
+        assert_eq!(problem.json_len(), problem_json(&problem).len());
+
+        // Escaped, with the optional fields
+        assert_eq!(
+            problem_json(&NOT_FOUND),
+            "{\"type\":\"https://example.com/problems/no-device\",\"title\":\"Not Found\",\
+             \"status\":404,\"detail\":\"No device \\\"a/b\\\"\\n\",\"instance\":\"/devices/a%2Fb\"}"
+        );
+        assert_eq!(NOT_FOUND.json_len(), problem_json(&NOT_FOUND).len());
+
+        for status in [0, 7, 10, 99, 100, 999, 1000, 65535] {
+            let json = problem_json(&Problem::new(status, ""));
+
+            assert!(
+                json.contains(&format!(",\"status\":{}}}", status)),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn problem_serde() {
+        let problem = Problem::new(409, "Conflict")
+            .with_detail("Update in progress")
+            .with_instance("/ota");
+
+        // The JSON form is the one of the `Serialize` impl, and deserializes back
+        let json = problem_json(&problem);
+
+        assert_eq!(crate::test_json::to_vec(&problem), json.as_bytes());
+        assert_eq!(
+            crate::test_json::from_slice::<Problem<'_>>(json.as_bytes()).unwrap(),
+            problem
+        );
+
+        let json = b"{\"type\":\"about:blank\",\"title\":\"Conflict\",\"status\":409}";
+        assert_eq!(
+            crate::test_json::from_slice::<Problem<'_>>(json).unwrap(),
+            Problem::new(409, "Conflict")
+        );
+    }
+
+    fn assert_problem(request: &MockRequest, problem: &Problem<'_>) {
+        let len = problem.json_len().to_string();
+
+        assert_eq!(request.status, Some(problem.status));
+        assert_eq!(
+            request.response_header("Content-Type"),
+            Some(Problem::CONTENT_TYPE)
+        );
+        assert_eq!(
+            request.response_header("Content-Length"),
+            Some(len.as_str())
+        );
+        assert_eq!(request.response_header("Cache-Control"), Some("No-Cache"));
+        assert_eq!(request.response_str(), problem_json(problem));
+    }
+
+    #[test]
+    fn problem_sent() {
+        let mut request = MockRequest::new(Method::Get, "/devices/a%2Fb");
+
+        send_problem(Request::wrap(&mut request), &NOT_FOUND).unwrap();
+
+        assert_problem(&request, &NOT_FOUND);
+    }
+
+    #[cfg(feature = "nightly")]
+    mod asynch {
+        use crate::http::client::asynch::Client;
+        use crate::http::Method;
+        use crate::test_http::{MockResponse, MockServer, Recorded};
+        use crate::test_json::JsonSerDe;
+        use crate::utils::asyncify::block_on;
+        use crate::utils::rest::client::asynch::{call, RestError};
+
+        use crate::http::server::asynch::Request;
+        use crate::test_http::MockRequest;
+        use crate::utils::rest::server::asynch::send_problem;
+
+        use super::{assert_problem, device, json, BASE, GET_DEVICE, NOT_FOUND, PUT_DEVICE};
+
+        #[test]
+        fn problem_sent_async() {
+            let mut request = MockRequest::new(Method::Get, "/devices/a%2Fb");
+
+            block_on(send_problem(Request::wrap(&mut request), &NOT_FOUND)).unwrap();
+
+            assert_problem(&request, &NOT_FOUND);
+        }
+
+        #[test]
+        fn call_async() {
+            let server = MockServer::new(|request: &Recorded| match request.method {
+                Method::Get => json(),
+                _ => MockResponse::new(409, b"conflict"),
+            });
+
+            let mut client = Client::wrap(server.release());
+
+            let result = block_on(call(
+                &mut client,
+                JsonSerDe,
+                BASE,
+                &GET_DEVICE,
+                &[("id", "1")],
+                &(),
+                &mut [0; 256],
+            ));
+            assert_eq!(result.unwrap(), device());
+
+            let result = block_on(call(
+                &mut client,
+                JsonSerDe,
+                BASE,
+                &PUT_DEVICE,
+                &[("id", "1")],
+                &device(),
+                &mut [0; 256],
+            ));
+            assert!(matches!(result, Err(RestError::Status { status: 409, .. })));
+
+            let requests = &client.connection().requests;
+
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[1].body, crate::test_json::to_vec(&device()));
+        }
+    }
+}
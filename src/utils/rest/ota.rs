@@ -0,0 +1,627 @@
+//! REST endpoints for the fleet tools managing the firmware of the devices
+//!
+//! - `GET` on `INFO_URI` returns an `Info`, i.e. the running slot (with its `FirmwareInfo`) and the state of the update
+//! - `POST` on `UPDATE_URI` with an `UpdateRequest` starts an update from its URL, and responds with 202,
+//!   or with 409 if an update is in progress already
+//! - `POST` on `ROLLBACK_URI` rolls back to the previous firmware, by marking the running one invalid and rebooting
+//!
+//! As there is no portable handler registry, `OtaApi` only provides the handlers, which the application registers
+//! for these URIs and methods, composed with its authorization middleware (if any) with `Middleware::compose`.
+//!
+//! Downloading an update takes long, so the update handler only hands it over to `OtaApi::run`, which is to run
+//! on a thread of its own (e.g. with an `Unblocker`). `INFO_URI` can thus be polled during the update,
+//! and is then answered with the running slot as it was when the download started.
+
+use core::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::http::client::{self, Client};
+use crate::http::headers;
+use crate::http::server::{Connection, Handler, HandlerResult, Request};
+use crate::http::Headers;
+use crate::io::Write;
+use crate::ota::{Digest, Ota, Slot, VerifyError};
+use crate::storage::SerDe;
+use crate::utils::io::take;
+use crate::utils::json_io::{self, Limits};
+use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
+use crate::utils::ota::{download_verified, DownloadError};
+
+use super::server::send_problem;
+use super::Problem;
+
+pub const INFO_URI: &str = "/api/ota/info";
+pub const UPDATE_URI: &str = "/api/ota/update";
+pub const ROLLBACK_URI: &str = "/api/ota/rollback";
+
+// The largest `UpdateRequest` accepted, and the largest `Info` returned
+const REQUEST_LEN: usize = 512;
+const INFO_LEN: usize = 1024;
+
+/// The body of the requests of `UPDATE_URI`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UpdateRequest {
+    /// The URL of the firmware image
+    pub url: heapless::String<256>,
+    /// The SHA-256 digest of the image, as 64 hex digits
+    pub sha256: heapless::String<64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateState {
+    Idle,
+    /// The update was accepted, and is waiting for `OtaApi::run` to download it
+    Pending,
+    Downloading,
+    /// The update was downloaded and verified, and boots on the next restart
+    Completed { size: u64 },
+    Failed { error: heapless::String<64> },
+}
+
+impl UpdateState {
+    pub fn is_updating(&self) -> bool {
+        matches!(self, Self::Pending | Self::Downloading)
+    }
+}
+
+/// The response of `INFO_URI`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Info {
+    /// The running slot, which is `None` only if an update started before it could be read
+    pub running: Option<Slot>,
+    /// Whether an update is in progress, i.e. `update` is `Pending` or `Downloading`
+    pub updating: bool,
+    /// The state of the last update
+    pub update: UpdateState,
+}
+
+struct State {
+    update: UpdateState,
+    request: Option<(heapless::String<256>, [u8; 32])>,
+    running: Option<Slot>,
+}
+
+struct Inner<O, const N: usize> {
+    ota: O,
+    buf: [u8; N],
+}
+
+/// Shared state of the OTA endpoints
+///
+/// `S` (de)serializes the JSON bodies, and `N` is the size of the buffer used for downloading the updates.
+pub struct OtaApi<V, O, S, const N: usize = 2048>
+where
+    V: RawCondvar,
+{
+    inner: Mutex<V::RawMutex, Inner<O, N>>,
+    state: Mutex<V::RawMutex, State>,
+    state_changed: Condvar<V>,
+    serde: S,
+}
+
+impl<V, O, S, const N: usize> OtaApi<V, O, S, N>
+where
+    V: RawCondvar + Send + Sync,
+    V::RawMutex: Send + Sync,
+    O: Ota + Send,
+    S: SerDe + Send + Sync,
+{
+    pub fn new(ota: O, serde: S) -> Self {
+        Self {
+            inner: Mutex::new(Inner { ota, buf: [0; N] }),
+            state: Mutex::new(State {
+                update: UpdateState::Idle,
+                request: None,
+                running: None,
+            }),
+            state_changed: Condvar::new(),
+            serde,
+        }
+    }
+
+    pub fn state(&self) -> UpdateState {
+        self.state.lock().update.clone()
+    }
+
+    pub fn info_handler(&self) -> InfoHandler<'_, V, O, S, N> {
+        InfoHandler(self)
+    }
+
+    pub fn update_handler(&self) -> UpdateHandler<'_, V, O, S, N> {
+        UpdateHandler(self)
+    }
+
+    pub fn rollback_handler(&self) -> RollbackHandler<'_, V, O, S, N> {
+        RollbackHandler(self)
+    }
+
+    /// Downloads the updates accepted by the update handler, one at a time, with `client`; never returns
+    ///
+    /// `digest` is cloned for each update, for checking its image against the expected SHA-256 digest.
+    pub fn run<C, D>(&self, client: &mut Client<C>, digest: D)
+    where
+        C: client::Connection,
+        D: Digest<Output = [u8; 32]> + Clone,
+    {
+        loop {
+            let mut state = self.state.lock();
+
+            while state.request.is_none() {
+                state = self.state_changed.wait(state);
+            }
+
+            drop(state);
+
+            self.run_once(client, digest.clone());
+        }
+    }
+
+    /// Downloads the update accepted by the update handler, if any, returning whether there was one
+    pub fn run_once<C, D>(&self, client: &mut Client<C>, digest: D) -> bool
+    where
+        C: client::Connection,
+        D: Digest<Output = [u8; 32]>,
+    {
+        let mut state = self.state.lock();
+
+        let Some((url, expected)) = state.request.take() else {
+            return false;
+        };
+
+        let mut inner = self.inner.lock();
+
+        if let Ok(running) = inner.ota.get_running_slot() {
+            state.running = Some(running);
+        }
+
+        state.update = UpdateState::Downloading;
+
+        // From now on, the handlers do not lock the OTA until the download is over
+        drop(state);
+
+        let Inner { ota, buf } = &mut *inner;
+
+        let result = download_verified(client, ota, &url, &[], buf, digest, &expected);
+
+        drop(inner);
+
+        let update = match result {
+            Ok(size) => UpdateState::Completed { size },
+            Err(e) => UpdateState::Failed {
+                error: Self::error(e),
+            },
+        };
+
+        self.state.lock().update = update;
+        self.state_changed.notify_all();
+
+        true
+    }
+
+    fn error<H, E>(e: DownloadError<H, VerifyError<E>>) -> heapless::String<64> {
+        let mut error = heapless::String::new();
+
+        let _ = match e {
+            DownloadError::Http(_) => write!(error, "Download failed"),
+            DownloadError::Status(status) => write!(error, "Download failed with status {status}"),
+            DownloadError::InvalidRange => write!(error, "Invalid Content-Range"),
+            DownloadError::TooManyHeaders => write!(error, "Too many headers"),
+            DownloadError::Ota(VerifyError::Mismatch) => write!(error, "SHA-256 digest mismatch"),
+            DownloadError::Ota(VerifyError::Update(_)) => write!(error, "Update failed"),
+            DownloadError::InvalidFirmware => write!(error, "Invalid firmware"),
+            DownloadError::Policy(_) => write!(error, "Rejected by the update policy"),
+        };
+
+        error
+    }
+
+    fn handle_info<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let info = {
+            let mut state = self.state.lock();
+
+            // Do not wait for an in-flight update, which holds the OTA
+            if !state.update.is_updating() {
+                state.running = Some(self.inner.lock().ota.get_running_slot()?);
+            }
+
+            Info {
+                running: state.running.clone(),
+                updating: state.update.is_updating(),
+                update: state.update.clone(),
+            }
+        };
+
+        let mut buf = [0; INFO_LEN];
+        let json = self.serde.serialize(&mut buf, &info)?;
+
+        let mut len_buf = headers::ContentLenParseBuf::new();
+
+        let mut response = Request::wrap(connection).into_response(
+            200,
+            Some("OK"),
+            &[
+                headers::content_type("application/json"),
+                headers::content_len(json.len() as u64, &mut len_buf),
+                headers::cache_control_no_cache(),
+            ],
+        )?;
+
+        response.write_all(json)?;
+
+        Ok(())
+    }
+
+    fn handle_update<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let mut request = Request::wrap(connection);
+
+        let len = request.content_len().unwrap_or(u64::MAX);
+
+        let mut buf = [0; REQUEST_LEN];
+        let limits = Limits {
+            max_len: REQUEST_LEN,
+            ..Limits::new()
+        };
+
+        // Do not read past the body, should the connection carry more data after it
+        let body: Result<UpdateRequest, _> =
+            json_io::read(take(&mut request, len), &self.serde, &mut buf, &limits);
+
+        let Ok(body) = body else {
+            send_problem(
+                request,
+                &Problem::new(400, "Bad Request").with_detail("Invalid update request"),
+            )?;
+
+            return Ok(());
+        };
+
+        let Some(expected) = digest(&body.sha256) else {
+            send_problem(
+                request,
+                &Problem::new(400, "Bad Request").with_detail("Invalid SHA-256 digest"),
+            )?;
+
+            return Ok(());
+        };
+
+        {
+            let mut state = self.state.lock();
+
+            if state.update.is_updating() {
+                drop(state);
+
+                send_problem(
+                    request,
+                    &Problem::new(409, "Conflict").with_detail("An update is already in progress"),
+                )?;
+
+                return Ok(());
+            }
+
+            state.update = UpdateState::Pending;
+            state.request = Some((body.url, expected));
+        }
+
+        self.state_changed.notify_all();
+
+        request.into_response(202, Some("Accepted"), &[headers::location(INFO_URI)])?;
+
+        Ok(())
+    }
+
+    fn handle_rollback<C>(&self, connection: &mut C) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let request = Request::wrap(connection);
+
+        let state = self.state.lock();
+
+        if state.update.is_updating() {
+            drop(state);
+
+            send_problem(
+                request,
+                &Problem::new(409, "Conflict").with_detail("An update is in progress"),
+            )?;
+
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock();
+
+        let mut response = request.into_ok_response()?;
+        response.write_all(b"Rolling back")?;
+        response.flush()?;
+
+        Err(inner.ota.mark_running_slot_invalid_and_reboot().into())
+    }
+}
+
+fn digest(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+
+    let mut digest = [0; 32];
+
+    for (byte, digits) in digest.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+
+    Some(digest)
+}
+
+pub struct InfoHandler<'a, V, O, S, const N: usize>(&'a OtaApi<V, O, S, N>)
+where
+    V: RawCondvar;
+
+impl<V, O, S, C, const N: usize> Handler<C> for InfoHandler<'_, V, O, S, N>
+where
+    V: RawCondvar + Send + Sync,
+    V::RawMutex: Send + Sync,
+    O: Ota + Send,
+    S: SerDe + Send + Sync,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_info(connection)
+    }
+}
+
+pub struct UpdateHandler<'a, V, O, S, const N: usize>(&'a OtaApi<V, O, S, N>)
+where
+    V: RawCondvar;
+
+impl<V, O, S, C, const N: usize> Handler<C> for UpdateHandler<'_, V, O, S, N>
+where
+    V: RawCondvar + Send + Sync,
+    V::RawMutex: Send + Sync,
+    O: Ota + Send,
+    S: SerDe + Send + Sync,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_update(connection)
+    }
+}
+
+pub struct RollbackHandler<'a, V, O, S, const N: usize>(&'a OtaApi<V, O, S, N>)
+where
+    V: RawCondvar;
+
+impl<V, O, S, C, const N: usize> Handler<C> for RollbackHandler<'_, V, O, S, N>
+where
+    V: RawCondvar + Send + Sync,
+    V::RawMutex: Send + Sync,
+    O: Ota + Send,
+    S: SerDe + Send + Sync,
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        self.0.handle_rollback(connection)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::convert::TryInto;
+
+    use crate::http::Method;
+    use crate::ota::Digest;
+    use crate::test_http::{MockRequest, MockResponse, MockServer, Recorded};
+    use crate::test_json::{from_slice, to_vec, JsonSerDe};
+    use crate::test_ota::{padded_crc32, MockOta};
+    use crate::utils::mutex::StdRawCondvar;
+
+    use super::*;
+
+    type Api = OtaApi<StdRawCondvar, MockOta, JsonSerDe, 64>;
+
+    const IMAGE: &[u8] = b"A firmware image, spanning a few reads of the buffer of the download";
+    const IMAGE_URL: &str = "https://example.com/firmware.bin";
+
+    fn ok(result: HandlerResult) {
+        if let Err(e) = result {
+            panic!("Handler failed: {}", e);
+        }
+    }
+
+    fn sha256(image: &[u8]) -> heapless::String<64> {
+        let mut digest = padded_crc32();
+        digest.update(image);
+
+        let mut hex = heapless::String::new();
+
+        for byte in digest.finalize() {
+            write!(hex, "{:02x}", byte).unwrap();
+        }
+
+        hex
+    }
+
+    fn get_info(api: &Api) -> Info {
+        let mut request = MockRequest::new(Method::Get, INFO_URI);
+        ok(api.info_handler().handle(&mut request));
+
+        assert_eq!(request.status, Some(200));
+        assert_eq!(
+            request.response_header("Content-Type"),
+            Some("application/json")
+        );
+
+        from_slice(&request.response).unwrap()
+    }
+
+    fn update(api: &Api, body: &[u8]) -> MockRequest {
+        let mut request = MockRequest::new(Method::Post, UPDATE_URI).body(body);
+        ok(api.update_handler().handle(&mut request));
+
+        request
+    }
+
+    fn update_request(url: &str, sha256: &str) -> Vec<u8> {
+        to_vec(&UpdateRequest {
+            url: url.try_into().unwrap(),
+            sha256: sha256.try_into().unwrap(),
+        })
+    }
+
+    fn assert_problem(request: &MockRequest, status: u16, detail: &str) {
+        assert_eq!(request.status, Some(status));
+        assert_eq!(
+            request.response_header("Content-Type"),
+            Some(Problem::CONTENT_TYPE)
+        );
+
+        let problem: Problem<'_> = from_slice(&request.response).unwrap();
+        assert_eq!(problem.status, status);
+        assert_eq!(problem.detail, Some(detail));
+    }
+
+    fn serve(image: &'static [u8]) -> impl FnMut(&Recorded) -> MockResponse {
+        move |request| {
+            assert_eq!(request.uri, IMAGE_URL);
+
+            MockResponse::new(200, image).header("Content-Length", image.len())
+        }
+    }
+
+    #[test]
+    fn info_idle() {
+        let api = Api::new(MockOta::new(), JsonSerDe);
+
+        let info = get_info(&api);
+
+        assert_eq!(info.running.unwrap().label, "running");
+        assert!(!info.updating);
+        assert_eq!(info.update, UpdateState::Idle);
+    }
+
+    #[test]
+    fn update_completed() {
+        let api = Api::new(MockOta::new(), JsonSerDe);
+        let mut client = MockServer::new(serve(IMAGE));
+
+        // Nothing to download yet
+        assert!(!api.run_once(&mut client, padded_crc32()));
+
+        let request = update(&api, &update_request(IMAGE_URL, &sha256(IMAGE)));
+
+        assert_eq!(request.status, Some(202));
+        assert_eq!(request.response_header("Location"), Some(INFO_URI));
+        assert_eq!(api.state(), UpdateState::Pending);
+
+        let info = get_info(&api);
+        assert!(info.updating);
+        assert_eq!(info.update, UpdateState::Pending);
+
+        assert!(api.run_once(&mut client, padded_crc32()));
+
+        assert_eq!(
+            api.state(),
+            UpdateState::Completed {
+                size: IMAGE.len() as u64
+            }
+        );
+        assert_eq!(api.inner.lock().ota.completed.as_deref(), Some(IMAGE));
+        assert!(!get_info(&api).updating);
+
+        // Downloaded once only
+        assert_eq!(client.connection().requests.len(), 1);
+        assert!(!api.run_once(&mut client, padded_crc32()));
+    }
+
+    #[test]
+    fn update_failed() {
+        let api = Api::new(MockOta::new(), JsonSerDe);
+
+        // Not the image expected
+        update(&api, &update_request(IMAGE_URL, &sha256(b"Another image")));
+        assert!(api.run_once(&mut MockServer::new(serve(IMAGE)), padded_crc32()));
+
+        assert_eq!(
+            api.state(),
+            UpdateState::Failed {
+                error: "SHA-256 digest mismatch".try_into().unwrap()
+            }
+        );
+        assert!(api.inner.lock().ota.completed.is_none());
+
+        // Not found; accepted again, as the previous update is over
+        let request = update(&api, &update_request(IMAGE_URL, &sha256(IMAGE)));
+        assert_eq!(request.status, Some(202));
+
+        assert!(api.run_once(
+            &mut MockServer::new(|_: &Recorded| MockResponse::new(404, b"")),
+            padded_crc32()
+        ));
+
+        assert_eq!(
+            api.state(),
+            UpdateState::Failed {
+                error: "Download failed with status 404".try_into().unwrap()
+            }
+        );
+        assert_eq!(get_info(&api).update, api.state());
+    }
+
+    #[test]
+    fn update_rejected() {
+        let api = Api::new(MockOta::new(), JsonSerDe);
+
+        assert_problem(
+            &update(&api, b"{\"url\": \"https://example.com\""),
+            400,
+            "Invalid update request",
+        );
+
+        for sha256 in ["abcd", &"xy".repeat(32)] {
+            assert_problem(
+                &update(&api, &update_request(IMAGE_URL, sha256)),
+                400,
+                "Invalid SHA-256 digest",
+            );
+        }
+
+        assert_eq!(api.state(), UpdateState::Idle);
+
+        // Already in progress
+        update(&api, &update_request(IMAGE_URL, &sha256(IMAGE)));
+
+        assert_problem(
+            &update(&api, &update_request(IMAGE_URL, &sha256(IMAGE))),
+            409,
+            "An update is already in progress",
+        );
+
+        let mut request = MockRequest::new(Method::Post, ROLLBACK_URI);
+        ok(api.rollback_handler().handle(&mut request));
+
+        assert_problem(&request, 409, "An update is in progress");
+    }
+
+    #[test]
+    fn rollback() {
+        let api = Api::new(MockOta::new(), JsonSerDe);
+
+        let mut request = MockRequest::new(Method::Post, ROLLBACK_URI);
+
+        // Responds before rebooting; the mock cannot reboot, and fails instead
+        let result = api.rollback_handler().handle(&mut request);
+
+        assert!(result.is_err());
+        assert_eq!(request.status, Some(200));
+        assert_eq!(request.response_str(), "Rolling back");
+    }
+}
@@ -0,0 +1,810 @@
+use core::convert::TryInto;
+use core::fmt;
+use core::ops::ControlFlow;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::event_bus::{EventBus, Postbox};
+#[cfg(feature = "use_serde")]
+use crate::storage::Storage;
+use crate::storage::{
+    recover, Operation, Operations, RawStorage, StorageBase, Transaction, TransactionError,
+};
+
+/// The maximum length of the keys in `StorageEvent`; longer keys are truncated
+pub const MAX_EVENT_KEY_LEN: usize = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StorageEventKind {
+    Set,
+    Removed,
+    /// All keys starting with the key of the event were removed
+    RemovedAll,
+}
+
+/// A change of a storage, posted by `NotifyingStorage`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StorageEvent {
+    pub kind: StorageEventKind,
+    /// The key (or prefix, for `StorageEventKind::RemovedAll`), truncated to `MAX_EVENT_KEY_LEN`
+    pub key: heapless::String<MAX_EVENT_KEY_LEN>,
+    pub truncated: bool,
+}
+
+impl StorageEvent {
+    pub fn new(kind: StorageEventKind, key: &str) -> Self {
+        let mut truncated = heapless::String::new();
+
+        for c in key.chars() {
+            if truncated.push(c).is_err() {
+                break;
+            }
+        }
+
+        Self {
+            kind,
+            truncated: truncated.len() < key.len(),
+            key: truncated,
+        }
+    }
+
+    /// Whether the changed key(s) might start with `prefix`
+    ///
+    /// False positives are possible for truncated keys.
+    pub fn matches(&self, prefix: &str) -> bool {
+        let key = self.key.as_str();
+
+        key.starts_with(prefix)
+            || (self.truncated || self.kind == StorageEventKind::RemovedAll)
+                && prefix.starts_with(key)
+    }
+}
+
+/// A storage posting a `StorageEvent` to a postbox after each change.
+///
+/// Notifications never fail the change: they are posted with the configured wait (none by default),
+/// and dropped if the postbox is full or fails.
+pub struct NotifyingStorage<S, P> {
+    storage: S,
+    postbox: P,
+    wait: Option<Duration>,
+    dropped: usize,
+}
+
+impl<S, P> NotifyingStorage<S, P>
+where
+    P: Postbox<StorageEvent>,
+{
+    pub const fn new(storage: S, postbox: P) -> Self {
+        Self {
+            storage,
+            postbox,
+            wait: Some(Duration::ZERO),
+            dropped: 0,
+        }
+    }
+
+    /// Sets for how long posting a notification waits for room in the postbox; `None` waits forever
+    pub fn with_wait(mut self, wait: Option<Duration>) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn postbox(&self) -> &P {
+        &self.postbox
+    }
+
+    pub fn release(self) -> (S, P) {
+        (self.storage, self.postbox)
+    }
+
+    /// The number of notifications which could not be posted
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn notify(&mut self, kind: StorageEventKind, key: &str) {
+        let posted = self
+            .postbox
+            .post(&StorageEvent::new(kind, key), self.wait)
+            .unwrap_or(false);
+
+        if !posted {
+            self.dropped += 1;
+        }
+    }
+}
+
+impl<S, P> StorageBase for NotifyingStorage<S, P>
+where
+    S: StorageBase,
+    P: Postbox<StorageEvent>,
+{
+    type Error = S::Error;
+
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        self.storage.contains(name)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        let removed = self.storage.remove(name)?;
+
+        if removed {
+            self.notify(StorageEventKind::Removed, name);
+        }
+
+        Ok(removed)
+    }
+
+    fn for_each_key<F>(&self, prefix: &str, f: F) -> Result<bool, Self::Error>
+    where
+        F: FnMut(&str) -> ControlFlow<()>,
+    {
+        self.storage.for_each_key(prefix, f)
+    }
+
+    fn remove_all(&mut self, prefix: &str) -> Result<Option<usize>, Self::Error> {
+        let removed = self.storage.remove_all(prefix)?;
+
+        if matches!(removed, Some(removed) if removed > 0) {
+            self.notify(StorageEventKind::RemovedAll, prefix);
+        }
+
+        Ok(removed)
+    }
+
+    fn count(&self) -> Result<Option<usize>, Self::Error> {
+        self.storage.count()
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<S, P> Storage for NotifyingStorage<S, P>
+where
+    S: Storage,
+    P: Postbox<StorageEvent>,
+{
+    fn get<T>(&self, name: &str) -> Result<Option<T>, Self::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.storage.get(name)
+    }
+
+    fn set<T>(&mut self, name: &str, value: &T) -> Result<bool, Self::Error>
+    where
+        T: Serialize,
+    {
+        let result = self.storage.set(name, value)?;
+
+        self.notify(StorageEventKind::Set, name);
+
+        Ok(result)
+    }
+}
+
+impl<S, P> RawStorage for NotifyingStorage<S, P>
+where
+    S: RawStorage,
+    P: Postbox<StorageEvent>,
+{
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        self.storage.len(name)
+    }
+
+    fn get_raw<'a>(&self, name: &str, buf: &'a mut [u8]) -> Result<Option<&'a [u8]>, Self::Error> {
+        self.storage.get_raw(name, buf)
+    }
+
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        let result = self.storage.set_raw(name, buf)?;
+
+        self.notify(StorageEventKind::Set, name);
+
+        Ok(result)
+    }
+
+    fn get_raw_at(
+        &self,
+        name: &str,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        self.storage.get_raw_at(name, offset, buf)
+    }
+
+    fn apply_atomically(&mut self, operations: Operations<'_>) -> Result<bool, Self::Error> {
+        let applied = self.storage.apply_atomically(operations)?;
+
+        if applied {
+            for operation in operations.iter() {
+                match operation {
+                    Operation::Set(name, _) => self.notify(StorageEventKind::Set, name),
+                    Operation::Remove(name) => self.notify(StorageEventKind::Removed, name),
+                }
+            }
+        }
+
+        Ok(applied)
+    }
+}
+
+/// Subscribes `callback` to the events of `bus` about the keys starting with `prefix`
+/// (see `StorageEvent::matches`)
+pub fn subscribe_prefix<'a, B, F>(
+    bus: &'a B,
+    prefix: &str,
+    mut callback: F,
+) -> Result<B::Subscription<'a>, B::Error>
+where
+    B: EventBus<StorageEvent>,
+    F: FnMut(&StorageEvent) + Send + 'static,
+{
+    let prefix = StorageEvent::new(StorageEventKind::Set, prefix);
+
+    bus.subscribe(move |event: &StorageEvent| {
+        if event.matches(&prefix.key) {
+            callback(event);
+        }
+    })
+}
+
+/// The key under which `Migrator` stores the schema version
+pub const VERSION_KEY: &str = "_ver";
+
+/// A migration step, from the version passed to the next one
+pub type MigrationStep<R, E, const N: usize> = fn(u32, &mut Transaction<'_, R, N>) -> Result<(), E>;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MigrationError<S, E> {
+    StorageError(TransactionError<S>),
+    StepError {
+        version: u32,
+        error: E,
+    },
+    /// The stored version is newer than the latest known one, e.g. after a firmware downgrade
+    UnknownVersion(u32),
+    InvalidVersion,
+}
+
+impl<S, E> fmt::Display for MigrationError<S, E>
+where
+    S: fmt::Display,
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StorageError(e) => write!(f, "{e}"),
+            Self::StepError { version, error } => {
+                write!(f, "Migration from version {version} failed: {error}")
+            }
+            Self::UnknownVersion(version) => write!(f, "Unknown version {version}"),
+            Self::InvalidVersion => write!(f, "Invalid version"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S, E> std::error::Error for MigrationError<S, E>
+where
+    S: std::error::Error + 'static,
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // Displayed as the storage error itself
+            Self::StorageError(e) => e.source(),
+            Self::StepError { error, .. } => Some(error),
+            Self::UnknownVersion(_) | Self::InvalidVersion => None,
+        }
+    }
+}
+
+impl<S, E> From<TransactionError<S>> for MigrationError<S, E> {
+    fn from(e: TransactionError<S>) -> Self {
+        Self::StorageError(e)
+    }
+}
+
+/// Migrates the values of a storage between schema versions.
+///
+/// Step `n` migrates from version `n` to version `n + 1`, so the latest version is the number of steps.
+/// A storage without a stored version is at version 0, hence the steps should tolerate missing values.
+///
+/// Each step is applied in a transaction (of up to `N` bytes), which also records the new version.
+/// A failed step therefore leaves the storage at the previous version, so that it is retried by the next `migrate`.
+pub struct Migrator<'a, R, E, const N: usize = 256> {
+    steps: &'a [MigrationStep<R, E, N>],
+}
+
+impl<'a, R, E, const N: usize> Migrator<'a, R, E, N>
+where
+    R: RawStorage,
+{
+    pub const fn new(steps: &'a [MigrationStep<R, E, N>]) -> Self {
+        Self { steps }
+    }
+
+    pub fn latest_version(&self) -> u32 {
+        self.steps.len() as u32
+    }
+
+    pub fn version(&self, storage: &R) -> Result<u32, MigrationError<R::Error, E>> {
+        let mut buf = [0_u8; 4];
+
+        match storage
+            .get_raw(VERSION_KEY, &mut buf)
+            .map_err(|e| MigrationError::StorageError(TransactionError::StorageError(e)))?
+        {
+            Some(version) => Ok(u32::from_le_bytes(
+                version
+                    .try_into()
+                    .map_err(|_| MigrationError::InvalidVersion)?,
+            )),
+            None => Ok(0),
+        }
+    }
+
+    /// Applies the pending steps and returns the version migrated from.
+    ///
+    /// A transaction interrupted by a power loss is recovered first.
+    pub fn migrate(&self, storage: &mut R) -> Result<u32, MigrationError<R::Error, E>> {
+        recover(storage, &mut [0; N])?;
+
+        let from = self.version(storage)?;
+        let latest = self.latest_version();
+
+        if from > latest {
+            return Err(MigrationError::UnknownVersion(from));
+        }
+
+        for version in from..latest {
+            let mut transaction = Transaction::<_, N>::new(&mut *storage);
+
+            self.steps[version as usize](version, &mut transaction)
+                .map_err(|error| MigrationError::StepError { version, error })?;
+
+            transaction.set_raw(VERSION_KEY, &(version + 1).to_le_bytes())?;
+            transaction.commit()?;
+        }
+
+        Ok(from)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    extern crate std;
+
+    use core::cell::{Cell, RefCell};
+
+    use std::boxed::Box;
+    use std::string::{String, ToString};
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    use crate::event_bus::ErrorType;
+    use crate::storage::heap::{InMemoryStorage, InMemoryStorageError};
+
+    use super::*;
+
+    type Callback = Box<dyn FnMut(&StorageEvent) + Send>;
+
+    /// Delivers the posted events to its subscribers, and keeps them in `posted`.
+    /// Refuses the events once `capacity` events were posted, and fails if `fail` is set.
+    #[derive(Default)]
+    struct MockBus {
+        posted: RefCell<Vec<StorageEvent>>,
+        capacity: Cell<Option<usize>>,
+        fail: Cell<bool>,
+        subscribers: RefCell<Vec<Callback>>,
+    }
+
+    impl MockBus {
+        fn posted(&self) -> Vec<(StorageEventKind, String)> {
+            self.posted
+                .borrow()
+                .iter()
+                .map(|event| (event.kind, event.key.to_string()))
+                .collect()
+        }
+    }
+
+    impl ErrorType for MockBus {
+        type Error = ();
+    }
+
+    impl Postbox<StorageEvent> for MockBus {
+        fn post(&self, payload: &StorageEvent, wait: Option<Duration>) -> Result<bool, ()> {
+            assert_eq!(wait, Some(Duration::ZERO));
+
+            if self.fail.get() {
+                return Err(());
+            }
+
+            if matches!(self.capacity.get(), Some(capacity) if self.posted.borrow().len() >= capacity)
+            {
+                return Ok(false);
+            }
+
+            self.posted.borrow_mut().push(payload.clone());
+
+            for subscriber in self.subscribers.borrow_mut().iter_mut() {
+                subscriber(payload);
+            }
+
+            Ok(true)
+        }
+    }
+
+    impl EventBus<StorageEvent> for MockBus {
+        type Subscription<'a> = ();
+
+        fn subscribe<F>(&self, callback: F) -> Result<(), ()>
+        where
+            F: FnMut(&StorageEvent) + Send + 'static,
+        {
+            self.subscribers.borrow_mut().push(Box::new(callback));
+
+            Ok(())
+        }
+    }
+
+    use StorageEventKind::*;
+
+    fn event(kind: StorageEventKind, key: &str) -> (StorageEventKind, String) {
+        (kind, key.to_string())
+    }
+
+    #[test]
+    fn notifications() {
+        let bus = MockBus::default();
+        let mut storage = NotifyingStorage::new(InMemoryStorage::new(), &bus);
+
+        storage.set_raw("wifi/ssid", b"home").unwrap();
+        storage.set_raw("wifi/ssid", b"guest").unwrap();
+        storage.set_raw("wifi/pass", b"secret").unwrap();
+        assert!(storage.remove("wifi/pass").unwrap());
+        // Nothing changed
+        assert!(!storage.remove("wifi/pass").unwrap());
+        assert_eq!(storage.remove_all("mqtt/").unwrap(), Some(0));
+
+        assert_eq!(storage.remove_all("wifi/").unwrap(), Some(1));
+
+        assert_eq!(
+            bus.posted(),
+            [
+                event(Set, "wifi/ssid"),
+                event(Set, "wifi/ssid"),
+                event(Set, "wifi/pass"),
+                event(Removed, "wifi/pass"),
+                event(RemovedAll, "wifi/"),
+            ]
+        );
+        assert_eq!(storage.dropped(), 0);
+    }
+
+    #[test]
+    fn notifications_failed_writes() {
+        let bus = MockBus::default();
+        let mut storage = NotifyingStorage::new(InMemoryStorage::new(), &bus);
+
+        storage.set_raw("key", b"1").unwrap();
+
+        let (mut inner, bus) = storage.release();
+        inner.fail_next_write();
+        let mut storage = NotifyingStorage::new(inner, bus);
+
+        assert_eq!(
+            storage.set_raw("key", b"2"),
+            Err(InMemoryStorageError::Injected)
+        );
+        assert_eq!(bus.posted(), [event(Set, "key")]);
+    }
+
+    #[test]
+    fn notifications_transaction() {
+        let bus = MockBus::default();
+        let mut storage = NotifyingStorage::new(InMemoryStorage::new(), &bus);
+
+        let mut transaction = Transaction::<_>::new(&mut storage);
+        transaction.set_raw("a", b"1").unwrap();
+        transaction.set_raw("b", b"2").unwrap();
+        transaction.remove("c").unwrap();
+        transaction.commit().unwrap();
+
+        assert_eq!(
+            bus.posted(),
+            [event(Set, "a"), event(Set, "b"), event(Removed, "c")]
+        );
+    }
+
+    #[test]
+    fn notifications_dropped() {
+        let bus = MockBus::default();
+        bus.capacity.set(Some(1));
+
+        let mut storage = NotifyingStorage::new(InMemoryStorage::new(), &bus);
+
+        storage.set_raw("a", b"1").unwrap();
+        // The postbox is full, but the writes succeed
+        storage.set_raw("b", b"2").unwrap();
+        assert!(storage.remove("a").unwrap());
+
+        bus.fail.set(true);
+        storage.set_raw("c", b"3").unwrap();
+
+        assert_eq!(bus.posted(), [event(Set, "a")]);
+        assert_eq!(storage.dropped(), 3);
+        assert_eq!(storage.storage().count().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn event_truncated() {
+        let key = "k".repeat(MAX_EVENT_KEY_LEN + 1);
+        let event = StorageEvent::new(Set, &key);
+
+        assert!(event.truncated);
+        assert_eq!(event.key.len(), MAX_EVENT_KEY_LEN);
+
+        assert!(event.matches(""));
+        assert!(event.matches(&key[..10]));
+        // Might be the truncated key
+        assert!(event.matches(&key));
+        assert!(event.matches(&(key.clone() + "k")));
+        assert!(!event.matches(&(key[..10].to_string() + "x")));
+
+        // Not truncated in the middle of a character
+        let event = StorageEvent::new(Set, &("k".repeat(MAX_EVENT_KEY_LEN - 1) + "é"));
+        assert!(event.truncated);
+        assert_eq!(event.key.len(), MAX_EVENT_KEY_LEN - 1);
+    }
+
+    #[test]
+    fn event_matches() {
+        let event = StorageEvent::new(Set, "wifi/ssid");
+
+        assert!(!event.truncated);
+        assert!(event.matches("wifi/"));
+        assert!(event.matches("wifi/ssid"));
+        assert!(!event.matches("wifi/ssid2"));
+        assert!(!event.matches("mqtt/"));
+
+        let event = StorageEvent::new(RemovedAll, "wifi/");
+
+        assert!(event.matches("wifi/"));
+        assert!(event.matches("wifi/ssid"));
+        // The removed keys start with `wifi/`, hence also with `wifi`
+        assert!(event.matches("wifi"));
+        assert!(!event.matches("wifi2"));
+        assert!(!event.matches("mqtt/ssid"));
+    }
+
+    #[test]
+    fn subscribe_by_prefix() {
+        let bus = MockBus::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let received = received.clone();
+
+            subscribe_prefix(&bus, "wifi/", move |event| {
+                received.lock().unwrap().push(event.key.to_string());
+            })
+            .unwrap();
+        }
+
+        let mut storage = NotifyingStorage::new(InMemoryStorage::new(), &bus);
+
+        storage.set_raw("wifi/ssid", b"home").unwrap();
+        storage.set_raw("mqtt/url", b"mqtt://broker").unwrap();
+        storage.set_raw("wifi", b"on").unwrap();
+        storage.remove_all("").unwrap();
+
+        assert_eq!(*received.lock().unwrap(), ["wifi/ssid", ""]);
+    }
+
+    type Step = MigrationStep<InMemoryStorage, &'static str, 256>;
+
+    fn value(storage: &InMemoryStorage, name: &str) -> Option<Vec<u8>> {
+        let mut buf = [0; 16];
+
+        storage
+            .get_raw(name, &mut buf)
+            .unwrap()
+            .map(|value| value.to_vec())
+    }
+
+    /// Moves `ssid` into the `wifi/` namespace
+    fn namespace_ssid(
+        version: u32,
+        transaction: &mut Transaction<'_, InMemoryStorage, 256>,
+    ) -> Result<(), &'static str> {
+        assert_eq!(version, 0);
+
+        if let Some(ssid) = value(transaction.storage(), "ssid") {
+            transaction.set_raw("wifi/ssid", &ssid).unwrap();
+            transaction.remove("ssid").unwrap();
+        }
+
+        Ok(())
+    }
+
+    /// Adds a default channel; fails if there is a `fail` key
+    fn default_channel(
+        version: u32,
+        transaction: &mut Transaction<'_, InMemoryStorage, 256>,
+    ) -> Result<(), &'static str> {
+        assert_eq!(version, 1);
+
+        if transaction.storage().contains("fail").unwrap() {
+            return Err("failed");
+        }
+
+        transaction.set_raw("wifi/channel", b"1").unwrap();
+
+        Ok(())
+    }
+
+    const STEPS: &[Step] = &[namespace_ssid, default_channel];
+
+    #[test]
+    fn migrate() {
+        let migrator = Migrator::new(STEPS);
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("ssid", b"home").unwrap();
+
+        assert_eq!(migrator.latest_version(), 2);
+        assert_eq!(migrator.version(&storage).unwrap(), 0);
+        assert_eq!(migrator.migrate(&mut storage).unwrap(), 0);
+        assert_eq!(migrator.version(&storage).unwrap(), 2);
+
+        assert_eq!(value(&storage, "ssid"), None);
+        assert_eq!(value(&storage, "wifi/ssid").as_deref(), Some(&b"home"[..]));
+        assert_eq!(value(&storage, "wifi/channel").as_deref(), Some(&b"1"[..]));
+        assert_eq!(
+            value(&storage, VERSION_KEY).as_deref(),
+            Some(&2_u32.to_le_bytes()[..])
+        );
+
+        // Nothing is pending anymore
+        storage.set_raw("ssid", b"other").unwrap();
+
+        assert_eq!(migrator.migrate(&mut storage).unwrap(), 2);
+        assert_eq!(value(&storage, "ssid").as_deref(), Some(&b"other"[..]));
+    }
+
+    #[test]
+    fn migrate_step_failure() {
+        let migrator = Migrator::new(STEPS);
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("ssid", b"home").unwrap();
+        storage.set_raw("fail", b"").unwrap();
+
+        let error = migrator.migrate(&mut storage).unwrap_err();
+
+        assert!(matches!(
+            error,
+            MigrationError::StepError {
+                version: 1,
+                error: "failed"
+            }
+        ));
+        assert_eq!(error.to_string(), "Migration from version 1 failed: failed");
+
+        // The first step is kept, but the failed one is not recorded
+        assert_eq!(migrator.version(&storage).unwrap(), 1);
+        assert!(storage.contains("wifi/ssid").unwrap());
+        assert!(!storage.contains("wifi/channel").unwrap());
+
+        // And is retried by the next migration
+        storage.remove("fail").unwrap();
+
+        assert_eq!(migrator.migrate(&mut storage).unwrap(), 1);
+        assert_eq!(migrator.version(&storage).unwrap(), 2);
+        assert!(storage.contains("wifi/channel").unwrap());
+    }
+
+    #[test]
+    fn migrate_storage_failure() {
+        let migrator = Migrator::new(STEPS);
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw("ssid", b"home").unwrap();
+        storage.fail_next_write();
+
+        assert!(matches!(
+            migrator.migrate(&mut storage),
+            Err(MigrationError::StorageError(
+                TransactionError::StorageError(InMemoryStorageError::Injected)
+            ))
+        ));
+        assert_eq!(migrator.version(&storage).unwrap(), 0);
+        assert!(storage.contains("ssid").unwrap());
+    }
+
+    #[test]
+    fn migrate_interrupted() {
+        let migrator = Migrator::new(STEPS);
+        let mut storage = InMemoryStorage::new().with_atomic_updates(false);
+
+        storage.set_raw("ssid", b"home").unwrap();
+
+        // The power goes down after the journal and the first operation of the first step were written
+        storage.fail_after_writes(2);
+
+        assert!(migrator.migrate(&mut storage).is_err());
+        assert!(storage.contains(crate::storage::JOURNAL_KEY).unwrap());
+
+        storage.clear_failures();
+
+        // The interrupted step is completed from the journal, and only the second one is applied
+        assert_eq!(migrator.migrate(&mut storage).unwrap(), 1);
+        assert_eq!(migrator.version(&storage).unwrap(), 2);
+        assert!(!storage.contains("ssid").unwrap());
+        assert_eq!(value(&storage, "wifi/ssid").as_deref(), Some(&b"home"[..]));
+        assert!(!storage.contains(crate::storage::JOURNAL_KEY).unwrap());
+    }
+
+    #[test]
+    fn migrate_unknown_version() {
+        let migrator = Migrator::new(STEPS);
+        let mut storage = InMemoryStorage::new();
+
+        storage.set_raw(VERSION_KEY, &3_u32.to_le_bytes()).unwrap();
+
+        assert!(matches!(
+            migrator.migrate(&mut storage),
+            Err(MigrationError::UnknownVersion(3))
+        ));
+
+        storage.set_raw(VERSION_KEY, &[1, 0]).unwrap();
+
+        assert!(matches!(
+            migrator.version(&storage),
+            Err(MigrationError::InvalidVersion)
+        ));
+        assert!(matches!(
+            migrator.migrate(&mut storage),
+            Err(MigrationError::InvalidVersion)
+        ));
+    }
+
+    #[test]
+    fn migration_sources() {
+        type Error = MigrationError<InMemoryStorageError, core::fmt::Error>;
+
+        fn source(error: &Error) -> Option<String> {
+            std::error::Error::source(error).map(|source| source.to_string())
+        }
+
+        // Displayed as the transaction error, so its source is the one of the transaction error
+        let storage = Error::StorageError(TransactionError::StorageError(
+            InMemoryStorageError::Injected,
+        ));
+        assert_eq!(storage.to_string(), "Storage error: Injected failure");
+        assert_eq!(source(&storage).as_deref(), Some("Injected failure"));
+
+        assert_eq!(source(&Error::StorageError(TransactionError::Full)), None);
+
+        let step = Error::StepError {
+            version: 1,
+            error: core::fmt::Error,
+        };
+        assert_eq!(source(&step), Some(core::fmt::Error.to_string()));
+
+        assert_eq!(source(&Error::UnknownVersion(3)), None);
+        assert_eq!(source(&Error::InvalidVersion), None);
+    }
+}
@@ -0,0 +1,798 @@
+//! Local time on top of `sys_time::SystemTime`: time zones, and the broken-down date and time in a time zone
+//!
+//! Time zones are described by POSIX TZ strings (e.g. `CET-1CEST,M3.5.0,M10.5.0/3`), which carry the rules
+//! of their daylight saving time, so that no time zone database is needed.
+
+use core::convert::TryInto;
+use core::fmt::{self, Display};
+use core::str::FromStr;
+use core::time::Duration;
+
+use crate::sys_time::SystemTime;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The maximum length of the POSIX TZ string of a `PosixTimeZone`
+pub const MAX_SPEC_LEN: usize = 64;
+
+/// The maximum length of the abbreviations of a `PosixTimeZone` (e.g. `CEST`)
+pub const MAX_NAME_LEN: usize = 16;
+
+pub trait TimeZone {
+    /// The offset from UTC in effect at `utc` (the time since the UNIX epoch), in seconds east of UTC
+    fn offset(&self, utc: Duration) -> i32;
+}
+
+impl<Z> TimeZone for &Z
+where
+    Z: TimeZone,
+{
+    fn offset(&self, utc: Duration) -> i32 {
+        (*self).offset(utc)
+    }
+}
+
+/// A time zone with a constant offset from UTC, in seconds east of UTC
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixedOffset(pub i32);
+
+impl FixedOffset {
+    pub const UTC: Self = Self(0);
+
+    pub const fn hours(hours: i32) -> Self {
+        Self(hours * 60 * 60)
+    }
+}
+
+impl TimeZone for FixedOffset {
+    fn offset(&self, _utc: Duration) -> i32 {
+        self.0
+    }
+}
+
+/// The day of the year a daylight saving time transition happens on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransitionDay {
+    /// `Jn`: the day `n` (1 to 365) of the year, not counting February 29
+    Julian(u16),
+    /// `n`: the day `n` (0 to 365) of the year, counting February 29
+    ZeroBased(u16),
+    /// `Mm.w.d`: the weekday `d` (0 is Sunday) of the week `w` (1 to 5, 5 being the last) of the month `m`
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+}
+
+impl TransitionDay {
+    /// The days since the UNIX epoch of this day in `year`
+    fn days(&self, year: i32) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+
+        match *self {
+            Self::Julian(day) => {
+                let leap = is_leap_year(year) && day >= 60;
+
+                jan1 + day as i64 - 1 + leap as i64
+            }
+            Self::ZeroBased(day) => jan1 + day as i64,
+            Self::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                let first_weekday = weekday_from_days(first);
+
+                let mut day =
+                    (weekday as i64 - first_weekday as i64).rem_euclid(7) + (week as i64 - 1) * 7;
+
+                if day >= days_in_month(year, month) as i64 {
+                    day -= 7;
+                }
+
+                first + day
+            }
+        }
+    }
+}
+
+/// A daylight saving time transition: a day, and the local time of the day (in seconds, possibly negative or past 24h)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Transition {
+    pub day: TransitionDay,
+    pub time: i32,
+}
+
+impl Transition {
+    /// The UTC time (in seconds since the UNIX epoch) of the transition in `year`, for the `offset` in effect before it
+    fn utc(&self, year: i32, offset: i32) -> i64 {
+        self.day.days(year) * SECS_PER_DAY + self.time as i64 - offset as i64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DaylightSaving {
+    pub name: heapless::String<MAX_NAME_LEN>,
+    /// The offset in effect during daylight saving time, in seconds east of UTC
+    pub offset: i32,
+    pub start: Transition,
+    pub end: Transition,
+}
+
+/// A time zone described by a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3` for Central Europe
+///
+/// Note that the offsets of TZ strings are west of UTC (`CET-1` is one hour east of UTC),
+/// while the offsets of `TimeZone` are east of UTC, as usual.
+/// When daylight saving time has no rule (e.g. `EST5EDT`), the current US rule `M3.2.0,M11.1.0` is assumed.
+///
+/// The time zone is immutable once parsed, so that its parts cannot disagree with its TZ string,
+/// which is what it is displayed and (with the `use_serde` feature) (de)serialized as.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PosixTimeZone {
+    spec: heapless::String<MAX_SPEC_LEN>,
+    name: heapless::String<MAX_NAME_LEN>,
+    offset: i32,
+    daylight_saving: Option<DaylightSaving>,
+}
+
+impl PosixTimeZone {
+    /// The TZ string the time zone was parsed from
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    /// The abbreviation of standard time, e.g. `CET`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The offset in effect outside of daylight saving time, in seconds east of UTC
+    pub fn standard_offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// The daylight saving time of the time zone, if any
+    pub fn daylight_saving(&self) -> Option<&DaylightSaving> {
+        self.daylight_saving.as_ref()
+    }
+
+    /// Whether daylight saving time is in effect at `utc`
+    pub fn is_daylight_saving(&self, utc: Duration) -> bool {
+        let Some(dst) = &self.daylight_saving else {
+            return false;
+        };
+
+        let utc = utc.as_secs() as i64;
+
+        // The year of the local (standard) time, as the transitions are in local time
+        let (year, _, _) = civil_from_days((utc + self.offset as i64).div_euclid(SECS_PER_DAY));
+
+        let start = dst.start.utc(year, self.offset);
+        let end = dst.end.utc(year, dst.offset);
+
+        if start < end {
+            (start..end).contains(&utc)
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year
+            !(end..start).contains(&utc)
+        }
+    }
+
+    /// The abbreviation of the time zone in effect at `utc`, e.g. `CET` or `CEST`
+    pub fn abbreviation(&self, utc: Duration) -> &str {
+        match &self.daylight_saving {
+            Some(dst) if self.is_daylight_saving(utc) => &dst.name,
+            _ => &self.name,
+        }
+    }
+}
+
+impl TimeZone for PosixTimeZone {
+    fn offset(&self, utc: Duration) -> i32 {
+        match &self.daylight_saving {
+            Some(dst) if self.is_daylight_saving(utc) => dst.offset,
+            _ => self.offset,
+        }
+    }
+}
+
+impl FromStr for PosixTimeZone {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = s.try_into().map_err(|_| "Time zone too long")?;
+
+        let mut parser = Parser(s.as_bytes());
+
+        let name = parser.name()?;
+        // POSIX offsets are west of UTC
+        let offset = -parser.time()?;
+
+        let daylight_saving = if parser.0.is_empty() {
+            None
+        } else {
+            let dst_name = parser.name()?;
+
+            let dst_offset = if matches!(parser.0.first(), None | Some(b',')) {
+                offset + 60 * 60
+            } else {
+                -parser.time()?
+            };
+
+            let (start, end) = if parser.0.is_empty() {
+                (
+                    Transition {
+                        day: TransitionDay::MonthWeekDay {
+                            month: 3,
+                            week: 2,
+                            weekday: 0,
+                        },
+                        time: 2 * 60 * 60,
+                    },
+                    Transition {
+                        day: TransitionDay::MonthWeekDay {
+                            month: 11,
+                            week: 1,
+                            weekday: 0,
+                        },
+                        time: 2 * 60 * 60,
+                    },
+                )
+            } else {
+                parser.expect(b',')?;
+                let start = parser.transition()?;
+                parser.expect(b',')?;
+                let end = parser.transition()?;
+
+                (start, end)
+            };
+
+            Some(DaylightSaving {
+                name: dst_name,
+                offset: dst_offset,
+                start,
+                end,
+            })
+        };
+
+        if !parser.0.is_empty() {
+            return Err("Unexpected characters at the end of the time zone");
+        }
+
+        Ok(Self {
+            spec,
+            name,
+            offset,
+            daylight_saving,
+        })
+    }
+}
+
+impl Display for PosixTimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+struct Parser<'a>(&'a [u8]);
+
+impl<'a> Parser<'a> {
+    fn expect(&mut self, byte: u8) -> Result<(), &'static str> {
+        match self.0.split_first() {
+            Some((first, rest)) if *first == byte => {
+                self.0 = rest;
+                Ok(())
+            }
+            _ => Err("Malformed time zone"),
+        }
+    }
+
+    /// `std`/`dst`: at least 3 letters, or anything alphanumeric, `+` or `-` within `<>`
+    fn name(&mut self) -> Result<heapless::String<MAX_NAME_LEN>, &'static str> {
+        let (name, rest) = if let Some(quoted) = self.0.strip_prefix(b"<") {
+            let len = quoted
+                .iter()
+                .position(|byte| *byte == b'>')
+                .ok_or("Unterminated time zone name")?;
+
+            if !quoted[..len]
+                .iter()
+                .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'+' || *byte == b'-')
+            {
+                return Err("Invalid time zone name");
+            }
+
+            (&quoted[..len], &quoted[len + 1..])
+        } else {
+            let len = self
+                .0
+                .iter()
+                .position(|byte| !byte.is_ascii_alphabetic())
+                .unwrap_or(self.0.len());
+
+            self.0.split_at(len)
+        };
+
+        if name.len() < 3 {
+            return Err("Time zone name too short");
+        }
+
+        self.0 = rest;
+
+        // Only ASCII was accepted
+        core::str::from_utf8(name)
+            .unwrap()
+            .try_into()
+            .map_err(|_| "Time zone name too long")
+    }
+
+    /// `[+|-]hh[:mm[:ss]]`, in seconds
+    fn time(&mut self) -> Result<i32, &'static str> {
+        let negative = match self.0.first() {
+            Some(b'-') => true,
+            Some(b'+') => false,
+            _ => {
+                let digits = self.number(0, 167)?;
+                return self.minutes_seconds(digits);
+            }
+        };
+
+        self.0 = &self.0[1..];
+
+        let hours = self.number(0, 167)?;
+        let time = self.minutes_seconds(hours)?;
+
+        Ok(if negative { -time } else { time })
+    }
+
+    fn minutes_seconds(&mut self, hours: u16) -> Result<i32, &'static str> {
+        let mut time = hours as i32 * 60 * 60;
+
+        if self.0.first() == Some(&b':') {
+            self.0 = &self.0[1..];
+            time += self.number(0, 59)? as i32 * 60;
+
+            if self.0.first() == Some(&b':') {
+                self.0 = &self.0[1..];
+                time += self.number(0, 59)? as i32;
+            }
+        }
+
+        Ok(time)
+    }
+
+    fn number(&mut self, min: u16, max: u16) -> Result<u16, &'static str> {
+        let len = self
+            .0
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .unwrap_or(self.0.len());
+
+        if len == 0 || len > 3 {
+            return Err("Malformed time zone");
+        }
+
+        let (digits, rest) = self.0.split_at(len);
+
+        let number = digits
+            .iter()
+            .fold(0, |number, digit| number * 10 + (digit - b'0') as u16);
+
+        if !(min..=max).contains(&number) {
+            return Err("Time zone number out of range");
+        }
+
+        self.0 = rest;
+
+        Ok(number)
+    }
+
+    /// `date[/time]`, the time defaulting to 02:00:00
+    fn transition(&mut self) -> Result<Transition, &'static str> {
+        let day = match self.0.first() {
+            Some(b'J') => {
+                self.0 = &self.0[1..];
+                TransitionDay::Julian(self.number(1, 365)?)
+            }
+            Some(b'M') => {
+                self.0 = &self.0[1..];
+                let month = self.number(1, 12)? as u8;
+                self.expect(b'.')?;
+                let week = self.number(1, 5)? as u8;
+                self.expect(b'.')?;
+                let weekday = self.number(0, 6)? as u8;
+
+                TransitionDay::MonthWeekDay {
+                    month,
+                    week,
+                    weekday,
+                }
+            }
+            _ => TransitionDay::ZeroBased(self.number(0, 365)?),
+        };
+
+        let time = if self.0.first() == Some(&b'/') {
+            self.0 = &self.0[1..];
+            self.time()?
+        } else {
+            2 * 60 * 60
+        };
+
+        Ok(Transition { day, time })
+    }
+}
+
+/// A broken-down date and time, as shown to people
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    pub year: i32,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// 0 (Sunday) to 6 (Saturday)
+    pub weekday: u8,
+    /// The offset from UTC this date and time is in, in seconds east of UTC
+    pub offset: i32,
+}
+
+impl DateTime {
+    /// The date and time of `utc` (the time since the UNIX epoch) in time zone `tz`
+    pub fn from_utc<Z>(utc: Duration, tz: &Z) -> Self
+    where
+        Z: TimeZone,
+    {
+        let offset = tz.offset(utc);
+        let local = utc.as_secs() as i64 + offset as i64;
+
+        let days = local.div_euclid(SECS_PER_DAY);
+        let secs = local.rem_euclid(SECS_PER_DAY);
+
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+            weekday: weekday_from_days(days),
+            offset,
+        }
+    }
+
+    /// The current date and time of `clock` in time zone `tz`
+    pub fn now<S, Z>(clock: &S, tz: &Z) -> Self
+    where
+        S: SystemTime,
+        Z: TimeZone,
+    {
+        Self::from_utc(clock.now(), tz)
+    }
+}
+
+/// Formats as ISO 8601, e.g. `2024-03-31T03:00:00+02:00`
+impl Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs() / 60;
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            sign,
+            offset / 60,
+            offset % 60
+        )
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn weekday_from_days(days: i64) -> u8 {
+    // 1970-01-01 was a Thursday
+    (days + 4).rem_euclid(7) as u8
+}
+
+// The days since the UNIX epoch from a proleptic Gregorian date, and back,
+// as per http://howardhinnant.github.io/date_algorithms.html
+
+fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (year as i32, month, day)
+}
+
+#[cfg(feature = "use_serde")]
+mod serde_impl {
+    use core::fmt;
+
+    use serde::de::{self, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::PosixTimeZone;
+
+    impl Serialize for PosixTimeZone {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_str(self.spec())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for PosixTimeZone {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct PosixTimeZoneVisitor;
+
+            impl<'de> Visitor<'de> for PosixTimeZoneVisitor {
+                type Value = PosixTimeZone;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a POSIX TZ string")
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            deserializer.deserialize_str(PosixTimeZoneVisitor)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    const CET: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    const EST: &str = "EST5EDT";
+    const AEST: &str = "AEST-10AEDT,M10.1.0,M4.1.0/3";
+
+    fn utc(year: i32, month: u8, day: u8, hour: u8) -> Duration {
+        let secs = days_from_civil(year, month, day) * SECS_PER_DAY + hour as i64 * 60 * 60;
+
+        Duration::from_secs(secs as u64)
+    }
+
+    /// Checks the local time and the abbreviation just before the transition at `utc`, and at the transition
+    fn assert_transition(tz: &str, utc: Duration, before: (&str, &str), at: (&str, &str)) {
+        let tz: PosixTimeZone = tz.parse().unwrap();
+        let just_before = utc - Duration::from_secs(1);
+
+        assert_eq!(DateTime::from_utc(just_before, &tz).to_string(), before.0);
+        assert_eq!(tz.abbreviation(just_before), before.1);
+
+        assert_eq!(DateTime::from_utc(utc, &tz).to_string(), at.0);
+        assert_eq!(tz.abbreviation(utc), at.1);
+
+        assert_ne!(
+            tz.is_daylight_saving(just_before),
+            tz.is_daylight_saving(utc)
+        );
+    }
+
+    #[test]
+    fn transitions_central_europe() {
+        assert_transition(
+            CET,
+            utc(2024, 3, 31, 1),
+            ("2024-03-31T01:59:59+01:00", "CET"),
+            ("2024-03-31T03:00:00+02:00", "CEST"),
+        );
+        assert_transition(
+            CET,
+            utc(2024, 10, 27, 1),
+            ("2024-10-27T02:59:59+02:00", "CEST"),
+            ("2024-10-27T02:00:00+01:00", "CET"),
+        );
+    }
+
+    #[test]
+    fn transitions_us_default_rule() {
+        assert_transition(
+            EST,
+            utc(2024, 3, 10, 7),
+            ("2024-03-10T01:59:59-05:00", "EST"),
+            ("2024-03-10T03:00:00-04:00", "EDT"),
+        );
+        assert_transition(
+            EST,
+            utc(2024, 11, 3, 6),
+            ("2024-11-03T01:59:59-04:00", "EDT"),
+            ("2024-11-03T01:00:00-05:00", "EST"),
+        );
+    }
+
+    #[test]
+    fn transitions_southern_hemisphere() {
+        assert_transition(
+            AEST,
+            utc(2024, 4, 6, 16),
+            ("2024-04-07T02:59:59+11:00", "AEDT"),
+            ("2024-04-07T02:00:00+10:00", "AEST"),
+        );
+        assert_transition(
+            AEST,
+            utc(2024, 10, 5, 16),
+            ("2024-10-06T01:59:59+10:00", "AEST"),
+            ("2024-10-06T03:00:00+11:00", "AEDT"),
+        );
+
+        // Daylight saving time spans the new year
+        let tz: PosixTimeZone = AEST.parse().unwrap();
+        assert!(tz.is_daylight_saving(utc(2024, 1, 1, 0)));
+        assert!(!tz.is_daylight_saving(utc(2024, 7, 1, 0)));
+    }
+
+    #[test]
+    fn parse() {
+        let tz: PosixTimeZone = CET.parse().unwrap();
+
+        assert_eq!(tz.spec(), CET);
+        assert_eq!(tz.to_string(), CET);
+        assert_eq!(tz.name(), "CET");
+        assert_eq!(tz.standard_offset(), 60 * 60);
+
+        let dst = tz.daylight_saving().unwrap();
+        assert_eq!(dst.name, "CEST");
+        assert_eq!(dst.offset, 2 * 60 * 60);
+        assert_eq!(
+            dst.start,
+            Transition {
+                day: TransitionDay::MonthWeekDay {
+                    month: 3,
+                    week: 5,
+                    weekday: 0,
+                },
+                time: 2 * 60 * 60,
+            }
+        );
+        assert_eq!(dst.end.time, 3 * 60 * 60);
+
+        let tz: PosixTimeZone = EST.parse().unwrap();
+
+        assert_eq!(tz.standard_offset(), -5 * 60 * 60);
+
+        let dst = tz.daylight_saving().unwrap();
+        assert_eq!(dst.offset, -4 * 60 * 60);
+        assert_eq!(
+            dst.end.day,
+            TransitionDay::MonthWeekDay {
+                month: 11,
+                week: 1,
+                weekday: 0,
+            }
+        );
+
+        let tz: PosixTimeZone = "<+0330>-3:30".parse().unwrap();
+
+        assert_eq!(tz.name(), "+0330");
+        assert_eq!(tz.standard_offset(), 3 * 60 * 60 + 30 * 60);
+        assert!(tz.daylight_saving().is_none());
+        assert_eq!(tz.offset(utc(2024, 7, 1, 0)), tz.standard_offset());
+
+        let tz: PosixTimeZone = "IST-2IDT,J80/26,300/-1".parse().unwrap();
+        let dst = tz.daylight_saving().unwrap();
+
+        assert_eq!(dst.start.day, TransitionDay::Julian(80));
+        assert_eq!(dst.start.time, 26 * 60 * 60);
+        assert_eq!(dst.end.day, TransitionDay::ZeroBased(300));
+        assert_eq!(dst.end.time, -60 * 60);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for spec in [
+            "",
+            "CE-1",
+            "CET",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0/3",
+            "CET-1CEST,M3.5.7,M10.5.0/3",
+            "CET-1CE",
+            "<CET-1",
+            "CET-1 ",
+        ] {
+            assert!(spec.parse::<PosixTimeZone>().is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn fixed_offset() {
+        let date_time = DateTime::from_utc(utc(2024, 2, 29, 3), &FixedOffset::hours(-5));
+
+        assert_eq!(date_time.to_string(), "2024-02-28T22:00:00-05:00");
+        assert_eq!(date_time.weekday, 3);
+
+        assert_eq!(
+            DateTime::from_utc(Duration::ZERO, &FixedOffset::UTC).to_string(),
+            "1970-01-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn civil_days() {
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+
+        assert_eq!(
+            civil_from_days(days_from_civil(2024, 2, 29) + 1),
+            (2024, 3, 1)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil(2100, 2, 28) + 1),
+            (2100, 3, 1)
+        );
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn serde() {
+        use crate::test_json::{from_slice, to_vec};
+
+        let tz: PosixTimeZone = CET.parse().unwrap();
+
+        assert_eq!(to_vec(&tz), format!("\"{}\"", CET).as_bytes());
+        assert_eq!(from_slice::<PosixTimeZone>(&to_vec(&tz)).unwrap(), tz);
+
+        assert!(from_slice::<PosixTimeZone>(b"\"CET-1CEST,M3\"").is_err());
+    }
+}
@@ -0,0 +1,691 @@
+use core::time::Duration;
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "use_serde")]
+use serde::Serialize;
+
+#[cfg(feature = "alloc")]
+use crate::sys_time::Monotonic;
+#[cfg(feature = "alloc")]
+use crate::timer::{ErrorType, OnceTimer, PeriodicTimer, Timer, TimerCapabilities, TimerService};
+#[cfg(feature = "alloc")]
+use crate::utils::mutex::{Mutex, RawMutex};
+
+/// Returns the first instant strictly after `now` which is `offset` past a multiple of `period`
+///
+/// E.g. with a `period` of 15 minutes and a zero `offset`, returns the next quarter of an hour
+/// of a wall clock counting from the Unix epoch.
+/// If `period` is zero, returns `now`.
+pub fn next_aligned(now: Duration, period: Duration, offset: Duration) -> Duration {
+    let period = period.as_nanos();
+
+    if period == 0 {
+        return now;
+    }
+
+    let now = now.as_nanos();
+    let base = offset.as_nanos() % period;
+
+    let next = if now < base {
+        base
+    } else {
+        base + ((now - base) / period + 1) * period
+    };
+
+    Duration::new((next / 1_000_000_000) as u64, (next % 1_000_000_000) as u32)
+}
+
+/// A schedule firing at the boundaries computed by `next_aligned`, for timers driven by a clock
+/// which might be stepped (e.g. by SNTP) between scheduling and expiry
+///
+/// Call `poll` with the current time initially and on each expiry of the timer,
+/// and re-arm the timer with the returned duration, if any.
+///
+/// If the clock was stepped back by more than a period, the pending boundary is dropped and the next one
+/// is computed from the new time. To notice a clock which was stepped forward before the timer expires,
+/// cap the waits with `with_max_wait`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlignedSchedule {
+    period: Duration,
+    offset: Duration,
+    max_wait: Option<Duration>,
+    deadline: Option<Duration>,
+}
+
+impl AlignedSchedule {
+    pub const fn new(period: Duration, offset: Duration) -> Self {
+        Self {
+            period,
+            offset,
+            max_wait: None,
+            deadline: None,
+        }
+    }
+
+    /// Caps the durations returned by `poll`, so that the clock is re-checked at least every `max_wait`
+    pub const fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+
+    /// The pending boundary, if `poll` was called since the last one was reached
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Returns `None` if the pending boundary was reached, or else how long to wait before polling again
+    ///
+    /// If no boundary is pending, the next one after `now` becomes pending.
+    pub fn poll(&mut self, now: Duration) -> Option<Duration> {
+        let deadline = match self.deadline {
+            Some(deadline) if deadline.saturating_sub(now) <= self.period => deadline,
+            _ => next_aligned(now, self.period, self.offset),
+        };
+
+        if now >= deadline {
+            self.deadline = None;
+
+            None
+        } else {
+            self.deadline = Some(deadline);
+
+            let remaining = deadline - now;
+
+            Some(
+                self.max_wait
+                    .map_or(remaining, |max_wait| remaining.min(max_wait)),
+            )
+        }
+    }
+
+    /// Completes when the pending boundary (or the next one after the current time, if none is pending) is reached
+    #[cfg(feature = "nightly")]
+    pub async fn wait<T, C>(&mut self, timer: &mut T, clock: &C) -> Result<(), T::Error>
+    where
+        T: crate::timer::asynch::OnceTimer,
+        C: crate::sys_time::SystemTime,
+    {
+        while let Some(wait) = self.poll(clock.now()) {
+            timer.after(wait).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The statistics of a timer created by `NamedTimerService`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize))]
+pub struct TimerStats {
+    pub name: &'static str,
+    /// The period of the timer, if it was last scheduled with `every`
+    pub period: Option<Duration>,
+    /// The time of the next expiry, if the timer is scheduled
+    pub deadline: Option<Duration>,
+    pub fires: u32,
+    pub last_fire: Option<Duration>,
+}
+
+/// The statistics of the timers of a `NamedTimerService`, as returned by `NamedTimerService::snapshot`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize))]
+pub struct TimersSnapshot<const N: usize> {
+    pub timers: heapless::Vec<TimerStats, N>,
+    /// How many existing timers are not tracked, because more than `N` timers were created
+    pub untracked: usize,
+}
+
+#[cfg(feature = "alloc")]
+struct Registry<const N: usize> {
+    timers: heapless::Vec<(u32, TimerStats), N>,
+    next_id: u32,
+    untracked: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<const N: usize> Registry<N> {
+    fn stats(&mut self, id: Option<u32>) -> Option<&mut TimerStats> {
+        let id = id?;
+
+        self.timers
+            .iter_mut()
+            .find(|(timer_id, _)| *timer_id == id)
+            .map(|(_, stats)| stats)
+    }
+}
+
+/// A `TimerService` decorator keeping the statistics of (at most `N` of) the timers it creates
+///
+/// The timers are named when created with `named_timer`. The times are taken from `clock`.
+/// The wrapped service remains usable directly, for timers which do not need to be diagnosed.
+#[cfg(feature = "alloc")]
+pub struct NamedTimerService<S, C, R, const N: usize = 16>
+where
+    R: RawMutex,
+{
+    service: S,
+    clock: C,
+    registry: Arc<Mutex<R, Registry<N>>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<S, C, R, const N: usize> NamedTimerService<S, C, R, N>
+where
+    S: TimerService,
+    C: Monotonic + Clone + Send + 'static,
+    R: RawMutex + Send + Sync + 'static,
+{
+    pub fn new(service: S, clock: C) -> Self {
+        Self {
+            service,
+            clock,
+            registry: Arc::new(Mutex::new(Registry {
+                timers: heapless::Vec::new(),
+                next_id: 0,
+                untracked: 0,
+            })),
+        }
+    }
+
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    pub fn release(self) -> S {
+        self.service
+    }
+
+    pub fn named_timer<F>(
+        &self,
+        name: &'static str,
+        mut callback: F,
+    ) -> Result<NamedTimer<S::Timer<'_>, C, R, N>, S::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let id = {
+            let mut registry = self.registry.lock();
+
+            let id = registry.next_id;
+            registry.next_id = registry.next_id.wrapping_add(1);
+
+            let stats = TimerStats {
+                name,
+                period: None,
+                deadline: None,
+                fires: 0,
+                last_fire: None,
+            };
+
+            if registry.timers.push((id, stats)).is_ok() {
+                Some(id)
+            } else {
+                registry.untracked += 1;
+                None
+            }
+        };
+
+        let timer = {
+            let registry = self.registry.clone();
+            let clock = self.clock.clone();
+
+            self.service.timer(move || {
+                {
+                    let mut registry = registry.lock();
+
+                    if let Some(stats) = registry.stats(id) {
+                        let now = clock.now();
+
+                        stats.fires = stats.fires.saturating_add(1);
+                        stats.last_fire = Some(now);
+                        stats.deadline = stats.period.map(|period| now + period);
+                    }
+                }
+
+                callback();
+            })
+        };
+
+        match timer {
+            Ok(timer) => Ok(NamedTimer {
+                timer,
+                id,
+                clock: self.clock.clone(),
+                registry: self.registry.clone(),
+            }),
+            Err(e) => {
+                remove(&self.registry, id);
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the statistics of the existing timers, in the order they were created
+    pub fn snapshot(&self) -> TimersSnapshot<N> {
+        let registry = self.registry.lock();
+
+        TimersSnapshot {
+            timers: registry
+                .timers
+                .iter()
+                .map(|(_, stats)| stats.clone())
+                .collect(),
+            untracked: registry.untracked,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn remove<R, const N: usize>(registry: &Mutex<R, Registry<N>>, id: Option<u32>)
+where
+    R: RawMutex,
+{
+    let mut registry = registry.lock();
+
+    if let Some(id) = id {
+        if let Some(index) = registry
+            .timers
+            .iter()
+            .position(|(timer_id, _)| *timer_id == id)
+        {
+            registry.timers.remove(index);
+        }
+    } else {
+        registry.untracked -= 1;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S, C, R, const N: usize> ErrorType for NamedTimerService<S, C, R, N>
+where
+    S: ErrorType,
+    R: RawMutex,
+{
+    type Error = S::Error;
+}
+
+#[cfg(feature = "alloc")]
+impl<S, C, R, const N: usize> TimerService for NamedTimerService<S, C, R, N>
+where
+    S: TimerService,
+    C: Monotonic + Clone + Send + 'static,
+    R: RawMutex + Send + Sync + 'static,
+{
+    type Timer<'a> = NamedTimer<S::Timer<'a>, C, R, N> where Self: 'a;
+
+    fn timer<F>(&self, callback: F) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.named_timer("", callback)
+    }
+
+    fn capabilities(&self) -> TimerCapabilities {
+        self.service.capabilities()
+    }
+}
+
+/// A timer created by `NamedTimerService`
+#[cfg(feature = "alloc")]
+pub struct NamedTimer<T, C, R, const N: usize>
+where
+    R: RawMutex,
+{
+    timer: T,
+    id: Option<u32>,
+    clock: C,
+    registry: Arc<Mutex<R, Registry<N>>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> NamedTimer<T, C, R, N>
+where
+    C: Monotonic,
+    R: RawMutex,
+{
+    fn schedule(&self, period: Option<Duration>, duration: Option<Duration>) {
+        let deadline = duration.map(|duration| self.clock.now() + duration);
+
+        if let Some(stats) = self.registry.lock().stats(self.id) {
+            stats.period = period;
+            stats.deadline = deadline;
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> Drop for NamedTimer<T, C, R, N>
+where
+    R: RawMutex,
+{
+    fn drop(&mut self) {
+        remove(&self.registry, self.id);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> ErrorType for NamedTimer<T, C, R, N>
+where
+    T: ErrorType,
+    R: RawMutex,
+{
+    type Error = T::Error;
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> Timer for NamedTimer<T, C, R, N>
+where
+    T: Timer,
+    C: Monotonic,
+    R: RawMutex,
+{
+    fn is_scheduled(&self) -> Result<bool, Self::Error> {
+        self.timer.is_scheduled()
+    }
+
+    fn cancel(&mut self) -> Result<bool, Self::Error> {
+        let cancelled = self.timer.cancel()?;
+
+        self.schedule(None, None);
+
+        Ok(cancelled)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> OnceTimer for NamedTimer<T, C, R, N>
+where
+    T: OnceTimer,
+    C: Monotonic,
+    R: RawMutex,
+{
+    fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.timer.after(duration)?;
+
+        self.schedule(None, Some(duration));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, C, R, const N: usize> PeriodicTimer for NamedTimer<T, C, R, N>
+where
+    T: PeriodicTimer,
+    C: Monotonic,
+    R: RawMutex,
+{
+    fn every(&mut self, duration: Duration) -> Result<(), Self::Error> {
+        self.timer.every(duration)?;
+
+        self.schedule(Some(duration), Some(duration));
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use crate::test_timer::MockTimerService;
+    use crate::utils::mutex::StdRawMutex;
+
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn next_aligned_boundaries() {
+        let quarter = MINUTE * 15;
+
+        assert_eq!(
+            next_aligned(MINUTE * 7, quarter, Duration::ZERO),
+            MINUTE * 15
+        );
+        // Strictly after `now`
+        assert_eq!(
+            next_aligned(MINUTE * 15, quarter, Duration::ZERO),
+            MINUTE * 30
+        );
+        assert_eq!(
+            next_aligned(MINUTE * 15 - MS, quarter, Duration::ZERO),
+            MINUTE * 15
+        );
+
+        // Five minutes past each quarter, with an offset larger than the period
+        assert_eq!(next_aligned(MINUTE * 7, quarter, MINUTE * 20), MINUTE * 20);
+        assert_eq!(next_aligned(MINUTE * 2, quarter, MINUTE * 20), MINUTE * 5);
+        assert_eq!(next_aligned(MINUTE * 5, quarter, MINUTE * 5), MINUTE * 20);
+
+        // Sub-second periods
+        assert_eq!(
+            next_aligned(Duration::new(1, 999_999_999), MS * 250, Duration::ZERO),
+            Duration::from_secs(2)
+        );
+
+        assert_eq!(next_aligned(MINUTE * 7, Duration::ZERO, MINUTE), MINUTE * 7);
+    }
+
+    #[test]
+    fn aligned_schedule() {
+        let mut schedule = AlignedSchedule::new(MINUTE * 15, Duration::ZERO);
+
+        assert_eq!(schedule.deadline(), None);
+
+        assert_eq!(schedule.poll(MINUTE * 7), Some(MINUTE * 8));
+        assert_eq!(schedule.deadline(), Some(MINUTE * 15));
+
+        // Expired early, e.g. because the clock was slewed
+        assert_eq!(schedule.poll(MINUTE * 14), Some(MINUTE));
+
+        assert_eq!(schedule.poll(MINUTE * 15), None);
+        assert_eq!(schedule.deadline(), None);
+
+        // The next boundary
+        assert_eq!(schedule.poll(MINUTE * 15), Some(MINUTE * 15));
+        assert_eq!(schedule.deadline(), Some(MINUTE * 30));
+
+        // Reached late, e.g. because the clock was stepped forward
+        assert_eq!(schedule.poll(MINUTE * 42), None);
+    }
+
+    #[test]
+    fn aligned_schedule_stepped_back() {
+        let mut schedule = AlignedSchedule::new(MINUTE * 15, Duration::ZERO);
+
+        assert_eq!(schedule.poll(MINUTE * 62), Some(MINUTE * 13));
+
+        // Stepped back by less than a period: the pending boundary is kept
+        assert_eq!(schedule.poll(MINUTE * 61), Some(MINUTE * 14));
+        assert_eq!(schedule.deadline(), Some(MINUTE * 75));
+
+        // Stepped back by more than a period: the next boundary is computed from the new time
+        assert_eq!(schedule.poll(MINUTE * 32), Some(MINUTE * 13));
+        assert_eq!(schedule.deadline(), Some(MINUTE * 45));
+    }
+
+    #[test]
+    fn aligned_schedule_max_wait() {
+        let mut schedule = AlignedSchedule::new(MINUTE * 15, MINUTE * 5).with_max_wait(MINUTE * 4);
+
+        assert_eq!(schedule.period(), MINUTE * 15);
+        assert_eq!(schedule.offset(), MINUTE * 5);
+
+        assert_eq!(schedule.poll(MINUTE * 6), Some(MINUTE * 4));
+        assert_eq!(schedule.poll(MINUTE * 10), Some(MINUTE * 4));
+        assert_eq!(schedule.poll(MINUTE * 18), Some(MINUTE * 2));
+        assert_eq!(schedule.poll(MINUTE * 20), None);
+    }
+
+    type Service = NamedTimerService<MockTimerService, MockTimerService, StdRawMutex, 2>;
+
+    fn service() -> (MockTimerService, Service) {
+        let service = MockTimerService::new();
+
+        (
+            service.clone(),
+            NamedTimerService::new(service.clone(), service),
+        )
+    }
+
+    fn stats(
+        name: &'static str,
+        period: Option<Duration>,
+        deadline: Option<Duration>,
+        fires: u32,
+        last_fire: Option<Duration>,
+    ) -> TimerStats {
+        TimerStats {
+            name,
+            period,
+            deadline,
+            fires,
+            last_fire,
+        }
+    }
+
+    #[test]
+    fn named_timers() {
+        let (clock, service) = service();
+
+        let mut blink = service.named_timer("blink", || ()).unwrap();
+        let mut once = service.named_timer("once", || ()).unwrap();
+
+        assert_eq!(
+            service.snapshot().timers,
+            [
+                stats("blink", None, None, 0, None),
+                stats("once", None, None, 0, None),
+            ]
+        );
+
+        blink.every(MS * 10).unwrap();
+        once.after(MS * 25).unwrap();
+
+        clock.advance(MS * 30);
+
+        assert_eq!(
+            service.snapshot().timers,
+            [
+                stats("blink", Some(MS * 10), Some(MS * 40), 3, Some(MS * 30)),
+                stats("once", None, None, 1, Some(MS * 25)),
+            ]
+        );
+
+        assert!(blink.cancel().unwrap());
+        once.after(MS * 5).unwrap();
+
+        assert_eq!(
+            service.snapshot().timers,
+            [
+                stats("blink", None, None, 3, Some(MS * 30)),
+                stats("once", None, Some(MS * 35), 1, Some(MS * 25)),
+            ]
+        );
+        assert!(once.is_scheduled().unwrap());
+    }
+
+    #[test]
+    fn named_timers_untracked() {
+        let (_, service) = service();
+
+        let first = service.named_timer("first", || ()).unwrap();
+        // Through `TimerService`, unnamed
+        let second = service.timer(|| ()).unwrap();
+
+        {
+            let _third = service.named_timer("third", || ()).unwrap();
+
+            let snapshot = service.snapshot();
+
+            assert_eq!(snapshot.timers.len(), 2);
+            assert_eq!(snapshot.timers[1].name, "");
+            assert_eq!(snapshot.untracked, 1);
+        }
+
+        assert_eq!(service.snapshot().untracked, 0);
+
+        // The slot of a dropped timer is reused
+        core::mem::drop(first);
+
+        let _fourth = service.named_timer("fourth", || ()).unwrap();
+
+        let snapshot = service.snapshot();
+
+        assert_eq!(
+            snapshot
+                .timers
+                .iter()
+                .map(|stats| stats.name)
+                .collect::<Vec<_>>(),
+            ["", "fourth"]
+        );
+        assert_eq!(snapshot.untracked, 0);
+
+        core::mem::drop(second);
+    }
+
+    #[test]
+    fn named_timers_callbacks() {
+        let (clock, service) = service();
+
+        let fires = std::sync::Arc::new(AtomicU32::new(0));
+
+        let mut timer = {
+            let fires = fires.clone();
+
+            service
+                .named_timer("counted", move || {
+                    fires.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap()
+        };
+
+        timer.every(MS * 10).unwrap();
+        clock.advance(MS * 20);
+
+        assert_eq!(fires.load(Ordering::SeqCst), 2);
+        assert_eq!(service.snapshot().timers[0].fires, 2);
+        assert_eq!(service.service().scheduled(), 1);
+    }
+
+    #[cfg(feature = "use_serde")]
+    #[test]
+    fn snapshot_serde() {
+        let (clock, service) = service();
+
+        let mut timer = service.named_timer("blink", || ()).unwrap();
+
+        timer.every(MS * 10).unwrap();
+        clock.advance(MS * 10);
+
+        let json = crate::test_json::to_vec(&service.snapshot());
+
+        assert_eq!(
+            core::str::from_utf8(&json).unwrap(),
+            concat!(
+                r#"{"timers":[{"name":"blink","period":{"secs":0,"nanos":10000000},"#,
+                r#""deadline":{"secs":0,"nanos":20000000},"fires":1,"#,
+                r#""last_fire":{"secs":0,"nanos":10000000}}],"untracked":0}"#,
+            )
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use core::fmt;
+use core::time::Duration;
+
+/// How long `sleep_until` sleeps at most before re-checking the wall clock
+pub const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How far past the target `sleep_until` may wake up and still report `Wakeup::Reached`
+pub const STEP_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// How many times `sleep_until` re-arms the timer for the time remaining, when the timer expired
+/// before the target but not because its sleep was capped to `MAX_SLEEP`
+pub const MAX_REARMS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wakeup {
+    /// The target was reached as scheduled
+    Reached,
+    /// The wall clock was found past the target by more than `STEP_TOLERANCE`, because it was stepped forward,
+    /// or because the target was already in the past
+    Passed(Duration),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepError<E, S = core::convert::Infallible> {
+    TimerError(E),
+    /// The timer kept expiring before the target, e.g. because the wall clock kept being stepped backwards
+    TooManyRearms,
+    /// Subscribing to the steps of the wall clock failed
+    StepsError(S),
+}
+
+impl<E, S> fmt::Display for SleepError<E, S>
+where
+    E: fmt::Display,
+    S: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TimerError(e) => write!(f, "Timer error: {e}"),
+            Self::TooManyRearms => write!(f, "Too many re-arms"),
+            Self::StepsError(e) => write!(f, "Clock steps error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E, S> std::error::Error for SleepError<E, S>
+where
+    E: fmt::Display + fmt::Debug,
+    S: fmt::Display + fmt::Debug,
+{
+}
+
+/// Sleeps until `system_time` reaches `target`, a duration since the Unix epoch
+///
+/// The wall clock is re-checked every `MAX_SLEEP` and on each expiry of `timer`, so that steps of the clock
+/// (e.g. by SNTP) in either direction are accounted for.
+#[cfg(feature = "nightly")]
+pub async fn sleep_until<S, T>(
+    system_time: &S,
+    timer: &mut T,
+    target: Duration,
+) -> Result<Wakeup, SleepError<T::Error>>
+where
+    S: crate::sys_time::SystemTime,
+    T: crate::timer::asynch::OnceTimer,
+{
+    let mut rearms = 0;
+    let mut capped = true;
+
+    loop {
+        let now = system_time.now();
+
+        if let Some(wakeup) = wakeup(now, target) {
+            break Ok(wakeup);
+        }
+
+        if !capped {
+            if rearms == MAX_REARMS {
+                break Err(SleepError::TooManyRearms);
+            }
+
+            rearms += 1;
+        }
+
+        let remaining = target - now;
+        capped = remaining > MAX_SLEEP;
+
+        timer
+            .after(remaining.min(MAX_SLEEP))
+            .await
+            .map_err(SleepError::TimerError)?;
+    }
+}
+
+/// Like `sleep_until`, but re-checks the wall clock as soon as it is stepped, rather than with the next `MAX_SLEEP`
+/// re-check, by subscribing to its steps
+#[cfg(all(
+    feature = "nightly",
+    feature = "alloc",
+    feature = "asyncify",
+    target_has_atomic = "ptr"
+))]
+pub async fn sleep_until_stepped<R, S, T>(
+    system_time: &S,
+    timer: &mut T,
+    target: Duration,
+) -> Result<Wakeup, SleepError<T::Error, S::Error>>
+where
+    R: crate::utils::mutex::RawMutex + Send + Sync + 'static,
+    S: crate::sys_time::SystemTime + crate::sys_time::SystemTimeSteps,
+    T: crate::timer::asynch::OnceTimer,
+{
+    use core::future::poll_fn;
+    use core::pin::pin;
+    use core::task::{Poll, Waker};
+
+    use alloc::sync::Arc;
+
+    use crate::utils::asyncify::select::{select2, Either};
+    use crate::utils::mutex::Mutex;
+
+    struct StepState {
+        stepped: bool,
+        waker: Option<Waker>,
+    }
+
+    let state = Arc::new(Mutex::<R, _>::new(StepState {
+        stepped: false,
+        waker: None,
+    }));
+
+    let callback_state = state.clone();
+
+    let _subscription = system_time
+        .subscribe(move |_| {
+            let mut state = callback_state.lock();
+
+            state.stepped = true;
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        })
+        .map_err(SleepError::StepsError)?;
+
+    let mut rearms = 0;
+    let mut capped = true;
+
+    loop {
+        // Clear before reading the clock, so that a step in between is not missed
+        state.lock().stepped = false;
+
+        let now = system_time.now();
+
+        if let Some(wakeup) = wakeup(now, target) {
+            break Ok(wakeup);
+        }
+
+        if !capped {
+            if rearms == MAX_REARMS {
+                break Err(SleepError::TooManyRearms);
+            }
+
+            rearms += 1;
+        }
+
+        let remaining = target - now;
+        capped = remaining > MAX_SLEEP;
+
+        let stepped = poll_fn(|cx| {
+            let mut state = state.lock();
+
+            if state.stepped {
+                Poll::Ready(())
+            } else {
+                state.waker = Some(cx.waker().clone());
+
+                Poll::Pending
+            }
+        });
+
+        match select2(pin!(timer.after(remaining.min(MAX_SLEEP))), pin!(stepped)).await {
+            Either::First((result, _)) => result.map_err(SleepError::TimerError)?,
+            // Waking up early because of a step is expected, so it does not count as a re-arm
+            Either::Second(_) => capped = true,
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+fn wakeup(now: Duration, target: Duration) -> Option<Wakeup> {
+    if now >= target {
+        let late = now - target;
+
+        Some(if late > STEP_TOLERANCE {
+            Wakeup::Passed(late)
+        } else {
+            Wakeup::Reached
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "nightly", feature = "std"))]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use crate::sys_time::SystemTime;
+    use crate::timer::asynch::{ErrorType, OnceTimer};
+    use crate::utils::asyncify::block_on;
+
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    struct Clock(Cell<Duration>);
+
+    impl SystemTime for Clock {
+        fn now(&self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    /// Expires right away, moving `clock` to the time returned by `expiry` for the current time and the duration waited for
+    struct ScriptedTimer<'a, F> {
+        clock: &'a Clock,
+        waits: Vec<Duration>,
+        expiry: F,
+    }
+
+    impl<'a, F> ErrorType for ScriptedTimer<'a, F> {
+        type Error = Infallible;
+    }
+
+    impl<'a, F> OnceTimer for ScriptedTimer<'a, F>
+    where
+        F: FnMut(Duration, Duration) -> Duration,
+    {
+        async fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+            self.waits.push(duration);
+            self.clock.0.set((self.expiry)(self.clock.now(), duration));
+
+            Ok(())
+        }
+    }
+
+    fn sleep<F>(
+        now: Duration,
+        target: Duration,
+        expiry: F,
+    ) -> (Result<Wakeup, SleepError<Infallible>>, Vec<Duration>)
+    where
+        F: FnMut(Duration, Duration) -> Duration,
+    {
+        let clock = Clock(Cell::new(now));
+
+        let mut timer = ScriptedTimer {
+            clock: &clock,
+            waits: Vec::new(),
+            expiry,
+        };
+
+        let result = block_on(sleep_until(&clock, &mut timer, target));
+
+        (result, timer.waits)
+    }
+
+    #[test]
+    fn reached() {
+        let (result, waits) = sleep(SEC * 1000, SEC * 1150, |now, waited| now + waited);
+
+        assert!(matches!(result, Ok(Wakeup::Reached)));
+        assert_eq!(waits, [MAX_SLEEP, MAX_SLEEP, SEC * 30]);
+
+        // Late, but within the tolerance
+        let (result, _) = sleep(SEC * 1000, SEC * 1010, |now, waited| {
+            now + waited + STEP_TOLERANCE
+        });
+
+        assert!(matches!(result, Ok(Wakeup::Reached)));
+    }
+
+    #[test]
+    fn stepped_forward() {
+        let (result, waits) = sleep(SEC * 1000, SEC * 1150, |_, _| SEC * 1160);
+
+        assert!(matches!(result, Ok(Wakeup::Passed(passed)) if passed == SEC * 10));
+        assert_eq!(waits, [MAX_SLEEP]);
+
+        // Already in the past
+        let (result, waits) = sleep(SEC * 1000, SEC * 900, |now, waited| now + waited);
+
+        assert!(matches!(result, Ok(Wakeup::Passed(passed)) if passed == SEC * 100));
+        assert!(waits.is_empty());
+    }
+
+    #[test]
+    fn stepped_back() {
+        let mut stepped = false;
+
+        let (result, waits) = sleep(SEC * 1000, SEC * 1100, |now, waited| {
+            if stepped {
+                now + waited
+            } else {
+                stepped = true;
+
+                now + waited - SEC * 30
+            }
+        });
+
+        assert!(matches!(result, Ok(Wakeup::Reached)));
+        assert_eq!(waits, [MAX_SLEEP, MAX_SLEEP, SEC * 10]);
+    }
+
+    #[test]
+    fn too_many_rearms() {
+        // The clock keeps being stepped back by half the time waited for
+        let (result, waits) = sleep(SEC * 1000, SEC * 1010, |now, waited| now + waited / 2);
+
+        assert!(matches!(result, Err(SleepError::TooManyRearms)));
+        assert_eq!(waits.len(), MAX_REARMS + 1);
+
+        // Capped sleeps do not count as re-arms
+        let (result, waits) = sleep(SEC * 1000, SEC * 5000, |now, waited| now + waited);
+
+        assert!(matches!(result, Ok(Wakeup::Reached)));
+        assert!(waits.len() > MAX_REARMS);
+    }
+
+    #[cfg(feature = "asyncify")]
+    mod stepped {
+        use core::convert::Infallible;
+        use core::future::pending;
+        use core::time::Duration;
+
+        use crate::sys_time::{SimulatedSystemTime, SystemTime, SystemTimeSetter};
+        use crate::timer::asynch::{ErrorType, OnceTimer};
+        use crate::utils::asyncify::block_on;
+        use crate::utils::mutex::StdRawMutex;
+        use crate::utils::wallclock::{
+            sleep_until_stepped, SleepError, Wakeup, MAX_REARMS, MAX_SLEEP,
+        };
+
+        use super::SEC;
+
+        /// Calls `on_wait` with the clock and the duration waited for, and expires right away if it returns `true`,
+        /// or never otherwise
+        struct SteppingTimer<'a, F> {
+            clock: &'a SimulatedSystemTime,
+            waits: Vec<Duration>,
+            on_wait: F,
+        }
+
+        impl<'a, F> ErrorType for SteppingTimer<'a, F> {
+            type Error = Infallible;
+        }
+
+        impl<'a, F> OnceTimer for SteppingTimer<'a, F>
+        where
+            F: FnMut(&SimulatedSystemTime, Duration) -> bool,
+        {
+            async fn after(&mut self, duration: Duration) -> Result<(), Self::Error> {
+                self.waits.push(duration);
+
+                if !(self.on_wait)(self.clock, duration) {
+                    pending::<()>().await;
+                }
+
+                Ok(())
+            }
+        }
+
+        type SleepResult = Result<Wakeup, SleepError<Infallible, Infallible>>;
+
+        fn sleep<F>(target: Duration, on_wait: F) -> (SleepResult, Vec<Duration>)
+        where
+            F: FnMut(&SimulatedSystemTime, Duration) -> bool,
+        {
+            let clock = SimulatedSystemTime::new();
+            clock.set_time(SEC * 1000).unwrap();
+
+            let mut timer = SteppingTimer {
+                clock: &clock,
+                waits: Vec::new(),
+                on_wait,
+            };
+
+            let result = block_on(sleep_until_stepped::<StdRawMutex, _, _>(
+                &clock, &mut timer, target,
+            ));
+
+            (result, timer.waits)
+        }
+
+        #[test]
+        fn stepped_to_target() {
+            // The timer never expires, so only the step wakes the sleep up
+            let (result, waits) = sleep(SEC * 2000, |clock, _| {
+                clock.set_time(SEC * 2000).unwrap();
+
+                false
+            });
+
+            assert!(matches!(result, Ok(Wakeup::Reached)));
+            assert_eq!(waits, [MAX_SLEEP]);
+        }
+
+        #[test]
+        fn stepped_past() {
+            let (result, waits) = sleep(SEC * 2000, |clock, _| {
+                clock.set_time(SEC * 2100).unwrap();
+
+                false
+            });
+
+            assert!(
+                matches!(result, Ok(Wakeup::Passed(passed)) if passed >= SEC * 100 && passed < SEC * 101)
+            );
+            assert_eq!(waits.len(), 1);
+        }
+
+        #[test]
+        fn stepped_back() {
+            let mut steps = 0;
+
+            // Waking up because of a step does not count as a re-arm, however many steps there are
+            let (result, waits) = sleep(SEC * 1030, |clock, _| {
+                steps += 1;
+
+                if steps <= MAX_REARMS * 2 {
+                    clock.set_time(clock.now() - SEC).unwrap();
+                } else {
+                    clock.set_time(SEC * 1030).unwrap();
+                }
+
+                false
+            });
+
+            assert!(matches!(result, Ok(Wakeup::Reached)));
+            assert_eq!(waits.len(), MAX_REARMS * 2 + 1);
+            assert!(waits
+                .iter()
+                .all(|wait| *wait > SEC * 29 && *wait <= MAX_SLEEP));
+        }
+
+        #[test]
+        fn expired_early() {
+            // The timer keeps expiring right away, without the clock being stepped
+            let (result, waits) = sleep(SEC * 1030, |_, _| true);
+
+            assert!(matches!(result, Err(SleepError::TooManyRearms)));
+            assert_eq!(waits.len(), MAX_REARMS + 1);
+        }
+    }
+}
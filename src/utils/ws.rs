@@ -0,0 +1,971 @@
+//! A Websocket client over any `socket::TcpConnect`, and the codec of the Websocket frames it is built on
+//!
+//! `WsConnector` implements `ws::client::Connector` for `ws://` URLs over a plain `TcpConnect`, or for `wss://` URLs
+//! over a `TcpConnect` which does TLS, so hosts (with `socket::StdStack`) and devices alike have a runnable client.
+
+use core::cell::Cell;
+use core::convert::TryFrom;
+use core::fmt;
+use core::str;
+
+use crate::io::{self, Read, ReadExactError, Write};
+use crate::socket::TcpConnect;
+use crate::utils::backoff::xorshift32;
+use crate::utils::digest::{Digest, Sha1};
+use crate::ws::client::{Connector, WsClientConfig};
+use crate::ws::{ErrorType, FrameType, Receiver, Sender};
+
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// The largest handshake response accepted, headers included
+const MAX_RESPONSE_LEN: usize = 1024;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WsError<E> {
+    Io(E),
+    InvalidUrl,
+    /// A `wss://` URL passed to a connector which does not do TLS, or a `ws://` URL to one which does
+    UnsupportedScheme,
+    /// The server did not upgrade the connection, and replied with this HTTP status instead of 101
+    Rejected(u16),
+    /// The server upgraded the connection with an invalid response, e.g. with a wrong `Sec-WebSocket-Accept`,
+    /// or with a subprotocol which was not offered
+    InvalidHandshake,
+    /// A frame which is not valid, or which cannot be sent (`FrameType::SocketClose`)
+    InvalidFrame,
+    /// A frame larger than `WsClientConfig::max_frame_size`
+    FrameTooLarge,
+    /// The connection was closed in the middle of a frame, or of the handshake
+    UnexpectedEof,
+}
+
+impl<E> fmt::Display for WsError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::InvalidUrl => write!(f, "Invalid URL"),
+            Self::UnsupportedScheme => write!(f, "Unsupported URL scheme"),
+            Self::Rejected(status) => write!(f, "Upgrade rejected with status {status}"),
+            Self::InvalidHandshake => write!(f, "Invalid handshake"),
+            Self::InvalidFrame => write!(f, "Invalid frame"),
+            Self::FrameTooLarge => write!(f, "Frame too large"),
+            Self::UnexpectedEof => write!(f, "Unexpected EOF"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for WsError<E> where E: fmt::Display + fmt::Debug {}
+
+impl<E> From<ReadExactError<E>> for WsError<E> {
+    fn from(e: ReadExactError<E>) -> Self {
+        match e {
+            ReadExactError::UnexpectedEof => Self::UnexpectedEof,
+            ReadExactError::Other(e) => Self::Io(e),
+        }
+    }
+}
+
+/// The header of a Websocket frame, as defined by RFC 6455
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameHeader {
+    pub frame_type: FrameType,
+    pub payload_len: u64,
+    /// The masking key of the payload, which is set for the frames sent by clients, and only for them
+    pub mask_key: Option<[u8; 4]>,
+}
+
+impl FrameHeader {
+    /// The size of the largest header
+    pub const MAX_LEN: usize = 14;
+
+    /// Reads the header of the next frame, or returns `Ok(None)` if the connection was closed before it
+    pub fn recv<R>(mut read: R) -> Result<Option<Self>, WsError<R::Error>>
+    where
+        R: Read,
+    {
+        let mut buf = [0; 2];
+
+        if read.read(&mut buf[..1]).map_err(WsError::Io)? == 0 {
+            return Ok(None);
+        }
+
+        read.read_exact(&mut buf[1..])?;
+
+        // The reserved bits are for extensions, and no extension is negotiated
+        if buf[0] & 0x70 != 0 {
+            return Err(WsError::InvalidFrame);
+        }
+
+        let final_ = buf[0] & 0x80 != 0;
+        let opcode = buf[0] & 0x0f;
+
+        // Control frames cannot be fragmented
+        if opcode >= 8 && !final_ {
+            return Err(WsError::InvalidFrame);
+        }
+
+        let frame_type = match opcode {
+            0 => FrameType::Continue(final_),
+            1 => FrameType::Text(!final_),
+            2 => FrameType::Binary(!final_),
+            8 => FrameType::Close,
+            9 => FrameType::Ping,
+            10 => FrameType::Pong,
+            _ => return Err(WsError::InvalidFrame),
+        };
+
+        let payload_len = match buf[1] & 0x7f {
+            126 => {
+                let mut len = [0; 2];
+                read.read_exact(&mut len)?;
+
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0; 8];
+                read.read_exact(&mut len)?;
+
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+
+        let mask_key = if buf[1] & 0x80 != 0 {
+            let mut mask_key = [0; 4];
+            read.read_exact(&mut mask_key)?;
+
+            Some(mask_key)
+        } else {
+            None
+        };
+
+        let header = Self {
+            frame_type,
+            payload_len,
+            mask_key,
+        };
+
+        header.check()?;
+
+        Ok(Some(header))
+    }
+
+    /// Writes the header, after which the (masked, if `mask_key` is set) payload is to be written
+    pub fn send<W>(&self, mut write: W) -> Result<(), WsError<W::Error>>
+    where
+        W: Write,
+    {
+        self.check()?;
+
+        let (opcode, final_) = match self.frame_type {
+            FrameType::Text(fragmented) => (1, !fragmented),
+            FrameType::Binary(fragmented) => (2, !fragmented),
+            FrameType::Continue(final_) => (0, final_),
+            FrameType::Close => (8, true),
+            FrameType::Ping => (9, true),
+            FrameType::Pong => (10, true),
+            FrameType::SocketClose => return Err(WsError::InvalidFrame),
+        };
+
+        let mut buf = [0; Self::MAX_LEN];
+
+        buf[0] = opcode | if final_ { 0x80 } else { 0 };
+
+        let mut len = 2;
+
+        if self.payload_len < 126 {
+            buf[1] = self.payload_len as u8;
+        } else if self.payload_len <= u16::MAX as u64 {
+            buf[1] = 126;
+            buf[2..4].copy_from_slice(&(self.payload_len as u16).to_be_bytes());
+            len += 2;
+        } else {
+            buf[1] = 127;
+            buf[2..10].copy_from_slice(&self.payload_len.to_be_bytes());
+            len += 8;
+        }
+
+        if let Some(mask_key) = self.mask_key {
+            buf[1] |= 0x80;
+            buf[len..len + 4].copy_from_slice(&mask_key);
+            len += 4;
+        }
+
+        write.write_all(&buf[..len]).map_err(WsError::Io)
+    }
+
+    /// Masks (or unmasks) `data`, the part of the payload starting at `offset`, with `mask_key`
+    pub fn mask(mask_key: [u8; 4], offset: usize, data: &mut [u8]) {
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte ^= mask_key[(offset + index) % 4];
+        }
+    }
+
+    fn check<E>(&self) -> Result<(), WsError<E>> {
+        let control = matches!(
+            self.frame_type,
+            FrameType::Close | FrameType::Ping | FrameType::Pong
+        );
+
+        // Control frames are limited to 125 bytes
+        if control && self.payload_len > 125 {
+            Err(WsError::InvalidFrame)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+struct Url<'a> {
+    secure: bool,
+    authority: &'a str,
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+impl<'a> Url<'a> {
+    fn parse(url: &'a str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+
+        let secure = if scheme.eq_ignore_ascii_case("wss") {
+            true
+        } else if scheme.eq_ignore_ascii_case("ws") {
+            false
+        } else {
+            return None;
+        };
+
+        let (authority, path) = rest
+            .find(['/', '?'])
+            .map(|index| rest.split_at(index))
+            .unwrap_or((rest, ""));
+
+        let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+            let (host, port) = rest.split_once(']')?;
+
+            (host, port.strip_prefix(':'))
+        } else {
+            authority
+                .split_once(':')
+                .map(|(host, port)| (host, Some(port)))
+                .unwrap_or((authority, None))
+        };
+
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None if secure => 443,
+            None => 80,
+        };
+
+        (!host.is_empty()).then_some(Self {
+            secure,
+            authority,
+            host,
+            port,
+            path,
+        })
+    }
+}
+
+fn base64<const N: usize>(data: &[u8]) -> heapless::String<N> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = heapless::String::new();
+
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| {
+            bits | ((*byte as u32) << (16 - index * 8))
+        });
+
+        for index in 0..4 {
+            let c = if index <= chunk.len() {
+                ALPHABET[(bits >> (18 - index * 6)) as usize & 0x3f] as char
+            } else {
+                '='
+            };
+
+            let _ = encoded.push(c);
+        }
+    }
+
+    encoded
+}
+
+fn write_all<W>(mut write: W, parts: &[&str]) -> Result<(), WsError<W::Error>>
+where
+    W: Write,
+{
+    for part in parts {
+        write.write_all(part.as_bytes()).map_err(WsError::Io)?;
+    }
+
+    Ok(())
+}
+
+/// Sends the upgrade request, and checks the response, returning the index of the selected subprotocol
+fn handshake<C>(
+    mut conn: C,
+    url: &Url<'_>,
+    config: &WsClientConfig<'_>,
+    key: &str,
+) -> Result<Option<usize>, WsError<C::Error>>
+where
+    C: Read + Write,
+{
+    let path = if url.path.starts_with('/') { "" } else { "/" };
+
+    write_all(
+        &mut conn,
+        &[
+            "GET ",
+            path,
+            url.path,
+            " HTTP/1.1\r\nHost: ",
+            url.authority,
+            "\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: ",
+            key,
+            "\r\nSec-WebSocket-Version: 13\r\n",
+        ],
+    )?;
+
+    for (index, subprotocol) in config.subprotocols.iter().enumerate() {
+        let prefix = if index == 0 {
+            "Sec-WebSocket-Protocol: "
+        } else {
+            ", "
+        };
+
+        write_all(&mut conn, &[prefix, subprotocol])?;
+    }
+
+    if !config.subprotocols.is_empty() {
+        write_all(&mut conn, &["\r\n"])?;
+    }
+
+    for (name, value) in config.headers {
+        write_all(&mut conn, &[name, ": ", value, "\r\n"])?;
+    }
+
+    write_all(&mut conn, &["\r\n"])?;
+    conn.flush().map_err(WsError::Io)?;
+
+    // Read byte by byte, so that nothing past the response (i.e. the first frames) is consumed
+    let mut buf = [0; MAX_RESPONSE_LEN];
+    let mut len = 0;
+
+    while !buf[..len].ends_with(b"\r\n\r\n") {
+        if len == buf.len() {
+            return Err(WsError::InvalidHandshake);
+        }
+
+        conn.read_exact(&mut buf[len..len + 1])?;
+        len += 1;
+    }
+
+    let response = str::from_utf8(&buf[..len]).map_err(|_| WsError::InvalidHandshake)?;
+    let mut lines = response.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(WsError::InvalidHandshake)?;
+
+    if status != 101 {
+        return Err(WsError::Rejected(status));
+    }
+
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID);
+
+    let accept: heapless::String<28> = base64(&sha1.finalize());
+
+    let mut upgrade = false;
+    let mut connection = false;
+    let mut accepted = false;
+    let mut subprotocol = None;
+
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+
+        if name.eq_ignore_ascii_case("Upgrade") {
+            upgrade = value.eq_ignore_ascii_case("websocket");
+        } else if name.eq_ignore_ascii_case("Connection") {
+            connection = value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("Upgrade"));
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Accept") {
+            accepted = value == accept;
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Protocol") {
+            subprotocol = Some(
+                config
+                    .subprotocols
+                    .iter()
+                    .position(|subprotocol| *subprotocol == value)
+                    .ok_or(WsError::InvalidHandshake)?,
+            );
+        }
+    }
+
+    if upgrade && connection && accepted {
+        Ok(subprotocol)
+    } else {
+        Err(WsError::InvalidHandshake)
+    }
+}
+
+/// A connection opened by `WsConnector`
+pub struct WsConnection<C> {
+    conn: C,
+    max_frame_size: Option<usize>,
+    pending: Option<FrameHeader>,
+    prng: u32,
+}
+
+impl<C> WsConnection<C> {
+    pub fn release(self) -> C {
+        self.conn
+    }
+}
+
+impl<C> ErrorType for WsConnection<C>
+where
+    C: io::ErrorType,
+{
+    type Error = WsError<C::Error>;
+}
+
+/// Receives the frames of the server
+///
+/// When `frame_data_buf` is too small for the payload of a frame, returns its type and its length
+/// without receiving it, so that it can be received with a large enough buffer by the next call.
+impl<C> Receiver for WsConnection<C>
+where
+    C: Read,
+{
+    fn recv(&mut self, frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+        let header = match self.pending.take() {
+            Some(header) => header,
+            None => match FrameHeader::recv(&mut self.conn)? {
+                Some(header) => header,
+                None => return Ok((FrameType::SocketClose, 0)),
+            },
+        };
+
+        // The frames of servers must not be masked
+        if header.mask_key.is_some() {
+            return Err(WsError::InvalidFrame);
+        }
+
+        let len = usize::try_from(header.payload_len)
+            .ok()
+            .filter(|len| self.max_frame_size.map(|max| *len <= max).unwrap_or(true))
+            .ok_or(WsError::FrameTooLarge)?;
+
+        if len > frame_data_buf.len() {
+            self.pending = Some(header);
+        } else {
+            self.conn.read_exact(&mut frame_data_buf[..len])?;
+        }
+
+        Ok((header.frame_type, len))
+    }
+}
+
+impl<C> Sender for WsConnection<C>
+where
+    C: Write,
+{
+    fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        self.prng = xorshift32(self.prng);
+
+        let mask_key = self.prng.to_le_bytes();
+
+        FrameHeader {
+            frame_type,
+            payload_len: frame_data.len() as u64,
+            mask_key: Some(mask_key),
+        }
+        .send(&mut self.conn)?;
+
+        let mut buf = [0; 64];
+
+        for (index, chunk) in frame_data.chunks(buf.len()).enumerate() {
+            let buf = &mut buf[..chunk.len()];
+
+            buf.copy_from_slice(chunk);
+            FrameHeader::mask(mask_key, index * 64, buf);
+
+            self.conn.write_all(buf).map_err(WsError::Io)?;
+        }
+
+        self.conn.flush().map_err(WsError::Io)
+    }
+}
+
+/// A `ws::client::Connector` over a `TcpConnect`
+///
+/// The keys of the handshakes and the masks of the frames come from a xorshift PRNG, which is seeded on creation
+/// and must therefore be seeded from a random source (e.g. the hardware RNG), with a non-zero seed.
+pub struct WsConnector<T> {
+    tcp: T,
+    secure: bool,
+    prng: Cell<u32>,
+}
+
+impl<T> WsConnector<T>
+where
+    T: TcpConnect,
+{
+    /// A connector for `ws://` URLs
+    pub const fn new(tcp: T, seed: u32) -> Self {
+        Self {
+            tcp,
+            secure: false,
+            prng: Cell::new(seed),
+        }
+    }
+
+    /// A connector for `wss://` URLs, where `tcp` opens TLS connections
+    pub const fn new_secure(tcp: T, seed: u32) -> Self {
+        Self {
+            tcp,
+            secure: true,
+            prng: Cell::new(seed),
+        }
+    }
+
+    fn next(&self) -> u32 {
+        let next = xorshift32(self.prng.get());
+
+        self.prng.set(next);
+
+        next
+    }
+}
+
+impl<T> ErrorType for WsConnector<T>
+where
+    T: TcpConnect,
+{
+    type Error = WsError<T::Error>;
+}
+
+impl<T> Connector for WsConnector<T>
+where
+    T: TcpConnect,
+{
+    type Connection<'a> = WsConnection<T::Connection<'a>> where Self: 'a;
+
+    fn connect(
+        &self,
+        config: &WsClientConfig<'_>,
+    ) -> Result<(Self::Connection<'_>, Option<usize>), Self::Error> {
+        let url = Url::parse(config.url).ok_or(WsError::InvalidUrl)?;
+
+        if url.secure != self.secure {
+            return Err(WsError::UnsupportedScheme);
+        }
+
+        let mut key = [0; 16];
+
+        for chunk in key.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&self.next().to_le_bytes());
+        }
+
+        let key: heapless::String<24> = base64(&key);
+
+        let mut conn = self.tcp.connect(url.host, url.port).map_err(WsError::Io)?;
+
+        let subprotocol = handshake(&mut conn, &url, config, &key)?;
+
+        Ok((
+            WsConnection {
+                conn,
+                max_frame_size: config.max_frame_size,
+                pending: None,
+                prng: self.next(),
+            },
+            subprotocol,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::cell::RefCell;
+    use core::convert::TryInto;
+
+    use crate::io::ErrorKind;
+
+    use super::*;
+
+    type Respond = Box<dyn Fn(&str) -> Vec<u8>>;
+
+    /// A TCP stack whose server replies to the request of the connection with `respond(request)`,
+    /// once the request is complete
+    struct Scripted {
+        respond: Respond,
+        connected: RefCell<Option<(String, u16)>>,
+        written: RefCell<Vec<u8>>,
+    }
+
+    impl Scripted {
+        fn new(respond: impl Fn(&str) -> Vec<u8> + 'static) -> Self {
+            Self {
+                respond: Box::new(respond),
+                connected: RefCell::new(None),
+                written: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn request(&self) -> String {
+            let written = self.written.borrow();
+            let len = request_len(&written).unwrap();
+
+            String::from_utf8(written[..len].to_vec()).unwrap()
+        }
+    }
+
+    fn request_len(data: &[u8]) -> Option<usize> {
+        data.windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|index| index + 4)
+    }
+
+    impl TcpConnect for Scripted {
+        type Error = ErrorKind;
+
+        type Connection<'a>
+            = ScriptedConnection<'a>
+        where
+            Self: 'a;
+
+        fn connect(&self, host: &str, port: u16) -> Result<Self::Connection<'_>, Self::Error> {
+            *self.connected.borrow_mut() = Some((host.to_string(), port));
+
+            Ok(ScriptedConnection {
+                stack: self,
+                response: None,
+            })
+        }
+    }
+
+    struct ScriptedConnection<'a> {
+        stack: &'a Scripted,
+        response: Option<std::collections::VecDeque<u8>>,
+    }
+
+    impl io::ErrorType for ScriptedConnection<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for ScriptedConnection<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.response.is_none() {
+                let written = self.stack.written.borrow();
+
+                // The client reads before its request is complete
+                let len = request_len(&written).ok_or(ErrorKind::Other)?;
+                let request = str::from_utf8(&written[..len]).unwrap();
+
+                self.response = Some((self.stack.respond)(request).into());
+            }
+
+            let response = self.response.as_mut().unwrap();
+            let len = buf.len().min(response.len());
+
+            for (byte, response) in buf.iter_mut().zip(response.drain(..len)) {
+                *byte = response;
+            }
+
+            Ok(len)
+        }
+    }
+
+    impl Write for ScriptedConnection<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.stack.written.borrow_mut().extend_from_slice(buf);
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request
+            .split("\r\n")
+            .filter_map(|line| line.split_once(": "))
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    fn accept(key: &str) -> heapless::String<28> {
+        let mut sha1 = Sha1::new();
+        sha1.update(key.as_bytes());
+        sha1.update(ACCEPT_GUID);
+
+        base64(&sha1.finalize())
+    }
+
+    /// The response of a server upgrading the connection, with `extra` headers
+    fn upgraded(request: &str, extra: &str) -> Vec<u8> {
+        let key = header(request, "Sec-WebSocket-Key").unwrap();
+
+        format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n{}\r\n",
+            accept(key),
+            extra
+        )
+        .into_bytes()
+    }
+
+    fn config(url: &str) -> WsClientConfig<'_> {
+        WsClientConfig {
+            url,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accept_key() {
+        // The example of RFC 6455, section 1.3
+        assert_eq!(
+            accept("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn handshake_request() {
+        let stack =
+            Scripted::new(|request| upgraded(request, "Sec-WebSocket-Protocol: v1.json\r\n"));
+        let connector = WsConnector::new(&stack, 0x1234_5678);
+
+        let config = WsClientConfig {
+            url: "ws://device.local:8080/events?since=42",
+            subprotocols: &["v2.json", "v1.json"],
+            headers: &[("Authorization", "Bearer 0123")],
+            max_frame_size: None,
+        };
+
+        let (_, subprotocol) = connector.connect(&config).unwrap();
+        assert_eq!(subprotocol, Some(1));
+
+        assert_eq!(
+            stack.connected.borrow().clone(),
+            Some(("device.local".to_string(), 8080))
+        );
+
+        let request = stack.request();
+        let key = header(&request, "Sec-WebSocket-Key").unwrap();
+
+        assert_eq!(
+            request,
+            format!(
+                "GET /events?since=42 HTTP/1.1\r\nHost: device.local:8080\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\
+                 Sec-WebSocket-Protocol: v2.json, v1.json\r\nAuthorization: Bearer 0123\r\n\r\n",
+                key
+            )
+        );
+
+        // 16 random bytes
+        assert_eq!(key.len(), 24);
+        assert!(key.ends_with("=="));
+
+        // Another key for the next connection
+        stack.written.borrow_mut().clear();
+        connector.connect(&config).unwrap();
+
+        assert_ne!(header(&stack.request(), "Sec-WebSocket-Key").unwrap(), key);
+    }
+
+    #[test]
+    fn handshake_then_frames() {
+        let stack = Scripted::new(|request| {
+            let mut response = upgraded(request, "");
+
+            // The first frame of the server, right after the response
+            response.extend_from_slice(b"\x81\x05hello");
+
+            response
+        });
+
+        let connector = WsConnector::new(&stack, 1);
+        let (mut connection, subprotocol) = connector.connect(&config("ws://[::1]/")).unwrap();
+
+        assert_eq!(subprotocol, None);
+        assert_eq!(
+            stack.connected.borrow().clone(),
+            Some(("::1".to_string(), 80))
+        );
+
+        let mut buf = [0; 16];
+        assert_eq!(
+            connection.recv(&mut buf).unwrap(),
+            (FrameType::Text(false), 5)
+        );
+        assert_eq!(&buf[..5], b"hello");
+
+        assert_eq!(
+            connection.recv(&mut buf).unwrap(),
+            (FrameType::SocketClose, 0)
+        );
+
+        // The frames of the client are masked
+        let len = stack.request().len();
+        connection.send(FrameType::Text(false), b"hi").unwrap();
+
+        let written = stack.written.borrow();
+        let frame = &written[len..];
+
+        assert_eq!(&frame[..2], &[0x81, 0x82]);
+
+        let mut payload = frame[6..].to_vec();
+        FrameHeader::mask(frame[2..6].try_into().unwrap(), 0, &mut payload);
+        assert_eq!(payload, b"hi");
+    }
+
+    #[test]
+    fn handshake_rejected() {
+        for (response, status) in [
+            (
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Bearer\r\nContent-Length: 0\r\n\r\n",
+                401,
+            ),
+            ("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n", 200),
+            ("HTTP/1.0 404 Not Found\r\n\r\n", 404),
+        ] {
+            let stack = Scripted::new(move |_| response.as_bytes().to_vec());
+
+            let connector = WsConnector::new(&stack, 1);
+
+            let result = connector.connect(&config("ws://device.local/"));
+            assert!(matches!(result, Err(WsError::Rejected(s)) if s == status));
+        }
+    }
+
+    #[test]
+    fn handshake_invalid() {
+        let responses: [fn(&str) -> Vec<u8>; 6] = [
+            // A wrong accept key
+            |_| upgraded("Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n", ""),
+            // No Upgrade header
+            |request| {
+                String::from_utf8(upgraded(request, ""))
+                    .unwrap()
+                    .replace("Upgrade: websocket\r\n", "")
+                    .into_bytes()
+            },
+            // A subprotocol which was not offered
+            |request| upgraded(request, "Sec-WebSocket-Protocol: v3.json\r\n"),
+            // Not a status line
+            |_| b"SSH-2.0-OpenSSH_9.6\r\n\r\n".to_vec(),
+            // Too long
+            |_| {
+                let mut response = b"HTTP/1.1 101 Switching Protocols\r\n".to_vec();
+                response.resize(2 * MAX_RESPONSE_LEN, b'a');
+
+                response
+            },
+            // Not UTF-8
+            |_| b"HTTP/1.1 101 \xff\r\n\r\n".to_vec(),
+        ];
+
+        for respond in responses {
+            let stack = Scripted::new(respond);
+
+            let config = WsClientConfig {
+                url: "ws://device.local/",
+                subprotocols: &["v2.json", "v1.json"],
+                ..Default::default()
+            };
+
+            let connector = WsConnector::new(&stack, 1);
+
+            let result = connector.connect(&config);
+            assert!(matches!(result, Err(WsError::InvalidHandshake)));
+        }
+
+        // Closed in the middle of the response
+        let stack = Scripted::new(|_| b"HTTP/1.1 101 Switching Protocols\r\n".to_vec());
+
+        let connector = WsConnector::new(&stack, 1);
+
+        let result = connector.connect(&config("ws://device.local/"));
+        assert!(matches!(result, Err(WsError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn connect_urls() {
+        let stack = Scripted::new(|request| upgraded(request, ""));
+
+        for url in [
+            "http://device.local/",
+            "ws://",
+            "ws://:80/",
+            "ws://device.local:port/",
+        ] {
+            let connector = WsConnector::new(&stack, 1);
+            let result = connector.connect(&config(url));
+            assert!(matches!(result, Err(WsError::InvalidUrl)), "{}", url);
+        }
+
+        let connector = WsConnector::new(&stack, 1);
+
+        let result = connector.connect(&config("wss://device.local/"));
+        assert!(matches!(result, Err(WsError::UnsupportedScheme)));
+
+        let connector = WsConnector::new_secure(&stack, 1);
+
+        let result = connector.connect(&config("ws://device.local/"));
+        assert!(matches!(result, Err(WsError::UnsupportedScheme)));
+
+        WsConnector::new_secure(&stack, 1)
+            .connect(&config("WSS://device.local"))
+            .unwrap();
+
+        assert_eq!(
+            stack.connected.borrow().clone(),
+            Some(("device.local".to_string(), 443))
+        );
+        assert!(stack.request().starts_with("GET / HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn frame_too_large() {
+        let stack = Scripted::new(|request| {
+            let mut response = upgraded(request, "");
+            response.extend_from_slice(b"\x82\x7e\x01\x00");
+            response.resize(response.len() + 256, 0);
+
+            response
+        });
+
+        let config = WsClientConfig {
+            url: "ws://device.local/",
+            max_frame_size: Some(255),
+            ..Default::default()
+        };
+
+        let connector = WsConnector::new(&stack, 1);
+
+        let (mut connection, _) = connector.connect(&config).unwrap();
+
+        let result = connection.recv(&mut [0; 512]);
+        assert!(matches!(result, Err(WsError::FrameTooLarge)));
+    }
+}
@@ -16,6 +16,8 @@ use strum_macros::{Display, EnumIter, EnumMessage, EnumString, EnumVariantNames,
 #[cfg(feature = "use_numenum")]
 use num_enum::TryFromPrimitive;
 
+use crate::{ipv4, ipv6};
+
 #[derive(EnumSetType, Debug, PartialOrd)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -347,6 +349,70 @@ pub trait Wifi {
     fn is_started(&self) -> Result<bool, Self::Error>;
     fn is_connected(&self) -> Result<bool, Self::Error>;
 
+    /// Returns the IP configuration of the client (STA) interface, or `Ok(None)` if the driver
+    /// does not support querying it.
+    fn get_ip_configuration(&self) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Applies the IP configuration to the client (STA) interface, with the same semantics as
+    /// `Eth::set_ip_configuration`.
+    ///
+    /// Returns `Ok(false)` if the driver does not support changing the IP configuration.
+    fn set_ip_configuration(
+        &mut self,
+        _conf: &ipv4::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Same as `get_ip_configuration`, but for the IPv6 configuration of the client (STA) interface.
+    fn get_ipv6_configuration(&self) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Same as `set_ip_configuration`, but for the IPv6 configuration of the client (STA) interface.
+    fn set_ipv6_configuration(
+        &mut self,
+        _conf: &ipv6::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Returns the IPv4 and the IPv6 configurations together, or `Ok(None)` if the driver supports querying neither.
+    fn get_ip_stack_configuration(&self) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+        Ok(ipv6::IpConfiguration::new(
+            self.get_ip_configuration()?,
+            self.get_ipv6_configuration()?,
+        ))
+    }
+
+    /// Applies the IPv4 and the IPv6 parts of the configuration; a protocol without a part is left as is.
+    ///
+    /// Returns `Ok(false)` if the driver does not support changing the configuration of either part.
+    fn set_ip_stack_configuration(
+        &mut self,
+        conf: &ipv6::IpConfiguration,
+    ) -> Result<bool, Self::Error> {
+        let ipv4 = match conf.ipv4() {
+            Some(ipv4) => self.set_ip_configuration(ipv4)?,
+            None => true,
+        };
+
+        let ipv6 = match conf.ipv6() {
+            Some(ipv6) => self.set_ipv6_configuration(ipv6)?,
+            None => true,
+        };
+
+        Ok(ipv4 && ipv6)
+    }
+
+    /// Returns the current DHCP lease of the client (STA) interface, or `Ok(None)` if the interface
+    /// is not bound to a lease or if the driver does not support reporting it.
+    fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+        Ok(None)
+    }
+
     fn scan_n<const N: usize>(
         &mut self,
     ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error>;
@@ -397,6 +463,43 @@ where
         (**self).is_connected()
     }
 
+    fn get_ip_configuration(&self) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+        (**self).get_ip_configuration()
+    }
+
+    fn set_ip_configuration(
+        &mut self,
+        conf: &ipv4::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ip_configuration(conf)
+    }
+
+    fn get_ipv6_configuration(&self) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+        (**self).get_ipv6_configuration()
+    }
+
+    fn set_ipv6_configuration(
+        &mut self,
+        conf: &ipv6::ClientConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ipv6_configuration(conf)
+    }
+
+    fn get_ip_stack_configuration(&self) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+        (**self).get_ip_stack_configuration()
+    }
+
+    fn set_ip_stack_configuration(
+        &mut self,
+        conf: &ipv6::IpConfiguration,
+    ) -> Result<bool, Self::Error> {
+        (*self).set_ip_stack_configuration(conf)
+    }
+
+    fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+        (**self).get_dhcp_lease_info()
+    }
+
     fn scan_n<const N: usize>(
         &mut self,
     ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error> {
@@ -431,6 +534,62 @@ pub mod asynch {
         async fn is_started(&self) -> Result<bool, Self::Error>;
         async fn is_connected(&self) -> Result<bool, Self::Error>;
 
+        async fn get_ip_configuration(
+            &self,
+        ) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn set_ip_configuration(
+            &mut self,
+            _conf: &ipv4::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        async fn get_ipv6_configuration(
+            &self,
+        ) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+            Ok(None)
+        }
+
+        async fn set_ipv6_configuration(
+            &mut self,
+            _conf: &ipv6::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        async fn get_ip_stack_configuration(
+            &self,
+        ) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+            Ok(ipv6::IpConfiguration::new(
+                self.get_ip_configuration().await?,
+                self.get_ipv6_configuration().await?,
+            ))
+        }
+
+        async fn set_ip_stack_configuration(
+            &mut self,
+            conf: &ipv6::IpConfiguration,
+        ) -> Result<bool, Self::Error> {
+            let ipv4 = match conf.ipv4() {
+                Some(ipv4) => self.set_ip_configuration(ipv4).await?,
+                None => true,
+            };
+
+            let ipv6 = match conf.ipv6() {
+                Some(ipv6) => self.set_ipv6_configuration(ipv6).await?,
+                None => true,
+            };
+
+            Ok(ipv4 && ipv6)
+        }
+
+        async fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+            Ok(None)
+        }
+
         async fn scan_n<const N: usize>(
             &mut self,
         ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error>;
@@ -481,6 +640,49 @@ pub mod asynch {
             (**self).is_connected().await
         }
 
+        async fn get_ip_configuration(
+            &self,
+        ) -> Result<Option<ipv4::ClientConfiguration>, Self::Error> {
+            (**self).get_ip_configuration().await
+        }
+
+        async fn set_ip_configuration(
+            &mut self,
+            conf: &ipv4::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ip_configuration(conf).await
+        }
+
+        async fn get_ipv6_configuration(
+            &self,
+        ) -> Result<Option<ipv6::ClientConfiguration>, Self::Error> {
+            (**self).get_ipv6_configuration().await
+        }
+
+        async fn set_ipv6_configuration(
+            &mut self,
+            conf: &ipv6::ClientConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ipv6_configuration(conf).await
+        }
+
+        async fn get_ip_stack_configuration(
+            &self,
+        ) -> Result<Option<ipv6::IpConfiguration>, Self::Error> {
+            (**self).get_ip_stack_configuration().await
+        }
+
+        async fn set_ip_stack_configuration(
+            &mut self,
+            conf: &ipv6::IpConfiguration,
+        ) -> Result<bool, Self::Error> {
+            (**self).set_ip_stack_configuration(conf).await
+        }
+
+        async fn get_dhcp_lease_info(&self) -> Result<Option<ipv4::DhcpLeaseInfo>, Self::Error> {
+            (**self).get_dhcp_lease_info().await
+        }
+
         async fn scan_n<const N: usize>(
             &mut self,
         ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error> {
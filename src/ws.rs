@@ -111,6 +111,67 @@ pub mod server {
     }
 }
 
+pub mod client {
+    pub use super::*;
+
+    /// What to connect to, and how
+    #[derive(Clone, Debug, Default)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct WsClientConfig<'a> {
+        /// The `ws://` or `wss://` URL of the endpoint, e.g. `wss://example.com/api/events`
+        pub url: &'a str,
+        /// The subprotocols offered to the server, in order of preference
+        pub subprotocols: &'a [&'a str],
+        /// Additional headers of the upgrade request, e.g. `Authorization`
+        pub headers: &'a [(&'a str, &'a str)],
+        /// The largest frame expected from the server, if known, so that implementations can size their buffers
+        /// (and reject larger frames)
+        pub max_frame_size: Option<usize>,
+    }
+
+    pub trait Connector: ErrorType {
+        type Connection<'a>: Sender<Error = Self::Error> + Receiver<Error = Self::Error>
+        where
+            Self: 'a;
+
+        /// Opens a connection, and performs the upgrade handshake
+        ///
+        /// Returns the index in `WsClientConfig::subprotocols` of the subprotocol the server selected, if any.
+        fn connect(
+            &self,
+            config: &WsClientConfig<'_>,
+        ) -> Result<(Self::Connection<'_>, Option<usize>), Self::Error>;
+    }
+
+    impl<C> Connector for &C
+    where
+        C: Connector,
+    {
+        type Connection<'a> = C::Connection<'a> where Self: 'a;
+
+        fn connect(
+            &self,
+            config: &WsClientConfig<'_>,
+        ) -> Result<(Self::Connection<'_>, Option<usize>), Self::Error> {
+            (*self).connect(config)
+        }
+    }
+
+    impl<C> Connector for &mut C
+    where
+        C: Connector,
+    {
+        type Connection<'a> = C::Connection<'a> where Self: 'a;
+
+        fn connect(
+            &self,
+            config: &WsClientConfig<'_>,
+        ) -> Result<(Self::Connection<'_>, Option<usize>), Self::Error> {
+            (**self).connect(config)
+        }
+    }
+}
+
 pub mod callback_server {
     pub use super::*;
 
@@ -212,4 +273,59 @@ pub mod asynch {
             }
         }
     }
+
+    pub mod client {
+        pub use super::*;
+
+        pub use crate::ws::client::WsClientConfig;
+
+        pub trait Connector: ErrorType {
+            type Sender<'a>: Sender<Error = Self::Error>
+            where
+                Self: 'a;
+            type Receiver<'a>: Receiver<Error = Self::Error>
+            where
+                Self: 'a;
+
+            /// Opens a connection, and performs the upgrade handshake
+            ///
+            /// Returns the index in `WsClientConfig::subprotocols` of the subprotocol the server selected, if any.
+            async fn connect(
+                &self,
+                config: &WsClientConfig<'_>,
+            ) -> Result<(Self::Sender<'_>, Self::Receiver<'_>, Option<usize>), Self::Error>;
+        }
+
+        impl<C> Connector for &C
+        where
+            C: Connector,
+        {
+            type Sender<'a> = C::Sender<'a> where Self: 'a;
+            type Receiver<'a> = C::Receiver<'a> where Self: 'a;
+
+            async fn connect(
+                &self,
+                config: &WsClientConfig<'_>,
+            ) -> Result<(Self::Sender<'_>, Self::Receiver<'_>, Option<usize>), Self::Error>
+            {
+                (*self).connect(config).await
+            }
+        }
+
+        impl<C> Connector for &mut C
+        where
+            C: Connector,
+        {
+            type Sender<'a> = C::Sender<'a> where Self: 'a;
+            type Receiver<'a> = C::Receiver<'a> where Self: 'a;
+
+            async fn connect(
+                &self,
+                config: &WsClientConfig<'_>,
+            ) -> Result<(Self::Sender<'_>, Self::Receiver<'_>, Option<usize>), Self::Error>
+            {
+                (**self).connect(config).await
+            }
+        }
+    }
 }
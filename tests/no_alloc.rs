@@ -0,0 +1,333 @@
+//! Tests of the timer and event bus APIs meant to be usable without `alloc`
+//!
+//! Run with `cargo test --no-default-features --test no_alloc`, so that the crate is built without `alloc`,
+//! and with `--features asyncify` for the async timers.
+
+#![no_std]
+
+use core::cell::UnsafeCell;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::time::Duration;
+
+use embedded_svc::event_bus::Postbox;
+use embedded_svc::timer::{ErrorType, OnceTimer, PeriodicTimer, Timer, TimerService};
+use embedded_svc::utils::event_bus::static_bus::{StaticBusError, StaticEventBus};
+use embedded_svc::utils::mutex::RawMutex;
+
+const MS: Duration = Duration::from_millis(1);
+
+/// A callback stored in a buffer of a few words, with its type erased
+struct InlineCallback {
+    buf: [MaybeUninit<usize>; 4],
+    call: unsafe fn(*mut u8),
+    drop: unsafe fn(*mut u8),
+}
+
+impl InlineCallback {
+    fn new<F>(callback: F) -> Option<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        unsafe fn call<F: FnMut()>(ptr: *mut u8) {
+            (*(ptr as *mut F))()
+        }
+
+        unsafe fn drop<F>(ptr: *mut u8) {
+            core::ptr::drop_in_place(ptr as *mut F)
+        }
+
+        if size_of::<F>() > size_of::<[usize; 4]>() || align_of::<F>() > align_of::<usize>() {
+            return None;
+        }
+
+        let mut buf = [MaybeUninit::uninit(); 4];
+
+        // Safety: the buffer is large enough and aligned for `F`
+        unsafe { (buf.as_mut_ptr() as *mut F).write(callback) };
+
+        Some(Self {
+            buf,
+            call: call::<F>,
+            drop: drop::<F>,
+        })
+    }
+
+    fn call(&mut self) {
+        // Safety: the buffer holds an `F`, which `call` was instantiated with
+        unsafe { (self.call)(self.buf.as_mut_ptr() as *mut u8) }
+    }
+}
+
+impl Drop for InlineCallback {
+    fn drop(&mut self) {
+        // Safety: the buffer holds an `F`, which `drop` was instantiated with
+        unsafe { (self.drop)(self.buf.as_mut_ptr() as *mut u8) }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum InlineError {
+    /// The callback does not fit in the buffer
+    TooLarge,
+    /// The service has a single timer, which exists already
+    Busy,
+}
+
+/// A timer service with a single timer, whose callback is kept inline
+///
+/// Time only passes with `expire`, which expires the timer if it is scheduled.
+struct InlineTimerService {
+    locked: AtomicBool,
+    callback: UnsafeCell<Option<InlineCallback>>,
+    scheduled: AtomicBool,
+    periodic: AtomicBool,
+}
+
+// Safety: the callback is only accessed with `locked` taken, and it is `Send`
+unsafe impl Sync for InlineTimerService {}
+
+impl InlineTimerService {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            callback: UnsafeCell::new(None),
+            scheduled: AtomicBool::new(false),
+            periodic: AtomicBool::new(false),
+        }
+    }
+
+    fn with_callback<R>(&self, f: impl FnOnce(&mut Option<InlineCallback>) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        // Safety: `locked` is taken
+        let result = f(unsafe { &mut *self.callback.get() });
+
+        self.locked.store(false, Ordering::Release);
+
+        result
+    }
+
+    fn expire(&self) {
+        if self.scheduled.load(Ordering::SeqCst) {
+            if !self.periodic.load(Ordering::SeqCst) {
+                self.scheduled.store(false, Ordering::SeqCst);
+            }
+
+            self.with_callback(|callback| callback.as_mut().unwrap().call());
+        }
+    }
+}
+
+impl ErrorType for InlineTimerService {
+    type Error = InlineError;
+}
+
+impl TimerService for InlineTimerService {
+    type Timer<'a> = InlineTimer<'a> where Self: 'a;
+
+    fn timer<F>(&self, callback: F) -> Result<Self::Timer<'_>, Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let callback = InlineCallback::new(callback).ok_or(InlineError::TooLarge)?;
+
+        self.with_callback(|slot| {
+            if slot.is_some() {
+                Err(InlineError::Busy)
+            } else {
+                *slot = Some(callback);
+
+                Ok(InlineTimer(self))
+            }
+        })
+    }
+}
+
+struct InlineTimer<'a>(&'a InlineTimerService);
+
+impl Drop for InlineTimer<'_> {
+    fn drop(&mut self) {
+        self.0.scheduled.store(false, Ordering::SeqCst);
+        self.0.with_callback(|callback| *callback = None);
+    }
+}
+
+impl ErrorType for InlineTimer<'_> {
+    type Error = InlineError;
+}
+
+impl Timer for InlineTimer<'_> {
+    fn is_scheduled(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.scheduled.load(Ordering::SeqCst))
+    }
+
+    fn cancel(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.scheduled.swap(false, Ordering::SeqCst))
+    }
+}
+
+impl OnceTimer for InlineTimer<'_> {
+    fn after(&mut self, _duration: Duration) -> Result<(), Self::Error> {
+        self.0.periodic.store(false, Ordering::SeqCst);
+        self.0.scheduled.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+impl PeriodicTimer for InlineTimer<'_> {
+    fn every(&mut self, _duration: Duration) -> Result<(), Self::Error> {
+        self.0.periodic.store(true, Ordering::SeqCst);
+        self.0.scheduled.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn timer_with() {
+    static FIRES: AtomicU32 = AtomicU32::new(0);
+
+    fn fire(state: &mut (u32, &'static AtomicU32)) {
+        state.0 += 1;
+        state.1.store(state.0, Ordering::SeqCst);
+    }
+
+    let service = InlineTimerService::new();
+
+    let mut timer = service.timer_with((0, &FIRES), fire).unwrap();
+
+    // The service has a single timer
+    assert_eq!(service.timer(|| ()).err(), Some(InlineError::Busy));
+
+    timer.every(MS * 10).unwrap();
+
+    service.expire();
+    service.expire();
+    assert_eq!(FIRES.load(Ordering::SeqCst), 2);
+
+    // The carried value persists across the expiries
+    timer.after(MS * 10).unwrap();
+
+    service.expire();
+    service.expire();
+    assert_eq!(FIRES.load(Ordering::SeqCst), 3);
+
+    drop(timer);
+
+    assert_eq!(
+        service.timer_with([0_u8; 64], |_| ()).err(),
+        Some(InlineError::TooLarge)
+    );
+}
+
+/// A raw mutex spinning until it is unlocked
+struct SpinRawMutex(AtomicBool);
+
+impl RawMutex for SpinRawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self(AtomicBool::new(false));
+
+    fn new() -> Self {
+        Self::INIT
+    }
+
+    unsafe fn lock(&self) {
+        while self
+            .0
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+    }
+
+    unsafe fn unlock(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[test]
+fn static_bus() {
+    static BUS: StaticEventBus<SpinRawMutex, u32, 2, 2> = StaticEventBus::new();
+
+    let first = BUS.subscribe_queue().unwrap();
+    let second = BUS.subscribe_queue().unwrap();
+
+    assert_eq!(
+        BUS.subscribe_queue().err(),
+        Some(StaticBusError::NoFreeSlot)
+    );
+
+    assert_eq!(BUS.post(&1, None), Ok(true));
+    assert_eq!(BUS.post(&2, None), Ok(true));
+    assert_eq!(BUS.post(&3, None), Err(StaticBusError::Overflow(2)));
+
+    assert_eq!(first.try_recv(), Some(1));
+
+    drop(second);
+    assert_eq!(BUS.subscribers(), 1);
+}
+
+#[cfg(feature = "asyncify")]
+mod asyncify {
+    use core::future::Future;
+    use core::pin::{pin, Pin};
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use embedded_svc::utils::asyncify::timer::{AsyncTimerService, TimerSignal};
+
+    use super::*;
+
+    const NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(core::ptr::null(), &NOOP_VTABLE),
+        |_| (),
+        |_| (),
+        |_| (),
+    );
+
+    fn poll<F>(future: Pin<&mut F>) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        // Safety: the vtable functions do nothing with the (null) data pointer
+        let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &NOOP_VTABLE)) };
+
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn timer_static() {
+        static SERVICE: InlineTimerService = InlineTimerService::new();
+        static SIGNAL: TimerSignal = TimerSignal::new();
+
+        let service = &SERVICE;
+        let async_service = AsyncTimerService::new(service);
+
+        let mut timer = async_service.timer_static(&SIGNAL).unwrap();
+
+        {
+            let mut after = pin!(timer.after(MS * 10));
+            assert!(poll(after.as_mut()).is_pending());
+
+            service.expire();
+            assert!(poll(after.as_mut()).is_ready());
+        }
+
+        timer.every(MS * 10).unwrap();
+
+        service.expire();
+        service.expire();
+
+        assert!(matches!(poll(pin!(timer.next_tick())), Poll::Ready(Ok(1))));
+        assert!(matches!(poll(pin!(timer.next_tick())), Poll::Ready(Ok(1))));
+        assert!(poll(pin!(timer.next_tick())).is_pending());
+    }
+}